@@ -0,0 +1,932 @@
+use super::{now_ms, Backend, PersistenceError};
+use crate::{BulkString, RespArray, RespEncode, RespFrame};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+// fsync 和检查自动重写的间隔
+const AOF_CRON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// appendfsync：写入 AOF 之后什么时候 fsync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AppendFsync {
+    // 每次写入之后都 fsync，返回给客户端之前数据已经落盘
+    Always,
+    // 后台每秒 fsync 一次，最多丢失一秒的数据
+    #[default]
+    EverySec,
+    // 交给操作系统决定
+    No,
+}
+
+/// AOF 的配置，文件保存在 SaveConfig::dir 下的 dirname 目录中
+#[derive(Debug, Clone, PartialEq)]
+pub struct AofConfig {
+    pub enabled: bool,
+    pub fsync: AppendFsync,
+    pub dirname: String,
+    pub filename: String,
+    // AOF 的大小比上次重写之后增长超过这个百分比时自动重写，0 表示关闭自动重写
+    pub auto_rewrite_percentage: u64,
+    // AOF 小于这个大小时不自动重写
+    pub auto_rewrite_min_size: u64,
+}
+
+/// Redis 7 的 multi part AOF：一个 base 文件 (RDB 格式的快照) 加上之后的若干个 incr 文件，
+/// 由 manifest 文件记录。重写之后旧的文件变成 history，随后被删除
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub base: Option<AofInfo>,
+    pub incrs: Vec<AofInfo>,
+    pub history: Vec<AofInfo>,
+}
+
+/// manifest 中的一行：`file appendonly.aof.1.incr.aof seq 1 type i`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AofInfo {
+    pub name: String,
+    pub seq: u64,
+    pub kind: AofKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AofKind {
+    Base,
+    Incr,
+    History,
+}
+
+/// AOF 的状态，对应 Redis server 结构中 aof_* 的字段
+#[derive(Debug, Default)]
+pub struct Aof {
+    config: Mutex<AofConfig>,
+    state: Mutex<AofState>,
+    // 当前命令传播的写命令，命令执行结束之后一起写入
+    pending: Mutex<Vec<Vec<Vec<u8>>>>,
+    // 打开了 incr 文件，写命令需要传播
+    open: AtomicBool,
+    rewrite_in_progress: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct AofState {
+    manifest: Manifest,
+    // 当前写入的 incr 文件，fsync 时 clone 出来在锁外执行
+    file: Option<Arc<fs::File>>,
+    // 写入失败、还没有写入文件的数据，之后继续写入，保证 AOF 中的命令不会缺失
+    buf: Vec<u8>,
+    // 有写入还没有 fsync
+    unsynced: bool,
+    // 最近一次写入或者 fsync 失败的原因，恢复之前拒绝执行写命令，对应 Redis 的 MISCONF
+    write_error: Option<String>,
+    fsync_error: Option<String>,
+    // AOF 所有文件的大小，以及上次重写之后的大小，用于判断是否自动重写
+    current_size: u64,
+    rewrite_base_size: u64,
+    // 最后一次写入之后命令流的 offset，以及已经 fsync 到的 offset，WAITAOF 据此判断写入是否落盘
+    written_offset: u64,
+    fsynced_offset: u64,
+}
+
+impl AofState {
+    /// 把缓冲的数据写入文件，失败时保留没有写入的部分，下次从这里继续写入
+    fn flush(&mut self) -> io::Result<()> {
+        let Some(file) = self.file.clone() else {
+            return Ok(());
+        };
+        while !self.buf.is_empty() {
+            match (&*file).write(&self.buf) {
+                Ok(0) => {
+                    let e = io::Error::from(io::ErrorKind::WriteZero);
+                    self.write_error = Some(e.to_string());
+                    return Err(e);
+                }
+                Ok(n) => {
+                    self.buf.drain(..n);
+                    self.current_size += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.write_error = Some(e.to_string());
+                    return Err(e);
+                }
+            }
+        }
+        self.write_error = None;
+        Ok(())
+    }
+}
+
+impl Default for AofConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fsync: AppendFsync::default(),
+            dirname: "appendonlydir".to_string(),
+            filename: "appendonly.aof".to_string(),
+            auto_rewrite_percentage: 100,
+            auto_rewrite_min_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl FromStr for AppendFsync {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(AppendFsync::Always),
+            "everysec" => Ok(AppendFsync::EverySec),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(format!("invalid appendfsync policy: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for AppendFsync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        })
+    }
+}
+
+impl AofKind {
+    fn as_char(self) -> char {
+        match self {
+            AofKind::Base => 'b',
+            AofKind::Incr => 'i',
+            AofKind::History => 'h',
+        }
+    }
+}
+
+impl Manifest {
+    /// 解析 manifest 文件，每行是若干个 key value，忽略空行和 `#` 开头的注释
+    pub fn parse(s: &str) -> Result<Self, PersistenceError> {
+        let invalid =
+            |line: &str| PersistenceError::Aof(format!("Invalid AOF manifest line: {}", line));
+        let mut manifest = Manifest::default();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if !parts.len().is_multiple_of(2) {
+                return Err(invalid(line));
+            }
+            let (mut name, mut seq, mut kind) = (None, None, None);
+            for pair in parts.chunks(2) {
+                match pair[0] {
+                    "file" => name = Some(pair[1].to_string()),
+                    "seq" => seq = pair[1].parse().ok(),
+                    "type" => {
+                        kind = match pair[1] {
+                            "b" => Some(AofKind::Base),
+                            "i" => Some(AofKind::Incr),
+                            "h" => Some(AofKind::History),
+                            _ => return Err(invalid(line)),
+                        }
+                    }
+                    // 和 Redis 一样忽略不认识的字段，兼容之后的版本
+                    _ => {}
+                }
+            }
+            let (Some(name), Some(seq), Some(kind)) = (name, seq, kind) else {
+                return Err(invalid(line));
+            };
+            // 文件名只能是目录中的文件
+            if name.contains('/') || name.contains('\\') {
+                return Err(invalid(line));
+            }
+            let info = AofInfo { name, seq, kind };
+            match kind {
+                AofKind::Base if manifest.base.is_some() => {
+                    return Err(PersistenceError::Aof(
+                        "Found duplicate base file information".to_string(),
+                    ))
+                }
+                AofKind::Base => manifest.base = Some(info),
+                AofKind::Incr => {
+                    if manifest.incrs.last().is_some_and(|last| last.seq >= seq) {
+                        return Err(PersistenceError::Aof(
+                            "Found a non-monotonic sequence number".to_string(),
+                        ));
+                    }
+                    manifest.incrs.push(info);
+                }
+                AofKind::History => manifest.history.push(info),
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// 新的 incr 文件：`<filename>.<seq>.incr.aof`，seq 在上一个 incr 文件的基础上加一
+    fn next_incr(&mut self, filename: &str) -> AofInfo {
+        let seq = self.incrs.last().map_or(1, |last| last.seq + 1);
+        let info = AofInfo {
+            name: format!("{}.{}.incr.aof", filename, seq),
+            seq,
+            kind: AofKind::Incr,
+        };
+        self.incrs.push(info.clone());
+        info
+    }
+
+    /// 新的 base 文件：`<filename>.<seq>.base.rdb`
+    fn next_base(&self, filename: &str) -> AofInfo {
+        let seq = self.base.as_ref().map_or(1, |base| base.seq + 1);
+        AofInfo {
+            name: format!("{}.{}.base.rdb", filename, seq),
+            seq,
+            kind: AofKind::Base,
+        }
+    }
+
+    /// 重写完成：新的 base 替换旧的 base，new_incr 之前的 incr 文件都变成 history
+    fn rewritten(&mut self, base: AofInfo, new_incr: Option<u64>) {
+        let old_base = self.base.replace(base);
+        let keep_from = match new_incr {
+            Some(seq) => self
+                .incrs
+                .iter()
+                .position(|info| info.seq >= seq)
+                .unwrap_or(self.incrs.len()),
+            None => self.incrs.len(),
+        };
+        let old_incrs: Vec<AofInfo> = self.incrs.drain(..keep_from).collect();
+        self.history
+            .extend(old_base.into_iter().chain(old_incrs).map(|info| AofInfo {
+                kind: AofKind::History,
+                ..info
+            }));
+    }
+}
+
+/// base、history、incr 的顺序和 Redis 一样
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for info in self.base.iter().chain(&self.history).chain(&self.incrs) {
+            writeln!(
+                f,
+                "file {} seq {} type {}",
+                info.name,
+                info.seq,
+                info.kind.as_char()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Aof {
+    fn config(&self) -> MutexGuard<'_, AofConfig> {
+        self.config.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn state(&self) -> MutexGuard<'_, AofState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Vec<Vec<Vec<u8>>>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Backend {
+    pub fn configure_aof(&self, config: AofConfig) {
+        *self.aof.config() = config;
+    }
+
+    pub fn aof_config(&self) -> AofConfig {
+        self.aof.config().clone()
+    }
+
+    /// AOF 文件所在的目录
+    pub fn aof_dir(&self) -> PathBuf {
+        self.save_config().dir.join(&self.aof.config().dirname)
+    }
+
+    fn aof_manifest_path(&self) -> PathBuf {
+        let filename = self.aof.config().filename.clone();
+        self.aof_dir().join(format!("{}.manifest", filename))
+    }
+
+    pub fn aof_rewrite_in_progress(&self) -> bool {
+        self.aof.rewrite_in_progress.load(Ordering::Relaxed)
+    }
+
+    /// 写命令执行之后是否需要传播，没有打开 AOF 也没有 replication backlog 时不需要改写命令
+    pub fn propagation_enabled(&self) -> bool {
+        self.aof.open.load(Ordering::Relaxed) || self.has_backlog()
+    }
+
+    /// 记录当前命令产生的一条写命令，由 `propagate_pending` 统一写入
+    pub fn propagate(&self, argv: Vec<Vec<u8>>) {
+        if self.propagation_enabled() {
+            self.aof.pending().push(argv);
+        }
+    }
+
+    /// 把当前命令产生的写命令追加到 AOF，调用方需要持有执行锁。
+    /// 有多条命令时 (EXEC、脚本) 用 MULTI/EXEC 包起来，加载时也是原子的
+    ///
+    /// 写入 AOF 失败时命令仍然传播给 replica，返回的错误需要回复给客户端
+    pub fn propagate_pending(&self) -> Result<(), PersistenceError> {
+        let commands = mem::take(&mut *self.aof.pending());
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let wrap = commands.len() > 1;
+        let mut buf = Vec::new();
+        let mut encode = |argv: Vec<Vec<u8>>| {
+            let frames: Vec<RespFrame> = argv
+                .into_iter()
+                .map(|a| BulkString::new(a).into())
+                .collect();
+            buf.extend_from_slice(&RespArray::new(frames).encode());
+        };
+        if wrap {
+            encode(vec![b"MULTI".to_vec()]);
+        }
+        commands.into_iter().for_each(&mut encode);
+        if wrap {
+            encode(vec![b"EXEC".to_vec()]);
+        }
+        let ret = self.feed_aof(&buf);
+        self.feed_replication(&buf);
+        self.mark_aof_written();
+        ret
+    }
+
+    /// 把编码之后的命令追加到 AOF，replica 收到 master 的命令流时也直接调用
+    pub(crate) fn feed_aof(&self, buf: &[u8]) -> Result<(), PersistenceError> {
+        self.append_aof(buf).map_err(|e| {
+            warn!("Error writing to the AOF file: {}", e);
+            PersistenceError::AofWrite(e.to_string())
+        })
+    }
+
+    fn append_aof(&self, buf: &[u8]) -> io::Result<()> {
+        let fsync = self.aof.config().fsync;
+        let mut state = self.aof.state();
+        let Some(file) = state.file.clone() else {
+            return Ok(());
+        };
+        state.buf.extend_from_slice(buf);
+        state.flush()?;
+        match fsync {
+            AppendFsync::Always => match file.sync_data() {
+                Ok(()) => state.fsync_error = None,
+                Err(e) => {
+                    state.fsync_error = Some(e.to_string());
+                    return Err(e);
+                }
+            },
+            AppendFsync::EverySec => state.unsynced = true,
+            AppendFsync::No => {}
+        }
+        Ok(())
+    }
+
+    /// 写入 AOF 或者 fsync 失败的原因，恢复之前写命令返回 MISCONF
+    pub fn aof_write_error(&self) -> Option<String> {
+        let state = self.aof.state();
+        state
+            .write_error
+            .clone()
+            .or_else(|| state.fsync_error.clone())
+    }
+
+    /// 重新写入之前写入失败的数据，由 AOF 的 cron 调用
+    fn retry_aof_write(&self) {
+        let _guard = self.lock();
+        let mut state = self.aof.state();
+        if state.buf.is_empty() {
+            return;
+        }
+        match state.flush() {
+            Ok(()) => {
+                info!("AOF write error looks solved, can write again");
+                state.unsynced = true;
+                drop(state);
+                self.mark_aof_written();
+            }
+            Err(e) => warn!("Error writing to the AOF file: {}", e),
+        }
+    }
+
+    /// 读取 manifest 文件，AOF 不存在时返回 None
+    pub fn load_aof_manifest(&self) -> Result<Option<Manifest>, PersistenceError> {
+        match fs::read_to_string(self.aof_manifest_path()) {
+            Ok(s) => Manifest::parse(&s).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 启动时加载数据之后打开 AOF：有 manifest 时继续追加最后一个 incr 文件，
+    /// 否则用当前的数据集生成 base 文件，再创建新的 incr 文件
+    pub fn open_aof(&self) -> Result<(), PersistenceError> {
+        let dir = self.aof_dir();
+        fs::create_dir_all(&dir)?;
+        let filename = self.aof.config().filename.clone();
+        let mut manifest = match self.load_aof_manifest()? {
+            Some(manifest) => manifest,
+            None => {
+                info!("Creating AOF base file {}", filename);
+                let mut manifest = Manifest::default();
+                let base = manifest.next_base(&filename);
+                write_file_synced(&dir, &base.name, &self.rdb_snapshot())?;
+                manifest.base = Some(base);
+                manifest
+            }
+        };
+        let created = manifest.incrs.is_empty();
+        let incr = match manifest.incrs.last() {
+            Some(incr) => incr.clone(),
+            None => manifest.next_incr(&filename),
+        };
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(&incr.name))?;
+        if created {
+            write_manifest(&dir, &self.aof_manifest_path(), &manifest)?;
+        }
+        let size = aof_files(&manifest)
+            .map(|info| fs::metadata(dir.join(&info.name)).map_or(0, |m| m.len()))
+            .sum();
+        self.ensure_backlog();
+        let offset = self.replication_offset();
+        let mut state = self.aof.state();
+        *state = AofState {
+            manifest,
+            file: Some(Arc::new(file)),
+            buf: Vec::new(),
+            unsynced: false,
+            write_error: None,
+            fsync_error: None,
+            current_size: size,
+            rewrite_base_size: size,
+            written_offset: offset,
+            fsynced_offset: offset,
+        };
+        self.aof.open.store(true, Ordering::Relaxed);
+        info!("Append only file {} opened", incr.name);
+        Ok(())
+    }
+
+    /// BGREWRITEAOF：在执行锁内生成快照并切换到新的 incr 文件，后台线程写入新的 base 文件，
+    /// 完成之后更新 manifest，删除旧的文件。调用方需要持有执行锁
+    ///
+    /// 重写期间的写命令写入新的 incr 文件，manifest 在重写完成之前仍然包含旧的文件，
+    /// 任何时候崩溃都可以从 manifest 恢复完整的数据
+    pub fn rewrite_aof(&self) -> Result<(), PersistenceError> {
+        let aof = &self.aof;
+        if aof.rewrite_in_progress.swap(true, Ordering::AcqRel) {
+            return Err(PersistenceError::AofRewriteInProgress);
+        }
+        let ret = self.start_rewrite();
+        if ret.is_err() {
+            aof.rewrite_in_progress.store(false, Ordering::Release);
+        }
+        ret
+    }
+
+    fn start_rewrite(&self) -> Result<(), PersistenceError> {
+        let dir = self.aof_dir();
+        fs::create_dir_all(&dir)?;
+        let filename = self.aof.config().filename.clone();
+        let snapshot = self.rdb_snapshot();
+        let manifest_path = self.aof_manifest_path();
+        let (base, new_incr) = {
+            let mut state = self.aof.state();
+            // 没有写入的数据属于旧的 incr 文件，写入成功之前不能切换文件
+            if let Some(e) = &state.write_error {
+                return Err(PersistenceError::AofWrite(e.clone()));
+            }
+            if state.file.is_none() {
+                state.manifest = self.load_aof_manifest()?.unwrap_or_default();
+            }
+            let base = state.manifest.next_base(&filename);
+            let new_incr = match state.file.clone() {
+                Some(old) => {
+                    let mut manifest = state.manifest.clone();
+                    let incr = manifest.next_incr(&filename);
+                    let file = fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(dir.join(&incr.name))?;
+                    write_manifest(&dir, &manifest_path, &manifest)?;
+                    old.sync_data()?;
+                    state.manifest = manifest;
+                    state.file = Some(Arc::new(file));
+                    state.unsynced = false;
+                    state.fsynced_offset = state.written_offset;
+                    Some(incr.seq)
+                }
+                None => None,
+            };
+            (base, new_incr)
+        };
+        info!("Background append only file rewriting started");
+        let backend = self.clone();
+        thread::spawn(move || {
+            let ret = backend.finish_rewrite(&dir, &manifest_path, base, new_incr, &snapshot);
+            match ret {
+                Ok(()) => info!("Background AOF rewrite finished successfully"),
+                Err(e) => warn!("Background AOF rewrite error: {}", e),
+            }
+            backend
+                .aof
+                .rewrite_in_progress
+                .store(false, Ordering::Release);
+        });
+        Ok(())
+    }
+
+    fn finish_rewrite(
+        &self,
+        dir: &Path,
+        manifest_path: &Path,
+        base: AofInfo,
+        new_incr: Option<u64>,
+        snapshot: &[u8],
+    ) -> Result<(), PersistenceError> {
+        write_file_synced(dir, &base.name, snapshot)?;
+        let mut state = self.aof.state();
+        let mut manifest = state.manifest.clone();
+        manifest.rewritten(base, new_incr);
+        // 新的 manifest 不再引用旧的文件之后才能删除它们
+        let history = mem::take(&mut manifest.history);
+        write_manifest(dir, manifest_path, &manifest)?;
+        for info in history {
+            if let Err(e) = fs::remove_file(dir.join(&info.name)) {
+                warn!("failed to remove AOF history file {}: {}", info.name, e);
+            }
+        }
+        let size = aof_files(&manifest)
+            .map(|info| fs::metadata(dir.join(&info.name)).map_or(0, |m| m.len()))
+            .sum();
+        state.manifest = manifest;
+        state.current_size = size;
+        state.rewrite_base_size = size;
+        Ok(())
+    }
+
+    /// 是否需要自动重写，对应 Redis serverCron 中 auto-aof-rewrite-percentage 的检查
+    fn should_rewrite_aof(&self) -> bool {
+        let config = self.aof_config();
+        if !self.propagation_enabled()
+            || self.aof_rewrite_in_progress()
+            || config.auto_rewrite_percentage == 0
+        {
+            return false;
+        }
+        let state = self.aof.state();
+        let base = state.rewrite_base_size.max(1);
+        state.current_size > config.auto_rewrite_min_size
+            && (state.current_size - base.min(state.current_size)) * 100 / base
+                >= config.auto_rewrite_percentage
+    }
+
+    /// everysec 时 fsync 还没有落盘的写入，fsync 在锁外执行，不阻塞写命令。
+    /// fsync 是阻塞的 IO，由 cron 在 blocking 线程中调用
+    fn fsync_aof(&self) {
+        let (file, offset) = {
+            let mut state = self.aof.state();
+            if !state.unsynced {
+                return;
+            }
+            state.unsynced = false;
+            (state.file.clone(), state.written_offset)
+        };
+        let Some(file) = file else {
+            return;
+        };
+        let ret = file.sync_data();
+        let mut state = self.aof.state();
+        match ret {
+            Ok(()) => {
+                state.fsync_error = None;
+                state.fsynced_offset = state.fsynced_offset.max(offset);
+                drop(state);
+                self.notify_acks();
+            }
+            Err(e) => {
+                warn!("failed to fsync the AOF file: {}", e);
+                // 下一秒重试
+                state.fsync_error = Some(e.to_string());
+                state.unsynced = true;
+            }
+        }
+    }
+
+    /// 写入 AOF 之后记录命令流的 offset。只有真正 fsync 之后 fsynced_offset 才前进：
+    /// appendfsync always 时写入之后已经 fsync；everysec 时等到下一次 fsync；
+    /// no 时交给操作系统，不知道什么时候落盘，只有重写等操作 fsync 之后才前进
+    pub(crate) fn mark_aof_written(&self) {
+        let offset = self.replication_offset();
+        let fsync = self.aof.config().fsync;
+        let mut state = self.aof.state();
+        // 还有没有写入文件的数据时，offset 停留在上一次成功写入的位置
+        if state.file.is_none() || !state.buf.is_empty() {
+            return;
+        }
+        state.written_offset = offset;
+        // 重新写入之前失败的数据时还没有 fsync (unsynced)，由 cron 完成
+        if fsync == AppendFsync::Always && !state.unsynced && state.fsync_error.is_none() {
+            state.fsynced_offset = offset;
+            drop(state);
+            self.notify_acks();
+        }
+    }
+
+    /// 本地 AOF 已经 fsync 到的 offset，没有打开 AOF 时为 0
+    pub(crate) fn aof_fsynced_offset(&self) -> u64 {
+        let state = self.aof.state();
+        match state.file {
+            Some(_) => state.fsynced_offset,
+            None => 0,
+        }
+    }
+}
+
+/// base 文件和 incr 文件，按照加载的顺序
+pub fn aof_files(manifest: &Manifest) -> impl Iterator<Item = &AofInfo> {
+    manifest.base.iter().chain(&manifest.incrs)
+}
+
+/// 先写入临时文件并 fsync，再重命名为目标文件
+fn write_file_synced(dir: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!("temp-{}-{}-{}", name, std::process::id(), now_ms()));
+    let ret = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, dir.join(name))
+    })();
+    if ret.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    ret
+}
+
+fn write_manifest(dir: &Path, path: &Path, manifest: &Manifest) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    write_file_synced(dir, &name, manifest.to_string().as_bytes())
+}
+
+/// 每秒 fsync 一次 (appendfsync everysec)，并检查是否需要自动重写
+pub fn spawn_aof_cron(backend: Backend) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(AOF_CRON_INTERVAL);
+        loop {
+            interval.tick().await;
+            let b = backend.clone();
+            let ret = tokio::task::spawn_blocking(move || {
+                b.retry_aof_write();
+                b.fsync_aof();
+            })
+            .await;
+            if let Err(e) = ret {
+                warn!("AOF fsync task failed: {}", e);
+            }
+            if backend.should_rewrite_aof() {
+                let _guard = backend.lock_async().await;
+                info!("Starting automatic rewriting of AOF");
+                if let Err(e) = backend.rewrite_aof() {
+                    warn!("failed to start AOF rewrite: {}", e);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cmd::{Command, Outcome, Session},
+        Value,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("simple-redis-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn wait_rewrite(backend: &Backend) {
+        while backend.aof_rewrite_in_progress() {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_manifest() {
+        let s = "file appendonly.aof.2.base.rdb seq 2 type b\n\
+                 # comment\n\
+                 file appendonly.aof.1.incr.aof seq 1 type h\n\
+                 file appendonly.aof.3.incr.aof seq 3 type i\n\
+                 file appendonly.aof.4.incr.aof type i seq 4 unknown x\n";
+        let manifest = Manifest::parse(s).unwrap();
+        assert_eq!(manifest.base.as_ref().unwrap().seq, 2);
+        assert_eq!(manifest.history.len(), 1);
+        assert_eq!(
+            manifest.incrs.iter().map(|i| i.seq).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        let s = manifest.to_string();
+        assert_eq!(Manifest::parse(&s).unwrap(), manifest);
+        assert!(s.starts_with("file appendonly.aof.2.base.rdb seq 2 type b\n"));
+
+        assert!(Manifest::parse("file a seq 1").is_err());
+        assert!(Manifest::parse("file a seq 1 type x").is_err());
+        assert!(Manifest::parse("file ../a seq 1 type i").is_err());
+        assert!(Manifest::parse("file a seq 2 type i\nfile b seq 1 type i").is_err());
+    }
+
+    #[test]
+    fn test_fsynced_offset() {
+        let dir = temp_dir("aof-fsync");
+        let backend = Backend::new();
+        backend.configure_save(crate::SaveConfig {
+            dir: dir.clone(),
+            ..Default::default()
+        });
+        backend.configure_aof(AofConfig {
+            enabled: true,
+            fsync: AppendFsync::No,
+            ..Default::default()
+        });
+        backend.open_aof().unwrap();
+        let opened = backend.aof_fsynced_offset();
+
+        // appendfsync no 时没有 fsync，fsynced_offset 不前进
+        backend.propagate(vec![b"SET".to_vec(), b"a".to_vec(), b"1".to_vec()]);
+        backend.propagate_pending().unwrap();
+        backend.fsync_aof();
+        assert!(backend.replication_offset() > opened);
+        assert_eq!(backend.aof_fsynced_offset(), opened);
+
+        // everysec 时等到 fsync 之后才前进
+        backend.configure_aof(AofConfig {
+            enabled: true,
+            fsync: AppendFsync::EverySec,
+            ..Default::default()
+        });
+        backend.propagate(vec![b"SET".to_vec(), b"b".to_vec(), b"2".to_vec()]);
+        backend.propagate_pending().unwrap();
+        assert_eq!(backend.aof_fsynced_offset(), opened);
+        backend.fsync_aof();
+        assert_eq!(backend.aof_fsynced_offset(), backend.replication_offset());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_error() {
+        let dir = temp_dir("aof-error");
+        let backend = Backend::new();
+        backend.configure_save(crate::SaveConfig {
+            dir: dir.clone(),
+            ..Default::default()
+        });
+        backend.configure_aof(AofConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        backend.open_aof().unwrap();
+        let path = dir.join("appendonlydir").join("appendonly.aof.1.incr.aof");
+        // 只读打开的文件写入会失败
+        let writable = backend
+            .aof
+            .state()
+            .file
+            .replace(Arc::new(fs::File::open(&path).unwrap()));
+
+        backend.propagate(vec![b"SET".to_vec(), b"a".to_vec(), b"1".to_vec()]);
+        assert!(matches!(
+            backend.propagate_pending(),
+            Err(PersistenceError::AofWrite(_))
+        ));
+        assert!(backend.aof_write_error().is_some());
+        assert!(fs::read(&path).unwrap().is_empty());
+        // 写入恢复之前拒绝写命令
+        let frame = RespArray::new([
+            BulkString::new("SET").into(),
+            BulkString::new("b").into(),
+            BulkString::new("2").into(),
+        ])
+        .into();
+        let mut session = Session::new(1);
+        let cmd = Command::from_session(frame, &mut session).unwrap();
+        let Outcome::Reply(RespFrame::Error(e)) = cmd.execute_or_block(&mut session, &backend)
+        else {
+            panic!("write command should be refused");
+        };
+        assert!(e.0.starts_with("MISCONF"));
+        assert!(!backend.exists(b"b"));
+
+        backend.aof.state().file = writable;
+        backend.retry_aof_write();
+        assert_eq!(backend.aof_write_error(), None);
+        assert_eq!(
+            fs::read(&path).unwrap(),
+            b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_append_and_rewrite() {
+        let dir = temp_dir("aof");
+        let backend = Backend::new();
+        backend.configure_save(crate::SaveConfig {
+            dir: dir.clone(),
+            ..Default::default()
+        });
+        backend.configure_aof(AofConfig {
+            enabled: true,
+            fsync: AppendFsync::Always,
+            ..Default::default()
+        });
+        backend.set(b"a".to_vec(), Value::String(b"1".to_vec()));
+        backend.open_aof().unwrap();
+        let aof_dir = dir.join("appendonlydir");
+        assert_eq!(
+            fs::read_to_string(aof_dir.join("appendonly.aof.manifest")).unwrap(),
+            "file appendonly.aof.1.base.rdb seq 1 type b\n\
+             file appendonly.aof.1.incr.aof seq 1 type i\n"
+        );
+        assert!(fs::read(aof_dir.join("appendonly.aof.1.base.rdb"))
+            .unwrap()
+            .starts_with(b"REDIS"));
+
+        backend.propagate(vec![b"SET".to_vec(), b"b".to_vec(), b"2".to_vec()]);
+        backend.propagate_pending().unwrap();
+        backend.propagate(vec![b"INCR".to_vec(), b"c".to_vec()]);
+        backend.propagate(vec![b"INCR".to_vec(), b"c".to_vec()]);
+        backend.propagate_pending().unwrap();
+        assert_eq!(
+            fs::read(aof_dir.join("appendonly.aof.1.incr.aof")).unwrap(),
+            b"*3\r\n$3\r\nSET\r\n$1\r\nb\r\n$1\r\n2\r\n\
+              *1\r\n$5\r\nMULTI\r\n*2\r\n$4\r\nINCR\r\n$1\r\nc\r\n\
+              *2\r\n$4\r\nINCR\r\n$1\r\nc\r\n*1\r\n$4\r\nEXEC\r\n"
+        );
+
+        backend
+            .aof
+            .rewrite_in_progress
+            .store(true, Ordering::Relaxed);
+        assert!(matches!(
+            backend.rewrite_aof(),
+            Err(PersistenceError::AofRewriteInProgress)
+        ));
+        backend
+            .aof
+            .rewrite_in_progress
+            .store(false, Ordering::Relaxed);
+        backend.rewrite_aof().unwrap();
+        wait_rewrite(&backend);
+        backend.propagate(vec![b"SET".to_vec(), b"d".to_vec(), b"4".to_vec()]);
+        backend.propagate_pending().unwrap();
+        let manifest = backend.load_aof_manifest().unwrap().unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            "file appendonly.aof.2.base.rdb seq 2 type b\n\
+             file appendonly.aof.2.incr.aof seq 2 type i\n"
+        );
+        assert!(!aof_dir.join("appendonly.aof.1.base.rdb").exists());
+        assert!(!aof_dir.join("appendonly.aof.1.incr.aof").exists());
+        assert_eq!(
+            fs::read(aof_dir.join("appendonly.aof.2.incr.aof")).unwrap(),
+            b"*3\r\n$3\r\nSET\r\n$1\r\nd\r\n$1\r\n4\r\n"
+        );
+
+        // 重新打开时继续追加最后一个 incr 文件
+        let reopened = Backend::new();
+        reopened.configure_save(backend.save_config());
+        reopened.configure_aof(backend.aof_config());
+        reopened.open_aof().unwrap();
+        assert_eq!(reopened.load_aof_manifest().unwrap().unwrap(), manifest);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
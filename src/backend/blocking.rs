@@ -0,0 +1,291 @@
+use super::{Backend, BackendError};
+use crate::RespFrame;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// 尝试用 key 上的数据唤醒阻塞的客户端，成功时返回给客户端的响应，没有数据时返回 None。
+/// 返回 Err 时客户端被唤醒并收到错误 (例如 XREADGROUP 的消费组被删除)，
+/// 只有 key 的类型不匹配时和 Redis 一样当作没有数据，客户端继续阻塞
+pub type ServeFn =
+    Box<dyn FnMut(&Backend, &[u8]) -> Result<Option<RespFrame>, BackendError> + Send>;
+
+/// 阻塞在 key 上的客户端，对应 Redis 的 blocking_keys 字典
+///
+/// 每个 key 维护一个按阻塞先后顺序排列的队列。写入 key 之后只是把 key 标记为 ready，
+/// 等到当前命令执行完成后 (仍然持有执行锁) 再按照 FIFO 的顺序唤醒客户端，
+/// 保证被唤醒的客户端看到的是命令执行完成后的状态，也不会被其他连接插队
+#[derive(Default)]
+pub struct Blocking {
+    clients: Mutex<BlockedClients>,
+    // 有数据写入、需要检查的 key，单独一把锁，唤醒客户端的过程中可以继续标记
+    ready: Mutex<Vec<Vec<u8>>>,
+    // 阻塞的客户端数量，没有客户端阻塞时写入不需要加锁
+    blocked: AtomicUsize,
+}
+
+#[derive(Default)]
+struct BlockedClients {
+    next_id: u64,
+    clients: HashMap<u64, BlockedClient>,
+    keys: HashMap<Vec<u8>, VecDeque<u64>>,
+}
+
+struct BlockedClient {
+    keys: Vec<Vec<u8>>,
+    serve: ServeFn,
+    tx: oneshot::Sender<RespFrame>,
+}
+
+/// 阻塞中的客户端持有的句柄，drop 时 (例如连接断开) 自动从等待队列中移除
+pub struct Waiter {
+    backend: Backend,
+    id: u64,
+    rx: oneshot::Receiver<RespFrame>,
+}
+
+impl fmt::Debug for Blocking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Blocking")
+            .field("blocked", &self.blocked.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl fmt::Debug for Waiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Waiter").field("id", &self.id).finish()
+    }
+}
+
+impl Blocking {
+    /// 阻塞的客户端数量
+    pub fn len(&self) -> usize {
+        self.blocked.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn clients(&self) -> MutexGuard<'_, BlockedClients> {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn ready(&self) -> MutexGuard<'_, Vec<Vec<u8>>> {
+        self.ready.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn block(&self, keys: Vec<Vec<u8>>, serve: ServeFn) -> (u64, oneshot::Receiver<RespFrame>) {
+        let (tx, rx) = oneshot::channel();
+        let mut inner = self.clients();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        for key in &keys {
+            let queue = inner.keys.entry(key.clone()).or_default();
+            // 同一个 key 在参数中出现多次时只排一次队
+            if queue.back() != Some(&id) {
+                queue.push_back(id);
+            }
+        }
+        inner.clients.insert(id, BlockedClient { keys, serve, tx });
+        self.blocked.fetch_add(1, Ordering::Relaxed);
+        (id, rx)
+    }
+
+    /// 把客户端从所有的等待队列中移除，客户端已经被唤醒或者移除时返回 false
+    fn unblock(&self, id: u64) -> bool {
+        self.clients().remove(id).is_some_and(|_| {
+            self.blocked.fetch_sub(1, Ordering::Relaxed);
+            true
+        })
+    }
+
+    fn signal(&self, key: &[u8]) {
+        if self.is_empty() {
+            return;
+        }
+        let mut ready = self.ready();
+        if !ready.iter().any(|k| k == key) {
+            ready.push(key.to_vec());
+        }
+    }
+}
+
+impl BlockedClients {
+    fn remove(&mut self, id: u64) -> Option<BlockedClient> {
+        let client = self.clients.remove(&id)?;
+        for key in &client.keys {
+            if let Some(queue) = self.keys.get_mut(key) {
+                queue.retain(|v| *v != id);
+                if queue.is_empty() {
+                    self.keys.remove(key);
+                }
+            }
+        }
+        Some(client)
+    }
+}
+
+impl Backend {
+    /// 让客户端阻塞在 keys 上，调用方需要持有执行锁，保证检查数据和开始阻塞之间没有其他写入
+    pub fn block(&self, keys: Vec<Vec<u8>>, serve: ServeFn) -> Waiter {
+        let (id, rx) = self.blocking.block(keys, serve);
+        Waiter {
+            backend: self.clone(),
+            id,
+            rx,
+        }
+    }
+
+    /// 标记 key 上有新的数据，如果有客户端阻塞在这个 key 上，稍后会尝试唤醒它们
+    pub fn signal_key_ready(&self, key: &[u8]) {
+        self.blocking.signal(key);
+    }
+
+    /// 按照阻塞的先后顺序唤醒 ready 的 key 上的客户端，需要在持有执行锁的情况下调用
+    ///
+    /// 唤醒客户端时执行的操作本身也可能写入其他 key (例如 BLMOVE)，因此一直处理到没有 ready 的 key 为止
+    pub fn serve_blocked_clients(&self) {
+        loop {
+            let keys = std::mem::take(&mut *self.blocking.ready());
+            if keys.is_empty() {
+                return;
+            }
+            let mut inner = self.blocking.clients();
+            for key in keys {
+                let ids: Vec<u64> = match inner.keys.get(&key) {
+                    Some(queue) => queue.iter().copied().collect(),
+                    None => continue,
+                };
+                for id in ids {
+                    let Some(client) = inner.clients.get_mut(&id) else {
+                        continue;
+                    };
+                    // 连接已经断开，不能再消费数据
+                    let reply = if client.tx.is_closed() {
+                        None
+                    } else {
+                        match (client.serve)(self, &key) {
+                            Ok(Some(reply)) => Some(reply),
+                            Ok(None) | Err(BackendError::WrongType) => continue,
+                            Err(e) => Some(e.into()),
+                        }
+                    };
+                    if let Some(client) = inner.remove(id) {
+                        self.blocking.blocked.fetch_sub(1, Ordering::Relaxed);
+                        if let Some(reply) = reply {
+                            // 在 clients 锁内发送，超时的一方拿到锁之后一定能看到结果
+                            let _ = client.tx.send(reply);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Waiter {
+    /// 替换唤醒客户端时执行的操作，例如在原来的操作之后记录需要传播的写命令。已经被唤醒时什么也不做
+    pub fn map_serve(&self, f: impl FnOnce(ServeFn) -> ServeFn) {
+        let mut inner = self.backend.blocking.clients();
+        if let Some(client) = inner.clients.get_mut(&self.id) {
+            let serve = std::mem::replace(&mut client.serve, Box::new(|_, _| Ok(None)));
+            client.serve = f(serve);
+        }
+    }
+
+    /// 等待被唤醒，timeout 为 None 时一直等待，超时返回 None
+    pub async fn wait(mut self, timeout: Option<Duration>) -> Option<RespFrame> {
+        let ret = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, &mut self.rx).await.ok(),
+            None => Some((&mut self.rx).await),
+        };
+        if let Some(Ok(reply)) = ret {
+            return Some(reply);
+        }
+        // 超时和唤醒可能同时发生：移除失败说明已经被唤醒，结果一定已经发送
+        if self.backend.blocking.unblock(self.id) {
+            None
+        } else {
+            self.rx.try_recv().ok()
+        }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        self.backend.blocking.unblock(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, List};
+
+    fn pop_serve() -> ServeFn {
+        Box::new(|backend, key| {
+            backend
+                .write_value(key, false, |list: &mut List| {
+                    let value = list.pop_front();
+                    let popped = value.is_some();
+                    (value, popped)
+                })
+                .map(|v| v.flatten().map(|v| BulkString::new(v).into()))
+        })
+    }
+
+    fn push(backend: &Backend, key: &[u8], value: &str) {
+        backend
+            .write_value(key, true, |list: &mut List| {
+                list.push_back(value.as_bytes().to_vec());
+                ((), true)
+            })
+            .unwrap();
+        backend.serve_blocked_clients();
+    }
+
+    #[tokio::test]
+    async fn test_blocking_fifo() {
+        let backend = Backend::new();
+        let first = backend.block(vec![b"a".to_vec(), b"b".to_vec()], pop_serve());
+        let second = backend.block(vec![b"b".to_vec()], pop_serve());
+        assert_eq!(backend.blocking.len(), 2);
+
+        push(&backend, b"b", "1");
+        assert_eq!(backend.blocking.len(), 1);
+        assert_eq!(first.wait(None).await, Some(BulkString::new("1").into()));
+
+        push(&backend, b"a", "2");
+        assert_eq!(backend.blocking.len(), 1);
+        assert_eq!(backend.read_value(b"a", |l: &List| l.len()), Ok(Some(1)));
+
+        push(&backend, b"b", "3");
+        assert_eq!(second.wait(None).await, Some(BulkString::new("3").into()));
+        assert!(backend.blocking.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_timeout_and_drop() {
+        let backend = Backend::new();
+        let waiter = backend.block(vec![b"a".to_vec()], pop_serve());
+        let ret = waiter.wait(Some(Duration::from_millis(10))).await;
+        assert_eq!(ret, None);
+        assert!(backend.blocking.is_empty());
+
+        // 断开的连接不会消费数据
+        let waiter = backend.block(vec![b"a".to_vec()], pop_serve());
+        drop(waiter);
+        assert!(backend.blocking.is_empty());
+        push(&backend, b"a", "1");
+        assert_eq!(backend.read_value(b"a", |l: &List| l.len()), Ok(Some(1)));
+    }
+}
@@ -0,0 +1,845 @@
+use super::{now_ms, replication::random_id, Backend};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+use tracing::{info, warn};
+
+/// hash slot 的数量
+pub const CLUSTER_SLOTS: usize = 16384;
+/// 超过这个时间连接不上的节点标记为 fail?，握手一直没有完成的节点被移除，对应 cluster-node-timeout
+pub const CLUSTER_NODE_TIMEOUT: u64 = 15_000;
+
+/// 集群的配置：节点配置文件 (nodes.conf) 以及对外公布的地址
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    pub enabled: bool,
+    pub config_file: PathBuf,
+    pub announce_ip: String,
+    pub port: u16,
+}
+
+/// 集群的状态，对应 Redis 的 clusterState
+///
+/// 节点之间没有单独的 cluster bus，后台任务定期通过客户端端口获取其他节点的 CLUSTER NODES，
+/// 每个节点只对自己声明的 slot 负责，config epoch 更大的声明覆盖旧的归属
+#[derive(Debug, Default)]
+pub struct Cluster {
+    enabled: AtomicBool,
+    // 集群是否可用，只在节点和 slot 的归属变化以及定期检查节点故障时重新计算，对应 Redis 的 clusterUpdateState
+    ok: AtomicBool,
+    state: Mutex<ClusterState>,
+}
+
+#[derive(Debug)]
+struct ClusterState {
+    config_file: Option<PathBuf>,
+    myself: String,
+    current_epoch: u64,
+    nodes: BTreeMap<String, ClusterNode>,
+    // 每个 slot 所属节点的 id
+    slots: Vec<Option<String>>,
+    // 正在迁出到其他节点、正在从其他节点迁入的 slot
+    migrating: BTreeMap<u16, String>,
+    importing: BTreeMap<u16, String>,
+}
+
+/// 集群中的一个节点
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterNode {
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    pub config_epoch: u64,
+    // CLUSTER MEET 之后还没有拿到节点真正的 id
+    pub handshake: bool,
+    // 最近一次获取节点信息是否成功，以及成功的时间 (unix 毫秒)
+    pub connected: bool,
+    pub pong_recv: u64,
+}
+
+/// CLUSTER NODES 或者 nodes.conf 中的一行
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeEntry {
+    pub node: ClusterNode,
+    pub myself: bool,
+    pub slots: Vec<(u16, u16)>,
+    // [slot->-id] 和 [slot-<-id]
+    pub migrating: Vec<(u16, String)>,
+    pub importing: Vec<(u16, String)>,
+}
+
+/// key 所在的 slot 应该由谁处理
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlotRoute {
+    Myself,
+    // 自己负责，但是正在迁移到这个地址的节点
+    Migrating(String),
+    // 由其他节点负责，但是正在迁入到自己，带有 owner 的地址
+    Importing(Option<String>),
+    Moved(String),
+    Unassigned,
+}
+
+/// CLUSTER SETSLOT 的操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetSlot {
+    Importing(String),
+    Migrating(String),
+    Stable,
+    Node(String),
+}
+
+/// CRC16 (XMODEM)，和 Redis 的 crc16 一样
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &b| {
+        (0..8).fold(crc ^ ((b as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// key 所在的 slot。key 中有非空的 {hashtag} 时只计算第一个 hashtag，相同 hashtag 的 key 在同一个 slot
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&b| b == b'{').and_then(|start| {
+        let len = key[start + 1..].iter().position(|&b| b == b'}')?;
+        (len > 0).then(|| &key[start + 1..start + 1 + len])
+    });
+    crc16(tag.unwrap_or(key)) & (CLUSTER_SLOTS as u16 - 1)
+}
+
+impl Default for ClusterState {
+    fn default() -> Self {
+        Self {
+            config_file: None,
+            myself: String::new(),
+            current_epoch: 0,
+            nodes: BTreeMap::new(),
+            slots: vec![None; CLUSTER_SLOTS],
+            migrating: BTreeMap::new(),
+            importing: BTreeMap::new(),
+        }
+    }
+}
+
+impl ClusterNode {
+    fn new(id: String, ip: String, port: u16) -> Self {
+        Self {
+            id,
+            ip,
+            port,
+            config_epoch: 0,
+            handshake: false,
+            connected: false,
+            pong_recv: 0,
+        }
+    }
+
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+
+    /// 很久没有连接上的节点
+    pub fn failing(&self) -> bool {
+        !self.connected && now_ms().saturating_sub(self.pong_recv) > CLUSTER_NODE_TIMEOUT
+    }
+}
+
+impl NodeEntry {
+    /// 解析 CLUSTER NODES 中的一行：
+    /// `<id> <ip:port@cport> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state> <slot> ...`
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 8 {
+            return None;
+        }
+        let addr = fields[1].split(['@', ',']).next()?;
+        let (ip, port) = addr.rsplit_once(':')?;
+        let flags: Vec<&str> = fields[2].split(',').collect();
+        let mut entry = NodeEntry {
+            node: ClusterNode {
+                id: fields[0].to_string(),
+                ip: ip.to_string(),
+                port: port.parse().ok()?,
+                config_epoch: fields[6].parse().ok()?,
+                handshake: flags.contains(&"handshake"),
+                connected: fields[7] == "connected",
+                pong_recv: fields[5].parse().ok()?,
+            },
+            myself: flags.contains(&"myself"),
+            slots: vec![],
+            migrating: vec![],
+            importing: vec![],
+        };
+        for field in &fields[8..] {
+            if let Some(field) = field.strip_prefix('[').and_then(|f| f.strip_suffix(']')) {
+                if let Some((slot, id)) = field.split_once("->-") {
+                    entry.migrating.push((parse_slot(slot)?, id.to_string()));
+                } else if let Some((slot, id)) = field.split_once("-<-") {
+                    entry.importing.push((parse_slot(slot)?, id.to_string()));
+                }
+                continue;
+            }
+            let (start, end) = field.split_once('-').unwrap_or((field, field));
+            entry.slots.push((parse_slot(start)?, parse_slot(end)?));
+        }
+        Some(entry)
+    }
+}
+
+fn parse_slot(s: &str) -> Option<u16> {
+    s.parse()
+        .ok()
+        .filter(|slot| (*slot as usize) < CLUSTER_SLOTS)
+}
+
+impl ClusterState {
+    fn myself(&self) -> Option<&ClusterNode> {
+        self.nodes.get(&self.myself)
+    }
+
+    fn myself_mut(&mut self) -> Option<&mut ClusterNode> {
+        self.nodes.get_mut(&self.myself)
+    }
+
+    fn addr_of(&self, id: &str) -> Option<String> {
+        self.nodes.get(id).map(ClusterNode::addr)
+    }
+
+    fn epoch_of(&self, id: &str) -> u64 {
+        self.nodes.get(id).map_or(0, |node| node.config_epoch)
+    }
+
+    /// 把自己的 config epoch 设置为新的最大值，自己声明的 slot 会覆盖其他节点的旧声明
+    fn bump_epoch(&mut self) {
+        self.current_epoch += 1;
+        let epoch = self.current_epoch;
+        if let Some(myself) = self.myself_mut() {
+            myself.config_epoch = epoch;
+        }
+    }
+
+    /// 节点负责的 slot，合并成连续的区间
+    fn slot_ranges(&self, id: &str) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for (slot, owner) in self.slots.iter().enumerate() {
+            if owner.as_deref() != Some(id) {
+                continue;
+            }
+            let slot = slot as u16;
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == slot => *end = slot,
+                _ => ranges.push((slot, slot)),
+            }
+        }
+        ranges
+    }
+
+    fn node_line(&self, node: &ClusterNode) -> String {
+        let myself = node.id == self.myself;
+        let mut flags = match (myself, node.handshake) {
+            (true, _) => "myself,master".to_string(),
+            (false, true) => "handshake".to_string(),
+            (false, false) => "master".to_string(),
+        };
+        if !myself && !node.handshake && node.failing() {
+            flags.push_str(",fail?");
+        }
+        let connected = myself || node.connected;
+        let mut line = format!(
+            "{} {}:{}@{} {} - 0 {} {} {}",
+            node.id,
+            node.ip,
+            node.port,
+            node.port as u32 + 10000,
+            flags,
+            if myself { 0 } else { node.pong_recv },
+            node.config_epoch,
+            if connected {
+                "connected"
+            } else {
+                "disconnected"
+            }
+        );
+        for (start, end) in self.slot_ranges(&node.id) {
+            match start == end {
+                true => write!(line, " {}", start),
+                false => write!(line, " {}-{}", start, end),
+            }
+            .unwrap_or_default();
+        }
+        if myself {
+            for (slot, id) in &self.migrating {
+                write!(line, " [{}->-{}]", slot, id).unwrap_or_default();
+            }
+            for (slot, id) in &self.importing {
+                write!(line, " [{}-<-{}]", slot, id).unwrap_or_default();
+            }
+        }
+        line
+    }
+
+    /// 所有 slot 都有节点负责，并且负责的节点都可以连接上
+    fn is_ok(&self) -> bool {
+        self.slots.iter().all(|owner| {
+            owner
+                .as_ref()
+                .and_then(|id| self.nodes.get(id))
+                .is_some_and(|node| node.id == self.myself || !node.failing())
+        })
+    }
+
+    /// CLUSTER NODES 的内容，每行一个节点，自己在第一行
+    fn nodes_text(&self) -> String {
+        let myself = self.myself().into_iter();
+        let others = self.nodes.values().filter(|node| node.id != self.myself);
+        myself
+            .chain(others)
+            .map(|node| self.node_line(node) + "\n")
+            .collect()
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.config_file else {
+            return;
+        };
+        let content = format!(
+            "{}vars currentEpoch {} lastVoteEpoch 0\n",
+            self.nodes_text(),
+            self.current_epoch
+        );
+        if let Err(e) = fs::write(path, content) {
+            warn!("failed to save cluster config {}: {}", path.display(), e);
+        }
+    }
+
+    /// 加载 nodes.conf
+    fn load(&mut self, content: &str) -> Result<(), String> {
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            if let Some(vars) = line.strip_prefix("vars ") {
+                let vars: Vec<&str> = vars.split_whitespace().collect();
+                for pair in vars.chunks(2) {
+                    if let ["currentEpoch", epoch] = pair {
+                        self.current_epoch = epoch.parse().unwrap_or_default();
+                    }
+                }
+                continue;
+            }
+            let entry = NodeEntry::parse(line).ok_or_else(|| {
+                format!(
+                    "Unrecoverable error: corrupted cluster config file \"{}\"",
+                    line
+                )
+            })?;
+            let id = entry.node.id.clone();
+            if entry.myself {
+                self.myself = id.clone();
+                self.migrating.extend(entry.migrating);
+                self.importing.extend(entry.importing);
+            }
+            for (start, end) in entry.slots {
+                for slot in start..=end {
+                    self.slots[slot as usize] = Some(id.clone());
+                }
+            }
+            self.current_epoch = self.current_epoch.max(entry.node.config_epoch);
+            self.nodes.insert(id, entry.node);
+        }
+        if self.myself.is_empty() {
+            return Err(
+                "Unrecoverable error: myself node not found in cluster config file".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Cluster {
+    fn state(&self) -> MutexGuard<'_, ClusterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 重新计算集群是否可用，调用方持有修改之后的状态
+    fn update_state(&self, state: &ClusterState) {
+        let ok = state.is_ok();
+        if self.ok.swap(ok, Ordering::Relaxed) != ok {
+            info!("Cluster state changed: {}", if ok { "ok" } else { "fail" });
+        }
+    }
+}
+
+impl Backend {
+    /// 开启集群模式：加载 nodes.conf，不存在时创建新的节点
+    pub fn configure_cluster(&self, config: ClusterConfig) -> Result<(), String> {
+        if !config.enabled {
+            return Ok(());
+        }
+        let mut state = self.cluster.state();
+        *state = ClusterState::default();
+        match fs::read_to_string(&config.config_file) {
+            Ok(content) => state.load(&content)?,
+            Err(_) => state.myself = random_id(),
+        }
+        let id = state.myself.clone();
+        let myself = state
+            .nodes
+            .entry(id.clone())
+            .or_insert_with(|| ClusterNode::new(id.clone(), String::new(), 0));
+        myself.ip = config.announce_ip;
+        myself.port = config.port;
+        myself.handshake = false;
+        state.config_file = Some(config.config_file);
+        state.save();
+        self.cluster.update_state(&state);
+        info!("Cluster node id: {}", id);
+        self.cluster.enabled.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn cluster_enabled(&self) -> bool {
+        self.cluster.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn cluster_myself(&self) -> String {
+        self.cluster.state().myself.clone()
+    }
+
+    /// 所有 slot 都有节点负责，并且负责的节点都可以连接上。每条命令都会检查，只读取保存的结果
+    pub fn cluster_ok(&self) -> bool {
+        self.cluster.ok.load(Ordering::Relaxed)
+    }
+
+    /// 节点超时之后变成 fail，由集群的后台任务定期调用
+    pub fn cluster_update_state(&self) {
+        let state = self.cluster.state();
+        self.cluster.update_state(&state);
+    }
+
+    /// 处理 slot 的节点
+    pub fn cluster_route(&self, slot: u16) -> SlotRoute {
+        let state = self.cluster.state();
+        let owner = state.slots[slot as usize].as_deref();
+        match owner {
+            Some(id) if id == state.myself => match state.migrating.get(&slot) {
+                Some(target) => state
+                    .addr_of(target)
+                    .map_or(SlotRoute::Myself, SlotRoute::Migrating),
+                None => SlotRoute::Myself,
+            },
+            _ if state.importing.contains_key(&slot) => {
+                SlotRoute::Importing(owner.and_then(|id| state.addr_of(id)))
+            }
+            Some(id) => state
+                .addr_of(id)
+                .map_or(SlotRoute::Unassigned, SlotRoute::Moved),
+            None => SlotRoute::Unassigned,
+        }
+    }
+
+    /// 所有的节点，自己在最前面
+    pub fn cluster_nodes(&self) -> Vec<ClusterNode> {
+        let state = self.cluster.state();
+        let myself = state.myself().cloned().into_iter();
+        let others = state
+            .nodes
+            .values()
+            .filter(|node| node.id != state.myself)
+            .cloned();
+        myself.chain(others).collect()
+    }
+
+    /// 节点负责的 slot 区间
+    pub fn cluster_slot_ranges(&self, id: &str) -> Vec<(u16, u16)> {
+        self.cluster.state().slot_ranges(id)
+    }
+
+    /// 已经分配的 slot 数量
+    pub fn cluster_assigned_slots(&self) -> usize {
+        self.cluster.state().slots.iter().flatten().count()
+    }
+
+    pub fn cluster_current_epoch(&self) -> u64 {
+        self.cluster.state().current_epoch
+    }
+
+    pub fn cluster_nodes_text(&self) -> String {
+        self.cluster.state().nodes_text()
+    }
+
+    /// CLUSTER ADDSLOTS，所有的 slot 都没有被分配时才分配给自己
+    pub fn cluster_add_slots(&self, slots: &[u16]) -> Result<(), String> {
+        let mut state = self.cluster.state();
+        if let Some(slot) = slots.iter().find(|&&s| state.slots[s as usize].is_some()) {
+            return Err(format!("Slot {} is already busy", slot));
+        }
+        let myself = state.myself.clone();
+        for &slot in slots {
+            state.slots[slot as usize] = Some(myself.clone());
+            state.importing.remove(&slot);
+        }
+        if state.myself().is_some_and(|node| node.config_epoch == 0) {
+            state.bump_epoch();
+        }
+        state.save();
+        self.cluster.update_state(&state);
+        Ok(())
+    }
+
+    /// CLUSTER DELSLOTS
+    pub fn cluster_del_slots(&self, slots: &[u16]) -> Result<(), String> {
+        let mut state = self.cluster.state();
+        if let Some(slot) = slots.iter().find(|&&s| state.slots[s as usize].is_none()) {
+            return Err(format!("Slot {} is already unassigned", slot));
+        }
+        for &slot in slots {
+            state.slots[slot as usize] = None;
+            state.migrating.remove(&slot);
+            state.importing.remove(&slot);
+        }
+        state.save();
+        self.cluster.update_state(&state);
+        Ok(())
+    }
+
+    /// CLUSTER SETSLOT slot IMPORTING|MIGRATING|STABLE|NODE
+    pub fn cluster_set_slot(&self, slot: u16, op: SetSlot) -> Result<(), String> {
+        let mut state = self.cluster.state();
+        let owner = state.slots[slot as usize].clone();
+        let mine = owner.as_deref() == Some(state.myself.as_str());
+        let known = |state: &ClusterState, id: &str| {
+            state
+                .nodes
+                .get(id)
+                .filter(|node| !node.handshake)
+                .map(|_| ())
+                .ok_or_else(|| format!("I don't know about node {}", id))
+        };
+        match op {
+            SetSlot::Importing(id) => {
+                if mine {
+                    return Err(format!("I'm already the owner of hash slot {}", slot));
+                }
+                known(&state, &id)?;
+                state.importing.insert(slot, id);
+            }
+            SetSlot::Migrating(id) => {
+                if !mine {
+                    return Err(format!("I'm not the owner of hash slot {}", slot));
+                }
+                known(&state, &id)?;
+                state.migrating.insert(slot, id);
+            }
+            SetSlot::Stable => {
+                state.migrating.remove(&slot);
+                state.importing.remove(&slot);
+            }
+            SetSlot::Node(id) => {
+                known(&state, &id)?;
+                // 迁移完成，新的 owner 使用更大的 epoch，其他节点会接受它的声明
+                if id == state.myself && state.importing.remove(&slot).is_some() {
+                    state.bump_epoch();
+                }
+                if id != state.myself {
+                    state.migrating.remove(&slot);
+                }
+                state.slots[slot as usize] = Some(id);
+            }
+        }
+        state.save();
+        self.cluster.update_state(&state);
+        Ok(())
+    }
+
+    /// CLUSTER MEET：记录节点的地址，后台任务连接上之后拿到节点真正的 id
+    pub fn cluster_meet(&self, ip: String, port: u16) {
+        let mut state = self.cluster.state();
+        if state
+            .nodes
+            .values()
+            .any(|node| node.ip == ip && node.port == port)
+        {
+            return;
+        }
+        let mut node = ClusterNode::new(random_id(), ip, port);
+        node.handshake = true;
+        node.pong_recv = now_ms();
+        state.nodes.insert(node.id.clone(), node);
+    }
+
+    /// 需要定期获取信息的其他节点
+    pub fn cluster_peers(&self) -> Vec<ClusterNode> {
+        let state = self.cluster.state();
+        state
+            .nodes
+            .values()
+            .filter(|node| node.id != state.myself)
+            .cloned()
+            .collect()
+    }
+
+    /// 连接不上节点，握手一直没有完成的节点被移除
+    pub fn cluster_node_unreachable(&self, id: &str) {
+        let mut state = self.cluster.state();
+        let Some(node) = state.nodes.get_mut(id) else {
+            return;
+        };
+        node.connected = false;
+        if node.handshake && node.failing() {
+            state.nodes.remove(id);
+        }
+        self.cluster.update_state(&state);
+    }
+
+    /// 合并从节点 id 获取的 CLUSTER NODES，返回这个节点是否已经认识自己
+    pub fn cluster_gossip(&self, id: &str, text: &str) -> bool {
+        let entries: Vec<NodeEntry> = text.lines().filter_map(NodeEntry::parse).collect();
+        let Some(sender) = entries.iter().find(|entry| entry.myself) else {
+            return true;
+        };
+        let mut state = self.cluster.state();
+        let Some(known) = state.nodes.get(id).cloned() else {
+            return true;
+        };
+        // 握手完成，用节点真正的 id 替换掉临时的 id
+        let sender_id = sender.node.id.clone();
+        if sender_id == state.myself {
+            state.nodes.remove(id);
+            return true;
+        }
+        if sender_id != id {
+            state.nodes.remove(id);
+        }
+        let epoch = sender.node.config_epoch;
+        // 只有节点或者 slot 的归属变化时才保存 nodes.conf
+        let mut changed = sender_id != id || known.handshake || known.config_epoch != epoch;
+        state.nodes.insert(
+            sender_id.clone(),
+            ClusterNode {
+                id: sender_id.clone(),
+                config_epoch: epoch,
+                handshake: false,
+                connected: true,
+                pong_recv: now_ms(),
+                ..known
+            },
+        );
+        state.current_epoch = state.current_epoch.max(epoch);
+
+        // 只接受节点对自己负责的 slot 的声明，epoch 更大时覆盖原来的 owner
+        for &(start, end) in &sender.slots {
+            for slot in start..=end {
+                let owner = state.slots[slot as usize].clone();
+                let replace = match owner.as_deref() {
+                    None => true,
+                    Some(owner) if owner == sender_id => false,
+                    Some(owner) => state.epoch_of(owner) < epoch,
+                };
+                if replace {
+                    changed = true;
+                    if owner.as_deref() == Some(state.myself.as_str()) {
+                        state.migrating.remove(&slot);
+                    }
+                    state.slots[slot as usize] = Some(sender_id.clone());
+                    if state.importing.get(&slot) == Some(&sender_id) {
+                        state.importing.remove(&slot);
+                    }
+                }
+            }
+        }
+        // 和自己的 epoch 冲突时，id 更大的一方递增 epoch
+        let my_epoch = state.myself().map_or(0, |node| node.config_epoch);
+        if epoch > 0 && epoch == my_epoch && sender_id < state.myself {
+            state.bump_epoch();
+            changed = true;
+        }
+
+        // 通过这个节点认识其他的节点
+        let myself = state.myself().cloned();
+        for entry in entries
+            .iter()
+            .filter(|entry| !entry.myself && !entry.node.handshake)
+        {
+            let same_addr =
+                |node: &ClusterNode| node.ip == entry.node.ip && node.port == entry.node.port;
+            if state.nodes.contains_key(&entry.node.id) || myself.as_ref().is_some_and(same_addr) {
+                continue;
+            }
+            state
+                .nodes
+                .retain(|_, node| !(node.handshake && same_addr(node)));
+            let node = ClusterNode {
+                connected: false,
+                pong_recv: now_ms(),
+                ..entry.node.clone()
+            };
+            state.nodes.insert(node.id.clone(), node);
+            changed = true;
+        }
+        if changed {
+            state.save();
+        }
+        self.cluster.update_state(&state);
+        entries.iter().any(|entry| entry.node.id == state.myself)
+    }
+
+    /// slot 中的 key，最多 count 个
+    pub fn keys_in_slot(&self, slot: u16, count: usize) -> Vec<Vec<u8>> {
+        self.db
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|key| key_hash_slot(key) == slot)
+            .take(count)
+            .collect()
+    }
+
+    pub fn count_keys_in_slot(&self, slot: u16) -> usize {
+        self.db
+            .iter()
+            .filter(|entry| key_hash_slot(entry.key()) == slot)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cluster_backend(port: u16) -> Backend {
+        let backend = Backend::new();
+        let dir = std::env::temp_dir().join(format!(
+            "simple-redis-cluster-{}-{}",
+            std::process::id(),
+            port
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        backend
+            .configure_cluster(ClusterConfig {
+                enabled: true,
+                config_file: dir.join("nodes.conf"),
+                announce_ip: "127.0.0.1".to_string(),
+                port,
+            })
+            .unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_key_hash_slot() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"user1000")
+        );
+        assert_eq!(key_hash_slot(b"foo{}{bar}"), key_hash_slot(b"foo{}{bar}"));
+        assert_ne!(key_hash_slot(b"foo{}{bar}"), key_hash_slot(b"bar"));
+        assert_eq!(key_hash_slot(b"foo{{bar}}zap"), key_hash_slot(b"{bar"));
+        assert_eq!(key_hash_slot(b"foo{bar}{zap}"), key_hash_slot(b"bar"));
+    }
+
+    #[test]
+    fn test_nodes_line() {
+        let line = "07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004,hostname4 \
+                    myself,master - 0 1426238317239 4 connected 0-5460 5462 [5461->-e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca]";
+        let entry = NodeEntry::parse(line).unwrap();
+        assert!(entry.myself);
+        assert_eq!(entry.node.port, 30004);
+        assert_eq!(entry.node.config_epoch, 4);
+        assert_eq!(entry.slots, vec![(0, 5460), (5462, 5462)]);
+        assert_eq!(entry.migrating.len(), 1);
+        assert_eq!(NodeEntry::parse("foo bar"), None);
+    }
+
+    #[test]
+    fn test_cluster_gossip_and_config() {
+        let a = cluster_backend(7000);
+        let b = cluster_backend(7001);
+        a.cluster_add_slots(&(0..8192).collect::<Vec<_>>()).unwrap();
+        b.cluster_add_slots(&(8192..16384).collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(
+            a.cluster_add_slots(&[1]),
+            Err("Slot 1 is already busy".to_string())
+        );
+        assert!(!a.cluster_ok());
+
+        // a MEET b 之后拿到 b 的信息，b 还不认识 a
+        a.cluster_meet("127.0.0.1".to_string(), 7001);
+        let placeholder = a.cluster_peers()[0].id.clone();
+        assert!(!a.cluster_gossip(&placeholder, &b.cluster_nodes_text()));
+        assert!(a.cluster_ok());
+        assert_eq!(a.cluster_route(0), SlotRoute::Myself);
+        assert_eq!(
+            a.cluster_route(9000),
+            SlotRoute::Moved("127.0.0.1:7001".to_string())
+        );
+        let b_id = b.cluster_myself();
+        assert_eq!(a.cluster_peers()[0].id, b_id);
+
+        // 迁移 slot 0 到 b
+        let a_id = a.cluster_myself();
+        b.cluster_meet("127.0.0.1".to_string(), 7000);
+        let placeholder = b.cluster_peers()[0].id.clone();
+        assert!(b.cluster_gossip(&placeholder, &a.cluster_nodes_text()));
+        b.cluster_set_slot(0, SetSlot::Importing(a_id.clone()))
+            .unwrap();
+        a.cluster_set_slot(0, SetSlot::Migrating(b_id.clone()))
+            .unwrap();
+        assert_eq!(
+            a.cluster_route(0),
+            SlotRoute::Migrating("127.0.0.1:7001".to_string())
+        );
+        assert_eq!(
+            b.cluster_route(0),
+            SlotRoute::Importing(Some("127.0.0.1:7000".to_string()))
+        );
+        b.cluster_set_slot(0, SetSlot::Node(b_id.clone())).unwrap();
+        assert_eq!(b.cluster_route(0), SlotRoute::Myself);
+        // a 通过 b 更大的 epoch 得知迁移完成
+        a.cluster_gossip(&b_id, &b.cluster_nodes_text());
+        assert_eq!(
+            a.cluster_route(0),
+            SlotRoute::Moved("127.0.0.1:7001".to_string())
+        );
+        assert_eq!(a.cluster_slot_ranges(&a_id), vec![(1, 8191)]);
+
+        // 重新加载 nodes.conf
+        let path = a.cluster.state().config_file.clone().unwrap();
+        let loaded = Backend::new();
+        loaded
+            .configure_cluster(ClusterConfig {
+                enabled: true,
+                config_file: path,
+                announce_ip: "127.0.0.1".to_string(),
+                port: 7000,
+            })
+            .unwrap();
+        assert_eq!(loaded.cluster_myself(), a_id);
+        assert_eq!(
+            loaded.cluster_slot_ranges(&b_id),
+            vec![(0, 0), (8192, 16383)]
+        );
+        assert_eq!(loaded.cluster_current_epoch(), a.cluster_current_epoch());
+        assert!(loaded.cluster_ok());
+
+        // b 很久没有连接上，集群状态在检查节点故障时变成不可用
+        assert!(a.cluster_ok());
+        a.cluster.state().nodes.get_mut(&b_id).unwrap().pong_recv = 0;
+        assert!(a.cluster_ok());
+        a.cluster_node_unreachable(&b_id);
+        assert!(!a.cluster_ok());
+        a.cluster_gossip(&b_id, &b.cluster_nodes_text());
+        assert!(a.cluster_ok());
+    }
+}
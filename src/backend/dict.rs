@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+/// 元素较多的 hash/set 使用的字典，对应 Redis 的 dict
+///
+/// 元素依次存放在 Vec 中，HashMap 只保存 key 到下标的映射，删除时用最后一个元素填补空位。
+/// 这样随机取元素是 O(1) 的，SCAN 也可以直接用下标作为游标
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dict<V> {
+    index: HashMap<Vec<u8>, usize>,
+    entries: Vec<(Vec<u8>, V)>,
+}
+
+impl<V> Default for Dict<V> {
+    fn default() -> Self {
+        Self {
+            index: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<V> Dict<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        self.index.get(key).map(|&i| &mut self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// 写入 key，返回原来的 value
+    pub fn insert(&mut self, key: Vec<u8>, value: V) -> Option<V> {
+        match self.index.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.swap_remove(i);
+        if let Some((moved, _)) = self.entries.get(i) {
+            self.index.insert(moved.clone(), i);
+        }
+        Some(value)
+    }
+
+    /// 按照下标获取元素，用于随机取元素
+    pub fn get_index(&self, i: usize) -> Option<(&[u8], &V)> {
+        self.entries.get(i).map(|(k, v)| (k.as_slice(), v))
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&[u8], &V)> + ExactSizeIterator {
+        self.entries.iter().map(|(k, v)| (k.as_slice(), v))
+    }
+
+    /// 从 cursor 开始遍历最多 count 个元素，返回下一次的游标，遍历结束时游标为 0
+    ///
+    /// 从后往前遍历：删除元素时只会把末尾 (已经遍历过) 的元素移动到前面，
+    /// 因此和 Redis 一样保证遍历期间一直存在的元素至少会被返回一次
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&[u8], &V)>) {
+        let end = match cursor {
+            0 => self.entries.len(),
+            cursor => (cursor as usize).min(self.entries.len()),
+        };
+        let start = end.saturating_sub(count.max(1));
+        let items = self.entries[start..end]
+            .iter()
+            .rev()
+            .map(|(k, v)| (k.as_slice(), v))
+            .collect();
+        (start as u64, items)
+    }
+}
+
+impl<V> FromIterator<(Vec<u8>, V)> for Dict<V> {
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, V)>>(iter: I) -> Self {
+        let mut dict = Dict::new();
+        for (k, v) in iter {
+            dict.insert(k, v);
+        }
+        dict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_dict() {
+        let mut dict: Dict<i32> = (0..5).map(|i| (vec![b'a' + i as u8], i)).collect();
+        assert_eq!(dict.len(), 5);
+        assert_eq!(dict.insert(b"a".to_vec(), 10), Some(0));
+        assert_eq!(dict.get(b"a"), Some(&10));
+        assert_eq!(dict.remove(b"b"), Some(1));
+        assert_eq!(dict.remove(b"b"), None);
+        // 最后一个元素被移动到了被删除的位置
+        assert_eq!(dict.get_index(1), Some((&b"e"[..], &4)));
+        assert_eq!(dict.get(b"e"), Some(&4));
+        *dict.get_mut(b"e").unwrap() += 1;
+        assert_eq!(dict.get(b"e"), Some(&5));
+    }
+
+    #[test]
+    fn test_dict_scan_with_removal() {
+        let mut dict: Dict<()> = (0..100)
+            .map(|i: i32| (i.to_be_bytes().to_vec(), ()))
+            .collect();
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut removed: i32 = 0;
+        loop {
+            let (next, items) = dict.scan(cursor, 7);
+            seen.extend(items.into_iter().map(|(k, _)| k.to_vec()));
+            // 遍历过程中删除元素，没有被删除的元素都必须被遍历到
+            for _ in 0..3 {
+                let key = (removed * 3).to_be_bytes().to_vec();
+                if dict.remove(&key).is_some() {
+                    seen.insert(key);
+                }
+                removed += 1;
+            }
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        for i in 0..100i32 {
+            assert!(seen.contains(i.to_be_bytes().as_slice()), "missing {}", i);
+        }
+    }
+}
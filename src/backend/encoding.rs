@@ -0,0 +1,244 @@
+use super::{LpValue, RdbError};
+
+// ziplist 中整数的编码
+const ZIP_INT_16B: u8 = 0xc0;
+const ZIP_INT_32B: u8 = 0xd0;
+const ZIP_INT_64B: u8 = 0xe0;
+const ZIP_INT_24B: u8 = 0xf0;
+const ZIP_INT_8B: u8 = 0xfe;
+const ZIP_END: u8 = 0xff;
+
+/// (key, value) 列表
+type Pairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// 读取 Redis 紧凑编码使用的小端整数和切片，越界时返回 Eof
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8], pos: usize) -> Self {
+        Self { buf, pos }
+    }
+
+    fn u8(&mut self) -> Result<u8, RdbError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RdbError> {
+        let end = self.pos.checked_add(len).ok_or(RdbError::Eof)?;
+        let data = self.buf.get(self.pos..end).ok_or(RdbError::Eof)?;
+        self.pos = end;
+        Ok(data)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RdbError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+/// 解析 ziplist (Redis 7.0 之前 list/hash/zset 的紧凑编码)：
+/// 4 字节总长度 + 4 字节最后一个元素的偏移 + 2 字节元素数量 + 元素... + 0xFF，
+/// 每个元素是 前一个元素的长度 + 编码 + 数据
+pub fn decode_ziplist(buf: &[u8]) -> Result<Vec<LpValue>, RdbError> {
+    let mut c = Cursor::new(buf, 10);
+    let mut items = Vec::new();
+    loop {
+        let first = c.u8()?;
+        if first == ZIP_END {
+            break;
+        }
+        // prevlen：小于 254 时一个字节，否则是 0xFE 之后的 4 字节
+        if first == 0xfe {
+            c.take(4)?;
+        }
+        let encoding = c.u8()?;
+        let item = match encoding >> 6 {
+            0 => LpValue::Str(c.take((encoding & 0x3f) as usize)?.to_vec()),
+            1 => {
+                let len = (((encoding & 0x3f) as usize) << 8) | c.u8()? as usize;
+                LpValue::Str(c.take(len)?.to_vec())
+            }
+            2 => {
+                let len = u32::from_be_bytes(c.array()?);
+                LpValue::Str(c.take(len as usize)?.to_vec())
+            }
+            _ => LpValue::Int(match encoding {
+                ZIP_INT_16B => i16::from_le_bytes(c.array()?) as i64,
+                ZIP_INT_32B => i32::from_le_bytes(c.array()?) as i64,
+                ZIP_INT_64B => i64::from_le_bytes(c.array()?),
+                ZIP_INT_24B => {
+                    let b: [u8; 3] = c.array()?;
+                    (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as i64
+                }
+                ZIP_INT_8B => c.u8()? as i8 as i64,
+                // 1111xxxx：xxxx 减一就是 0 到 12 的整数
+                0xf1..=0xfd => (encoding & 0x0f) as i64 - 1,
+                _ => {
+                    return Err(RdbError::Invalid(format!(
+                        "unknown ziplist encoding {:#x}",
+                        encoding
+                    )))
+                }
+            }),
+        };
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// 解析 intset (只包含整数的 set 的紧凑编码)：4 字节的整数宽度 (2/4/8) + 4 字节元素数量 + 元素
+pub fn decode_intset(buf: &[u8]) -> Result<Vec<i64>, RdbError> {
+    let mut c = Cursor::new(buf, 0);
+    let width = u32::from_le_bytes(c.array()?);
+    let len = u32::from_le_bytes(c.array()?);
+    (0..len)
+        .map(|_| match width {
+            2 => Ok(i16::from_le_bytes(c.array()?) as i64),
+            4 => Ok(i32::from_le_bytes(c.array()?) as i64),
+            8 => Ok(i64::from_le_bytes(c.array()?)),
+            _ => Err(RdbError::Invalid(format!(
+                "invalid intset encoding {}",
+                width
+            ))),
+        })
+        .collect()
+}
+
+/// 解析 zipmap (Redis 2.6 之前 hash 的紧凑编码)：1 字节元素数量 + (key 长度, key, value 长度,
+/// 空闲字节数, value, 空闲字节)... + 0xFF，长度小于 254 时一个字节，否则是 0xFE 之后的 4 字节
+pub fn decode_zipmap(buf: &[u8]) -> Result<Pairs, RdbError> {
+    let mut c = Cursor::new(buf, 1);
+    let len = |c: &mut Cursor| -> Result<Option<usize>, RdbError> {
+        match c.u8()? {
+            ZIP_END => Ok(None),
+            0xfe => Ok(Some(u32::from_le_bytes(c.array()?) as usize)),
+            n => Ok(Some(n as usize)),
+        }
+    };
+    let mut pairs = Vec::new();
+    while let Some(key_len) = len(&mut c)? {
+        let key = c.take(key_len)?.to_vec();
+        let value_len = len(&mut c)?.ok_or(RdbError::Eof)?;
+        let free = c.u8()? as usize;
+        let value = c.take(value_len)?.to_vec();
+        c.take(free)?;
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+/// LZF 最大的膨胀比例：3 个字节的反向引用最多复制 7 + 255 + 2 = 264 个字节
+const LZF_MAX_RATIO: usize = 88;
+
+/// LZF 解压缩，RDB 中较长的字符串在开启 rdbcompression 时使用 LZF 压缩
+///
+/// 控制字节小于 32 时之后是 ctrl + 1 个字面字节；否则高 3 位是长度 (7 时再读一个字节)，
+/// 低 5 位和下一个字节是向前的偏移，从已经解压的数据中复制 长度 + 2 个字节
+pub fn lzf_decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>, RdbError> {
+    let invalid = || RdbError::Invalid("invalid lzf compressed string".to_string());
+    // 长度来自不可信的数据，不能直接按照它分配内存，解压的结果不可能超过最大的膨胀比例
+    if expected_len > input.len().saturating_mul(LZF_MAX_RATIO) {
+        return Err(invalid());
+    }
+    let mut out = Vec::new();
+    let mut c = Cursor::new(input, 0);
+    while c.pos < input.len() {
+        if out.len() > expected_len {
+            return Err(invalid());
+        }
+        let ctrl = c.u8()? as usize;
+        if ctrl < 32 {
+            out.extend_from_slice(c.take(ctrl + 1)?);
+            continue;
+        }
+        let mut len = ctrl >> 5;
+        if len == 7 {
+            len += c.u8()? as usize;
+        }
+        let back = ((ctrl & 0x1f) << 8) + c.u8()? as usize + 1;
+        let start = out.len().checked_sub(back).ok_or_else(invalid)?;
+        // 复制的范围可以和正在写入的部分重叠，只能逐个字节复制
+        for i in 0..len + 2 {
+            out.push(out[start + i]);
+        }
+    }
+    if out.len() != expected_len {
+        return Err(invalid());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ziplist() {
+        // ziplist.c 注释中的例子：包含 "2" 和 "5" 两个元素
+        let zl = [
+            0x0f, 0, 0, 0, 0x0c, 0, 0, 0, 0x02, 0, 0x00, 0xf3, 0x02, 0xf6, 0xff,
+        ];
+        assert_eq!(
+            decode_ziplist(&zl).unwrap(),
+            vec![LpValue::Int(2), LpValue::Int(5)]
+        );
+
+        let mut zl = vec![0; 10];
+        zl.extend_from_slice(&[0x00, 0x05]);
+        zl.extend_from_slice(b"hello");
+        zl.extend_from_slice(&[0x07, ZIP_INT_16B, 0x18, 0xfc]);
+        zl.extend_from_slice(&[0x04, ZIP_INT_24B, 0x60, 0x79, 0xfe]);
+        zl.extend_from_slice(&[0x05, ZIP_INT_8B, 0x80]);
+        zl.extend_from_slice(&[0x03, 0x41, 0x2c]);
+        zl.extend_from_slice(&[b'x'; 300]);
+        zl.push(ZIP_END);
+        assert_eq!(
+            decode_ziplist(&zl).unwrap(),
+            vec![
+                LpValue::Str(b"hello".to_vec()),
+                LpValue::Int(-1000),
+                LpValue::Int(-100000),
+                LpValue::Int(-128),
+                LpValue::Str(vec![b'x'; 300]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_intset_and_zipmap() {
+        let is = [2, 0, 0, 0, 3, 0, 0, 0, 0xff, 0xff, 1, 0, 0x10, 0x27];
+        assert_eq!(decode_intset(&is).unwrap(), vec![-1, 1, 10000]);
+
+        // zipmap.c 注释中的例子："foo" => "bar", "hello" => "world"，bar 之后有一个空闲字节
+        let mut zm = vec![0x02, 0x03];
+        zm.extend_from_slice(b"foo");
+        zm.extend_from_slice(&[0x03, 0x01]);
+        zm.extend_from_slice(b"bar?");
+        zm.push(0x05);
+        zm.extend_from_slice(b"hello");
+        zm.extend_from_slice(&[0x05, 0x00]);
+        zm.extend_from_slice(b"world");
+        zm.push(ZIP_END);
+        assert_eq!(
+            decode_zipmap(&zm).unwrap(),
+            vec![
+                (b"foo".to_vec(), b"bar".to_vec()),
+                (b"hello".to_vec(), b"world".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lzf_decompress() {
+        // 字面量 "ab"，然后从 2 个字节之前复制 7 个字节，再从 1 个字节之前复制 20 个字节
+        let data = [0x01, b'a', b'b', 0xa0, 0x01, 0xe0, 0x0b, 0x00];
+        let expected = [b"ababababa".as_slice(), &[b'a'; 20]].concat();
+        assert_eq!(lzf_decompress(&data, expected.len()).unwrap(), expected);
+        assert!(lzf_decompress(&data, 10).is_err());
+        assert!(lzf_decompress(&[0x20, 0x05], 2).is_err());
+        // 声明的长度远超过数据能够解压出来的长度时直接返回错误，不会尝试分配内存
+        assert!(lzf_decompress(&data, 1 << 40).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use super::{now_ms, Backend};
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+// 每轮抽样的 key 数量
+const ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP: usize = 20;
+// 抽样中过期 key 的比例超过这个值 (百分比) 时，继续下一轮抽样
+const ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE: usize = 10;
+// 一次 cycle 最多占用的时间
+const ACTIVE_EXPIRE_CYCLE_TIME_LIMIT: Duration = Duration::from_millis(25);
+// cycle 执行的间隔，对应 Redis 默认的 hz 10
+const ACTIVE_EXPIRE_CYCLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 记录 key 的过期时间，对应 Redis 中的 expires 字典
+///
+/// 除了按 key 查找之外，主动过期需要随机抽样，因此额外用一个 Vec 保存所有的 key
+#[derive(Debug, Default)]
+pub struct Expires(Mutex<ExpiresInner>);
+
+#[derive(Debug, Default)]
+struct ExpiresInner {
+    // key -> (过期时间, 在 keys 中的下标)
+    deadlines: HashMap<Vec<u8>, (u64, usize)>,
+    keys: Vec<Vec<u8>>,
+}
+
+impl Expires {
+    pub fn get(&self, key: &[u8]) -> Option<u64> {
+        self.inner().deadlines.get(key).map(|(at, _)| *at)
+    }
+
+    pub fn insert(&self, key: Vec<u8>, at: u64) {
+        let mut inner = self.inner();
+        let pos = inner.keys.len();
+        match inner.deadlines.get_mut(&key) {
+            Some(entry) => entry.0 = at,
+            None => {
+                inner.keys.push(key.clone());
+                inner.deadlines.insert(key, (at, pos));
+            }
+        }
+    }
+
+    pub fn remove(&self, key: &[u8]) -> Option<u64> {
+        let mut inner = self.inner();
+        let (at, pos) = inner.deadlines.remove(key)?;
+        // 用最后一个 key 填补空位，保证删除是 O(1) 的
+        inner.keys.swap_remove(pos);
+        if let Some(moved) = inner.keys.get(pos).cloned() {
+            if let Some(entry) = inner.deadlines.get_mut(&moved) {
+                entry.1 = pos;
+            }
+        }
+        Some(at)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner().keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 随机抽取最多 n 个设置了过期时间的 key
+    pub fn sample(&self, n: usize) -> Vec<(Vec<u8>, u64)> {
+        let inner = self.inner();
+        if inner.keys.is_empty() {
+            return Vec::new();
+        }
+        let mut rng = rand::thread_rng();
+        (0..n.min(inner.keys.len()))
+            .map(|_| {
+                let key = &inner.keys[rng.gen_range(0..inner.keys.len())];
+                (key.clone(), inner.deadlines[key].0)
+            })
+            .collect()
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, ExpiresInner> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Backend {
+    /// 主动过期，参考 Redis 的 activeExpireCycle：
+    /// 每轮随机抽样一批 key 删除其中过期的，过期比例较高时继续抽样，直到超出时间限制
+    pub fn active_expire_cycle(&self) -> usize {
+        expire_cycle(&self.expires, |key| self.remove(key).is_some() as usize)
+    }
+
+    /// hash field 的主动过期，抽样有 field 过期的 hash，删除其中所有已经过期的 field
+    pub fn active_expire_fields_cycle(&self) -> usize {
+        expire_cycle(&self.field_expires, |key| self.expire_fields(key))
+    }
+}
+
+/// 从 table 中抽样，对已经过期的 key 调用 expire，返回 expire 删除的总数
+fn expire_cycle(table: &Expires, expire: impl Fn(&[u8]) -> usize) -> usize {
+    let start = Instant::now();
+    let mut expired = 0;
+    loop {
+        let samples = table.sample(ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP);
+        if samples.is_empty() {
+            break;
+        }
+        let now = now_ms();
+        let mut stale = 0;
+        for (key, at) in samples.iter() {
+            // 同一个 key 可能被抽到多次，只统计真正删除的
+            if *at <= now {
+                let n = expire(key);
+                if n > 0 {
+                    stale += 1;
+                    expired += n;
+                }
+            }
+        }
+        if stale * 100 <= samples.len() * ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE
+            || start.elapsed() > ACTIVE_EXPIRE_CYCLE_TIME_LIMIT
+        {
+            break;
+        }
+    }
+    expired
+}
+
+/// 启动后台的主动过期任务
+pub fn spawn_active_expire(backend: Backend) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ACTIVE_EXPIRE_CYCLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let (expired, fields) = {
+                let _guard = backend.lock_async().await;
+                (
+                    backend.active_expire_cycle(),
+                    backend.active_expire_fields_cycle(),
+                )
+            };
+            if expired > 0 {
+                debug!("active expire cycle removed {} keys", expired);
+            }
+            if fields > 0 {
+                debug!("active expire cycle removed {} hash fields", fields);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash, Value};
+
+    // 抽样是随机的，测试多跑几轮，但不能无限循环
+    const MAX_TEST_CYCLES: usize = 1000;
+
+    #[test]
+    fn test_expires_table() {
+        let expires = Expires::default();
+        expires.insert(b"a".to_vec(), 1);
+        expires.insert(b"b".to_vec(), 2);
+        expires.insert(b"c".to_vec(), 3);
+        expires.insert(b"a".to_vec(), 10);
+        assert_eq!(expires.len(), 3);
+        assert_eq!(expires.get(b"a"), Some(10));
+
+        assert_eq!(expires.remove(b"a"), Some(10));
+        assert_eq!(expires.remove(b"a"), None);
+        assert_eq!(expires.get(b"c"), Some(3));
+        assert_eq!(expires.remove(b"c"), Some(3));
+        assert_eq!(expires.get(b"b"), Some(2));
+        assert_eq!(expires.sample(5), vec![(b"b".to_vec(), 2)]);
+    }
+
+    #[test]
+    fn test_active_expire_cycle() {
+        let backend = Backend::new();
+        let now = now_ms();
+        for i in 0..1000 {
+            let key = format!("key:{}", i).into_bytes();
+            backend.set(key.clone(), Value::String(b"v".to_vec()));
+            // 一半的 key 已经过期
+            let at = if i % 2 == 0 { now - 1 } else { now + 100_000 };
+            backend.set_expire(&key, at);
+        }
+        backend.set(b"persistent".to_vec(), Value::String(b"v".to_vec()));
+
+        let mut expired = 0;
+        for _ in 0..MAX_TEST_CYCLES {
+            if expired >= 500 {
+                break;
+            }
+            expired += backend.active_expire_cycle();
+        }
+        assert_eq!(expired, 500);
+        assert_eq!(backend.expires.len(), 500);
+        assert_eq!(backend.db.len(), 501);
+    }
+
+    #[test]
+    fn test_active_expire_fields_cycle() {
+        let backend = Backend::new();
+        let now = now_ms();
+        let mut hash: Hash = (0..10)
+            .map(|i| (format!("f{}", i).into_bytes(), b"v".to_vec()))
+            .collect();
+        for i in 0..5 {
+            hash.set_expire(format!("f{}", i).as_bytes(), now - 1);
+        }
+        hash.set_expire(b"f5", now + 100_000);
+        backend.set(b"h".to_vec(), Value::Hash(hash));
+        // field 全部过期的 hash 会被删除
+        let mut hash = Hash::new();
+        hash.set(b"f".to_vec(), b"v".to_vec());
+        hash.set_expire(b"f", now - 1);
+        backend.set(b"gone".to_vec(), Value::Hash(hash));
+        assert_eq!(backend.field_expires.len(), 2);
+
+        // 抽样是随机的，一轮不一定能抽到所有过期的 hash
+        let mut expired = 0;
+        for _ in 0..MAX_TEST_CYCLES {
+            if expired >= 6 {
+                break;
+            }
+            expired += backend.active_expire_fields_cycle();
+        }
+        assert_eq!(expired, 6);
+        assert!(!backend.db.contains_key(b"gone".as_slice()));
+        assert_eq!(backend.field_expires.get(b"h"), Some(now + 100_000));
+        let Some(Value::Hash(hash)) = backend.get(b"h") else {
+            panic!("h should be a hash");
+        };
+        assert_eq!(hash.len(), 5);
+    }
+}
@@ -0,0 +1,194 @@
+use super::{
+    rdb::{RdbError, RdbReader, RdbWriter, RDB_OPCODE_FUNCTION2},
+    Backend,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Mutex, MutexGuard},
+};
+
+/// FUNCTION LOAD 加载的 library，library 名字 -> library，对应 Redis 的 functionsLibCtx
+///
+/// 和 EVAL 的脚本缓存不同，library 属于数据集的一部分，会随数据一起持久化
+#[derive(Debug, Default)]
+pub struct Functions(Mutex<BTreeMap<String, Library>>);
+
+/// 一个 library 的代码以及其中注册的函数，注册的 Lua 函数保存在共享的虚拟机中
+#[derive(Debug, Clone, PartialEq)]
+pub struct Library {
+    pub name: String,
+    pub engine: String,
+    pub code: String,
+    pub functions: Vec<FunctionInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+/// FUNCTION RESTORE 遇到已经存在的 library 时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestorePolicy {
+    // 已经存在时报错
+    Append,
+    // 覆盖已经存在的 library
+    Replace,
+    // 先删除所有的 library
+    Flush,
+}
+
+impl FunctionInfo {
+    /// 声明了 no-writes 的函数可以通过 FCALL_RO 调用，执行时不能写入
+    pub fn is_read_only(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "no-writes")
+    }
+}
+
+impl Functions {
+    /// 加载一组 library，任何一个 library 或者函数名冲突时都不会加载。返回错误信息
+    pub fn load(&self, libraries: Vec<Library>, policy: RestorePolicy) -> Result<(), String> {
+        let mut inner = self.inner();
+        let mut merged = match policy {
+            RestorePolicy::Flush => BTreeMap::new(),
+            _ => inner.clone(),
+        };
+        for library in libraries {
+            if policy == RestorePolicy::Append && merged.contains_key(&library.name) {
+                return Err(format!("Library '{}' already exists", library.name));
+            }
+            merged.insert(library.name.clone(), library);
+        }
+        let mut names = HashSet::new();
+        for function in merged.values().flat_map(|library| library.functions.iter()) {
+            if !names.insert(function.name.as_str()) {
+                return Err(format!("Function {} already exists", function.name));
+            }
+        }
+        *inner = merged;
+        Ok(())
+    }
+
+    /// 删除 library，不存在时返回 false
+    pub fn delete(&self, name: &str) -> bool {
+        self.inner().remove(name).is_some()
+    }
+
+    pub fn flush(&self) {
+        self.inner().clear();
+    }
+
+    /// 按照名字排序的所有 library
+    pub fn libraries(&self) -> Vec<Library> {
+        self.inner().values().cloned().collect()
+    }
+
+    /// 查找函数，返回函数所在的 library 的名字
+    pub fn find(&self, function: &str) -> Option<(String, FunctionInfo)> {
+        self.inner().values().find_map(|library| {
+            library
+                .functions
+                .iter()
+                .find(|f| f.name == function)
+                .map(|f| (library.name.clone(), f.clone()))
+        })
+    }
+
+    /// FUNCTION DUMP 的 payload，和 Redis 一样每个 library 保存为一条 FUNCTION2 记录
+    pub fn dump(&self) -> Vec<u8> {
+        let mut writer = RdbWriter::new();
+        for library in self.inner().values() {
+            writer.write_u8(RDB_OPCODE_FUNCTION2);
+            writer.write_string(library.code.as_bytes());
+        }
+        writer.into_payload()
+    }
+
+    fn inner(&self) -> MutexGuard<'_, BTreeMap<String, Library>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 解析 FUNCTION DUMP 的 payload，返回每个 library 的代码
+pub fn parse_function_dump(payload: &[u8]) -> Result<Vec<String>, RdbError> {
+    let mut reader = RdbReader::from_payload(payload)?;
+    let mut codes = Vec::new();
+    while !reader.is_empty() {
+        let opcode = reader.read_u8()?;
+        if opcode != RDB_OPCODE_FUNCTION2 {
+            return Err(RdbError::Invalid(
+                "given type is not a function".to_string(),
+            ));
+        }
+        let code = String::from_utf8(reader.read_string()?)
+            .map_err(|e| RdbError::Invalid(e.to_string()))?;
+        codes.push(code);
+    }
+    Ok(codes)
+}
+
+impl Backend {
+    /// 查找 FCALL 调用的函数
+    pub fn find_function(&self, name: &str) -> Option<(String, FunctionInfo)> {
+        self.functions.find(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str, functions: &[&str]) -> Library {
+        Library {
+            name: name.to_string(),
+            engine: "LUA".to_string(),
+            code: format!("#!lua name={}\n", name),
+            functions: functions
+                .iter()
+                .map(|f| FunctionInfo {
+                    name: f.to_string(),
+                    description: None,
+                    flags: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_functions_load() {
+        let functions = Functions::default();
+        functions
+            .load(vec![library("a", &["f1", "f2"])], RestorePolicy::Append)
+            .unwrap();
+        assert_eq!(
+            functions.load(vec![library("a", &["f3"])], RestorePolicy::Append),
+            Err("Library 'a' already exists".to_string())
+        );
+        assert_eq!(
+            functions.load(vec![library("b", &["f1"])], RestorePolicy::Append),
+            Err("Function f1 already exists".to_string())
+        );
+        // 冲突时不会加载任何 library
+        assert_eq!(functions.libraries().len(), 1);
+
+        functions
+            .load(vec![library("a", &["f3"])], RestorePolicy::Replace)
+            .unwrap();
+        assert!(functions.find("f1").is_none());
+        assert_eq!(functions.find("f3").unwrap().0, "a");
+
+        let payload = functions.dump();
+        assert_eq!(
+            parse_function_dump(&payload).unwrap(),
+            vec!["#!lua name=a\n".to_string()]
+        );
+        functions
+            .load(vec![library("c", &["f3"])], RestorePolicy::Flush)
+            .unwrap();
+        assert!(!functions.delete("a"));
+        assert!(functions.delete("c"));
+        assert!(functions.libraries().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+/// glob 风格的匹配，规则和 Redis 的 stringmatchlen 一致，用于 KEYS/SCAN MATCH/PSUBSCRIBE 等
+///
+/// 支持 `*`、`?`、`[abc]`、`[^abc]`、`[a-z]` 以及 `\` 转义
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+    let (mut p, mut s) = (0, 0);
+    // 最近一个 `*` 的位置以及它当前匹配到的位置，用于回溯
+    let mut backtrack: Option<(usize, usize)> = None;
+    while s < string.len() {
+        let matched = match pattern.get(p) {
+            Some(b'*') => {
+                // 连续的 `*` 等价于一个
+                while pattern.get(p + 1) == Some(&b'*') {
+                    p += 1;
+                }
+                backtrack = Some((p, s));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p + 1, string[s], nocase),
+            Some(b'\\') if p + 1 < pattern.len() => eq(pattern[p + 1], string[s]).then_some(p + 2),
+            Some(&c) => eq(c, string[s]).then_some(p + 1),
+            None => None,
+        };
+        match (matched, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                s += 1;
+            }
+            // 让上一个 `*` 多匹配一个字符
+            (None, Some((star, pos))) => {
+                backtrack = Some((star, pos + 1));
+                p = star + 1;
+                s = pos + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p.min(pattern.len())..].iter().all(|&c| c == b'*')
+}
+
+/// 匹配 `[...]`，start 是 `[` 之后的位置，匹配成功时返回 `]` 之后的位置
+fn match_class(pattern: &[u8], start: usize, c: u8, nocase: bool) -> Option<usize> {
+    let fold = |v: u8| if nocase { v.to_ascii_lowercase() } else { v };
+    let c = fold(c);
+    let mut p = start;
+    let not = pattern.get(p) == Some(&b'^');
+    if not {
+        p += 1;
+    }
+    let mut matched = false;
+    loop {
+        match pattern.get(p) {
+            // 和 Redis 一样，没有闭合的 `[` 把剩余的部分都当成字符集合
+            None => {
+                p -= 1;
+                break;
+            }
+            Some(b']') => break,
+            Some(b'\\') if p + 1 < pattern.len() => {
+                matched |= fold(pattern[p + 1]) == c;
+                p += 2;
+            }
+            Some(&from) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let (from, to) = (fold(from), fold(pattern[p + 2]));
+                let (from, to) = if from > to { (to, from) } else { (from, to) };
+                matched |= (from..=to).contains(&c);
+                p += 3;
+            }
+            Some(&v) => {
+                matched |= fold(v) == c;
+                p += 1;
+            }
+        }
+    }
+    (matched != not).then_some(p + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        for (pattern, string, expected) in [
+            ("*", "", true),
+            ("*", "anything", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("h*llo", "heeeello", true),
+            ("h*llo", "hello world", false),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-c]llo", "hbllo", true),
+            ("h[c-a]llo", "hbllo", true),
+            ("h[a-c]llo", "hdllo", false),
+            ("h\\*llo", "h*llo", true),
+            ("h\\*llo", "hello", false),
+            ("*.user.*", "session.user.42", true),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYbZ", false),
+            ("news.*", "news.", true),
+        ] {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), string.as_bytes(), false),
+                expected,
+                "{} {}",
+                pattern,
+                string
+            );
+        }
+        assert!(glob_match(b"HELLO*", b"hello world", true));
+        assert!(!glob_match(b"HELLO*", b"hello world", false));
+    }
+}
@@ -0,0 +1,322 @@
+use super::{Dict, ListPack};
+use std::collections::{BTreeSet, HashMap};
+
+// 超过任意一个限制时，从 listpack 转换成 hashtable，和 Redis 一样不会再转换回去
+const HASH_MAX_LISTPACK_ENTRIES: usize = 128;
+const HASH_MAX_LISTPACK_VALUE: usize = 64;
+
+/// (field, value)
+type Entry<'a> = (&'a [u8], &'a [u8]);
+
+/// hash 类型的 value
+///
+/// field 较少且都比较短时，field 和 value 依次交替存放在 listpack 中，否则使用字典。
+/// 和 Redis 7.4 一样，每个 field 可以单独设置过期时间
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Hash {
+    fields: Fields,
+    ttls: FieldTtls,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Fields {
+    Packed(ListPack),
+    Table(Dict<Vec<u8>>),
+}
+
+/// field 的过期时间 (unix 毫秒)，额外按照过期时间排序，方便找到最早过期的 field
+#[derive(Debug, Default, Clone, PartialEq)]
+struct FieldTtls {
+    deadlines: HashMap<Vec<u8>, u64>,
+    order: BTreeSet<(u64, Vec<u8>)>,
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Fields::Packed(ListPack::default())
+    }
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.fields {
+            Fields::Packed(pack) => pack.len() / 2,
+            Fields::Table(dict) => dict.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 当前使用的编码，和 OBJECT ENCODING 的返回值一致
+    pub fn encoding(&self) -> &'static str {
+        match &self.fields {
+            Fields::Packed(_) if !self.ttls.is_empty() => "listpackex",
+            Fields::Packed(_) => "listpack",
+            Fields::Table(_) => "hashtable",
+        }
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&[u8]> {
+        match &self.fields {
+            Fields::Packed(pack) => pairs(pack)
+                .into_iter()
+                .find(|(f, _)| *f == field)
+                .map(|(_, v)| v),
+            Fields::Table(dict) => dict.get(field).map(|v| v.as_slice()),
+        }
+    }
+
+    pub fn contains(&self, field: &[u8]) -> bool {
+        self.get(field).is_some()
+    }
+
+    /// 写入 field，同时清除 field 原有的过期时间，新增 field 时返回 true
+    pub fn set(&mut self, field: Vec<u8>, value: Vec<u8>) -> bool {
+        self.ttls.remove(&field);
+        self.set_keep_ttl(field, value)
+    }
+
+    /// 写入 field，保留 field 原有的过期时间 (HINCRBY 等)，新增 field 时返回 true
+    pub fn set_keep_ttl(&mut self, field: Vec<u8>, value: Vec<u8>) -> bool {
+        if let Fields::Packed(pack) = &mut self.fields {
+            let mut pairs = pairs(pack);
+            let pos = pairs.iter().position(|(f, _)| *f == field.as_slice());
+            let fits = field.len() <= HASH_MAX_LISTPACK_VALUE
+                && value.len() <= HASH_MAX_LISTPACK_VALUE
+                && (pos.is_some() || pairs.len() < HASH_MAX_LISTPACK_ENTRIES);
+            if fits {
+                match pos {
+                    Some(i) => pairs[i].1 = &value,
+                    None => pairs.push((&field, &value)),
+                }
+                let packed = pack_pairs(pairs);
+                *pack = packed;
+                return pos.is_none();
+            }
+            self.convert();
+        }
+        match &mut self.fields {
+            Fields::Table(dict) => dict.insert(field, value).is_none(),
+            Fields::Packed(_) => unreachable!("hash should be converted to hashtable"),
+        }
+    }
+
+    /// 删除 field，field 存在时返回 true
+    pub fn remove(&mut self, field: &[u8]) -> bool {
+        self.ttls.remove(field);
+        match &mut self.fields {
+            Fields::Packed(pack) => {
+                let mut pairs = pairs(pack);
+                let Some(pos) = pairs.iter().position(|(f, _)| *f == field) else {
+                    return false;
+                };
+                pairs.remove(pos);
+                let packed = pack_pairs(pairs);
+                *pack = packed;
+                true
+            }
+            Fields::Table(dict) => dict.remove(field).is_some(),
+        }
+    }
+
+    /// 所有的 (field, value)
+    pub fn entries(&self) -> Vec<Entry<'_>> {
+        match &self.fields {
+            Fields::Packed(pack) => pairs(pack),
+            Fields::Table(dict) => dict.iter().map(|(f, v)| (f, v.as_slice())).collect(),
+        }
+    }
+
+    /// 按照下标获取 (field, value)，用于随机取 field
+    pub fn get_index(&self, i: usize) -> Option<Entry<'_>> {
+        match &self.fields {
+            Fields::Packed(pack) => pairs(pack).get(i).copied(),
+            Fields::Table(dict) => dict.get_index(i).map(|(f, v)| (f, v.as_slice())),
+        }
+    }
+
+    /// HSCAN 使用，listpack 编码时和 Redis 一样一次返回所有的 field
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<Entry<'_>>) {
+        match &self.fields {
+            Fields::Packed(pack) => (0, pairs(pack)),
+            Fields::Table(dict) => {
+                let (cursor, items) = dict.scan(cursor, count);
+                let items = items.into_iter().map(|(f, v)| (f, v.as_slice())).collect();
+                (cursor, items)
+            }
+        }
+    }
+
+    /// 获取 field 的过期时间 (unix 毫秒)
+    pub fn get_expire(&self, field: &[u8]) -> Option<u64> {
+        self.ttls.deadlines.get(field).copied()
+    }
+
+    /// 设置 field 的过期时间 (unix 毫秒)，field 不存在时返回 false
+    pub fn set_expire(&mut self, field: &[u8], at: u64) -> bool {
+        if !self.contains(field) {
+            return false;
+        }
+        self.ttls.insert(field.to_vec(), at);
+        true
+    }
+
+    /// 清除 field 的过期时间，原本设置了过期时间时返回 true
+    pub fn persist(&mut self, field: &[u8]) -> bool {
+        self.ttls.remove(field)
+    }
+
+    /// 最早过期的 field 的过期时间，没有 field 设置过期时间时返回 None
+    pub fn next_expire(&self) -> Option<u64> {
+        self.ttls.order.first().map(|(at, _)| *at)
+    }
+
+    /// 删除所有在 now 之前过期的 field，返回删除的数量
+    pub fn remove_expired(&mut self, now: u64) -> usize {
+        let mut removed = 0;
+        while let Some((at, field)) = self.ttls.order.first() {
+            if *at > now {
+                break;
+            }
+            let field = field.clone();
+            self.remove(&field);
+            removed += 1;
+        }
+        removed
+    }
+
+    fn convert(&mut self) {
+        if let Fields::Packed(pack) = &self.fields {
+            let dict = pairs(pack)
+                .into_iter()
+                .map(|(f, v)| (f.to_vec(), v.to_vec()))
+                .collect();
+            self.fields = Fields::Table(dict);
+        }
+    }
+}
+
+impl FieldTtls {
+    fn is_empty(&self) -> bool {
+        self.deadlines.is_empty()
+    }
+
+    fn insert(&mut self, field: Vec<u8>, at: u64) {
+        if let Some(old) = self.deadlines.insert(field.clone(), at) {
+            self.order.remove(&(old, field.clone()));
+        }
+        self.order.insert((at, field));
+    }
+
+    fn remove(&mut self, field: &[u8]) -> bool {
+        match self.deadlines.remove(field) {
+            Some(at) => {
+                self.order.remove(&(at, field.to_vec()));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl FromIterator<(Vec<u8>, Vec<u8>)> for Hash {
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>>(iter: I) -> Self {
+        let mut hash = Hash::new();
+        for (field, value) in iter {
+            hash.set(field, value);
+        }
+        hash
+    }
+}
+
+fn pairs(pack: &ListPack) -> Vec<Entry<'_>> {
+    pack.entries()
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+fn pack_pairs(pairs: Vec<Entry<'_>>) -> ListPack {
+    pairs.into_iter().flat_map(|(f, v)| [f, v]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_ops() {
+        let mut hash = Hash::new();
+        assert!(hash.set(b"a".to_vec(), b"1".to_vec()));
+        assert!(hash.set(b"b".to_vec(), b"2".to_vec()));
+        assert!(!hash.set(b"a".to_vec(), b"3".to_vec()));
+        assert_eq!(hash.len(), 2);
+        assert_eq!(hash.get(b"a"), Some(&b"3"[..]));
+        assert_eq!(hash.get(b"c"), None);
+        assert_eq!(
+            hash.entries(),
+            vec![(&b"a"[..], &b"3"[..]), (&b"b"[..], &b"2"[..])]
+        );
+        assert!(hash.remove(b"a"));
+        assert!(!hash.remove(b"a"));
+        assert_eq!(hash.get_index(0), Some((&b"b"[..], &b"2"[..])));
+        assert_eq!(hash.encoding(), "listpack");
+    }
+
+    #[test]
+    fn test_hash_encoding_conversion() {
+        let mut hash: Hash = (0..HASH_MAX_LISTPACK_ENTRIES)
+            .map(|i| (i.to_string().into_bytes(), b"v".to_vec()))
+            .collect();
+        assert_eq!(hash.encoding(), "listpack");
+        hash.set(b"0".to_vec(), b"x".to_vec());
+        assert_eq!(hash.encoding(), "listpack");
+        hash.set(b"new".to_vec(), b"v".to_vec());
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(hash.len(), HASH_MAX_LISTPACK_ENTRIES + 1);
+        assert_eq!(hash.get(b"0"), Some(&b"x"[..]));
+
+        let mut hash = Hash::new();
+        hash.set(b"f".to_vec(), vec![b'x'; HASH_MAX_LISTPACK_VALUE + 1]);
+        assert_eq!(hash.encoding(), "hashtable");
+        // 删除之后也不会转换回 listpack
+        hash.set(b"f".to_vec(), b"v".to_vec());
+        assert_eq!(hash.encoding(), "hashtable");
+    }
+
+    #[test]
+    fn test_hash_field_expire() {
+        let mut hash: Hash = (0..3)
+            .map(|i| (format!("f{}", i).into_bytes(), b"v".to_vec()))
+            .collect();
+        assert!(!hash.set_expire(b"missing", 10));
+        assert!(hash.set_expire(b"f0", 20));
+        assert!(hash.set_expire(b"f1", 10));
+        assert_eq!(hash.encoding(), "listpackex");
+        assert_eq!(hash.get_expire(b"f0"), Some(20));
+        assert_eq!(hash.next_expire(), Some(10));
+
+        // HINCRBY 等保留过期时间，HSET 清除过期时间
+        hash.set_keep_ttl(b"f1".to_vec(), b"1".to_vec());
+        assert_eq!(hash.get_expire(b"f1"), Some(10));
+        hash.set(b"f0".to_vec(), b"x".to_vec());
+        assert_eq!(hash.get_expire(b"f0"), None);
+        assert_eq!(hash.next_expire(), Some(10));
+
+        assert!(hash.set_expire(b"f2", 15));
+        assert_eq!(hash.remove_expired(12), 1);
+        assert!(!hash.contains(b"f1"));
+        assert!(hash.persist(b"f2"));
+        assert!(!hash.persist(b"f2"));
+        assert_eq!(hash.next_expire(), None);
+        assert_eq!(hash.remove_expired(100), 0);
+        assert_eq!(hash.len(), 2);
+        assert_eq!(hash.encoding(), "listpack");
+    }
+}
@@ -0,0 +1,300 @@
+use super::ListPack;
+use std::collections::{vec_deque, VecDeque};
+
+// 超过任意一个限制时，从 listpack 转换成 quicklist
+const LIST_MAX_LISTPACK_ENTRIES: usize = 128;
+const LIST_MAX_LISTPACK_BYTES: usize = 8 * 1024;
+
+/// list 类型的 value
+///
+/// 元素较少时所有元素紧凑地存放在一块连续内存中 (类似 Redis 的 listpack)，
+/// 元素变多之后转换成双端队列 (对应 Redis 的 quicklist)
+#[derive(Debug, Clone, PartialEq)]
+pub enum List {
+    Packed(ListPack),
+    Linked(VecDeque<Vec<u8>>),
+}
+
+pub enum ListIter<'a> {
+    Packed(std::vec::IntoIter<&'a [u8]>),
+    Linked(vec_deque::Iter<'a, Vec<u8>>),
+}
+
+impl Default for List {
+    fn default() -> Self {
+        List::Packed(ListPack::default())
+    }
+}
+
+impl List {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            List::Packed(pack) => pack.len(),
+            List::Linked(deque) => deque.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 当前使用的编码，和 OBJECT ENCODING 的返回值一致
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            List::Packed(_) => "listpack",
+            List::Linked(_) => "quicklist",
+        }
+    }
+
+    pub fn iter(&self) -> ListIter<'_> {
+        match self {
+            List::Packed(pack) => ListIter::Packed(pack.entries().into_iter()),
+            List::Linked(deque) => ListIter::Linked(deque.iter()),
+        }
+    }
+
+    pub fn push_back(&mut self, value: Vec<u8>) {
+        match self {
+            // 追加到末尾不需要重新编码
+            List::Packed(pack) if fits(pack.bytes() + value.len(), pack.len() + 1) => {
+                pack.push_back(&value)
+            }
+            _ => self.modify(|deque| deque.push_back(value)),
+        }
+    }
+
+    pub fn push_front(&mut self, value: Vec<u8>) {
+        self.modify(|deque| deque.push_front(value));
+    }
+
+    pub fn pop_front(&mut self) -> Option<Vec<u8>> {
+        self.modify(|deque| deque.pop_front())
+    }
+
+    pub fn pop_back(&mut self) -> Option<Vec<u8>> {
+        self.modify(|deque| deque.pop_back())
+    }
+
+    pub fn get(&self, index: usize) -> Option<Vec<u8>> {
+        match self {
+            List::Packed(pack) => pack.entries().get(index).map(|v| v.to_vec()),
+            List::Linked(deque) => deque.get(index).cloned(),
+        }
+    }
+
+    pub fn set(&mut self, index: usize, value: Vec<u8>) -> bool {
+        self.modify(|deque| match deque.get_mut(index) {
+            Some(v) => {
+                *v = value;
+                true
+            }
+            None => false,
+        })
+    }
+
+    pub fn insert(&mut self, index: usize, value: Vec<u8>) {
+        self.modify(|deque| deque.insert(index, value));
+    }
+
+    /// 返回 [start, end] 范围内的元素，调用方保证 start <= end < len
+    pub fn range(&self, start: usize, end: usize) -> Vec<Vec<u8>> {
+        self.iter()
+            .skip(start)
+            .take(end + 1 - start)
+            .map(|v| v.to_vec())
+            .collect()
+    }
+
+    /// 只保留 [start, end] 范围内的元素，start > end 时清空
+    pub fn trim(&mut self, start: usize, end: usize) {
+        self.modify(|deque| {
+            if start > end || start >= deque.len() {
+                deque.clear();
+            } else {
+                deque.truncate(end + 1);
+                deque.drain(..start);
+            }
+        });
+    }
+
+    /// 删除和 value 相等的元素，count > 0 从头开始，count < 0 从尾开始，0 表示全部删除
+    pub fn remove(&mut self, value: &[u8], count: i64) -> usize {
+        self.modify(|deque| {
+            let limit = if count == 0 {
+                usize::MAX
+            } else {
+                count.unsigned_abs() as usize
+            };
+            // 一次遍历，remaining 为还可以删除的个数
+            let mut remaining = limit;
+            let mut matched = |v: &Vec<u8>| {
+                let hit = remaining > 0 && v.as_slice() == value;
+                remaining -= hit as usize;
+                hit
+            };
+            if count < 0 {
+                let mut kept = VecDeque::with_capacity(deque.len());
+                while let Some(v) = deque.pop_back() {
+                    if !matched(&v) {
+                        kept.push_front(v);
+                    }
+                }
+                *deque = kept;
+            } else {
+                deque.retain(|v| !matched(v));
+            }
+            limit - remaining
+        })
+    }
+
+    /// 在双端队列上执行修改，之后根据大小重新选择编码
+    fn modify<T>(&mut self, f: impl FnOnce(&mut VecDeque<Vec<u8>>) -> T) -> T {
+        let (mut deque, was_packed) = match std::mem::take(self) {
+            List::Packed(pack) => (
+                pack.entries().into_iter().map(|v| v.to_vec()).collect(),
+                true,
+            ),
+            List::Linked(deque) => (deque, false),
+        };
+        let ret = f(&mut deque);
+
+        // quicklist 缩小到限制的一半以下才转换回 listpack，避免在边界上反复转换。
+        // 先比较元素个数，大的 list 不需要计算总字节数
+        let scale = if was_packed { 1 } else { 2 };
+        let packed = deque.len() * scale <= LIST_MAX_LISTPACK_ENTRIES
+            && fits(
+                deque.iter().map(|v| v.len()).sum::<usize>() * scale,
+                deque.len() * scale,
+            );
+        *self = if packed {
+            List::Packed(deque.iter().map(|v| v.as_slice()).collect())
+        } else {
+            List::Linked(deque)
+        };
+        ret
+    }
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ListIter::Packed(iter) => iter.next(),
+            ListIter::Linked(iter) => iter.next().map(|v| v.as_slice()),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            ListIter::Packed(iter) => iter.size_hint(),
+            ListIter::Linked(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for ListIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            ListIter::Packed(iter) => iter.next_back(),
+            ListIter::Linked(iter) => iter.next_back().map(|v| v.as_slice()),
+        }
+    }
+}
+
+impl ExactSizeIterator for ListIter<'_> {}
+
+impl FromIterator<Vec<u8>> for List {
+    fn from_iter<I: IntoIterator<Item = Vec<u8>>>(iter: I) -> Self {
+        let mut list = List::new();
+        for v in iter {
+            list.push_back(v);
+        }
+        list
+    }
+}
+
+fn fits(bytes: usize, len: usize) -> bool {
+    len <= LIST_MAX_LISTPACK_ENTRIES && bytes <= LIST_MAX_LISTPACK_BYTES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(list: &List) -> Vec<Vec<u8>> {
+        list.iter().map(|v| v.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_list_ops() {
+        let mut list: List = [b"b".to_vec(), b"c".to_vec()].into_iter().collect();
+        list.push_front(b"a".to_vec());
+        list.push_back(b"d".to_vec());
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.encoding(), "listpack");
+        assert_eq!(list.get(2), Some(b"c".to_vec()));
+        assert_eq!(list.range(1, 2), vec![b"b".to_vec(), b"c".to_vec()]);
+
+        assert!(list.set(0, b"x".to_vec()));
+        assert!(!list.set(10, b"x".to_vec()));
+        list.insert(1, b"x".to_vec());
+        assert_eq!(list.remove(b"x", -1), 1);
+        assert_eq!(
+            items(&list),
+            vec![b"x".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+
+        assert_eq!(list.pop_front(), Some(b"x".to_vec()));
+        assert_eq!(list.pop_back(), Some(b"d".to_vec()));
+        list.trim(1, 5);
+        assert_eq!(items(&list), vec![b"c".to_vec()]);
+        list.trim(1, 0);
+        assert!(list.is_empty());
+
+        let mut list: List = ["a", "b", "a", "c", "a"]
+            .iter()
+            .map(|v| v.as_bytes().to_vec())
+            .collect();
+        assert_eq!(list.remove(b"a", 1), 1);
+        assert_eq!(list.remove(b"a", -1), 1);
+        assert_eq!(
+            items(&list),
+            vec![b"b".to_vec(), b"a".to_vec(), b"c".to_vec()]
+        );
+        assert_eq!(list.remove(b"a", 0), 1);
+        assert_eq!(list.remove(b"x", 0), 0);
+        assert_eq!(items(&list), vec![b"b".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    fn test_list_encoding_conversion() {
+        let mut list = List::new();
+        for i in 0..LIST_MAX_LISTPACK_ENTRIES {
+            list.push_back(i.to_string().into_bytes());
+        }
+        assert_eq!(list.encoding(), "listpack");
+        list.push_front(b"head".to_vec());
+        assert_eq!(list.encoding(), "quicklist");
+        assert_eq!(list.get(0), Some(b"head".to_vec()));
+        assert_eq!(list.get(128), Some(b"127".to_vec()));
+
+        // 缩小到一半以下才会转换回 listpack
+        while list.len() > LIST_MAX_LISTPACK_ENTRIES / 2 + 1 {
+            list.pop_back();
+            assert_eq!(list.encoding(), "quicklist");
+        }
+        list.pop_back();
+        assert_eq!(list.encoding(), "listpack");
+        assert_eq!(list.iter().next_back(), Some(&b"62"[..]));
+
+        // 单个很大的元素也会触发转换
+        let mut list = List::new();
+        list.push_back(vec![b'x'; LIST_MAX_LISTPACK_BYTES + 1]);
+        assert_eq!(list.encoding(), "quicklist");
+    }
+}
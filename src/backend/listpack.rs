@@ -0,0 +1,95 @@
+/// 紧凑地存放一组元素 (类似 Redis 的 listpack)，list/hash 元素较少时使用
+///
+/// 每个元素按照 varint(长度) + 数据 的格式依次存放在一块连续内存中
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListPack {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+impl ListPack {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_back(&mut self, value: &[u8]) {
+        write_varint(&mut self.buf, value.len());
+        self.buf.extend_from_slice(value);
+        self.len += 1;
+    }
+
+    /// 所有元素数据的总长度，不包含编码长度的开销
+    pub fn bytes(&self) -> usize {
+        self.entries().iter().map(|v| v.len()).sum()
+    }
+
+    pub fn entries(&self) -> Vec<&[u8]> {
+        let mut entries = Vec::with_capacity(self.len);
+        let mut pos = 0;
+        while pos < self.buf.len() {
+            let (len, n) = read_varint(&self.buf[pos..]);
+            pos += n;
+            entries.push(&self.buf[pos..pos + len]);
+            pos += len;
+        }
+        entries
+    }
+}
+
+impl<'a> FromIterator<&'a [u8]> for ListPack {
+    fn from_iter<I: IntoIterator<Item = &'a [u8]>>(iter: I) -> Self {
+        let mut pack = ListPack::default();
+        for v in iter {
+            pack.push_back(v);
+        }
+        pack
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn read_varint(buf: &[u8]) -> (usize, usize) {
+    let mut n = 0;
+    let mut shift = 0;
+    for (i, b) in buf.iter().enumerate() {
+        n |= ((b & 0x7f) as usize) << shift;
+        if b & 0x80 == 0 {
+            return (n, i + 1);
+        }
+        shift += 7;
+    }
+    (n, buf.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listpack() {
+        let values: [&[u8]; 3] = [b"a", b"", &[b'x'; 300]];
+        let pack: ListPack = values.into_iter().collect();
+        assert_eq!(pack.len(), 3);
+        assert_eq!(pack.entries(), values.to_vec());
+        assert_eq!(pack.bytes(), 301);
+    }
+
+    #[test]
+    fn test_varint() {
+        for n in [0, 1, 127, 128, 300, 16384, usize::MAX >> 1] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, n);
+            assert_eq!(read_varint(&buf), (n, buf.len()));
+        }
+    }
+}
@@ -0,0 +1,686 @@
+mod aof;
+mod blocking;
+mod cluster;
+mod dict;
+mod encoding;
+mod expire;
+mod function;
+mod glob;
+mod hash;
+mod list;
+mod listpack;
+mod persistence;
+mod pubsub;
+mod rax;
+mod rdb;
+mod replication;
+mod script;
+mod set;
+mod stream;
+mod watch;
+mod zset;
+
+use crate::{RespFrame, SimpleError};
+use dashmap::DashMap;
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex, MutexGuard, TryLockError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+pub use aof::{aof_files, spawn_aof_cron, Aof, AofConfig, AofInfo, AofKind, AppendFsync, Manifest};
+pub use blocking::{Blocking, ServeFn, Waiter};
+pub use cluster::{
+    crc16, key_hash_slot, Cluster, ClusterConfig, ClusterNode, NodeEntry, SetSlot, SlotRoute,
+    CLUSTER_NODE_TIMEOUT, CLUSTER_SLOTS,
+};
+pub use dict::Dict;
+pub use expire::{spawn_active_expire, Expires};
+pub use function::{parse_function_dump, FunctionInfo, Functions, Library, RestorePolicy};
+pub use glob::glob_match;
+pub use hash::Hash;
+pub use list::List;
+pub use listpack::ListPack;
+pub use persistence::{spawn_save_scheduler, Persistence, PersistenceError, SaveConfig, SaveRule};
+pub use pubsub::{
+    message_channel, MessageReceiver, MessageSender, PubSub, DEFAULT_PUBSUB_BUFFER_LIMIT,
+};
+pub use rax::Rax;
+pub use rdb::{crc64, decode_listpack, LpValue, RdbError, RdbReader, RdbWriter, RDB_VERSION};
+pub use replication::{
+    AckWait, LinkState, ReplicaFeed, ReplicaStatus, Replication, ReplicationStatus,
+    DEFAULT_REPLICA_BUFFER_LIMIT, REPL_BACKLOG_SIZE,
+};
+pub use script::{sha1_hex, KillError, LuaVm, ScriptCache, ScriptRun, SCRIPT_BUSY_POLL};
+pub use set::Set;
+pub use stream::{
+    AutoClaim, ClaimOptions, Consumer, ConsumerGroup, PendingEntry, Stream, StreamFields, StreamId,
+    TrimStrategy, STREAM_NODE_MAX_ENTRIES,
+};
+pub use watch::Versions;
+pub use zset::{LexBound, LexRange, ScoreRange, ZSet};
+
+/// 所有连接共享的存储，clone 时只增加引用计数
+#[derive(Debug, Clone)]
+pub struct Backend(Arc<BackendInner>);
+
+#[derive(Debug)]
+pub struct BackendInner {
+    // key -> value
+    pub(crate) db: DashMap<Vec<u8>, Value>,
+    // key -> 过期时间 (unix 毫秒)，只记录设置了过期时间的 key
+    pub(crate) expires: Expires,
+    // key -> 最早过期的 field 的过期时间，只记录有 field 设置了过期时间的 hash
+    pub(crate) field_expires: Expires,
+    // 阻塞在 key 上等待数据的客户端
+    pub(crate) blocking: Blocking,
+    // pub/sub 的订阅关系
+    pub(crate) pubsub: PubSub,
+    // 被 WATCH 的 key 的修改版本
+    pub(crate) versions: Versions,
+    // EVAL 加载过的脚本
+    pub(crate) scripts: ScriptCache,
+    // 脚本和函数共用的 Lua 虚拟机
+    pub(crate) lua: LuaVm,
+    // FUNCTION LOAD 加载的 library
+    pub(crate) functions: Functions,
+    // RDB 持久化的配置和状态
+    pub(crate) persistence: Persistence,
+    // AOF 持久化的配置和状态
+    pub(crate) aof: Aof,
+    // 主从复制的状态
+    pub(crate) replication: Replication,
+    // 集群模式下节点和 slot 的状态
+    pub(crate) cluster: Cluster,
+    // Redis 是单线程执行命令的，这里用一把锁保证每条命令的原子性
+    lock: Mutex<()>,
+}
+
+/// key 对应的 value 类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(Vec<u8>),
+    List(List),
+    Hash(Hash),
+    Set(Set),
+    ZSet(ZSet),
+    Stream(Stream),
+}
+
+/// 可以保存在 keyspace 中的集合类型，用于统一处理类型检查以及空集合的删除
+pub trait TypedValue: Default {
+    fn from_value(value: &Value) -> Option<&Self>;
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self>;
+    fn into_value(self) -> Value;
+    fn is_empty(&self) -> bool;
+
+    /// 最早过期的元素的过期时间，只有 hash 的 field 可以单独设置过期时间
+    fn next_field_expire(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BackendError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("NOGROUP {0}")]
+    NoGroup(String),
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+}
+
+impl Deref for Backend {
+    type Target = BackendInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Default for BackendInner {
+    fn default() -> Self {
+        Self {
+            db: DashMap::new(),
+            expires: Expires::default(),
+            field_expires: Expires::default(),
+            blocking: Blocking::default(),
+            pubsub: PubSub::default(),
+            versions: Versions::default(),
+            scripts: ScriptCache::default(),
+            lua: LuaVm::default(),
+            functions: Functions::default(),
+            persistence: Persistence::default(),
+            aof: Aof::default(),
+            replication: Replication::default(),
+            cluster: Cluster::default(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self(Arc::new(BackendInner::default()))
+    }
+}
+
+impl From<BackendError> for RespFrame {
+    fn from(e: BackendError) -> Self {
+        SimpleError::new(e.to_string()).into()
+    }
+}
+
+impl Backend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取执行锁，持有期间其他连接的命令不会被执行
+    pub fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 不等待的获取执行锁，锁被其他连接持有时返回 None
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, ()>> {
+        match self.lock.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// 在异步任务中获取执行锁。脚本可能执行很久，这时不阻塞 tokio 的线程，等待脚本结束
+    pub async fn lock_async(&self) -> MutexGuard<'_, ()> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            if !self.lua.run().is_running() {
+                return self.lock();
+            }
+            tokio::time::sleep(SCRIPT_BUSY_POLL).await;
+        }
+    }
+
+    /// 脚本执行超过这个时间之后，其他连接的命令返回 BUSY
+    pub fn set_lua_time_limit(&self, limit: Duration) {
+        self.lua.run().set_time_limit(limit);
+    }
+
+    /// 获取 key 对应的 value，已经过期的 key 会在这里被删除
+    pub fn get(&self, key: &[u8]) -> Option<Value> {
+        self.expire_if_needed(key);
+        self.db.get(key).map(|v| v.value().clone())
+    }
+
+    /// 获取 string 类型的 value，类型不匹配时返回 WRONGTYPE
+    pub fn get_string(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BackendError> {
+        self.expire_if_needed(key);
+        match self.db.get(key).as_deref() {
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(BackendError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// 只读访问 key 对应的集合，key 不存在时返回 None，类型不匹配时返回 WRONGTYPE
+    pub fn read_value<T: TypedValue, R>(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<Option<R>, BackendError> {
+        self.expire_if_needed(key);
+        match self.db.get(key) {
+            Some(value) => match T::from_value(value.value()) {
+                Some(value) => Ok(Some(f(value))),
+                None => Err(BackendError::WrongType),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// 同时只读访问多个 key 对应的集合，不需要复制集合。key 不存在时对应 None，
+    /// 任何一个 key 类型不匹配都返回 WRONGTYPE
+    pub fn read_values<T: TypedValue, R>(
+        &self,
+        keys: &[Vec<u8>],
+        f: impl FnOnce(Vec<Option<&T>>) -> R,
+    ) -> Result<R, BackendError> {
+        for key in keys {
+            self.expire_if_needed(key);
+        }
+        // 同一个 key 出现多次时只加一次读锁
+        let mut unique: Vec<&[u8]> = Vec::with_capacity(keys.len());
+        let index: Vec<usize> = keys
+            .iter()
+            .map(
+                |key| match unique.iter().position(|k| *k == key.as_slice()) {
+                    Some(i) => i,
+                    None => {
+                        unique.push(key);
+                        unique.len() - 1
+                    }
+                },
+            )
+            .collect();
+        let entries: Vec<_> = unique.iter().map(|key| self.db.get(*key)).collect();
+        let values = entries
+            .iter()
+            .map(|entry| match entry {
+                Some(entry) => T::from_value(entry.value())
+                    .map(Some)
+                    .ok_or(BackendError::WrongType),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(f(index.into_iter().map(|i| values[i]).collect()))
+    }
+
+    /// 修改 key 对应的集合
+    ///
+    /// key 不存在时，create 为 true 则先创建一个空集合，否则返回 None；修改之后集合为空时删除 key。
+    /// f 返回结果以及是否修改了集合，没有修改时不会通知 WATCH 的客户端和阻塞的客户端
+    pub fn write_value<T: TypedValue, R>(
+        &self,
+        key: &[u8],
+        create: bool,
+        f: impl FnOnce(&mut T) -> (R, bool),
+    ) -> Result<Option<R>, BackendError> {
+        self.expire_if_needed(key);
+        let (ret, modified, empty, next_field_expire) = {
+            let mut entry = match self.db.get_mut(key) {
+                Some(entry) => entry,
+                None if create => self
+                    .db
+                    .entry(key.to_vec())
+                    .or_insert_with(|| T::default().into_value()),
+                None => return Ok(None),
+            };
+            let value = T::from_value_mut(entry.value_mut()).ok_or(BackendError::WrongType)?;
+            let (ret, modified) = f(value);
+            (ret, modified, value.is_empty(), value.next_field_expire())
+        };
+        // 需要先释放 entry 的引用再删除，否则 DashMap 会死锁
+        if !modified {
+            // 没有修改时集合为空只可能是刚创建的，key 原本就不存在
+            if empty {
+                self.db.remove(key);
+            }
+        } else if empty {
+            self.remove(key);
+        } else {
+            self.track_field_expire(key, next_field_expire);
+            self.signal_modified_key(key);
+            self.signal_key_ready(key);
+        }
+        Ok(Some(ret))
+    }
+
+    pub fn exists(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.db.contains_key(key)
+    }
+
+    /// 写入 value，同时清除 key 原有的过期时间
+    pub fn set(&self, key: Vec<u8>, value: Value) {
+        self.expires.remove(&key);
+        self.set_keep_ttl(key, value);
+    }
+
+    /// 写入 value，保留 key 原有的过期时间
+    pub fn set_keep_ttl(&self, key: Vec<u8>, value: Value) {
+        let next_field_expire = match &value {
+            Value::Hash(hash) => hash.next_expire(),
+            _ => None,
+        };
+        self.track_field_expire(&key, next_field_expire);
+        self.signal_modified_key(&key);
+        self.db.insert(key, value);
+    }
+
+    /// 获取 key 的过期时间 (unix 毫秒)
+    pub fn get_expire(&self, key: &[u8]) -> Option<u64> {
+        self.expires.get(key)
+    }
+
+    /// 设置 key 的过期时间 (unix 毫秒)，key 不存在时返回 false
+    pub fn set_expire(&self, key: &[u8], at: u64) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+        self.expires.insert(key.to_vec(), at);
+        self.signal_modified_key(key);
+        true
+    }
+
+    /// 清除 key 的过期时间，原本设置了过期时间时返回 true
+    pub fn persist(&self, key: &[u8]) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+        let removed = self.expires.remove(key).is_some();
+        if removed {
+            self.signal_modified_key(key);
+        }
+        removed
+    }
+
+    /// 删除 key 以及它的过期时间
+    pub fn remove(&self, key: &[u8]) -> Option<Value> {
+        self.expires.remove(key);
+        self.track_field_expire(key, None);
+        let value = self.db.remove(key).map(|(_, v)| v);
+        if value.is_some() {
+            self.signal_modified_key(key);
+        }
+        value
+    }
+
+    /// 删除所有的 key，replica 全量同步之前调用
+    pub fn flush_all(&self) {
+        let keys: Vec<Vec<u8>> = self.db.iter().map(|entry| entry.key().clone()).collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    /// 惰性删除：访问 key 时检查是否过期，过期则删除并返回 true
+    ///
+    /// key 没有过期时，同时删除 hash 中已经过期的 field
+    pub fn expire_if_needed(&self, key: &[u8]) -> bool {
+        match self.get_expire(key) {
+            Some(at) if at <= now_ms() => {
+                self.remove(key);
+                true
+            }
+            _ if self.field_expires.is_empty() => false,
+            _ => match self.field_expires.get(key) {
+                Some(at) if at <= now_ms() => {
+                    self.expire_fields(key);
+                    !self.db.contains_key(key)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// 删除 hash 中所有已经过期的 field，返回删除的数量，field 全部过期时删除 key
+    pub fn expire_fields(&self, key: &[u8]) -> usize {
+        let (removed, empty, next_field_expire) = match self.db.get_mut(key) {
+            Some(mut entry) => match entry.value_mut() {
+                Value::Hash(hash) => {
+                    let removed = hash.remove_expired(now_ms());
+                    (removed, hash.is_empty(), hash.next_expire())
+                }
+                _ => (0, false, None),
+            },
+            None => (0, false, None),
+        };
+        if empty {
+            self.remove(key);
+        } else {
+            self.track_field_expire(key, next_field_expire);
+            if removed > 0 {
+                self.signal_modified_key(key);
+            }
+        }
+        removed
+    }
+
+    /// 更新 key 中最早过期的 field 的过期时间，供惰性删除和主动过期使用
+    fn track_field_expire(&self, key: &[u8], at: Option<u64>) {
+        match at {
+            Some(at) => self.field_expires.insert(key.to_vec(), at),
+            // 没有任何 hash 设置 field 的过期时间时不需要加锁
+            None if !self.field_expires.is_empty() => {
+                self.field_expires.remove(key);
+            }
+            None => {}
+        }
+    }
+}
+
+impl TypedValue for List {
+    fn from_value(value: &Value) -> Option<&Self> {
+        match value {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::List(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        List::is_empty(self)
+    }
+}
+
+impl TypedValue for Hash {
+    fn from_value(value: &Value) -> Option<&Self> {
+        match value {
+            Value::Hash(hash) => Some(hash),
+            _ => None,
+        }
+    }
+
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::Hash(hash) => Some(hash),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::Hash(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        Hash::is_empty(self)
+    }
+
+    fn next_field_expire(&self) -> Option<u64> {
+        self.next_expire()
+    }
+}
+
+impl TypedValue for Set {
+    fn from_value(value: &Value) -> Option<&Self> {
+        match value {
+            Value::Set(set) => Some(set),
+            _ => None,
+        }
+    }
+
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::Set(set) => Some(set),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::Set(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        Set::is_empty(self)
+    }
+}
+
+impl TypedValue for ZSet {
+    fn from_value(value: &Value) -> Option<&Self> {
+        match value {
+            Value::ZSet(zset) => Some(zset),
+            _ => None,
+        }
+    }
+
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::ZSet(zset) => Some(zset),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::ZSet(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        ZSet::is_empty(self)
+    }
+}
+
+impl TypedValue for Stream {
+    fn from_value(value: &Value) -> Option<&Self> {
+        match value {
+            Value::Stream(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self> {
+        match value {
+            Value::Stream(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::Stream(self)
+    }
+
+    /// 和 Redis 一样，entry 全部被删除之后 stream 仍然保留，
+    /// 只有从来没有添加过 entry 并且没有消费组 (XGROUP CREATE MKSTREAM) 的 stream 才会被删除
+    fn is_empty(&self) -> bool {
+        self.entries_added() == 0 && self.groups().next().is_none()
+    }
+}
+
+/// 按照 Redis string2ll 的规则解析整数：不允许空格、'+' 号和多余的前导 0
+pub fn parse_i64(buf: &[u8]) -> Option<i64> {
+    let digits = buf.strip_prefix(b"-").unwrap_or(buf);
+    if digits.is_empty() || digits.len() > 19 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    if digits[0] == b'0' && (digits.len() > 1 || digits.len() != buf.len()) {
+        return None;
+    }
+    std::str::from_utf8(buf).ok()?.parse().ok()
+}
+
+/// 当前的 unix 时间 (毫秒)
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_expire() {
+        let backend = Backend::new();
+        backend.set(b"hello".to_vec(), Value::String(b"world".to_vec()));
+        assert!(backend.set_expire(b"hello", now_ms() + 10_000));
+        assert_eq!(backend.get_string(b"hello"), Ok(Some(b"world".to_vec())));
+
+        backend.set_expire(b"hello", now_ms() - 1);
+        assert_eq!(backend.get(b"hello"), None);
+        assert!(backend.expires.is_empty());
+        assert!(!backend.set_expire(b"hello", now_ms() + 10_000));
+    }
+
+    #[test]
+    fn test_typed_value() {
+        let backend = Backend::new();
+        let ret = backend.write_value(b"list", false, |list: &mut List| (list.len(), false));
+        assert_eq!(ret, Ok(None));
+
+        // 创建之后没有修改的空集合不会留下
+        let ret = backend.write_value(b"list", true, |list: &mut List| (list.len(), false));
+        assert_eq!(ret, Ok(Some(0)));
+        assert!(!backend.exists(b"list"));
+
+        let ret = backend.write_value(b"list", true, |list: &mut List| {
+            list.push_back(b"a".to_vec());
+            (list.len(), true)
+        });
+        assert_eq!(ret, Ok(Some(1)));
+        assert_eq!(backend.get_string(b"list"), Err(BackendError::WrongType));
+
+        // 集合为空之后 key 会被删除
+        backend
+            .write_value(b"list", false, |list: &mut List| (list.pop_front(), true))
+            .unwrap();
+        assert!(!backend.exists(b"list"));
+
+        backend.set(b"s".to_vec(), Value::String(b"v".to_vec()));
+        let ret = backend.read_value(b"s", |list: &List| list.len());
+        assert_eq!(ret, Err(BackendError::WrongType));
+
+        // 同时读取多个 key，同一个 key 可以出现多次
+        backend
+            .write_value(b"list", true, |list: &mut List| {
+                list.push_back(b"a".to_vec());
+                ((), true)
+            })
+            .unwrap();
+        let keys = [b"list".to_vec(), b"missing".to_vec(), b"list".to_vec()];
+        let ret = backend.read_values(&keys, |lists: Vec<Option<&List>>| {
+            lists.iter().map(|l| l.map(|l| l.len())).collect::<Vec<_>>()
+        });
+        assert_eq!(ret, Ok(vec![Some(1), None, Some(1)]));
+        let keys = [b"list".to_vec(), b"s".to_vec()];
+        let ret = backend.read_values(&keys, |lists: Vec<Option<&List>>| lists.len());
+        assert_eq!(ret, Err(BackendError::WrongType));
+    }
+
+    #[test]
+    fn test_parse_i64() {
+        assert_eq!(parse_i64(b"0"), Some(0));
+        assert_eq!(parse_i64(b"-10"), Some(-10));
+        assert_eq!(parse_i64(b"9223372036854775807"), Some(i64::MAX));
+        assert_eq!(parse_i64(b"-9223372036854775808"), Some(i64::MIN));
+        for buf in [
+            &b""[..],
+            b"-",
+            b"+1",
+            b" 1",
+            b"1 ",
+            b"01",
+            b"-0",
+            b"1.0",
+            b"9223372036854775808",
+        ] {
+            assert_eq!(parse_i64(buf), None);
+        }
+    }
+
+    #[test]
+    fn test_set_clears_ttl() {
+        let backend = Backend::new();
+        backend.set(b"hello".to_vec(), Value::String(b"world".to_vec()));
+        backend.set_expire(b"hello", now_ms() + 10_000);
+
+        backend.set_keep_ttl(b"hello".to_vec(), Value::String(b"foo".to_vec()));
+        assert!(backend.get_expire(b"hello").is_some());
+
+        backend.set(b"hello".to_vec(), Value::String(b"bar".to_vec()));
+        assert_eq!(backend.get_expire(b"hello"), None);
+    }
+}
@@ -0,0 +1,379 @@
+use super::{now_ms, Backend, RdbError};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+// 检查 save 规则的间隔
+const SAVE_CRON_INTERVAL: Duration = Duration::from_secs(1);
+// BGSAVE 失败之后，按照 save 规则重试之前至少等待的秒数，对应 Redis 的 CONFIG_BGSAVE_RETRY_DELAY
+const BGSAVE_RETRY_DELAY: u64 = 5;
+
+/// RDB 持久化的状态，对应 Redis server 结构中 dirty、lastsave、rdb_child_pid 等字段
+#[derive(Debug)]
+pub struct Persistence {
+    config: Mutex<SaveConfig>,
+    // 上次保存之后的修改次数
+    dirty: AtomicU64,
+    // 上次成功保存的时间 (unix 秒)
+    last_save: AtomicU64,
+    // 上次尝试 BGSAVE 的时间 (unix 秒)
+    last_bgsave_try: AtomicU64,
+    last_bgsave_ok: AtomicBool,
+    bgsave_in_progress: AtomicBool,
+    // BGSAVE SCHEDULE：正在保存时，等这次保存结束之后再执行一次
+    bgsave_scheduled: AtomicBool,
+}
+
+/// 保存的位置以及自动保存的规则
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveConfig {
+    pub dir: PathBuf,
+    pub dbfilename: String,
+    pub rules: Vec<SaveRule>,
+}
+
+/// `save <seconds> <changes>`：距离上次保存超过 seconds 秒并且至少有 changes 次修改时自动 BGSAVE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SaveRule {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum PersistenceError {
+    #[error("Background save already in progress")]
+    InProgress,
+    #[error("Background append only file rewriting already in progress")]
+    AofRewriteInProgress,
+    #[error("{0}")]
+    Aof(String),
+    #[error("MISCONF Errors writing to the AOF file: {0}")]
+    AofWrite(String),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Rdb(#[from] RdbError),
+}
+
+impl Default for SaveConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+            dbfilename: "dump.rdb".to_string(),
+            // Redis 7 的默认规则
+            rules: vec![
+                SaveRule::new(3600, 1),
+                SaveRule::new(300, 100),
+                SaveRule::new(60, 10000),
+            ],
+        }
+    }
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        // 和 Redis 一样启动时间作为上次保存的时间，避免启动之后立即触发保存
+        let now = now_secs();
+        Self {
+            config: Mutex::new(SaveConfig::default()),
+            dirty: AtomicU64::new(0),
+            last_save: AtomicU64::new(now),
+            last_bgsave_try: AtomicU64::new(0),
+            last_bgsave_ok: AtomicBool::new(true),
+            bgsave_in_progress: AtomicBool::new(false),
+            bgsave_scheduled: AtomicBool::new(false),
+        }
+    }
+}
+
+impl SaveRule {
+    pub fn new(seconds: u64, changes: u64) -> Self {
+        Self { seconds, changes }
+    }
+
+    /// 解析 `save` 配置，例如 "3600 1 300 100"，空字符串表示关闭自动保存
+    pub fn parse_rules(s: &str) -> Option<Vec<SaveRule>> {
+        let parts: Vec<u64> = s
+            .split_whitespace()
+            .map(|p| p.parse().ok())
+            .collect::<Option<_>>()?;
+        if !parts.len().is_multiple_of(2) {
+            return None;
+        }
+        Some(parts.chunks(2).map(|p| SaveRule::new(p[0], p[1])).collect())
+    }
+}
+
+impl SaveConfig {
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+}
+
+impl Persistence {
+    fn config(&self) -> MutexGuard<'_, SaveConfig> {
+        self.config.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 保存成功之后更新状态，dirty 减去开始保存时的值，保存期间的修改留到下次保存
+    fn saved(&self, dirty_before: u64) {
+        let _ = self
+            .dirty
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dirty| {
+                Some(dirty.saturating_sub(dirty_before))
+            });
+        self.last_save.store(now_secs(), Ordering::Relaxed);
+    }
+}
+
+impl Backend {
+    pub fn configure_save(&self, config: SaveConfig) {
+        *self.persistence.config() = config;
+    }
+
+    pub fn save_config(&self) -> SaveConfig {
+        self.persistence.config().clone()
+    }
+
+    /// 记录一次修改，save 规则根据修改次数决定是否保存
+    pub fn mark_dirty(&self) {
+        self.persistence.dirty.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 上次保存之后的修改次数
+    pub fn dirty(&self) -> u64 {
+        self.persistence.dirty.load(Ordering::Relaxed)
+    }
+
+    /// 上次成功保存的时间 (unix 秒)
+    pub fn last_save(&self) -> u64 {
+        self.persistence.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.persistence.bgsave_in_progress.load(Ordering::Relaxed)
+    }
+
+    /// SAVE：同步保存，调用方需要持有执行锁
+    pub fn save(&self) -> Result<(), PersistenceError> {
+        if self.bgsave_in_progress() {
+            return Err(PersistenceError::InProgress);
+        }
+        let dirty = self.dirty();
+        let data = self.rdb_snapshot();
+        write_rdb_file(&self.save_config(), &data)?;
+        self.persistence.saved(dirty);
+        info!("DB saved on disk");
+        Ok(())
+    }
+
+    /// BGSAVE：在执行锁内生成快照，在后台线程中写入文件，调用方需要持有执行锁
+    ///
+    /// Redis 通过 fork 得到数据集的快照，这里直接序列化，写文件和 fsync 不占用执行锁
+    pub fn bgsave(&self) -> Result<(), PersistenceError> {
+        let p = &self.persistence;
+        if p.bgsave_in_progress.swap(true, Ordering::AcqRel) {
+            return Err(PersistenceError::InProgress);
+        }
+        p.bgsave_scheduled.store(false, Ordering::Relaxed);
+        p.last_bgsave_try.store(now_secs(), Ordering::Relaxed);
+        let dirty = self.dirty();
+        let data = self.rdb_snapshot();
+        let config = self.save_config();
+        let backend = self.clone();
+        info!("Background saving started");
+        thread::spawn(move || {
+            let p = &backend.persistence;
+            match write_rdb_file(&config, &data) {
+                Ok(()) => {
+                    p.saved(dirty);
+                    p.last_bgsave_ok.store(true, Ordering::Relaxed);
+                    info!("Background saving terminated with success");
+                }
+                Err(e) => {
+                    p.last_bgsave_ok.store(false, Ordering::Relaxed);
+                    warn!("Background saving error: {}", e);
+                }
+            }
+            p.bgsave_in_progress.store(false, Ordering::Release);
+        });
+        Ok(())
+    }
+
+    /// BGSAVE SCHEDULE：正在保存时等这次保存结束之后再执行，返回是否需要等待
+    pub fn schedule_bgsave(&self) -> Result<bool, PersistenceError> {
+        if self.bgsave_in_progress() {
+            self.persistence
+                .bgsave_scheduled
+                .store(true, Ordering::Relaxed);
+            return Ok(true);
+        }
+        self.bgsave().map(|_| false)
+    }
+
+    /// 启动时加载 RDB 文件，返回其中 function library 的代码，文件不存在时返回 None。
+    /// 加载的 key 不计入修改次数
+    pub fn load_rdb_file(&self) -> Result<Option<Vec<String>>, PersistenceError> {
+        let data = match fs::read(self.save_config().path()) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let functions = self.load_rdb(&data)?;
+        self.clear_dirty();
+        Ok(Some(functions))
+    }
+
+    /// 启动时加载的数据不计入修改次数
+    pub(crate) fn clear_dirty(&self) {
+        self.persistence.dirty.store(0, Ordering::Relaxed);
+    }
+
+    /// 是否需要按照 save 规则或者 BGSAVE SCHEDULE 开始 BGSAVE，对应 Redis serverCron 中的检查
+    fn should_bgsave(&self) -> bool {
+        let p = &self.persistence;
+        if self.bgsave_in_progress() {
+            return false;
+        }
+        if p.bgsave_scheduled.load(Ordering::Relaxed) {
+            return true;
+        }
+        let now = now_secs();
+        let dirty = self.dirty();
+        let since_save = now.saturating_sub(self.last_save());
+        // 上次失败的话，等待一段时间再重试
+        let can_retry = p.last_bgsave_ok.load(Ordering::Relaxed)
+            || now.saturating_sub(p.last_bgsave_try.load(Ordering::Relaxed)) > BGSAVE_RETRY_DELAY;
+        can_retry
+            && p.config()
+                .rules
+                .iter()
+                .any(|rule| dirty >= rule.changes && since_save > rule.seconds)
+    }
+}
+
+/// 先写入临时文件并 fsync，再重命名为目标文件，保证目标文件总是完整的
+fn write_rdb_file(config: &SaveConfig, data: &[u8]) -> io::Result<()> {
+    let tmp = config
+        .dir
+        .join(format!("temp-{}-{}.rdb", std::process::id(), now_ms()));
+    let ret = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, config.path())
+    })();
+    if ret.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    ret
+}
+
+fn now_secs() -> u64 {
+    now_ms() / 1000
+}
+
+/// 定期检查 save 规则，满足时开始 BGSAVE
+pub fn spawn_save_scheduler(backend: Backend) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAVE_CRON_INTERVAL);
+        loop {
+            interval.tick().await;
+            let _guard = backend.lock_async().await;
+            if backend.should_bgsave() {
+                if let Err(e) = backend.bgsave() {
+                    warn!("failed to start background saving: {}", e);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("simple-redis-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn wait_bgsave(backend: &Backend) {
+        while backend.bgsave_in_progress() {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_parse_save_rules() {
+        assert_eq!(
+            SaveRule::parse_rules("3600 1 300 100"),
+            Some(vec![SaveRule::new(3600, 1), SaveRule::new(300, 100)])
+        );
+        assert_eq!(SaveRule::parse_rules(""), Some(vec![]));
+        assert_eq!(SaveRule::parse_rules("3600"), None);
+        assert_eq!(SaveRule::parse_rules("a 1"), None);
+    }
+
+    #[test]
+    fn test_save_and_bgsave() {
+        let dir = temp_dir("save");
+        let backend = Backend::new();
+        backend.configure_save(SaveConfig {
+            dir: dir.clone(),
+            dbfilename: "test.rdb".to_string(),
+            rules: vec![SaveRule::new(0, 2)],
+        });
+        assert_eq!(backend.load_rdb_file().unwrap(), None);
+
+        backend.set(b"a".to_vec(), Value::String(b"1".to_vec()));
+        assert_eq!(backend.dirty(), 1);
+        assert!(!backend.should_bgsave());
+        backend.save().unwrap();
+        assert_eq!(backend.dirty(), 0);
+        let loaded = Backend::new();
+        loaded.configure_save(backend.save_config());
+        assert_eq!(loaded.load_rdb_file().unwrap(), Some(vec![]));
+        assert_eq!(loaded.get(b"a"), Some(Value::String(b"1".to_vec())));
+        assert_eq!(loaded.dirty(), 0);
+
+        backend.set(b"b".to_vec(), Value::String(b"2".to_vec()));
+        backend.remove(b"a");
+        assert!(!backend.should_bgsave());
+        backend.persistence.last_save.store(0, Ordering::Relaxed);
+        assert!(backend.should_bgsave());
+        backend.bgsave().unwrap();
+        wait_bgsave(&backend);
+        assert_eq!(backend.dirty(), 0);
+        let loaded = Backend::new();
+        loaded
+            .load_rdb(&fs::read(dir.join("test.rdb")).unwrap())
+            .unwrap();
+        assert_eq!(loaded.get(b"a"), None);
+        assert_eq!(loaded.get(b"b"), Some(Value::String(b"2".to_vec())));
+
+        // 保存失败时不会清除 dirty
+        backend.configure_save(SaveConfig {
+            dir: dir.join("missing"),
+            ..backend.save_config()
+        });
+        backend.mark_dirty();
+        assert!(backend.save().is_err());
+        assert_eq!(backend.dirty(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,403 @@
+use super::{glob_match, Backend};
+use crate::{BulkString, RespFrame, RespPush};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+use tokio::sync::{mpsc, Notify};
+
+/// 订阅者输出缓冲区的默认上限，对应 Redis `client-output-buffer-limit pubsub` 的 hard limit
+pub const DEFAULT_PUBSUB_BUFFER_LIMIT: usize = 32 * 1024 * 1024;
+
+/// 订阅者接收消息的队列，消息统一使用 RESP3 的 push frame，由网络层按照协议版本转换
+///
+/// 队列本身不限长度，但是记录还没有推送给客户端的消息的字节数，
+/// 客户端读得太慢、积压超过上限时不再写入，接收方随后断开连接
+#[derive(Debug, Clone)]
+pub struct MessageSender {
+    tx: mpsc::UnboundedSender<(RespFrame, usize)>,
+    buffer: Arc<OutputBuffer>,
+}
+
+/// 连接持有的接收端，见 [`MessageSender`]
+#[derive(Debug)]
+pub struct MessageReceiver {
+    rx: mpsc::UnboundedReceiver<(RespFrame, usize)>,
+    buffer: Arc<OutputBuffer>,
+}
+
+#[derive(Debug)]
+struct OutputBuffer {
+    // 已经写入队列、还没有被连接取走的消息的字节数
+    pending: AtomicUsize,
+    // 0 表示不限制
+    limit: usize,
+    overflowed: AtomicBool,
+    // 溢出时通知连接，连接可能正阻塞在向客户端写数据上
+    overflow: Notify,
+}
+
+/// 创建订阅者的消息队列，limit 是输出缓冲区的上限 (字节)，0 表示不限制
+pub fn message_channel(limit: usize) -> (MessageSender, MessageReceiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let buffer = Arc::new(OutputBuffer {
+        pending: AtomicUsize::new(0),
+        limit,
+        overflowed: AtomicBool::new(false),
+        overflow: Notify::new(),
+    });
+    let sender = MessageSender {
+        tx,
+        buffer: buffer.clone(),
+    };
+    (sender, MessageReceiver { rx, buffer })
+}
+
+impl MessageSender {
+    /// 写入一条消息，size 是消息内容的字节数。超过上限的那条消息仍然写入，用来唤醒接收方，
+    /// 之后的消息直接丢弃
+    fn send(&self, frame: RespFrame, size: usize) {
+        let buffer = &self.buffer;
+        if buffer.overflowed.load(Ordering::Relaxed) {
+            return;
+        }
+        let pending = buffer.pending.fetch_add(size, Ordering::Relaxed) + size;
+        if buffer.limit > 0 && pending > buffer.limit {
+            buffer.overflowed.store(true, Ordering::Relaxed);
+            buffer.overflow.notify_one();
+        }
+        // 连接已经断开但还没有来得及取消订阅，忽略即可
+        let _ = self.tx.send((frame, size));
+    }
+}
+
+impl MessageReceiver {
+    /// 等待下一条消息，输出缓冲区超过上限时返回 None，调用方需要断开连接
+    pub async fn recv(&mut self) -> Option<RespFrame> {
+        let (frame, size) = self.rx.recv().await?;
+        if self.buffer.overflowed.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.buffer.pending.fetch_sub(size, Ordering::Relaxed);
+        Some(frame)
+    }
+
+    /// 输出缓冲区超过上限时返回
+    pub async fn overflowed(&self) {
+        while !self.buffer.overflowed.load(Ordering::Relaxed) {
+            self.buffer.overflow.notified().await;
+        }
+    }
+}
+
+/// pub/sub 的订阅关系，对应 Redis 的 pubsub_channels/pubsub_patterns/pubsub_shard_channels 字典
+///
+/// shard channel 在集群中按照 hash slot 路由到不同的节点，单机时所有的 slot 都在本节点，
+/// 和普通的 channel 只是互相独立的两个命名空间
+///
+/// 只记录 channel (pattern) 到订阅者的映射，每个连接订阅了哪些 channel 由连接自己维护
+#[derive(Debug)]
+pub struct PubSub {
+    inner: Mutex<PubSubInner>,
+    // 新订阅者的输出缓冲区上限
+    buffer_limit: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct PubSubInner {
+    // channel -> 客户端 id -> 消息队列
+    channels: HashMap<Vec<u8>, HashMap<u64, MessageSender>>,
+    // pattern -> 客户端 id -> 消息队列
+    patterns: HashMap<Vec<u8>, HashMap<u64, MessageSender>>,
+    // shard channel -> 客户端 id -> 消息队列
+    shard_channels: HashMap<Vec<u8>, HashMap<u64, MessageSender>>,
+}
+
+impl Default for PubSub {
+    fn default() -> Self {
+        PubSub {
+            inner: Mutex::default(),
+            buffer_limit: AtomicUsize::new(DEFAULT_PUBSUB_BUFFER_LIMIT),
+        }
+    }
+}
+
+impl PubSub {
+    /// 创建订阅者的消息队列，使用当前配置的输出缓冲区上限
+    pub fn channel(&self) -> (MessageSender, MessageReceiver) {
+        message_channel(self.buffer_limit.load(Ordering::Relaxed))
+    }
+
+    /// 订阅 channel，已经订阅过时返回 false
+    pub fn subscribe(&self, channel: Vec<u8>, id: u64, tx: MessageSender) -> bool {
+        add(&mut self.inner().channels, channel, id, tx)
+    }
+
+    /// 取消订阅 channel，没有订阅过时返回 false
+    pub fn unsubscribe(&self, channel: &[u8], id: u64) -> bool {
+        remove(&mut self.inner().channels, channel, id)
+    }
+
+    pub fn psubscribe(&self, pattern: Vec<u8>, id: u64, tx: MessageSender) -> bool {
+        add(&mut self.inner().patterns, pattern, id, tx)
+    }
+
+    pub fn punsubscribe(&self, pattern: &[u8], id: u64) -> bool {
+        remove(&mut self.inner().patterns, pattern, id)
+    }
+
+    pub fn ssubscribe(&self, channel: Vec<u8>, id: u64, tx: MessageSender) -> bool {
+        add(&mut self.inner().shard_channels, channel, id, tx)
+    }
+
+    pub fn sunsubscribe(&self, channel: &[u8], id: u64) -> bool {
+        remove(&mut self.inner().shard_channels, channel, id)
+    }
+
+    /// 向 channel 以及匹配 channel 的 pattern 的订阅者发送消息，返回收到消息的订阅者数量
+    ///
+    /// 同一个客户端通过多个 pattern 匹配到时会收到多条消息，和 Redis 一样分别计数
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        let inner = self.inner();
+        let mut receivers = 0;
+        if let Some(subscribers) = inner.channels.get(channel) {
+            for tx in subscribers.values() {
+                let frame = RespPush::new([
+                    BulkString::new("message").into(),
+                    BulkString::new(channel).into(),
+                    BulkString::new(message).into(),
+                ]);
+                tx.send(frame.into(), channel.len() + message.len());
+                receivers += 1;
+            }
+        }
+        for (pattern, subscribers) in inner.patterns.iter() {
+            if !glob_match(pattern, channel, false) {
+                continue;
+            }
+            for tx in subscribers.values() {
+                let frame = RespPush::new([
+                    BulkString::new("pmessage").into(),
+                    BulkString::new(pattern.as_slice()).into(),
+                    BulkString::new(channel).into(),
+                    BulkString::new(message).into(),
+                ]);
+                tx.send(frame.into(), pattern.len() + channel.len() + message.len());
+                receivers += 1;
+            }
+        }
+        receivers
+    }
+
+    /// 向 shard channel 的订阅者发送消息，shard channel 不支持 pattern 订阅
+    pub fn spublish(&self, channel: &[u8], message: &[u8]) -> usize {
+        let inner = self.inner();
+        let Some(subscribers) = inner.shard_channels.get(channel) else {
+            return 0;
+        };
+        for tx in subscribers.values() {
+            let frame = RespPush::new([
+                BulkString::new("smessage").into(),
+                BulkString::new(channel).into(),
+                BulkString::new(message).into(),
+            ]);
+            tx.send(frame.into(), channel.len() + message.len());
+        }
+        subscribers.len()
+    }
+
+    /// 有订阅者的 channel，pattern 不为 None 时只返回匹配的 channel
+    pub fn channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        matched(&self.inner().channels, pattern)
+    }
+
+    /// channel 的订阅者数量，不包括通过 pattern 订阅的
+    pub fn numsub(&self, channel: &[u8]) -> usize {
+        self.inner().channels.get(channel).map_or(0, |s| s.len())
+    }
+
+    /// 有订阅者的 shard channel，pattern 不为 None 时只返回匹配的 shard channel
+    pub fn shard_channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        matched(&self.inner().shard_channels, pattern)
+    }
+
+    pub fn shard_numsub(&self, channel: &[u8]) -> usize {
+        self.inner()
+            .shard_channels
+            .get(channel)
+            .map_or(0, |s| s.len())
+    }
+
+    /// 被订阅的 pattern 的数量
+    pub fn numpat(&self) -> usize {
+        self.inner().patterns.len()
+    }
+
+    fn inner(&self) -> MutexGuard<'_, PubSubInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn matched(
+    table: &HashMap<Vec<u8>, HashMap<u64, MessageSender>>,
+    pattern: Option<&[u8]>,
+) -> Vec<Vec<u8>> {
+    let mut channels: Vec<Vec<u8>> = table
+        .keys()
+        .filter(|channel| pattern.is_none_or(|p| glob_match(p, channel, false)))
+        .cloned()
+        .collect();
+    channels.sort();
+    channels
+}
+
+fn add(
+    table: &mut HashMap<Vec<u8>, HashMap<u64, MessageSender>>,
+    channel: Vec<u8>,
+    id: u64,
+    tx: MessageSender,
+) -> bool {
+    table.entry(channel).or_default().insert(id, tx).is_none()
+}
+
+fn remove(
+    table: &mut HashMap<Vec<u8>, HashMap<u64, MessageSender>>,
+    channel: &[u8],
+    id: u64,
+) -> bool {
+    let Some(subscribers) = table.get_mut(channel) else {
+        return false;
+    };
+    let removed = subscribers.remove(&id).is_some();
+    // 没有订阅者的 channel 不再出现在 PUBSUB CHANNELS 中
+    if subscribers.is_empty() {
+        table.remove(channel);
+    }
+    removed
+}
+
+impl Backend {
+    /// 订阅者输出缓冲区的上限 (字节)，0 表示不限制，只影响之后第一次订阅的连接
+    pub fn set_pubsub_buffer_limit(&self, limit: usize) {
+        self.pubsub.buffer_limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// 发布消息，返回收到消息的订阅者数量
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        self.pubsub.publish(channel, message)
+    }
+
+    /// 发布 shard channel 的消息，返回收到消息的订阅者数量
+    pub fn spublish(&self, channel: &[u8], message: &[u8]) -> usize {
+        self.pubsub.spublish(channel, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pubsub() {
+        let pubsub = PubSub::default();
+        let (tx1, mut rx1) = pubsub.channel();
+        let (tx2, mut rx2) = pubsub.channel();
+        assert!(pubsub.subscribe(b"news".to_vec(), 1, tx1.clone()));
+        assert!(!pubsub.subscribe(b"news".to_vec(), 1, tx1.clone()));
+        assert!(pubsub.psubscribe(b"n*".to_vec(), 1, tx1));
+        assert!(pubsub.subscribe(b"news".to_vec(), 2, tx2.clone()));
+        assert!(pubsub.subscribe(b"sports".to_vec(), 2, tx2));
+
+        assert_eq!(pubsub.publish(b"news", b"hi"), 3);
+        assert_eq!(
+            rx1.rx.try_recv().unwrap().0,
+            RespPush::new([b"message".into(), b"news".into(), b"hi".into()]).into()
+        );
+        assert_eq!(
+            rx1.rx.try_recv().unwrap().0,
+            RespPush::new([
+                b"pmessage".into(),
+                b"n*".into(),
+                b"news".into(),
+                b"hi".into()
+            ])
+            .into()
+        );
+        assert!(rx2.rx.try_recv().is_ok());
+        assert_eq!(pubsub.publish(b"weather", b"sunny"), 0);
+
+        assert_eq!(
+            pubsub.channels(None),
+            vec![b"news".to_vec(), b"sports".to_vec()]
+        );
+        assert_eq!(pubsub.channels(Some(b"s*")), vec![b"sports".to_vec()]);
+        assert_eq!(pubsub.numsub(b"news"), 2);
+        assert_eq!(pubsub.numpat(), 1);
+
+        assert!(pubsub.unsubscribe(b"sports", 2));
+        assert!(!pubsub.unsubscribe(b"sports", 2));
+        assert!(pubsub.punsubscribe(b"n*", 1));
+        assert_eq!(pubsub.channels(None), vec![b"news".to_vec()]);
+        assert_eq!(pubsub.numpat(), 0);
+        assert_eq!(pubsub.publish(b"news", b"bye"), 2);
+        assert!(rx2.rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_shard_pubsub() {
+        let pubsub = PubSub::default();
+        let (tx, mut rx) = pubsub.channel();
+        assert!(pubsub.ssubscribe(b"orders".to_vec(), 1, tx.clone()));
+        assert!(pubsub.psubscribe(b"*".to_vec(), 1, tx));
+
+        // shard channel 和普通的 channel 互不影响
+        assert_eq!(pubsub.spublish(b"orders", b"1"), 1);
+        assert_eq!(
+            rx.rx.try_recv().unwrap().0,
+            RespPush::new([b"smessage".into(), b"orders".into(), b"1".into()]).into()
+        );
+        assert!(rx.rx.try_recv().is_err());
+        assert_eq!(pubsub.publish(b"orders", b"2"), 1);
+        assert_eq!(pubsub.spublish(b"other", b"3"), 0);
+
+        assert_eq!(pubsub.shard_channels(None), vec![b"orders".to_vec()]);
+        assert!(pubsub.channels(None).is_empty());
+        assert_eq!(pubsub.shard_numsub(b"orders"), 1);
+        assert_eq!(pubsub.numsub(b"orders"), 0);
+        assert!(pubsub.sunsubscribe(b"orders", 1));
+        assert!(pubsub.shard_channels(None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_output_buffer_limit() {
+        let pubsub = PubSub::default();
+        pubsub.buffer_limit.store(10, Ordering::Relaxed);
+        let (tx, mut rx) = pubsub.channel();
+        pubsub.subscribe(b"news".to_vec(), 1, tx);
+
+        // 消息被取走之后不再占用缓冲区
+        for _ in 0..3 {
+            assert_eq!(pubsub.publish(b"news", b"hi"), 1);
+            assert!(rx.recv().await.is_some());
+        }
+        // 积压超过上限之后连接需要断开，之后的消息被丢弃
+        pubsub.publish(b"news", b"hi");
+        pubsub.publish(b"news", b"hi");
+        assert_eq!(rx.recv().await, None);
+        pubsub.publish(b"news", b"hi");
+        assert_eq!(rx.rx.len(), 1);
+
+        // 0 表示不限制
+        let (tx, mut rx) = message_channel(0);
+        pubsub.subscribe(b"news".to_vec(), 2, tx);
+        for _ in 0..10 {
+            pubsub.publish(b"news", b"hi");
+        }
+        for _ in 0..10 {
+            assert!(rx.recv().await.is_some());
+        }
+    }
+}
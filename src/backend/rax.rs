@@ -0,0 +1,370 @@
+use std::ops::Bound;
+
+/// 压缩前缀树 (radix tree)，对应 Redis 的 rax，按照 key 的字节序有序遍历
+///
+/// 每个节点保存从父节点到自己的一段边 (prefix)，只有一个子节点并且没有 value 的节点
+/// 会和子节点合并，因此公共前缀只保存一次。stream 的 ID 是 16 字节的大端整数，
+/// 同一毫秒内的 ID 共享前面的大部分字节
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rax<V> {
+    root: Node<V>,
+    len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node<V> {
+    prefix: Vec<u8>,
+    value: Option<V>,
+    // 按照 prefix 的第一个字节排序
+    children: Vec<Node<V>>,
+}
+
+impl<V> Default for Rax<V> {
+    fn default() -> Self {
+        Self {
+            root: Node::new(Vec::new(), None),
+            len: 0,
+        }
+    }
+}
+
+impl<V> Rax<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 节点的数量，包括根节点和没有 value 的中间节点
+    pub fn node_count(&self) -> usize {
+        self.root.count()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        let mut node = &self.root;
+        let mut key = key;
+        loop {
+            if key.is_empty() {
+                return node.value.as_ref();
+            }
+            let child = &node.children[node.find(key[0]).ok()?];
+            key = key.strip_prefix(child.prefix.as_slice())?;
+            node = child;
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        let mut key = key;
+        loop {
+            if key.is_empty() {
+                return node.value.as_mut();
+            }
+            let pos = node.find(key[0]).ok()?;
+            let child = &mut node.children[pos];
+            key = key.strip_prefix(child.prefix.as_slice())?;
+            node = child;
+        }
+    }
+
+    /// 插入 key，返回 key 原有的 value
+    pub fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let old = self.root.insert(key, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// 删除 key，返回 key 原有的 value
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let old = self.root.remove(key);
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    /// 字节序最小的 key
+    pub fn first(&self) -> Option<(Vec<u8>, &V)> {
+        let mut ret = None;
+        self.range(Bound::Unbounded, Bound::Unbounded, false, |key, value| {
+            ret = Some((key.to_vec(), value));
+            false
+        });
+        ret
+    }
+
+    /// 字节序最大的 key
+    pub fn last(&self) -> Option<(Vec<u8>, &V)> {
+        let mut ret = None;
+        self.range(Bound::Unbounded, Bound::Unbounded, true, |key, value| {
+            ret = Some((key.to_vec(), value));
+            false
+        });
+        ret
+    }
+
+    /// 按照字节序 (rev 为 true 时逆序) 遍历 [start, end] 范围内的 key，visit 返回 false 时停止
+    pub fn range<'a>(
+        &'a self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        rev: bool,
+        mut visit: impl FnMut(&[u8], &'a V) -> bool,
+    ) {
+        let mut path = Vec::new();
+        let range = (start, end);
+        self.root.walk(&mut path, &range, rev, &mut visit);
+    }
+}
+
+impl<V> Node<V> {
+    fn new(prefix: Vec<u8>, value: Option<V>) -> Self {
+        Self {
+            prefix,
+            value,
+            children: Vec::new(),
+        }
+    }
+
+    fn count(&self) -> usize {
+        1 + self.children.iter().map(Node::count).sum::<usize>()
+    }
+
+    fn find(&self, byte: u8) -> Result<usize, usize> {
+        self.children
+            .binary_search_by_key(&byte, |child| child.prefix[0])
+    }
+
+    /// key 是去掉了当前节点 prefix 之后剩下的部分
+    fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        if key.is_empty() {
+            return self.value.replace(value);
+        }
+        let pos = match self.find(key[0]) {
+            Ok(pos) => pos,
+            Err(pos) => {
+                self.children
+                    .insert(pos, Node::new(key.to_vec(), Some(value)));
+                return None;
+            }
+        };
+        let child = &mut self.children[pos];
+        let common = child
+            .prefix
+            .iter()
+            .zip(key)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if common < child.prefix.len() {
+            // 拆分边：公共部分成为新的中间节点，原来的子节点挂在它下面
+            let rest = child.prefix.split_off(common);
+            let mut split = Node::new(std::mem::take(&mut child.prefix), None);
+            let mut old = std::mem::replace(child, Node::new(Vec::new(), None));
+            old.prefix = rest;
+            split.children.push(old);
+            *child = split;
+        }
+        child.insert(&key[common..], value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        if key.is_empty() {
+            return self.value.take();
+        }
+        let pos = self.find(key[0]).ok()?;
+        let child = &mut self.children[pos];
+        let rest = key.strip_prefix(child.prefix.as_slice())?;
+        let old = child.remove(rest)?;
+        if child.value.is_none() {
+            match child.children.len() {
+                0 => {
+                    self.children.remove(pos);
+                }
+                // 只剩一个子节点时和子节点合并
+                1 => {
+                    let grandchild = child.children.pop().expect("child has one child");
+                    child.prefix.extend(grandchild.prefix);
+                    child.value = grandchild.value;
+                    child.children = grandchild.children;
+                }
+                _ => {}
+            }
+        }
+        Some(old)
+    }
+
+    /// 深度优先遍历，path 是从根节点到当前节点 (包含当前节点的 prefix) 的完整 key，返回 false 表示停止
+    fn walk<'a>(
+        &'a self,
+        path: &mut Vec<u8>,
+        range: &(Bound<&[u8]>, Bound<&[u8]>),
+        rev: bool,
+        visit: &mut impl FnMut(&[u8], &'a V) -> bool,
+    ) -> bool {
+        // 子树中所有的 key 都以 path 开头，整棵子树都在范围外时跳过
+        if before_start(path, range.0) || after_end(path, range.1) {
+            return true;
+        }
+        if !rev && !self.visit_value(path, range, visit) {
+            return false;
+        }
+        let children: Box<dyn Iterator<Item = &Node<V>>> = match rev {
+            true => Box::new(self.children.iter().rev()),
+            false => Box::new(self.children.iter()),
+        };
+        for child in children {
+            let len = path.len();
+            path.extend_from_slice(&child.prefix);
+            let more = child.walk(path, range, rev, visit);
+            path.truncate(len);
+            if !more {
+                return false;
+            }
+        }
+        !rev || self.visit_value(path, range, visit)
+    }
+
+    fn visit_value<'a>(
+        &'a self,
+        path: &[u8],
+        range: &(Bound<&[u8]>, Bound<&[u8]>),
+        visit: &mut impl FnMut(&[u8], &'a V) -> bool,
+    ) -> bool {
+        match &self.value {
+            Some(value) if range_contains(range, path) => visit(path, value),
+            _ => true,
+        }
+    }
+}
+
+/// 以 prefix 开头的 key 是否都小于 start
+fn before_start(prefix: &[u8], start: Bound<&[u8]>) -> bool {
+    match start {
+        Bound::Included(start) | Bound::Excluded(start) => {
+            !start.starts_with(prefix) && prefix < start
+        }
+        Bound::Unbounded => false,
+    }
+}
+
+/// 以 prefix 开头的 key 是否都大于 end
+fn after_end(prefix: &[u8], end: Bound<&[u8]>) -> bool {
+    match end {
+        Bound::Included(end) => prefix > end,
+        Bound::Excluded(end) => prefix >= end,
+        Bound::Unbounded => false,
+    }
+}
+
+fn range_contains(range: &(Bound<&[u8]>, Bound<&[u8]>), key: &[u8]) -> bool {
+    let after_start = match range.0 {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true,
+    };
+    let before_end = match range.1 {
+        Bound::Included(end) => key <= end,
+        Bound::Excluded(end) => key < end,
+        Bound::Unbounded => true,
+    };
+    after_start && before_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(rax: &Rax<usize>, start: Bound<&[u8]>, end: Bound<&[u8]>, rev: bool) -> Vec<String> {
+        let mut ret = Vec::new();
+        rax.range(start, end, rev, |key, _| {
+            ret.push(String::from_utf8_lossy(key).to_string());
+            true
+        });
+        ret
+    }
+
+    #[test]
+    fn test_rax_insert_remove() {
+        let mut rax = Rax::new();
+        for (i, key) in ["romane", "romanus", "romulus", "rubens", "ruber", "rom", ""]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(rax.insert(key.as_bytes(), i), None);
+        }
+        assert_eq!(rax.len(), 7);
+        assert_eq!(rax.insert(b"rom", 10), Some(5));
+        assert_eq!(rax.get(b"rom"), Some(&10));
+        assert_eq!(rax.get(b"ro"), None);
+        assert_eq!(rax.get(b""), Some(&6));
+        *rax.get_mut(b"ruber").unwrap() += 1;
+        assert_eq!(rax.get(b"ruber"), Some(&5));
+
+        assert_eq!(rax.remove(b"roman"), None);
+        assert_eq!(rax.remove(b"romane"), Some(0));
+        assert_eq!(rax.remove(b"rom"), Some(10));
+        assert_eq!(rax.get(b"romanus"), Some(&1));
+        assert_eq!(rax.len(), 5);
+        // root (保存 "" 的 value) -> r -> om -> anus/ulus，r -> ub -> ens/er
+        assert_eq!(rax.node_count(), 8);
+
+        let all = ["", "romanus", "romulus", "rubens", "ruber"];
+        let unbounded = Bound::Unbounded;
+        assert_eq!(keys(&rax, unbounded, unbounded, false), all);
+        let rev: Vec<_> = all.iter().rev().copied().collect();
+        assert_eq!(keys(&rax, unbounded, unbounded, true), rev);
+
+        for key in all {
+            assert!(rax.remove(key.as_bytes()).is_some());
+        }
+        assert!(rax.is_empty());
+        assert_eq!(rax.root, Node::new(Vec::new(), None));
+    }
+
+    #[test]
+    fn test_rax_range() {
+        let mut rax = Rax::new();
+        for i in 0..200usize {
+            rax.insert(&(i as u64 * 7).to_be_bytes(), i);
+        }
+        let key = |i: u64| (i * 7).to_be_bytes();
+        let (k10, k20) = (key(10), key(20));
+        let mut ret = Vec::new();
+        rax.range(
+            Bound::Included(&k10),
+            Bound::Excluded(&k20),
+            false,
+            |_, v| {
+                ret.push(*v);
+                true
+            },
+        );
+        assert_eq!(ret, (10..20).collect::<Vec<_>>());
+
+        let mut ret = Vec::new();
+        rax.range(Bound::Excluded(&k10), Bound::Unbounded, true, |_, v| {
+            ret.push(*v);
+            ret.len() < 3
+        });
+        assert_eq!(ret, vec![199, 198, 197]);
+
+        // 边界不存在时从下一个 key 开始
+        let start = 71u64.to_be_bytes();
+        let mut ret = Vec::new();
+        rax.range(Bound::Included(&start), Bound::Unbounded, false, |_, v| {
+            ret.push(*v);
+            false
+        });
+        assert_eq!(ret, vec![11]);
+        assert_eq!(rax.first().map(|(_, v)| *v), Some(0));
+        assert_eq!(rax.last().map(|(_, v)| *v), Some(199));
+    }
+}
@@ -0,0 +1,1492 @@
+use super::{
+    encoding::{decode_intset, decode_ziplist, decode_zipmap, lzf_decompress},
+    now_ms, Backend, Hash, List, PendingEntry, Set, Stream, StreamFields, StreamId, Value, ZSet,
+    STREAM_NODE_MAX_ENTRIES,
+};
+use std::{collections::HashMap, ops::Bound};
+use thiserror::Error;
+use tracing::warn;
+
+/// 生成的 RDB 格式的版本，对应 Redis 7.4
+pub const RDB_VERSION: u16 = 12;
+
+// value 的类型，保存时只使用不依赖内部编码的类型，加载时支持 Redis 所有版本的编码
+const RDB_TYPE_STRING: u8 = 0;
+const RDB_TYPE_LIST: u8 = 1;
+const RDB_TYPE_SET: u8 = 2;
+const RDB_TYPE_ZSET: u8 = 3;
+const RDB_TYPE_HASH: u8 = 4;
+const RDB_TYPE_ZSET_2: u8 = 5;
+const RDB_TYPE_MODULE_PRE_GA: u8 = 6;
+const RDB_TYPE_MODULE_2: u8 = 7;
+const RDB_TYPE_HASH_ZIPMAP: u8 = 9;
+const RDB_TYPE_LIST_ZIPLIST: u8 = 10;
+const RDB_TYPE_SET_INTSET: u8 = 11;
+const RDB_TYPE_ZSET_ZIPLIST: u8 = 12;
+const RDB_TYPE_HASH_ZIPLIST: u8 = 13;
+const RDB_TYPE_LIST_QUICKLIST: u8 = 14;
+const RDB_TYPE_STREAM_LISTPACKS: u8 = 15;
+const RDB_TYPE_HASH_LISTPACK: u8 = 16;
+const RDB_TYPE_ZSET_LISTPACK: u8 = 17;
+const RDB_TYPE_LIST_QUICKLIST_2: u8 = 18;
+const RDB_TYPE_STREAM_LISTPACKS_2: u8 = 19;
+const RDB_TYPE_SET_LISTPACK: u8 = 20;
+const RDB_TYPE_STREAM_LISTPACKS_3: u8 = 21;
+const RDB_TYPE_HASH_METADATA_PRE_GA: u8 = 22;
+const RDB_TYPE_HASH_LISTPACK_EX_PRE_GA: u8 = 23;
+const RDB_TYPE_HASH_METADATA: u8 = 24;
+const RDB_TYPE_HASH_LISTPACK_EX: u8 = 25;
+
+const RDB_OPCODE_SLOT_INFO: u8 = 244;
+/// function library，内容是 library 的代码
+pub const RDB_OPCODE_FUNCTION2: u8 = 245;
+const RDB_OPCODE_FUNCTION_PRE_GA: u8 = 246;
+const RDB_OPCODE_MODULE_AUX: u8 = 247;
+const RDB_OPCODE_IDLE: u8 = 248;
+const RDB_OPCODE_FREQ: u8 = 249;
+const RDB_OPCODE_AUX: u8 = 250;
+const RDB_OPCODE_RESIZEDB: u8 = 251;
+const RDB_OPCODE_EXPIRETIME_MS: u8 = 252;
+const RDB_OPCODE_EXPIRETIME: u8 = 253;
+const RDB_OPCODE_SELECTDB: u8 = 254;
+const RDB_OPCODE_EOF: u8 = 255;
+
+// 长度的最高两位是 11 时，低 6 位表示字符串的特殊编码
+const RDB_ENC_INT8: u8 = 0;
+const RDB_ENC_INT16: u8 = 1;
+const RDB_ENC_INT32: u8 = 2;
+const RDB_ENC_LZF: u8 = 3;
+
+// module 数据中每个值之前的类型，用于跳过无法加载的 module 数据
+const RDB_MODULE_OPCODE_EOF: u64 = 0;
+const RDB_MODULE_OPCODE_SINT: u64 = 1;
+const RDB_MODULE_OPCODE_UINT: u64 = 2;
+const RDB_MODULE_OPCODE_FLOAT: u64 = 3;
+const RDB_MODULE_OPCODE_DOUBLE: u64 = 4;
+const RDB_MODULE_OPCODE_STRING: u64 = 5;
+
+// quicklist 节点的类型
+const QUICKLIST_NODE_CONTAINER_PLAIN: u64 = 1;
+const QUICKLIST_NODE_CONTAINER_PACKED: u64 = 2;
+
+// stream listpack 中 entry 的 flag
+const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RdbError {
+    #[error("unexpected end of rdb data")]
+    Eof,
+    #[error("invalid rdb data: {0}")]
+    Invalid(String),
+}
+
+/// 按照 RDB 的格式写入数据
+#[derive(Debug, Default)]
+pub struct RdbWriter {
+    buf: Vec<u8>,
+}
+
+impl RdbWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// 长度编码：高两位 00 表示 6 位长度，01 表示 14 位长度，10000000/10000001 之后是 32/64 位大端长度
+    pub fn write_len(&mut self, len: u64) {
+        if len < 1 << 6 {
+            self.write_u8(len as u8);
+        } else if len < 1 << 14 {
+            self.write_u8(0x40 | (len >> 8) as u8);
+            self.write_u8(len as u8);
+        } else if len <= u32::MAX as u64 {
+            self.write_u8(0x80);
+            self.write_bytes(&(len as u32).to_be_bytes());
+        } else {
+            self.write_u8(0x81);
+            self.write_bytes(&len.to_be_bytes());
+        }
+    }
+
+    pub fn write_string(&mut self, data: &[u8]) {
+        self.write_len(data.len() as u64);
+        self.write_bytes(data);
+    }
+
+    /// 8 字节小端的毫秒时间戳
+    pub fn write_ms(&mut self, ms: i64) {
+        self.write_bytes(&ms.to_le_bytes());
+    }
+
+    /// DUMP 的 payload：内容之后是 2 字节的 RDB 版本和 8 字节的 CRC64，都是小端
+    pub fn into_payload(mut self) -> Vec<u8> {
+        self.write_bytes(&RDB_VERSION.to_le_bytes());
+        let crc = crc64(0, &self.buf);
+        self.write_bytes(&crc.to_le_bytes());
+        self.buf
+    }
+
+    /// RDB 文件：内容之后是 EOF 和整个文件的 CRC64
+    pub fn into_file(mut self) -> Vec<u8> {
+        self.write_u8(RDB_OPCODE_EOF);
+        let crc = crc64(0, &self.buf);
+        self.write_bytes(&crc.to_le_bytes());
+        self.buf
+    }
+}
+
+/// 读取 RDB 格式的数据
+#[derive(Debug)]
+pub struct RdbReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RdbReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// 检查 DUMP 的 payload 的版本和 CRC64，返回去掉尾部之后的内容
+    pub fn from_payload(payload: &'a [u8]) -> Result<Self, RdbError> {
+        let invalid = || RdbError::Invalid("payload version or checksum are wrong".to_string());
+        if payload.len() < 10 {
+            return Err(invalid());
+        }
+        let (body, crc) = payload.split_at(payload.len() - 8);
+        let version = u16::from_le_bytes([body[body.len() - 2], body[body.len() - 1]]);
+        if version > RDB_VERSION || crc64(0, body) != u64::from_le_bytes(crc.try_into().unwrap()) {
+            return Err(invalid());
+        }
+        Ok(Self::new(&body[..body.len() - 2]))
+    }
+
+    /// 检查 RDB 文件头 `REDIS0012` 以及结尾的 CRC64 (为 0 时表示保存时没有计算校验和)，
+    /// 返回文件的版本和从文件头之后开始读取的 reader
+    pub fn from_file(data: &'a [u8]) -> Result<(u16, Self), RdbError> {
+        let header = data.get(..9).ok_or(RdbError::Eof)?;
+        if &header[..5] != b"REDIS" {
+            return Err(RdbError::Invalid("wrong signature".to_string()));
+        }
+        let version = std::str::from_utf8(&header[5..])
+            .ok()
+            .and_then(|v| v.parse::<u16>().ok())
+            .filter(|v| (1..=RDB_VERSION).contains(v))
+            .ok_or_else(|| RdbError::Invalid("unsupported rdb version".to_string()))?;
+        if version >= 5 && data.len() >= 18 {
+            let (body, crc) = data.split_at(data.len() - 8);
+            let expected = u64::from_le_bytes(crc.try_into().unwrap());
+            if expected != 0 && crc64(0, body) != expected {
+                return Err(RdbError::Invalid("wrong checksum".to_string()));
+            }
+        }
+        Ok((version, Self { buf: data, pos: 9 }))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, RdbError> {
+        let v = *self.buf.get(self.pos).ok_or(RdbError::Eof)?;
+        self.pos += 1;
+        Ok(v)
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], RdbError> {
+        let end = self.pos.checked_add(len).ok_or(RdbError::Eof)?;
+        let data = self.buf.get(self.pos..end).ok_or(RdbError::Eof)?;
+        self.pos = end;
+        Ok(data)
+    }
+
+    pub fn read_len(&mut self) -> Result<u64, RdbError> {
+        let first = self.read_u8()?;
+        match first >> 6 {
+            0 => Ok((first & 0x3f) as u64),
+            1 => Ok((((first & 0x3f) as u64) << 8) | self.read_u8()? as u64),
+            _ => match first {
+                0x80 => Ok(u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()) as u64),
+                0x81 => Ok(u64::from_be_bytes(self.read_bytes(8)?.try_into().unwrap())),
+                _ => Err(RdbError::Invalid(format!(
+                    "unknown length encoding {:#x}",
+                    first
+                ))),
+            },
+        }
+    }
+
+    /// 读取字符串，支持整数编码和 LZF 压缩的字符串
+    pub fn read_string(&mut self) -> Result<Vec<u8>, RdbError> {
+        let first = *self.buf.get(self.pos).ok_or(RdbError::Eof)?;
+        if first >> 6 != 3 {
+            let len = self.read_len()?;
+            return Ok(self.read_bytes(len as usize)?.to_vec());
+        }
+        self.pos += 1;
+        let n = match first & 0x3f {
+            RDB_ENC_INT8 => self.read_u8()? as i8 as i64,
+            RDB_ENC_INT16 => i16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()) as i64,
+            RDB_ENC_INT32 => i32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()) as i64,
+            RDB_ENC_LZF => {
+                let compressed_len = self.read_len()?;
+                let len = self.read_len()?;
+                return lzf_decompress(self.read_bytes(compressed_len as usize)?, len as usize);
+            }
+            enc => {
+                return Err(RdbError::Invalid(format!(
+                    "unknown string encoding {}",
+                    enc
+                )))
+            }
+        };
+        Ok(n.to_string().into_bytes())
+    }
+
+    pub fn read_ms(&mut self) -> Result<i64, RdbError> {
+        Ok(i64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    fn read_utf8(&mut self) -> Result<String, RdbError> {
+        String::from_utf8(self.read_string()?).map_err(|e| RdbError::Invalid(e.to_string()))
+    }
+
+    fn read_stream_id(&mut self) -> Result<StreamId, RdbError> {
+        Ok(StreamId::new(self.read_len()?, self.read_len()?))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, RdbError> {
+        Ok(f64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    /// RDB_TYPE_ZSET 中字符串形式的分数，长度 253/254/255 分别表示 nan、inf 和 -inf
+    fn read_double_string(&mut self) -> Result<f64, RdbError> {
+        match self.read_u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => parse_score(self.read_bytes(len as usize)?),
+        }
+    }
+
+    /// 跳过无法加载的 module 数据：一系列 (类型, 值)，直到 EOF
+    fn skip_module_value(&mut self) -> Result<(), RdbError> {
+        loop {
+            match self.read_len()? {
+                RDB_MODULE_OPCODE_EOF => return Ok(()),
+                RDB_MODULE_OPCODE_SINT | RDB_MODULE_OPCODE_UINT => {
+                    self.read_len()?;
+                }
+                RDB_MODULE_OPCODE_FLOAT => {
+                    self.read_bytes(4)?;
+                }
+                RDB_MODULE_OPCODE_DOUBLE => {
+                    self.read_bytes(8)?;
+                }
+                RDB_MODULE_OPCODE_STRING => {
+                    self.read_string()?;
+                }
+                opcode => {
+                    return Err(RdbError::Invalid(format!(
+                        "unknown module opcode {}",
+                        opcode
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl Backend {
+    /// 整个数据集的 RDB 文件内容，调用方需要持有执行锁以保证快照的一致性
+    ///
+    /// 已经过期但还没有被删除的 key 也会保存，加载时跳过
+    pub fn rdb_snapshot(&self) -> Vec<u8> {
+        let mut w = RdbWriter::new();
+        w.write_bytes(format!("REDIS{:04}", RDB_VERSION).as_bytes());
+        for (key, value) in [
+            ("redis-ver", "7.4.0".to_string()),
+            ("redis-bits", "64".to_string()),
+            ("ctime", (now_ms() / 1000).to_string()),
+            ("used-mem", "0".to_string()),
+        ] {
+            w.write_u8(RDB_OPCODE_AUX);
+            w.write_string(key.as_bytes());
+            w.write_string(value.as_bytes());
+        }
+        for library in self.functions.libraries() {
+            w.write_u8(RDB_OPCODE_FUNCTION2);
+            w.write_string(library.code.as_bytes());
+        }
+
+        w.write_u8(RDB_OPCODE_SELECTDB);
+        w.write_len(0);
+        w.write_u8(RDB_OPCODE_RESIZEDB);
+        w.write_len(self.db.len() as u64);
+        w.write_len(self.expires.len() as u64);
+        for entry in self.db.iter() {
+            if let Some(at) = self.expires.get(entry.key()) {
+                w.write_u8(RDB_OPCODE_EXPIRETIME_MS);
+                w.write_ms(at as i64);
+            }
+            write_value(&mut w, entry.key(), entry.value());
+        }
+        w.into_file()
+    }
+
+    /// 加载 RDB 文件中的 key，返回其中 function library 的代码，由调用方编译之后加载
+    ///
+    /// 支持 Redis 各个版本生成的 RDB 文件。只有一个数据库，包含其他数据库的 RDB 文件加载失败，
+    /// 不会把不同数据库中的 key 合并在一起；已经过期的 key 不加载；LRU/LFU 信息和 module 数据被跳过
+    pub fn load_rdb(&self, data: &[u8]) -> Result<Vec<String>, RdbError> {
+        let (_, mut r) = RdbReader::from_file(data)?;
+        let now = now_ms();
+        let mut functions = Vec::new();
+        let mut expire = None;
+        loop {
+            match r.read_u8()? {
+                RDB_OPCODE_EOF => break,
+                RDB_OPCODE_AUX => {
+                    r.read_string()?;
+                    r.read_string()?;
+                }
+                RDB_OPCODE_FUNCTION2 => functions.push(r.read_utf8()?),
+                RDB_OPCODE_FUNCTION_PRE_GA => {
+                    return Err(RdbError::Invalid(
+                        "pre-release function format is not supported".to_string(),
+                    ))
+                }
+                RDB_OPCODE_MODULE_AUX => {
+                    let module_id = r.read_len()?;
+                    // when_opcode 和 when
+                    r.read_len()?;
+                    r.read_len()?;
+                    r.skip_module_value()?;
+                    warn!("skipped aux data of module {:#x}", module_id);
+                }
+                RDB_OPCODE_SELECTDB => {
+                    let db = r.read_len()?;
+                    if db != 0 {
+                        return Err(RdbError::Invalid(format!(
+                            "db {} is not supported, only db 0 can be loaded",
+                            db
+                        )));
+                    }
+                }
+                RDB_OPCODE_SLOT_INFO => {
+                    // slot、slot 中 key 的数量、slot 中设置了过期时间的 key 的数量
+                    for _ in 0..3 {
+                        r.read_len()?;
+                    }
+                }
+                RDB_OPCODE_IDLE => {
+                    r.read_len()?;
+                }
+                RDB_OPCODE_FREQ => {
+                    r.read_u8()?;
+                }
+                RDB_OPCODE_RESIZEDB => {
+                    r.read_len()?;
+                    r.read_len()?;
+                }
+                RDB_OPCODE_EXPIRETIME_MS => expire = Some(r.read_ms()?.max(0) as u64),
+                RDB_OPCODE_EXPIRETIME => {
+                    let secs = u32::from_le_bytes(r.read_bytes(4)?.try_into().unwrap());
+                    expire = Some(secs as u64 * 1000);
+                }
+                ty => {
+                    let key = r.read_string()?;
+                    let value = read_value(&mut r, ty)?;
+                    let at = expire.take();
+                    let Some(value) = value else {
+                        warn!(
+                            "skipped module value of key {}",
+                            String::from_utf8_lossy(&key)
+                        );
+                        continue;
+                    };
+                    // hash 的 field 可能全部过期了，和 Redis 一样也跳过空的集合
+                    if is_empty(&value) || at.is_some_and(|at| at <= now) {
+                        continue;
+                    }
+                    self.set(key.clone(), value);
+                    if let Some(at) = at {
+                        self.expires.insert(key, at);
+                    }
+                }
+            }
+        }
+        Ok(functions)
+    }
+}
+
+fn write_value(w: &mut RdbWriter, key: &[u8], value: &Value) {
+    let ty = match value {
+        Value::String(_) => RDB_TYPE_STRING,
+        Value::List(_) => RDB_TYPE_LIST,
+        Value::Set(_) => RDB_TYPE_SET,
+        Value::ZSet(_) => RDB_TYPE_ZSET_2,
+        Value::Hash(hash) if hash.next_expire().is_some() => RDB_TYPE_HASH_METADATA,
+        Value::Hash(_) => RDB_TYPE_HASH,
+        Value::Stream(_) => RDB_TYPE_STREAM_LISTPACKS_3,
+    };
+    w.write_u8(ty);
+    w.write_string(key);
+    match value {
+        Value::String(s) => w.write_string(s),
+        Value::List(list) => {
+            w.write_len(list.len() as u64);
+            for item in list.iter() {
+                w.write_string(item);
+            }
+        }
+        Value::Set(set) => {
+            let members = set.members();
+            w.write_len(members.len() as u64);
+            for member in members {
+                w.write_string(&member);
+            }
+        }
+        Value::ZSet(zset) => {
+            let entries = zset.entries();
+            w.write_len(entries.len() as u64);
+            // 和 Redis 一样从分数最大的开始保存
+            for (member, score) in entries.iter().rev() {
+                w.write_string(member);
+                w.write_bytes(&score.to_le_bytes());
+            }
+        }
+        Value::Hash(hash) => write_hash(w, hash),
+        Value::Stream(stream) => write_stream(w, stream),
+    }
+}
+
+/// 有 field 设置了过期时间时使用 Redis 7.4 的 HASH_METADATA 格式：先保存最早的过期时间，
+/// 每个 field 之前保存相对于它的过期时间加一，0 表示没有过期时间
+fn write_hash(w: &mut RdbWriter, hash: &Hash) {
+    let entries = hash.entries();
+    let min = hash.next_expire();
+    if let Some(min) = min {
+        w.write_ms(min as i64);
+    }
+    w.write_len(entries.len() as u64);
+    for (field, value) in entries {
+        if let Some(min) = min {
+            w.write_len(hash.get_expire(field).map_or(0, |at| at - min + 1));
+        }
+        w.write_string(field);
+        w.write_string(value);
+    }
+}
+
+/// entry 按照 STREAM_NODE_MAX_ENTRIES 分成多个 listpack 节点保存，节点的 key 是第一个 entry 的
+/// ID (master ID)，节点中的 ID 保存为相对于它的差值
+fn write_stream(w: &mut RdbWriter, stream: &Stream) {
+    let entries = stream.range(Bound::Unbounded, Bound::Unbounded, false, None);
+    w.write_len(entries.chunks(STREAM_NODE_MAX_ENTRIES).len() as u64);
+    for node in entries.chunks(STREAM_NODE_MAX_ENTRIES) {
+        w.write_string(&node[0].0.to_bytes());
+        w.write_string(&stream_node(node));
+    }
+    w.write_len(stream.len() as u64);
+    let first_id = stream.first_entry().map_or(StreamId::MIN, |(id, _)| id);
+    for id in [stream.last_id(), first_id, stream.max_deleted_id()] {
+        w.write_len(id.ms);
+        w.write_len(id.seq);
+    }
+    w.write_len(stream.entries_added());
+
+    let groups: Vec<_> = stream.groups().collect();
+    w.write_len(groups.len() as u64);
+    for (name, group) in groups {
+        w.write_string(name.as_bytes());
+        w.write_len(group.last_id().ms);
+        w.write_len(group.last_id().seq);
+        // 和 Redis 一样无法确定时保存 -1
+        w.write_len(group.entries_read().unwrap_or(u64::MAX));
+
+        w.write_len(group.pending_len() as u64);
+        group.pending_range(Bound::Unbounded, Bound::Unbounded, None, |id, entry| {
+            w.write_bytes(&id.to_bytes());
+            w.write_ms(entry.delivery_time as i64);
+            w.write_len(entry.delivery_count);
+            true
+        });
+        let consumers: Vec<_> = group.consumers().collect();
+        w.write_len(consumers.len() as u64);
+        for (name, consumer) in consumers {
+            w.write_string(name.as_bytes());
+            w.write_ms(consumer.seen_time as i64);
+            w.write_ms(consumer.active_time.map_or(-1, |t| t as i64));
+            w.write_len(consumer.pending_len() as u64);
+            group.pending_range(Bound::Unbounded, Bound::Unbounded, Some(name), |id, _| {
+                w.write_bytes(&id.to_bytes());
+                true
+            });
+        }
+    }
+}
+
+/// 一个 stream 节点的 listpack：master entry (count, deleted, master field 数量, master field..., 0)
+/// 之后依次是每个 entry，field 和 master entry 相同的 entry 只保存 value
+fn stream_node(node: &[(StreamId, StreamFields)]) -> Vec<u8> {
+    let (master_id, master_fields) = &node[0];
+    let mut lp = LpWriter::default();
+    lp.push_int(node.len() as i64);
+    lp.push_int(0);
+    lp.push_int(master_fields.len() as i64);
+    for (field, _) in master_fields {
+        lp.push_str(field);
+    }
+    lp.push_int(0);
+    for (id, fields) in node {
+        let same = fields.len() == master_fields.len()
+            && fields.iter().zip(master_fields).all(|(a, b)| a.0 == b.0);
+        lp.push_int(if same { STREAM_ITEM_FLAG_SAMEFIELDS } else { 0 });
+        lp.push_int(id.ms.wrapping_sub(master_id.ms) as i64);
+        lp.push_int(id.seq.wrapping_sub(master_id.seq) as i64);
+        if same {
+            for (_, value) in fields {
+                lp.push_str(value);
+            }
+            // lp-count：这个 entry 占用的元素数量，用于从后往前遍历
+            lp.push_int(fields.len() as i64 + 3);
+        } else {
+            lp.push_int(fields.len() as i64);
+            for (field, value) in fields {
+                lp.push_str(field);
+                lp.push_str(value);
+            }
+            lp.push_int(fields.len() as i64 * 2 + 4);
+        }
+    }
+    lp.finish()
+}
+
+/// 读取一个 value，module 的 value 无法加载，返回 None
+fn read_value(r: &mut RdbReader, ty: u8) -> Result<Option<Value>, RdbError> {
+    let value = match ty {
+        RDB_TYPE_STRING => Value::String(r.read_string()?),
+        RDB_TYPE_LIST => {
+            let mut list = List::new();
+            for _ in 0..r.read_len()? {
+                list.push_back(r.read_string()?);
+            }
+            Value::List(list)
+        }
+        RDB_TYPE_SET => {
+            let mut set = Set::new();
+            for _ in 0..r.read_len()? {
+                set.insert(r.read_string()?);
+            }
+            Value::Set(set)
+        }
+        RDB_TYPE_ZSET | RDB_TYPE_ZSET_2 => {
+            let mut zset = ZSet::new();
+            for _ in 0..r.read_len()? {
+                let member = r.read_string()?;
+                let score = match ty {
+                    RDB_TYPE_ZSET => r.read_double_string()?,
+                    _ => r.read_f64()?,
+                };
+                zset.insert(member, score);
+            }
+            Value::ZSet(zset)
+        }
+        RDB_TYPE_HASH => {
+            let mut hash = Hash::new();
+            for _ in 0..r.read_len()? {
+                let field = r.read_string()?;
+                hash.set(field, r.read_string()?);
+            }
+            Value::Hash(hash)
+        }
+        RDB_TYPE_HASH_METADATA | RDB_TYPE_HASH_METADATA_PRE_GA => {
+            // 正式版本中 field 的过期时间是相对于最早的过期时间保存的
+            let min = match ty {
+                RDB_TYPE_HASH_METADATA => Some(r.read_ms()?.max(0) as u64),
+                _ => None,
+            };
+            let mut fields = Vec::new();
+            for _ in 0..r.read_len()? {
+                let ttl = r.read_len()?;
+                let at = match (ttl, min) {
+                    (0, _) => None,
+                    (ttl, Some(min)) => Some(min + ttl - 1),
+                    (ttl, None) => Some(ttl),
+                };
+                let field = r.read_string()?;
+                fields.push((field, r.read_string()?, at));
+            }
+            Value::Hash(hash_with_ttls(fields))
+        }
+        RDB_TYPE_MODULE_2 => {
+            r.read_len()?;
+            r.skip_module_value()?;
+            return Ok(None);
+        }
+        RDB_TYPE_MODULE_PRE_GA => {
+            return Err(RdbError::Invalid(
+                "module value without RDB_TYPE_MODULE_2 can not be skipped".to_string(),
+            ))
+        }
+        RDB_TYPE_HASH_ZIPMAP => {
+            Value::Hash(decode_zipmap(&r.read_string()?)?.into_iter().collect())
+        }
+        RDB_TYPE_LIST_ZIPLIST => Value::List(
+            decode_ziplist(&r.read_string()?)?
+                .into_iter()
+                .map(LpValue::into_bytes)
+                .collect(),
+        ),
+        RDB_TYPE_SET_INTSET => Value::Set(
+            decode_intset(&r.read_string()?)?
+                .into_iter()
+                .map(|n| n.to_string().into_bytes())
+                .collect(),
+        ),
+        RDB_TYPE_SET_LISTPACK => Value::Set(
+            decode_listpack(&r.read_string()?)?
+                .into_iter()
+                .map(LpValue::into_bytes)
+                .collect(),
+        ),
+        RDB_TYPE_ZSET_ZIPLIST | RDB_TYPE_ZSET_LISTPACK => {
+            let blob = r.read_string()?;
+            let items = match ty {
+                RDB_TYPE_ZSET_ZIPLIST => decode_ziplist(&blob)?,
+                _ => decode_listpack(&blob)?,
+            };
+            let mut zset = ZSet::new();
+            for pair in items.chunks(2) {
+                let [member, score] = pair else {
+                    return Err(RdbError::Invalid("odd number of zset elements".to_string()));
+                };
+                let score = match score {
+                    LpValue::Int(n) => *n as f64,
+                    LpValue::Str(s) => parse_score(s)?,
+                };
+                zset.insert(member.clone().into_bytes(), score);
+            }
+            Value::ZSet(zset)
+        }
+        RDB_TYPE_HASH_ZIPLIST | RDB_TYPE_HASH_LISTPACK => {
+            let blob = r.read_string()?;
+            let items = match ty {
+                RDB_TYPE_HASH_ZIPLIST => decode_ziplist(&blob)?,
+                _ => decode_listpack(&blob)?,
+            };
+            let mut hash = Hash::new();
+            for pair in items.chunks(2) {
+                let [field, value] = pair else {
+                    return Err(RdbError::Invalid("odd number of hash elements".to_string()));
+                };
+                hash.set(field.clone().into_bytes(), value.clone().into_bytes());
+            }
+            Value::Hash(hash)
+        }
+        RDB_TYPE_HASH_LISTPACK_EX | RDB_TYPE_HASH_LISTPACK_EX_PRE_GA => {
+            // 最早的过期时间只是用于加载时快速判断，listpack 中的过期时间是绝对时间，0 表示没有
+            if ty == RDB_TYPE_HASH_LISTPACK_EX {
+                r.read_ms()?;
+            }
+            let items = decode_listpack(&r.read_string()?)?;
+            let mut fields = Vec::new();
+            for triple in items.chunks(3) {
+                let [field, value, ttl] = triple else {
+                    return Err(RdbError::Invalid(
+                        "invalid hash listpack with ttl".to_string(),
+                    ));
+                };
+                let at = Some(ttl.to_int()? as u64).filter(|at| *at > 0);
+                fields.push((field.clone().into_bytes(), value.clone().into_bytes(), at));
+            }
+            Value::Hash(hash_with_ttls(fields))
+        }
+        RDB_TYPE_LIST_QUICKLIST | RDB_TYPE_LIST_QUICKLIST_2 => {
+            let mut list = List::new();
+            for _ in 0..r.read_len()? {
+                let container = match ty {
+                    RDB_TYPE_LIST_QUICKLIST_2 => r.read_len()?,
+                    _ => QUICKLIST_NODE_CONTAINER_PACKED,
+                };
+                let blob = r.read_string()?;
+                let items = match (ty, container) {
+                    // 较大的元素单独保存为一个节点
+                    (_, QUICKLIST_NODE_CONTAINER_PLAIN) => {
+                        list.push_back(blob);
+                        continue;
+                    }
+                    (RDB_TYPE_LIST_QUICKLIST, _) => decode_ziplist(&blob)?,
+                    _ => decode_listpack(&blob)?,
+                };
+                for item in items {
+                    list.push_back(item.into_bytes());
+                }
+            }
+            Value::List(list)
+        }
+        RDB_TYPE_STREAM_LISTPACKS | RDB_TYPE_STREAM_LISTPACKS_2 | RDB_TYPE_STREAM_LISTPACKS_3 => {
+            Value::Stream(read_stream(r, ty)?)
+        }
+        ty => return Err(RdbError::Invalid(format!("unknown value type {}", ty))),
+    };
+    Ok(Some(value))
+}
+
+/// 创建 field 带有过期时间的 hash，已经过期的 field 不加载
+fn hash_with_ttls(fields: Vec<(Vec<u8>, Vec<u8>, Option<u64>)>) -> Hash {
+    let now = now_ms();
+    let mut hash = Hash::new();
+    for (field, value, at) in fields {
+        if at.is_some_and(|at| at <= now) {
+            continue;
+        }
+        if let Some(at) = at {
+            hash.set(field.clone(), value);
+            hash.set_expire(&field, at);
+        } else {
+            hash.set(field, value);
+        }
+    }
+    hash
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::String(_) => false,
+        Value::List(list) => list.is_empty(),
+        Value::Hash(hash) => hash.is_empty(),
+        Value::Set(set) => set.is_empty(),
+        Value::ZSet(zset) => zset.is_empty(),
+        // 空的 stream 也是有效的 key
+        Value::Stream(_) => false,
+    }
+}
+
+fn parse_score(s: &[u8]) -> Result<f64, RdbError> {
+    std::str::from_utf8(s)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| RdbError::Invalid("invalid zset score".to_string()))
+}
+
+/// 读取 stream，RDB_TYPE_STREAM_LISTPACKS_2 增加了 ID 相关的元数据和消费组的 entries_read，
+/// RDB_TYPE_STREAM_LISTPACKS_3 增加了消费者的 active_time
+fn read_stream(r: &mut RdbReader, ty: u8) -> Result<Stream, RdbError> {
+    let mut stream = Stream::new();
+    for _ in 0..r.read_len()? {
+        let master = r.read_string()?;
+        if master.len() != 16 {
+            return Err(RdbError::Invalid(
+                "stream node key is not a stream id".to_string(),
+            ));
+        }
+        let lp = r.read_string()?;
+        read_stream_node(&mut stream, StreamId::from_bytes(&master), &lp)?;
+    }
+    let len = r.read_len()?;
+    let last_id = r.read_stream_id()?;
+    if ty == RDB_TYPE_STREAM_LISTPACKS {
+        // 旧版本没有记录被删除的 entry，和 Redis 一样认为没有删除过
+        stream.restore_meta(last_id, StreamId::MIN, len);
+    } else {
+        let _first_id = r.read_stream_id()?;
+        let max_deleted_id = r.read_stream_id()?;
+        let entries_added = r.read_len()?;
+        stream.restore_meta(last_id, max_deleted_id, entries_added);
+    }
+
+    for _ in 0..r.read_len()? {
+        let name = r.read_utf8()?;
+        let last_id = r.read_stream_id()?;
+        let entries_read = match ty {
+            RDB_TYPE_STREAM_LISTPACKS => None,
+            _ => Some(r.read_len()?).filter(|n| *n != u64::MAX),
+        };
+        if !stream.create_group(&name, last_id, entries_read) {
+            return Err(RdbError::Invalid(format!(
+                "duplicated consumer group {}",
+                name
+            )));
+        }
+        let group = stream.group_mut(&name).expect("group just created");
+
+        // 全局 PEL 中没有记录消费者，读到消费者的 PEL 时再关联起来
+        let mut pending = HashMap::new();
+        for _ in 0..r.read_len()? {
+            let id = StreamId::from_bytes(r.read_bytes(16)?);
+            let delivery_time = r.read_ms()?.max(0) as u64;
+            pending.insert(id, (delivery_time, r.read_len()?));
+        }
+        for _ in 0..r.read_len()? {
+            let consumer = r.read_utf8()?;
+            let seen_time = r.read_ms()?.max(0) as u64;
+            // 旧版本没有 active_time，和 Redis 一样使用 seen_time
+            let active_time = match ty {
+                RDB_TYPE_STREAM_LISTPACKS_3 => r.read_ms()?,
+                _ => seen_time as i64,
+            };
+            group.seen_consumer(&consumer, seen_time).active_time =
+                (active_time >= 0).then_some(active_time as u64);
+            for _ in 0..r.read_len()? {
+                let id = StreamId::from_bytes(r.read_bytes(16)?);
+                let (delivery_time, delivery_count) = pending.remove(&id).ok_or_else(|| {
+                    RdbError::Invalid("consumer pending entry not found in group PEL".to_string())
+                })?;
+                let entry = PendingEntry {
+                    consumer: consumer.clone(),
+                    delivery_time,
+                    delivery_count,
+                };
+                group.restore_pending(id, entry);
+            }
+        }
+    }
+    Ok(stream)
+}
+
+fn read_stream_node(stream: &mut Stream, master_id: StreamId, lp: &[u8]) -> Result<(), RdbError> {
+    let mut items = decode_listpack(lp)?.into_iter();
+    let mut next = || items.next().ok_or(RdbError::Eof);
+    let count = next()?.to_int()?;
+    let deleted = next()?.to_int()?;
+    let mut master_fields = Vec::new();
+    for _ in 0..next()?.to_int()? {
+        master_fields.push(next()?.into_bytes());
+    }
+    // master entry 结尾的 0
+    next()?;
+
+    for _ in 0..count + deleted {
+        let flags = next()?.to_int()?;
+        let ms = master_id.ms.wrapping_add(next()?.to_int()? as u64);
+        let seq = master_id.seq.wrapping_add(next()?.to_int()? as u64);
+        let mut fields = StreamFields::new();
+        if flags & STREAM_ITEM_FLAG_SAMEFIELDS != 0 {
+            for field in &master_fields {
+                fields.push((field.clone(), next()?.into_bytes()));
+            }
+        } else {
+            for _ in 0..next()?.to_int()? {
+                let field = next()?.into_bytes();
+                fields.push((field, next()?.into_bytes()));
+            }
+        }
+        // lp-count
+        next()?;
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            stream.insert(StreamId::new(ms, seq), fields);
+        }
+    }
+    Ok(())
+}
+
+/// Redis listpack 中的一个元素
+#[derive(Debug, Clone, PartialEq)]
+pub enum LpValue {
+    Int(i64),
+    Str(Vec<u8>),
+}
+
+impl LpValue {
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            LpValue::Int(v) => v.to_string().into_bytes(),
+            LpValue::Str(s) => s,
+        }
+    }
+
+    /// 字符串编码的整数也可以读取为整数
+    pub fn to_int(&self) -> Result<i64, RdbError> {
+        match self {
+            LpValue::Int(v) => Ok(*v),
+            LpValue::Str(s) => std::str::from_utf8(s)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| RdbError::Invalid("listpack integer expected".to_string())),
+        }
+    }
+}
+
+/// 生成 Redis 格式的 listpack：4 字节总长度 + 2 字节元素数量 + 元素... + 0xFF，
+/// 每个元素是 编码 + 数据 + 反向的元素长度 (backlen)。和 ListPack 不同，这个格式只用于 RDB
+#[derive(Debug)]
+struct LpWriter {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+impl Default for LpWriter {
+    fn default() -> Self {
+        Self {
+            buf: vec![0; 6],
+            len: 0,
+        }
+    }
+}
+
+impl LpWriter {
+    fn push_int(&mut self, v: i64) {
+        let start = self.buf.len();
+        if (0..128).contains(&v) {
+            self.buf.push(v as u8);
+        } else {
+            self.buf.push(0xf4);
+            self.buf.extend_from_slice(&v.to_le_bytes());
+        }
+        self.push_backlen(start);
+    }
+
+    fn push_str(&mut self, s: &[u8]) {
+        let start = self.buf.len();
+        if s.len() < 1 << 6 {
+            self.buf.push(0x80 | s.len() as u8);
+        } else if s.len() < 1 << 12 {
+            self.buf.push(0xe0 | (s.len() >> 8) as u8);
+            self.buf.push(s.len() as u8);
+        } else {
+            self.buf.push(0xf0);
+            self.buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        }
+        self.buf.extend_from_slice(s);
+        self.push_backlen(start);
+    }
+
+    /// backlen 从后往前读取：最后一个字节是最低的 7 位，除了第一个字节之外都设置了最高位
+    fn push_backlen(&mut self, start: usize) {
+        let len = self.buf.len() - start;
+        let mut bytes = Vec::new();
+        let mut n = len;
+        loop {
+            bytes.push((n & 0x7f) as u8);
+            n >>= 7;
+            if n == 0 {
+                break;
+            }
+        }
+        bytes.reverse();
+        let last = bytes.len() - 1;
+        for b in &mut bytes[1..=last] {
+            *b |= 0x80;
+        }
+        self.buf.extend_from_slice(&bytes);
+        self.len += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.buf.push(0xff);
+        let total = self.buf.len() as u32;
+        self.buf[..4].copy_from_slice(&total.to_le_bytes());
+        // 元素数量超过 65535 时保存 65535，表示需要遍历才能知道
+        let len = self.len.min(u16::MAX as usize) as u16;
+        self.buf[4..6].copy_from_slice(&len.to_le_bytes());
+        self.buf
+    }
+}
+
+/// 解析 Redis 格式的 listpack，支持所有的整数和字符串编码
+pub fn decode_listpack(buf: &[u8]) -> Result<Vec<LpValue>, RdbError> {
+    let mut r = RdbReader::new(buf);
+    r.read_bytes(6)?;
+    let mut items = Vec::new();
+    loop {
+        let start = r.pos;
+        let b = r.read_u8()?;
+        let item = match b {
+            0xff => break,
+            0x00..=0x7f => LpValue::Int(b as i64),
+            0x80..=0xbf => LpValue::Str(r.read_bytes((b & 0x3f) as usize)?.to_vec()),
+            0xc0..=0xdf => {
+                // 13 位有符号整数
+                let v = (((b & 0x1f) as i64) << 8) | r.read_u8()? as i64;
+                LpValue::Int(if v >= 1 << 12 { v - (1 << 13) } else { v })
+            }
+            0xe0..=0xef => {
+                let len = (((b & 0x0f) as usize) << 8) | r.read_u8()? as usize;
+                LpValue::Str(r.read_bytes(len)?.to_vec())
+            }
+            0xf0 => {
+                let len = u32::from_le_bytes(r.read_bytes(4)?.try_into().unwrap());
+                LpValue::Str(r.read_bytes(len as usize)?.to_vec())
+            }
+            0xf1 => LpValue::Int(i16::from_le_bytes(r.read_bytes(2)?.try_into().unwrap()) as i64),
+            0xf2 => {
+                let b = r.read_bytes(3)?;
+                // 放到 i32 的高 24 位再算术右移，完成符号扩展
+                LpValue::Int((i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as i64)
+            }
+            0xf3 => LpValue::Int(i32::from_le_bytes(r.read_bytes(4)?.try_into().unwrap()) as i64),
+            0xf4 => LpValue::Int(i64::from_le_bytes(r.read_bytes(8)?.try_into().unwrap())),
+            _ => {
+                return Err(RdbError::Invalid(format!(
+                    "unknown listpack encoding {:#x}",
+                    b
+                )))
+            }
+        };
+        let backlen = match r.pos - start {
+            0..=127 => 1,
+            128..=16383 => 2,
+            16384..=2097151 => 3,
+            2097152..=268435455 => 4,
+            _ => 5,
+        };
+        r.read_bytes(backlen)?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Redis 使用的 CRC64 (Jones 多项式，反射输入输出)，用于 DUMP 的 payload 以及 RDB 文件的校验和
+pub fn crc64(crc: u64, data: &[u8]) -> u64 {
+    const POLY: u64 = 0x95ac9329ac4bc9b5;
+    let mut crc = crc;
+    for &byte in data {
+        crc ^= byte as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClaimOptions;
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(crc64(0, b"123456789"), 0xe9c6d914c4b8d9ca);
+        assert_eq!(crc64(0, b""), 0);
+    }
+
+    #[test]
+    fn test_rdb_len_and_payload() {
+        let mut writer = RdbWriter::new();
+        for len in [0, 63, 64, 16383, 16384, u32::MAX as u64 + 1] {
+            writer.write_len(len);
+        }
+        writer.write_string(b"hello");
+        let payload = writer.into_payload();
+
+        let mut reader = RdbReader::from_payload(&payload).unwrap();
+        for len in [0, 63, 64, 16383, 16384, u32::MAX as u64 + 1] {
+            assert_eq!(reader.read_len().unwrap(), len);
+        }
+        assert_eq!(reader.read_string().unwrap(), b"hello");
+        assert!(reader.is_empty());
+        assert_eq!(reader.read_u8(), Err(RdbError::Eof));
+
+        let mut broken = payload.clone();
+        broken[0] ^= 1;
+        assert!(RdbReader::from_payload(&broken).is_err());
+    }
+
+    #[test]
+    fn test_listpack() {
+        let long = vec![b'x'; 5000];
+        let mut lp = LpWriter::default();
+        lp.push_int(5);
+        lp.push_int(-1);
+        lp.push_int(i64::MAX);
+        lp.push_str(b"a");
+        lp.push_str(&[b'y'; 200]);
+        lp.push_str(&long);
+        let buf = lp.finish();
+        assert_eq!(
+            u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize,
+            buf.len()
+        );
+        assert_eq!(
+            decode_listpack(&buf).unwrap(),
+            vec![
+                LpValue::Int(5),
+                LpValue::Int(-1),
+                LpValue::Int(i64::MAX),
+                LpValue::Str(b"a".to_vec()),
+                LpValue::Str(vec![b'y'; 200]),
+                LpValue::Str(long),
+            ]
+        );
+
+        // Redis 生成的 13/16/24 位整数编码：-4096、1000、-100000
+        let redis = [
+            0x13, 0, 0, 0, 3, 0, 0xd0, 0x00, 2, 0xf1, 0xe8, 0x03, 3, 0xf2, 0x60, 0x79, 0xfe, 4,
+            0xff,
+        ];
+        assert_eq!(
+            decode_listpack(&redis).unwrap(),
+            vec![
+                LpValue::Int(-4096),
+                LpValue::Int(1000),
+                LpValue::Int(-100000)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rdb_snapshot() {
+        let backend = Backend::new();
+        let now = now_ms();
+        backend.set(b"s".to_vec(), Value::String(b"v".to_vec()));
+        backend.set(b"expired".to_vec(), Value::String(b"v".to_vec()));
+        backend.expires.insert(b"expired".to_vec(), now - 1);
+        backend.set(b"ttl".to_vec(), Value::String(b"v".to_vec()));
+        backend.set_expire(b"ttl", now + 100_000);
+        let list: List = (0..200).map(|i| i.to_string().into_bytes()).collect();
+        backend.set(b"l".to_vec(), Value::List(list));
+        let set: Set = [b"1".to_vec(), b"a".to_vec()].into_iter().collect();
+        backend.set(b"set".to_vec(), Value::Set(set));
+        let zset: ZSet = [(b"a".to_vec(), 1.5), (b"b".to_vec(), -2.0)]
+            .into_iter()
+            .collect();
+        backend.set(b"z".to_vec(), Value::ZSet(zset));
+        let mut hash: Hash = [
+            (b"f1".to_vec(), b"v1".to_vec()),
+            (b"f2".to_vec(), b"v2".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        hash.set_expire(b"f2", now + 100_000);
+        backend.set(b"h".to_vec(), Value::Hash(hash));
+
+        let mut stream = Stream::new();
+        for i in 1..=250u64 {
+            let mut fields = vec![(b"f".to_vec(), i.to_string().into_bytes())];
+            if i % 3 == 0 {
+                fields.push((b"g".to_vec(), b"x".to_vec()));
+            }
+            stream.insert(StreamId::new(1000 + i / 2, i % 2), fields);
+        }
+        stream.remove(StreamId::new(1001, 0));
+        stream.create_group("g", StreamId::new(1002, 0), Some(3));
+        stream.create_group("empty", StreamId::MIN, None);
+        stream.group_mut("g").unwrap().seen_consumer("alice", now);
+        let opts = ClaimOptions {
+            min_idle: 0,
+            delivery_time: now,
+            retry_count: Some(2),
+            force: true,
+            just_id: false,
+            last_id: None,
+        };
+        let ids = [StreamId::new(1001, 1), StreamId::new(1002, 0)];
+        stream.claim("g", "bob", &ids, &opts, now).unwrap();
+        backend.set(b"st".to_vec(), Value::Stream(stream));
+
+        let data = backend.rdb_snapshot();
+        assert_eq!(&data[..9], b"REDIS0012");
+        let loaded = Backend::new();
+        assert!(loaded.load_rdb(&data).unwrap().is_empty());
+        assert!(!loaded.exists(b"expired"));
+        assert_eq!(loaded.get_expire(b"ttl"), Some(now + 100_000));
+        for key in ["s", "l", "set", "z", "h", "st"] {
+            let key = key.as_bytes();
+            assert_eq!(loaded.get(key), backend.get(key), "{:?}", key);
+        }
+        assert_eq!(loaded.field_expires.get(b"h"), Some(now + 100_000));
+
+        let mut broken = data.clone();
+        broken[20] ^= 1;
+        assert_eq!(
+            Backend::new().load_rdb(&broken),
+            Err(RdbError::Invalid("wrong checksum".to_string()))
+        );
+
+        // 只有一个数据库，其他数据库中的 key 不能加载
+        let mut w = RdbWriter::new();
+        w.write_bytes(b"REDIS0011");
+        w.write_u8(RDB_OPCODE_SELECTDB);
+        w.write_len(1);
+        key(&mut w, RDB_TYPE_STRING, "s");
+        w.write_string(b"v");
+        w.write_u8(RDB_OPCODE_EOF);
+        w.write_bytes(&[0; 8]);
+        let loaded = Backend::new();
+        assert!(loaded.load_rdb(&w.buf).is_err());
+        assert!(!loaded.exists(b"s"));
+    }
+
+    /// 只包含短字符串的 ziplist
+    fn ziplist(items: &[&[u8]]) -> Vec<u8> {
+        let mut zl = vec![0; 10];
+        let mut prevlen = 0;
+        for item in items {
+            zl.extend_from_slice(&[prevlen, item.len() as u8]);
+            zl.extend_from_slice(item);
+            prevlen = item.len() as u8 + 2;
+        }
+        zl.push(0xff);
+        zl
+    }
+
+    fn listpack(items: &[LpValue]) -> Vec<u8> {
+        let mut lp = LpWriter::default();
+        for item in items {
+            match item {
+                LpValue::Int(n) => lp.push_int(*n),
+                LpValue::Str(s) => lp.push_str(s),
+            }
+        }
+        lp.finish()
+    }
+
+    fn key(w: &mut RdbWriter, ty: u8, key: &str) {
+        w.write_u8(ty);
+        w.write_string(key.as_bytes());
+    }
+
+    fn bytes(items: &[&str]) -> Vec<Vec<u8>> {
+        items.iter().map(|s| s.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_load_redis_encodings() {
+        use LpValue::{Int, Str};
+        let now = now_ms();
+        let mut w = RdbWriter::new();
+        w.write_bytes(b"REDIS0011");
+        w.write_u8(RDB_OPCODE_AUX);
+        w.write_string(b"redis-ver");
+        w.write_string(b"7.2.4");
+        // module 的 aux 数据被跳过
+        w.write_u8(RDB_OPCODE_MODULE_AUX);
+        w.write_len(0x1234_5678_9abc_def0);
+        w.write_len(RDB_MODULE_OPCODE_UINT);
+        w.write_len(2);
+        w.write_len(RDB_MODULE_OPCODE_SINT);
+        w.write_len(5);
+        w.write_len(RDB_MODULE_OPCODE_STRING);
+        w.write_string(b"x");
+        w.write_len(RDB_MODULE_OPCODE_DOUBLE);
+        w.write_bytes(&1.5f64.to_le_bytes());
+        w.write_len(RDB_MODULE_OPCODE_EOF);
+        w.write_u8(RDB_OPCODE_FUNCTION2);
+        w.write_string(b"#!lua name=lib\n");
+        w.write_u8(RDB_OPCODE_SELECTDB);
+        w.write_len(0);
+        w.write_u8(RDB_OPCODE_RESIZEDB);
+        w.write_len(20);
+        w.write_len(1);
+        w.write_u8(RDB_OPCODE_SLOT_INFO);
+        w.write_bytes(&[1, 2, 3]);
+
+        // 整数编码的字符串，key 也可以是整数编码的
+        w.write_u8(RDB_OPCODE_IDLE);
+        w.write_len(100);
+        key(&mut w, RDB_TYPE_STRING, "int");
+        w.write_bytes(&[0xc1, 0x39, 0x30]);
+        w.write_u8(RDB_OPCODE_FREQ);
+        w.write_u8(5);
+        w.write_u8(RDB_TYPE_STRING);
+        w.write_bytes(&[0xc0, 0xf9]);
+        w.write_bytes(&[0xc2, 0x15, 0xcd, 0x5b, 0x07]);
+        // LZF 压缩的字符串
+        key(&mut w, RDB_TYPE_STRING, "lzf");
+        w.write_bytes(&[0xc3, 8, 29, 0x01, b'a', b'b', 0xa0, 0x01, 0xe0, 0x0b, 0x00]);
+        // 以秒为单位的过期时间
+        w.write_u8(RDB_OPCODE_EXPIRETIME);
+        w.write_bytes(&((now / 1000 + 1000) as u32).to_le_bytes());
+        key(&mut w, RDB_TYPE_STRING, "sec");
+        w.write_string(b"v");
+        // key 是二进制安全的，不是合法 UTF-8 的 key 也可以加载
+        w.write_u8(RDB_OPCODE_EXPIRETIME_MS);
+        w.write_ms(now as i64 + 100_000);
+        w.write_u8(RDB_TYPE_STRING);
+        w.write_string(b"bin\xff\xfe");
+        w.write_string(b"v");
+        key(&mut w, RDB_TYPE_STRING, "afterbin");
+        w.write_string(b"v");
+
+        key(&mut w, RDB_TYPE_SET_INTSET, "intset");
+        w.write_string(&[2, 0, 0, 0, 2, 0, 0, 0, 0xff, 0xff, 1, 0]);
+        key(&mut w, RDB_TYPE_SET_LISTPACK, "setlp");
+        w.write_string(&listpack(&[Str(b"a".to_vec()), Int(7)]));
+        key(&mut w, RDB_TYPE_LIST_ZIPLIST, "listzl");
+        w.write_string(&ziplist(&[b"a", b"b"]));
+        key(&mut w, RDB_TYPE_LIST_QUICKLIST, "quicklist");
+        w.write_len(2);
+        w.write_string(&ziplist(&[b"a"]));
+        w.write_string(&ziplist(&[b"b", b"c"]));
+        key(&mut w, RDB_TYPE_LIST_QUICKLIST_2, "quicklist2");
+        w.write_len(2);
+        w.write_len(QUICKLIST_NODE_CONTAINER_PACKED);
+        w.write_string(&listpack(&[Str(b"a".to_vec()), Int(-5)]));
+        w.write_len(QUICKLIST_NODE_CONTAINER_PLAIN);
+        w.write_string(b"plain");
+        key(&mut w, RDB_TYPE_HASH_ZIPMAP, "zipmap");
+        w.write_string(&[1, 1, b'f', 1, 0, b'v', 0xff]);
+        key(&mut w, RDB_TYPE_HASH_ZIPLIST, "hashzl");
+        w.write_string(&ziplist(&[b"f", b"v"]));
+        key(&mut w, RDB_TYPE_HASH_LISTPACK, "hashlp");
+        w.write_string(&listpack(&[Str(b"f".to_vec()), Int(1)]));
+        key(&mut w, RDB_TYPE_HASH_LISTPACK_EX, "hashex");
+        w.write_ms(now as i64 - 1);
+        w.write_string(&listpack(&[
+            Str(b"f1".to_vec()),
+            Str(b"v1".to_vec()),
+            Int(0),
+            Str(b"f2".to_vec()),
+            Str(b"v2".to_vec()),
+            Int(now as i64 + 100_000),
+            Str(b"expired".to_vec()),
+            Str(b"v3".to_vec()),
+            Int(now as i64 - 1),
+        ]));
+        key(&mut w, RDB_TYPE_HASH_METADATA_PRE_GA, "hashmeta");
+        w.write_len(1);
+        w.write_len(now + 100_000);
+        w.write_string(b"f");
+        w.write_string(b"v");
+        // 全部 field 都过期的 hash 不加载
+        key(&mut w, RDB_TYPE_HASH_LISTPACK_EX_PRE_GA, "allexpired");
+        w.write_string(&listpack(&[Str(b"f".to_vec()), Int(1), Int(1)]));
+
+        key(&mut w, RDB_TYPE_ZSET, "zset1");
+        w.write_len(3);
+        for (member, score) in [
+            (&b"a"[..], &b"\x031.5"[..]),
+            (b"b", b"\xfe"),
+            (b"c", b"\xff"),
+        ] {
+            w.write_string(member);
+            w.write_bytes(score);
+        }
+        key(&mut w, RDB_TYPE_ZSET_ZIPLIST, "zsetzl");
+        let mut zl = vec![0; 10];
+        zl.extend_from_slice(&[0x00, 0x01, b'a', 0x03, 0xf2, 0x02, 0x01, b'b']);
+        zl.extend_from_slice(&[0x03, 0x03, b'2', b'.', b'5', 0xff]);
+        w.write_string(&zl);
+        key(&mut w, RDB_TYPE_ZSET_LISTPACK, "zsetlp");
+        w.write_string(&listpack(&[Str(b"a".to_vec()), Int(-3)]));
+
+        // module 的 value 被跳过
+        key(&mut w, RDB_TYPE_MODULE_2, "module");
+        w.write_len(0x1234_5678_9abc_def0);
+        w.write_len(RDB_MODULE_OPCODE_FLOAT);
+        w.write_bytes(&[0; 4]);
+        w.write_len(RDB_MODULE_OPCODE_EOF);
+
+        // Redis 5.0 的 stream：没有 ID 元数据、entries_read 和 active_time
+        let entries = vec![
+            (StreamId::new(1, 0), vec![(b"f".to_vec(), b"1".to_vec())]),
+            (StreamId::new(2, 0), vec![(b"g".to_vec(), b"2".to_vec())]),
+        ];
+        key(&mut w, RDB_TYPE_STREAM_LISTPACKS, "stream");
+        w.write_len(1);
+        w.write_string(&StreamId::new(1, 0).to_bytes());
+        w.write_string(&stream_node(&entries));
+        w.write_len(2);
+        w.write_len(2);
+        w.write_len(0);
+        w.write_len(1);
+        w.write_string(b"group");
+        w.write_len(1);
+        w.write_len(0);
+        w.write_len(1);
+        w.write_bytes(&StreamId::new(1, 0).to_bytes());
+        w.write_ms(1000);
+        w.write_len(3);
+        w.write_len(1);
+        w.write_string(b"alice");
+        w.write_ms(2000);
+        w.write_len(1);
+        w.write_bytes(&StreamId::new(1, 0).to_bytes());
+
+        // 校验和为 0 表示保存时没有计算校验和
+        w.write_u8(RDB_OPCODE_EOF);
+        w.write_bytes(&[0; 8]);
+        let backend = Backend::new();
+        let functions = backend.load_rdb(&w.buf).unwrap();
+        assert_eq!(functions, vec!["#!lua name=lib\n".to_string()]);
+
+        let string = |s: &str| Some(Value::String(s.as_bytes().to_vec()));
+        assert_eq!(backend.get(b"int"), string("12345"));
+        assert_eq!(backend.get(b"-7"), string("123456789"));
+        assert_eq!(
+            backend.get(b"lzf"),
+            Some(Value::String(
+                [b"ababababa".as_slice(), &[b'a'; 20]].concat()
+            ))
+        );
+        assert_eq!(backend.get_expire(b"sec"), Some((now / 1000 + 1000) * 1000));
+        assert_eq!(backend.get(b"bin\xff\xfe"), string("v"));
+        assert_eq!(backend.get_expire(b"bin\xff\xfe"), Some(now + 100_000));
+        assert_eq!(backend.get(b"afterbin"), string("v"));
+        assert_eq!(backend.get_expire(b"afterbin"), None);
+        let set = |items: &[&str]| Some(Value::Set(bytes(items).into_iter().collect()));
+        assert_eq!(backend.get(b"intset"), set(&["-1", "1"]));
+        assert_eq!(backend.get(b"setlp"), set(&["a", "7"]));
+        let list = |items: &[&str]| Some(Value::List(bytes(items).into_iter().collect()));
+        assert_eq!(backend.get(b"listzl"), list(&["a", "b"]));
+        assert_eq!(backend.get(b"quicklist"), list(&["a", "b", "c"]));
+        assert_eq!(backend.get(b"quicklist2"), list(&["a", "-5", "plain"]));
+        let hash = |items: &[(&str, &str)]| {
+            let hash: Hash = items
+                .iter()
+                .map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec()))
+                .collect();
+            Some(Value::Hash(hash))
+        };
+        assert_eq!(backend.get(b"zipmap"), hash(&[("f", "v")]));
+        assert_eq!(backend.get(b"hashzl"), hash(&[("f", "v")]));
+        assert_eq!(backend.get(b"hashlp"), hash(&[("f", "1")]));
+        let Some(Value::Hash(hashex)) = backend.get(b"hashex") else {
+            panic!("hashex should be a hash");
+        };
+        assert_eq!(hashex.len(), 2);
+        assert_eq!(hashex.get_expire(b"f1"), None);
+        assert_eq!(hashex.get_expire(b"f2"), Some(now + 100_000));
+        let Some(Value::Hash(hashmeta)) = backend.get(b"hashmeta") else {
+            panic!("hashmeta should be a hash");
+        };
+        assert_eq!(hashmeta.get_expire(b"f"), Some(now + 100_000));
+        assert!(!backend.exists(b"allexpired"));
+
+        let zset = |items: &[(&str, f64)]| {
+            let zset: ZSet = items
+                .iter()
+                .map(|(m, s)| (m.as_bytes().to_vec(), *s))
+                .collect();
+            Some(Value::ZSet(zset))
+        };
+        assert_eq!(
+            backend.get(b"zset1"),
+            zset(&[("a", 1.5), ("b", f64::INFINITY), ("c", f64::NEG_INFINITY)])
+        );
+        assert_eq!(backend.get(b"zsetzl"), zset(&[("a", 1.0), ("b", 2.5)]));
+        assert_eq!(backend.get(b"zsetlp"), zset(&[("a", -3.0)]));
+        assert!(!backend.exists(b"module"));
+
+        let Some(Value::Stream(stream)) = backend.get(b"stream") else {
+            panic!("stream should be a stream");
+        };
+        assert_eq!(stream.len(), 2);
+        assert_eq!(stream.entries_added(), 2);
+        let group = stream.group("group").unwrap();
+        assert_eq!(group.entries_read(), None);
+        let consumer = group.consumer("alice").unwrap();
+        assert_eq!(consumer.active_time, Some(2000));
+        assert_eq!(
+            group
+                .pending_entry(StreamId::new(1, 0))
+                .unwrap()
+                .delivery_count,
+            3
+        );
+    }
+}
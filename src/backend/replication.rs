@@ -0,0 +1,655 @@
+use super::{now_ms, Backend};
+use crate::{RespArray, RespFrame};
+use bytes::Bytes;
+use rand::Rng;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    time::Instant,
+};
+use tracing::warn;
+
+/// backlog 的默认大小，和 Redis 的 repl-backlog-size 一样
+pub const REPL_BACKLOG_SIZE: usize = 1024 * 1024;
+
+/// replica 输出缓冲区的默认上限，对应 Redis `client-output-buffer-limit replica` 的 hard limit
+pub const DEFAULT_REPLICA_BUFFER_LIMIT: usize = 256 * 1024 * 1024;
+
+/// 主从复制的状态，对应 Redis server 结构中 repl_* 和 master_* 的字段
+///
+/// master 把写命令按照 RESP 格式追加到命令流中，replid + offset 标识命令流中的位置。
+/// 最近的命令流保存在环形的 backlog 中，replica 断线重连之后可以从 backlog 中继续同步 (PSYNC)
+#[derive(Debug)]
+pub struct Replication {
+    state: Mutex<ReplState>,
+    // 是否是 replica，写命令检查时不需要加锁
+    replica: AtomicBool,
+    // 要同步的 master，连接 master 的任务监听这个值的变化
+    master: watch::Sender<Option<(String, u16)>>,
+    // replica 确认了新的 offset 或者本地 AOF fsync 之后通知 WAIT/WAITAOF 的客户端
+    acks: watch::Sender<()>,
+}
+
+#[derive(Debug)]
+struct ReplState {
+    // 自己监听的端口，REPLCONF listening-port 发送给 master
+    port: u16,
+    link: LinkState,
+    // 上次收到 master 数据的时间 (unix 毫秒)
+    last_io: u64,
+    replid: String,
+    // 切换 master 之前的 replid，second_replid_offset 之前的命令流和 replid2 相同
+    replid2: String,
+    second_replid_offset: i64,
+    // 命令流中最后一个字节的 offset
+    offset: u64,
+    backlog: Option<Backlog>,
+    backlog_size: usize,
+    // 每个 replica 积压的命令流的上限，0 表示不限制
+    buffer_limit: usize,
+    replicas: BTreeMap<u64, Replica>,
+}
+
+/// 环形的 backlog，保存命令流中最后的 size 个字节
+#[derive(Debug)]
+struct Backlog {
+    buf: VecDeque<u8>,
+    // buf 中第一个字节的 offset
+    start: u64,
+}
+
+/// 连接到自己的 replica
+#[derive(Debug)]
+struct Replica {
+    addr: String,
+    port: u16,
+    tx: mpsc::UnboundedSender<Bytes>,
+    // 已经写入 tx、还没有发送给 replica 的字节数
+    pending: Arc<AtomicUsize>,
+    // 从 replicas 中移除时 drop，通知连接断开
+    _closed: oneshot::Sender<()>,
+    // replica 通过 REPLCONF ACK 确认的 offset，以及确认的时间 (unix 毫秒)
+    ack_offset: u64,
+    ack_time: u64,
+    // replica 的 AOF fsync 到的 offset (REPLCONF ACK 中的 FACK)
+    aof_ack_offset: u64,
+}
+
+/// WAIT/WAITAOF 阻塞时等待的条件，对应 Redis 的 BLOCKED_WAIT 和 BLOCKED_WAITAOF
+#[derive(Debug, Clone)]
+pub struct AckWait {
+    // 客户端最后一次写入之后命令流的 offset
+    pub offset: u64,
+    pub numreplicas: usize,
+    // WAITAOF 要求本地 AOF fsync 的数量 (0 或 1)，WAIT 时为 None
+    pub numlocal: Option<usize>,
+    // None 表示一直等待
+    pub timeout: Option<Duration>,
+}
+
+/// replica 和 master 连接的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    // 等待连接
+    Connect,
+    // 正在连接、握手
+    Connecting,
+    // 正在接收全量同步的数据
+    Sync,
+    // 同步完成，正在接收命令流
+    Connected,
+}
+
+/// PSYNC/SYNC 的结果：先发送给 replica 的数据 (全量同步的 RDB 或者 backlog 中的命令)，之后是命令流
+#[derive(Debug)]
+pub struct ReplicaFeed {
+    pub id: u64,
+    pub preamble: Vec<u8>,
+    pub rx: mpsc::UnboundedReceiver<Bytes>,
+    pending: Arc<AtomicUsize>,
+    closed: oneshot::Receiver<()>,
+}
+
+/// ROLE 和 INFO replication 需要的状态
+#[derive(Debug, Clone)]
+pub struct ReplicationStatus {
+    // replica 时是 master 的地址
+    pub master: Option<(String, u16)>,
+    pub link: LinkState,
+    pub last_io: u64,
+    pub replid: String,
+    pub replid2: String,
+    pub second_replid_offset: i64,
+    pub offset: u64,
+    // backlog 中第一个字节的 offset 以及长度
+    pub backlog: Option<(u64, usize)>,
+    pub backlog_size: usize,
+    pub replicas: Vec<ReplicaStatus>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplicaStatus {
+    pub addr: String,
+    pub port: u16,
+    pub ack_offset: u64,
+    pub ack_time: u64,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(ReplState {
+                port: 6379,
+                link: LinkState::Connect,
+                last_io: 0,
+                replid: random_id(),
+                replid2: "0".repeat(40),
+                second_replid_offset: -1,
+                offset: 0,
+                backlog: None,
+                backlog_size: REPL_BACKLOG_SIZE,
+                buffer_limit: DEFAULT_REPLICA_BUFFER_LIMIT,
+                replicas: BTreeMap::new(),
+            }),
+            replica: AtomicBool::new(false),
+            master: watch::Sender::new(None),
+            acks: watch::Sender::new(()),
+        }
+    }
+}
+
+impl ReplicaFeed {
+    /// 下一段命令流，replica 被 master 断开时 (输出缓冲区溢出或者自己变成了 replica) 返回 None
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        let data = tokio::select! {
+            biased;
+            _ = &mut self.closed => return None,
+            data = self.rx.recv() => data?,
+        };
+        self.pending.fetch_sub(data.len(), Ordering::Relaxed);
+        Some(data)
+    }
+
+    /// replica 被 master 断开时返回，向 replica 写数据阻塞时也需要检查
+    pub async fn closed(&mut self) {
+        let _ = (&mut self.closed).await;
+    }
+}
+
+impl LinkState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkState::Connect => "connect",
+            LinkState::Connecting => "connecting",
+            LinkState::Sync => "sync",
+            LinkState::Connected => "connected",
+        }
+    }
+}
+
+impl fmt::Display for LinkState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Backlog {
+    fn new(start: u64) -> Self {
+        Self {
+            buf: VecDeque::new(),
+            start,
+        }
+    }
+
+    fn append(&mut self, data: &[u8], size: usize) {
+        self.buf.extend(data);
+        if self.buf.len() > size {
+            let excess = self.buf.len() - size;
+            self.buf.drain(..excess);
+            self.start += excess as u64;
+        }
+    }
+
+    /// 从 offset 开始到结尾的数据，offset 不在 backlog 中时返回 None
+    fn read_from(&self, offset: u64) -> Option<Vec<u8>> {
+        let skip = usize::try_from(offset.checked_sub(self.start)?).ok()?;
+        (skip <= self.buf.len()).then(|| self.buf.iter().skip(skip).copied().collect())
+    }
+}
+
+impl ReplState {
+    /// 切换 replid，之前的命令流仍然可以通过 replid2 继续同步，对应 Redis 的 shiftReplicationId
+    fn shift_replid(&mut self) {
+        self.replid2 = std::mem::replace(&mut self.replid, random_id());
+        self.second_replid_offset = self.offset as i64 + 1;
+    }
+
+    fn create_backlog(&mut self) {
+        if self.backlog.is_none() {
+            self.backlog = Some(Backlog::new(self.offset + 1));
+        }
+    }
+}
+
+impl Replication {
+    fn state(&self) -> MutexGuard<'_, ReplState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Backend {
+    pub fn set_listening_port(&self, port: u16) {
+        self.replication.state().port = port;
+    }
+
+    pub fn listening_port(&self) -> u16 {
+        self.replication.state().port
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.replication.replica.load(Ordering::Relaxed)
+    }
+
+    /// 有 backlog 时写命令需要追加到命令流
+    pub(crate) fn has_backlog(&self) -> bool {
+        self.replication.state().backlog.is_some()
+    }
+
+    /// 监听 REPLICAOF 设置的 master
+    pub fn watch_master(&self) -> watch::Receiver<Option<(String, u16)>> {
+        self.replication.master.subscribe()
+    }
+
+    /// REPLICAOF host port 或者 REPLICAOF NO ONE (master 为 None)，已经是这个 master 的 replica 时返回 false
+    ///
+    /// 切换 master 时断开自己的 replica，它们重新连接之后用新的 replid 同步
+    pub fn set_master(&self, master: Option<(String, u16)>) -> bool {
+        if *self.replication.master.borrow() == master {
+            return false;
+        }
+        let mut state = self.replication.state();
+        match &master {
+            Some(_) => state.link = LinkState::Connect,
+            // 提升为 master：之前从原来的 master 同步的命令流仍然可以用 replid2 继续同步
+            None => {
+                state.shift_replid();
+                state.create_backlog();
+            }
+        }
+        state.replicas.clear();
+        self.replication
+            .replica
+            .store(master.is_some(), Ordering::Relaxed);
+        self.replication.master.send_replace(master);
+        true
+    }
+
+    pub fn master(&self) -> Option<(String, u16)> {
+        self.replication.master.borrow().clone()
+    }
+
+    pub fn set_link_state(&self, link: LinkState) {
+        self.replication.state().link = link;
+    }
+
+    /// 收到了 master 的数据
+    pub fn touch_master_io(&self) {
+        self.replication.state().last_io = now_ms();
+    }
+
+    /// replica 发送 PSYNC 时使用的 replid 和下一个需要的 offset
+    pub fn psync_position(&self) -> (String, u64) {
+        let state = self.replication.state();
+        (state.replid.clone(), state.offset + 1)
+    }
+
+    /// replica 完成全量同步：使用 master 的 replid 和 offset，清空 backlog 重新开始
+    pub fn full_synced(&self, replid: String, offset: u64) {
+        let mut state = self.replication.state();
+        state.replid = replid;
+        state.replid2 = "0".repeat(40);
+        state.second_replid_offset = -1;
+        state.offset = offset;
+        state.backlog = Some(Backlog::new(offset + 1));
+        state.link = LinkState::Connected;
+        state.last_io = now_ms();
+        state.replicas.clear();
+    }
+
+    /// replica 部分同步成功 (+CONTINUE)，master 切换过 replid 时同样切换，自己的 replica 可以继续同步
+    pub fn partial_synced(&self, replid: Option<String>) {
+        let mut state = self.replication.state();
+        if let Some(replid) = replid.filter(|id| *id != state.replid) {
+            state.replid2 = std::mem::replace(&mut state.replid, replid);
+            state.second_replid_offset = state.offset as i64 + 1;
+            state.replicas.clear();
+        }
+        state.create_backlog();
+        state.link = LinkState::Connected;
+        state.last_io = now_ms();
+    }
+
+    /// 把命令流追加到 backlog，发送给所有的 replica，调用方需要持有执行锁
+    pub fn feed_replication(&self, data: &[u8]) {
+        let mut state = self.replication.state();
+        let size = state.backlog_size;
+        let Some(backlog) = state.backlog.as_mut() else {
+            return;
+        };
+        backlog.append(data, size);
+        state.offset += data.len() as u64;
+        let data = Bytes::copy_from_slice(data);
+        let limit = state.buffer_limit;
+        // 连接已经断开的 replica 直接移除，积压超过上限的 replica 和 Redis 一样断开，之后重新同步
+        state.replicas.retain(|_, replica| {
+            let pending = replica.pending.fetch_add(data.len(), Ordering::Relaxed) + data.len();
+            if limit > 0 && pending > limit {
+                warn!(
+                    "Replica {}:{} closed for overcoming of output buffer limits",
+                    replica.addr, replica.port
+                );
+                return false;
+            }
+            replica.tx.send(data.clone()).is_ok()
+        });
+    }
+
+    /// replica 积压的命令流的上限 (字节)，0 表示不限制
+    pub fn set_replica_buffer_limit(&self, limit: usize) {
+        self.replication.state().buffer_limit = limit;
+    }
+
+    /// PSYNC replid offset：可以从 backlog 中继续同步时返回 +CONTINUE 和 backlog 中的数据，
+    /// 否则返回 +FULLRESYNC 和 RDB 快照。SYNC 没有 +FULLRESYNC 这一行 (psync 为 false)。
+    /// 调用方需要持有执行锁，快照和之后的命令流之间不会有遗漏
+    pub fn psync(
+        &self,
+        id: u64,
+        addr: String,
+        port: u16,
+        request: Option<(&str, i64)>,
+    ) -> ReplicaFeed {
+        let partial = request.and_then(|(replid, offset)| self.partial_sync(replid, offset));
+        let preamble = match partial {
+            Some(preamble) => preamble,
+            None => {
+                let snapshot = self.rdb_snapshot();
+                let mut state = self.replication.state();
+                state.create_backlog();
+                let mut preamble = match request {
+                    Some(_) => format!("+FULLRESYNC {} {}\r\n", state.replid, state.offset),
+                    None => String::new(),
+                }
+                .into_bytes();
+                preamble.extend_from_slice(format!("${}\r\n", snapshot.len()).as_bytes());
+                preamble.extend_from_slice(&snapshot);
+                preamble
+            }
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (closed_tx, closed) = oneshot::channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let mut state = self.replication.state();
+        state.replicas.insert(
+            id,
+            Replica {
+                addr,
+                port,
+                tx,
+                pending: pending.clone(),
+                _closed: closed_tx,
+                ack_offset: 0,
+                ack_time: now_ms(),
+                aof_ack_offset: 0,
+            },
+        );
+        ReplicaFeed {
+            id,
+            preamble,
+            rx,
+            pending,
+            closed,
+        }
+    }
+
+    fn partial_sync(&self, replid: &str, offset: i64) -> Option<Vec<u8>> {
+        let state = self.replication.state();
+        let offset = u64::try_from(offset).ok()?;
+        let same_history = replid == state.replid
+            || (replid == state.replid2 && offset as i64 <= state.second_replid_offset);
+        if !same_history || offset > state.offset + 1 {
+            return None;
+        }
+        let data = state.backlog.as_ref()?.read_from(offset)?;
+        let mut preamble = format!("+CONTINUE {}\r\n", state.replid).into_bytes();
+        preamble.extend_from_slice(&data);
+        Some(preamble)
+    }
+
+    /// REPLCONF ACK offset [FACK aofoffset]
+    pub fn replica_ack(&self, id: u64, offset: u64, aof_offset: Option<u64>) {
+        if let Some(replica) = self.replication.state().replicas.get_mut(&id) {
+            replica.ack_offset = offset;
+            replica.ack_time = now_ms();
+            if let Some(aof_offset) = aof_offset {
+                replica.aof_ack_offset = aof_offset;
+            }
+        }
+        self.notify_acks();
+    }
+
+    /// WAIT 阻塞时让所有的 replica 立即发送 REPLCONF ACK，调用方需要持有执行锁
+    pub fn request_acks(&self) {
+        if self.connected_replicas() > 0 {
+            self.feed_replication(b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n");
+        }
+    }
+
+    /// 唤醒 WAIT/WAITAOF 的客户端重新检查
+    pub(crate) fn notify_acks(&self) {
+        self.replication.acks.send_replace(());
+    }
+
+    /// 命令流当前的 offset
+    pub fn replication_offset(&self) -> u64 {
+        self.replication.state().offset
+    }
+
+    /// 没有 replica 时也创建 backlog，命令流的 offset 随着写入增长，WAITAOF 需要用它标识写入的位置
+    pub(crate) fn ensure_backlog(&self) {
+        self.replication.state().create_backlog();
+    }
+
+    /// 已经确认到 offset 的数量：本地 AOF 是否 fsync (0 或 1)，以及 replica 的数量。
+    /// WAIT 统计 REPLCONF ACK 的 offset，WAITAOF 统计 replica 的 AOF fsync 的 offset
+    fn acked(&self, wait: &AckWait) -> (usize, usize) {
+        let local = (self.aof_fsynced_offset() >= wait.offset) as usize;
+        let state = self.replication.state();
+        let replicas = state
+            .replicas
+            .values()
+            .filter(|replica| match wait.numlocal {
+                Some(_) => replica.aof_ack_offset >= wait.offset,
+                None => replica.ack_offset >= wait.offset,
+            })
+            .count();
+        (local, replicas)
+    }
+
+    /// 是否已经满足 WAIT/WAITAOF 的条件
+    pub fn ack_satisfied(&self, wait: &AckWait) -> bool {
+        let (local, replicas) = self.acked(wait);
+        local >= wait.numlocal.unwrap_or_default() && replicas >= wait.numreplicas
+    }
+
+    /// WAIT 返回确认的 replica 数量，WAITAOF 返回 [本地, replica 数量]
+    pub fn ack_reply(&self, wait: &AckWait) -> RespFrame {
+        let (local, replicas) = self.acked(wait);
+        match wait.numlocal {
+            Some(_) => RespArray::new(vec![(local as i64).into(), (replicas as i64).into()]).into(),
+            None => (replicas as i64).into(),
+        }
+    }
+
+    /// 等待满足 WAIT/WAITAOF 的条件或者超时，不能持有执行锁
+    pub async fn wait_ack(&self, wait: AckWait) -> RespFrame {
+        let mut acks = self.replication.acks.subscribe();
+        let deadline = wait.timeout.map(|timeout| Instant::now() + timeout);
+        while !self.ack_satisfied(&wait) {
+            let changed = acks.changed();
+            let notified = match deadline {
+                Some(deadline) => {
+                    matches!(tokio::time::timeout_at(deadline, changed).await, Ok(Ok(())))
+                }
+                None => changed.await.is_ok(),
+            };
+            if !notified {
+                break;
+            }
+        }
+        self.ack_reply(&wait)
+    }
+
+    /// replica 断开连接
+    pub fn remove_replica(&self, id: u64) {
+        self.replication.state().replicas.remove(&id);
+    }
+
+    pub fn connected_replicas(&self) -> usize {
+        self.replication.state().replicas.len()
+    }
+
+    pub fn replication_status(&self) -> ReplicationStatus {
+        let master = self.master();
+        let state = self.replication.state();
+        ReplicationStatus {
+            master,
+            link: state.link,
+            last_io: state.last_io,
+            replid: state.replid.clone(),
+            replid2: state.replid2.clone(),
+            second_replid_offset: state.second_replid_offset,
+            offset: state.offset,
+            backlog: state
+                .backlog
+                .as_ref()
+                .map(|backlog| (backlog.start, backlog.buf.len())),
+            backlog_size: state.backlog_size,
+            replicas: state
+                .replicas
+                .values()
+                .map(|replica| ReplicaStatus {
+                    addr: replica.addr.clone(),
+                    port: replica.port,
+                    ack_offset: replica.ack_offset,
+                    ack_time: replica.ack_time,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 40 个字符的随机 id，用作 replid 和 cluster 的 node id
+pub(crate) fn random_id() -> String {
+    let mut rng = rand::thread_rng();
+    (0..40)
+        .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap_or('0'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    #[test]
+    fn test_backlog() {
+        let mut backlog = Backlog::new(1);
+        backlog.append(b"hello", 8);
+        assert_eq!(backlog.read_from(1).unwrap(), b"hello");
+        assert_eq!(backlog.read_from(6).unwrap(), b"");
+        assert_eq!(backlog.read_from(7), None);
+        backlog.append(b"world", 8);
+        assert_eq!(backlog.start, 3);
+        assert_eq!(backlog.read_from(2), None);
+        assert_eq!(backlog.read_from(3).unwrap(), b"lloworld");
+        assert_eq!(backlog.read_from(9).unwrap(), b"ld");
+    }
+
+    #[test]
+    fn test_psync() {
+        let backend = Backend::new();
+        backend.set(b"a".to_vec(), Value::String(b"1".to_vec()));
+        let (replid, offset) = backend.psync_position();
+        assert_eq!(offset, 1);
+
+        let mut feed = backend.psync(1, "127.0.0.1".to_string(), 6380, Some(("?", -1)));
+        let preamble = String::from_utf8_lossy(&feed.preamble).to_string();
+        assert!(preamble.starts_with(&format!("+FULLRESYNC {} 0\r\n$", replid)));
+        backend.feed_replication(b"*1\r\n$4\r\nPING\r\n");
+        assert_eq!(feed.rx.try_recv().unwrap(), &b"*1\r\n$4\r\nPING\r\n"[..]);
+        backend.replica_ack(1, 14, None);
+        assert_eq!(backend.replication_status().replicas[0].ack_offset, 14);
+
+        // 从 backlog 中继续同步
+        let feed = backend.psync(2, "127.0.0.1".to_string(), 6381, Some((&replid, 5)));
+        assert_eq!(
+            feed.preamble,
+            format!("+CONTINUE {}\r\n$4\r\nPING\r\n", replid).as_bytes()
+        );
+        let feed = backend.psync(3, "127.0.0.1".to_string(), 6381, Some((&replid, 15)));
+        assert_eq!(
+            feed.preamble,
+            format!("+CONTINUE {}\r\n", replid).as_bytes()
+        );
+        let feed = backend.psync(3, "127.0.0.1".to_string(), 6381, Some((&replid, 16)));
+        assert!(feed.preamble.starts_with(b"+FULLRESYNC"));
+        let feed = backend.psync(3, "127.0.0.1".to_string(), 6381, Some(("x", 1)));
+        assert!(feed.preamble.starts_with(b"+FULLRESYNC"));
+        assert_eq!(backend.connected_replicas(), 3);
+
+        // 变成 replica 之后断开所有的 replica，再提升为 master 时旧的 replid 可以继续同步
+        assert!(backend.set_master(Some(("127.0.0.1".to_string(), 7000))));
+        assert!(!backend.set_master(Some(("127.0.0.1".to_string(), 7000))));
+        assert!(backend.is_replica());
+        assert_eq!(backend.connected_replicas(), 0);
+        assert!(backend.set_master(None));
+        let status = backend.replication_status();
+        assert_eq!(status.replid2, replid);
+        assert_eq!(status.second_replid_offset, 15);
+        let feed = backend.psync(4, "127.0.0.1".to_string(), 6381, Some((&replid, 15)));
+        assert!(feed
+            .preamble
+            .starts_with(format!("+CONTINUE {}", status.replid).as_bytes()));
+        let feed = backend.psync(4, "127.0.0.1".to_string(), 6381, None);
+        assert!(feed.preamble.starts_with(b"$"));
+    }
+
+    #[tokio::test]
+    async fn test_replica_buffer_limit() {
+        let backend = Backend::new();
+        backend.set_replica_buffer_limit(20);
+        let mut feed = backend.psync(1, "127.0.0.1".to_string(), 6380, None);
+        let ping = b"*1\r\n$4\r\nPING\r\n";
+
+        // 发送出去的命令流不再占用缓冲区
+        for _ in 0..3 {
+            backend.feed_replication(ping);
+            assert_eq!(feed.recv().await.unwrap(), &ping[..]);
+        }
+        // 积压超过上限之后 replica 被断开
+        backend.feed_replication(ping);
+        assert_eq!(backend.connected_replicas(), 1);
+        backend.feed_replication(ping);
+        assert_eq!(backend.connected_replicas(), 0);
+        assert_eq!(feed.recv().await, None);
+    }
+}
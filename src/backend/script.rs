@@ -0,0 +1,215 @@
+use mlua::Lua;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+// 和 Redis 的 lua-time-limit 默认值一致，单位毫秒
+const DEFAULT_LUA_TIME_LIMIT: u64 = 5000;
+// 有脚本在执行时，异步任务每隔这么久尝试一次获取执行锁
+pub const SCRIPT_BUSY_POLL: Duration = Duration::from_millis(10);
+
+/// EVAL/SCRIPT LOAD 加载过的脚本，sha1 -> 脚本内容，对应 Redis 的 lua_scripts 字典
+///
+/// 和 Redis 一样只保存在内存中，不会随数据一起持久化
+#[derive(Debug, Default)]
+pub struct ScriptCache(Mutex<HashMap<String, String>>);
+
+impl ScriptCache {
+    /// 缓存脚本，返回脚本的 sha1
+    pub fn insert(&self, body: String) -> String {
+        let sha = sha1_hex(body.as_bytes());
+        self.inner().entry(sha.clone()).or_insert(body);
+        sha
+    }
+
+    /// sha1 不区分大小写
+    pub fn get(&self, sha: &str) -> Option<String> {
+        self.inner().get(&sha.to_lowercase()).cloned()
+    }
+
+    pub fn contains(&self, sha: &str) -> bool {
+        self.inner().contains_key(&sha.to_lowercase())
+    }
+
+    pub fn clear(&self) {
+        self.inner().clear();
+    }
+
+    fn inner(&self) -> MutexGuard<'_, HashMap<String, String>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 脚本和函数共用的 Lua 虚拟机，第一次执行脚本时创建，之后一直保留，对应 Redis 的 lctx
+///
+/// 编译好的脚本缓存在虚拟机中，EVAL/EVALSHA 不需要每次重新编译
+#[derive(Debug, Default)]
+pub struct LuaVm {
+    lua: Mutex<Option<Lua>>,
+    // 正在执行的脚本，其他连接不持有执行锁时也可以访问
+    run: Arc<ScriptRun>,
+}
+
+/// 正在执行的脚本的状态，用于 BUSY 和 SCRIPT KILL
+#[derive(Debug)]
+pub struct ScriptRun {
+    current: Mutex<Option<RunningScript>>,
+    // 脚本执行超过这个时间 (毫秒) 之后，其他连接的命令返回 BUSY
+    time_limit: AtomicU64,
+}
+
+#[derive(Debug)]
+struct RunningScript {
+    started: Instant,
+    // FCALL 调用的函数，只能被 FUNCTION KILL 终止
+    function: bool,
+    // 执行过写命令的脚本不能被终止，否则只有一部分写入生效
+    wrote: bool,
+    killed: bool,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum KillError {
+    #[error("NOTBUSY No scripts in execution right now.")]
+    NotBusy,
+    #[error("UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.")]
+    Unkillable,
+}
+
+impl LuaVm {
+    /// 获取虚拟机，还没有创建时为 None。调用方持有执行锁，不会和其他脚本竞争
+    pub fn lock(&self) -> MutexGuard<'_, Option<Lua>> {
+        self.lua.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn run(&self) -> &Arc<ScriptRun> {
+        &self.run
+    }
+}
+
+impl Default for ScriptRun {
+    fn default() -> Self {
+        Self {
+            current: Mutex::new(None),
+            time_limit: AtomicU64::new(DEFAULT_LUA_TIME_LIMIT),
+        }
+    }
+}
+
+impl ScriptRun {
+    pub fn set_time_limit(&self, limit: Duration) {
+        self.time_limit
+            .store(limit.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// 开始执行脚本，function 表示 FCALL 调用的函数
+    pub fn start(&self, function: bool) {
+        *self.current() = Some(RunningScript {
+            started: Instant::now(),
+            function,
+            wrote: false,
+            killed: false,
+        });
+    }
+
+    pub fn finish(&self) {
+        *self.current() = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.current().is_some()
+    }
+
+    /// 脚本中执行了写命令
+    pub fn mark_write(&self) {
+        if let Some(script) = self.current().as_mut() {
+            script.wrote = true;
+        }
+    }
+
+    /// 脚本被 SCRIPT KILL/FUNCTION KILL 终止，虚拟机的 hook 检查之后抛出错误
+    pub fn is_killed(&self) -> bool {
+        self.current().as_ref().is_some_and(|script| script.killed)
+    }
+
+    /// 脚本执行超过 lua-time-limit 时返回 BUSY 错误，这时只能执行 SCRIPT KILL/FUNCTION KILL
+    pub fn busy_error(&self) -> Option<String> {
+        let limit = Duration::from_millis(self.time_limit.load(Ordering::Relaxed));
+        self.current()
+            .as_ref()
+            .filter(|script| script.started.elapsed() >= limit)
+            .map(|script| {
+                format!(
+                    "BUSY Redis is busy running a script. You can only call {} KILL or SHUTDOWN NOSAVE.",
+                    if script.function { "FUNCTION" } else { "SCRIPT" }
+                )
+            })
+    }
+
+    /// SCRIPT KILL (function 为 false) 或者 FUNCTION KILL
+    pub fn kill(&self, function: bool) -> Result<(), KillError> {
+        match self.current().as_mut() {
+            Some(script) if script.function == function => match script.wrote {
+                true => Err(KillError::Unkillable),
+                false => {
+                    script.killed = true;
+                    Ok(())
+                }
+            },
+            _ => Err(KillError::NotBusy),
+        }
+    }
+
+    fn current(&self) -> MutexGuard<'_, Option<RunningScript>> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 40 个字符的小写十六进制 sha1，EVALSHA 和 redis.sha1hex 使用
+pub fn sha1_hex(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_cache() {
+        let cache = ScriptCache::default();
+        let sha = cache.insert("return 1".to_string());
+        assert_eq!(sha, "e0e1f9fabfc9d4800c877a703b823ac0578ff8db");
+        assert_eq!(cache.get(&sha.to_uppercase()).as_deref(), Some("return 1"));
+        assert!(cache.contains(&sha));
+        cache.clear();
+        assert!(!cache.contains(&sha));
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+
+    #[test]
+    fn test_script_run() {
+        let run = ScriptRun::default();
+        assert_eq!(run.kill(false), Err(KillError::NotBusy));
+        run.set_time_limit(Duration::ZERO);
+        run.start(false);
+        assert!(run.busy_error().unwrap().contains("SCRIPT KILL"));
+        // SCRIPT KILL 不能终止函数，FUNCTION KILL 也不能终止脚本
+        assert_eq!(run.kill(true), Err(KillError::NotBusy));
+        run.kill(false).unwrap();
+        assert!(run.is_killed());
+        run.finish();
+        assert!(!run.is_running() && run.busy_error().is_none());
+
+        run.start(true);
+        run.mark_write();
+        assert!(run.busy_error().unwrap().contains("FUNCTION KILL"));
+        assert_eq!(run.kill(true), Err(KillError::Unkillable));
+        assert!(!run.is_killed());
+    }
+}
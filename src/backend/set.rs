@@ -0,0 +1,168 @@
+use super::{parse_i64, Dict};
+
+// 超过这个数量时，从 intset 转换成 hashtable，和 Redis 一样不会再转换回去
+const SET_MAX_INTSET_ENTRIES: usize = 512;
+
+/// set 类型的 value
+///
+/// 所有的元素都是整数时使用 intset：有序的 i64 数组，用二分查找判断元素是否存在；
+/// 出现非整数的元素或者元素太多时使用字典
+#[derive(Debug, Clone, PartialEq)]
+pub enum Set {
+    IntSet(Vec<i64>),
+    Table(Dict<()>),
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Set::IntSet(Vec::new())
+    }
+}
+
+impl Set {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Set::IntSet(ints) => ints.len(),
+            Set::Table(dict) => dict.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 当前使用的编码，和 OBJECT ENCODING 的返回值一致
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Set::IntSet(_) => "intset",
+            Set::Table(_) => "hashtable",
+        }
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            Set::IntSet(ints) => parse_i64(member).is_some_and(|v| ints.binary_search(&v).is_ok()),
+            Set::Table(dict) => dict.contains_key(member),
+        }
+    }
+
+    /// 添加元素，元素原本不存在时返回 true
+    pub fn insert(&mut self, member: Vec<u8>) -> bool {
+        if let Set::IntSet(ints) = self {
+            if let Some(v) = parse_i64(&member) {
+                match ints.binary_search(&v) {
+                    Ok(_) => return false,
+                    Err(pos) if ints.len() < SET_MAX_INTSET_ENTRIES => {
+                        ints.insert(pos, v);
+                        return true;
+                    }
+                    Err(_) => {}
+                }
+            }
+            self.convert();
+        }
+        match self {
+            Set::Table(dict) => dict.insert(member, ()).is_none(),
+            Set::IntSet(_) => unreachable!("set should be converted to hashtable"),
+        }
+    }
+
+    /// 删除元素，元素存在时返回 true
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            Set::IntSet(ints) => match parse_i64(member).map(|v| ints.binary_search(&v)) {
+                Some(Ok(pos)) => {
+                    ints.remove(pos);
+                    true
+                }
+                _ => false,
+            },
+            Set::Table(dict) => dict.remove(member).is_some(),
+        }
+    }
+
+    /// 所有的元素，intset 编码时按照从小到大的顺序
+    pub fn members(&self) -> Vec<Vec<u8>> {
+        match self {
+            Set::IntSet(ints) => ints.iter().map(|v| v.to_string().into_bytes()).collect(),
+            Set::Table(dict) => dict.iter().map(|(k, _)| k.to_vec()).collect(),
+        }
+    }
+
+    /// 按照下标获取元素，用于随机取元素
+    pub fn get_index(&self, i: usize) -> Option<Vec<u8>> {
+        match self {
+            Set::IntSet(ints) => ints.get(i).map(|v| v.to_string().into_bytes()),
+            Set::Table(dict) => dict.get_index(i).map(|(k, _)| k.to_vec()),
+        }
+    }
+
+    fn convert(&mut self) {
+        if let Set::IntSet(ints) = self {
+            let dict = ints
+                .iter()
+                .map(|v| (v.to_string().into_bytes(), ()))
+                .collect();
+            *self = Set::Table(dict);
+        }
+    }
+}
+
+impl FromIterator<Vec<u8>> for Set {
+    fn from_iter<I: IntoIterator<Item = Vec<u8>>>(iter: I) -> Self {
+        let mut set = Set::new();
+        for member in iter {
+            set.insert(member);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intset() {
+        let mut set: Set = ["3", "1", "2", "1"]
+            .iter()
+            .map(|v| v.as_bytes().to_vec())
+            .collect();
+        assert_eq!(set.encoding(), "intset");
+        assert_eq!(set.len(), 3);
+        assert_eq!(
+            set.members(),
+            vec![b"1".to_vec(), b"2".to_vec(), b"3".to_vec()]
+        );
+        assert!(set.contains(b"2"));
+        // 和 Redis 一样，"02" 不是整数的规范形式，不会被当成 2
+        assert!(!set.contains(b"02"));
+        assert!(set.remove(b"2"));
+        assert!(!set.remove(b"2"));
+        assert_eq!(set.get_index(1), Some(b"3".to_vec()));
+    }
+
+    #[test]
+    fn test_set_encoding_conversion() {
+        let mut set = Set::new();
+        assert!(set.insert(b"1".to_vec()));
+        assert!(set.insert(b"a".to_vec()));
+        assert_eq!(set.encoding(), "hashtable");
+        assert!(set.contains(b"1"));
+        assert!(!set.insert(b"1".to_vec()));
+        assert!(set.remove(b"1"));
+        assert_eq!(set.encoding(), "hashtable");
+
+        let mut set: Set = (0..SET_MAX_INTSET_ENTRIES)
+            .map(|i| i.to_string().into_bytes())
+            .collect();
+        assert_eq!(set.encoding(), "intset");
+        set.insert(b"-1".to_vec());
+        assert_eq!(set.encoding(), "hashtable");
+        assert_eq!(set.len(), SET_MAX_INTSET_ENTRIES + 1);
+    }
+}
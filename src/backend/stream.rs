@@ -0,0 +1,894 @@
+use super::Rax;
+use std::{collections::BTreeMap, fmt, ops::Bound};
+
+// 和 Redis 的 stream-node-max-entries 默认值一致，近似裁剪时按这个数量整批删除
+pub const STREAM_NODE_MAX_ENTRIES: usize = 100;
+
+/// stream entry 的 ID，由毫秒时间戳和同一毫秒内的序号组成，格式为 `ms-seq`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+/// 一条 entry 的 field value 列表，保持添加时的顺序
+pub type StreamFields = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// 裁剪 stream 的方式，对应 XADD/XTRIM 的 MAXLEN 和 MINID
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimStrategy {
+    // 最多保留多少条 entry
+    MaxLen(usize),
+    // 删除 ID 小于它的 entry
+    MinId(StreamId),
+}
+
+/// 已经分发给消费者、还没有被确认的 entry，对应 Redis 的 streamNACK
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEntry {
+    pub consumer: String,
+    // 最后一次分发的时间 (unix 毫秒)
+    pub delivery_time: u64,
+    // 分发的次数
+    pub delivery_count: u64,
+}
+
+/// 消费组中的消费者
+#[derive(Debug, Clone, PartialEq)]
+pub struct Consumer {
+    // 最后一次尝试读取或者认领的时间
+    pub seen_time: u64,
+    // 最后一次成功读取或者认领到 entry 的时间，None 表示从来没有过
+    pub active_time: Option<u64>,
+    // 分发给这个消费者、还没有被确认的 entry 的 ID
+    pending: Rax<()>,
+}
+
+/// 消费组，对应 Redis 的 streamCG
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumerGroup {
+    // 最后一条分发给消费者的 entry 的 ID
+    last_id: StreamId,
+    // 消费组已经读取的 entry 数量，用于计算 lag，None 表示无法确定
+    entries_read: Option<u64>,
+    // 整个消费组的 pending entries list (PEL)，按照 ID 排序
+    pending: Rax<PendingEntry>,
+    consumers: BTreeMap<String, Consumer>,
+}
+
+/// XCLAIM/XAUTOCLAIM 认领 pending entry 的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClaimOptions {
+    // 只认领空闲时间不小于 min_idle 毫秒的 entry
+    pub min_idle: u64,
+    // 认领之后 entry 的分发时间
+    pub delivery_time: u64,
+    // 直接设置分发次数，None 时分发次数加一
+    pub retry_count: Option<u64>,
+    // entry 不在 PEL 中时也创建 pending entry
+    pub force: bool,
+    // 只返回 ID，不增加分发次数
+    pub just_id: bool,
+    // 同时把消费组的 last_id 推进到这个 ID
+    pub last_id: Option<StreamId>,
+}
+
+/// XAUTOCLAIM 的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoClaim {
+    // 下一次扫描的起点，扫描完整个 PEL 时为 0-0
+    pub next: StreamId,
+    pub claimed: Vec<(StreamId, StreamFields)>,
+    // entry 已经从 stream 中删除，从 PEL 中移除的 ID
+    pub deleted: Vec<StreamId>,
+}
+
+/// stream 类型的 value
+///
+/// entry 按照 ID 的大端字节序保存在 radix tree 中，ID 是递增的，因此遍历的顺序就是添加的顺序
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stream {
+    entries: Rax<StreamFields>,
+    // 最后添加的 entry 的 ID，entry 被删除之后也不会变小
+    last_id: StreamId,
+    // 被删除的 entry 中最大的 ID
+    max_deleted_id: StreamId,
+    // 添加过的 entry 总数，包括已经被删除的
+    entries_added: u64,
+    // 消费组，按照名字排序
+    groups: BTreeMap<String, ConsumerGroup>,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        Self { ms, seq }
+    }
+
+    /// 解析 `ms-seq` 或者 `ms`，没有 seq 时使用 default_seq
+    pub fn parse(buf: &[u8], default_seq: u64) -> Option<Self> {
+        let s = std::str::from_utf8(buf).ok()?;
+        let (ms, seq) = match s.split_once('-') {
+            Some((ms, seq)) => (ms, parse_u64(seq)?),
+            None => (s, default_seq),
+        };
+        Some(Self::new(parse_u64(ms)?, seq))
+    }
+
+    /// 下一个 ID，已经是最大值时返回 None
+    pub fn next(self) -> Option<Self> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => Some(Self::new(self.ms.checked_add(1)?, 0)),
+        }
+    }
+
+    /// 上一个 ID，已经是最小值时返回 None
+    pub fn prev(self) -> Option<Self> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => Some(Self::new(self.ms.checked_sub(1)?, u64::MAX)),
+        }
+    }
+
+    /// 16 字节的大端表示，rax 的 key 以及 RDB 中使用
+    pub(crate) fn to_bytes(self) -> [u8; 16] {
+        let mut buf = [0; 16];
+        buf[..8].copy_from_slice(&self.ms.to_be_bytes());
+        buf[8..].copy_from_slice(&self.seq.to_be_bytes());
+        buf
+    }
+
+    pub(crate) fn from_bytes(buf: &[u8]) -> Self {
+        let ms = u64::from_be_bytes(buf[..8].try_into().expect("stream id has 16 bytes"));
+        let seq = u64::from_be_bytes(buf[8..].try_into().expect("stream id has 16 bytes"));
+        Self::new(ms, seq)
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl Stream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// 保存 entry 的 radix tree 的节点数量
+    pub fn node_count(&self) -> usize {
+        self.entries.node_count()
+    }
+
+    pub fn max_deleted_id(&self) -> StreamId {
+        self.max_deleted_id
+    }
+
+    pub fn entries_added(&self) -> u64 {
+        self.entries_added
+    }
+
+    /// 从 RDB 加载时恢复元数据，entry 已经通过 insert 添加
+    pub fn restore_meta(
+        &mut self,
+        last_id: StreamId,
+        max_deleted_id: StreamId,
+        entries_added: u64,
+    ) {
+        self.last_id = last_id;
+        self.max_deleted_id = max_deleted_id;
+        self.entries_added = entries_added;
+    }
+
+    /// 自动生成的下一个 ID：当前时间大于最后一个 ID 的时间时使用当前时间，否则序号加一
+    pub fn next_id(&self, now: u64) -> Option<StreamId> {
+        match now > self.last_id.ms {
+            true => Some(StreamId::new(now, 0)),
+            false => self.last_id.next(),
+        }
+    }
+
+    /// `ms-*` 形式的 ID：ms 和最后一个 ID 相同时序号加一，否则序号为 0
+    pub fn next_id_with_ms(&self, ms: u64) -> Option<StreamId> {
+        match ms.cmp(&self.last_id.ms) {
+            std::cmp::Ordering::Greater => Some(StreamId::new(ms, 0)),
+            std::cmp::Ordering::Equal => Some(StreamId::new(ms, self.last_id.seq.checked_add(1)?)),
+            std::cmp::Ordering::Less => None,
+        }
+    }
+
+    /// 添加 entry，ID 必须大于最后一个 ID (空的 stream 中必须大于 0-0)，否则返回 false
+    pub fn insert(&mut self, id: StreamId, fields: StreamFields) -> bool {
+        if id <= self.last_id {
+            return false;
+        }
+        self.entries.insert(&id.to_bytes(), fields);
+        self.last_id = id;
+        self.entries_added += 1;
+        true
+    }
+
+    pub fn get(&self, id: StreamId) -> Option<&StreamFields> {
+        self.entries.get(&id.to_bytes())
+    }
+
+    /// 删除 entry，entry 存在时返回 true
+    pub fn remove(&mut self, id: StreamId) -> bool {
+        if self.entries.remove(&id.to_bytes()).is_none() {
+            return false;
+        }
+        self.max_deleted_id = self.max_deleted_id.max(id);
+        true
+    }
+
+    pub fn first_entry(&self) -> Option<(StreamId, &StreamFields)> {
+        let (key, fields) = self.entries.first()?;
+        Some((StreamId::from_bytes(&key), fields))
+    }
+
+    pub fn last_entry(&self) -> Option<(StreamId, &StreamFields)> {
+        let (key, fields) = self.entries.last()?;
+        Some((StreamId::from_bytes(&key), fields))
+    }
+
+    /// 范围内的 entry，rev 为 true 时从后往前，最多返回 count 条
+    pub fn range(
+        &self,
+        start: Bound<StreamId>,
+        end: Bound<StreamId>,
+        rev: bool,
+        count: Option<usize>,
+    ) -> Vec<(StreamId, StreamFields)> {
+        let count = count.unwrap_or(usize::MAX);
+        let mut ret = Vec::new();
+        if count == 0 {
+            return ret;
+        }
+        let (start, end) = (start.map(StreamId::to_bytes), end.map(StreamId::to_bytes));
+        self.entries.range(
+            start.as_ref().map(|v| v.as_slice()),
+            end.as_ref().map(|v| v.as_slice()),
+            rev,
+            |key, fields| {
+                ret.push((StreamId::from_bytes(key), fields.clone()));
+                ret.len() < count
+            },
+        );
+        ret
+    }
+
+    /// 裁剪 stream，返回删除的数量
+    ///
+    /// approx 为 true 时和 Redis 删除整个 listpack 节点一样，每次删除 STREAM_NODE_MAX_ENTRIES 条，
+    /// 剩下的不足一批的 entry 保留；limit 限制最多删除的数量，None 表示不限制。
+    /// 近似裁剪默认的 limit (100 个节点) 由命令解析时填入
+    pub fn trim(&mut self, strategy: TrimStrategy, approx: bool, limit: Option<usize>) -> usize {
+        let mut n = match strategy {
+            TrimStrategy::MaxLen(maxlen) => self.len().saturating_sub(maxlen),
+            TrimStrategy::MinId(min_id) => {
+                let mut n = 0;
+                self.entries.range(
+                    Bound::Unbounded,
+                    Bound::Excluded(&min_id.to_bytes()),
+                    false,
+                    |_, _| {
+                        n += 1;
+                        true
+                    },
+                );
+                n
+            }
+        };
+        if approx {
+            n -= n % STREAM_NODE_MAX_ENTRIES;
+        }
+        if let Some(limit) = limit {
+            n = n.min(limit);
+        }
+        if n == 0 {
+            return 0;
+        }
+        // 只收集 ID，不复制 entry 的内容
+        let mut ids = Vec::with_capacity(n);
+        self.entries
+            .range(Bound::Unbounded, Bound::Unbounded, false, |key, _| {
+                ids.push(StreamId::from_bytes(key));
+                ids.len() < n
+            });
+        for id in ids {
+            self.remove(id);
+        }
+        n
+    }
+}
+
+impl Stream {
+    /// 创建消费组，消费组已经存在时返回 false
+    pub fn create_group(
+        &mut self,
+        name: &str,
+        last_id: StreamId,
+        entries_read: Option<u64>,
+    ) -> bool {
+        if self.groups.contains_key(name) {
+            return false;
+        }
+        let group = ConsumerGroup {
+            last_id,
+            entries_read,
+            pending: Rax::new(),
+            consumers: BTreeMap::new(),
+        };
+        self.groups.insert(name.to_string(), group);
+        true
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    pub fn group(&self, name: &str) -> Option<&ConsumerGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut ConsumerGroup> {
+        self.groups.get_mut(name)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&String, &ConsumerGroup)> {
+        self.groups.iter()
+    }
+
+    /// 消费组还没有读取的 entry 数量，无法确定时返回 None
+    pub fn group_lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        let entries_read = match group.entries_read {
+            Some(n) if !self.has_tombstones_after(group.last_id) => n,
+            _ => self.estimate_entries_read(group.last_id)?,
+        };
+        Some(self.entries_added.saturating_sub(entries_read))
+    }
+
+    /// XREADGROUP 读取新的 entry：分发 ID 大于消费组 last_id 的 entry，noack 为 false 时加入 PEL。
+    /// 消费组不存在时返回 None
+    pub fn read_group(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        count: Option<usize>,
+        noack: bool,
+        now: u64,
+    ) -> Option<Vec<(StreamId, StreamFields)>> {
+        let (last_id, mut entries_read) = {
+            let group = self.groups.get(group)?;
+            (group.last_id, group.entries_read)
+        };
+        let entries = self.range(Bound::Excluded(last_id), Bound::Unbounded, false, count);
+        for (id, _) in &entries {
+            // 后面没有被删除的 entry 时读取的数量可以直接加一，否则重新估算
+            entries_read = match entries_read {
+                Some(n) if !self.has_tombstones_after(*id) => Some(n + 1),
+                _ => self.estimate_entries_read(*id),
+            };
+        }
+
+        let group = self.groups.get_mut(group)?;
+        group.seen_consumer(consumer, now);
+        if let Some((id, _)) = entries.last() {
+            group.last_id = *id;
+            group.entries_read = entries_read;
+            group.active_consumer(consumer, now);
+        }
+        if !noack {
+            for (id, _) in &entries {
+                group.assign(*id, consumer, now, 1);
+            }
+        }
+        Some(entries)
+    }
+
+    /// XREADGROUP 读取历史：返回消费者 PEL 中 ID 大于 start 的 entry，同时更新分发时间和次数。
+    /// entry 已经从 stream 中删除时 fields 为 None。消费组不存在时返回 None
+    pub fn read_pending(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        start: StreamId,
+        count: Option<usize>,
+        now: u64,
+    ) -> Option<Vec<(StreamId, Option<StreamFields>)>> {
+        let group = self.groups.get_mut(group)?;
+        let consumer = group.seen_consumer(consumer, now);
+        let ids = collect_ids(&consumer.pending, Bound::Excluded(start), count);
+        let ret = ids
+            .into_iter()
+            .map(|id| {
+                let fields = self.entries.get(&id.to_bytes()).cloned();
+                if fields.is_some() {
+                    if let Some(entry) = group.pending.get_mut(&id.to_bytes()) {
+                        entry.delivery_time = now;
+                        entry.delivery_count += 1;
+                    }
+                }
+                (id, fields)
+            })
+            .collect();
+        Some(ret)
+    }
+
+    /// XCLAIM：把 ids 中空闲时间足够长的 pending entry 转移给 consumer，返回认领成功的 entry。
+    /// entry 已经从 stream 中删除时直接从 PEL 中移除。消费组不存在时返回 None
+    pub fn claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        ids: &[StreamId],
+        opts: &ClaimOptions,
+        now: u64,
+    ) -> Option<Vec<(StreamId, StreamFields)>> {
+        let group = self.groups.get_mut(group)?;
+        if let Some(last_id) = opts.last_id {
+            group.last_id = group.last_id.max(last_id);
+        }
+        group.seen_consumer(consumer, now);
+        let mut ret = Vec::new();
+        for id in ids {
+            let Some(fields) = self.entries.get(&id.to_bytes()) else {
+                group.ack(*id);
+                continue;
+            };
+            let delivery_count = match group.pending.get(&id.to_bytes()) {
+                Some(entry) if now.saturating_sub(entry.delivery_time) < opts.min_idle => continue,
+                Some(entry) => entry.delivery_count,
+                None if opts.force => 0,
+                None => continue,
+            };
+            let delivery_count = match opts.retry_count {
+                Some(n) => n,
+                None if opts.just_id => delivery_count,
+                None => delivery_count + 1,
+            };
+            group.assign(*id, consumer, opts.delivery_time, delivery_count);
+            group.active_consumer(consumer, now);
+            ret.push((*id, fields.clone()));
+        }
+        Some(ret)
+    }
+
+    /// XAUTOCLAIM：从 start 开始扫描 PEL，认领最多 count 条空闲时间足够长的 entry。
+    /// 和 Redis 一样最多检查 count * 10 条 pending entry。消费组不存在时返回 None
+    pub fn auto_claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        start: Bound<StreamId>,
+        count: usize,
+        opts: &ClaimOptions,
+        now: u64,
+    ) -> Option<AutoClaim> {
+        let group = self.groups.get_mut(group)?;
+        group.seen_consumer(consumer, now);
+        let mut attempts = count.saturating_mul(10);
+        let mut count = count;
+        let mut ret = AutoClaim {
+            next: StreamId::MIN,
+            claimed: Vec::new(),
+            deleted: Vec::new(),
+        };
+        let ids = collect_ids(&group.pending, start, Some(attempts.saturating_add(1)));
+        for id in ids {
+            if attempts == 0 || count == 0 {
+                ret.next = id;
+                break;
+            }
+            attempts -= 1;
+            let Some(fields) = self.entries.get(&id.to_bytes()) else {
+                group.ack(id);
+                ret.deleted.push(id);
+                count -= 1;
+                continue;
+            };
+            let Some(entry) = group.pending.get(&id.to_bytes()) else {
+                continue;
+            };
+            if now.saturating_sub(entry.delivery_time) < opts.min_idle {
+                continue;
+            }
+            let delivery_count = entry.delivery_count + !opts.just_id as u64;
+            group.assign(id, consumer, opts.delivery_time, delivery_count);
+            group.active_consumer(consumer, now);
+            ret.claimed.push((id, fields.clone()));
+            count -= 1;
+        }
+        Some(ret)
+    }
+
+    /// ID 不小于 start 的范围内是否有被删除的 entry
+    fn has_tombstones_after(&self, start: StreamId) -> bool {
+        !self.is_empty() && self.max_deleted_id != StreamId::MIN && start <= self.max_deleted_id
+    }
+
+    /// 估算 id 是 stream 中添加的第几条 entry，参考 Redis 的 streamEstimateDistanceFromFirstEverEntry
+    fn estimate_entries_read(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        if id == self.last_id || (self.is_empty() && id < self.last_id) {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+        let (first_id, _) = self.first_entry()?;
+        // 第一条 entry 之前没有被删除的 entry 时才能确定
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first_id {
+            let before_first = self.entries_added - self.len() as u64;
+            if id < first_id {
+                return Some(before_first);
+            }
+            if id == first_id {
+                return Some(before_first + 1);
+            }
+        }
+        None
+    }
+}
+
+impl ConsumerGroup {
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    pub fn entries_read(&self) -> Option<u64> {
+        self.entries_read
+    }
+
+    /// 从 RDB 加载时恢复 pending entry，同时添加到消费者的 PEL 中
+    pub fn restore_pending(&mut self, id: StreamId, entry: PendingEntry) {
+        self.assign(
+            id,
+            &entry.consumer,
+            entry.delivery_time,
+            entry.delivery_count,
+        );
+    }
+
+    /// XGROUP SETID，修改最后分发的 ID
+    pub fn set_last_id(&mut self, id: StreamId, entries_read: Option<u64>) {
+        self.last_id = id;
+        self.entries_read = entries_read;
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn consumers(&self) -> impl Iterator<Item = (&String, &Consumer)> {
+        self.consumers.iter()
+    }
+
+    pub fn consumer(&self, name: &str) -> Option<&Consumer> {
+        self.consumers.get(name)
+    }
+
+    /// 创建消费者，已经存在时返回 false
+    pub fn create_consumer(&mut self, name: &str, now: u64) -> bool {
+        if self.consumers.contains_key(name) {
+            return false;
+        }
+        self.consumers.insert(name.to_string(), Consumer::new(now));
+        true
+    }
+
+    /// 删除消费者以及它的 pending entry，返回删除的 pending entry 数量，消费者不存在时返回 None
+    pub fn remove_consumer(&mut self, name: &str) -> Option<usize> {
+        let consumer = self.consumers.remove(name)?;
+        let ids = collect_ids(&consumer.pending, Bound::Unbounded, None);
+        for id in &ids {
+            self.pending.remove(&id.to_bytes());
+        }
+        Some(ids.len())
+    }
+
+    /// 确认 entry，从 PEL 中移除，entry 不在 PEL 中时返回 false
+    pub fn ack(&mut self, id: StreamId) -> bool {
+        let key = id.to_bytes();
+        let Some(entry) = self.pending.remove(&key) else {
+            return false;
+        };
+        if let Some(consumer) = self.consumers.get_mut(&entry.consumer) {
+            consumer.pending.remove(&key);
+        }
+        true
+    }
+
+    pub fn pending_entry(&self, id: StreamId) -> Option<&PendingEntry> {
+        self.pending.get(&id.to_bytes())
+    }
+
+    pub fn first_pending(&self) -> Option<StreamId> {
+        self.pending
+            .first()
+            .map(|(key, _)| StreamId::from_bytes(&key))
+    }
+
+    pub fn last_pending(&self) -> Option<StreamId> {
+        self.pending
+            .last()
+            .map(|(key, _)| StreamId::from_bytes(&key))
+    }
+
+    /// 按照 ID 顺序遍历范围内的 pending entry，consumer 不为 None 时只遍历这个消费者的，
+    /// visit 返回 false 时停止
+    pub fn pending_range(
+        &self,
+        start: Bound<StreamId>,
+        end: Bound<StreamId>,
+        consumer: Option<&str>,
+        mut visit: impl FnMut(StreamId, &PendingEntry) -> bool,
+    ) {
+        let (start, end) = (start.map(StreamId::to_bytes), end.map(StreamId::to_bytes));
+        let (start, end) = (
+            start.as_ref().map(|v| v.as_slice()),
+            end.as_ref().map(|v| v.as_slice()),
+        );
+        match consumer {
+            Some(name) => {
+                let Some(consumer) = self.consumers.get(name) else {
+                    return;
+                };
+                consumer
+                    .pending
+                    .range(start, end, false, |key, _| match self.pending.get(key) {
+                        Some(entry) => visit(StreamId::from_bytes(key), entry),
+                        None => true,
+                    });
+            }
+            None => self.pending.range(start, end, false, |key, entry| {
+                visit(StreamId::from_bytes(key), entry)
+            }),
+        }
+    }
+
+    /// 获取消费者并更新 seen_time，不存在时创建
+    pub fn seen_consumer(&mut self, name: &str, now: u64) -> &mut Consumer {
+        let consumer = self
+            .consumers
+            .entry(name.to_string())
+            .or_insert_with(|| Consumer::new(now));
+        consumer.seen_time = now;
+        consumer
+    }
+
+    fn active_consumer(&mut self, name: &str, now: u64) {
+        if let Some(consumer) = self.consumers.get_mut(name) {
+            consumer.active_time = Some(now);
+        }
+    }
+
+    /// 把 entry 分发给 consumer，entry 已经属于其他消费者时转移过来
+    fn assign(&mut self, id: StreamId, consumer: &str, delivery_time: u64, delivery_count: u64) {
+        let key = id.to_bytes();
+        let entry = PendingEntry {
+            consumer: consumer.to_string(),
+            delivery_time,
+            delivery_count,
+        };
+        if let Some(old) = self.pending.insert(&key, entry) {
+            if old.consumer != consumer {
+                if let Some(old) = self.consumers.get_mut(&old.consumer) {
+                    old.pending.remove(&key);
+                }
+            }
+        }
+        self.consumers
+            .entry(consumer.to_string())
+            .or_insert_with(|| Consumer::new(delivery_time))
+            .pending
+            .insert(&key, ());
+    }
+}
+
+impl Consumer {
+    fn new(now: u64) -> Self {
+        Self {
+            seen_time: now,
+            active_time: None,
+            pending: Rax::new(),
+        }
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// rax 中从 start 开始的最多 count 个 ID
+fn collect_ids<V>(rax: &Rax<V>, start: Bound<StreamId>, count: Option<usize>) -> Vec<StreamId> {
+    let count = count.unwrap_or(usize::MAX);
+    let mut ids = Vec::new();
+    if count == 0 {
+        return ids;
+    }
+    let start = start.map(StreamId::to_bytes);
+    rax.range(
+        start.as_ref().map(|v| v.as_slice()),
+        Bound::Unbounded,
+        false,
+        |key, _| {
+            ids.push(StreamId::from_bytes(key));
+            ids.len() < count
+        },
+    );
+    ids
+}
+
+/// 只允许数字，不允许符号和空格
+fn parse_u64(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(v: &str) -> StreamFields {
+        vec![(b"f".to_vec(), v.as_bytes().to_vec())]
+    }
+
+    #[test]
+    fn test_stream_id() {
+        assert_eq!(StreamId::parse(b"5-3", 0), Some(StreamId::new(5, 3)));
+        assert_eq!(
+            StreamId::parse(b"5", u64::MAX),
+            Some(StreamId::new(5, u64::MAX))
+        );
+        assert_eq!(StreamId::parse(b"5-", 0), None);
+        assert_eq!(StreamId::parse(b"-5", 0), None);
+        assert_eq!(StreamId::parse(b"+5", 0), None);
+        assert_eq!(StreamId::new(5, u64::MAX).next(), Some(StreamId::new(6, 0)));
+        assert_eq!(StreamId::MAX.next(), None);
+        assert_eq!(StreamId::new(5, 0).prev(), Some(StreamId::new(4, u64::MAX)));
+        assert_eq!(StreamId::MIN.prev(), None);
+        assert_eq!(StreamId::new(1, 2).to_string(), "1-2");
+        assert!(StreamId::new(1, 2) < StreamId::new(2, 0));
+    }
+
+    #[test]
+    fn test_stream_insert_range() {
+        let mut stream = Stream::new();
+        assert!(!stream.insert(StreamId::MIN, fields("a")));
+        assert_eq!(stream.next_id_with_ms(0), Some(StreamId::new(0, 1)));
+        assert_eq!(stream.next_id(10), Some(StreamId::new(10, 0)));
+        for i in 1..=5 {
+            assert!(stream.insert(StreamId::new(10, i), fields(&i.to_string())));
+        }
+        assert!(!stream.insert(StreamId::new(10, 5), fields("x")));
+        assert_eq!(stream.next_id(5), Some(StreamId::new(10, 6)));
+        assert_eq!(stream.next_id_with_ms(10), Some(StreamId::new(10, 6)));
+        assert_eq!(stream.next_id_with_ms(9), None);
+
+        let ids = |entries: Vec<(StreamId, StreamFields)>| {
+            entries
+                .into_iter()
+                .map(|(id, _)| id.seq)
+                .collect::<Vec<_>>()
+        };
+        let all = stream.range(Bound::Unbounded, Bound::Unbounded, false, None);
+        assert_eq!(ids(all), vec![1, 2, 3, 4, 5]);
+        let start = Bound::Excluded(StreamId::new(10, 2));
+        let rev = stream.range(start, Bound::Unbounded, true, Some(2));
+        assert_eq!(ids(rev), vec![5, 4]);
+
+        assert!(stream.remove(StreamId::new(10, 3)));
+        assert!(!stream.remove(StreamId::new(10, 3)));
+        assert_eq!(stream.max_deleted_id(), StreamId::new(10, 3));
+        assert_eq!(stream.len(), 4);
+        assert_eq!(stream.entries_added(), 5);
+        assert_eq!(stream.first_entry().map(|(id, _)| id.seq), Some(1));
+        assert_eq!(
+            stream.last_entry(),
+            Some((StreamId::new(10, 5), &fields("5")))
+        );
+    }
+
+    #[test]
+    fn test_stream_trim() {
+        let mut stream = Stream::new();
+        for i in 1..=250 {
+            stream.insert(StreamId::new(i, 0), fields("v"));
+        }
+        // 近似裁剪只删除整批的 entry
+        assert_eq!(stream.trim(TrimStrategy::MaxLen(100), true, None), 100);
+        assert_eq!(stream.len(), 150);
+        assert_eq!(stream.trim(TrimStrategy::MaxLen(100), true, None), 0);
+        assert_eq!(stream.trim(TrimStrategy::MaxLen(100), false, Some(10)), 10);
+        assert_eq!(stream.trim(TrimStrategy::MaxLen(100), false, None), 40);
+        assert_eq!(stream.first_entry().map(|(id, _)| id.ms), Some(151));
+
+        let min_id = StreamId::new(201, 0);
+        assert_eq!(stream.trim(TrimStrategy::MinId(min_id), false, None), 50);
+        assert_eq!(stream.first_entry().map(|(id, _)| id), Some(min_id));
+        assert_eq!(stream.max_deleted_id(), StreamId::new(200, 0));
+    }
+
+    #[test]
+    fn test_stream_groups() {
+        let mut stream = Stream::new();
+        for i in 1..=5 {
+            stream.insert(StreamId::new(i, 0), fields(&i.to_string()));
+        }
+        assert!(stream.create_group("g", StreamId::MIN, None));
+        assert!(!stream.create_group("g", StreamId::MIN, None));
+        assert_eq!(stream.read_group("missing", "a", None, false, 0), None);
+
+        let entries = stream.read_group("g", "a", Some(2), false, 100).unwrap();
+        assert_eq!(entries.len(), 2);
+        let group = stream.group("g").unwrap();
+        assert_eq!(group.last_id(), StreamId::new(2, 0));
+        assert_eq!(group.entries_read(), Some(2));
+        assert_eq!(stream.group_lag(group), Some(3));
+        assert_eq!(group.consumer("a").map(|c| c.pending_len()), Some(2));
+
+        // 认领之后 entry 转移到新的消费者
+        let opts = ClaimOptions {
+            min_idle: 50,
+            delivery_time: 200,
+            retry_count: None,
+            force: false,
+            just_id: false,
+            last_id: None,
+        };
+        let ids = [StreamId::new(1, 0)];
+        assert_eq!(stream.claim("g", "b", &ids, &opts, 120).unwrap().len(), 0);
+        assert_eq!(stream.claim("g", "b", &ids, &opts, 200).unwrap().len(), 1);
+        let group = stream.group("g").unwrap();
+        let entry = group.pending_entry(StreamId::new(1, 0)).unwrap();
+        assert_eq!(entry.consumer, "b");
+        assert_eq!(entry.delivery_count, 2);
+        assert_eq!(group.consumer("a").map(|c| c.pending_len()), Some(1));
+
+        // 删除了第一条 entry 之后仍然可以推算出读取的数量
+        stream.remove(StreamId::new(1, 0));
+        let entries = stream.read_group("g", "a", None, true, 300).unwrap();
+        assert_eq!(entries.len(), 3);
+        let group = stream.group("g").unwrap();
+        assert_eq!(group.entries_read(), Some(5));
+        assert_eq!(stream.group_lag(group), Some(0));
+        assert_eq!(group.pending_len(), 2);
+
+        let pending = stream
+            .read_pending("g", "b", StreamId::MIN, None, 400)
+            .unwrap();
+        assert_eq!(pending, vec![(StreamId::new(1, 0), None)]);
+        let group = stream.group_mut("g").unwrap();
+        assert_eq!(group.remove_consumer("b"), Some(1));
+        assert!(group.ack(StreamId::new(2, 0)));
+        assert_eq!(group.pending_len(), 0);
+        assert!(stream.remove_group("g"));
+    }
+}
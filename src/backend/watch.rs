@@ -0,0 +1,116 @@
+use super::Backend;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+/// 被 WATCH 的 key 的修改版本，对应 Redis 的 watched_keys 字典
+///
+/// 只有被 WATCH 的 key 才需要记录版本：WATCH 时记下当前的版本，EXEC 时版本变了说明 key 被修改、
+/// 删除或者过期了。key 不再被任何连接 WATCH 时移除，删除的 key 也不会一直占用内存
+#[derive(Debug, Default)]
+pub struct Versions {
+    inner: Mutex<HashMap<Vec<u8>, WatchedKey>>,
+    // 被 WATCH 的 key 的数量，没有 key 被 WATCH 时写入不需要加锁
+    watched: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct WatchedKey {
+    // WATCH 这个 key 的连接数
+    watchers: usize,
+    version: u64,
+}
+
+impl Versions {
+    /// 开始 WATCH key，返回 key 当前的版本
+    pub fn watch(&self, key: &[u8]) -> u64 {
+        let mut inner = self.inner();
+        let entry = inner.entry(key.to_vec()).or_insert_with(|| {
+            self.watched.fetch_add(1, Ordering::Relaxed);
+            WatchedKey::default()
+        });
+        entry.watchers += 1;
+        entry.version
+    }
+
+    pub fn unwatch(&self, key: &[u8]) {
+        let mut inner = self.inner();
+        if let Some(entry) = inner.get_mut(key) {
+            entry.watchers -= 1;
+            if entry.watchers == 0 {
+                inner.remove(key);
+                self.watched.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// key 当前的版本，调用方需要先 WATCH 这个 key
+    pub fn version(&self, key: &[u8]) -> Option<u64> {
+        self.inner().get(key).map(|entry| entry.version)
+    }
+
+    fn touch(&self, key: &[u8]) {
+        if self.watched.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        if let Some(entry) = self.inner().get_mut(key) {
+            entry.version += 1;
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, HashMap<Vec<u8>, WatchedKey>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Backend {
+    /// 标记 key 被修改，对应 Redis 的 signalModifiedKey，同时计入上次保存之后的修改次数
+    pub fn signal_modified_key(&self, key: &[u8]) {
+        self.versions.touch(key);
+        self.mark_dirty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{List, Value};
+
+    #[test]
+    fn test_watch_versions() {
+        let backend = Backend::new();
+        let v1 = backend.versions.watch(b"a");
+        let v2 = backend.versions.watch(b"a");
+        assert_eq!(v1, v2);
+
+        // 没有被 WATCH 的 key 不记录版本
+        backend.set(b"b".to_vec(), Value::String(b"1".to_vec()));
+        assert_eq!(backend.versions.version(b"b"), None);
+
+        backend
+            .write_value(b"a", true, |list: &mut List| {
+                list.push_back(b"x".to_vec());
+                ((), true)
+            })
+            .unwrap();
+        let v3 = backend.versions.version(b"a").unwrap();
+        assert_ne!(v3, v1);
+
+        // 没有修改集合时版本不变
+        backend
+            .write_value(b"a", false, |list: &mut List| (list.len(), false))
+            .unwrap();
+        assert_eq!(backend.versions.version(b"a"), Some(v3));
+        backend.remove(b"a");
+        assert_ne!(backend.versions.version(b"a"), Some(v3));
+
+        backend.versions.unwatch(b"a");
+        assert!(backend.versions.version(b"a").is_some());
+        backend.versions.unwatch(b"a");
+        assert_eq!(backend.versions.version(b"a"), None);
+    }
+}
@@ -0,0 +1,675 @@
+use rand::Rng;
+use std::{cmp::Ordering, collections::HashMap, ops::Bound};
+
+// 和 Redis 一样，最多 32 层，每一层的节点以 1/4 的概率出现在上一层
+const ZSKIPLIST_MAXLEVEL: usize = 32;
+const ZSKIPLIST_P: f64 = 0.25;
+// 头节点在 nodes 中的下标
+const HEADER: usize = 0;
+
+/// sorted set 类型的 value，对应 Redis 的 skiplist 编码
+///
+/// 字典保存 member -> score，用于 O(1) 查找分数；跳表按照 (score, member) 排序，
+/// 每一层的指针都记录了跨越的节点数 (span)，因此按排名查找和计算排名都是 O(log n) 的
+#[derive(Debug, Clone, Default)]
+pub struct ZSet {
+    dict: HashMap<Vec<u8>, f64>,
+    list: SkipList,
+}
+
+/// 分数范围，对应 ZRANGE BYSCORE/ZCOUNT 的 min max，`(` 前缀表示不包含
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreRange {
+    pub min: Bound<f64>,
+    pub max: Bound<f64>,
+}
+
+/// 字典序范围，对应 ZRANGE BYLEX/ZLEXCOUNT 的 min max
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexRange {
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+/// 字典序范围的边界，`-`/`+` 表示无穷小/无穷大，`[`/`(` 前缀表示包含/不包含
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexBound {
+    NegInf,
+    PosInf,
+    Included(Vec<u8>),
+    Excluded(Vec<u8>),
+}
+
+/// 跳表，节点保存在 Vec 中，用下标代替指针，删除的节点放入 free 中复用
+#[derive(Debug, Clone)]
+struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    tail: Option<usize>,
+    len: usize,
+    level: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    member: Vec<u8>,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    forward: Option<usize>,
+    // 到 forward 节点跨越的节点数
+    span: usize,
+}
+
+impl PartialEq for ZSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.dict == other.dict
+    }
+}
+
+impl ZSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dict.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dict.is_empty()
+    }
+
+    /// 当前使用的编码，和 OBJECT ENCODING 的返回值一致
+    pub fn encoding(&self) -> &'static str {
+        "skiplist"
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.dict.get(member).copied()
+    }
+
+    /// 写入 member，新增 member 时返回 true
+    pub fn insert(&mut self, member: Vec<u8>, score: f64) -> bool {
+        match self.dict.get_mut(&member) {
+            Some(current) => {
+                if *current != score {
+                    // 分数变化时需要重新排序
+                    let old = std::mem::replace(current, score);
+                    self.list.delete(old, &member);
+                    self.list.insert(score, member);
+                }
+                false
+            }
+            None => {
+                self.dict.insert(member.clone(), score);
+                self.list.insert(score, member);
+                true
+            }
+        }
+    }
+
+    /// 删除 member，member 存在时返回 true
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.dict.remove(member) {
+            Some(score) => self.list.delete(score, member),
+            None => false,
+        }
+    }
+
+    /// member 按照分数从小到大的排名 (从 0 开始)
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        let score = self.score(member)?;
+        self.list.rank(score, member).map(|rank| rank - 1)
+    }
+
+    /// 删除并返回分数最小 (min 为 true) 或者最大的 member
+    pub fn pop(&mut self, min: bool) -> Option<(Vec<u8>, f64)> {
+        let x = if min {
+            self.list.first()
+        } else {
+            self.list.tail
+        }?;
+        let node = &self.list.nodes[x];
+        let (member, score) = (node.member.clone(), node.score);
+        self.remove(&member);
+        Some((member, score))
+    }
+
+    /// 按照排名获取 [start, stop] 之间的 member，rev 为 true 时排名从分数最大的 member 开始
+    pub fn range_by_rank(&self, start: usize, stop: usize, rev: bool) -> Vec<(Vec<u8>, f64)> {
+        if start > stop || start >= self.len() {
+            return Vec::new();
+        }
+        let stop = stop.min(self.len() - 1);
+        let rank = if rev { self.len() - start } else { start + 1 };
+        let first = self.list.get_by_rank(rank);
+        self.list.collect(first, rev, stop - start + 1, |_| true)
+    }
+
+    /// 分数在 range 之间的 member，跳过前 offset 个之后最多返回 limit 个
+    pub fn range_by_score(
+        &self,
+        range: &ScoreRange,
+        rev: bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<(Vec<u8>, f64)> {
+        let first = if rev {
+            self.list
+                .last_in_range(|n| range.lte_max(n.score), |n| range.gte_min(n.score))
+        } else {
+            self.list
+                .first_in_range(|n| range.gte_min(n.score), |n| range.lte_max(n.score))
+        };
+        let first = self.list.skip(first, rev, offset);
+        let check = |n: &Node| {
+            if rev {
+                range.gte_min(n.score)
+            } else {
+                range.lte_max(n.score)
+            }
+        };
+        self.list
+            .collect(first, rev, limit.unwrap_or(usize::MAX), check)
+    }
+
+    /// member 在 range 之间的 member，只有所有 member 的分数都相同时结果才有意义
+    pub fn range_by_lex(
+        &self,
+        range: &LexRange,
+        rev: bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<(Vec<u8>, f64)> {
+        let first = if rev {
+            self.list
+                .last_in_range(|n| range.lte_max(&n.member), |n| range.gte_min(&n.member))
+        } else {
+            self.list
+                .first_in_range(|n| range.gte_min(&n.member), |n| range.lte_max(&n.member))
+        };
+        let first = self.list.skip(first, rev, offset);
+        let check = |n: &Node| {
+            if rev {
+                range.gte_min(&n.member)
+            } else {
+                range.lte_max(&n.member)
+            }
+        };
+        self.list
+            .collect(first, rev, limit.unwrap_or(usize::MAX), check)
+    }
+
+    /// 分数在 range 之间的 member 数量，通过首尾两个 member 的排名计算，O(log n)
+    pub fn count(&self, range: &ScoreRange) -> usize {
+        let first = self
+            .list
+            .first_in_range(|n| range.gte_min(n.score), |n| range.lte_max(n.score));
+        let last = self
+            .list
+            .last_in_range(|n| range.lte_max(n.score), |n| range.gte_min(n.score));
+        self.count_between(first, last)
+    }
+
+    /// member 在 range 之间的 member 数量
+    pub fn lex_count(&self, range: &LexRange) -> usize {
+        let first = self
+            .list
+            .first_in_range(|n| range.gte_min(&n.member), |n| range.lte_max(&n.member));
+        let last = self
+            .list
+            .last_in_range(|n| range.lte_max(&n.member), |n| range.gte_min(&n.member));
+        self.count_between(first, last)
+    }
+
+    /// 按照分数从小到大的顺序返回所有的 (member, score)
+    pub fn entries(&self) -> Vec<(Vec<u8>, f64)> {
+        self.list
+            .collect(self.list.first(), false, usize::MAX, |_| true)
+    }
+
+    fn count_between(&self, first: Option<usize>, last: Option<usize>) -> usize {
+        let (Some(first), Some(last)) = (first, last) else {
+            return 0;
+        };
+        let rank = |x: usize| {
+            let node = &self.list.nodes[x];
+            self.list.rank(node.score, &node.member).unwrap_or_default()
+        };
+        (rank(last) + 1).saturating_sub(rank(first))
+    }
+}
+
+impl FromIterator<(Vec<u8>, f64)> for ZSet {
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, f64)>>(iter: I) -> Self {
+        let mut zset = ZSet::new();
+        for (member, score) in iter {
+            zset.insert(member, score);
+        }
+        zset
+    }
+}
+
+impl ScoreRange {
+    fn gte_min(&self, score: f64) -> bool {
+        match self.min {
+            Bound::Included(min) => score >= min,
+            Bound::Excluded(min) => score > min,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn lte_max(&self, score: f64) -> bool {
+        match self.max {
+            Bound::Included(max) => score <= max,
+            Bound::Excluded(max) => score < max,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl LexRange {
+    fn gte_min(&self, member: &[u8]) -> bool {
+        match &self.min {
+            LexBound::NegInf => true,
+            LexBound::PosInf => false,
+            LexBound::Included(min) => member >= min.as_slice(),
+            LexBound::Excluded(min) => member > min.as_slice(),
+        }
+    }
+
+    fn lte_max(&self, member: &[u8]) -> bool {
+        match &self.max {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Included(max) => member <= max.as_slice(),
+            LexBound::Excluded(max) => member < max.as_slice(),
+        }
+    }
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        let header = Node {
+            member: Vec::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0
+                };
+                ZSKIPLIST_MAXLEVEL
+            ],
+        };
+        Self {
+            nodes: vec![header],
+            free: Vec::new(),
+            tail: None,
+            len: 0,
+            level: 1,
+        }
+    }
+}
+
+impl SkipList {
+    fn forward(&self, x: usize, i: usize) -> Option<usize> {
+        self.nodes[x].levels[i].forward
+    }
+
+    fn first(&self) -> Option<usize> {
+        self.forward(HEADER, 0)
+    }
+
+    /// 节点 x 是否排在 (score, member) 之前
+    fn less(&self, x: usize, score: f64, member: &[u8]) -> bool {
+        let node = &self.nodes[x];
+        match node.score.partial_cmp(&score) {
+            Some(Ordering::Less) => true,
+            Some(Ordering::Equal) => node.member.as_slice() < member,
+            _ => false,
+        }
+    }
+
+    fn random_level() -> usize {
+        let mut rng = rand::thread_rng();
+        let mut level = 1;
+        while level < ZSKIPLIST_MAXLEVEL && rng.gen::<f64>() < ZSKIPLIST_P {
+            level += 1;
+        }
+        level
+    }
+
+    /// 插入节点，调用方保证 member 不存在
+    fn insert(&mut self, score: f64, member: Vec<u8>) {
+        let mut update = [HEADER; ZSKIPLIST_MAXLEVEL];
+        // rank[i] 是 update[i] 的排名
+        let mut rank = [0; ZSKIPLIST_MAXLEVEL];
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.forward(x, i) {
+                if !self.less(next, score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = Self::random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEADER;
+                self.nodes[HEADER].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            member,
+            score,
+            backward: (update[0] != HEADER).then_some(update[0]),
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0
+                };
+                level
+            ],
+        };
+        let x = match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        for i in 0..level {
+            let prev = self.nodes[update[i]].levels[i];
+            self.nodes[x].levels[i] = Level {
+                forward: prev.forward,
+                span: prev.span - (rank[0] - rank[i]),
+            };
+            self.nodes[update[i]].levels[i] = Level {
+                forward: Some(x),
+                span: rank[0] - rank[i] + 1,
+            };
+        }
+        // 更高的层跨过了新节点
+        for (i, &u) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[u].levels[i].span += 1;
+        }
+        match self.forward(x, 0) {
+            Some(next) => self.nodes[next].backward = Some(x),
+            None => self.tail = Some(x),
+        }
+        self.len += 1;
+    }
+
+    /// 删除 (score, member) 对应的节点，节点存在时返回 true
+    fn delete(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [HEADER; ZSKIPLIST_MAXLEVEL];
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !self.less(next, score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+        let Some(x) = self.forward(x, 0) else {
+            return false;
+        };
+        if self.nodes[x].score != score || self.nodes[x].member != member {
+            return false;
+        }
+
+        for (i, &u) in update.iter().enumerate().take(self.level) {
+            if self.forward(u, i) == Some(x) {
+                let level = self.nodes[x].levels[i];
+                self.nodes[u].levels[i] = Level {
+                    forward: level.forward,
+                    span: self.nodes[u].levels[i].span + level.span - 1,
+                };
+            } else {
+                self.nodes[u].levels[i].span -= 1;
+            }
+        }
+        let backward = self.nodes[x].backward;
+        match self.forward(x, 0) {
+            Some(next) => self.nodes[next].backward = backward,
+            None => self.tail = backward,
+        }
+        while self.level > 1 && self.forward(HEADER, self.level - 1).is_none() {
+            self.level -= 1;
+        }
+        self.len -= 1;
+        // 释放 member 占用的内存，节点留给后续插入复用
+        self.nodes[x].member = Vec::new();
+        self.nodes[x].levels = Vec::new();
+        self.free.push(x);
+        true
+    }
+
+    /// (score, member) 的排名，从 1 开始
+    fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                let node = &self.nodes[next];
+                if node.score > score || (node.score == score && node.member.as_slice() > member) {
+                    break;
+                }
+                rank += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if x != HEADER && self.nodes[x].member == member {
+                return Some(rank);
+            }
+        }
+        None
+    }
+
+    /// 排名为 rank (从 1 开始) 的节点
+    fn get_by_rank(&self, rank: usize) -> Option<usize> {
+        let mut traversed = 0;
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if traversed + self.nodes[x].levels[i].span > rank {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == rank {
+                return (x != HEADER).then_some(x);
+            }
+        }
+        None
+    }
+
+    /// 第一个满足 gte_min 的节点，如果它也满足 lte_max 则在范围内
+    fn first_in_range(
+        &self,
+        gte_min: impl Fn(&Node) -> bool,
+        lte_max: impl Fn(&Node) -> bool,
+    ) -> Option<usize> {
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if gte_min(&self.nodes[next]) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.forward(x, 0).filter(|&x| lte_max(&self.nodes[x]))
+    }
+
+    /// 最后一个满足 lte_max 的节点，如果它也满足 gte_min 则在范围内
+    fn last_in_range(
+        &self,
+        lte_max: impl Fn(&Node) -> bool,
+        gte_min: impl Fn(&Node) -> bool,
+    ) -> Option<usize> {
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !lte_max(&self.nodes[next]) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        (x != HEADER && gte_min(&self.nodes[x])).then_some(x)
+    }
+
+    fn step(&self, x: usize, rev: bool) -> Option<usize> {
+        if rev {
+            self.nodes[x].backward
+        } else {
+            self.forward(x, 0)
+        }
+    }
+
+    fn skip(&self, mut x: Option<usize>, rev: bool, n: usize) -> Option<usize> {
+        for _ in 0..n {
+            x = self.step(x?, rev);
+        }
+        x
+    }
+
+    /// 从 x 开始按照方向收集最多 limit 个满足 check 的节点
+    fn collect(
+        &self,
+        mut x: Option<usize>,
+        rev: bool,
+        limit: usize,
+        check: impl Fn(&Node) -> bool,
+    ) -> Vec<(Vec<u8>, f64)> {
+        let mut ret = Vec::new();
+        while let Some(i) = x {
+            let node = &self.nodes[i];
+            if ret.len() >= limit || !check(node) {
+                break;
+            }
+            ret.push((node.member.clone(), node.score));
+            x = self.step(i, rev);
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(entries: Vec<(Vec<u8>, f64)>) -> Vec<String> {
+        entries
+            .into_iter()
+            .map(|(m, _)| String::from_utf8(m).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_zset_rank() {
+        let mut zset: ZSet = (0..1000)
+            .map(|i| (format!("m{:04}", i).into_bytes(), (i / 2) as f64))
+            .collect();
+        assert_eq!(zset.len(), 1000);
+        for i in (0..1000).step_by(37) {
+            let member = format!("m{:04}", i);
+            assert_eq!(zset.rank(member.as_bytes()), Some(i));
+        }
+        // 更新分数之后排名变化
+        assert!(!zset.insert(b"m0000".to_vec(), 1000.0));
+        assert_eq!(zset.rank(b"m0000"), Some(999));
+        assert_eq!(zset.rank(b"m0001"), Some(0));
+
+        for i in (1..1000).step_by(2) {
+            assert!(zset.remove(format!("m{:04}", i).as_bytes()));
+        }
+        assert!(!zset.remove(b"m0001"));
+        assert_eq!(zset.len(), 500);
+        assert_eq!(zset.rank(b"m0002"), Some(0));
+        assert_eq!(zset.rank(b"m0998"), Some(498));
+        assert_eq!(zset.rank(b"missing"), None);
+        assert_eq!(
+            members(zset.range_by_rank(0, 2, false)),
+            vec!["m0002", "m0004", "m0006"]
+        );
+        assert_eq!(
+            members(zset.range_by_rank(0, 1, true)),
+            vec!["m0000", "m0998"]
+        );
+        assert_eq!(zset.range_by_rank(500, 600, false), vec![]);
+    }
+
+    #[test]
+    fn test_zset_range_by_score() {
+        let zset: ZSet = [("a", 1.0), ("b", 2.0), ("c", 3.0), ("d", 4.0)]
+            .iter()
+            .map(|(m, s)| (m.as_bytes().to_vec(), *s))
+            .collect();
+        let range = ScoreRange {
+            min: Bound::Excluded(1.0),
+            max: Bound::Included(4.0),
+        };
+        assert_eq!(
+            members(zset.range_by_score(&range, false, 0, None)),
+            vec!["b", "c", "d"]
+        );
+        assert_eq!(
+            members(zset.range_by_score(&range, true, 1, Some(1))),
+            vec!["c"]
+        );
+        assert_eq!(zset.count(&range), 3);
+        let range = ScoreRange {
+            min: Bound::Excluded(4.0),
+            max: Bound::Unbounded,
+        };
+        assert_eq!(zset.count(&range), 0);
+        assert_eq!(zset.range_by_score(&range, true, 0, None), vec![]);
+    }
+
+    #[test]
+    fn test_zset_range_by_lex() {
+        let mut zset: ZSet = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|m| (m.as_bytes().to_vec(), 0.0))
+            .collect();
+        let range = LexRange {
+            min: LexBound::Included(b"b".to_vec()),
+            max: LexBound::Excluded(b"e".to_vec()),
+        };
+        assert_eq!(
+            members(zset.range_by_lex(&range, false, 0, None)),
+            vec!["b", "c", "d"]
+        );
+        assert_eq!(
+            members(zset.range_by_lex(&range, true, 0, Some(2))),
+            vec!["d", "c"]
+        );
+        assert_eq!(zset.lex_count(&range), 3);
+
+        assert_eq!(zset.pop(true), Some((b"a".to_vec(), 0.0)));
+        assert_eq!(zset.pop(false), Some((b"e".to_vec(), 0.0)));
+        assert_eq!(members(zset.entries()), vec!["b", "c", "d"]);
+    }
+}
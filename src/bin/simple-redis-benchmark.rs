@@ -0,0 +1,339 @@
+use anyhow::{anyhow, Result};
+use bytes::BytesMut;
+use clap::Parser;
+use simple_redis::{BulkString, RespArray, RespDecode, RespDecodeError, RespEncode, RespFrame};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// 类似 redis-benchmark 的压测工具，请求使用 RespEncode 编码，回复使用 RespDecode 解码
+#[derive(Debug, Parser)]
+#[command(name = "simple-redis-benchmark", version, about)]
+struct Args {
+    /// 服务器地址
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    /// 服务器端口
+    #[arg(short, long, default_value_t = 6379)]
+    port: u16,
+    /// 并发的客户端连接数
+    #[arg(short, long, default_value_t = 50)]
+    clients: usize,
+    /// 每个测试的请求总数
+    #[arg(short = 'n', long, default_value_t = 100_000)]
+    requests: usize,
+    /// 每次批量发送的请求数 (pipeline 深度)
+    #[arg(short = 'P', long, default_value_t = 1)]
+    pipeline: usize,
+    /// key 的取值范围，0 表示所有请求使用同一个 key
+    #[arg(short = 'r', long, default_value_t = 0)]
+    keyspace: u64,
+    /// SET/LPUSH/HSET 等命令的 value 大小 (字节)
+    #[arg(short = 'd', long, default_value_t = 3)]
+    data_size: usize,
+    /// 需要执行的命令，以逗号分隔
+    #[arg(
+        short = 't',
+        long,
+        value_delimiter = ',',
+        default_value = "ping,set,get,incr,lpush,rpush,lpop,rpop,sadd,hset,mset"
+    )]
+    tests: Vec<BenchCommand>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum BenchCommand {
+    Ping,
+    Set,
+    Get,
+    Incr,
+    Lpush,
+    Rpush,
+    Lpop,
+    Rpop,
+    Sadd,
+    Hset,
+    Mset,
+}
+
+/// 单个测试的统计结果
+#[derive(Debug)]
+struct Report {
+    requests: usize,
+    errors: usize,
+    elapsed: Duration,
+    // 每个请求的延迟，单位微秒，已排序
+    latencies: Vec<u64>,
+}
+
+/// 简单的 xorshift 随机数，用于生成随机 key
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
+
+impl BenchCommand {
+    fn name(&self) -> &'static str {
+        match self {
+            BenchCommand::Ping => "PING",
+            BenchCommand::Set => "SET",
+            BenchCommand::Get => "GET",
+            BenchCommand::Incr => "INCR",
+            BenchCommand::Lpush => "LPUSH",
+            BenchCommand::Rpush => "RPUSH",
+            BenchCommand::Lpop => "LPOP",
+            BenchCommand::Rpop => "RPOP",
+            BenchCommand::Sadd => "SADD",
+            BenchCommand::Hset => "HSET",
+            BenchCommand::Mset => "MSET (10 keys)",
+        }
+    }
+
+    /// 构造一条请求，key 的后缀由 keyspace 决定
+    fn request(&self, rng: &mut XorShift, keyspace: u64, value: &[u8]) -> RespArray {
+        let mut key = || {
+            let n = if keyspace == 0 {
+                0
+            } else {
+                rng.next() % keyspace
+            };
+            format!("{:012}", n)
+        };
+        let args: Vec<Vec<u8>> = match self {
+            BenchCommand::Ping => vec![b"PING".to_vec()],
+            BenchCommand::Set => vec![
+                b"SET".to_vec(),
+                format!("key:{}", key()).into_bytes(),
+                value.to_vec(),
+            ],
+            BenchCommand::Get => vec![b"GET".to_vec(), format!("key:{}", key()).into_bytes()],
+            BenchCommand::Incr => vec![b"INCR".to_vec(), format!("counter:{}", key()).into_bytes()],
+            BenchCommand::Lpush => vec![b"LPUSH".to_vec(), b"mylist".to_vec(), value.to_vec()],
+            BenchCommand::Rpush => vec![b"RPUSH".to_vec(), b"mylist".to_vec(), value.to_vec()],
+            BenchCommand::Lpop => vec![b"LPOP".to_vec(), b"mylist".to_vec()],
+            BenchCommand::Rpop => vec![b"RPOP".to_vec(), b"mylist".to_vec()],
+            BenchCommand::Sadd => vec![
+                b"SADD".to_vec(),
+                b"myset".to_vec(),
+                format!("element:{}", key()).into_bytes(),
+            ],
+            BenchCommand::Hset => vec![
+                b"HSET".to_vec(),
+                b"myhash".to_vec(),
+                format!("element:{}", key()).into_bytes(),
+                value.to_vec(),
+            ],
+            BenchCommand::Mset => {
+                let mut args = vec![b"MSET".to_vec()];
+                for _ in 0..10 {
+                    args.push(format!("key:{}", key()).into_bytes());
+                    args.push(value.to_vec());
+                }
+                args
+            }
+        };
+        RespArray::new(
+            args.into_iter()
+                .map(|arg| BulkString::new(arg).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+}
+
+impl Report {
+    fn percentile(&self, p: f64) -> u64 {
+        if self.latencies.is_empty() {
+            return 0;
+        }
+        let idx = ((self.latencies.len() as f64 * p / 100.0).ceil() as usize)
+            .clamp(1, self.latencies.len());
+        self.latencies[idx - 1]
+    }
+
+    fn throughput(&self) -> f64 {
+        self.requests as f64 / self.elapsed.as_secs_f64()
+    }
+
+    fn print(&self, name: &str, args: &Args) {
+        let avg = if self.latencies.is_empty() {
+            0.0
+        } else {
+            self.latencies.iter().sum::<u64>() as f64 / self.latencies.len() as f64
+        };
+        let ms = |us: u64| us as f64 / 1000.0;
+        println!("====== {} ======", name);
+        println!(
+            "  {} requests completed in {:.2} seconds",
+            self.requests,
+            self.elapsed.as_secs_f64()
+        );
+        println!("  {} parallel clients", args.clients);
+        println!("  {} bytes payload", args.data_size);
+        println!("  {} pipeline depth", args.pipeline);
+        if self.errors > 0 {
+            println!("  {} error replies", self.errors);
+        }
+        println!("  throughput: {:.2} requests per second", self.throughput());
+        println!("  latency summary (msec):");
+        println!(
+            "  {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "avg", "min", "p50", "p95", "p99", "max"
+        );
+        println!(
+            "  {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            avg / 1000.0,
+            ms(self.latencies.first().copied().unwrap_or_default()),
+            ms(self.percentile(50.0)),
+            ms(self.percentile(95.0)),
+            ms(self.percentile(99.0)),
+            ms(self.latencies.last().copied().unwrap_or_default()),
+        );
+        println!();
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Arc::new(Args::parse());
+    if args.clients == 0 || args.pipeline == 0 {
+        return Err(anyhow!("clients and pipeline must be greater than 0"));
+    }
+    for cmd in args.tests.iter() {
+        let report = run_test(args.clone(), *cmd).await?;
+        report.print(cmd.name(), &args);
+    }
+    Ok(())
+}
+
+async fn run_test(args: Arc<Args>, cmd: BenchCommand) -> Result<Report> {
+    let issued = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+
+    let mut handles = Vec::with_capacity(args.clients);
+    for id in 0..args.clients {
+        let args = args.clone();
+        let issued = issued.clone();
+        handles.push(tokio::spawn(async move {
+            run_client(id as u64, args, cmd, issued).await
+        }));
+    }
+
+    let mut latencies = Vec::with_capacity(args.requests);
+    let mut errors = 0;
+    for handle in handles {
+        let (client_latencies, client_errors) = handle.await??;
+        latencies.extend(client_latencies);
+        errors += client_errors;
+    }
+    let elapsed = start.elapsed();
+    latencies.sort_unstable();
+
+    Ok(Report {
+        requests: latencies.len(),
+        errors,
+        elapsed,
+        latencies,
+    })
+}
+
+/// 一个客户端连接：每次领取一批请求，编码后一次性发送，再读取同样数量的回复
+async fn run_client(
+    id: u64,
+    args: Arc<Args>,
+    cmd: BenchCommand,
+    issued: Arc<AtomicUsize>,
+) -> Result<(Vec<u64>, usize)> {
+    let mut stream = TcpStream::connect((args.host.as_str(), args.port)).await?;
+    stream.set_nodelay(true)?;
+
+    let value = vec![b'x'; args.data_size];
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15 ^ (id + 1).wrapping_mul(0x2545_F491_4F6C_DD1D));
+    let mut buf = BytesMut::with_capacity(16 * 1024);
+    let mut latencies = Vec::new();
+    let mut errors = 0;
+
+    loop {
+        let claimed = issued.fetch_add(args.pipeline, Ordering::Relaxed);
+        if claimed >= args.requests {
+            break;
+        }
+        let batch = args.pipeline.min(args.requests - claimed);
+
+        let mut request = Vec::new();
+        for _ in 0..batch {
+            request.extend(cmd.request(&mut rng, args.keyspace, &value).encode());
+        }
+
+        let start = Instant::now();
+        stream.write_all(&request).await?;
+        let mut received = 0;
+        while received < batch {
+            match RespFrame::decode(&mut buf) {
+                Ok(frame) => {
+                    if matches!(frame, RespFrame::Error(_)) {
+                        errors += 1;
+                    }
+                    received += 1;
+                }
+                Err(RespDecodeError::NotComplete) => {
+                    if stream.read_buf(&mut buf).await? == 0 {
+                        return Err(anyhow!("connection closed by server"));
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let elapsed = start.elapsed().as_micros() as u64;
+        latencies.extend(std::iter::repeat_n(elapsed, batch));
+    }
+
+    Ok((latencies, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_encode() {
+        let mut rng = XorShift(1);
+        let frame = BenchCommand::Set.request(&mut rng, 0, b"abc");
+        assert_eq!(
+            frame.encode(),
+            b"*3\r\n$3\r\nSET\r\n$16\r\nkey:000000000000\r\n$3\r\nabc\r\n"
+        );
+
+        let frame = BenchCommand::Mset.request(&mut rng, 100, b"v");
+        assert_eq!(frame.len(), 21);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let report = Report {
+            requests: 100,
+            errors: 0,
+            elapsed: Duration::from_secs(1),
+            latencies: (1..=100).collect(),
+        };
+        assert_eq!(report.percentile(50.0), 50);
+        assert_eq!(report.percentile(99.0), 99);
+        assert_eq!(report.percentile(100.0), 100);
+        assert_eq!(report.throughput(), 100.0);
+    }
+}
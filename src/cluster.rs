@@ -0,0 +1,119 @@
+use crate::{
+    replication::encode_command, Backend, ClusterNode, RespDecode, RespDecodeError, RespFrame,
+};
+use anyhow::{anyhow, bail, Result};
+use bytes::BytesMut;
+use futures::future::join_all;
+use std::{collections::HashMap, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    task::JoinHandle,
+    time::{interval, timeout},
+};
+use tracing::{info, warn};
+
+// 获取其他节点信息的间隔，对应 Redis cluster bus 的 PING
+const GOSSIP_INTERVAL: Duration = Duration::from_secs(1);
+// 连接节点以及等待响应的超时时间
+const GOSSIP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 和其他节点之间的连接，节点地址不变时一直复用
+struct PeerLink {
+    stream: TcpStream,
+    buf: BytesMut,
+}
+
+/// 启动集群的后台任务：定期通过 CLUSTER NODES 获取其他节点的信息并合并到自己的状态，
+/// 还不认识自己的节点发送 CLUSTER MEET，这样 MEET 一个节点就可以加入整个集群
+pub fn spawn_cluster_cron(backend: Backend) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut links: HashMap<String, PeerLink> = HashMap::new();
+        let mut ticker = interval(GOSSIP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if !backend.cluster_enabled() {
+                continue;
+            }
+            let Some(myself) = backend.cluster_nodes().into_iter().next() else {
+                continue;
+            };
+            let polls = backend.cluster_peers().into_iter().map(|peer| {
+                let link = links.remove(&peer.addr());
+                async move {
+                    let ret = timeout(GOSSIP_TIMEOUT, gossip(link, &peer)).await;
+                    let ret = ret.unwrap_or_else(|_| Err(anyhow!("timeout")));
+                    (peer, ret)
+                }
+            });
+            for (peer, ret) in join_all(polls).await {
+                match ret {
+                    Ok((link, text)) => {
+                        links.insert(peer.addr(), link);
+                        if !backend.cluster_gossip(&peer.id, &text) {
+                            info!("Sending CLUSTER MEET to node {}", peer.addr());
+                            meet(&mut links, &peer, &myself).await;
+                        }
+                    }
+                    Err(e) => {
+                        if peer.connected {
+                            warn!("Lost connection with node {}: {}", peer.addr(), e);
+                        }
+                        backend.cluster_node_unreachable(&peer.id);
+                    }
+                }
+            }
+            // 没有连接上的节点超时之后集群变成不可用
+            backend.cluster_update_state();
+        }
+    })
+}
+
+/// 获取节点的 CLUSTER NODES，连接断开时重新连接
+async fn gossip(link: Option<PeerLink>, peer: &ClusterNode) -> Result<(PeerLink, String)> {
+    let mut link = match link {
+        Some(link) => link,
+        None => PeerLink {
+            stream: TcpStream::connect((peer.ip.as_str(), peer.port)).await?,
+            buf: BytesMut::new(),
+        },
+    };
+    match link.request(&["CLUSTER", "NODES"]).await? {
+        RespFrame::BulkString(text) => Ok((link, String::from_utf8_lossy(&text.0).into_owned())),
+        RespFrame::Error(e) => bail!("error reply to CLUSTER NODES: {}", e.0),
+        frame => bail!("unexpected reply to CLUSTER NODES: {:?}", frame),
+    }
+}
+
+/// 让节点认识自己，节点下次获取自己的信息时完成握手
+async fn meet(links: &mut HashMap<String, PeerLink>, peer: &ClusterNode, myself: &ClusterNode) {
+    let Some(link) = links.get_mut(&peer.addr()) else {
+        return;
+    };
+    let port = myself.port.to_string();
+    let ret = timeout(
+        GOSSIP_TIMEOUT,
+        link.request(&["CLUSTER", "MEET", &myself.ip, &port]),
+    )
+    .await;
+    if !matches!(ret, Ok(Ok(RespFrame::SimpleString(_)))) {
+        links.remove(&peer.addr());
+    }
+}
+
+impl PeerLink {
+    async fn request(&mut self, args: &[&str]) -> Result<RespFrame> {
+        self.stream.write_all(&encode_command(args)).await?;
+        loop {
+            match RespFrame::decode(&mut self.buf) {
+                Ok(frame) => return Ok(frame),
+                Err(RespDecodeError::NotComplete) => {
+                    if self.stream.read_buf(&mut self.buf).await? == 0 {
+                        bail!("connection closed");
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
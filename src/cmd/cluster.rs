@@ -0,0 +1,631 @@
+use super::{
+    command_name, parse_int, parse_string, CommandError, CommandExecutor, SessionExecutor,
+};
+use crate::{
+    key_hash_slot, Backend, BulkString, RespArray, RespFrame, RespMap, SetSlot, SimpleError,
+    SimpleString, SlotRoute, CLUSTER_SLOTS,
+};
+use std::{collections::BTreeSet, fmt::Write};
+
+/// CLUSTER KEYSLOT key | COUNTKEYSINSLOT slot | GETKEYSINSLOT slot count | SLOTS | SHARDS |
+/// NODES | MYID | INFO | ADDSLOTS slot [slot ...] | ADDSLOTSRANGE start end [start end ...] |
+/// DELSLOTS slot [slot ...] | DELSLOTSRANGE start end [start end ...] |
+/// SETSLOT slot IMPORTING id|MIGRATING id|STABLE|NODE id | MEET ip port [cport]
+#[derive(Debug)]
+pub enum Cluster {
+    KeySlot(Vec<u8>),
+    CountKeysInSlot(u16),
+    GetKeysInSlot(u16, usize),
+    Slots,
+    Shards,
+    Nodes,
+    MyId,
+    Info,
+    AddSlots(Vec<u16>),
+    DelSlots(Vec<u16>),
+    SetSlot(u16, SetSlot),
+    Meet(String, u16),
+}
+
+/// ASKING，下一条命令可以访问正在迁入的 slot
+#[derive(Debug)]
+pub struct Asking;
+
+/// 集群模式下需要检查 key 所在 slot 的请求：命令名以及命令访问的 key
+#[derive(Debug)]
+pub struct ClusterRequest {
+    name: String,
+    keys: Vec<Vec<u8>>,
+}
+
+const CLUSTER_DISABLED: &str = "ERR This instance has cluster support disabled";
+
+fn parse_slot(arg: &[u8]) -> Result<u16, CommandError> {
+    parse_int(arg)
+        .ok()
+        .filter(|slot| (0..CLUSTER_SLOTS as i64).contains(slot))
+        .map(|slot| slot as u16)
+        .ok_or_else(|| CommandError::InvalidArgument("Invalid or out of range slot".to_string()))
+}
+
+/// ADDSLOTS/DELSLOTS 的 slot 列表，或者 ADDSLOTSRANGE/DELSLOTSRANGE 的区间列表
+fn parse_slots(args: &[Vec<u8>], range: bool) -> Result<Vec<u16>, CommandError> {
+    let mut slots = Vec::new();
+    if range {
+        for pair in args.chunks(2) {
+            let (start, end) = (parse_slot(&pair[0])?, parse_slot(&pair[1])?);
+            if start > end {
+                return Err(CommandError::InvalidArgument(format!(
+                    "start slot number {} is greater than end slot number {}",
+                    start, end
+                )));
+            }
+            slots.extend(start..=end);
+        }
+    } else {
+        for arg in args {
+            slots.push(parse_slot(arg)?);
+        }
+    }
+    let mut seen = BTreeSet::new();
+    if let Some(slot) = slots.iter().find(|&&slot| !seen.insert(slot)) {
+        return Err(CommandError::InvalidArgument(format!(
+            "Slot {} specified multiple times",
+            slot
+        )));
+    }
+    Ok(slots)
+}
+
+impl Cluster {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let Some(sub) = args.first() else {
+            return Err(CommandError::WrongArity("cluster".to_string()));
+        };
+        let sub = String::from_utf8_lossy(sub).to_lowercase();
+        let args = &args[1..];
+        let arity = || CommandError::WrongArity(format!("cluster|{}", sub));
+        match (sub.as_str(), args) {
+            ("keyslot", [key]) => Ok(Cluster::KeySlot(key.clone())),
+            ("countkeysinslot", [slot]) => Ok(Cluster::CountKeysInSlot(parse_slot(slot)?)),
+            ("getkeysinslot", [slot, count]) => {
+                let count = parse_int(count)
+                    .ok()
+                    .and_then(|count| usize::try_from(count).ok())
+                    .ok_or_else(|| {
+                        CommandError::InvalidArgument("Invalid number of keys".to_string())
+                    })?;
+                Ok(Cluster::GetKeysInSlot(parse_slot(slot)?, count))
+            }
+            ("slots", []) => Ok(Cluster::Slots),
+            ("shards", []) => Ok(Cluster::Shards),
+            ("nodes", []) => Ok(Cluster::Nodes),
+            ("myid", []) => Ok(Cluster::MyId),
+            ("info", []) => Ok(Cluster::Info),
+            ("addslots", [_, ..]) => Ok(Cluster::AddSlots(parse_slots(args, false)?)),
+            ("delslots", [_, ..]) => Ok(Cluster::DelSlots(parse_slots(args, false)?)),
+            ("addslotsrange", [_, _, ..]) if args.len().is_multiple_of(2) => {
+                Ok(Cluster::AddSlots(parse_slots(args, true)?))
+            }
+            ("delslotsrange", [_, _, ..]) if args.len().is_multiple_of(2) => {
+                Ok(Cluster::DelSlots(parse_slots(args, true)?))
+            }
+            ("setslot", [slot, action, rest @ ..]) => {
+                let slot = parse_slot(slot)?;
+                let action = String::from_utf8_lossy(action).to_lowercase();
+                let id = || match rest {
+                    [id] => parse_string(id.clone()),
+                    _ => Err(CommandError::Syntax),
+                };
+                let op = match action.as_str() {
+                    "importing" => SetSlot::Importing(id()?),
+                    "migrating" => SetSlot::Migrating(id()?),
+                    "node" => SetSlot::Node(id()?),
+                    "stable" if rest.is_empty() => SetSlot::Stable,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid CLUSTER SETSLOT action or number of arguments. \
+                             Try CLUSTER HELP"
+                                .to_string(),
+                        ))
+                    }
+                };
+                Ok(Cluster::SetSlot(slot, op))
+            }
+            ("meet", [ip, port, ..]) if args.len() <= 3 => {
+                let ip = parse_string(ip.clone())?;
+                let port = parse_int(port)
+                    .ok()
+                    .and_then(|port| u16::try_from(port).ok())
+                    .filter(|port| *port > 0)
+                    .ok_or_else(|| {
+                        CommandError::InvalidArgument(format!(
+                            "Invalid node address specified: {}:{}",
+                            ip,
+                            String::from_utf8_lossy(port)
+                        ))
+                    })?;
+                Ok(Cluster::Meet(ip, port))
+            }
+            (
+                "keyslot" | "countkeysinslot" | "getkeysinslot" | "slots" | "shards" | "nodes"
+                | "myid" | "info" | "addslots" | "delslots" | "addslotsrange" | "delslotsrange"
+                | "setslot" | "meet",
+                _,
+            ) => Err(arity()),
+            _ => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'. Try CLUSTER HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+impl CommandExecutor for Cluster {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !backend.cluster_enabled() {
+            return SimpleError::new(CLUSTER_DISABLED).into();
+        }
+        let ok = |ret: Result<(), String>| match ret {
+            Ok(()) => SimpleString::new("OK").into(),
+            Err(e) => CommandError::InvalidArgument(e).into(),
+        };
+        match self {
+            Cluster::KeySlot(key) => (key_hash_slot(&key) as i64).into(),
+            Cluster::CountKeysInSlot(slot) => (backend.count_keys_in_slot(slot) as i64).into(),
+            Cluster::GetKeysInSlot(slot, count) => RespArray::new(
+                backend
+                    .keys_in_slot(slot, count)
+                    .into_iter()
+                    .map(|key| BulkString::new(key).into())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Cluster::Slots => cluster_slots(backend),
+            Cluster::Shards => cluster_shards(backend),
+            Cluster::Nodes => BulkString::new(backend.cluster_nodes_text()).into(),
+            Cluster::MyId => BulkString::new(backend.cluster_myself()).into(),
+            Cluster::Info => BulkString::new(cluster_info(backend)).into(),
+            Cluster::AddSlots(slots) => ok(backend.cluster_add_slots(&slots)),
+            Cluster::DelSlots(slots) => ok(backend.cluster_del_slots(&slots)),
+            Cluster::SetSlot(slot, op) => ok(backend.cluster_set_slot(slot, op)),
+            Cluster::Meet(ip, port) => {
+                backend.cluster_meet(ip, port);
+                SimpleString::new("OK").into()
+            }
+        }
+    }
+}
+
+/// CLUSTER SLOTS：每个 slot 区间一项 [start, end, [ip, port, id, {}]]
+fn cluster_slots(backend: &Backend) -> RespFrame {
+    let mut ranges = Vec::new();
+    for node in backend.cluster_nodes() {
+        for (start, end) in backend.cluster_slot_ranges(&node.id) {
+            let owner = RespArray::new(vec![
+                BulkString::new(node.ip.clone()).into(),
+                (node.port as i64).into(),
+                BulkString::new(node.id.clone()).into(),
+                RespMap::new().into(),
+            ]);
+            let range: RespFrame = RespArray::new(vec![
+                (start as i64).into(),
+                (end as i64).into(),
+                owner.into(),
+            ])
+            .into();
+            ranges.push((start, range));
+        }
+    }
+    ranges.sort_by_key(|(start, _)| *start);
+    RespArray::new(
+        ranges
+            .into_iter()
+            .map(|(_, range)| range)
+            .collect::<Vec<_>>(),
+    )
+    .into()
+}
+
+/// CLUSTER SHARDS：每个负责 slot 的节点是一个 shard
+fn cluster_shards(backend: &Backend) -> RespFrame {
+    let shards: Vec<RespFrame> = backend
+        .cluster_nodes()
+        .into_iter()
+        .filter(|node| !node.handshake)
+        .map(|node| {
+            let slots: Vec<RespFrame> = backend
+                .cluster_slot_ranges(&node.id)
+                .into_iter()
+                .flat_map(|(start, end)| [(start as i64).into(), (end as i64).into()])
+                .collect();
+            let mut info = RespMap::new();
+            info.insert("id".to_string(), BulkString::new(node.id.clone()).into());
+            info.insert("port".to_string(), (node.port as i64).into());
+            info.insert("ip".to_string(), BulkString::new(node.ip.clone()).into());
+            info.insert(
+                "endpoint".to_string(),
+                BulkString::new(node.ip.clone()).into(),
+            );
+            info.insert("role".to_string(), BulkString::new("master").into());
+            info.insert("replication-offset".to_string(), 0.into());
+            let health = if node.failing() { "fail" } else { "online" };
+            info.insert("health".to_string(), BulkString::new(health).into());
+            let mut shard = RespMap::new();
+            shard.insert("slots".to_string(), RespArray::new(slots).into());
+            shard.insert(
+                "nodes".to_string(),
+                RespArray::new(vec![info.into()]).into(),
+            );
+            shard.into()
+        })
+        .collect();
+    RespArray::new(shards).into()
+}
+
+/// CLUSTER INFO，字段和 Redis 一样
+fn cluster_info(backend: &Backend) -> String {
+    let nodes = backend.cluster_nodes();
+    let myself = backend.cluster_myself();
+    let size = nodes
+        .iter()
+        .filter(|node| !backend.cluster_slot_ranges(&node.id).is_empty())
+        .count();
+    let assigned = backend.cluster_assigned_slots();
+    let mut info = String::new();
+    let _ = write!(
+        info,
+        "cluster_state:{}\r\ncluster_slots_assigned:{}\r\ncluster_slots_ok:{}\r\n\
+         cluster_slots_pfail:0\r\ncluster_slots_fail:0\r\ncluster_known_nodes:{}\r\n\
+         cluster_size:{}\r\ncluster_current_epoch:{}\r\ncluster_my_epoch:{}\r\n",
+        if backend.cluster_ok() { "ok" } else { "fail" },
+        assigned,
+        assigned,
+        nodes.len(),
+        size,
+        backend.cluster_current_epoch(),
+        nodes
+            .iter()
+            .find(|node| node.id == myself)
+            .map_or(0, |node| node.config_epoch),
+    );
+    info
+}
+
+impl Asking {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        super::validate_args("asking", &args, 0)?;
+        Ok(Asking)
+    }
+}
+
+impl SessionExecutor for Asking {
+    fn execute_session(self, session: &mut super::Session, backend: &Backend) -> RespFrame {
+        if !backend.cluster_enabled() {
+            return SimpleError::new(CLUSTER_DISABLED).into();
+        }
+        session.asking = true;
+        SimpleString::new("OK").into()
+    }
+}
+
+impl CommandExecutor for Asking {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut super::Session::default(), backend)
+    }
+}
+
+/// 命令参数中的 key，没有 key 的命令返回空的列表
+fn command_keys(name: &str, args: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    // numkeys 之后的 key
+    let numkeys = |pos: usize| -> Vec<Vec<u8>> {
+        let n = args
+            .get(pos)
+            .and_then(|n| parse_int(n).ok())
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(0);
+        args.iter().skip(pos + 1).take(n).cloned().collect()
+    };
+    match name {
+        "ping" | "hello" | "subscribe" | "unsubscribe" | "psubscribe" | "punsubscribe"
+        | "publish" | "pubsub" | "multi" | "exec" | "discard" | "unwatch" | "script"
+        | "function" | "save" | "bgsave" | "lastsave" | "bgrewriteaof" | "replicaof"
+        | "slaveof" | "replconf" | "psync" | "sync" | "role" | "info" | "wait" | "waitaof"
+        | "cluster" | "asking" => vec![],
+        "mget" | "sinter" | "sunion" | "sdiff" | "sinterstore" | "sunionstore" | "sdiffstore"
+        | "watch" | "ssubscribe" | "sunsubscribe" => args.to_vec(),
+        "mset" | "msetnx" => args.iter().step_by(2).cloned().collect(),
+        "lmove" | "rpoplpush" | "smove" | "blmove" | "brpoplpush" => {
+            args.iter().take(2).cloned().collect()
+        }
+        "blpop" | "brpop" | "bzpopmin" | "bzpopmax" => {
+            args[..args.len().saturating_sub(1)].to_vec()
+        }
+        "lmpop" | "zmpop" | "sintercard" | "zunion" | "zinter" | "zdiff" => numkeys(0),
+        "blmpop" | "bzmpop" | "eval" | "evalsha" | "fcall" | "fcall_ro" => numkeys(1),
+        "zunionstore" | "zinterstore" | "zdiffstore" => {
+            args.iter().take(1).cloned().chain(numkeys(1)).collect()
+        }
+        "xgroup" | "xinfo" => args.iter().skip(1).take(1).cloned().collect(),
+        "xread" | "xreadgroup" => {
+            let streams = args
+                .iter()
+                .position(|arg| arg.eq_ignore_ascii_case(b"streams"))
+                .map_or(&args[args.len()..], |pos| &args[pos + 1..]);
+            streams[..streams.len() / 2].to_vec()
+        }
+        _ => args.iter().take(1).cloned().collect(),
+    }
+}
+
+impl ClusterRequest {
+    /// 集群模式下记录请求访问的 key，命令解析成功之后再检查 key 是否由自己负责
+    pub fn new(frame: &RespFrame, backend: &Backend) -> Option<Self> {
+        let RespFrame::Array(array) = frame else {
+            return None;
+        };
+        if !backend.cluster_enabled() {
+            return None;
+        }
+        let name = command_name(array).ok()?;
+        let args: Vec<Vec<u8>> = array
+            .0
+            .iter()
+            .skip(1)
+            .map(|frame| match frame {
+                RespFrame::BulkString(s) => Some(s.0.clone()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        let keys = command_keys(&name, &args);
+        Some(ClusterRequest { name, keys })
+    }
+
+    /// 检查 key 所在的 slot，不由自己处理时返回 MOVED/ASK 等错误，事务中出现重定向时整个事务被放弃
+    pub fn redirect(self, session: &mut super::Session, backend: &Backend) -> Option<RespFrame> {
+        if self.name == "asking" {
+            return None;
+        }
+        let asking = std::mem::take(&mut session.asking);
+        let reply = self.route(asking, backend)?;
+        if let Some(transaction) = session.transaction.as_mut() {
+            transaction.abort();
+        }
+        Some(SimpleError::new(reply).into())
+    }
+
+    fn route(&self, asking: bool, backend: &Backend) -> Option<String> {
+        let mut slots = self.keys.iter().map(|key| key_hash_slot(key));
+        let slot = slots.next()?;
+        if slots.any(|other| other != slot) {
+            return Some("CROSSSLOT Keys in request don't hash to the same slot".to_string());
+        }
+        // 迁移过程中多个 key 只有一部分在这个节点上，客户端需要稍后重试
+        let try_again = "TRYAGAIN Multiple keys request during rehashing of slot".to_string();
+        match backend.cluster_route(slot) {
+            SlotRoute::Unassigned => Some("CLUSTERDOWN Hash slot not served".to_string()),
+            _ if !backend.cluster_ok() => Some("CLUSTERDOWN The cluster is down".to_string()),
+            SlotRoute::Myself => None,
+            // 迁出过程中不在这个节点上的 key 可能已经迁移到目标节点
+            SlotRoute::Migrating(addr) => match self.missing_keys(backend) {
+                0 => None,
+                missing if missing < self.keys.len() => Some(try_again),
+                _ => Some(format!("ASK {} {}", slot, addr)),
+            },
+            SlotRoute::Importing(_) if asking => match self.missing_keys(backend) {
+                missing if missing > 0 && missing < self.keys.len() => Some(try_again),
+                _ => None,
+            },
+            SlotRoute::Importing(None) => Some("CLUSTERDOWN Hash slot not served".to_string()),
+            SlotRoute::Importing(Some(addr)) | SlotRoute::Moved(addr) => {
+                Some(format!("MOVED {} {}", slot, addr))
+            }
+        }
+    }
+
+    /// 请求的 key 中不在这个节点上的数量，只在 slot 迁移过程中需要检查
+    fn missing_keys(&self, backend: &Backend) -> usize {
+        self.keys.iter().filter(|key| !backend.exists(key)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::tests::run, ClusterConfig};
+    use anyhow::Result;
+
+    fn cluster_backend(port: u16) -> Backend {
+        let backend = Backend::new();
+        let dir = std::env::temp_dir().join(format!(
+            "simple-redis-cluster-cmd-{}-{}",
+            std::process::id(),
+            port
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        backend
+            .configure_cluster(ClusterConfig {
+                enabled: true,
+                config_file: dir.join("nodes.conf"),
+                announce_ip: "127.0.0.1".to_string(),
+                port,
+            })
+            .unwrap();
+        backend
+    }
+
+    fn request(backend: &Backend, args: &[&str]) -> ClusterRequest {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        ClusterRequest::new(&RespArray::new(frames).into(), backend).unwrap()
+    }
+
+    fn redirect(backend: &Backend, session: &mut super::super::Session, args: &[&str]) -> String {
+        match request(backend, args).redirect(session, backend) {
+            Some(RespFrame::Error(e)) => e.0,
+            Some(frame) => panic!("expected error, got {:?}", frame),
+            None => String::new(),
+        }
+    }
+
+    #[test]
+    fn test_cluster_command() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, &["CLUSTER", "KEYSLOT", "foo"])?,
+            SimpleError::new(CLUSTER_DISABLED).into()
+        );
+
+        let backend = cluster_backend(7100);
+        assert_eq!(run(&backend, &["CLUSTER", "KEYSLOT", "foo"])?, 12182.into());
+        let err = run(
+            &backend,
+            &["CLUSTER", "ADDSLOTSRANGE", "0", "100", "50", "60"],
+        );
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "Slot 50 specified multiple times"
+        );
+        let err = run(&backend, &["CLUSTER", "ADDSLOTS", "16384"]);
+        assert_eq!(err.unwrap_err().to_string(), "Invalid or out of range slot");
+        assert_eq!(
+            run(&backend, &["CLUSTER", "ADDSLOTSRANGE", "0", "16383"])?,
+            SimpleString::new("OK").into()
+        );
+        run(&backend, &["SET", "{user}.a", "1"])?;
+        run(&backend, &["SET", "{user}.b", "2"])?;
+        let slot = key_hash_slot(b"user").to_string();
+        assert_eq!(
+            run(&backend, &["CLUSTER", "COUNTKEYSINSLOT", &slot])?,
+            2.into()
+        );
+        let RespFrame::Array(keys) = run(&backend, &["CLUSTER", "GETKEYSINSLOT", &slot, "1"])?
+        else {
+            panic!("expected array");
+        };
+        assert_eq!(keys.len(), 1);
+
+        let id = backend.cluster_myself();
+        let expected: RespFrame = RespArray::new(vec![RespArray::new(vec![
+            0.into(),
+            16383.into(),
+            RespArray::new(vec![
+                BulkString::new("127.0.0.1").into(),
+                7100.into(),
+                BulkString::new(id.clone()).into(),
+                RespMap::new().into(),
+            ])
+            .into(),
+        ])
+        .into()])
+        .into();
+        assert_eq!(run(&backend, &["CLUSTER", "SLOTS"])?, expected);
+        let RespFrame::BulkString(nodes) = run(&backend, &["CLUSTER", "NODES"])? else {
+            panic!("expected bulk string");
+        };
+        assert!(String::from_utf8(nodes.0)?.starts_with(&format!(
+            "{} 127.0.0.1:7100@17100 myself,master - 0 0 1 connected 0-16383\n",
+            id
+        )));
+        let RespFrame::BulkString(info) = run(&backend, &["CLUSTER", "INFO"])? else {
+            panic!("expected bulk string");
+        };
+        assert!(String::from_utf8(info.0)?.starts_with("cluster_state:ok\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_redirect() -> Result<()> {
+        let a = cluster_backend(7200);
+        let b = cluster_backend(7201);
+        a.cluster_add_slots(&(0..8192).collect::<Vec<_>>()).unwrap();
+        let mut session = super::super::Session::default();
+        // foo 在 slot 12182，还没有节点负责
+        assert_eq!(
+            redirect(&a, &mut session, &["GET", "foo"]),
+            "CLUSTERDOWN Hash slot not served"
+        );
+        assert_eq!(
+            redirect(&a, &mut session, &["GET", "bar"]),
+            "CLUSTERDOWN The cluster is down"
+        );
+
+        b.cluster_add_slots(&(8192..16384).collect::<Vec<_>>())
+            .unwrap();
+        a.cluster_meet("127.0.0.1".to_string(), 7201);
+        let placeholder = a.cluster_peers()[0].id.clone();
+        a.cluster_gossip(&placeholder, &b.cluster_nodes_text());
+        b.cluster_meet("127.0.0.1".to_string(), 7200);
+        let placeholder = b.cluster_peers()[0].id.clone();
+        b.cluster_gossip(&placeholder, &a.cluster_nodes_text());
+
+        // bar 在 slot 5061
+        assert_eq!(redirect(&a, &mut session, &["GET", "bar"]), "");
+        assert_eq!(
+            redirect(&a, &mut session, &["GET", "foo"]),
+            "MOVED 12182 127.0.0.1:7201"
+        );
+        assert_eq!(
+            redirect(&a, &mut session, &["MSET", "foo", "1", "bar", "2"]),
+            "CROSSSLOT Keys in request don't hash to the same slot"
+        );
+        assert_eq!(
+            redirect(&a, &mut session, &["MGET", "{foo}.a", "{foo}.b"]),
+            "MOVED 12182 127.0.0.1:7201"
+        );
+        assert_eq!(redirect(&a, &mut session, &["PING"]), "");
+
+        // 把 slot 5061 从 a 迁移到 b
+        run(&a, &["SET", "bar", "1"])?;
+        run(&a, &["SET", "{bar}.a", "1"])?;
+        let (a_id, b_id) = (a.cluster_myself(), b.cluster_myself());
+        b.cluster_set_slot(5061, SetSlot::Importing(a_id)).unwrap();
+        a.cluster_set_slot(5061, SetSlot::Migrating(b_id)).unwrap();
+        assert_eq!(redirect(&a, &mut session, &["GET", "bar"]), "");
+        assert_eq!(
+            redirect(&a, &mut session, &["GET", "{bar}.b"]),
+            "ASK 5061 127.0.0.1:7201"
+        );
+        assert_eq!(
+            redirect(&a, &mut session, &["MGET", "{bar}.a", "{bar}.b"]),
+            "TRYAGAIN Multiple keys request during rehashing of slot"
+        );
+        assert_eq!(
+            redirect(&b, &mut session, &["GET", "{bar}.b"]),
+            "MOVED 5061 127.0.0.1:7200"
+        );
+        assert_eq!(redirect(&b, &mut session, &["ASKING"]), "");
+        Asking.execute_session(&mut session, &b);
+        assert_eq!(redirect(&b, &mut session, &["GET", "{bar}.b"]), "");
+        assert!(!session.asking);
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_keys() {
+        let keys = |args: &[&str]| {
+            let args: Vec<Vec<u8>> = args.iter().map(|arg| arg.as_bytes().to_vec()).collect();
+            command_keys(&String::from_utf8_lossy(&args[0]), &args[1..])
+        };
+        let bytes = |keys: &[&str]| -> Vec<Vec<u8>> {
+            keys.iter().map(|key| key.as_bytes().to_vec()).collect()
+        };
+        assert_eq!(keys(&["get", "a"]), bytes(&["a"]));
+        assert_eq!(keys(&["mset", "a", "1", "b", "2"]), bytes(&["a", "b"]));
+        assert_eq!(keys(&["blpop", "a", "b", "0"]), bytes(&["a", "b"]));
+        assert_eq!(keys(&["eval", "return 1", "1", "a", "x"]), bytes(&["a"]));
+        assert_eq!(
+            keys(&["zunionstore", "d", "2", "a", "b", "WEIGHTS", "1", "2"]),
+            bytes(&["d", "a", "b"])
+        );
+        assert_eq!(
+            keys(&["xread", "COUNT", "1", "STREAMS", "a", "b", "0", "0"]),
+            bytes(&["a", "b"])
+        );
+        assert_eq!(keys(&["xgroup", "CREATE", "s", "g", "$"]), bytes(&["s"]));
+        assert!(keys(&["ping"]).is_empty());
+    }
+}
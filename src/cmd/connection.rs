@@ -0,0 +1,257 @@
+use super::{parse_int, parse_string, CommandError, CommandExecutor, SessionExecutor, Transaction};
+use crate::{
+    Backend, BulkString, MessageReceiver, MessageSender, Protocol, RespArray, RespFrame, RespMap,
+    SimpleError, SimpleString,
+};
+use std::collections::BTreeSet;
+
+/// 每个连接的状态
+#[derive(Debug, Default)]
+pub struct Session {
+    pub id: u64,
+    pub protocol: Protocol,
+    pub name: Option<String>,
+    // 订阅的 channel、pattern 和 shard channel，对应 Redis client 的 pubsub_channels 等字典
+    pub(crate) channels: BTreeSet<Vec<u8>>,
+    pub(crate) patterns: BTreeSet<Vec<u8>>,
+    pub(crate) shard_channels: BTreeSet<Vec<u8>>,
+    // 第一次订阅时创建，发布者写入消息，网络层读取之后推送给客户端
+    pub(crate) messages: Option<(MessageSender, MessageReceiver)>,
+    // MULTI 之后不为 None，命令先排队，EXEC 时一起执行
+    pub(crate) transaction: Option<Transaction>,
+    // WATCH 的 key 以及 WATCH 时 key 的版本
+    pub(crate) watched: Vec<(Vec<u8>, u64)>,
+    // 正在执行的写命令的原始参数 (对应 Redis client 的 argv)，执行之后改写并传播到 AOF
+    pub(crate) argv: Option<Vec<Vec<u8>>>,
+    // 客户端的 IP，replica 连接时用于 INFO replication
+    pub(crate) addr: Option<String>,
+    // replica 通过 REPLCONF listening-port 告知的端口
+    pub(crate) replica_port: Option<u16>,
+    // 最后一次写入之后命令流的 offset (对应 Redis client 的 woff)，WAIT/WAITAOF 等待这个位置被确认
+    pub(crate) woff: u64,
+    // 集群模式下 ASKING 之后，下一条命令可以访问正在迁入的 slot
+    pub(crate) asking: bool,
+}
+
+/// PING [message]
+#[derive(Debug)]
+pub struct Ping {
+    message: Option<Vec<u8>>,
+}
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+#[derive(Debug)]
+pub struct Hello {
+    protover: Option<i64>,
+    name: Option<String>,
+}
+
+impl Session {
+    pub fn new(id: u64) -> Self {
+        Session {
+            id,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_addr(mut self, addr: Option<String>) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    /// 订阅的 channel 和 pattern 的总数，SUBSCRIBE/PSUBSCRIBE 的确认消息中返回这个值
+    pub fn subscriptions(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// 订阅的 shard channel 的数量，和 Redis 一样与普通的订阅分开计数
+    pub fn shard_subscriptions(&self) -> usize {
+        self.shard_channels.len()
+    }
+
+    /// 有订阅时连接处于订阅模式，RESP2 下只能执行订阅相关的命令
+    pub fn is_subscriber(&self) -> bool {
+        self.subscriptions() + self.shard_subscriptions() > 0
+    }
+
+    pub(crate) fn message_sender(&mut self, backend: &Backend) -> MessageSender {
+        self.messages
+            .get_or_insert_with(|| backend.pubsub.channel())
+            .0
+            .clone()
+    }
+
+    /// 等待订阅的 channel 上的消息，没有订阅过时一直等待。
+    /// 积压的消息超过输出缓冲区上限时返回 None，连接需要断开
+    pub async fn next_message(&mut self) -> Option<RespFrame> {
+        match self.messages.as_mut() {
+            // session 自己持有 sender，队列不会被关闭，recv 返回 None 只可能是溢出
+            Some((_, rx)) => rx.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// 订阅的消息积压超过输出缓冲区上限时返回，没有订阅过时一直等待
+    pub async fn output_overflowed(&self) {
+        match self.messages.as_ref() {
+            Some((_, rx)) => rx.overflowed().await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+impl Ping {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() > 1 {
+            return Err(CommandError::WrongArity("ping".to_string()));
+        }
+        Ok(Ping {
+            message: args.into_iter().next(),
+        })
+    }
+}
+
+impl CommandExecutor for Ping {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.message {
+            Some(message) => BulkString::new(message).into(),
+            None => SimpleString::new("PONG").into(),
+        }
+    }
+}
+
+impl SessionExecutor for Ping {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        // RESP2 的订阅模式下只能接收 push 的格式，返回 ["pong", message]
+        if session.is_subscriber() && session.protocol == Protocol::Resp2 {
+            return RespArray::new([
+                BulkString::new("pong").into(),
+                BulkString::new(self.message.unwrap_or_default()).into(),
+            ])
+            .into();
+        }
+        self.execute(backend)
+    }
+}
+
+impl Hello {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut args = args.into_iter();
+        let protover = match args.next() {
+            Some(arg) => Some(parse_int(&arg).map_err(|_| {
+                CommandError::InvalidArgument(
+                    "Protocol version is not an integer or out of range".to_string(),
+                )
+            })?),
+            None => None,
+        };
+        let mut name = None;
+        let args: Vec<Vec<u8>> = args.collect();
+        let mut i = 0;
+        while i < args.len() {
+            let remaining = args.len() - i - 1;
+            match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                // 没有配置密码，和 Redis 的 default 用户 (nopass) 一样接受任意密码
+                "AUTH" if remaining >= 2 => i += 3,
+                "SETNAME" if remaining >= 1 => {
+                    name = Some(parse_string(args[i + 1].clone())?);
+                    i += 2;
+                }
+                other => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Syntax error in HELLO option '{}'",
+                        other
+                    )))
+                }
+            }
+        }
+        Ok(Hello { protover, name })
+    }
+}
+
+impl CommandExecutor for Hello {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for Hello {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        match self.protover {
+            None => {}
+            Some(2) => session.protocol = Protocol::Resp2,
+            Some(3) => session.protocol = Protocol::Resp3,
+            Some(_) => return SimpleError::new("NOPROTO unsupported protocol version").into(),
+        }
+        if let Some(name) = self.name {
+            session.name = Some(name);
+        }
+
+        let proto = match session.protocol {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        };
+        let mut map = RespMap::new();
+        map.insert("server".to_string(), BulkString::new("redis").into());
+        map.insert("version".to_string(), BulkString::new("7.4.0").into());
+        map.insert("proto".to_string(), proto.into());
+        map.insert("id".to_string(), (session.id as i64).into());
+        let mode = if backend.cluster_enabled() {
+            "cluster"
+        } else {
+            "standalone"
+        };
+        map.insert("mode".to_string(), BulkString::new(mode).into());
+        let role = if backend.is_replica() {
+            "replica"
+        } else {
+            "master"
+        };
+        map.insert("role".to_string(), BulkString::new(role).into());
+        map.insert("modules".to_string(), RespArray::new(vec![]).into());
+        map.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use anyhow::Result;
+
+    fn hello(session: &mut Session, args: &[&str]) -> Result<RespFrame> {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        let cmd: Command = RespArray::new(frames).try_into()?;
+        let Command::Hello(cmd) = cmd else {
+            panic!("expected HELLO");
+        };
+        Ok(cmd.execute_session(session, &Backend::new()))
+    }
+
+    #[test]
+    fn test_hello() -> Result<()> {
+        let mut session = Session::new(7);
+        let RespFrame::Map(map) = hello(&mut session, &["HELLO", "3", "SETNAME", "worker"])? else {
+            panic!("HELLO should return a map");
+        };
+        assert_eq!(session.protocol, Protocol::Resp3);
+        assert_eq!(session.name.as_deref(), Some("worker"));
+        assert_eq!(map.get(b"proto".as_slice()), Some(&3.into()));
+        assert_eq!(map.get(b"id".as_slice()), Some(&7.into()));
+
+        hello(&mut session, &["HELLO", "2", "AUTH", "default", "secret"])?;
+        assert_eq!(session.protocol, Protocol::Resp2);
+
+        assert_eq!(
+            hello(&mut session, &["HELLO", "4"])?,
+            SimpleError::new("NOPROTO unsupported protocol version").into()
+        );
+        assert!(hello(&mut session, &["HELLO", "x"]).is_err());
+        assert!(hello(&mut session, &["HELLO", "3", "AUTH", "default"]).is_err());
+        assert_eq!(session.protocol, Protocol::Resp2);
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use super::{parse_int, validate_args, CommandError, CommandExecutor};
+use crate::{backend::now_ms, Backend, RespFrame};
+
+/// EXPIRE key seconds [NX|XX|GT|LT]
+/// PEXPIRE key milliseconds [NX|XX|GT|LT]
+/// EXPIREAT key unix-time-seconds [NX|XX|GT|LT]
+/// PEXPIREAT key unix-time-milliseconds [NX|XX|GT|LT]
+#[derive(Debug)]
+pub struct Expire {
+    name: &'static str,
+    key: Vec<u8>,
+    time: ExpireTime,
+    flags: ExpireFlags,
+}
+
+/// TTL/PTTL/EXPIRETIME/PEXPIRETIME key
+#[derive(Debug)]
+pub struct Ttl {
+    key: Vec<u8>,
+    kind: TtlKind,
+}
+
+/// PERSIST key
+#[derive(Debug)]
+pub struct Persist {
+    key: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExpireTime {
+    // 相对当前时间的毫秒数
+    Relative(i64),
+    // unix 毫秒
+    Absolute(i64),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct ExpireFlags {
+    // 只有 key 没有过期时间时才设置
+    nx: bool,
+    // 只有 key 已经有过期时间时才设置
+    xx: bool,
+    // 新的过期时间大于当前的过期时间时才设置，没有过期时间的 key 视为永不过期
+    gt: bool,
+    // 新的过期时间小于当前的过期时间时才设置
+    lt: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TtlKind {
+    Ttl,
+    PTtl,
+    ExpireTime,
+    PExpireTime,
+}
+
+impl Expire {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let n = parse_int(&args.next().unwrap_or_default())?;
+
+        let invalid =
+            || CommandError::InvalidArgument(format!("invalid expire time in '{}' command", name));
+        let time = match name {
+            "expire" => ExpireTime::Relative(n.checked_mul(1000).ok_or_else(invalid)?),
+            "pexpire" => ExpireTime::Relative(n),
+            "expireat" => ExpireTime::Absolute(n.checked_mul(1000).ok_or_else(invalid)?),
+            _ => ExpireTime::Absolute(n),
+        };
+
+        let mut flags = ExpireFlags::default();
+        for arg in args {
+            match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                "NX" => flags.nx = true,
+                "XX" => flags.xx = true,
+                "GT" => flags.gt = true,
+                "LT" => flags.lt = true,
+                other => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unsupported option {}",
+                        other
+                    )))
+                }
+            }
+        }
+        if flags.nx && (flags.xx || flags.gt || flags.lt) {
+            return Err(CommandError::InvalidArgument(
+                "NX and XX, GT or LT options at the same time are not compatible".to_string(),
+            ));
+        }
+        if flags.gt && flags.lt {
+            return Err(CommandError::InvalidArgument(
+                "GT and LT options at the same time are not compatible".to_string(),
+            ));
+        }
+
+        Ok(Expire {
+            name,
+            key,
+            time,
+            flags,
+        })
+    }
+}
+
+impl CommandExecutor for Expire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !backend.exists(&self.key) {
+            return 0.into();
+        }
+        let now = now_ms() as i64;
+        let at = match self.time {
+            ExpireTime::Relative(ms) => match ms.checked_add(now) {
+                Some(at) => at,
+                None => {
+                    return CommandError::InvalidArgument(format!(
+                        "invalid expire time in '{}' command",
+                        self.name
+                    ))
+                    .into()
+                }
+            },
+            ExpireTime::Absolute(at) => at,
+        };
+
+        let current = backend.get_expire(&self.key).map(|v| v as i64);
+        let flags = self.flags;
+        let rejected = flags.nx && current.is_some()
+            || flags.xx && current.is_none()
+            || flags.gt && current.is_none_or(|current| at <= current)
+            || flags.lt && current.is_some_and(|current| at >= current);
+        if rejected {
+            return 0.into();
+        }
+
+        // 过期时间已经过去时直接删除 key
+        if at <= now {
+            backend.remove(&self.key);
+        } else {
+            backend.set_expire(&self.key, at as u64);
+        }
+        1.into()
+    }
+}
+
+impl Ttl {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args(name, &args, 1)?;
+        let kind = match name {
+            "ttl" => TtlKind::Ttl,
+            "pttl" => TtlKind::PTtl,
+            "expiretime" => TtlKind::ExpireTime,
+            _ => TtlKind::PExpireTime,
+        };
+        Ok(Ttl {
+            key: args.into_iter().next().unwrap_or_default(),
+            kind,
+        })
+    }
+}
+
+impl CommandExecutor for Ttl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !backend.exists(&self.key) {
+            return (-2).into();
+        }
+        let at = match backend.get_expire(&self.key) {
+            Some(at) => at as i64,
+            None => return (-1).into(),
+        };
+        let ttl = (at - now_ms() as i64).max(0);
+        let ret = match self.kind {
+            // 和 Redis 一样四舍五入到秒
+            TtlKind::Ttl => (ttl + 500) / 1000,
+            TtlKind::PTtl => ttl,
+            TtlKind::ExpireTime => at / 1000,
+            TtlKind::PExpireTime => at,
+        };
+        ret.into()
+    }
+}
+
+impl Persist {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("persist", &args, 1)?;
+        Ok(Persist {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for Persist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        (backend.persist(&self.key) as i64).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::run;
+    use crate::Value;
+    use anyhow::Result;
+
+    #[test]
+    fn test_expire_ttl() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["EXPIRE", "k", "100"])?, 0.into());
+        assert_eq!(run(&backend, &["TTL", "k"])?, (-2).into());
+
+        backend.set(b"k".to_vec(), Value::String(b"v".to_vec()));
+        assert_eq!(run(&backend, &["TTL", "k"])?, (-1).into());
+        assert_eq!(run(&backend, &["EXPIRETIME", "k"])?, (-1).into());
+
+        assert_eq!(run(&backend, &["EXPIRE", "k", "100"])?, 1.into());
+        assert_eq!(run(&backend, &["TTL", "k"])?, 100.into());
+        let RespFrame::Integer(pttl) = run(&backend, &["PTTL", "k"])? else {
+            panic!("PTTL should return an integer");
+        };
+        assert!(pttl > 99_000 && pttl <= 100_000);
+
+        assert_eq!(
+            run(&backend, &["PEXPIREAT", "k", "99999999999999"])?,
+            1.into()
+        );
+        assert_eq!(run(&backend, &["PEXPIRETIME", "k"])?, 99999999999999.into());
+        assert_eq!(run(&backend, &["EXPIRETIME", "k"])?, 99999999999.into());
+
+        assert_eq!(run(&backend, &["PERSIST", "k"])?, 1.into());
+        assert_eq!(run(&backend, &["PERSIST", "k"])?, 0.into());
+        assert_eq!(run(&backend, &["TTL", "k"])?, (-1).into());
+
+        // 过去的时间会直接删除 key
+        assert_eq!(run(&backend, &["EXPIREAT", "k", "1"])?, 1.into());
+        assert_eq!(backend.get(b"k"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_flags() -> Result<()> {
+        let backend = Backend::new();
+        backend.set(b"k".to_vec(), Value::String(b"v".to_vec()));
+
+        assert_eq!(run(&backend, &["EXPIRE", "k", "100", "XX"])?, 0.into());
+        assert_eq!(run(&backend, &["EXPIRE", "k", "100", "GT"])?, 0.into());
+        assert_eq!(run(&backend, &["EXPIRE", "k", "100", "NX"])?, 1.into());
+        assert_eq!(run(&backend, &["EXPIRE", "k", "200", "NX"])?, 0.into());
+        assert_eq!(run(&backend, &["EXPIRE", "k", "50", "GT"])?, 0.into());
+        assert_eq!(run(&backend, &["EXPIRE", "k", "200", "gt"])?, 1.into());
+        assert_eq!(run(&backend, &["EXPIRE", "k", "300", "LT"])?, 0.into());
+        assert_eq!(
+            run(&backend, &["EXPIRE", "k", "150", "XX", "LT"])?,
+            1.into()
+        );
+        assert_eq!(run(&backend, &["TTL", "k"])?, 150.into());
+
+        backend.persist(b"k");
+        assert_eq!(run(&backend, &["EXPIRE", "k", "100", "LT"])?, 1.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_invalid() {
+        let backend = Backend::new();
+        let ret = run(&backend, &["EXPIRE", "k", "100", "NX", "GT"]);
+        assert!(ret.is_err());
+        let ret = run(&backend, &["EXPIRE", "k", "100", "GT", "LT"]);
+        assert!(ret.is_err());
+        let ret = run(&backend, &["EXPIRE", "k", "abc"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>().unwrap(),
+            CommandError::NotInteger
+        );
+        let ret = run(&backend, &["EXPIRE", "k", &i64::MAX.to_string()]);
+        assert!(ret.is_err());
+    }
+}
@@ -0,0 +1,744 @@
+use super::{
+    parse_string,
+    scripting::{
+        call_with_redis, lua_error_message, parse_keys_and_args, runtime_error, script_error,
+        string_table, with_lua, ScriptError,
+    },
+    CommandError, CommandExecutor,
+};
+use crate::{
+    glob_match, parse_function_dump, Backend, BulkString, FunctionInfo, Library, RdbError,
+    RespArray, RespFrame, RespMap, RespNullBulkString, RespSet, RestorePolicy, SimpleError,
+    SimpleString,
+};
+use mlua::{Function as LuaFunction, Lua, Table, Value, Variadic};
+
+/// FUNCTION LOAD [REPLACE] code | LIST [WITHCODE] [LIBRARYNAME pattern] | DELETE library |
+/// DUMP | RESTORE payload [FLUSH|APPEND|REPLACE] | FLUSH [ASYNC|SYNC] | KILL
+#[derive(Debug)]
+pub enum Function {
+    Load {
+        code: String,
+        replace: bool,
+    },
+    List {
+        pattern: Option<Vec<u8>>,
+        with_code: bool,
+    },
+    Delete(String),
+    Dump,
+    Restore {
+        payload: Vec<u8>,
+        policy: RestorePolicy,
+    },
+    Flush,
+    Kill,
+}
+
+/// FCALL function numkeys [key [key ...]] [arg [arg ...]]
+/// FCALL_RO function numkeys [key [key ...]] [arg [arg ...]]
+#[derive(Debug)]
+pub struct FCall {
+    function: String,
+    keys: Vec<Vec<u8>>,
+    args: Vec<Vec<u8>>,
+    read_only: bool,
+}
+
+/// 函数可以声明的 flag
+const FUNCTION_FLAGS: [&str; 5] = [
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+/// 加载 library 时提供的 redis.register_function，注册的函数保存在返回的 table 中
+const LOADER: &str = r#"
+local registered = {}
+redis.register_function = function(...)
+    local name, callback, flags, description
+    if select('#', ...) == 1 and type(...) == 'table' then
+        local t = ...
+        name, callback, flags, description = t.function_name, t.callback, t.flags, t.description
+    elseif select('#', ...) == 2 then
+        name, callback = ...
+    else
+        error('wrong number of arguments to redis.register_function', 2)
+    end
+    if type(name) ~= 'string' then
+        error('function_name argument given to redis.register_function must be a string', 2)
+    end
+    if type(callback) ~= 'function' then
+        error('callback argument given to redis.register_function must be a function', 2)
+    end
+    if registered[name] then
+        error('Function ' .. name .. ' already exists', 2)
+    end
+    registered[name] = { callback = callback, flags = flags or {}, description = description }
+end
+return registered
+"#;
+
+/// library 注册的函数保存在虚拟机 registry 的这个 table 中：
+/// library 名字 -> 函数名 -> {callback, flags, description}
+const LIBRARIES_REGISTRY: &str = "libraries";
+
+impl Function {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut args = args.into_iter();
+        let Some(sub) = args.next() else {
+            return Err(CommandError::WrongArity("function".to_string()));
+        };
+        let sub = String::from_utf8_lossy(&sub).to_lowercase();
+        let args: Vec<Vec<u8>> = args.collect();
+        let arity = || CommandError::WrongArity(format!("function|{}", sub));
+        match sub.as_str() {
+            "load" => {
+                let (replace, code) = match args.as_slice() {
+                    [code] => (false, code),
+                    [flag, code] if flag.eq_ignore_ascii_case(b"replace") => (true, code),
+                    [] => return Err(arity()),
+                    _ => return Err(CommandError::Syntax),
+                };
+                Ok(Function::Load {
+                    code: parse_string(code.clone())?,
+                    replace,
+                })
+            }
+            "list" => {
+                let mut pattern = None;
+                let mut with_code = false;
+                let mut args = args.into_iter();
+                while let Some(arg) = args.next() {
+                    match String::from_utf8_lossy(&arg).to_lowercase().as_str() {
+                        "withcode" => with_code = true,
+                        "libraryname" => pattern = Some(args.next().ok_or(CommandError::Syntax)?),
+                        _ => return Err(CommandError::Syntax),
+                    }
+                }
+                Ok(Function::List { pattern, with_code })
+            }
+            "delete" if args.len() == 1 => Ok(Function::Delete(parse_string(
+                args.into_iter().next().unwrap_or_default(),
+            )?)),
+            "dump" if args.is_empty() => Ok(Function::Dump),
+            "restore" if matches!(args.len(), 1 | 2) => {
+                let mut args = args.into_iter();
+                let payload = args.next().unwrap_or_default();
+                let policy = match args.next() {
+                    None => RestorePolicy::Append,
+                    Some(policy) => match String::from_utf8_lossy(&policy).to_lowercase().as_str()
+                    {
+                        "append" => RestorePolicy::Append,
+                        "replace" => RestorePolicy::Replace,
+                        "flush" => RestorePolicy::Flush,
+                        _ => {
+                            return Err(CommandError::InvalidArgument(
+                                "Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE."
+                                    .to_string(),
+                            ))
+                        }
+                    },
+                };
+                Ok(Function::Restore { payload, policy })
+            }
+            "flush" if args.len() <= 1 => {
+                if let Some(mode) = args.first() {
+                    let mode = String::from_utf8_lossy(mode).to_lowercase();
+                    if mode != "async" && mode != "sync" {
+                        return Err(CommandError::Syntax);
+                    }
+                }
+                Ok(Function::Flush)
+            }
+            "kill" if args.is_empty() => Ok(Function::Kill),
+            "delete" | "dump" | "restore" | "flush" | "kill" => Err(arity()),
+            _ => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'. Try FUNCTION HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+impl CommandExecutor for Function {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self {
+            Function::Load { code, replace } => {
+                let policy = match replace {
+                    true => RestorePolicy::Replace,
+                    false => RestorePolicy::Append,
+                };
+                match load_libraries(backend, vec![code], policy) {
+                    Ok(names) => {
+                        backend.mark_dirty();
+                        BulkString::new(names.concat()).into()
+                    }
+                    Err(e) => error(e),
+                }
+            }
+            Function::List { pattern, with_code } => RespArray::new(
+                backend
+                    .functions
+                    .libraries()
+                    .into_iter()
+                    .filter(|library| {
+                        pattern
+                            .as_ref()
+                            .is_none_or(|p| glob_match(p, library.name.as_bytes(), false))
+                    })
+                    .map(|library| library_info(library, with_code))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Function::Delete(name) => match delete_library(backend, &name) {
+                true => {
+                    backend.mark_dirty();
+                    SimpleString::new("OK").into()
+                }
+                false => error("Library not found".to_string()),
+            },
+            Function::Dump => BulkString::new(backend.functions.dump()).into(),
+            Function::Restore { payload, policy } => {
+                let codes = match parse_function_dump(&payload) {
+                    Ok(codes) => codes,
+                    Err(RdbError::Invalid(msg)) => return error(msg),
+                    Err(e) => return error(e.to_string()),
+                };
+                match load_libraries(backend, codes, policy) {
+                    Ok(_) => {
+                        backend.mark_dirty();
+                        SimpleString::new("OK").into()
+                    }
+                    Err(e) => error(e),
+                }
+            }
+            Function::Flush => {
+                flush_libraries(backend);
+                backend.mark_dirty();
+                SimpleString::new("OK").into()
+            }
+            // 不需要执行锁，在函数执行期间由其他连接发送
+            Function::Kill => match backend.lua.run().kill(true) {
+                Ok(()) => SimpleString::new("OK").into(),
+                Err(e) => SimpleError::new(e.to_string()).into(),
+            },
+        }
+    }
+}
+
+impl FCall {
+    pub(crate) fn parse(name: &str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let function = parse_string(args.next().unwrap_or_default())?;
+        let (keys, args) = parse_keys_and_args(args)?;
+        Ok(FCall {
+            function,
+            keys,
+            args,
+            read_only: name == "fcall_ro",
+        })
+    }
+}
+
+impl CommandExecutor for FCall {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let Some((library, function)) = backend.find_function(&self.function) else {
+            return error("Function not found".to_string());
+        };
+        // FCALL_RO 只能调用声明了 no-writes 的函数，这样的函数即使通过 FCALL 调用也不能写入
+        if self.read_only && !function.is_read_only() {
+            return error(
+                "Can not execute a script with write flag using *_ro command.".to_string(),
+            );
+        }
+        match call_function(backend, &library, &function, self.keys, self.args) {
+            Ok(frame) => frame,
+            Err(e) => script_error(e, &self.function),
+        }
+    }
+}
+
+fn error(msg: String) -> RespFrame {
+    SimpleError::new(format!("ERR {}", msg)).into()
+}
+
+/// 调用 FUNCTION LOAD 时注册在虚拟机中的函数
+fn call_function(
+    backend: &Backend,
+    library: &str,
+    function: &FunctionInfo,
+    keys: Vec<Vec<u8>>,
+    args: Vec<Vec<u8>>,
+) -> Result<RespFrame, ScriptError> {
+    let runtime = runtime_error;
+    with_lua(backend, |lua| {
+        let registered: Table = libraries_table(lua)
+            .and_then(|libraries| libraries.raw_get(library))
+            .map_err(runtime)?;
+        let entry: Table = registered
+            .raw_get(function.name.as_str())
+            .map_err(runtime)?;
+        let callback: LuaFunction = entry.raw_get("callback").map_err(runtime)?;
+        let args = Variadic::from_iter([
+            Value::Table(string_table(lua, &keys).map_err(runtime)?),
+            Value::Table(string_table(lua, &args).map_err(runtime)?),
+        ]);
+        call_with_redis(lua, backend, callback, args, function.is_read_only(), true)
+    })
+    .map_err(runtime)?
+}
+
+/// 虚拟机中保存注册的函数的 table，第一次使用时创建
+fn libraries_table(lua: &Lua) -> mlua::Result<Table<'_>> {
+    match lua.named_registry_value::<Option<Table>>(LIBRARIES_REGISTRY)? {
+        Some(libraries) => Ok(libraries),
+        None => {
+            let libraries = lua.create_table()?;
+            lua.set_named_registry_value(LIBRARIES_REGISTRY, libraries.clone())?;
+            Ok(libraries)
+        }
+    }
+}
+
+/// 在共享的虚拟机中加载一组 library，返回 library 的名字。FUNCTION LOAD/RESTORE 以及
+/// 加载 RDB/AOF 时使用，注册的函数保存在虚拟机中，FCALL 直接调用，不需要重新加载代码
+///
+/// 任何一个 library 加载失败或者名字冲突时，已经加载的 library 都不会改变
+pub(crate) fn load_libraries(
+    backend: &Backend,
+    codes: Vec<String>,
+    policy: RestorePolicy,
+) -> Result<Vec<String>, String> {
+    with_lua(backend, |lua| {
+        let mut libraries = Vec::new();
+        let mut registered = Vec::new();
+        for code in codes {
+            let (library, functions) = compile_library(lua, code)?;
+            registered.push((library.name.clone(), functions));
+            libraries.push(library);
+        }
+        backend.functions.load(libraries, policy)?;
+        if policy == RestorePolicy::Flush {
+            lua.set_named_registry_value(LIBRARIES_REGISTRY, Value::Nil)
+                .map_err(|e| lua_error_message(&e))?;
+        }
+        let table = libraries_table(lua).map_err(|e| lua_error_message(&e))?;
+        let mut names = Vec::new();
+        for (name, functions) in registered {
+            table
+                .raw_set(name.as_str(), functions)
+                .map_err(|e| lua_error_message(&e))?;
+            names.push(name);
+        }
+        Ok(names)
+    })
+    .map_err(|e| lua_error_message(&e))?
+}
+
+/// 删除 library 以及虚拟机中注册的函数，不存在时返回 false
+fn delete_library(backend: &Backend, name: &str) -> bool {
+    if !backend.functions.delete(name) {
+        return false;
+    }
+    if let Some(lua) = backend.lua.lock().as_ref() {
+        if let Ok(libraries) = libraries_table(lua) {
+            let _ = libraries.raw_set(name, Value::Nil);
+        }
+    }
+    true
+}
+
+/// 删除所有的 library 以及虚拟机中注册的函数
+pub(crate) fn flush_libraries(backend: &Backend) {
+    backend.functions.flush();
+    if let Some(lua) = backend.lua.lock().as_ref() {
+        let _ = lua.set_named_registry_value(LIBRARIES_REGISTRY, Value::Nil);
+    }
+}
+
+/// 解析 library 代码第一行的 `#!<engine> name=<library>`，返回 library 名字和去掉第一行的代码
+fn parse_metadata(code: &str) -> Result<(String, &str), String> {
+    let (header, body) = code.split_once('\n').unwrap_or((code, ""));
+    let Some(header) = header.strip_prefix("#!") else {
+        return Err("Missing library metadata".to_string());
+    };
+    let mut parts = header.split_whitespace();
+    let engine = parts.next().unwrap_or_default();
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(format!("Engine '{}' not found", engine));
+    }
+    let mut name = None;
+    for part in parts {
+        match part.strip_prefix("name=") {
+            Some(value) => name = Some(value.to_string()),
+            None => return Err(format!("Invalid metadata value given: {}", part)),
+        }
+    }
+    let Some(name) = name else {
+        return Err("Library name was not given".to_string());
+    };
+    if !is_valid_name(&name) {
+        return Err(
+            "Library names can only contain letters, numbers, or underscores(_) and must be at least one character long"
+                .to_string(),
+        );
+    }
+    Ok((name, body))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// 执行 library 的代码，返回注册的函数：函数名 -> {callback, flags, description}
+fn load_library<'lua>(lua: &'lua Lua, body: &str) -> Result<Table<'lua>, String> {
+    let registered: Table = lua
+        .load(LOADER)
+        .set_name("@redis")
+        .eval()
+        .map_err(|e| lua_error_message(&e))?;
+    let ret = lua
+        .load(body)
+        .set_name("@user_function")
+        .into_function()
+        .map_err(|e| format!("Error compiling function: {}", lua_error_message(&e)))
+        .and_then(|func| {
+            func.call::<_, ()>(())
+                .map_err(|e| format!("Error registering functions: {}", lua_error_message(&e)))
+        });
+    // 只能在加载 library 时注册函数
+    let redis: Table = lua
+        .globals()
+        .get("redis")
+        .map_err(|e| lua_error_message(&e))?;
+    redis
+        .raw_set("register_function", Value::Nil)
+        .map_err(|e| lua_error_message(&e))?;
+    ret.map(|_| registered)
+}
+
+/// 加载 library 并检查注册的函数，返回 library 和注册的函数
+fn compile_library(lua: &Lua, code: String) -> Result<(Library, Table<'_>), String> {
+    let (name, body) = parse_metadata(&code)?;
+    let registered = load_library(lua, body)?;
+    let mut functions = Vec::new();
+    for pair in registered.clone().pairs::<String, Table>() {
+        let (function, entry) = pair.map_err(|e| lua_error_message(&e))?;
+        if !is_valid_name(&function) {
+            return Err(
+                "Function names can only contain letters, numbers, or underscores(_) and must be at least one character long"
+                    .to_string(),
+            );
+        }
+        let flags: Vec<String> = entry
+            .get::<_, Table>("flags")
+            .and_then(|flags| flags.sequence_values::<String>().collect())
+            .map_err(|_| "unknown flag given".to_string())?;
+        if let Some(flag) = flags.iter().find(|f| !FUNCTION_FLAGS.contains(&f.as_str())) {
+            return Err(format!("unknown flag given: {}", flag));
+        }
+        let description: Option<String> = entry
+            .get("description")
+            .map_err(|_| "description must be a string".to_string())?;
+        functions.push(FunctionInfo {
+            name: function,
+            description,
+            flags,
+        });
+    }
+    if functions.is_empty() {
+        return Err("No functions registered".to_string());
+    }
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    let library = Library {
+        name,
+        engine: "LUA".to_string(),
+        code,
+        functions,
+    };
+    Ok((library, registered))
+}
+
+/// FUNCTION LIST 中的一个 library
+fn library_info(library: Library, with_code: bool) -> RespFrame {
+    let functions: Vec<RespFrame> = library
+        .functions
+        .into_iter()
+        .map(|function| {
+            let mut map = RespMap::new();
+            map.insert("name".to_string(), BulkString::new(function.name).into());
+            map.insert(
+                "description".to_string(),
+                function
+                    .description
+                    .map_or(RespNullBulkString.into(), |d| BulkString::new(d).into()),
+            );
+            map.insert(
+                "flags".to_string(),
+                RespSet::new(
+                    function
+                        .flags
+                        .into_iter()
+                        .map(|flag| BulkString::new(flag).into())
+                        .collect::<Vec<_>>(),
+                )
+                .into(),
+            );
+            map.into()
+        })
+        .collect();
+    let mut map = RespMap::new();
+    map.insert(
+        "library_name".to_string(),
+        BulkString::new(library.name).into(),
+    );
+    map.insert("engine".to_string(), BulkString::new(library.engine).into());
+    map.insert("functions".to_string(), RespArray::new(functions).into());
+    if with_code {
+        map.insert(
+            "library_code".to_string(),
+            BulkString::new(library.code).into(),
+        );
+    }
+    map.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::run;
+    use anyhow::Result;
+
+    const LIBRARY: &str = "#!lua name=mylib
+local function set(keys, args)
+    return redis.call('SET', keys[1], args[1])
+end
+redis.register_function('myset', set)
+redis.register_function{
+    function_name = 'myget',
+    callback = function(keys) return redis.call('GET', keys[1]) end,
+    flags = { 'no-writes' },
+    description = 'get a key',
+}
+redis.register_function{
+    function_name = 'sneaky',
+    callback = function(keys) return redis.call('SET', keys[1], 'x') end,
+    flags = { 'no-writes' },
+}
+";
+
+    fn bulk(s: &str) -> RespFrame {
+        BulkString::new(s).into()
+    }
+
+    #[test]
+    fn test_function_load_and_fcall() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, &["FUNCTION", "LOAD", LIBRARY])?,
+            bulk("mylib")
+        );
+        assert_eq!(
+            run(&backend, &["FUNCTION", "LOAD", LIBRARY])?,
+            SimpleError::new("ERR Library 'mylib' already exists").into()
+        );
+        assert_eq!(
+            run(&backend, &["FUNCTION", "LOAD", "REPLACE", LIBRARY])?,
+            bulk("mylib")
+        );
+
+        assert_eq!(
+            run(&backend, &["FCALL", "myset", "1", "k", "v"])?,
+            "OK".into()
+        );
+        assert_eq!(run(&backend, &["FCALL_RO", "myget", "1", "k"])?, bulk("v"));
+        assert_eq!(
+            run(&backend, &["FCALL_RO", "myset", "1", "k", "v"])?,
+            SimpleError::new("ERR Can not execute a script with write flag using *_ro command.")
+                .into()
+        );
+        // 声明了 no-writes 的函数执行写命令时会被拒绝
+        assert_eq!(
+            run(&backend, &["FCALL", "sneaky", "1", "k"])?,
+            SimpleError::new("ERR Write commands are not allowed from read-only scripts.").into()
+        );
+        assert_eq!(run(&backend, &["GET", "k"])?, bulk("v"));
+        assert_eq!(
+            run(&backend, &["FCALL", "nosuch", "0"])?,
+            SimpleError::new("ERR Function not found").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_load_errors() -> Result<()> {
+        let backend = Backend::new();
+        let cases = [
+            ("return 1", "ERR Missing library metadata"),
+            ("#!js name=a\n", "ERR Engine 'js' not found"),
+            ("#!lua\n", "ERR Library name was not given"),
+            ("#!lua name=a foo=b\n", "ERR Invalid metadata value given: foo=b"),
+            ("#!lua name=a\nreturn 1", "ERR No functions registered"),
+            (
+                "#!lua name=a\nredis.register_function{function_name='f', callback=function() end, flags={'bad'}}",
+                "ERR unknown flag given: bad",
+            ),
+        ];
+        for (code, err) in cases {
+            assert_eq!(
+                run(&backend, &["FUNCTION", "LOAD", code])?,
+                SimpleError::new(err).into()
+            );
+        }
+        let RespFrame::Error(e) = run(&backend, &["FUNCTION", "LOAD", "#!lua name=a\nreturn ("])?
+        else {
+            panic!("compile error expected");
+        };
+        assert!(e.starts_with("ERR Error compiling function"));
+
+        // 不同的 library 中不能有同名的函数
+        run(&backend, &["FUNCTION", "LOAD", LIBRARY])?;
+        assert_eq!(
+            run(
+                &backend,
+                &[
+                    "FUNCTION",
+                    "LOAD",
+                    "#!lua name=other\nredis.register_function('myget', function() end)"
+                ]
+            )?,
+            SimpleError::new("ERR Function myget already exists").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_list_delete_dump_restore() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["FUNCTION", "LOAD", LIBRARY])?;
+        run(
+            &backend,
+            &[
+                "FUNCTION",
+                "LOAD",
+                "#!lua name=other\nredis.register_function('f', function() return 1 end)",
+            ],
+        )?;
+
+        let RespFrame::Array(list) = run(&backend, &["FUNCTION", "LIST", "LIBRARYNAME", "my*"])?
+        else {
+            panic!("array expected");
+        };
+        assert_eq!(list.len(), 1);
+        let RespFrame::Map(ref library) = list[0] else {
+            panic!("map expected");
+        };
+        assert_eq!(library.get(b"library_name"), Some(&bulk("mylib")));
+        assert!(library.get(b"library_code").is_none());
+        let Some(RespFrame::Array(functions)) = library.get(b"functions") else {
+            panic!("array expected");
+        };
+        assert_eq!(functions.len(), 3);
+        let RespFrame::Map(ref myget) = functions[0] else {
+            panic!("map expected");
+        };
+        assert_eq!(myget.get(b"name"), Some(&bulk("myget")));
+        assert_eq!(myget.get(b"description"), Some(&bulk("get a key")));
+        assert_eq!(
+            myget.get(b"flags"),
+            Some(&RespSet::new([bulk("no-writes")]).into())
+        );
+
+        let RespFrame::BulkString(payload) = run(&backend, &["FUNCTION", "DUMP"])? else {
+            panic!("bulk string expected");
+        };
+        assert_eq!(
+            run(&backend, &["FUNCTION", "DELETE", "other"])?,
+            "OK".into()
+        );
+        assert_eq!(
+            run(&backend, &["FUNCTION", "DELETE", "other"])?,
+            SimpleError::new("ERR Library not found").into()
+        );
+        assert_eq!(
+            run(&backend, &["FCALL", "f", "0"])?,
+            SimpleError::new("ERR Function not found").into()
+        );
+
+        let restore = |policy| {
+            Function::Restore {
+                payload: payload.0.clone(),
+                policy,
+            }
+            .execute(&backend)
+        };
+        assert_eq!(
+            restore(RestorePolicy::Append),
+            SimpleError::new("ERR Library 'mylib' already exists").into()
+        );
+        assert_eq!(restore(RestorePolicy::Replace), "OK".into());
+        assert_eq!(run(&backend, &["FCALL", "f", "0"])?, 1.into());
+        run(&backend, &["FUNCTION", "FLUSH"])?;
+        assert_eq!(restore(RestorePolicy::Append), "OK".into());
+        assert_eq!(backend.functions.libraries().len(), 2);
+
+        assert_eq!(
+            run(&backend, &["FUNCTION", "RESTORE", "garbage"])?,
+            SimpleError::new("ERR payload version or checksum are wrong").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_state_and_kill() -> Result<()> {
+        let backend = Backend::new();
+        let counter = "#!lua name=counter
+local n = 0
+redis.register_function('incr', function() n = n + 1 return n end)
+redis.register_function('spin', function() while true do end end)
+";
+        run(&backend, &["FUNCTION", "LOAD", counter])?;
+        // library 只在加载时执行一次，FCALL 直接调用注册的函数
+        assert_eq!(run(&backend, &["FCALL", "incr", "0"])?, 1.into());
+        assert_eq!(run(&backend, &["FCALL", "incr", "0"])?, 2.into());
+        run(&backend, &["FUNCTION", "LOAD", "REPLACE", counter])?;
+        assert_eq!(run(&backend, &["FCALL", "incr", "0"])?, 1.into());
+        // 加载之后不能再注册函数
+        let RespFrame::Error(e) = run(
+            &backend,
+            &["EVAL", "return redis.register_function('x', print)", "0"],
+        )?
+        else {
+            panic!("register_function should be unavailable");
+        };
+        assert!(e.contains("register_function"));
+
+        let b = backend.clone();
+        let handle = std::thread::spawn(move || run(&b, &["FCALL", "spin", "0"]));
+        while !backend.lua.run().is_running() {
+            std::thread::yield_now();
+        }
+        assert_eq!(
+            run(&backend, &["SCRIPT", "KILL"])?,
+            SimpleError::new("NOTBUSY No scripts in execution right now.").into()
+        );
+        assert_eq!(run(&backend, &["FUNCTION", "KILL"])?, "OK".into());
+        let RespFrame::Error(e) = handle.join().unwrap()? else {
+            panic!("killed function should fail");
+        };
+        assert!(e.starts_with("ERR Script killed by user with SCRIPT KILL"));
+
+        run(&backend, &["FUNCTION", "DELETE", "counter"])?;
+        assert_eq!(
+            run(&backend, &["FCALL", "incr", "0"])?,
+            SimpleError::new("ERR Function not found").into()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1278 @@
+use super::{
+    parse_float, parse_int, validate_args, CommandError, CommandExecutor, Session, SessionExecutor,
+};
+use crate::{
+    backend::{glob_match, now_ms},
+    format_double, Backend, BulkString, Hash, Protocol, RespArray, RespFrame, RespMap,
+    RespNullBulkString, SimpleString,
+};
+use rand::{seq::index, Rng};
+
+/// HSET key field value [field value ...]，以及已经废弃的 HMSET
+#[derive(Debug)]
+pub struct HSet {
+    name: &'static str,
+    key: Vec<u8>,
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// HSETNX key field value
+#[derive(Debug)]
+pub struct HSetNx {
+    key: Vec<u8>,
+    field: Vec<u8>,
+    value: Vec<u8>,
+}
+
+/// HGET key field
+#[derive(Debug)]
+pub struct HGet {
+    key: Vec<u8>,
+    field: Vec<u8>,
+}
+
+/// HMGET key field [field ...]
+#[derive(Debug)]
+pub struct HMGet {
+    key: Vec<u8>,
+    fields: Vec<Vec<u8>>,
+}
+
+/// HGETALL key
+#[derive(Debug)]
+pub struct HGetAll {
+    key: Vec<u8>,
+}
+
+/// HDEL key field [field ...]
+#[derive(Debug)]
+pub struct HDel {
+    key: Vec<u8>,
+    fields: Vec<Vec<u8>>,
+}
+
+/// HEXISTS key field
+#[derive(Debug)]
+pub struct HExists {
+    key: Vec<u8>,
+    field: Vec<u8>,
+}
+
+/// HINCRBY key field increment
+#[derive(Debug)]
+pub struct HIncrBy {
+    key: Vec<u8>,
+    field: Vec<u8>,
+    delta: i64,
+}
+
+/// HINCRBYFLOAT key field increment
+#[derive(Debug)]
+pub struct HIncrByFloat {
+    key: Vec<u8>,
+    field: Vec<u8>,
+    delta: f64,
+}
+
+/// HKEYS key
+#[derive(Debug)]
+pub struct HKeys {
+    key: Vec<u8>,
+}
+
+/// HVALS key
+#[derive(Debug)]
+pub struct HVals {
+    key: Vec<u8>,
+}
+
+/// HLEN key
+#[derive(Debug)]
+pub struct HLen {
+    key: Vec<u8>,
+}
+
+/// HSTRLEN key field
+#[derive(Debug)]
+pub struct HStrLen {
+    key: Vec<u8>,
+    field: Vec<u8>,
+}
+
+/// HRANDFIELD key [count [WITHVALUES]]
+#[derive(Debug)]
+pub struct HRandField {
+    key: Vec<u8>,
+    count: Option<i64>,
+    with_values: bool,
+}
+
+/// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+#[derive(Debug)]
+pub struct HScan {
+    key: Vec<u8>,
+    cursor: u64,
+    pattern: Option<Vec<u8>>,
+    count: usize,
+    no_values: bool,
+}
+
+/// HEXPIRE key seconds [NX|XX|GT|LT] FIELDS numfields field [field ...]
+/// HPEXPIRE key milliseconds [NX|XX|GT|LT] FIELDS numfields field [field ...]
+/// HEXPIREAT key unix-time-seconds [NX|XX|GT|LT] FIELDS numfields field [field ...]
+/// HPEXPIREAT key unix-time-milliseconds [NX|XX|GT|LT] FIELDS numfields field [field ...]
+#[derive(Debug)]
+pub struct HExpire {
+    name: &'static str,
+    key: Vec<u8>,
+    // 毫秒，relative 为 true 时是相对当前时间的毫秒数，否则是 unix 毫秒
+    time: i64,
+    relative: bool,
+    condition: Option<ExpireCondition>,
+    fields: Vec<Vec<u8>>,
+}
+
+/// HTTL/HPTTL/HEXPIRETIME/HPEXPIRETIME key FIELDS numfields field [field ...]
+#[derive(Debug)]
+pub struct HTtl {
+    name: &'static str,
+    key: Vec<u8>,
+    fields: Vec<Vec<u8>>,
+}
+
+/// HPERSIST key FIELDS numfields field [field ...]
+#[derive(Debug)]
+pub struct HPersist {
+    key: Vec<u8>,
+    fields: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExpireCondition {
+    // 只有 field 没有过期时间时才设置
+    Nx,
+    // 只有 field 已经有过期时间时才设置
+    Xx,
+    // 新的过期时间大于当前的过期时间时才设置，没有过期时间的 field 视为永不过期
+    Gt,
+    // 新的过期时间小于当前的过期时间时才设置
+    Lt,
+}
+
+// field 的过期时间上限，和 Redis 的 EB_EXPIRE_TIME_MAX 一致
+const HASH_FIELD_EXPIRE_TIME_MAX: i64 = (1 << 48) - 1;
+
+// field 相关命令 (HEXPIRE/HTTL/HPERSIST 等) 对每个 field 的返回值
+// field 或 key 不存在
+const FIELD_NOT_FOUND: i64 = -2;
+// field 没有过期时间
+const FIELD_NO_TTL: i64 = -1;
+// 不满足 NX/XX/GT/LT 条件
+const FIELD_CONDITION_NOT_MET: i64 = 0;
+// 设置或者清除成功
+const FIELD_OK: i64 = 1;
+// 过期时间已经过去，field 被删除
+const FIELD_DELETED: i64 = 2;
+
+impl HSet {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 3 || args.len().is_multiple_of(2) {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let mut pairs = Vec::with_capacity(args.len() / 2);
+        while let (Some(field), Some(value)) = (args.next(), args.next()) {
+            pairs.push((field, value));
+        }
+        Ok(HSet { name, key, pairs })
+    }
+}
+
+impl CommandExecutor for HSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, true, |hash: &mut Hash| {
+            let mut added = 0;
+            for (field, value) in self.pairs {
+                added += hash.set(field, value) as i64;
+            }
+            (added, true)
+        });
+        match ret {
+            Ok(_) if self.name == "hmset" => SimpleString::new("OK").into(),
+            Ok(added) => added.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HSetNx {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hsetnx", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(HSetNx {
+            key: args.next().unwrap_or_default(),
+            field: args.next().unwrap_or_default(),
+            value: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HSetNx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, true, |hash: &mut Hash| {
+            let added = !hash.contains(&self.field) && hash.set(self.field, self.value);
+            (added, added)
+        });
+        match ret {
+            Ok(added) => (added.unwrap_or_default() as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HGet {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hget", &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(HGet {
+            key: args.next().unwrap_or_default(),
+            field: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            hash.get(&self.field).map(|v| v.to_vec())
+        });
+        match ret {
+            Ok(Some(Some(value))) => BulkString::new(value).into(),
+            Ok(_) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HMGet {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("hmget".to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(HMGet {
+            key: args.next().unwrap_or_default(),
+            fields: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for HMGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            self.fields
+                .iter()
+                .map(|field| match hash.get(field) {
+                    Some(value) => BulkString::new(value).into(),
+                    None => RespNullBulkString.into(),
+                })
+                .collect::<Vec<RespFrame>>()
+        });
+        match ret {
+            Ok(Some(values)) => RespArray::new(values).into(),
+            Ok(None) => RespArray::new(
+                self.fields
+                    .iter()
+                    .map(|_| RespNullBulkString.into())
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HGetAll {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hgetall", &args, 1)?;
+        Ok(HGetAll {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HGetAll {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // RESP2 的客户端会在发送前被转换成 field value 交替的数组
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            // 和 HKEYS/HVALS/HSCAN 一样按照存储的顺序返回
+            let mut map = RespMap::new();
+            for (field, value) in hash.entries() {
+                map.push(field, BulkString::new(value).into());
+            }
+            map
+        });
+        match ret {
+            Ok(map) => map.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HDel {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("hdel".to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(HDel {
+            key: args.next().unwrap_or_default(),
+            fields: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for HDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |hash: &mut Hash| {
+            let removed = self
+                .fields
+                .iter()
+                .filter(|field| hash.remove(field))
+                .count();
+            (removed as i64, removed > 0)
+        });
+        match ret {
+            Ok(removed) => removed.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HExists {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hexists", &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(HExists {
+            key: args.next().unwrap_or_default(),
+            field: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HExists {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |hash: &Hash| hash.contains(&self.field)) {
+            Ok(exists) => (exists.unwrap_or_default() as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HIncrBy {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hincrby", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(HIncrBy {
+            key: args.next().unwrap_or_default(),
+            field: args.next().unwrap_or_default(),
+            delta: parse_int(&args.next().unwrap_or_default())?,
+        })
+    }
+}
+
+impl CommandExecutor for HIncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, true, |hash: &mut Hash| {
+            let incr = |hash: &mut Hash| {
+                let current = match hash.get(&self.field) {
+                    Some(value) => parse_int(value).map_err(|_| {
+                        CommandError::InvalidArgument("hash value is not an integer".to_string())
+                    })?,
+                    None => 0,
+                };
+                let n = current.checked_add(self.delta).ok_or_else(|| {
+                    CommandError::InvalidArgument(
+                        "increment or decrement would overflow".to_string(),
+                    )
+                })?;
+                // 和 Redis 一样，HINCRBY 保留 field 的过期时间
+                hash.set_keep_ttl(self.field, n.to_string().into_bytes());
+                Ok::<_, CommandError>(n)
+            };
+            let ret = incr(hash);
+            let modified = ret.is_ok();
+            (ret, modified)
+        });
+        match ret {
+            Ok(Some(Ok(n))) => n.into(),
+            Ok(Some(Err(e))) => e.into(),
+            Ok(None) => unreachable!("key is created if missing"),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HIncrByFloat {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hincrbyfloat", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(HIncrByFloat {
+            key: args.next().unwrap_or_default(),
+            field: args.next().unwrap_or_default(),
+            delta: parse_float(&args.next().unwrap_or_default())?,
+        })
+    }
+}
+
+impl CommandExecutor for HIncrByFloat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, true, |hash: &mut Hash| {
+            let incr = |hash: &mut Hash| {
+                let current = match hash.get(&self.field) {
+                    Some(value) => parse_float(value).map_err(|_| {
+                        CommandError::InvalidArgument("hash value is not a float".to_string())
+                    })?,
+                    None => 0.0,
+                };
+                let n = current + self.delta;
+                if !n.is_finite() {
+                    return Err(CommandError::InvalidArgument(
+                        "increment would produce NaN or Infinity".to_string(),
+                    ));
+                }
+                let value = format_double(n).into_bytes();
+                hash.set_keep_ttl(self.field, value.clone());
+                Ok(value)
+            };
+            let ret = incr(hash);
+            let modified = ret.is_ok();
+            (ret, modified)
+        });
+        match ret {
+            Ok(Some(Ok(value))) => BulkString::new(value).into(),
+            Ok(Some(Err(e))) => e.into(),
+            Ok(None) => unreachable!("key is created if missing"),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HKeys {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hkeys", &args, 1)?;
+        Ok(HKeys {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HKeys {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            hash.entries()
+                .into_iter()
+                .map(|(field, _)| BulkString::new(field).into())
+                .collect::<Vec<RespFrame>>()
+        });
+        match ret {
+            Ok(fields) => RespArray::new(fields.unwrap_or_default()).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HVals {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hvals", &args, 1)?;
+        Ok(HVals {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HVals {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            hash.entries()
+                .into_iter()
+                .map(|(_, value)| BulkString::new(value).into())
+                .collect::<Vec<RespFrame>>()
+        });
+        match ret {
+            Ok(values) => RespArray::new(values.unwrap_or_default()).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HLen {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hlen", &args, 1)?;
+        Ok(HLen {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |hash: &Hash| hash.len() as i64) {
+            Ok(len) => len.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HStrLen {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("hstrlen", &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(HStrLen {
+            key: args.next().unwrap_or_default(),
+            field: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for HStrLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            hash.get(&self.field).map_or(0, |v| v.len() as i64)
+        });
+        match ret {
+            Ok(len) => len.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HRandField {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() || args.len() > 3 {
+            return Err(CommandError::WrongArity("hrandfield".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let count = match args.next() {
+            Some(arg) => Some(parse_int(&arg)?),
+            None => None,
+        };
+        let with_values = match args.next() {
+            Some(arg) if arg.eq_ignore_ascii_case(b"WITHVALUES") => true,
+            Some(_) => return Err(CommandError::Syntax),
+            None => false,
+        };
+        // 带 WITHVALUES 时返回的元素个数是 count 的两倍
+        let limit = if with_values { i64::MAX / 2 } else { i64::MAX };
+        if count.is_some_and(|n| n.checked_abs().is_none_or(|n| n > limit)) {
+            return Err(CommandError::InvalidArgument(
+                "value is out of range".to_string(),
+            ));
+        }
+        Ok(HRandField {
+            key,
+            count,
+            with_values,
+        })
+    }
+}
+
+impl CommandExecutor for HRandField {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for HRandField {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            let mut rng = rand::thread_rng();
+            let len = hash.len();
+            let indexes: Vec<usize> = match self.count {
+                None => vec![rng.gen_range(0..len)],
+                // 负数允许重复
+                Some(n) if n < 0 => (0..n.unsigned_abs())
+                    .map(|_| rng.gen_range(0..len))
+                    .collect(),
+                Some(n) if n as usize >= len => (0..len).collect(),
+                Some(n) => index::sample(&mut rng, len, n as usize).into_vec(),
+            };
+            indexes
+                .into_iter()
+                .filter_map(|i| hash.get_index(i))
+                .map(|(f, v)| (f.to_vec(), v.to_vec()))
+                .collect::<Vec<_>>()
+        });
+        let pairs = match ret {
+            Ok(pairs) => pairs,
+            Err(e) => return e.into(),
+        };
+        let Some(_) = self.count else {
+            return match pairs.and_then(|mut pairs| pairs.pop()) {
+                Some((field, _)) => BulkString::new(field).into(),
+                None => RespNullBulkString.into(),
+            };
+        };
+
+        let pairs = pairs.unwrap_or_default();
+        let frames: Vec<RespFrame> = match (self.with_values, session.protocol) {
+            (false, _) => pairs
+                .into_iter()
+                .map(|(field, _)| BulkString::new(field).into())
+                .collect(),
+            // RESP3 下每一对 field value 是一个数组
+            (true, Protocol::Resp3) => pairs
+                .into_iter()
+                .map(|(field, value)| {
+                    RespArray::new([BulkString::new(field).into(), BulkString::new(value).into()])
+                        .into()
+                })
+                .collect(),
+            (true, Protocol::Resp2) => pairs
+                .into_iter()
+                .flat_map(|(field, value)| {
+                    [BulkString::new(field).into(), BulkString::new(value).into()]
+                })
+                .collect(),
+        };
+        RespArray::new(frames).into()
+    }
+}
+
+impl HScan {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("hscan".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let cursor = parse_cursor(&args.next().unwrap_or_default())?;
+        let mut scan = HScan {
+            key,
+            cursor,
+            pattern: None,
+            count: 10,
+            no_values: false,
+        };
+        while let Some(arg) = args.next() {
+            match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                "MATCH" => scan.pattern = Some(args.next().ok_or(CommandError::Syntax)?),
+                "COUNT" => {
+                    let count = parse_int(&args.next().ok_or(CommandError::Syntax)?)?;
+                    if count < 1 {
+                        return Err(CommandError::Syntax);
+                    }
+                    scan.count = count as usize;
+                }
+                "NOVALUES" => scan.no_values = true,
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+impl CommandExecutor for HScan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            let (cursor, items) = hash.scan(self.cursor, self.count);
+            let mut frames = Vec::with_capacity(items.len() * 2);
+            for (field, value) in items {
+                if let Some(pattern) = &self.pattern {
+                    if !glob_match(pattern, field, false) {
+                        continue;
+                    }
+                }
+                frames.push(BulkString::new(field).into());
+                if !self.no_values {
+                    frames.push(BulkString::new(value).into());
+                }
+            }
+            (cursor, frames)
+        });
+        match ret {
+            Ok(ret) => {
+                let (cursor, frames) = ret.unwrap_or_default();
+                RespArray::new([
+                    BulkString::new(cursor.to_string()).into(),
+                    RespArray::new(frames).into(),
+                ])
+                .into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HExpire {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 5 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let n = parse_int(&args.next().unwrap_or_default())?;
+        if !(0..=HASH_FIELD_EXPIRE_TIME_MAX).contains(&n) {
+            return Err(CommandError::InvalidArgument(format!(
+                "invalid expire time, must be >= 0 and <= {}",
+                HASH_FIELD_EXPIRE_TIME_MAX
+            )));
+        }
+        let (time, relative) = match name {
+            "hexpire" => (n * 1000, true),
+            "hpexpire" => (n, true),
+            "hexpireat" => (n * 1000, false),
+            _ => (n, false),
+        };
+
+        let mut args: Vec<_> = args.collect();
+        let condition = match String::from_utf8_lossy(&args[0]).to_uppercase().as_str() {
+            "NX" => Some(ExpireCondition::Nx),
+            "XX" => Some(ExpireCondition::Xx),
+            "GT" => Some(ExpireCondition::Gt),
+            "LT" => Some(ExpireCondition::Lt),
+            _ => None,
+        };
+        if condition.is_some() {
+            args.remove(0);
+        }
+        Ok(HExpire {
+            name,
+            key,
+            time,
+            relative,
+            condition,
+            fields: parse_fields(args)?,
+        })
+    }
+}
+
+impl CommandExecutor for HExpire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let now = now_ms() as i64;
+        let at = match self.relative {
+            true => self.time + now,
+            false => self.time,
+        };
+        if at > HASH_FIELD_EXPIRE_TIME_MAX {
+            return CommandError::InvalidArgument(format!(
+                "invalid expire time in '{}' command",
+                self.name
+            ))
+            .into();
+        }
+        let ret = backend.write_value(&self.key, false, |hash: &mut Hash| {
+            let codes = self
+                .fields
+                .iter()
+                .map(|field| {
+                    if !hash.contains(field) {
+                        return FIELD_NOT_FOUND;
+                    }
+                    let current = hash.get_expire(field).map(|v| v as i64);
+                    let met = match self.condition {
+                        None => true,
+                        Some(ExpireCondition::Nx) => current.is_none(),
+                        Some(ExpireCondition::Xx) => current.is_some(),
+                        Some(ExpireCondition::Gt) => current.is_some_and(|current| at > current),
+                        Some(ExpireCondition::Lt) => current.is_none_or(|current| at < current),
+                    };
+                    if !met {
+                        return FIELD_CONDITION_NOT_MET;
+                    }
+                    // 过期时间已经过去时直接删除 field
+                    if at <= now {
+                        hash.remove(field);
+                        FIELD_DELETED
+                    } else {
+                        hash.set_expire(field, at as u64);
+                        FIELD_OK
+                    }
+                })
+                .collect::<Vec<_>>();
+            let modified = codes
+                .iter()
+                .any(|code| *code == FIELD_OK || *code == FIELD_DELETED);
+            (codes, modified)
+        });
+        match ret {
+            Ok(codes) => field_codes(codes, self.fields.len()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HTtl {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 4 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        Ok(HTtl {
+            name,
+            key,
+            fields: parse_fields(args.collect())?,
+        })
+    }
+}
+
+impl CommandExecutor for HTtl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let now = now_ms() as i64;
+        let ret = backend.read_value(&self.key, |hash: &Hash| {
+            self.fields
+                .iter()
+                .map(|field| {
+                    if !hash.contains(field) {
+                        return FIELD_NOT_FOUND;
+                    }
+                    let Some(at) = hash.get_expire(field).map(|v| v as i64) else {
+                        return FIELD_NO_TTL;
+                    };
+                    let ttl = (at - now).max(0);
+                    match self.name {
+                        // 和 Redis 一样向上取整到秒
+                        "httl" => (ttl + 999) / 1000,
+                        "hpttl" => ttl,
+                        "hexpiretime" => at / 1000,
+                        _ => at,
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        match ret {
+            Ok(codes) => field_codes(codes, self.fields.len()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl HPersist {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 4 {
+            return Err(CommandError::WrongArity("hpersist".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        Ok(HPersist {
+            key,
+            fields: parse_fields(args.collect())?,
+        })
+    }
+}
+
+impl CommandExecutor for HPersist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |hash: &mut Hash| {
+            let codes = self
+                .fields
+                .iter()
+                .map(|field| match (hash.contains(field), hash.persist(field)) {
+                    (false, _) => FIELD_NOT_FOUND,
+                    (true, false) => FIELD_NO_TTL,
+                    (true, true) => FIELD_OK,
+                })
+                .collect::<Vec<_>>();
+            let modified = codes.contains(&FIELD_OK);
+            (codes, modified)
+        });
+        match ret {
+            Ok(codes) => field_codes(codes, self.fields.len()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+/// 解析 `FIELDS numfields field [field ...]`
+fn parse_fields(args: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, CommandError> {
+    let mut args = args.into_iter();
+    if !args
+        .next()
+        .is_some_and(|arg| arg.eq_ignore_ascii_case(b"FIELDS"))
+    {
+        return Err(CommandError::InvalidArgument(
+            "Mandatory argument FIELDS is missing or not at the right position".to_string(),
+        ));
+    }
+    let numfields = parse_int(&args.next().unwrap_or_default())
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| {
+            CommandError::InvalidArgument("Number of fields must be a positive integer".to_string())
+        })?;
+    let fields: Vec<_> = args.collect();
+    if fields.len() as i64 != numfields {
+        return Err(CommandError::InvalidArgument(
+            "The `numfields` parameter must match the number of arguments".to_string(),
+        ));
+    }
+    Ok(fields)
+}
+
+/// 每个 field 一个返回值，key 不存在时所有 field 都是 -2
+fn field_codes(codes: Option<Vec<i64>>, n: usize) -> RespFrame {
+    let codes = codes.unwrap_or_else(|| vec![FIELD_NOT_FOUND; n]);
+    RespArray::new(codes.into_iter().map(RespFrame::from).collect::<Vec<_>>()).into()
+}
+
+/// SCAN 系列命令的游标是无符号整数
+pub(crate) fn parse_cursor(arg: &[u8]) -> Result<u64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| CommandError::InvalidArgument("invalid cursor".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{tests::run, Command};
+    use crate::{BackendError, SimpleError};
+    use anyhow::Result;
+    use std::collections::HashSet;
+
+    fn bulk(v: &str) -> RespFrame {
+        BulkString::new(v).into()
+    }
+
+    #[test]
+    fn test_hset_hget() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["HSET", "h", "a", "1", "b", "2"])?, 2.into());
+        assert_eq!(run(&backend, &["HSET", "h", "a", "3", "c", "4"])?, 1.into());
+        assert_eq!(
+            run(&backend, &["HMSET", "h", "d", "5"])?,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(run(&backend, &["HSETNX", "h", "a", "x"])?, 0.into());
+        assert_eq!(run(&backend, &["HSETNX", "h", "e", "6"])?, 1.into());
+        assert_eq!(run(&backend, &["HGET", "h", "a"])?, bulk("3"));
+        assert_eq!(
+            run(&backend, &["HGET", "h", "x"])?,
+            RespNullBulkString.into()
+        );
+        assert_eq!(
+            run(&backend, &["HMGET", "h", "a", "x", "b"])?,
+            RespArray::new([bulk("3"), RespNullBulkString.into(), bulk("2")]).into()
+        );
+        assert_eq!(
+            run(&backend, &["HMGET", "missing", "a"])?,
+            RespArray::new([RespNullBulkString.into()]).into()
+        );
+        assert_eq!(run(&backend, &["HLEN", "h"])?, 5.into());
+        assert_eq!(run(&backend, &["HEXISTS", "h", "e"])?, 1.into());
+        assert_eq!(run(&backend, &["HSTRLEN", "h", "e"])?, 1.into());
+        assert_eq!(
+            run(&backend, &["HKEYS", "h"])?,
+            RespArray::new([bulk("a"), bulk("b"), bulk("c"), bulk("d"), bulk("e")]).into()
+        );
+        assert_eq!(
+            run(&backend, &["HVALS", "h"])?,
+            RespArray::new([bulk("3"), bulk("2"), bulk("4"), bulk("5"), bulk("6")]).into()
+        );
+
+        assert_eq!(run(&backend, &["HDEL", "h", "a", "b", "x"])?, 2.into());
+        assert_eq!(run(&backend, &["HDEL", "h", "c", "d", "e"])?, 3.into());
+        assert!(!backend.exists(b"h"));
+        assert!(run(&backend, &["HSET", "h", "a"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hgetall() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["HGETALL", "h"])?, RespMap::new().into());
+        run(&backend, &["HSET", "h", "name", "tom", "age", "18"])?;
+
+        let mut map = RespMap::new();
+        map.insert(b"name", bulk("tom"));
+        map.insert(b"age", bulk("18"));
+        let frame = run(&backend, &["HGETALL", "h"])?;
+        assert_eq!(frame, map.into());
+        // RESP2 的客户端收到的是 field value 交替的数组，顺序和 HKEYS 一致
+        assert_eq!(
+            frame.into_protocol(Protocol::Resp2),
+            RespArray::new([bulk("name"), bulk("tom"), bulk("age"), bulk("18")]).into()
+        );
+        assert_eq!(
+            run(&backend, &["HKEYS", "h"])?,
+            RespArray::new([bulk("name"), bulk("age")]).into()
+        );
+
+        // 不是合法 UTF-8 的 field 原样返回，不会合并成同一个 key
+        let hset: RespFrame = RespArray::new([
+            bulk("HSET"),
+            bulk("b"),
+            BulkString::new(vec![0xff]).into(),
+            bulk("1"),
+            BulkString::new(vec![0xfe]).into(),
+            bulk("2"),
+        ])
+        .into();
+        assert_eq!(Command::try_from(hset)?.execute(&backend), 2.into());
+        let frame = run(&backend, &["HGETALL", "b"])?;
+        assert_eq!(
+            frame.into_protocol(Protocol::Resp2),
+            RespArray::new([
+                BulkString::new(vec![0xff]).into(),
+                bulk("1"),
+                BulkString::new(vec![0xfe]).into(),
+                bulk("2"),
+            ])
+            .into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hincrby() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["HINCRBY", "h", "n", "5"])?, 5.into());
+        assert_eq!(run(&backend, &["HINCRBY", "h", "n", "-7"])?, (-2).into());
+        assert_eq!(
+            run(&backend, &["HINCRBYFLOAT", "h", "n", "0.5"])?,
+            bulk("-1.5")
+        );
+        assert_eq!(
+            run(&backend, &["HINCRBY", "h", "n", "1"])?,
+            SimpleError::new("ERR hash value is not an integer").into()
+        );
+        run(
+            &backend,
+            &["HSET", "h", "max", &i64::MAX.to_string(), "s", "abc"],
+        )?;
+        assert_eq!(
+            run(&backend, &["HINCRBY", "h", "max", "1"])?,
+            SimpleError::new("ERR increment or decrement would overflow").into()
+        );
+        assert_eq!(
+            run(&backend, &["HINCRBYFLOAT", "h", "s", "1"])?,
+            SimpleError::new("ERR hash value is not a float").into()
+        );
+        assert_eq!(
+            run(&backend, &["HINCRBYFLOAT", "h", "f", "1e3"])?,
+            bulk("1000")
+        );
+
+        run(&backend, &["SET", "str", "v"])?;
+        assert_eq!(
+            run(&backend, &["HINCRBY", "str", "n", "1"])?,
+            BackendError::WrongType.into()
+        );
+        assert_eq!(
+            run(&backend, &["HGET", "str", "n"])?,
+            BackendError::WrongType.into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hrandfield() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, &["HRANDFIELD", "h"])?,
+            RespNullBulkString.into()
+        );
+        assert_eq!(
+            run(&backend, &["HRANDFIELD", "h", "3"])?,
+            RespArray::new(vec![]).into()
+        );
+        run(&backend, &["HSET", "h", "a", "1", "b", "2", "c", "3"])?;
+
+        let RespFrame::Array(fields) = run(&backend, &["HRANDFIELD", "h", "2"])? else {
+            panic!("HRANDFIELD with count should return an array");
+        };
+        let fields: HashSet<_> = fields.iter().map(|f| format!("{:?}", f)).collect();
+        assert_eq!(fields.len(), 2);
+
+        let RespFrame::Array(fields) = run(&backend, &["HRANDFIELD", "h", "-10"])? else {
+            panic!("HRANDFIELD with count should return an array");
+        };
+        assert_eq!(fields.len(), 10);
+
+        // RESP2 下是平铺的数组，RESP3 下每一对是一个数组
+        let RespFrame::Array(pairs) = run(&backend, &["HRANDFIELD", "h", "5", "WITHVALUES"])?
+        else {
+            panic!("HRANDFIELD with count should return an array");
+        };
+        assert_eq!(pairs.len(), 6);
+        let mut session = Session {
+            protocol: Protocol::Resp3,
+            ..Default::default()
+        };
+        let cmd = HRandField::parse(vec![b"h".to_vec(), b"-1".to_vec(), b"WITHVALUES".to_vec()])?;
+        let RespFrame::Array(pairs) = cmd.execute_session(&mut session, &backend) else {
+            panic!("HRANDFIELD with count should return an array");
+        };
+        assert!(matches!(&pairs[0], RespFrame::Array(pair) if pair.len() == 2));
+
+        assert!(run(&backend, &["HRANDFIELD", "h", "1", "WITHSCORES"]).is_err());
+        assert!(run(&backend, &["HRANDFIELD", "h", &i64::MIN.to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hscan() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["HSET", "h", "a1", "1", "a2", "2", "b1", "3"])?;
+        assert_eq!(
+            run(&backend, &["HSCAN", "h", "0", "MATCH", "a*"])?,
+            RespArray::new([
+                bulk("0"),
+                RespArray::new([bulk("a1"), bulk("1"), bulk("a2"), bulk("2")]).into(),
+            ])
+            .into()
+        );
+        assert_eq!(
+            run(&backend, &["HSCAN", "h", "0", "NOVALUES"])?,
+            RespArray::new([
+                bulk("0"),
+                RespArray::new([bulk("a1"), bulk("a2"), bulk("b1")]).into(),
+            ])
+            .into()
+        );
+
+        // hashtable 编码时按照 COUNT 分批返回
+        for i in 0..200 {
+            run(&backend, &["HSET", "big", &format!("f{}", i), "v"])?;
+        }
+        let mut cursor = "0".to_string();
+        let mut seen = HashSet::new();
+        loop {
+            let RespFrame::Array(ret) = run(&backend, &["HSCAN", "big", &cursor, "COUNT", "50"])?
+            else {
+                panic!("HSCAN should return an array");
+            };
+            let (RespFrame::BulkString(next), RespFrame::Array(items)) = (&ret[0], &ret[1]) else {
+                panic!("unexpected HSCAN reply");
+            };
+            assert!(items.len() <= 100);
+            for item in items.iter().step_by(2) {
+                seen.insert(format!("{:?}", item));
+            }
+            cursor = String::from_utf8(next.to_vec())?;
+            if cursor == "0" {
+                break;
+            }
+        }
+        assert_eq!(seen.len(), 200);
+        assert!(run(&backend, &["HSCAN", "h", "-1"]).is_err());
+        assert!(run(&backend, &["HSCAN", "h", "0", "COUNT", "0"]).is_err());
+        Ok(())
+    }
+
+    fn codes(codes: &[i64]) -> RespFrame {
+        RespArray::new(codes.iter().map(|v| (*v).into()).collect::<Vec<_>>()).into()
+    }
+
+    #[test]
+    fn test_hexpire_httl() -> Result<()> {
+        let backend = Backend::new();
+        let args = ["HEXPIRE", "h", "100", "FIELDS", "2", "a", "b"];
+        assert_eq!(run(&backend, &args)?, codes(&[-2, -2]));
+        run(&backend, &["HSET", "h", "a", "1", "b", "2", "c", "3"])?;
+
+        let args = ["HEXPIRE", "h", "100", "FIELDS", "2", "a", "x"];
+        assert_eq!(run(&backend, &args)?, codes(&[1, -2]));
+        let args = ["HTTL", "h", "FIELDS", "3", "a", "b", "x"];
+        assert_eq!(run(&backend, &args)?, codes(&[100, -1, -2]));
+        let args = ["HEXPIRE", "h", "200", "NX", "FIELDS", "2", "a", "b"];
+        assert_eq!(run(&backend, &args)?, codes(&[0, 1]));
+        let args = ["HEXPIRE", "h", "150", "GT", "FIELDS", "2", "a", "c"];
+        assert_eq!(run(&backend, &args)?, codes(&[1, 0]));
+        let args = ["HEXPIRE", "h", "120", "LT", "FIELDS", "2", "a", "c"];
+        assert_eq!(run(&backend, &args)?, codes(&[1, 1]));
+        let args = [
+            "HPEXPIREAT",
+            "h",
+            "99999999999999",
+            "XX",
+            "FIELDS",
+            "1",
+            "b",
+        ];
+        assert_eq!(run(&backend, &args)?, codes(&[1]));
+        let args = ["HPEXPIRETIME", "h", "FIELDS", "1", "b"];
+        assert_eq!(run(&backend, &args)?, codes(&[99999999999999]));
+
+        // HSET 清除过期时间，HINCRBY 保留过期时间
+        run(&backend, &["HSET", "h", "a", "10"])?;
+        run(&backend, &["HINCRBY", "h", "c", "1"])?;
+        let args = ["HTTL", "h", "FIELDS", "2", "a", "c"];
+        assert_eq!(run(&backend, &args)?, codes(&[-1, 120]));
+
+        let args = ["HPERSIST", "h", "FIELDS", "3", "a", "c", "x"];
+        assert_eq!(run(&backend, &args)?, codes(&[-1, 1, -2]));
+
+        // 过去的时间直接删除 field，field 全部删除后 key 也被删除
+        let args = ["HEXPIRE", "h", "0", "FIELDS", "3", "a", "b", "c"];
+        assert_eq!(run(&backend, &args)?, codes(&[2, 2, 2]));
+        assert!(!backend.exists(b"h"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_field_lazy_expire() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["HSET", "h", "a", "1", "b", "2"])?;
+        let args = ["HPEXPIRE", "h", "1", "FIELDS", "1", "a"];
+        assert_eq!(run(&backend, &args)?, codes(&[1]));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        assert_eq!(
+            run(&backend, &["HGET", "h", "a"])?,
+            RespNullBulkString.into()
+        );
+        assert_eq!(run(&backend, &["HLEN", "h"])?, 1.into());
+        assert!(backend.field_expires.is_empty());
+
+        let args = ["HPEXPIRE", "h", "1", "FIELDS", "1", "b"];
+        assert_eq!(run(&backend, &args)?, codes(&[1]));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(!backend.exists(b"h"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexpire_errors() -> Result<()> {
+        let backend = Backend::new();
+        for (args, err) in [
+            (
+                &["HEXPIRE", "h", "-1", "FIELDS", "1", "a"][..],
+                "invalid expire time, must be >= 0 and <= 281474976710655",
+            ),
+            (
+                &["HEXPIRE", "h", "10", "FIELD", "1", "a"],
+                "Mandatory argument FIELDS is missing or not at the right position",
+            ),
+            (
+                &["HTTL", "h", "FIELDS", "0", "a"],
+                "Number of fields must be a positive integer",
+            ),
+            (
+                &["HPERSIST", "h", "FIELDS", "2", "a"],
+                "The `numfields` parameter must match the number of arguments",
+            ),
+        ] {
+            let cmd = RespArray::new(
+                args.iter()
+                    .map(|v| BulkString::new(*v).into())
+                    .collect::<Vec<_>>(),
+            );
+            let ret = Command::try_from(cmd);
+            assert_eq!(
+                ret.err(),
+                Some(CommandError::InvalidArgument(err.to_string()))
+            );
+        }
+
+        backend.set(b"s".to_vec(), crate::Value::String(b"v".to_vec()));
+        let args = ["HTTL", "s", "FIELDS", "1", "a"];
+        assert_eq!(run(&backend, &args)?, BackendError::WrongType.into());
+        Ok(())
+    }
+}
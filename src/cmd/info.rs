@@ -0,0 +1,139 @@
+use super::{parse_string, CommandError, CommandExecutor};
+use crate::{backend::now_ms, Backend, BulkString, LinkState, RespFrame};
+use std::fmt::Write;
+
+/// INFO [section [section ...]]，目前只有 replication 和 cluster
+#[derive(Debug)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+impl Info {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let sections = args
+            .into_iter()
+            .map(|arg| parse_string(arg).map(|s| s.to_lowercase()))
+            .collect::<Result<_, _>>()?;
+        Ok(Info { sections })
+    }
+
+    fn contains(&self, section: &str) -> bool {
+        self.sections.is_empty()
+            || self
+                .sections
+                .iter()
+                .any(|s| s == section || s == "all" || s == "default" || s == "everything")
+    }
+}
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut info = String::new();
+        if self.contains("replication") {
+            replication_info(backend, &mut info);
+        }
+        if self.contains("cluster") {
+            if !info.is_empty() {
+                info.push_str("\r\n");
+            }
+            let _ = write!(
+                info,
+                "# Cluster\r\ncluster_enabled:{}\r\n",
+                backend.cluster_enabled() as u8
+            );
+        }
+        BulkString::new(info).into()
+    }
+}
+
+/// INFO replication，字段和 Redis 一样
+fn replication_info(backend: &Backend, info: &mut String) {
+    let status = backend.replication_status();
+    let now = now_ms();
+    info.push_str("# Replication\r\n");
+    match &status.master {
+        Some((host, port)) => {
+            let up = status.link == LinkState::Connected;
+            let last_io = match up {
+                true => (now.saturating_sub(status.last_io) / 1000) as i64,
+                false => -1,
+            };
+            let _ = write!(
+                info,
+                "role:slave\r\nmaster_host:{}\r\nmaster_port:{}\r\nmaster_link_status:{}\r\n\
+                 master_last_io_seconds_ago:{}\r\nmaster_sync_in_progress:{}\r\n\
+                 slave_read_repl_offset:{}\r\nslave_repl_offset:{}\r\nslave_priority:100\r\n\
+                 slave_read_only:1\r\nreplica_announced:1\r\n",
+                host,
+                port,
+                if up { "up" } else { "down" },
+                last_io,
+                (status.link == LinkState::Sync) as u8,
+                status.offset,
+                status.offset,
+            );
+        }
+        None => info.push_str("role:master\r\n"),
+    }
+    let _ = write!(info, "connected_slaves:{}\r\n", status.replicas.len());
+    for (i, replica) in status.replicas.iter().enumerate() {
+        let _ = write!(
+            info,
+            "slave{}:ip={},port={},state=online,offset={},lag={}\r\n",
+            i,
+            replica.addr,
+            replica.port,
+            replica.ack_offset,
+            now.saturating_sub(replica.ack_time) / 1000
+        );
+    }
+    let (first_byte, histlen) = status.backlog.unwrap_or_default();
+    let _ = write!(
+        info,
+        "master_failover_state:no-failover\r\nmaster_replid:{}\r\nmaster_replid2:{}\r\n\
+         master_repl_offset:{}\r\nsecond_repl_offset:{}\r\nrepl_backlog_active:{}\r\n\
+         repl_backlog_size:{}\r\nrepl_backlog_first_byte_offset:{}\r\nrepl_backlog_histlen:{}\r\n",
+        status.replid,
+        status.replid2,
+        status.offset,
+        status.second_replid_offset,
+        status.backlog.is_some() as u8,
+        status.backlog_size,
+        first_byte,
+        histlen,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::run;
+    use anyhow::Result;
+
+    fn info(backend: &Backend, args: &[&str]) -> Result<String> {
+        match run(backend, args)? {
+            RespFrame::BulkString(s) => Ok(String::from_utf8(s.0)?),
+            frame => panic!("expected bulk string, got {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_info_replication() -> Result<()> {
+        let backend = Backend::new();
+        let s = info(&backend, &["INFO", "replication"])?;
+        assert!(s.starts_with("# Replication\r\nrole:master\r\nconnected_slaves:0\r\n"));
+        assert!(s.contains("repl_backlog_active:0\r\n"));
+        assert!(s.contains("second_repl_offset:-1\r\n"));
+        assert_eq!(info(&backend, &["INFO", "keyspace"])?, "");
+        assert_eq!(
+            info(&backend, &["INFO", "cluster"])?,
+            "# Cluster\r\ncluster_enabled:0\r\n"
+        );
+
+        backend.set_master(Some(("localhost".to_string(), 7000)));
+        let s = info(&backend, &["INFO"])?;
+        assert!(s.contains("role:slave\r\nmaster_host:localhost\r\nmaster_port:7000\r\n"));
+        assert!(s.contains("master_link_status:down\r\nmaster_last_io_seconds_ago:-1\r\n"));
+        Ok(())
+    }
+}
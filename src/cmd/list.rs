@@ -0,0 +1,1093 @@
+use super::{
+    parse_int, parse_timeout, serve_now, serve_or_block, validate_args, BlockingExecutor,
+    CommandError, CommandExecutor, Outcome,
+};
+use crate::{
+    Backend, BackendError, BulkString, List, RespArray, RespFrame, RespNullArray,
+    RespNullBulkString, ServeFn, SimpleString,
+};
+use std::time::Duration;
+
+/// LPUSH/RPUSH/LPUSHX/RPUSHX key element [element ...]
+#[derive(Debug)]
+pub struct Push {
+    key: Vec<u8>,
+    elements: Vec<Vec<u8>>,
+    left: bool,
+    // 只有 key 已经存在时才写入 (LPUSHX/RPUSHX)
+    exists: bool,
+}
+
+/// LPOP/RPOP key [count]
+#[derive(Debug)]
+pub struct Pop {
+    key: Vec<u8>,
+    count: Option<usize>,
+    left: bool,
+}
+
+/// LLEN key
+#[derive(Debug)]
+pub struct LLen {
+    key: Vec<u8>,
+}
+
+/// LRANGE key start stop
+#[derive(Debug)]
+pub struct LRange {
+    key: Vec<u8>,
+    start: i64,
+    stop: i64,
+}
+
+/// LINDEX key index
+#[derive(Debug)]
+pub struct LIndex {
+    key: Vec<u8>,
+    index: i64,
+}
+
+/// LSET key index element
+#[derive(Debug)]
+pub struct LSet {
+    key: Vec<u8>,
+    index: i64,
+    element: Vec<u8>,
+}
+
+/// LREM key count element
+#[derive(Debug)]
+pub struct LRem {
+    key: Vec<u8>,
+    count: i64,
+    element: Vec<u8>,
+}
+
+/// LTRIM key start stop
+#[derive(Debug)]
+pub struct LTrim {
+    key: Vec<u8>,
+    start: i64,
+    stop: i64,
+}
+
+/// LINSERT key BEFORE|AFTER pivot element
+#[derive(Debug)]
+pub struct LInsert {
+    key: Vec<u8>,
+    before: bool,
+    pivot: Vec<u8>,
+    element: Vec<u8>,
+}
+
+/// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+#[derive(Debug)]
+pub struct LPos {
+    key: Vec<u8>,
+    element: Vec<u8>,
+    rank: i64,
+    count: Option<usize>,
+    maxlen: usize,
+}
+
+/// LMOVE source destination LEFT|RIGHT LEFT|RIGHT, 以及 RPOPLPUSH source destination
+#[derive(Debug)]
+pub struct LMove {
+    source: Vec<u8>,
+    destination: Vec<u8>,
+    from_left: bool,
+    to_left: bool,
+}
+
+/// LMPOP numkeys key [key ...] LEFT|RIGHT [COUNT count]
+/// BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count]
+#[derive(Debug)]
+pub struct LMPop {
+    keys: Vec<Vec<u8>>,
+    left: bool,
+    count: usize,
+    block: bool,
+    timeout: Option<Duration>,
+}
+
+/// BLPOP/BRPOP key [key ...] timeout
+#[derive(Debug)]
+pub struct BPop {
+    keys: Vec<Vec<u8>>,
+    left: bool,
+    timeout: Option<Duration>,
+}
+
+/// BLMOVE source destination LEFT|RIGHT LEFT|RIGHT timeout, 以及 BRPOPLPUSH source destination timeout
+#[derive(Debug)]
+pub struct BLMove {
+    lmove: LMove,
+    timeout: Option<Duration>,
+}
+
+impl Push {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(Push {
+            key: args.next().unwrap_or_default(),
+            elements: args.collect(),
+            left: name.starts_with('l'),
+            exists: name.ends_with('x'),
+        })
+    }
+}
+
+impl CommandExecutor for Push {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, !self.exists, |list: &mut List| {
+            for element in self.elements {
+                if self.left {
+                    list.push_front(element);
+                } else {
+                    list.push_back(element);
+                }
+            }
+            (list.len() as i64, true)
+        });
+        match ret {
+            Ok(len) => len.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl Pop {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let count = match args.next() {
+            Some(arg) => Some(parse_positive(&arg)?),
+            None => None,
+        };
+        Ok(Pop {
+            key,
+            count,
+            left: name == "lpop",
+        })
+    }
+}
+
+impl CommandExecutor for Pop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let count = self.count.unwrap_or(1);
+        let ret = backend.write_value(&self.key, false, |list: &mut List| {
+            let elements = pop_n(list, self.left, count);
+            let popped = !elements.is_empty();
+            (elements, popped)
+        });
+        match (ret, self.count) {
+            (Ok(Some(elements)), Some(_)) => bulk_array(elements),
+            (Ok(Some(mut elements)), None) => match elements.pop() {
+                Some(element) => BulkString::new(element).into(),
+                None => RespNullBulkString.into(),
+            },
+            // 带 count 参数时，key 不存在返回 null array
+            (Ok(None), Some(_)) => RespNullArray.into(),
+            (Ok(None), None) => RespNullBulkString.into(),
+            (Err(e), _) => e.into(),
+        }
+    }
+}
+
+impl LLen {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("llen", &args, 1)?;
+        Ok(LLen {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for LLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |list: &List| list.len() as i64) {
+            Ok(len) => len.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl LRange {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("lrange", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(LRange {
+            key: args.next().unwrap_or_default(),
+            start: parse_int(&args.next().unwrap_or_default())?,
+            stop: parse_int(&args.next().unwrap_or_default())?,
+        })
+    }
+}
+
+impl CommandExecutor for LRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |list: &List| {
+            match normalize_range(self.start, self.stop, list.len()) {
+                Some((start, end)) => list.range(start, end),
+                None => vec![],
+            }
+        });
+        match ret {
+            Ok(elements) => bulk_array(elements.unwrap_or_default()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl LIndex {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("lindex", &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(LIndex {
+            key: args.next().unwrap_or_default(),
+            index: parse_int(&args.next().unwrap_or_default())?,
+        })
+    }
+}
+
+impl CommandExecutor for LIndex {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |list: &List| {
+            normalize_index(self.index, list.len()).and_then(|i| list.get(i))
+        });
+        match ret {
+            Ok(Some(Some(element))) => BulkString::new(element).into(),
+            Ok(_) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl LSet {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("lset", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(LSet {
+            key: args.next().unwrap_or_default(),
+            index: parse_int(&args.next().unwrap_or_default())?,
+            element: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for LSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |list: &mut List| {
+            let set = match normalize_index(self.index, list.len()) {
+                Some(i) => list.set(i, self.element),
+                None => false,
+            };
+            (set, set)
+        });
+        match ret {
+            Ok(Some(true)) => SimpleString::new("OK").into(),
+            Ok(Some(false)) => {
+                CommandError::InvalidArgument("index out of range".to_string()).into()
+            }
+            Ok(None) => CommandError::InvalidArgument("no such key".to_string()).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl LRem {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("lrem", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(LRem {
+            key: args.next().unwrap_or_default(),
+            count: parse_int(&args.next().unwrap_or_default())?,
+            element: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for LRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |list: &mut List| {
+            let removed = list.remove(&self.element, self.count);
+            (removed as i64, removed > 0)
+        });
+        match ret {
+            Ok(removed) => removed.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl LTrim {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("ltrim", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(LTrim {
+            key: args.next().unwrap_or_default(),
+            start: parse_int(&args.next().unwrap_or_default())?,
+            stop: parse_int(&args.next().unwrap_or_default())?,
+        })
+    }
+}
+
+impl CommandExecutor for LTrim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |list: &mut List| {
+            let len = list.len();
+            match normalize_range(self.start, self.stop, len) {
+                Some((start, end)) => list.trim(start, end),
+                // 范围之外的全部删除
+                None => list.trim(1, 0),
+            }
+            ((), list.len() != len)
+        });
+        match ret {
+            Ok(_) => SimpleString::new("OK").into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl LInsert {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("linsert", &args, 4)?;
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let before = match String::from_utf8_lossy(&args.next().unwrap_or_default())
+            .to_uppercase()
+            .as_str()
+        {
+            "BEFORE" => true,
+            "AFTER" => false,
+            _ => return Err(CommandError::Syntax),
+        };
+        Ok(LInsert {
+            key,
+            before,
+            pivot: args.next().unwrap_or_default(),
+            element: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for LInsert {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |list: &mut List| {
+            match list.iter().position(|v| v == self.pivot.as_slice()) {
+                Some(i) => {
+                    list.insert(if self.before { i } else { i + 1 }, self.element);
+                    (list.len() as i64, true)
+                }
+                // 没有找到 pivot，list 没有变化
+                None => (-1, false),
+            }
+        });
+        match ret {
+            Ok(len) => len.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl LPos {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("lpos".to_string()));
+        }
+        let mut args = args.into_iter();
+        let mut lpos = LPos {
+            key: args.next().unwrap_or_default(),
+            element: args.next().unwrap_or_default(),
+            rank: 1,
+            count: None,
+            maxlen: 0,
+        };
+        while let Some(arg) = args.next() {
+            let option = String::from_utf8_lossy(&arg).to_uppercase();
+            let n = parse_int(&args.next().ok_or(CommandError::Syntax)?)?;
+            match option.as_str() {
+                "RANK" if n == 0 || n == i64::MIN => {
+                    return Err(CommandError::InvalidArgument(
+                        "RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the last match".to_string(),
+                    ))
+                }
+                "RANK" => lpos.rank = n,
+                "COUNT" if n < 0 => {
+                    return Err(CommandError::InvalidArgument(
+                        "COUNT can't be negative".to_string(),
+                    ))
+                }
+                "COUNT" => lpos.count = Some(n as usize),
+                "MAXLEN" if n < 0 => {
+                    return Err(CommandError::InvalidArgument(
+                        "MAXLEN can't be negative".to_string(),
+                    ))
+                }
+                "MAXLEN" => lpos.maxlen = n as usize,
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(lpos)
+    }
+}
+
+impl CommandExecutor for LPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // COUNT 0 表示返回所有匹配的位置
+        let limit = match self.count {
+            Some(0) => usize::MAX,
+            Some(n) => n,
+            None => 1,
+        };
+        let maxlen = if self.maxlen == 0 {
+            usize::MAX
+        } else {
+            self.maxlen
+        };
+        let skip = (self.rank.unsigned_abs() - 1) as usize;
+        let ret = backend.read_value(&self.key, |list: &List| {
+            let len = list.len();
+            let matches = |(_, v): &(usize, &[u8])| *v == self.element.as_slice();
+            let positions: Vec<i64> = if self.rank > 0 {
+                list.iter()
+                    .enumerate()
+                    .take(maxlen)
+                    .filter(matches)
+                    .skip(skip)
+                    .take(limit)
+                    .map(|(i, _)| i as i64)
+                    .collect()
+            } else {
+                list.iter()
+                    .enumerate()
+                    .rev()
+                    .take(maxlen)
+                    .filter(matches)
+                    .skip(skip)
+                    .take(limit)
+                    .map(|(i, _)| i as i64)
+                    .collect()
+            };
+            debug_assert!(positions.iter().all(|&i| (i as usize) < len));
+            positions
+        });
+        let positions = match ret {
+            Ok(positions) => positions.unwrap_or_default(),
+            Err(e) => return e.into(),
+        };
+        match self.count {
+            Some(_) => RespArray::new(
+                positions
+                    .into_iter()
+                    .map(RespFrame::from)
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            None => match positions.first() {
+                Some(&i) => i.into(),
+                None => RespNullBulkString.into(),
+            },
+        }
+    }
+}
+
+impl LMove {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let rpoplpush = name == "rpoplpush";
+        validate_args(name, &args, if rpoplpush { 2 } else { 4 })?;
+        let mut args = args.into_iter();
+        let source = args.next().unwrap_or_default();
+        let destination = args.next().unwrap_or_default();
+        let (from_left, to_left) = if rpoplpush {
+            (false, true)
+        } else {
+            (
+                parse_direction(&args.next().unwrap_or_default())?,
+                parse_direction(&args.next().unwrap_or_default())?,
+            )
+        };
+        Ok(LMove {
+            source,
+            destination,
+            from_left,
+            to_left,
+        })
+    }
+}
+
+impl CommandExecutor for LMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match lmove(
+            backend,
+            &self.source,
+            &self.destination,
+            self.from_left,
+            self.to_left,
+        ) {
+            Ok(Some(element)) => BulkString::new(element).into(),
+            Ok(None) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+/// 从 source 弹出一个元素并写入 destination，source 为空时返回 None
+pub(crate) fn lmove(
+    backend: &Backend,
+    source: &[u8],
+    destination: &[u8],
+    from_left: bool,
+    to_left: bool,
+) -> Result<Option<Vec<u8>>, BackendError> {
+    // 和 Redis 一样 source 不存在时不检查 destination；
+    // 先检查 destination 的类型，保证出错时 source 没有被修改
+    if backend.read_value(source, |_: &List| ())?.is_none() {
+        return Ok(None);
+    }
+    backend.read_value(destination, |_: &List| ())?;
+    let element = backend.write_value(source, false, |list: &mut List| {
+        let element = if from_left {
+            list.pop_front()
+        } else {
+            list.pop_back()
+        };
+        let popped = element.is_some();
+        (element, popped)
+    })?;
+    let Some(element) = element.flatten() else {
+        return Ok(None);
+    };
+    backend.write_value(destination, true, |list: &mut List| {
+        if to_left {
+            list.push_front(element.clone());
+        } else {
+            list.push_back(element.clone());
+        }
+        ((), true)
+    })?;
+    Ok(Some(element))
+}
+
+impl LMPop {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let block = name == "blmpop";
+        if args.len() < if block { 4 } else { 3 } {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let timeout = match block {
+            true => parse_timeout(&args.next().unwrap_or_default())?,
+            false => None,
+        };
+        let numkeys = parse_int(&args.next().unwrap_or_default())
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| {
+                CommandError::InvalidArgument("numkeys should be greater than 0".to_string())
+            })?;
+        if numkeys as usize >= args.len() {
+            return Err(CommandError::Syntax);
+        }
+        let keys = args.by_ref().take(numkeys as usize).collect();
+        let left = parse_direction(&args.next().unwrap_or_default())?;
+        let count = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => 1,
+            (Some(option), Some(count), None) if option.eq_ignore_ascii_case(b"COUNT") => {
+                parse_int(&count).ok().filter(|n| *n > 0).ok_or_else(|| {
+                    CommandError::InvalidArgument("count should be greater than 0".to_string())
+                })? as usize
+            }
+            _ => return Err(CommandError::Syntax),
+        };
+        Ok(LMPop {
+            keys,
+            left,
+            count,
+            block,
+            timeout,
+        })
+    }
+
+    fn serve(&self) -> ServeFn {
+        let (left, count) = (self.left, self.count);
+        Box::new(move |backend, key| {
+            let elements = backend.write_value(key, false, |list: &mut List| {
+                let elements = pop_n(list, left, count);
+                let popped = !elements.is_empty();
+                (elements, popped)
+            })?;
+            Ok(elements
+                .filter(|elements| !elements.is_empty())
+                .map(|elements| {
+                    RespArray::new([BulkString::new(key).into(), bulk_array(elements)]).into()
+                }))
+        })
+    }
+}
+
+impl CommandExecutor for LMPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        serve_now(backend, &self.keys, self.serve())
+    }
+}
+
+impl BlockingExecutor for LMPop {
+    fn execute_blocking(self, backend: &Backend) -> Outcome {
+        if !self.block {
+            return Outcome::Reply(self.execute(backend));
+        }
+        let serve = self.serve();
+        serve_or_block(backend, self.keys, serve, self.timeout)
+    }
+}
+
+impl BPop {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args;
+        let timeout = parse_timeout(&args.pop().unwrap_or_default())?;
+        Ok(BPop {
+            keys: args,
+            left: name == "blpop",
+            timeout,
+        })
+    }
+
+    fn serve(&self) -> ServeFn {
+        let left = self.left;
+        Box::new(move |backend, key| {
+            let element = backend.write_value(key, false, |list: &mut List| {
+                let elements = pop_n(list, left, 1);
+                let popped = !elements.is_empty();
+                (elements, popped)
+            })?;
+            Ok(element.and_then(|mut v| v.pop()).map(|v| {
+                RespArray::new([BulkString::new(key).into(), BulkString::new(v).into()]).into()
+            }))
+        })
+    }
+}
+
+impl CommandExecutor for BPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        serve_now(backend, &self.keys, self.serve())
+    }
+}
+
+impl BlockingExecutor for BPop {
+    fn execute_blocking(self, backend: &Backend) -> Outcome {
+        let serve = self.serve();
+        serve_or_block(backend, self.keys, serve, self.timeout)
+    }
+}
+
+impl BLMove {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let brpoplpush = name == "brpoplpush";
+        validate_args(name, &args, if brpoplpush { 3 } else { 5 })?;
+        let mut args = args;
+        let timeout = parse_timeout(&args.pop().unwrap_or_default())?;
+        let name = if brpoplpush { "rpoplpush" } else { "lmove" };
+        Ok(BLMove {
+            lmove: LMove::parse(name, args)?,
+            timeout,
+        })
+    }
+
+    fn serve(&self) -> ServeFn {
+        let destination = self.lmove.destination.clone();
+        let (from_left, to_left) = (self.lmove.from_left, self.lmove.to_left);
+        Box::new(move |backend, key| {
+            let element = lmove(backend, key, &destination, from_left, to_left)?;
+            Ok(element.map(|v| BulkString::new(v).into()))
+        })
+    }
+}
+
+impl CommandExecutor for BLMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        serve_now(
+            backend,
+            std::slice::from_ref(&self.lmove.source),
+            self.serve(),
+        )
+    }
+}
+
+impl BlockingExecutor for BLMove {
+    fn execute_blocking(self, backend: &Backend) -> Outcome {
+        let serve = self.serve();
+        serve_or_block(backend, vec![self.lmove.source], serve, self.timeout)
+    }
+}
+
+fn pop_n(list: &mut List, left: bool, count: usize) -> Vec<Vec<u8>> {
+    let mut elements = Vec::with_capacity(count.min(list.len()));
+    while elements.len() < count {
+        let element = if left {
+            list.pop_front()
+        } else {
+            list.pop_back()
+        };
+        match element {
+            Some(element) => elements.push(element),
+            None => break,
+        }
+    }
+    elements
+}
+
+fn parse_direction(arg: &[u8]) -> Result<bool, CommandError> {
+    match String::from_utf8_lossy(arg).to_uppercase().as_str() {
+        "LEFT" => Ok(true),
+        "RIGHT" => Ok(false),
+        _ => Err(CommandError::Syntax),
+    }
+}
+
+fn parse_positive(arg: &[u8]) -> Result<usize, CommandError> {
+    match parse_int(arg)? {
+        n if n < 0 => Err(CommandError::InvalidArgument(
+            "value is out of range, must be positive".to_string(),
+        )),
+        n => Ok(n as usize),
+    }
+}
+
+fn bulk_array(elements: Vec<Vec<u8>>) -> RespFrame {
+    RespArray::new(
+        elements
+            .into_iter()
+            .map(|v| BulkString::new(v).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+/// 把可能为负数的下标转换成实际的下标，超出范围时返回 None
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+    (index >= 0 && (index as usize) < len).then_some(index as usize)
+}
+
+/// 按照 LRANGE 的规则转换 [start, stop]，结果为空时返回 None
+pub(crate) fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { start + len } else { start }.max(0);
+    let stop = if stop < 0 { stop + len } else { stop }.min(len - 1);
+    (start <= stop && start < len).then_some((start as usize, stop as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::{run, run_blocking};
+    use crate::SimpleError;
+    use anyhow::Result;
+
+    fn array(elements: &[&str]) -> RespFrame {
+        bulk_array(elements.iter().map(|v| v.as_bytes().to_vec()).collect())
+    }
+
+    #[test]
+    fn test_push_pop() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["LPUSHX", "l", "a"])?, 0.into());
+        assert!(!backend.exists(b"l"));
+        assert_eq!(run(&backend, &["LPUSH", "l", "b", "a"])?, 2.into());
+        assert_eq!(run(&backend, &["RPUSH", "l", "c", "d"])?, 4.into());
+        assert_eq!(run(&backend, &["RPUSHX", "l", "e"])?, 5.into());
+        assert_eq!(run(&backend, &["LLEN", "l"])?, 5.into());
+        assert_eq!(
+            run(&backend, &["LRANGE", "l", "0", "-1"])?,
+            array(&["a", "b", "c", "d", "e"])
+        );
+
+        assert_eq!(run(&backend, &["LPOP", "l"])?, BulkString::new("a").into());
+        assert_eq!(run(&backend, &["RPOP", "l", "2"])?, array(&["e", "d"]));
+        assert_eq!(run(&backend, &["LPOP", "l", "10"])?, array(&["b", "c"]));
+        assert!(!backend.exists(b"l"));
+        assert_eq!(run(&backend, &["LPOP", "l"])?, RespNullBulkString.into());
+        assert_eq!(run(&backend, &["LPOP", "l", "1"])?, RespNullArray.into());
+        assert!(run(&backend, &["LPOP", "l", "-1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_type() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SET", "s", "v"])?;
+        let wrong_type: RespFrame = BackendError::WrongType.into();
+        assert_eq!(run(&backend, &["LPUSH", "s", "a"])?, wrong_type);
+        assert_eq!(run(&backend, &["LRANGE", "s", "0", "-1"])?, wrong_type);
+        run(&backend, &["RPUSH", "l", "a"])?;
+        assert_eq!(run(&backend, &["GET", "l"])?, wrong_type);
+        assert_eq!(
+            run(&backend, &["LMOVE", "l", "s", "LEFT", "LEFT"])?,
+            wrong_type
+        );
+        assert_eq!(run(&backend, &["LLEN", "l"])?, 1.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lrange_lindex_lset() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["RPUSH", "l", "a", "b", "c"])?;
+        for (start, stop, expected) in [
+            ("0", "0", &["a"][..]),
+            ("-3", "2", &["a", "b", "c"]),
+            ("-100", "100", &["a", "b", "c"]),
+            ("5", "10", &[]),
+            ("2", "1", &[]),
+            ("-1", "-1", &["c"]),
+        ] {
+            assert_eq!(
+                run(&backend, &["LRANGE", "l", start, stop])?,
+                array(expected)
+            );
+        }
+        assert_eq!(
+            run(&backend, &["LINDEX", "l", "-1"])?,
+            BulkString::new("c").into()
+        );
+        assert_eq!(
+            run(&backend, &["LINDEX", "l", "3"])?,
+            RespNullBulkString.into()
+        );
+
+        assert_eq!(
+            run(&backend, &["LSET", "l", "-2", "x"])?,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(
+            run(&backend, &["LINDEX", "l", "1"])?,
+            BulkString::new("x").into()
+        );
+        assert_eq!(
+            run(&backend, &["LSET", "l", "3", "x"])?,
+            SimpleError::new("ERR index out of range").into()
+        );
+        assert_eq!(
+            run(&backend, &["LSET", "missing", "0", "x"])?,
+            SimpleError::new("ERR no such key").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lrem_ltrim_linsert() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["RPUSH", "l", "a", "b", "a", "c", "a"])?;
+        assert_eq!(run(&backend, &["LREM", "l", "-2", "a"])?, 2.into());
+        assert_eq!(
+            run(&backend, &["LRANGE", "l", "0", "-1"])?,
+            array(&["a", "b", "c"])
+        );
+        assert_eq!(
+            run(&backend, &["LINSERT", "l", "BEFORE", "c", "x"])?,
+            4.into()
+        );
+        assert_eq!(
+            run(&backend, &["LINSERT", "l", "after", "c", "y"])?,
+            5.into()
+        );
+        assert_eq!(
+            run(&backend, &["LINSERT", "l", "AFTER", "z", "y"])?,
+            (-1).into()
+        );
+        assert_eq!(
+            run(&backend, &["LINSERT", "missing", "AFTER", "z", "y"])?,
+            0.into()
+        );
+        assert_eq!(
+            run(&backend, &["LRANGE", "l", "0", "-1"])?,
+            array(&["a", "b", "x", "c", "y"])
+        );
+
+        run(&backend, &["LTRIM", "l", "1", "-2"])?;
+        assert_eq!(
+            run(&backend, &["LRANGE", "l", "0", "-1"])?,
+            array(&["b", "x", "c"])
+        );
+        run(&backend, &["LTRIM", "l", "5", "10"])?;
+        assert!(!backend.exists(b"l"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_lpos() -> Result<()> {
+        let backend = Backend::new();
+        run(
+            &backend,
+            &["RPUSH", "l", "a", "b", "c", "1", "2", "3", "c", "c"],
+        )?;
+        assert_eq!(run(&backend, &["LPOS", "l", "c"])?, 2.into());
+        assert_eq!(run(&backend, &["LPOS", "l", "c", "RANK", "2"])?, 6.into());
+        assert_eq!(run(&backend, &["LPOS", "l", "c", "RANK", "-1"])?, 7.into());
+        assert_eq!(
+            run(&backend, &["LPOS", "l", "c", "COUNT", "2"])?,
+            RespArray::new([2.into(), 6.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["LPOS", "l", "c", "RANK", "-1", "COUNT", "0"])?,
+            RespArray::new([7.into(), 6.into(), 2.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["LPOS", "l", "c", "COUNT", "0", "MAXLEN", "7"])?,
+            RespArray::new([2.into(), 6.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["LPOS", "l", "x"])?,
+            RespNullBulkString.into()
+        );
+        assert!(run(&backend, &["LPOS", "l", "c", "RANK", "0"]).is_err());
+        assert!(run(&backend, &["LPOS", "l", "c", "COUNT", "-1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lmove() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["RPUSH", "src", "a", "b", "c"])?;
+        assert_eq!(
+            run(&backend, &["LMOVE", "src", "dst", "RIGHT", "LEFT"])?,
+            BulkString::new("c").into()
+        );
+        assert_eq!(
+            run(&backend, &["LMOVE", "src", "dst", "LEFT", "RIGHT"])?,
+            BulkString::new("a").into()
+        );
+        assert_eq!(
+            run(&backend, &["RPOPLPUSH", "src", "dst"])?,
+            BulkString::new("b").into()
+        );
+        assert!(!backend.exists(b"src"));
+        assert_eq!(
+            run(&backend, &["LRANGE", "dst", "0", "-1"])?,
+            array(&["b", "c", "a"])
+        );
+        assert_eq!(
+            run(&backend, &["LMOVE", "src", "dst", "LEFT", "LEFT"])?,
+            RespNullBulkString.into()
+        );
+
+        // source 和 destination 相同时相当于旋转
+        assert_eq!(
+            run(&backend, &["LMOVE", "dst", "dst", "LEFT", "RIGHT"])?,
+            BulkString::new("b").into()
+        );
+        assert_eq!(
+            run(&backend, &["LRANGE", "dst", "0", "-1"])?,
+            array(&["c", "a", "b"])
+        );
+        assert!(run(&backend, &["LMOVE", "dst", "dst", "UP", "RIGHT"]).is_err());
+        Ok(())
+    }
+
+    fn blocked(outcome: Outcome) -> crate::Waiter {
+        match outcome {
+            Outcome::Block(waiter, _) => waiter,
+            Outcome::Reply(frame) => panic!("expected blocking, got {:?}", frame),
+            Outcome::Replies(frames) => panic!("expected blocking, got {:?}", frames),
+            Outcome::Replicate(feed) => panic!("expected blocking, got {:?}", feed),
+            Outcome::WaitAck(wait) => panic!("expected blocking, got {:?}", wait),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bpop() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["RPUSH", "b", "x", "y"])?;
+        let Outcome::Reply(reply) = run_blocking(&backend, &["BLPOP", "a", "b", "0"])? else {
+            panic!("BLPOP should not block when data is available");
+        };
+        assert_eq!(reply, array(&["b", "x"]));
+
+        // 先阻塞的客户端先被唤醒
+        let first = blocked(run_blocking(&backend, &["BRPOP", "a", "0"])?);
+        let second = blocked(run_blocking(&backend, &["BLPOP", "c", "a", "0"])?);
+        run(&backend, &["RPUSH", "a", "1"])?;
+        assert_eq!(first.wait(None).await, Some(array(&["a", "1"])));
+        assert!(!backend.exists(b"a"));
+
+        run(&backend, &["LPUSH", "c", "2", "3"])?;
+        assert_eq!(second.wait(None).await, Some(array(&["c", "3"])));
+        assert_eq!(run(&backend, &["LLEN", "c"])?, 1.into());
+
+        // 超时返回 None，由网络层转换成 null array
+        let waiter = blocked(run_blocking(&backend, &["BLPOP", "a", "0.01"])?);
+        assert_eq!(waiter.wait(Some(Duration::from_millis(10))).await, None);
+
+        // 不阻塞的版本 (事务中) 直接返回 null array
+        assert_eq!(run(&backend, &["BLPOP", "a", "0"])?, RespNullArray.into());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blmove_blmpop() -> Result<()> {
+        let backend = Backend::new();
+        let waiter = blocked(run_blocking(
+            &backend,
+            &["BLMOVE", "src", "dst", "RIGHT", "LEFT", "0"],
+        )?);
+        let mpop = blocked(run_blocking(
+            &backend,
+            &["BLMPOP", "0", "2", "x", "dst", "LEFT", "COUNT", "5"],
+        )?);
+        run(&backend, &["RPUSH", "src", "a", "b"])?;
+        assert_eq!(waiter.wait(None).await, Some(BulkString::new("b").into()));
+        // BLMOVE 写入 dst 之后继续唤醒等待 dst 的客户端
+        assert_eq!(
+            mpop.wait(None).await,
+            Some(RespArray::new([BulkString::new("dst").into(), array(&["b"])]).into())
+        );
+        assert!(!backend.exists(b"dst"));
+
+        assert_eq!(
+            run(&backend, &["LMPOP", "2", "x", "src", "RIGHT"])?,
+            RespArray::new([BulkString::new("src").into(), array(&["a"])]).into()
+        );
+        assert_eq!(
+            run(&backend, &["LMPOP", "1", "src", "LEFT"])?,
+            RespNullArray.into()
+        );
+        assert_eq!(
+            run(&backend, &["BRPOPLPUSH", "src", "dst", "0"])?,
+            RespNullArray.into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocking_errors() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SET", "s", "v"])?;
+        let Outcome::Reply(reply) = run_blocking(&backend, &["BLPOP", "a", "s", "0"])? else {
+            panic!("BLPOP should fail on the wrong type");
+        };
+        assert_eq!(reply, BackendError::WrongType.into());
+
+        for args in [
+            &["BLPOP", "a", "-1"][..],
+            &["BLPOP", "a", "abc"],
+            &["BLMPOP", "0", "0", "a", "LEFT"],
+            &["LMPOP", "2", "a", "LEFT"],
+            &["LMPOP", "1", "a", "LEFT", "COUNT", "0"],
+            &["LMPOP", "1", "a", "UP"],
+        ] {
+            assert!(run(&backend, args).is_err(), "{:?}", args);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,801 @@
+mod cluster;
+mod connection;
+mod expire;
+mod function;
+mod hash;
+mod info;
+mod list;
+mod persistence;
+mod propagate;
+mod pubsub;
+mod replication;
+mod scripting;
+mod set;
+mod stream;
+mod string;
+mod transaction;
+mod zset;
+
+use crate::{
+    backend::parse_i64, AckWait, Backend, BackendError, PersistenceError, ReplicaFeed, RespArray,
+    RespDecodeError, RespFrame, RespNullArray, ServeFn, SimpleError, SimpleString, Waiter,
+};
+use enum_dispatch::enum_dispatch;
+use std::time::Duration;
+use thiserror::Error;
+
+pub use cluster::{Asking, Cluster, ClusterRequest};
+pub use connection::{Hello, Ping, Session};
+pub use expire::{Expire, Persist, Ttl};
+pub use function::{FCall, Function};
+pub use hash::{
+    HDel, HExists, HExpire, HGet, HGetAll, HIncrBy, HIncrByFloat, HKeys, HLen, HMGet, HPersist,
+    HRandField, HScan, HSet, HSetNx, HStrLen, HTtl, HVals,
+};
+pub use info::Info;
+pub use list::{
+    BLMove, BPop, LIndex, LInsert, LLen, LMPop, LMove, LPos, LRange, LRem, LSet, LTrim, Pop, Push,
+};
+pub use persistence::{load_dataset, load_snapshot, BgRewriteAof, BgSave, LastSave, Save};
+pub use pubsub::{unsubscribe_all, PubSubQuery, Publish, Subscribe, SubscribeKind, Unsubscribe};
+pub use replication::{parse_replconf_ack, Psync, Replconf, ReplicaOf, Role, Wait};
+pub use scripting::{Eval, Script};
+pub use set::{
+    SAdd, SCard, SInterCard, SIsMember, SMIsMember, SMembers, SMove, SPop, SRandMember, SRem, SetOp,
+};
+pub use stream::{
+    XAck, XAdd, XAutoClaim, XClaim, XDel, XGroup, XInfo, XLen, XPending, XRange, XRead, XTrim,
+};
+pub use string::{
+    Append, Get, GetDel, GetEx, GetRange, IncrBy, IncrByFloat, MGet, MSet, Set, SetRange, StrLen,
+};
+pub use transaction::{unwatch_all, Discard, Exec, Multi, Transaction, Unwatch, Watch};
+pub use zset::{
+    BZPop, ZAdd, ZCount, ZIncrBy, ZLexCount, ZMPop, ZMScore, ZPop, ZRange, ZRank, ZRem, ZScore,
+    ZSetOp,
+};
+
+/// 执行命令，返回给客户端的 frame
+#[enum_dispatch]
+pub trait CommandExecutor {
+    fn execute(self, backend: &Backend) -> RespFrame;
+}
+
+/// 可能阻塞的命令 (BLPOP 等)，没有数据时返回 Waiter，由网络层在释放执行锁之后等待。
+/// 这类命令的 `CommandExecutor::execute` 是不阻塞的版本，没有数据时直接返回 null array
+pub trait BlockingExecutor {
+    fn execute_blocking(self, backend: &Backend) -> Outcome;
+}
+
+/// 需要访问连接状态的命令，例如 HELLO 切换协议版本、按照协议版本返回不同结构的命令
+pub trait SessionExecutor {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame;
+}
+
+/// 一条命令返回多个响应的命令，例如 SUBSCRIBE 对每个 channel 返回一条确认
+pub trait MultiReplyExecutor {
+    fn execute_multi(self, session: &mut Session, backend: &Backend) -> Vec<RespFrame>;
+}
+
+/// 命令执行的结果
+#[derive(Debug)]
+pub enum Outcome {
+    Reply(RespFrame),
+    // 依次发送多个响应
+    Replies(Vec<RespFrame>),
+    // 阻塞等待，timeout 为 None 时一直等待
+    Block(Waiter, Option<Duration>),
+    // PSYNC/SYNC 之后连接变成复制连接
+    Replicate(ReplicaFeed),
+    // WAIT/WAITAOF 等待 replica 或者本地 AOF 确认
+    WaitAck(AckWait),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CommandError {
+    #[error("unknown command '{0}'")]
+    UnknownCommand(String),
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("syntax error")]
+    Syntax,
+    #[error("value is not an integer or out of range")]
+    NotInteger,
+    #[error("value is not a valid float")]
+    NotFloat,
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("Protocol error: {0}")]
+    InvalidFrame(String),
+    #[error("{0}")]
+    RespError(#[from] RespDecodeError),
+}
+
+#[enum_dispatch(CommandExecutor)]
+#[derive(Debug)]
+pub enum Command {
+    Ping(Ping),
+    Hello(Hello),
+    Get(Get),
+    Set(Set),
+    IncrBy(IncrBy),
+    IncrByFloat(IncrByFloat),
+    Append(Append),
+    StrLen(StrLen),
+    GetRange(GetRange),
+    SetRange(SetRange),
+    MGet(MGet),
+    MSet(MSet),
+    GetDel(GetDel),
+    GetEx(GetEx),
+    Expire(Expire),
+    Ttl(Ttl),
+    Persist(Persist),
+    Push(Push),
+    Pop(Pop),
+    LLen(LLen),
+    LRange(LRange),
+    LIndex(LIndex),
+    LSet(LSet),
+    LRem(LRem),
+    LTrim(LTrim),
+    LInsert(LInsert),
+    LPos(LPos),
+    LMove(LMove),
+    LMPop(LMPop),
+    BPop(BPop),
+    BLMove(BLMove),
+    HSet(HSet),
+    HSetNx(HSetNx),
+    HGet(HGet),
+    HMGet(HMGet),
+    HGetAll(HGetAll),
+    HDel(HDel),
+    HExists(HExists),
+    HIncrBy(HIncrBy),
+    HIncrByFloat(HIncrByFloat),
+    HKeys(HKeys),
+    HVals(HVals),
+    HLen(HLen),
+    HStrLen(HStrLen),
+    HRandField(HRandField),
+    HScan(HScan),
+    HExpire(HExpire),
+    HTtl(HTtl),
+    HPersist(HPersist),
+    SAdd(SAdd),
+    SRem(SRem),
+    SMembers(SMembers),
+    SIsMember(SIsMember),
+    SMIsMember(SMIsMember),
+    SCard(SCard),
+    SPop(SPop),
+    SRandMember(SRandMember),
+    SetOp(SetOp),
+    SMove(SMove),
+    SInterCard(SInterCard),
+    ZAdd(ZAdd),
+    ZRem(ZRem),
+    ZScore(ZScore),
+    ZMScore(ZMScore),
+    ZIncrBy(ZIncrBy),
+    ZRank(ZRank),
+    ZRange(ZRange),
+    ZCount(ZCount),
+    ZLexCount(ZLexCount),
+    ZPop(ZPop),
+    ZMPop(ZMPop),
+    BZPop(BZPop),
+    XAdd(XAdd),
+    XRange(XRange),
+    XLen(XLen),
+    XDel(XDel),
+    XTrim(XTrim),
+    XRead(XRead),
+    XGroup(XGroup),
+    XAck(XAck),
+    XPending(XPending),
+    XClaim(XClaim),
+    XAutoClaim(XAutoClaim),
+    XInfo(XInfo),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    Publish(Publish),
+    PubSubQuery(PubSubQuery),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Eval(Eval),
+    Script(Script),
+    Function(Function),
+    FCall(FCall),
+    Save(Save),
+    BgSave(BgSave),
+    LastSave(LastSave),
+    BgRewriteAof(BgRewriteAof),
+    ZSetOp(ZSetOp),
+    ReplicaOf(ReplicaOf),
+    Replconf(Replconf),
+    Psync(Psync),
+    Role(Role),
+    Info(Info),
+    Wait(Wait),
+    Cluster(Cluster),
+    Asking(Asking),
+}
+
+impl Command {
+    /// 在连接的上下文中执行命令，阻塞命令在没有数据时返回 `Outcome::Block`
+    ///
+    /// 写命令 (`session.argv` 不为 None) 执行之后按照实际的效果改写，传播到 AOF
+    pub fn execute_or_block(self, session: &mut Session, backend: &Backend) -> Outcome {
+        let argv = session.argv.take();
+        // replica 只接受 master 同步过来的写命令，事务中有写命令时整个事务被放弃
+        if argv.as_deref().is_some_and(is_denied_on_replica) && backend.is_replica() {
+            if let Some(transaction) = session.transaction.as_mut() {
+                transaction.abort();
+            }
+            return Outcome::Reply(
+                SimpleError::new("READONLY You can't write against a read only replica.").into(),
+            );
+        }
+        // 写入 AOF 失败之后拒绝执行写命令，直到写入恢复
+        if argv.is_some() {
+            if let Some(e) = backend.aof_write_error() {
+                if let Some(transaction) = session.transaction.as_mut() {
+                    transaction.abort();
+                }
+                let e = PersistenceError::AofWrite(e);
+                return Outcome::Reply(SimpleError::new(e.to_string()).into());
+            }
+        }
+        // MULTI 之后除了控制事务的命令，其他命令都先排队
+        if let Some(transaction) = session.transaction.as_mut() {
+            if matches!(self, Command::Psync(_) | Command::ReplicaOf(_)) {
+                transaction.abort();
+                return Outcome::Reply(
+                    CommandError::InvalidArgument(
+                        "Command not allowed inside a transaction".to_string(),
+                    )
+                    .into(),
+                );
+            }
+            if !matches!(
+                self,
+                Command::Multi(_) | Command::Exec(_) | Command::Discard(_) | Command::Watch(_)
+            ) {
+                transaction.queue(self, argv);
+                return Outcome::Reply(SimpleString::new("QUEUED").into());
+            }
+        }
+        let dirty = backend.dirty();
+        let outcome = self.dispatch(session, backend);
+        match argv {
+            Some(argv) => propagate::propagate(argv, outcome, dirty, backend),
+            None => outcome,
+        }
+    }
+
+    /// 命令会执行 Lua 脚本，事务中也可能有脚本
+    pub fn runs_script(&self) -> bool {
+        matches!(
+            self,
+            Command::Eval(_) | Command::FCall(_) | Command::Exec(_)
+        )
+    }
+
+    /// SCRIPT KILL/FUNCTION KILL 在其他连接执行脚本期间发送，不能等待执行锁
+    pub fn is_script_kill(&self) -> bool {
+        matches!(
+            self,
+            Command::Script(Script::Kill) | Command::Function(Function::Kill)
+        )
+    }
+
+    fn dispatch(self, session: &mut Session, backend: &Backend) -> Outcome {
+        match self {
+            Command::Ping(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::Hello(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::Asking(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::HRandField(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::ZRange(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::ZPop(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::ZSetOp(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::BPop(cmd) => cmd.execute_blocking(backend),
+            Command::BLMove(cmd) => cmd.execute_blocking(backend),
+            Command::LMPop(cmd) => cmd.execute_blocking(backend),
+            Command::ZMPop(cmd) => cmd.execute_blocking(backend),
+            Command::BZPop(cmd) => cmd.execute_blocking(backend),
+            // XREAD/XREADGROUP 既会阻塞，返回结构又和协议版本有关
+            Command::XRead(cmd) => cmd
+                .with_protocol(session.protocol)
+                .execute_blocking(backend),
+            Command::Multi(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::Exec(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::Discard(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::Watch(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::Unwatch(cmd) => Outcome::Reply(cmd.execute_session(session, backend)),
+            Command::Subscribe(cmd) => Outcome::Replies(cmd.execute_multi(session, backend)),
+            Command::Unsubscribe(cmd) => Outcome::Replies(cmd.execute_multi(session, backend)),
+            Command::Replconf(cmd) => cmd.execute_replconf(session),
+            Command::Psync(cmd) => cmd.execute_psync(session, backend),
+            Command::Wait(cmd) => cmd.execute_wait(session, backend),
+            cmd => Outcome::Reply(cmd.execute(backend)),
+        }
+    }
+
+    /// 解析连接发送的命令，RESP2 的订阅模式下只能执行订阅相关的命令。
+    /// MULTI 之后解析失败的命令不能入队，EXEC 时整个事务会被放弃
+    pub fn from_session(frame: RespFrame, session: &mut Session) -> Result<Self, CommandError> {
+        session.argv = match &frame {
+            RespFrame::Array(array) => propagation_argv(array),
+            _ => None,
+        };
+        let ret = match &frame {
+            RespFrame::Array(array) => command_name(array)
+                .and_then(|name| pubsub::check_subscriber_mode(session, &name))
+                .and_then(|_| frame.try_into()),
+            _ => frame.try_into(),
+        };
+        if let (Err(_), Some(transaction)) = (&ret, session.transaction.as_mut()) {
+            transaction.abort();
+        }
+        ret
+    }
+}
+
+impl From<CommandError> for RespFrame {
+    fn from(e: CommandError) -> Self {
+        SimpleError::new(format!("ERR {}", e)).into()
+    }
+}
+
+impl TryFrom<RespFrame> for Command {
+    type Error = CommandError;
+
+    fn try_from(v: RespFrame) -> Result<Self, Self::Error> {
+        match v {
+            RespFrame::Array(array) => array.try_into(),
+            _ => Err(CommandError::InvalidFrame(
+                "command must be an array of bulk strings".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Command {
+    type Error = CommandError;
+
+    fn try_from(v: RespArray) -> Result<Self, Self::Error> {
+        let name = command_name(&v)?;
+        let args = extract_args(v, 1)?;
+        match name.as_str() {
+            "ping" => Ok(Ping::parse(args)?.into()),
+            "hello" => Ok(Hello::parse(args)?.into()),
+            "get" => Ok(Get::parse(args)?.into()),
+            "set" => Ok(Set::parse(args)?.into()),
+            "incr" => Ok(IncrBy::parse("incr", args)?.into()),
+            "decr" => Ok(IncrBy::parse("decr", args)?.into()),
+            "incrby" => Ok(IncrBy::parse("incrby", args)?.into()),
+            "decrby" => Ok(IncrBy::parse("decrby", args)?.into()),
+            "incrbyfloat" => Ok(IncrByFloat::parse(args)?.into()),
+            "append" => Ok(Append::parse(args)?.into()),
+            "strlen" => Ok(StrLen::parse(args)?.into()),
+            "getrange" => Ok(GetRange::parse(args)?.into()),
+            "setrange" => Ok(SetRange::parse(args)?.into()),
+            "mget" => Ok(MGet::parse(args)?.into()),
+            "mset" => Ok(MSet::parse("mset", args)?.into()),
+            "msetnx" => Ok(MSet::parse("msetnx", args)?.into()),
+            "getdel" => Ok(GetDel::parse(args)?.into()),
+            "getex" => Ok(GetEx::parse(args)?.into()),
+            "expire" => Ok(Expire::parse("expire", args)?.into()),
+            "pexpire" => Ok(Expire::parse("pexpire", args)?.into()),
+            "expireat" => Ok(Expire::parse("expireat", args)?.into()),
+            "pexpireat" => Ok(Expire::parse("pexpireat", args)?.into()),
+            "ttl" => Ok(Ttl::parse("ttl", args)?.into()),
+            "pttl" => Ok(Ttl::parse("pttl", args)?.into()),
+            "expiretime" => Ok(Ttl::parse("expiretime", args)?.into()),
+            "pexpiretime" => Ok(Ttl::parse("pexpiretime", args)?.into()),
+            "persist" => Ok(Persist::parse(args)?.into()),
+            "lpush" => Ok(Push::parse("lpush", args)?.into()),
+            "rpush" => Ok(Push::parse("rpush", args)?.into()),
+            "lpushx" => Ok(Push::parse("lpushx", args)?.into()),
+            "rpushx" => Ok(Push::parse("rpushx", args)?.into()),
+            "lpop" => Ok(Pop::parse("lpop", args)?.into()),
+            "rpop" => Ok(Pop::parse("rpop", args)?.into()),
+            "llen" => Ok(LLen::parse(args)?.into()),
+            "lrange" => Ok(LRange::parse(args)?.into()),
+            "lindex" => Ok(LIndex::parse(args)?.into()),
+            "lset" => Ok(LSet::parse(args)?.into()),
+            "lrem" => Ok(LRem::parse(args)?.into()),
+            "ltrim" => Ok(LTrim::parse(args)?.into()),
+            "linsert" => Ok(LInsert::parse(args)?.into()),
+            "lpos" => Ok(LPos::parse(args)?.into()),
+            "lmove" => Ok(LMove::parse("lmove", args)?.into()),
+            "rpoplpush" => Ok(LMove::parse("rpoplpush", args)?.into()),
+            "lmpop" => Ok(LMPop::parse("lmpop", args)?.into()),
+            "blmpop" => Ok(LMPop::parse("blmpop", args)?.into()),
+            "blpop" => Ok(BPop::parse("blpop", args)?.into()),
+            "brpop" => Ok(BPop::parse("brpop", args)?.into()),
+            "blmove" => Ok(BLMove::parse("blmove", args)?.into()),
+            "brpoplpush" => Ok(BLMove::parse("brpoplpush", args)?.into()),
+            "hset" => Ok(HSet::parse("hset", args)?.into()),
+            "hmset" => Ok(HSet::parse("hmset", args)?.into()),
+            "hsetnx" => Ok(HSetNx::parse(args)?.into()),
+            "hget" => Ok(HGet::parse(args)?.into()),
+            "hmget" => Ok(HMGet::parse(args)?.into()),
+            "hgetall" => Ok(HGetAll::parse(args)?.into()),
+            "hdel" => Ok(HDel::parse(args)?.into()),
+            "hexists" => Ok(HExists::parse(args)?.into()),
+            "hincrby" => Ok(HIncrBy::parse(args)?.into()),
+            "hincrbyfloat" => Ok(HIncrByFloat::parse(args)?.into()),
+            "hkeys" => Ok(HKeys::parse(args)?.into()),
+            "hvals" => Ok(HVals::parse(args)?.into()),
+            "hlen" => Ok(HLen::parse(args)?.into()),
+            "hstrlen" => Ok(HStrLen::parse(args)?.into()),
+            "hrandfield" => Ok(HRandField::parse(args)?.into()),
+            "hscan" => Ok(HScan::parse(args)?.into()),
+            "hexpire" => Ok(HExpire::parse("hexpire", args)?.into()),
+            "hpexpire" => Ok(HExpire::parse("hpexpire", args)?.into()),
+            "hexpireat" => Ok(HExpire::parse("hexpireat", args)?.into()),
+            "hpexpireat" => Ok(HExpire::parse("hpexpireat", args)?.into()),
+            "httl" => Ok(HTtl::parse("httl", args)?.into()),
+            "hpttl" => Ok(HTtl::parse("hpttl", args)?.into()),
+            "hexpiretime" => Ok(HTtl::parse("hexpiretime", args)?.into()),
+            "hpexpiretime" => Ok(HTtl::parse("hpexpiretime", args)?.into()),
+            "hpersist" => Ok(HPersist::parse(args)?.into()),
+            "sadd" => Ok(SAdd::parse(args)?.into()),
+            "srem" => Ok(SRem::parse(args)?.into()),
+            "smembers" => Ok(SMembers::parse(args)?.into()),
+            "sismember" => Ok(SIsMember::parse(args)?.into()),
+            "smismember" => Ok(SMIsMember::parse(args)?.into()),
+            "scard" => Ok(SCard::parse(args)?.into()),
+            "spop" => Ok(SPop::parse(args)?.into()),
+            "srandmember" => Ok(SRandMember::parse(args)?.into()),
+            "sinter" => Ok(SetOp::parse("sinter", args)?.into()),
+            "sunion" => Ok(SetOp::parse("sunion", args)?.into()),
+            "sdiff" => Ok(SetOp::parse("sdiff", args)?.into()),
+            "sinterstore" => Ok(SetOp::parse("sinterstore", args)?.into()),
+            "sunionstore" => Ok(SetOp::parse("sunionstore", args)?.into()),
+            "sdiffstore" => Ok(SetOp::parse("sdiffstore", args)?.into()),
+            "smove" => Ok(SMove::parse(args)?.into()),
+            "sintercard" => Ok(SInterCard::parse(args)?.into()),
+            "zadd" => Ok(ZAdd::parse(args)?.into()),
+            "zrem" => Ok(ZRem::parse(args)?.into()),
+            "zscore" => Ok(ZScore::parse(args)?.into()),
+            "zmscore" => Ok(ZMScore::parse(args)?.into()),
+            "zincrby" => Ok(ZIncrBy::parse(args)?.into()),
+            "zrank" => Ok(ZRank::parse("zrank", args)?.into()),
+            "zrevrank" => Ok(ZRank::parse("zrevrank", args)?.into()),
+            "zrange" => Ok(ZRange::parse(args)?.into()),
+            "zcount" => Ok(ZCount::parse(args)?.into()),
+            "zlexcount" => Ok(ZLexCount::parse(args)?.into()),
+            "zpopmin" => Ok(ZPop::parse("zpopmin", args)?.into()),
+            "zpopmax" => Ok(ZPop::parse("zpopmax", args)?.into()),
+            "zmpop" => Ok(ZMPop::parse("zmpop", args)?.into()),
+            "bzmpop" => Ok(ZMPop::parse("bzmpop", args)?.into()),
+            "bzpopmin" => Ok(BZPop::parse("bzpopmin", args)?.into()),
+            "bzpopmax" => Ok(BZPop::parse("bzpopmax", args)?.into()),
+            "xadd" => Ok(XAdd::parse(args)?.into()),
+            "xrange" => Ok(XRange::parse("xrange", args)?.into()),
+            "xrevrange" => Ok(XRange::parse("xrevrange", args)?.into()),
+            "xlen" => Ok(XLen::parse(args)?.into()),
+            "xdel" => Ok(XDel::parse(args)?.into()),
+            "xtrim" => Ok(XTrim::parse(args)?.into()),
+            "xread" => Ok(XRead::parse("xread", args)?.into()),
+            "xreadgroup" => Ok(XRead::parse("xreadgroup", args)?.into()),
+            "xgroup" => Ok(XGroup::parse(args)?.into()),
+            "xack" => Ok(XAck::parse(args)?.into()),
+            "xpending" => Ok(XPending::parse(args)?.into()),
+            "xclaim" => Ok(XClaim::parse(args)?.into()),
+            "xautoclaim" => Ok(XAutoClaim::parse(args)?.into()),
+            "xinfo" => Ok(XInfo::parse(args)?.into()),
+            "subscribe" => Ok(Subscribe::parse(SubscribeKind::Channel, args)?.into()),
+            "psubscribe" => Ok(Subscribe::parse(SubscribeKind::Pattern, args)?.into()),
+            "unsubscribe" => Ok(Unsubscribe::parse(SubscribeKind::Channel, args).into()),
+            "punsubscribe" => Ok(Unsubscribe::parse(SubscribeKind::Pattern, args).into()),
+            "ssubscribe" => Ok(Subscribe::parse(SubscribeKind::Shard, args)?.into()),
+            "sunsubscribe" => Ok(Unsubscribe::parse(SubscribeKind::Shard, args).into()),
+            "publish" => Ok(Publish::parse("publish", args)?.into()),
+            "spublish" => Ok(Publish::parse("spublish", args)?.into()),
+            "pubsub" => Ok(PubSubQuery::parse(args)?.into()),
+            "multi" => Ok(Multi::parse(args)?.into()),
+            "exec" => Ok(Exec::parse(args)?.into()),
+            "discard" => Ok(Discard::parse(args)?.into()),
+            "watch" => Ok(Watch::parse(args)?.into()),
+            "unwatch" => Ok(Unwatch::parse(args)?.into()),
+            "eval" => Ok(Eval::parse("eval", args)?.into()),
+            "evalsha" => Ok(Eval::parse("evalsha", args)?.into()),
+            "script" => Ok(Script::parse(args)?.into()),
+            "function" => Ok(Function::parse(args)?.into()),
+            "fcall" => Ok(FCall::parse("fcall", args)?.into()),
+            "fcall_ro" => Ok(FCall::parse("fcall_ro", args)?.into()),
+            "save" => Ok(Save::parse(args)?.into()),
+            "bgsave" => Ok(BgSave::parse(args)?.into()),
+            "lastsave" => Ok(LastSave::parse(args)?.into()),
+            "bgrewriteaof" => Ok(BgRewriteAof::parse(args)?.into()),
+            "replicaof" => Ok(ReplicaOf::parse("replicaof", args)?.into()),
+            "slaveof" => Ok(ReplicaOf::parse("slaveof", args)?.into()),
+            "replconf" => Ok(Replconf::parse(args)?.into()),
+            "psync" => Ok(Psync::parse_psync(args)?.into()),
+            "sync" => Ok(Psync::parse_sync(args)?.into()),
+            "role" => Ok(Role::parse(args)?.into()),
+            "info" => Ok(Info::parse(args)?.into()),
+            "wait" => Ok(Wait::parse_wait(args)?.into()),
+            "waitaof" => Ok(Wait::parse_waitaof(args)?.into()),
+            "cluster" => Ok(Cluster::parse(args)?.into()),
+            "asking" => Ok(Asking::parse(args)?.into()),
+            "zunion" => Ok(ZSetOp::parse("zunion", args)?.into()),
+            "zinter" => Ok(ZSetOp::parse("zinter", args)?.into()),
+            "zdiff" => Ok(ZSetOp::parse("zdiff", args)?.into()),
+            "zunionstore" => Ok(ZSetOp::parse("zunionstore", args)?.into()),
+            "zinterstore" => Ok(ZSetOp::parse("zinterstore", args)?.into()),
+            "zdiffstore" => Ok(ZSetOp::parse("zdiffstore", args)?.into()),
+            _ => Err(CommandError::UnknownCommand(name)),
+        }
+    }
+}
+
+/// 按照 RESP 的约定，命令的参数都是 BulkString，这里统一转换成 Vec<u8>
+fn command_name(value: &RespArray) -> Result<String, CommandError> {
+    match value.first() {
+        Some(RespFrame::BulkString(name)) => Ok(String::from_utf8_lossy(name).to_lowercase()),
+        _ => Err(CommandError::InvalidFrame(
+            "command must be an array of bulk strings".to_string(),
+        )),
+    }
+}
+
+fn extract_args(value: RespArray, start: usize) -> Result<Vec<Vec<u8>>, CommandError> {
+    value
+        .0
+        .into_iter()
+        .skip(start)
+        .map(|frame| match frame {
+            RespFrame::BulkString(s) => Ok(s.0),
+            _ => Err(CommandError::InvalidFrame(
+                "command arguments must be bulk strings".to_string(),
+            )),
+        })
+        .collect()
+}
+
+/// 修改数据的命令，只读的脚本 (FCALL_RO) 中不能执行
+const WRITE_COMMANDS: [&str; 73] = [
+    "set",
+    "incr",
+    "decr",
+    "incrby",
+    "decrby",
+    "incrbyfloat",
+    "append",
+    "setrange",
+    "mset",
+    "msetnx",
+    "getdel",
+    "getex",
+    "expire",
+    "pexpire",
+    "expireat",
+    "pexpireat",
+    "persist",
+    "lpush",
+    "rpush",
+    "lpushx",
+    "rpushx",
+    "lpop",
+    "rpop",
+    "lset",
+    "lrem",
+    "ltrim",
+    "linsert",
+    "lmove",
+    "rpoplpush",
+    "lmpop",
+    "blmpop",
+    "blpop",
+    "brpop",
+    "blmove",
+    "brpoplpush",
+    "hset",
+    "hmset",
+    "hsetnx",
+    "hdel",
+    "hincrby",
+    "hincrbyfloat",
+    "hexpire",
+    "hpexpire",
+    "hexpireat",
+    "hpexpireat",
+    "hpersist",
+    "sadd",
+    "srem",
+    "spop",
+    "sinterstore",
+    "sunionstore",
+    "sdiffstore",
+    "smove",
+    "zadd",
+    "zrem",
+    "zincrby",
+    "zpopmin",
+    "zpopmax",
+    "zmpop",
+    "bzmpop",
+    "bzpopmin",
+    "bzpopmax",
+    "zunionstore",
+    "zinterstore",
+    "zdiffstore",
+    "xadd",
+    "xdel",
+    "xtrim",
+    "xreadgroup",
+    "xgroup",
+    "xack",
+    "xclaim",
+    "xautoclaim",
+];
+
+fn is_write_command(name: &str) -> bool {
+    WRITE_COMMANDS.contains(&name)
+}
+
+/// replica 上不能执行的命令：写命令以及修改 function 的 FUNCTION 子命令
+fn is_denied_on_replica(argv: &[Vec<u8>]) -> bool {
+    match argv {
+        [name, sub, ..] if name.eq_ignore_ascii_case(b"function") => {
+            [&b"load"[..], b"delete", b"flush", b"restore"]
+                .iter()
+                .any(|s| sub.eq_ignore_ascii_case(s))
+        }
+        [name, ..] => is_write_command(&String::from_utf8_lossy(name).to_lowercase()),
+        [] => false,
+    }
+}
+
+/// 写命令以及 FUNCTION 需要传播，保留原始的参数
+pub(crate) fn propagation_argv(frame: &RespArray) -> Option<Vec<Vec<u8>>> {
+    let name = command_name(frame).ok()?;
+    if !is_write_command(&name) && name != "function" {
+        return None;
+    }
+    extract_args(frame.clone(), 0).ok()
+}
+
+/// 检查参数个数，`n_args` 不包含命令名本身
+fn validate_args(name: &str, args: &[Vec<u8>], n_args: usize) -> Result<(), CommandError> {
+    if args.len() != n_args {
+        return Err(CommandError::WrongArity(name.to_string()));
+    }
+    Ok(())
+}
+
+fn parse_string(arg: Vec<u8>) -> Result<String, CommandError> {
+    String::from_utf8(arg).map_err(|e| CommandError::InvalidArgument(e.to_string()))
+}
+
+fn parse_int(arg: &[u8]) -> Result<i64, CommandError> {
+    parse_i64(arg).ok_or(CommandError::NotInteger)
+}
+
+/// 解析浮点数，和 Redis 一样不允许前后有空格，也不接受 nan
+fn parse_float(arg: &[u8]) -> Result<f64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .filter(|s| !s.is_empty() && s.trim() == *s)
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|v| !v.is_nan())
+        .ok_or(CommandError::NotFloat)
+}
+
+/// 解析阻塞命令的超时时间 (秒，可以是小数)，0 表示一直等待
+fn parse_timeout(arg: &[u8]) -> Result<Option<Duration>, CommandError> {
+    let timeout = parse_float(arg)
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| {
+            CommandError::InvalidArgument("timeout is not a float or out of range".to_string())
+        })?;
+    if timeout < 0.0 {
+        return Err(CommandError::InvalidArgument(
+            "timeout is negative".to_string(),
+        ));
+    }
+    Ok((timeout > 0.0).then(|| Duration::from_secs_f64(timeout)))
+}
+
+/// 依次在 keys 上尝试获取数据，第一个有数据的 key 的结果作为响应
+fn serve_keys(
+    backend: &Backend,
+    keys: &[Vec<u8>],
+    serve: &mut ServeFn,
+) -> Result<Option<RespFrame>, BackendError> {
+    for key in keys {
+        if let Some(reply) = serve(backend, key)? {
+            return Ok(Some(reply));
+        }
+    }
+    Ok(None)
+}
+
+/// 阻塞命令的不阻塞版本 (例如在事务中执行)，没有数据时返回 null array
+fn serve_now(backend: &Backend, keys: &[Vec<u8>], mut serve: ServeFn) -> RespFrame {
+    match serve_keys(backend, keys, &mut serve) {
+        Ok(Some(reply)) => reply,
+        Ok(None) => RespNullArray.into(),
+        Err(e) => e.into(),
+    }
+}
+
+/// 有数据时立即返回，否则阻塞在 keys 上
+fn serve_or_block(
+    backend: &Backend,
+    keys: Vec<Vec<u8>>,
+    mut serve: ServeFn,
+    timeout: Option<Duration>,
+) -> Outcome {
+    match serve_keys(backend, &keys, &mut serve) {
+        Ok(Some(reply)) => Outcome::Reply(reply),
+        Ok(None) => Outcome::Block(backend.block(keys, serve), timeout),
+        Err(e) => Outcome::Reply(e.into()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{BulkString, RespDecode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    /// 解析并执行一条命令，供各个命令的单元测试使用
+    pub(crate) fn run(backend: &Backend, args: &[&str]) -> Result<RespFrame> {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        let cmd: Command = RespArray::new(frames).try_into()?;
+        let ret = cmd.execute(backend);
+        backend.serve_blocked_clients();
+        Ok(ret)
+    }
+
+    /// 和 run 一样，但是阻塞命令会返回 `Outcome::Block`
+    pub(crate) fn run_blocking(backend: &Backend, args: &[&str]) -> Result<Outcome> {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        let cmd: Command = RespArray::new(frames).try_into()?;
+        let outcome = cmd.execute_or_block(&mut Session::default(), backend);
+        backend.serve_blocked_clients();
+        Ok(outcome)
+    }
+
+    #[test]
+    fn test_command_parse() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n");
+        let frame = RespFrame::decode(&mut buf)?;
+        let cmd: Command = frame.try_into()?;
+        assert!(matches!(cmd, Command::Get(_)));
+
+        let frame: RespFrame = RespArray::new([b"foo".into()]).into();
+        let ret: Result<Command, _> = frame.try_into();
+        assert_eq!(
+            ret.unwrap_err(),
+            CommandError::UnknownCommand("foo".to_string())
+        );
+
+        let frame: RespFrame = RespArray::new([b"get".into()]).into();
+        let ret: Result<Command, _> = frame.try_into();
+        assert_eq!(
+            ret.unwrap_err(),
+            CommandError::WrongArity("get".to_string())
+        );
+        Ok(())
+    }
+}
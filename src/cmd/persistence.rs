@@ -0,0 +1,474 @@
+use super::{
+    command_name,
+    function::{flush_libraries, load_libraries},
+    validate_args, Command, CommandError, CommandExecutor, Session,
+};
+use crate::{
+    aof_files, AofKind, Backend, PersistenceError, RdbError, RespDecode, RespDecodeError,
+    RespFrame, RestorePolicy, SimpleString,
+};
+use bytes::BytesMut;
+use std::fs;
+use tracing::{info, warn};
+
+/// SAVE
+#[derive(Debug)]
+pub struct Save;
+
+/// BGSAVE [SCHEDULE]
+#[derive(Debug)]
+pub struct BgSave {
+    schedule: bool,
+}
+
+/// LASTSAVE
+#[derive(Debug)]
+pub struct LastSave;
+
+/// BGREWRITEAOF
+#[derive(Debug)]
+pub struct BgRewriteAof;
+
+/// 启动时加载数据。开启 AOF 时优先加载 AOF，AOF 不存在时加载 RDB 文件，之后打开 AOF 继续写入。
+/// 返回是否找到了 AOF 或者 RDB 文件
+pub fn load_dataset(backend: &Backend) -> Result<bool, PersistenceError> {
+    if !backend.aof_config().enabled {
+        return load_rdb_dataset(backend);
+    }
+    let loaded = load_aof(backend)? || load_rdb_dataset(backend)?;
+    backend.open_aof()?;
+    Ok(loaded)
+}
+
+/// 加载 RDB 文件，文件中的 function library 重新编译之后加载
+fn load_rdb_dataset(backend: &Backend) -> Result<bool, PersistenceError> {
+    let Some(codes) = backend.load_rdb_file()? else {
+        return Ok(false);
+    };
+    let count = codes.len();
+    load_functions(backend, codes)?;
+    info!(
+        "DB loaded from disk: {} keys, {} function libraries",
+        backend.db.len(),
+        count
+    );
+    Ok(true)
+}
+
+/// replica 全量同步时加载 master 发送的快照，替换掉原来所有的数据和 function
+pub fn load_snapshot(backend: &Backend, data: &[u8]) -> Result<(), PersistenceError> {
+    backend.flush_all();
+    flush_libraries(backend);
+    let codes = backend.load_rdb(data)?;
+    let count = codes.len();
+    load_functions(backend, codes)?;
+    info!(
+        "MASTER <-> REPLICA sync: loaded {} keys, {} function libraries",
+        backend.db.len(),
+        count
+    );
+    Ok(())
+}
+
+fn load_functions(backend: &Backend, codes: Vec<String>) -> Result<(), PersistenceError> {
+    load_libraries(backend, codes, RestorePolicy::Append)
+        .map(|_| ())
+        .map_err(|e| RdbError::Invalid(e).into())
+}
+
+/// 按照 manifest 依次加载 base 文件和 incr 文件，返回是否找到了 AOF
+///
+/// 最后一个文件的结尾不完整时 (例如写入时宕机)，和 Redis 的 aof-load-truncated 一样
+/// 截断到最后一条完整的命令，没有 EXEC 的事务整个丢弃
+fn load_aof(backend: &Backend) -> Result<bool, PersistenceError> {
+    let Some(manifest) = backend.load_aof_manifest()? else {
+        return Ok(false);
+    };
+    let dir = backend.aof_dir();
+    let files: Vec<_> = aof_files(&manifest).collect();
+    for (i, info) in files.iter().enumerate() {
+        let path = dir.join(&info.name);
+        let data = fs::read(&path).map_err(|e| {
+            PersistenceError::Aof(format!("The AOF file {} doesn't exist: {}", info.name, e))
+        })?;
+        // base 文件通常是 RDB 格式，也可以是命令
+        if info.kind == AofKind::Base && data.starts_with(b"REDIS") {
+            let codes = backend.load_rdb(&data)?;
+            load_functions(backend, codes)?;
+            continue;
+        }
+        let valid = replay_aof(backend, &data)?;
+        if valid < data.len() {
+            if i + 1 < files.len() {
+                return Err(PersistenceError::Aof(format!(
+                    "Unexpected end of file reading the append only file {}",
+                    info.name
+                )));
+            }
+            warn!(
+                "AOF {} was truncated, {} bytes at the end are discarded",
+                info.name,
+                data.len() - valid
+            );
+            fs::OpenOptions::new()
+                .write(true)
+                .open(&path)?
+                .set_len(valid as u64)?;
+        }
+    }
+    backend.clear_dirty();
+    info!("DB loaded from append only file: {} keys", backend.db.len());
+    Ok(true)
+}
+
+/// 依次执行 AOF 中的命令，返回最后一条完整的命令 (不在事务中) 结束的位置
+fn replay_aof(backend: &Backend, data: &[u8]) -> Result<usize, PersistenceError> {
+    let mut buf = BytesMut::from(data);
+    let mut session = Session::default();
+    let mut valid = 0;
+    while !buf.is_empty() {
+        let frame = match RespFrame::decode(&mut buf) {
+            Ok(frame) => frame,
+            Err(RespDecodeError::NotComplete) => break,
+            Err(e) => {
+                return Err(PersistenceError::Aof(format!(
+                    "Bad file format reading the append only file: {}",
+                    e
+                )))
+            }
+        };
+        // Redis 写入的 AOF 以 SELECT 0 开头，这里只有一个数据库
+        let select = match &frame {
+            RespFrame::Array(array) => command_name(array).is_ok_and(|name| name == "select"),
+            _ => false,
+        };
+        if !select {
+            let cmd = Command::from_session(frame, &mut session).map_err(|e| {
+                PersistenceError::Aof(format!("Error reading the append only file: {}", e))
+            })?;
+            cmd.execute_or_block(&mut session, backend);
+        }
+        if session.transaction.is_none() {
+            valid = data.len() - buf.len();
+        }
+    }
+    Ok(valid)
+}
+
+impl Save {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("save", &args, 0)?;
+        Ok(Save)
+    }
+}
+
+impl CommandExecutor for Save {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.save() {
+            Ok(()) => SimpleString::new("OK").into(),
+            Err(e) => CommandError::InvalidArgument(e.to_string()).into(),
+        }
+    }
+}
+
+impl BgSave {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        match args.as_slice() {
+            [] => Ok(BgSave { schedule: false }),
+            [arg] if arg.eq_ignore_ascii_case(b"schedule") => Ok(BgSave { schedule: true }),
+            [_] => Err(CommandError::Syntax),
+            _ => Err(CommandError::WrongArity("bgsave".to_string())),
+        }
+    }
+}
+
+impl CommandExecutor for BgSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = match self.schedule {
+            true => backend.schedule_bgsave(),
+            false => backend.bgsave().map(|_| false),
+        };
+        match ret {
+            Ok(true) => SimpleString::new("Background saving scheduled").into(),
+            Ok(false) => SimpleString::new("Background saving started").into(),
+            Err(e) => CommandError::InvalidArgument(e.to_string()).into(),
+        }
+    }
+}
+
+impl BgRewriteAof {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("bgrewriteaof", &args, 0)?;
+        Ok(BgRewriteAof)
+    }
+}
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.rewrite_aof() {
+            Ok(()) => SimpleString::new("Background append only file rewriting started").into(),
+            Err(e) => CommandError::InvalidArgument(e.to_string()).into(),
+        }
+    }
+}
+
+impl LastSave {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("lastsave", &args, 0)?;
+        Ok(LastSave)
+    }
+}
+
+impl CommandExecutor for LastSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        (backend.last_save() as i64).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cmd::{tests::run, Outcome},
+        AofConfig, AppendFsync, BulkString, RespArray, SaveConfig, SimpleError, Value,
+    };
+    use anyhow::Result;
+    use std::{io::Write, thread, time::Duration};
+
+    /// 和网络层一样执行命令，写命令传播到 AOF
+    fn call(backend: &Backend, session: &mut Session, args: &[&str]) -> Option<RespFrame> {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        let cmd = Command::from_session(RespArray::new(frames).into(), session).unwrap();
+        let outcome = cmd.execute_or_block(session, backend);
+        backend.propagate_pending().unwrap();
+        backend.serve_blocked_clients();
+        backend.propagate_pending().unwrap();
+        match outcome {
+            Outcome::Reply(frame) => Some(frame),
+            _ => None,
+        }
+    }
+
+    fn aof_backend(dir: &std::path::Path) -> Backend {
+        let backend = Backend::new();
+        backend.configure_save(SaveConfig {
+            dir: dir.to_path_buf(),
+            rules: vec![],
+            ..SaveConfig::default()
+        });
+        backend.configure_aof(AofConfig {
+            enabled: true,
+            fsync: AppendFsync::Always,
+            ..AofConfig::default()
+        });
+        backend
+    }
+
+    /// 加载之后的数据和原来的一样
+    fn assert_same(backend: &Backend, loaded: &Backend) {
+        for key in ["s", "e", "f", "set", "n", "h", "l", "l2", "z"] {
+            let key = key.as_bytes();
+            assert_eq!(loaded.get(key), backend.get(key), "key {:?}", key);
+            assert_eq!(
+                loaded.get_expire(key),
+                backend.get_expire(key),
+                "key {:?}",
+                key
+            );
+        }
+        // 扩展格式的 XPENDING 中有空闲时间，两次执行可能相差 1 毫秒，只比较 ID、消费者和投递次数
+        let pending = |backend: &Backend| {
+            let ret = run(backend, &["XPENDING", "st", "g", "-", "+", "10"]).unwrap();
+            let RespFrame::Array(entries) = ret else {
+                panic!("XPENDING should return an array");
+            };
+            entries
+                .iter()
+                .map(|entry| match entry {
+                    RespFrame::Array(entry) => [0, 1, 3].map(|i| entry[i].clone()),
+                    _ => panic!("XPENDING entry should be an array"),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pending(loaded), pending(backend));
+        for args in [
+            &["XRANGE", "st", "-", "+"][..],
+            &["XINFO", "GROUPS", "st"],
+            &["HPEXPIRETIME", "h", "FIELDS", "2", "a", "b"],
+        ] {
+            let args: Vec<&str> = args.to_vec();
+            assert_eq!(run(loaded, &args).unwrap(), run(backend, &args).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_aof_load_and_rewrite() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("simple-redis-aof-cmd-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let backend = aof_backend(&dir);
+        backend.set(b"s".to_vec(), Value::String(b"from rdb".to_vec()));
+        backend.save()?;
+        backend.remove(b"s");
+        // AOF 不存在时加载 RDB 文件，再生成 base 文件
+        assert!(load_dataset(&backend)?);
+        assert_eq!(backend.get(b"s"), Some(Value::String(b"from rdb".to_vec())));
+
+        let mut session = Session::new(1);
+        let session = &mut session;
+        for args in [
+            &["SET", "e", "v", "EX", "100"][..],
+            &["SET", "gone", "v"],
+            &["EXPIRE", "gone", "-1"],
+            &["INCRBYFLOAT", "f", "1.5"],
+            &["SADD", "set", "a", "b", "c"],
+            &["SPOP", "set"],
+            &["XADD", "st", "MAXLEN", "~", "100", "*", "k", "v"],
+            &["XGROUP", "CREATE", "st", "g", "0"],
+            &["XREADGROUP", "GROUP", "g", "c", "STREAMS", "st", ">"],
+            &["MULTI"],
+            &["INCR", "n"],
+            &["INCR", "n"],
+            &["EXEC"],
+            &["HSET", "h", "a", "1", "b", "2"],
+            &["HEXPIRE", "h", "100", "FIELDS", "2", "a", "c"],
+            &["RPUSH", "l", "a", "b"],
+            &["BLMOVE", "l", "l2", "LEFT", "RIGHT", "0"],
+            &["ZADD", "z", "1", "a", "2", "b"],
+            &["BZPOPMIN", "z", "0"],
+            &["SET", "x", "1", "NX", "GET"],
+            &["SET", "x", "2", "NX"],
+            &[
+                "EVAL",
+                "redis.call('SET', 'x', 'lua') redis.call('INCR', 'n')",
+                "0",
+            ],
+        ] {
+            call(&backend, session, args);
+        }
+        // 阻塞命令在被唤醒时传播
+        assert_eq!(call(&backend, session, &["BLPOP", "l3", "0"]), None);
+        call(&backend, &mut Session::new(2), &["RPUSH", "l3", "x", "y"]);
+        run(
+            &backend,
+            &[
+                "FUNCTION",
+                "LOAD",
+                "#!lua name=lib\nredis.register_function('f', function() return 1 end)",
+            ],
+        )?;
+        let mut session = Session::new(3);
+        call(
+            &backend,
+            &mut session,
+            &[
+                "FUNCTION",
+                "LOAD",
+                "#!lua name=lib2\nredis.register_function('f2', function() return 2 end)",
+            ],
+        );
+
+        let incr = dir.join("appendonlydir").join("appendonly.aof.1.incr.aof");
+        let content = String::from_utf8(fs::read(&incr)?)?;
+        assert!(content.contains("PXAT"));
+        assert!(content.contains("SREM"));
+        assert!(!content.contains("BLMOVE") && !content.contains("BZPOPMIN"));
+        assert!(content.contains("MULTI"));
+
+        let loaded = aof_backend(&dir);
+        assert!(load_dataset(&loaded)?);
+        assert_same(&backend, &loaded);
+        assert_eq!(loaded.get(b"gone"), None);
+        assert_eq!(
+            run(&loaded, &["LRANGE", "l3", "0", "-1"])?,
+            run(&backend, &["LRANGE", "l3", "0", "-1"])?
+        );
+        assert_eq!(run(&loaded, &["FCALL", "f2", "0"])?, 2.into());
+        assert_eq!(loaded.dirty(), 0);
+
+        // 不完整的命令和没有 EXEC 的事务被截断
+        let len = fs::metadata(&incr)?.len();
+        let mut file = fs::OpenOptions::new().append(true).open(&incr)?;
+        file.write_all(b"*1\r\n$5\r\nMULTI\r\n*2\r\n$4\r\nINCR\r\n$1\r\nn\r\n*2\r\n$4\r\nIN")?;
+        let loaded = aof_backend(&dir);
+        assert!(load_dataset(&loaded)?);
+        assert_same(&backend, &loaded);
+        assert_eq!(fs::metadata(&incr)?.len(), len);
+
+        // 重写之后只有新的 base 文件和之后的 incr 文件
+        let rewritten = call(&loaded, &mut Session::new(4), &["BGREWRITEAOF"]);
+        assert_eq!(
+            rewritten,
+            Some(SimpleString::new("Background append only file rewriting started").into())
+        );
+        while loaded.aof_rewrite_in_progress() {
+            thread::sleep(Duration::from_millis(5));
+        }
+        call(&loaded, &mut Session::new(4), &["INCR", "n"]);
+        call(&backend, &mut Session::new(4), &["INCR", "n"]);
+        assert!(!incr.exists());
+        let reloaded = aof_backend(&dir);
+        assert!(load_dataset(&reloaded)?);
+        assert_same(&backend, &reloaded);
+        assert_eq!(run(&reloaded, &["FCALL", "f2", "0"])?, 2.into());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_bgsave_lastsave() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("simple-redis-cmd-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let backend = Backend::new();
+        backend.configure_save(SaveConfig {
+            dir: dir.clone(),
+            rules: vec![],
+            ..SaveConfig::default()
+        });
+        assert!(!load_dataset(&backend)?);
+
+        run(&backend, &["SET", "a", "1"])?;
+        run(
+            &backend,
+            &[
+                "FUNCTION",
+                "LOAD",
+                "#!lua name=lib\nredis.register_function('f', function() return 1 end)",
+            ],
+        )?;
+        assert_eq!(run(&backend, &["SAVE"])?, SimpleString::new("OK").into());
+        assert_eq!(backend.dirty(), 0);
+        let RespFrame::Integer(last_save) = run(&backend, &["LASTSAVE"])? else {
+            panic!("LASTSAVE should return an integer");
+        };
+        assert!(last_save > 0);
+
+        run(&backend, &["SET", "b", "2"])?;
+        assert_eq!(
+            run(&backend, &["BGSAVE"])?,
+            SimpleString::new("Background saving started").into()
+        );
+        while backend.bgsave_in_progress() {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(run(&backend, &["BGSAVE", "FOO"]).is_err());
+
+        let loaded = Backend::new();
+        loaded.configure_save(backend.save_config());
+        assert!(load_dataset(&loaded)?);
+        assert_eq!(loaded.get(b"a"), Some(Value::String(b"1".to_vec())));
+        assert_eq!(loaded.get(b"b"), Some(Value::String(b"2".to_vec())));
+        assert_eq!(run(&loaded, &["FCALL", "f", "0"])?, 1.into());
+
+        // 目录不存在时保存失败
+        fs::remove_dir_all(&dir)?;
+        let RespFrame::Error(SimpleError(e)) = run(&backend, &["SAVE"])? else {
+            panic!("SAVE should fail");
+        };
+        assert!(e.starts_with("ERR "));
+        Ok(())
+    }
+}
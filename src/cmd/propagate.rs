@@ -0,0 +1,423 @@
+use super::Outcome;
+use crate::{Backend, Hash, RespFrame, Stream, StreamId, Value};
+
+/// 命令的参数，包括命令名
+type Argv = Vec<Vec<u8>>;
+
+/// 写命令执行之后传播到 AOF，对应 Redis 的 call() 中的 propagate
+///
+/// 命令的效果和执行时间、随机数有关时 (相对的过期时间、SPOP、XADD *、阻塞命令等)，
+/// 按照执行之后的状态改写成确定的命令，加载 AOF 时得到同样的结果。
+/// 阻塞的命令在被唤醒、真正取到数据时才传播
+pub(crate) fn propagate(argv: Argv, outcome: Outcome, dirty: u64, backend: &Backend) -> Outcome {
+    if !backend.propagation_enabled() {
+        return outcome;
+    }
+    match outcome {
+        Outcome::Reply(reply) => {
+            // 没有修改任何数据的命令 (例如 SET NX 失败) 不需要传播
+            if backend.dirty() != dirty {
+                propagate_reply(argv, &reply, backend);
+            }
+            Outcome::Reply(reply)
+        }
+        Outcome::Block(waiter, timeout) => {
+            waiter.map_serve(|mut serve| {
+                Box::new(move |backend, key| {
+                    let ret = serve(backend, key)?;
+                    if let Some(reply) = &ret {
+                        propagate_reply(argv.clone(), reply, backend);
+                    }
+                    Ok(ret)
+                })
+            });
+            Outcome::Block(waiter, timeout)
+        }
+        outcome => outcome,
+    }
+}
+
+fn propagate_reply(argv: Argv, reply: &RespFrame, backend: &Backend) {
+    if matches!(reply, RespFrame::Error(_)) {
+        return;
+    }
+    for argv in rewrite(argv, reply, backend) {
+        backend.propagate(argv);
+    }
+}
+
+/// 按照命令的返回值以及执行之后的状态改写命令，不需要改写的命令原样返回
+fn rewrite(argv: Argv, reply: &RespFrame, backend: &Backend) -> Vec<Argv> {
+    let name = String::from_utf8_lossy(&argv[0]).to_lowercase();
+    let arg = |i: usize| argv.get(i).cloned().unwrap_or_default();
+    match name.as_str() {
+        "expire" | "pexpire" | "expireat" | "pexpireat" | "getex" => {
+            vec![expire_state(backend, arg(1))]
+        }
+        "set" if argv.len() > 3 => string_state(backend, arg(1)),
+        "setex" | "psetex" | "incrbyfloat" => string_state(backend, arg(1)),
+        "hincrbyfloat" => hash_field_state(backend, arg(1), arg(2)),
+        "hexpire" | "hpexpire" | "hexpireat" | "hpexpireat" => {
+            hash_expire_state(backend, &argv, reply)
+        }
+        "spop" => {
+            let members: Vec<Vec<u8>> = items(reply).iter().filter_map(|f| bulk(f)).collect();
+            match members.is_empty() {
+                true => vec![],
+                false => vec![cmd([b"SREM".to_vec(), arg(1)].into_iter().chain(members))],
+            }
+        }
+        // 阻塞命令改写成对应的不阻塞的命令，key 是实际取到数据的 key
+        "blpop" | "brpop" => {
+            let pop: &[u8] = if name == "blpop" { b"LPOP" } else { b"RPOP" };
+            match items(reply).first().and_then(|f| bulk(f)) {
+                Some(key) => vec![vec![pop.to_vec(), key]],
+                None => vec![],
+            }
+        }
+        "brpoplpush" => vec![vec![b"RPOPLPUSH".to_vec(), arg(1), arg(2)]],
+        "blmove" => vec![[b"LMOVE".to_vec()]
+            .into_iter()
+            .chain(argv[1..5].iter().cloned())
+            .collect()],
+        "blmpop" => {
+            let items = items(reply);
+            let (Some(key), Some(elements)) = (items.first().and_then(|f| bulk(f)), items.get(1))
+            else {
+                return vec![];
+            };
+            let numkeys = parse_usize(&arg(2));
+            let count = self::items(elements).len().to_string();
+            vec![cmd([
+                b"LMPOP".to_vec(),
+                b"1".to_vec(),
+                key,
+                arg(3 + numkeys),
+                b"COUNT".to_vec(),
+                count.into_bytes(),
+            ])]
+        }
+        "bzpopmin" | "bzpopmax" => {
+            let items = items(reply);
+            match (
+                items.first().and_then(|f| bulk(f)),
+                items.get(1).and_then(|f| bulk(f)),
+            ) {
+                (Some(key), Some(member)) => vec![vec![b"ZREM".to_vec(), key, member]],
+                _ => vec![],
+            }
+        }
+        "bzmpop" => {
+            let items = items(reply);
+            let (Some(key), Some(elements)) = (items.first().and_then(|f| bulk(f)), items.get(1))
+            else {
+                return vec![];
+            };
+            let members = self::items(elements)
+                .iter()
+                .filter_map(|f| entry_id(f))
+                .collect::<Vec<_>>();
+            vec![cmd([b"ZREM".to_vec(), key].into_iter().chain(members))]
+        }
+        "xadd" => vec![xadd_with_id(argv, reply)],
+        "xreadgroup" => xreadgroup_state(backend, &argv, reply),
+        "xclaim" => {
+            // id 之后是可选的参数，第一个不是 ID 的参数之前都是 ID
+            let ids = argv[5..]
+                .iter()
+                .take_while(|id| StreamId::parse(id, 0).is_some())
+                .cloned()
+                .collect();
+            let mut ret = pending_state(backend, arg(1), arg(2), ids);
+            ret.extend(group_state(backend, arg(1), arg(2)));
+            ret
+        }
+        "xautoclaim" => {
+            let items = items(reply);
+            let ids = items
+                .iter()
+                .skip(1)
+                .flat_map(|f| self::items(f))
+                .filter_map(entry_id)
+                .collect();
+            pending_state(backend, arg(1), arg(2), ids)
+        }
+        _ => vec![argv],
+    }
+}
+
+fn cmd(args: impl IntoIterator<Item = Vec<u8>>) -> Argv {
+    args.into_iter().collect()
+}
+
+fn name_str(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).to_string()
+}
+
+fn parse_usize(buf: &[u8]) -> usize {
+    std::str::from_utf8(buf)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_default()
+}
+
+fn bulk(frame: &RespFrame) -> Option<Vec<u8>> {
+    match frame {
+        RespFrame::BulkString(s) => Some(s.0.clone()),
+        _ => None,
+    }
+}
+
+fn items(frame: &RespFrame) -> Vec<&RespFrame> {
+    match frame {
+        RespFrame::Array(array) => array.0.iter().collect(),
+        RespFrame::Set(set) => set.0.iter().collect(),
+        RespFrame::BulkString(_) => vec![frame],
+        _ => vec![],
+    }
+}
+
+/// stream entry 的 ID：JUSTID 时直接是 ID，否则是 [id, fields]；zset 的 [member, score] 取 member
+fn entry_id(frame: &RespFrame) -> Option<Vec<u8>> {
+    match frame {
+        RespFrame::Array(array) => array.0.first().and_then(bulk),
+        frame => bulk(frame),
+    }
+}
+
+/// 没有 DEL 命令，用已经过去的时间 PEXPIREAT 删除 key，加载时效果一样
+fn delete(key: Vec<u8>) -> Argv {
+    vec![b"PEXPIREAT".to_vec(), key, b"0".to_vec()]
+}
+
+/// key 当前的过期时间：PEXPIREAT 绝对时间、PERSIST，或者已经被删除
+fn expire_state(backend: &Backend, key: Vec<u8>) -> Argv {
+    if !backend.exists(&key) {
+        return delete(key);
+    }
+    match backend.get_expire(&key) {
+        Some(at) => vec![b"PEXPIREAT".to_vec(), key, at.to_string().into_bytes()],
+        None => vec![b"PERSIST".to_vec(), key],
+    }
+}
+
+/// 字符串当前的值和过期时间：SET key value [PXAT at]
+fn string_state(backend: &Backend, key: Vec<u8>) -> Vec<Argv> {
+    match backend.get(&key) {
+        Some(Value::String(value)) => {
+            let at = backend.get_expire(&key);
+            let mut argv = vec![b"SET".to_vec(), key, value];
+            if let Some(at) = at {
+                argv.extend([b"PXAT".to_vec(), at.to_string().into_bytes()]);
+            }
+            vec![argv]
+        }
+        Some(_) => vec![],
+        None => vec![delete(key)],
+    }
+}
+
+/// hash field 当前的值和过期时间：HSET key field value [HPEXPIREAT key at FIELDS 1 field]
+fn hash_field_state(backend: &Backend, key: Vec<u8>, field: Vec<u8>) -> Vec<Argv> {
+    let state = backend.read_value(&key, |hash: &Hash| {
+        hash.get(&field)
+            .map(|value| (value.to_vec(), hash.get_expire(&field)))
+    });
+    let Ok(Some(Some((value, at)))) = state else {
+        return vec![];
+    };
+    let mut ret = vec![vec![b"HSET".to_vec(), key.clone(), field.clone(), value]];
+    if let Some(at) = at {
+        ret.push(hpexpireat(key, at, vec![field]));
+    }
+    ret
+}
+
+fn hpexpireat(key: Vec<u8>, at: u64, fields: Vec<Vec<u8>>) -> Argv {
+    cmd([
+        b"HPEXPIREAT".to_vec(),
+        key,
+        at.to_string().into_bytes(),
+        b"FIELDS".to_vec(),
+        fields.len().to_string().into_bytes(),
+    ]
+    .into_iter()
+    .chain(fields))
+}
+
+/// HEXPIRE key time [NX|XX|GT|LT] FIELDS numfields field...：返回 1 的 field 设置了过期时间，
+/// 改写成 HPEXPIREAT 绝对时间；返回 2 的 field 因为时间已经过去被删除，改写成 HDEL
+fn hash_expire_state(backend: &Backend, argv: &Argv, reply: &RespFrame) -> Vec<Argv> {
+    let key = argv[1].clone();
+    let Some(pos) = argv.iter().position(|a| a.eq_ignore_ascii_case(b"FIELDS")) else {
+        return vec![];
+    };
+    let (mut updated, mut deleted) = (vec![], vec![]);
+    for (field, code) in argv[pos + 2..].iter().zip(items(reply)) {
+        match code {
+            RespFrame::Integer(1) => updated.push(field.clone()),
+            RespFrame::Integer(2) => deleted.push(field.clone()),
+            _ => {}
+        }
+    }
+    let mut ret = vec![];
+    let at = updated.first().and_then(|field| {
+        backend
+            .read_value(&key, |hash: &Hash| hash.get_expire(field))
+            .ok()
+            .flatten()
+            .flatten()
+    });
+    if let Some(at) = at {
+        ret.push(hpexpireat(key.clone(), at, updated));
+    }
+    if !deleted.is_empty() {
+        ret.push(cmd([b"HDEL".to_vec(), key].into_iter().chain(deleted)));
+    }
+    ret
+}
+
+/// XADD 的 ID 是 `*` 或者只指定了毫秒时，替换成实际生成的 ID
+fn xadd_with_id(mut argv: Argv, reply: &RespFrame) -> Argv {
+    let Some(id) = bulk(reply) else {
+        return argv;
+    };
+    let mut i = 2;
+    while i < argv.len() {
+        match String::from_utf8_lossy(&argv[i]).to_uppercase().as_str() {
+            "NOMKSTREAM" => i += 1,
+            "MAXLEN" | "MINID" => {
+                i += 1;
+                if matches!(argv.get(i).map(Vec::as_slice), Some(b"=" | b"~")) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            "LIMIT" => i += 2,
+            _ => break,
+        }
+    }
+    if let Some(arg) = argv.get_mut(i) {
+        *arg = id;
+    }
+    argv
+}
+
+/// XREADGROUP GROUP group consumer ... STREAMS key... id...：创建消费者，读到的 entry 按照 PEL
+/// 的状态改写成 XCLAIM，最后同步消费组最后分发的 ID
+fn xreadgroup_state(backend: &Backend, argv: &Argv, reply: &RespFrame) -> Vec<Argv> {
+    let Some(pos) = argv.iter().position(|a| a.eq_ignore_ascii_case(b"GROUP")) else {
+        return vec![];
+    };
+    let (group, consumer) = (argv[pos + 1].clone(), argv[pos + 2].clone());
+    let Some(streams) = argv.iter().position(|a| a.eq_ignore_ascii_case(b"STREAMS")) else {
+        return vec![];
+    };
+    let noack = argv[..streams]
+        .iter()
+        .any(|a| a.eq_ignore_ascii_case(b"NOACK"));
+    let keys = &argv[streams + 1..streams + 1 + (argv.len() - streams - 1) / 2];
+    let mut ret: Vec<Argv> = keys
+        .iter()
+        .map(|key| {
+            cmd([
+                b"XGROUP".to_vec(),
+                b"CREATECONSUMER".to_vec(),
+                key.clone(),
+                group.clone(),
+                consumer.clone(),
+            ])
+        })
+        .collect();
+    for (key, ids) in stream_entries(reply) {
+        if !noack {
+            ret.extend(pending_state(backend, key.clone(), group.clone(), ids));
+        }
+        ret.extend(group_state(backend, key, group.clone()));
+    }
+    ret
+}
+
+/// XREADGROUP 的返回值：RESP2 是 [[key, entries]...]，RESP3 是 key 到 entries 的 map
+fn stream_entries(reply: &RespFrame) -> Vec<(Vec<u8>, Vec<Vec<u8>>)> {
+    let ids = |entries: &RespFrame| items(entries).iter().filter_map(|f| entry_id(f)).collect();
+    match reply {
+        RespFrame::Map(map) => map
+            .0
+            .iter()
+            .map(|(key, entries)| (key.clone(), ids(entries)))
+            .collect(),
+        reply => items(reply)
+            .into_iter()
+            .filter_map(|stream| {
+                let parts = items(stream);
+                Some((bulk(parts.first()?)?, ids(parts.get(1)?)))
+            })
+            .collect(),
+    }
+}
+
+/// 按照 PEL 中的状态改写成 XCLAIM ... TIME RETRYCOUNT FORCE JUSTID，不在 PEL 中的 ID 改写成 XACK
+fn pending_state(backend: &Backend, key: Vec<u8>, group: Vec<u8>, ids: Argv) -> Vec<Argv> {
+    let group_name = name_str(&group);
+    let state = backend.read_value(&key, |stream: &Stream| {
+        let group = stream.group(&group_name)?;
+        Some(
+            ids.iter()
+                .filter_map(|id| StreamId::parse(id, 0))
+                .map(|id| (id, group.pending_entry(id).cloned()))
+                .collect::<Vec<_>>(),
+        )
+    });
+    let Ok(Some(Some(state))) = state else {
+        return vec![];
+    };
+    let mut acked = vec![];
+    let mut ret = vec![];
+    for (id, entry) in state {
+        match entry {
+            Some(entry) => ret.push(cmd([
+                b"XCLAIM".to_vec(),
+                key.clone(),
+                group.clone(),
+                entry.consumer.into_bytes(),
+                b"0".to_vec(),
+                id.to_string().into_bytes(),
+                b"TIME".to_vec(),
+                entry.delivery_time.to_string().into_bytes(),
+                b"RETRYCOUNT".to_vec(),
+                entry.delivery_count.to_string().into_bytes(),
+                b"FORCE".to_vec(),
+                b"JUSTID".to_vec(),
+            ])),
+            None => acked.push(id.to_string().into_bytes()),
+        }
+    }
+    if !acked.is_empty() {
+        ret.push(cmd([b"XACK".to_vec(), key, group].into_iter().chain(acked)));
+    }
+    ret
+}
+
+/// 消费组最后分发的 ID：XGROUP SETID key group id ENTRIESREAD n
+fn group_state(backend: &Backend, key: Vec<u8>, group: Vec<u8>) -> Vec<Argv> {
+    let group_name = name_str(&group);
+    let state = backend.read_value(&key, |stream: &Stream| {
+        stream
+            .group(&group_name)
+            .map(|g| (g.last_id(), g.entries_read()))
+    });
+    let Ok(Some(Some((last_id, entries_read)))) = state else {
+        return vec![];
+    };
+    let entries_read = entries_read.map_or(-1, |n| n as i64);
+    vec![cmd([
+        b"XGROUP".to_vec(),
+        b"SETID".to_vec(),
+        key,
+        group,
+        last_id.to_string().into_bytes(),
+        b"ENTRIESREAD".to_vec(),
+        entries_read.to_string().into_bytes(),
+    ])]
+}
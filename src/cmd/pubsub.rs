@@ -0,0 +1,506 @@
+use super::{validate_args, CommandError, CommandExecutor, MultiReplyExecutor, Session};
+use crate::{Backend, BulkString, MessageSender, RespArray, RespFrame, RespNull, RespPush};
+use std::collections::BTreeSet;
+
+/// 订阅的类型，channel 精确匹配，pattern 使用 glob 匹配，shard channel 是按照 hash slot 路由的 channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscribeKind {
+    Channel,
+    Pattern,
+    Shard,
+}
+
+/// SUBSCRIBE channel [channel ...]
+/// PSUBSCRIBE pattern [pattern ...]
+/// SSUBSCRIBE shardchannel [shardchannel ...]
+#[derive(Debug)]
+pub struct Subscribe {
+    kind: SubscribeKind,
+    channels: Vec<Vec<u8>>,
+}
+
+/// UNSUBSCRIBE [channel [channel ...]]
+/// PUNSUBSCRIBE [pattern [pattern ...]]
+/// SUNSUBSCRIBE [shardchannel [shardchannel ...]]
+#[derive(Debug)]
+pub struct Unsubscribe {
+    kind: SubscribeKind,
+    channels: Vec<Vec<u8>>,
+}
+
+/// PUBLISH channel message
+/// SPUBLISH shardchannel message
+#[derive(Debug)]
+pub struct Publish {
+    shard: bool,
+    channel: Vec<u8>,
+    message: Vec<u8>,
+}
+
+/// PUBSUB CHANNELS [pattern] | NUMSUB [channel ...] | NUMPAT
+///     | SHARDCHANNELS [pattern] | SHARDNUMSUB [shardchannel ...]
+#[derive(Debug)]
+pub enum PubSubQuery {
+    Channels(Option<Vec<u8>>),
+    NumSub(Vec<Vec<u8>>),
+    NumPat,
+    ShardChannels(Option<Vec<u8>>),
+    ShardNumSub(Vec<Vec<u8>>),
+}
+
+impl SubscribeKind {
+    fn subscribe_name(&self) -> &'static str {
+        match self {
+            SubscribeKind::Channel => "subscribe",
+            SubscribeKind::Pattern => "psubscribe",
+            SubscribeKind::Shard => "ssubscribe",
+        }
+    }
+
+    fn unsubscribe_name(&self) -> &'static str {
+        match self {
+            SubscribeKind::Channel => "unsubscribe",
+            SubscribeKind::Pattern => "punsubscribe",
+            SubscribeKind::Shard => "sunsubscribe",
+        }
+    }
+
+    /// 连接中记录的这一类订阅
+    fn subscribed<'a>(&self, session: &'a mut Session) -> &'a mut BTreeSet<Vec<u8>> {
+        match self {
+            SubscribeKind::Channel => &mut session.channels,
+            SubscribeKind::Pattern => &mut session.patterns,
+            SubscribeKind::Shard => &mut session.shard_channels,
+        }
+    }
+
+    /// 确认消息中返回的订阅数量，shard channel 单独计数
+    fn count(&self, session: &Session) -> usize {
+        match self {
+            SubscribeKind::Channel | SubscribeKind::Pattern => session.subscriptions(),
+            SubscribeKind::Shard => session.shard_subscriptions(),
+        }
+    }
+
+    fn subscribe(&self, backend: &Backend, channel: Vec<u8>, id: u64, tx: MessageSender) {
+        match self {
+            SubscribeKind::Channel => backend.pubsub.subscribe(channel, id, tx),
+            SubscribeKind::Pattern => backend.pubsub.psubscribe(channel, id, tx),
+            SubscribeKind::Shard => backend.pubsub.ssubscribe(channel, id, tx),
+        };
+    }
+
+    fn unsubscribe(&self, backend: &Backend, channel: &[u8], id: u64) {
+        match self {
+            SubscribeKind::Channel => backend.pubsub.unsubscribe(channel, id),
+            SubscribeKind::Pattern => backend.pubsub.punsubscribe(channel, id),
+            SubscribeKind::Shard => backend.pubsub.sunsubscribe(channel, id),
+        };
+    }
+}
+
+/// RESP2 的订阅模式下允许执行的命令
+const SUBSCRIBER_COMMANDS: [&str; 9] = [
+    "subscribe",
+    "psubscribe",
+    "ssubscribe",
+    "unsubscribe",
+    "punsubscribe",
+    "sunsubscribe",
+    "ping",
+    "quit",
+    "reset",
+];
+
+/// RESP2 的连接订阅之后只能接收 push 的消息，不能执行普通的命令。RESP3 可以区分 push 和响应，没有限制
+pub(crate) fn check_subscriber_mode(session: &Session, name: &str) -> Result<(), CommandError> {
+    if session.is_subscriber()
+        && session.protocol == crate::Protocol::Resp2
+        && !SUBSCRIBER_COMMANDS.contains(&name)
+    {
+        return Err(CommandError::InvalidArgument(format!(
+            "Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// 连接断开时取消所有的订阅
+pub fn unsubscribe_all(session: &mut Session, backend: &Backend) {
+    for kind in [
+        SubscribeKind::Channel,
+        SubscribeKind::Pattern,
+        SubscribeKind::Shard,
+    ] {
+        for channel in std::mem::take(kind.subscribed(session)) {
+            kind.unsubscribe(backend, &channel, session.id);
+        }
+    }
+}
+
+impl Subscribe {
+    pub(crate) fn parse(kind: SubscribeKind, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::WrongArity(kind.subscribe_name().to_string()));
+        }
+        Ok(Subscribe {
+            kind,
+            channels: args,
+        })
+    }
+}
+
+impl CommandExecutor for Subscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        // 订阅需要连接接收之后推送的消息
+        CommandError::InvalidArgument(format!(
+            "{} isn't allowed in this context",
+            self.kind.subscribe_name().to_uppercase()
+        ))
+        .into()
+    }
+}
+
+impl MultiReplyExecutor for Subscribe {
+    fn execute_multi(self, session: &mut Session, backend: &Backend) -> Vec<RespFrame> {
+        let tx = session.message_sender(backend);
+        let mut replies = Vec::with_capacity(self.channels.len());
+        for channel in self.channels {
+            if self.kind.subscribed(session).insert(channel.clone()) {
+                self.kind
+                    .subscribe(backend, channel.clone(), session.id, tx.clone());
+            }
+            replies.push(confirm(
+                self.kind.subscribe_name(),
+                BulkString::new(channel).into(),
+                self.kind.count(session),
+            ));
+        }
+        replies
+    }
+}
+
+impl Unsubscribe {
+    pub(crate) fn parse(kind: SubscribeKind, args: Vec<Vec<u8>>) -> Self {
+        Unsubscribe {
+            kind,
+            channels: args,
+        }
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        CommandError::InvalidArgument(format!(
+            "{} isn't allowed in this context",
+            self.kind.unsubscribe_name().to_uppercase()
+        ))
+        .into()
+    }
+}
+
+impl MultiReplyExecutor for Unsubscribe {
+    fn execute_multi(self, session: &mut Session, backend: &Backend) -> Vec<RespFrame> {
+        let name = self.kind.unsubscribe_name();
+        // 没有参数时取消这一类的所有订阅
+        let channels: Vec<Vec<u8>> = if self.channels.is_empty() {
+            self.kind.subscribed(session).iter().cloned().collect()
+        } else {
+            self.channels
+        };
+        if channels.is_empty() {
+            return vec![confirm(name, RespNull.into(), self.kind.count(session))];
+        }
+        let mut replies = Vec::with_capacity(channels.len());
+        for channel in channels {
+            if self.kind.subscribed(session).remove(&channel) {
+                self.kind.unsubscribe(backend, &channel, session.id);
+            }
+            replies.push(confirm(
+                name,
+                BulkString::new(channel).into(),
+                self.kind.count(session),
+            ));
+        }
+        replies
+    }
+}
+
+/// 订阅和取消订阅的确认消息，和订阅的消息一样使用 push 类型
+fn confirm(name: &str, channel: RespFrame, count: usize) -> RespFrame {
+    RespPush::new([BulkString::new(name).into(), channel, (count as i64).into()]).into()
+}
+
+impl Publish {
+    pub(crate) fn parse(name: &str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args(name, &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(Publish {
+            shard: name == "spublish",
+            channel: args.next().unwrap_or_default(),
+            message: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for Publish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let receivers = if self.shard {
+            backend.spublish(&self.channel, &self.message)
+        } else {
+            backend.publish(&self.channel, &self.message)
+        };
+        (receivers as i64).into()
+    }
+}
+
+impl PubSubQuery {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut args = args.into_iter();
+        let Some(sub) = args.next() else {
+            return Err(CommandError::WrongArity("pubsub".to_string()));
+        };
+        let sub = String::from_utf8_lossy(&sub).to_lowercase();
+        let args: Vec<Vec<u8>> = args.collect();
+        match sub.as_str() {
+            "channels" if args.len() <= 1 => Ok(PubSubQuery::Channels(args.into_iter().next())),
+            "numsub" => Ok(PubSubQuery::NumSub(args)),
+            "numpat" if args.is_empty() => Ok(PubSubQuery::NumPat),
+            "shardchannels" if args.len() <= 1 => {
+                Ok(PubSubQuery::ShardChannels(args.into_iter().next()))
+            }
+            "shardnumsub" => Ok(PubSubQuery::ShardNumSub(args)),
+            "channels" | "numpat" | "shardchannels" => {
+                Err(CommandError::WrongArity(format!("pubsub|{}", sub)))
+            }
+            _ => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'. Try PUBSUB HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+impl CommandExecutor for PubSubQuery {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self {
+            PubSubQuery::Channels(pattern) => {
+                channels_array(backend.pubsub.channels(pattern.as_deref()))
+            }
+            PubSubQuery::NumSub(channels) => {
+                numsub_array(channels, |channel| backend.pubsub.numsub(channel))
+            }
+            PubSubQuery::NumPat => (backend.pubsub.numpat() as i64).into(),
+            PubSubQuery::ShardChannels(pattern) => {
+                channels_array(backend.pubsub.shard_channels(pattern.as_deref()))
+            }
+            PubSubQuery::ShardNumSub(channels) => {
+                numsub_array(channels, |channel| backend.pubsub.shard_numsub(channel))
+            }
+        }
+    }
+}
+
+fn channels_array(channels: Vec<Vec<u8>>) -> RespFrame {
+    RespArray::new(
+        channels
+            .into_iter()
+            .map(|channel| BulkString::new(channel).into())
+            .collect::<Vec<_>>(),
+    )
+    .into()
+}
+
+/// [channel1, count1, channel2, count2, ...]
+fn numsub_array(channels: Vec<Vec<u8>>, numsub: impl Fn(&[u8]) -> usize) -> RespFrame {
+    RespArray::new(
+        channels
+            .into_iter()
+            .flat_map(|channel| {
+                let n = numsub(&channel) as i64;
+                [BulkString::new(channel).into(), n.into()]
+            })
+            .collect::<Vec<_>>(),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cmd::{tests::run, Command, Outcome},
+        Protocol, RespNullBulkString,
+    };
+    use anyhow::Result;
+
+    fn execute(session: &mut Session, backend: &Backend, args: &[&str]) -> Result<Vec<RespFrame>> {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        let cmd = Command::from_session(RespArray::new(frames).into(), session)?;
+        match cmd.execute_or_block(session, backend) {
+            Outcome::Reply(frame) => Ok(vec![frame]),
+            Outcome::Replies(frames) => Ok(frames),
+            Outcome::Block(..) | Outcome::Replicate(..) | Outcome::WaitAck(..) => {
+                panic!("pub/sub commands never block")
+            }
+        }
+    }
+
+    fn push(items: &[&str], count: Option<i64>) -> RespFrame {
+        let mut frames: Vec<RespFrame> = items
+            .iter()
+            .map(|item| BulkString::new(item.as_bytes()).into())
+            .collect();
+        if let Some(count) = count {
+            frames.push(count.into());
+        }
+        RespPush::new(frames).into()
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_publish() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(1);
+        assert_eq!(
+            execute(&mut session, &backend, &["SUBSCRIBE", "a", "b", "a"])?,
+            vec![
+                push(&["subscribe", "a"], Some(1)),
+                push(&["subscribe", "b"], Some(2)),
+                push(&["subscribe", "a"], Some(2)),
+            ]
+        );
+        assert_eq!(
+            execute(&mut session, &backend, &["PSUBSCRIBE", "h?llo"])?,
+            vec![push(&["psubscribe", "h?llo"], Some(3))]
+        );
+
+        assert_eq!(run(&backend, &["PUBLISH", "a", "1"])?, 1.into());
+        assert_eq!(run(&backend, &["PUBLISH", "hello", "2"])?, 1.into());
+        assert_eq!(run(&backend, &["PUBLISH", "c", "3"])?, 0.into());
+        assert_eq!(
+            session.next_message().await,
+            Some(push(&["message", "a", "1"], None))
+        );
+        assert_eq!(
+            session.next_message().await,
+            Some(push(&["pmessage", "h?llo", "hello", "2"], None))
+        );
+
+        assert_eq!(
+            run(&backend, &["PUBSUB", "CHANNELS"])?,
+            RespArray::new([b"a".into(), b"b".into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["PUBSUB", "NUMSUB", "a", "x"])?,
+            RespArray::new([b"a".into(), 1.into(), b"x".into(), 0.into()]).into()
+        );
+        assert_eq!(run(&backend, &["PUBSUB", "NUMPAT"])?, 1.into());
+
+        assert_eq!(
+            execute(&mut session, &backend, &["UNSUBSCRIBE"])?,
+            vec![
+                push(&["unsubscribe", "a"], Some(2)),
+                push(&["unsubscribe", "b"], Some(1)),
+            ]
+        );
+        assert_eq!(
+            execute(&mut session, &backend, &["PUNSUBSCRIBE"])?,
+            vec![push(&["punsubscribe", "h?llo"], Some(0))]
+        );
+        assert_eq!(
+            execute(&mut session, &backend, &["UNSUBSCRIBE"])?,
+            vec![RespPush::new([b"unsubscribe".into(), RespNull.into(), 0.into()]).into()]
+        );
+        assert_eq!(run(&backend, &["PUBLISH", "a", "1"])?, 0.into());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shard_subscribe() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(1);
+        execute(&mut session, &backend, &["SUBSCRIBE", "a"])?;
+        // shard channel 的订阅数量单独计算
+        assert_eq!(
+            execute(&mut session, &backend, &["SSUBSCRIBE", "a", "b"])?,
+            vec![
+                push(&["ssubscribe", "a"], Some(1)),
+                push(&["ssubscribe", "b"], Some(2)),
+            ]
+        );
+
+        assert_eq!(run(&backend, &["SPUBLISH", "a", "1"])?, 1.into());
+        assert_eq!(
+            session.next_message().await,
+            Some(push(&["smessage", "a", "1"], None))
+        );
+        assert_eq!(
+            run(&backend, &["PUBSUB", "SHARDCHANNELS", "b*"])?,
+            RespArray::new([b"b".into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["PUBSUB", "SHARDNUMSUB", "a", "c"])?,
+            RespArray::new([b"a".into(), 1.into(), b"c".into(), 0.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["PUBSUB", "CHANNELS"])?,
+            RespArray::new([b"a".into()]).into()
+        );
+
+        assert_eq!(
+            execute(&mut session, &backend, &["SUNSUBSCRIBE"])?,
+            vec![
+                push(&["sunsubscribe", "a"], Some(1)),
+                push(&["sunsubscribe", "b"], Some(0)),
+            ]
+        );
+        assert_eq!(run(&backend, &["SPUBLISH", "a", "2"])?, 0.into());
+        assert!(session.is_subscriber());
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscriber_mode() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(1);
+        execute(&mut session, &backend, &["SUBSCRIBE", "a"])?;
+        let ret = execute(&mut session, &backend, &["GET", "a"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>()?,
+            CommandError::InvalidArgument("Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context".to_string())
+        );
+
+        // RESP3 可以区分 push 和命令的响应，订阅之后仍然可以执行普通的命令
+        session.protocol = Protocol::Resp3;
+        assert_eq!(
+            execute(&mut session, &backend, &["GET", "a"])?,
+            vec![RespNullBulkString.into()]
+        );
+
+        session.protocol = Protocol::Resp2;
+        assert_eq!(
+            execute(&mut session, &backend, &["PING"])?,
+            vec![RespArray::new([b"pong".into(), b"".into()]).into()]
+        );
+
+        unsubscribe_all(&mut session, &backend);
+        assert!(!session.is_subscriber());
+        // 只订阅了 shard channel 时同样处于订阅模式
+        execute(&mut session, &backend, &["SSUBSCRIBE", "s"])?;
+        assert!(execute(&mut session, &backend, &["GET", "a"]).is_err());
+        unsubscribe_all(&mut session, &backend);
+        assert_eq!(
+            run(&backend, &["PUBSUB", "SHARDNUMSUB", "s"])?,
+            RespArray::new([b"s".into(), 0.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["PUBSUB", "NUMSUB", "a"])?,
+            RespArray::new([b"a".into(), 0.into()]).into()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,501 @@
+use super::{
+    parse_int, parse_string, validate_args, CommandError, CommandExecutor, Outcome, Session,
+};
+use crate::{
+    AckWait, AppendFsync, Backend, BulkString, LinkState, RespArray, RespFrame, SimpleError,
+    SimpleString,
+};
+use std::time::Duration;
+
+/// REPLICAOF host port | NO ONE，SLAVEOF 是同样的命令
+#[derive(Debug)]
+pub struct ReplicaOf {
+    master: Option<(String, u16)>,
+}
+
+/// REPLCONF option value [option value ...]，replica 握手时告知自己的端口和能力，之后定期发送 ACK
+#[derive(Debug)]
+pub struct Replconf {
+    options: Vec<(String, Vec<u8>)>,
+}
+
+/// PSYNC replid offset 或者 SYNC (replid 为 None)
+#[derive(Debug)]
+pub struct Psync {
+    request: Option<(String, i64)>,
+}
+
+/// ROLE
+#[derive(Debug)]
+pub struct Role;
+
+/// WAIT numreplicas timeout 或者 WAITAOF numlocal numreplicas timeout (numlocal 不为 None)
+#[derive(Debug)]
+pub struct Wait {
+    numlocal: Option<usize>,
+    numreplicas: usize,
+    // 毫秒，0 表示一直等待
+    timeout: u64,
+}
+
+/// 解析 replica 在复制连接上发送的 REPLCONF ACK offset [FACK aofoffset]，
+/// 返回确认的 offset 以及 AOF fsync 到的 offset
+pub fn parse_replconf_ack(frame: &RespFrame) -> Option<(u64, Option<u64>)> {
+    let RespFrame::Array(array) = frame else {
+        return None;
+    };
+    let arg = |i: usize| match array.0.get(i) {
+        Some(RespFrame::BulkString(s)) => Some(s.0.as_slice()),
+        _ => None,
+    };
+    let offset = |i: usize| std::str::from_utf8(arg(i)?).ok()?.parse().ok();
+    if !arg(0)?.eq_ignore_ascii_case(b"replconf") || !arg(1)?.eq_ignore_ascii_case(b"ack") {
+        return None;
+    }
+    let aof_offset = match arg(3) {
+        Some(fack) if fack.eq_ignore_ascii_case(b"fack") => offset(4),
+        _ => None,
+    };
+    Some((offset(2)?, aof_offset))
+}
+
+impl ReplicaOf {
+    pub(crate) fn parse(name: &str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args(name, &args, 2)?;
+        if args[0].eq_ignore_ascii_case(b"no") && args[1].eq_ignore_ascii_case(b"one") {
+            return Ok(ReplicaOf { master: None });
+        }
+        let mut args = args.into_iter();
+        let host = parse_string(args.next().unwrap_or_default())?;
+        let port = args
+            .next()
+            .and_then(|port| u16::try_from(parse_int(&port).ok()?).ok())
+            .ok_or_else(|| CommandError::InvalidArgument("Invalid master port".to_string()))?;
+        Ok(ReplicaOf {
+            master: Some((host, port)),
+        })
+    }
+}
+
+impl CommandExecutor for ReplicaOf {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if backend.cluster_enabled() {
+            return CommandError::InvalidArgument(
+                "REPLICAOF not allowed in cluster mode.".to_string(),
+            )
+            .into();
+        }
+        let replica = self.master.is_some();
+        match backend.set_master(self.master) {
+            false if replica => {
+                SimpleString::new("OK Already connected to specified master").into()
+            }
+            _ => SimpleString::new("OK").into(),
+        }
+    }
+}
+
+impl Replconf {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if !args.len().is_multiple_of(2) {
+            return Err(CommandError::Syntax);
+        }
+        let mut options = Vec::new();
+        let mut args = args.into_iter();
+        while let (Some(option), Some(value)) = (args.next(), args.next()) {
+            options.push((parse_string(option)?.to_lowercase(), value));
+        }
+        Ok(Replconf { options })
+    }
+
+    /// ACK 和 GETACK 只在复制连接上有意义，不返回任何响应
+    pub(crate) fn execute_replconf(self, session: &mut Session) -> Outcome {
+        for (option, value) in self.options {
+            match option.as_str() {
+                "listening-port" => match parse_int(&value).ok().and_then(|p| p.try_into().ok()) {
+                    Some(port) => session.replica_port = Some(port),
+                    None => return Outcome::Reply(CommandError::NotInteger.into()),
+                },
+                "ack" | "getack" => return Outcome::Replies(vec![]),
+                "capa" | "ip-address" | "rdb-only" | "rdb-filter-only" => {}
+                _ => {
+                    return Outcome::Reply(
+                        CommandError::InvalidArgument(format!(
+                            "Unrecognized REPLCONF option: {}",
+                            option
+                        ))
+                        .into(),
+                    )
+                }
+            }
+        }
+        Outcome::Reply(SimpleString::new("OK").into())
+    }
+}
+
+impl CommandExecutor for Replconf {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.execute_replconf(&mut Session::default()) {
+            Outcome::Reply(frame) => frame,
+            _ => RespArray::new(vec![]).into(),
+        }
+    }
+}
+
+impl Psync {
+    pub(crate) fn parse_psync(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("psync", &args, 2)?;
+        let offset = parse_int(&args[1])?;
+        let replid = parse_string(args.into_iter().next().unwrap_or_default())?;
+        Ok(Psync {
+            request: Some((replid, offset)),
+        })
+    }
+
+    pub(crate) fn parse_sync(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("sync", &args, 0)?;
+        Ok(Psync { request: None })
+    }
+
+    /// 连接变成复制连接，网络层先发送全量同步的快照或者 backlog 中的数据，之后持续发送命令流
+    pub(crate) fn execute_psync(self, session: &mut Session, backend: &Backend) -> Outcome {
+        // replica 还没有和自己的 master 同步完成时，数据是不完整的
+        let status = backend.replication_status();
+        if status.master.is_some() && status.link != LinkState::Connected {
+            return Outcome::Reply(
+                SimpleError::new("NOMASTERLINK Can't SYNC while not connected with my master")
+                    .into(),
+            );
+        }
+        let request = self
+            .request
+            .as_ref()
+            .map(|(replid, offset)| (replid.as_str(), *offset));
+        let feed = backend.psync(
+            session.id,
+            session.addr.clone().unwrap_or_default(),
+            session.replica_port.unwrap_or_default(),
+            request,
+        );
+        Outcome::Replicate(feed)
+    }
+}
+
+impl CommandExecutor for Psync {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        CommandError::InvalidArgument("Replica can't be used in this context".to_string()).into()
+    }
+}
+
+impl Wait {
+    pub(crate) fn parse_wait(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("wait", &args, 2)?;
+        Ok(Wait {
+            numlocal: None,
+            numreplicas: parse_count(&args[0])?,
+            timeout: parse_wait_timeout(&args[1])?,
+        })
+    }
+
+    pub(crate) fn parse_waitaof(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("waitaof", &args, 3)?;
+        Ok(Wait {
+            numlocal: Some(parse_count(&args[0])?),
+            numreplicas: parse_count(&args[1])?,
+            timeout: parse_wait_timeout(&args[2])?,
+        })
+    }
+
+    /// 已经满足条件时直接返回，否则让 replica 立即发送 ACK，之后由网络层等待
+    pub(crate) fn execute_wait(self, session: &mut Session, backend: &Backend) -> Outcome {
+        let name = if self.numlocal.is_some() {
+            "WAITAOF"
+        } else {
+            "WAIT"
+        };
+        if backend.is_replica() {
+            return Outcome::Reply(
+                CommandError::InvalidArgument(format!(
+                    "{} cannot be used with replica instances. Please also note that writes to replicas are just local and are not propagated.",
+                    name
+                ))
+                .into(),
+            );
+        }
+        if self.numlocal.is_some_and(|n| n > 0) && !backend.aof_config().enabled {
+            return Outcome::Reply(
+                CommandError::InvalidArgument(
+                    "WAITAOF cannot be used when numlocal is set but appendonly is disabled."
+                        .to_string(),
+                )
+                .into(),
+            );
+        }
+        // appendfsync no 时不会主动 fsync，本地的写入永远不会被确认
+        if self.numlocal.is_some_and(|n| n > 0) && backend.aof_config().fsync == AppendFsync::No {
+            return Outcome::Reply(
+                CommandError::InvalidArgument(
+                    "WAITAOF cannot be used when numlocal is set but appendfsync is no."
+                        .to_string(),
+                )
+                .into(),
+            );
+        }
+        let wait = AckWait {
+            offset: session.woff,
+            numreplicas: self.numreplicas,
+            numlocal: self.numlocal,
+            timeout: (self.timeout > 0).then(|| Duration::from_millis(self.timeout)),
+        };
+        if backend.ack_satisfied(&wait) {
+            return Outcome::Reply(backend.ack_reply(&wait));
+        }
+        backend.request_acks();
+        Outcome::WaitAck(wait)
+    }
+}
+
+impl CommandExecutor for Wait {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.execute_wait(&mut Session::default(), backend) {
+            Outcome::Reply(frame) => frame,
+            Outcome::WaitAck(wait) => backend.ack_reply(&wait),
+            _ => RespArray::new(vec![]).into(),
+        }
+    }
+}
+
+fn parse_count(arg: &[u8]) -> Result<usize, CommandError> {
+    usize::try_from(parse_int(arg)?).map_err(|_| CommandError::NotInteger)
+}
+
+fn parse_wait_timeout(arg: &[u8]) -> Result<u64, CommandError> {
+    u64::try_from(parse_int(arg)?)
+        .map_err(|_| CommandError::InvalidArgument("timeout is negative".to_string()))
+}
+
+impl Role {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("role", &args, 0)?;
+        Ok(Role)
+    }
+}
+
+impl CommandExecutor for Role {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let status = backend.replication_status();
+        let frames = match status.master {
+            Some((host, port)) => vec![
+                BulkString::new("slave").into(),
+                BulkString::new(host).into(),
+                (port as i64).into(),
+                BulkString::new(status.link.as_str()).into(),
+                (status.offset as i64).into(),
+            ],
+            None => vec![
+                BulkString::new("master").into(),
+                (status.offset as i64).into(),
+                RespArray::new(
+                    status
+                        .replicas
+                        .into_iter()
+                        .map(|replica| {
+                            RespArray::new(vec![
+                                BulkString::new(replica.addr).into(),
+                                BulkString::new(replica.port.to_string()).into(),
+                                BulkString::new(replica.ack_offset.to_string()).into(),
+                            ])
+                            .into()
+                        })
+                        .collect::<Vec<RespFrame>>(),
+                )
+                .into(),
+            ],
+        };
+        RespArray::new(frames).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cmd::{tests::run, Command},
+        AofConfig, RespFrame,
+    };
+    use anyhow::Result;
+
+    fn call(backend: &Backend, session: &mut Session, args: &[&str]) -> Outcome {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        let cmd = Command::from_session(RespArray::new(frames).into(), session).unwrap();
+        cmd.execute_or_block(session, backend)
+    }
+
+    #[test]
+    fn test_psync_and_role() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(7).with_addr(Some("127.0.0.1".to_string()));
+        let session = &mut session;
+        assert!(matches!(
+            call(
+                &backend,
+                session,
+                &["REPLCONF", "listening-port", "6380", "capa", "psync2"]
+            ),
+            Outcome::Reply(RespFrame::SimpleString(_))
+        ));
+        let Outcome::Replicate(mut feed) = call(&backend, session, &["PSYNC", "?", "-1"]) else {
+            panic!("expected replication");
+        };
+        assert!(feed.preamble.starts_with(b"+FULLRESYNC "));
+
+        // 写命令传播给 replica
+        call(&backend, &mut Session::new(8), &["SET", "a", "1"]);
+        backend.propagate_pending().unwrap();
+        assert_eq!(
+            feed.rx.try_recv()?,
+            &b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n"[..]
+        );
+        let ack: RespFrame =
+            RespArray::new(vec![b"REPLCONF".into(), b"ACK".into(), b"27".into()]).into();
+        assert_eq!(parse_replconf_ack(&ack), Some((27, None)));
+        backend.replica_ack(feed.id, 27, None);
+
+        let role = RespArray::new(vec![
+            BulkString::new("master").into(),
+            27.into(),
+            RespArray::new(vec![RespArray::new(vec![
+                BulkString::new("127.0.0.1").into(),
+                BulkString::new("6380").into(),
+                BulkString::new("27").into(),
+            ])
+            .into()])
+            .into(),
+        ]);
+        assert_eq!(run(&backend, &["ROLE"])?, role.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replicaof() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, &["REPLICAOF", "127.0.0.1", "7000"])?,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(
+            run(&backend, &["SLAVEOF", "127.0.0.1", "7000"])?,
+            SimpleString::new("OK Already connected to specified master").into()
+        );
+        assert!(run(&backend, &["REPLICAOF", "127.0.0.1", "port"]).is_err());
+        assert_eq!(
+            run(&backend, &["ROLE"])?,
+            RespArray::new(vec![
+                BulkString::new("slave").into(),
+                BulkString::new("127.0.0.1").into(),
+                7000.into(),
+                BulkString::new("connect").into(),
+                0.into(),
+            ])
+            .into()
+        );
+
+        // replica 只读，还没有同步完成时不能被其他 replica 同步
+        let mut session = Session::new(1);
+        assert_eq!(
+            match call(&backend, &mut session, &["SET", "a", "1"]) {
+                Outcome::Reply(frame) => frame,
+                _ => panic!("expected reply"),
+            },
+            SimpleError::new("READONLY You can't write against a read only replica.").into()
+        );
+        assert!(matches!(
+            call(&backend, &mut session, &["SYNC"]),
+            Outcome::Reply(RespFrame::Error(_))
+        ));
+
+        assert_eq!(
+            run(&backend, &["REPLICAOF", "NO", "ONE"])?,
+            SimpleString::new("OK").into()
+        );
+        assert!(!backend.is_replica());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(1);
+        assert_eq!(run(&backend, &["WAIT", "0", "0"])?, 0.into());
+        assert!(run(&backend, &["WAIT", "1", "-1"]).is_err());
+        assert_eq!(
+            run(&backend, &["WAITAOF", "1", "0", "0"])?,
+            SimpleError::new(
+                "ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled."
+            )
+            .into()
+        );
+        backend.configure_aof(AofConfig {
+            enabled: true,
+            fsync: AppendFsync::No,
+            ..Default::default()
+        });
+        assert_eq!(
+            run(&backend, &["WAITAOF", "1", "0", "0"])?,
+            SimpleError::new(
+                "ERR WAITAOF cannot be used when numlocal is set but appendfsync is no."
+            )
+            .into()
+        );
+        backend.configure_aof(AofConfig::default());
+
+        // 连接的 replica 确认之后返回
+        let mut feed = backend.psync(2, "127.0.0.1".to_string(), 6380, Some(("?", -1)));
+        call(&backend, &mut session, &["SET", "a", "1"]);
+        backend.propagate_pending().unwrap();
+        session.woff = backend.replication_offset();
+        let Outcome::WaitAck(wait) = call(&backend, &mut session, &["WAIT", "1", "0"]) else {
+            panic!("expected waiting");
+        };
+        // SET 和 GETACK
+        assert!(feed.rx.try_recv().is_ok());
+        assert_eq!(
+            feed.rx.try_recv()?,
+            &b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n"[..]
+        );
+        let waiting = tokio::spawn({
+            let backend = backend.clone();
+            async move { backend.wait_ack(wait).await }
+        });
+        backend.replica_ack(2, session.woff, None);
+        assert_eq!(waiting.await?, 1.into());
+
+        // WAITAOF 统计 replica 的 FACK，超时返回当前的数量
+        let Outcome::WaitAck(wait) = call(&backend, &mut session, &["WAITAOF", "0", "1", "10"])
+        else {
+            panic!("expected waiting");
+        };
+        assert_eq!(
+            backend.wait_ack(wait).await,
+            RespArray::new(vec![0.into(), 0.into()]).into()
+        );
+        backend.replica_ack(2, session.woff, Some(session.woff));
+        assert_eq!(
+            match call(&backend, &mut session, &["WAITAOF", "0", "1", "10"]) {
+                Outcome::Reply(frame) => frame,
+                _ => panic!("expected reply"),
+            },
+            RespArray::new(vec![0.into(), 1.into()]).into()
+        );
+
+        backend.set_master(Some(("127.0.0.1".to_string(), 7000)));
+        assert!(matches!(
+            call(&backend, &mut session, &["WAIT", "0", "0"]),
+            Outcome::Reply(RespFrame::Error(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,761 @@
+use super::{
+    command_name, is_write_command, parse_int, parse_string, propagation_argv, Command,
+    CommandError, CommandExecutor, Outcome, Session,
+};
+use crate::{
+    sha1_hex, Backend, BulkString, Protocol, RespArray, RespFrame, RespNullArray,
+    RespNullBulkString, ScriptRun, SimpleError, SimpleString,
+};
+use mlua::{Function, HookTriggers, Lua, LuaOptions, StdLib, Table, Value, Variadic};
+use std::sync::Arc;
+
+/// EVAL script numkeys [key [key ...]] [arg [arg ...]]
+/// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
+#[derive(Debug)]
+pub struct Eval {
+    script: ScriptSource,
+    keys: Vec<Vec<u8>>,
+    args: Vec<Vec<u8>>,
+}
+
+#[derive(Debug)]
+enum ScriptSource {
+    Body(String),
+    Sha(String),
+}
+
+/// SCRIPT LOAD script | EXISTS sha1 [sha1 ...] | FLUSH [ASYNC|SYNC] | KILL
+#[derive(Debug)]
+pub enum Script {
+    Load(String),
+    Exists(Vec<String>),
+    Flush,
+    Kill,
+}
+
+/// 脚本执行失败的原因，分别对应 Redis 返回的不同格式的错误
+#[derive(Debug)]
+pub(crate) enum ScriptError {
+    // 脚本有语法错误
+    Compile(String),
+    // 脚本运行时出错，例如调用了不存在的函数
+    Runtime(String),
+    // redis.call 执行的命令返回了错误，或者脚本 error() 了一个 {err = ...} table，原样返回
+    Reply(String),
+}
+
+/// 脚本中不能执行的命令：事务、订阅以及嵌套执行脚本
+const SCRIPT_DENIED_COMMANDS: [&str; 29] = [
+    "multi",
+    "exec",
+    "discard",
+    "watch",
+    "unwatch",
+    "subscribe",
+    "psubscribe",
+    "ssubscribe",
+    "unsubscribe",
+    "punsubscribe",
+    "sunsubscribe",
+    "eval",
+    "evalsha",
+    "script",
+    "function",
+    "fcall",
+    "fcall_ro",
+    "save",
+    "bgsave",
+    "bgrewriteaof",
+    "replicaof",
+    "slaveof",
+    "replconf",
+    "psync",
+    "sync",
+    "wait",
+    "waitaof",
+    "cluster",
+    "asking",
+];
+
+/// 用 Lua 实现的 redis 库函数。redis.call 在命令出错时把 {err = ...} 作为 Lua 错误抛出，
+/// 脚本没有捕获时这个错误会原样返回给客户端
+const PRELUDE: &str = r#"
+redis.call = function(...)
+    local reply = redis.pcall(...)
+    if type(reply) == 'table' and reply.err then
+        error(reply)
+    end
+    return reply
+end
+redis.error_reply = function(msg)
+    return { err = msg }
+end
+redis.status_reply = function(msg)
+    return { ok = msg }
+end
+redis.LOG_DEBUG = 0
+redis.LOG_VERBOSE = 1
+redis.LOG_NOTICE = 2
+redis.LOG_WARNING = 3
+redis.log = function(level, ...) end
+"#;
+
+/// 虚拟机在脚本之间共享，脚本不能创建全局变量，避免影响之后执行的脚本
+const PROTECT_GLOBALS: &str = r#"
+setmetatable(_G, {
+    __newindex = function(_, name)
+        error("Script attempted to create global variable '" .. tostring(name) .. "'", 2)
+    end,
+})
+"#;
+
+/// 编译好的脚本保存在虚拟机 registry 的这个 table 中：sha1 -> function
+const SCRIPTS_REGISTRY: &str = "scripts";
+
+// 和 Redis 一样，每执行这么多条指令检查一次脚本是否被终止
+const HOOK_INSTRUCTIONS: u32 = 100_000;
+
+impl Eval {
+    pub(crate) fn parse(name: &str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let script = parse_string(args.next().unwrap_or_default())?;
+        let script = match name {
+            "evalsha" => ScriptSource::Sha(script),
+            _ => ScriptSource::Body(script),
+        };
+        let (keys, args) = parse_keys_and_args(args)?;
+        Ok(Eval { script, keys, args })
+    }
+}
+
+/// 脚本的 KEYS 和 ARGV
+type KeysAndArgs = (Vec<Vec<u8>>, Vec<Vec<u8>>);
+
+/// numkeys [key ...] [arg ...]，EVAL 和 FCALL 共用
+pub(crate) fn parse_keys_and_args(
+    mut args: impl Iterator<Item = Vec<u8>>,
+) -> Result<KeysAndArgs, CommandError> {
+    let numkeys = parse_int(&args.next().unwrap_or_default())?;
+    if numkeys < 0 {
+        return Err(CommandError::InvalidArgument(
+            "Number of keys can't be negative".to_string(),
+        ));
+    }
+    let mut rest: Vec<Vec<u8>> = args.collect();
+    if numkeys as usize > rest.len() {
+        return Err(CommandError::InvalidArgument(
+            "Number of keys can't be greater than number of args".to_string(),
+        ));
+    }
+    let args = rest.split_off(numkeys as usize);
+    Ok((rest, args))
+}
+
+impl CommandExecutor for Eval {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.script {
+            ScriptSource::Body(body) => {
+                let sha = sha1_hex(body.as_bytes());
+                let ret = run_script(backend, &sha, Some(&body), self.keys, self.args);
+                // 和 Redis 一样，EVAL 执行过的脚本之后可以通过 EVALSHA 执行
+                backend.scripts.insert(body);
+                ret
+            }
+            ScriptSource::Sha(sha) => {
+                run_script(backend, &sha.to_lowercase(), None, self.keys, self.args)
+            }
+        }
+    }
+}
+
+impl Script {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut args = args.into_iter();
+        let Some(sub) = args.next() else {
+            return Err(CommandError::WrongArity("script".to_string()));
+        };
+        let sub = String::from_utf8_lossy(&sub).to_lowercase();
+        let args: Vec<Vec<u8>> = args.collect();
+        match sub.as_str() {
+            "load" if args.len() == 1 => Ok(Script::Load(parse_string(
+                args.into_iter().next().unwrap_or_default(),
+            )?)),
+            "exists" if !args.is_empty() => Ok(Script::Exists(
+                args.into_iter()
+                    .map(parse_string)
+                    .collect::<Result<_, _>>()?,
+            )),
+            "flush" if args.len() <= 1 => {
+                // ASYNC 和 SYNC 都是同步清空
+                if let Some(mode) = args.first() {
+                    let mode = String::from_utf8_lossy(mode).to_lowercase();
+                    if mode != "async" && mode != "sync" {
+                        return Err(CommandError::Syntax);
+                    }
+                }
+                Ok(Script::Flush)
+            }
+            "kill" if args.is_empty() => Ok(Script::Kill),
+            "load" | "exists" | "flush" | "kill" => {
+                Err(CommandError::WrongArity(format!("script|{}", sub)))
+            }
+            _ => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'. Try SCRIPT HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+impl CommandExecutor for Script {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self {
+            Script::Load(body) => {
+                // 只编译不执行，编译好的脚本缓存在虚拟机中
+                let sha = sha1_hex(body.as_bytes());
+                let ret = with_lua(backend, |lua| {
+                    load_script(lua, &sha, || Some(body.clone())).map(|_| ())
+                });
+                if let Err(e) = ret.map_err(runtime_error).and_then(|ret| ret) {
+                    return script_error(e, &sha);
+                }
+                BulkString::new(backend.scripts.insert(body)).into()
+            }
+            Script::Exists(shas) => RespArray::new(
+                shas.iter()
+                    .map(|sha| (backend.scripts.contains(sha) as i64).into())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Script::Flush => {
+                backend.scripts.clear();
+                // 编译好的脚本也一起丢弃
+                if let Some(lua) = backend.lua.lock().as_ref() {
+                    if let Err(e) = lua
+                        .create_table()
+                        .and_then(|scripts| lua.set_named_registry_value(SCRIPTS_REGISTRY, scripts))
+                    {
+                        return SimpleError::new(format!("ERR {}", lua_error_message(&e))).into();
+                    }
+                }
+                SimpleString::new("OK").into()
+            }
+            // 不需要执行锁，在脚本执行期间由其他连接发送
+            Script::Kill => match backend.lua.run().kill(false) {
+                Ok(()) => SimpleString::new("OK").into(),
+                Err(e) => SimpleError::new(e.to_string()).into(),
+            },
+        }
+    }
+}
+
+/// 在共享的 Lua 虚拟机中执行脚本，返回转换成 RESP 的结果。body 为 None 时 (EVALSHA)
+/// 执行已经加载的脚本
+///
+/// 调用方持有执行锁，脚本中通过 redis.call 执行的命令和脚本一起原子地执行
+fn run_script(
+    backend: &Backend,
+    sha: &str,
+    body: Option<&str>,
+    keys: Vec<Vec<u8>>,
+    args: Vec<Vec<u8>>,
+) -> RespFrame {
+    match eval_lua(backend, sha, body, keys, args) {
+        Ok(frame) => frame,
+        Err(e) => script_error(e, sha),
+    }
+}
+
+/// name 是脚本的 sha1 或者函数名
+pub(crate) fn script_error(e: ScriptError, name: &str) -> RespFrame {
+    let msg = match e {
+        ScriptError::Compile(msg) => format!(
+            "ERR Error compiling script (new function): {} script: {}",
+            msg, name
+        ),
+        ScriptError::Runtime(msg) => format!("ERR {} script: {}", msg, name),
+        ScriptError::Reply(msg) => msg,
+    };
+    SimpleError::new(msg).into()
+}
+
+/// 只加载 redis 库需要的标准库，不提供 io/os 等访问外部环境的库
+fn new_lua() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+        LuaOptions::default(),
+    )?;
+    let redis = lua.create_table()?;
+    redis.set(
+        "sha1hex",
+        lua.create_function(|_, s: mlua::String| Ok(sha1_hex(s.as_bytes())))?,
+    )?;
+    lua.globals().set("redis", redis)?;
+    lua.load(PRELUDE).set_name("@redis").exec()?;
+    Ok(lua)
+}
+
+/// 创建脚本和函数共用的虚拟机，每执行一定数量的指令检查一次脚本是否被终止
+fn new_vm(run: Arc<ScriptRun>) -> mlua::Result<Lua> {
+    let lua = new_lua()?;
+    lua.load(PROTECT_GLOBALS).set_name("@redis").exec()?;
+    lua.set_named_registry_value(SCRIPTS_REGISTRY, lua.create_table()?)?;
+    let triggers = HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS);
+    lua.set_hook(triggers, move |_, _| match run.is_killed() {
+        true => Err(mlua::Error::RuntimeError(
+            "Script killed by user with SCRIPT KILL...".to_string(),
+        )),
+        false => Ok(()),
+    });
+    Ok(lua)
+}
+
+/// 在共享的虚拟机中执行 f，虚拟机在第一次使用时创建，创建失败时返回错误
+pub(crate) fn with_lua<R>(backend: &Backend, f: impl FnOnce(&Lua) -> R) -> mlua::Result<R> {
+    let mut vm = backend.lua.lock();
+    let lua = match vm.take() {
+        Some(lua) => lua,
+        None => new_vm(backend.lua.run().clone())?,
+    };
+    Ok(f(vm.insert(lua)))
+}
+
+/// 返回缓存的编译好的脚本，没有缓存时编译 body 返回的代码并缓存。body 返回 None 表示脚本不存在
+fn load_script<'lua>(
+    lua: &'lua Lua,
+    sha: &str,
+    body: impl FnOnce() -> Option<String>,
+) -> Result<Function<'lua>, ScriptError> {
+    let runtime = |e: mlua::Error| ScriptError::Runtime(lua_error_message(&e));
+    let scripts: Table = lua
+        .named_registry_value(SCRIPTS_REGISTRY)
+        .map_err(runtime)?;
+    if let Some(func) = scripts
+        .raw_get::<_, Option<Function>>(sha)
+        .map_err(runtime)?
+    {
+        return Ok(func);
+    }
+    let Some(body) = body() else {
+        return Err(ScriptError::Reply(
+            "NOSCRIPT No matching script. Please use EVAL.".to_string(),
+        ));
+    };
+    let func = lua
+        .load(&body)
+        .set_name("@user_script")
+        .into_function()
+        .map_err(|e| ScriptError::Compile(lua_error_message(&e)))?;
+    scripts.raw_set(sha, func.clone()).map_err(runtime)?;
+    Ok(func)
+}
+
+fn eval_lua(
+    backend: &Backend,
+    sha: &str,
+    body: Option<&str>,
+    keys: Vec<Vec<u8>>,
+    args: Vec<Vec<u8>>,
+) -> Result<RespFrame, ScriptError> {
+    let runtime = |e: mlua::Error| ScriptError::Runtime(lua_error_message(&e));
+    with_lua(backend, |lua| {
+        let func = load_script(lua, sha, || match body {
+            Some(body) => Some(body.to_string()),
+            None => backend.scripts.get(sha),
+        })?;
+        // 全局变量是只读的，KEYS 和 ARGV 绕过 metatable 设置
+        let globals = lua.globals();
+        globals
+            .raw_set("KEYS", string_table(lua, &keys).map_err(runtime)?)
+            .map_err(runtime)?;
+        globals
+            .raw_set("ARGV", string_table(lua, &args).map_err(runtime)?)
+            .map_err(runtime)?;
+        call_with_redis(lua, backend, func, Variadic::new(), false, false)
+    })
+    .map_err(runtime)?
+}
+
+/// 在 scope 中注册访问 backend 的 redis.pcall，然后在保护模式下调用 func。
+/// read_only 为 true 时脚本中不能执行写命令，function 表示 FCALL 调用的函数
+pub(crate) fn call_with_redis<'lua>(
+    lua: &'lua Lua,
+    backend: &Backend,
+    func: Function<'lua>,
+    args: Variadic<Value<'lua>>,
+    read_only: bool,
+    function: bool,
+) -> Result<RespFrame, ScriptError> {
+    let runtime = |e: mlua::Error| ScriptError::Runtime(lua_error_message(&e));
+    // 执行期间其他连接可以通过 SCRIPT KILL/FUNCTION KILL 终止脚本
+    let run = backend.lua.run();
+    run.start(function);
+    let ret = lua.scope(|scope| {
+        // 脚本使用单独的连接状态执行命令，对应 Redis 的 lua_client
+        let mut session = Session::default();
+        let pcall = scope.create_function_mut(move |lua, args: Variadic<Value>| {
+            let frame = match command_frame(args) {
+                Ok(frame) => execute_command(frame, &mut session, backend, read_only),
+                Err(e) => e.into(),
+            };
+            frame_to_lua(lua, frame.into_protocol(Protocol::Resp2))
+        })?;
+        let redis: Table = lua.globals().get("redis")?;
+        redis.set("pcall", pcall)?;
+        let protected: Function = lua.globals().get("pcall")?;
+        let (ok, ret): (bool, Value) = protected.call((func, args))?;
+        Ok(if ok {
+            Ok(lua_to_frame(ret))
+        } else {
+            Err(error_value(ret))
+        })
+    });
+    run.finish();
+    ret.map_err(runtime)?
+}
+
+/// 脚本抛出的错误：{err = ...} 原样返回，其他的错误作为运行时错误
+fn error_value(value: Value) -> ScriptError {
+    match value {
+        Value::Table(table) => match table.raw_get::<_, Value>("err") {
+            Ok(Value::String(err)) => ScriptError::Reply(err.to_string_lossy().to_string()),
+            _ => ScriptError::Runtime("unknown error".to_string()),
+        },
+        Value::String(msg) => ScriptError::Runtime(msg.to_string_lossy().to_string()),
+        Value::Error(e) => ScriptError::Runtime(lua_error_message(&e)),
+        _ => ScriptError::Runtime("unknown error".to_string()),
+    }
+}
+
+pub(crate) fn runtime_error(e: mlua::Error) -> ScriptError {
+    ScriptError::Runtime(lua_error_message(&e))
+}
+
+/// mlua 的错误信息带有调用栈等内容，只保留第一行
+pub(crate) fn lua_error_message(e: &mlua::Error) -> String {
+    let msg = match e {
+        mlua::Error::SyntaxError { message, .. } => message.clone(),
+        mlua::Error::RuntimeError(message) => message.clone(),
+        mlua::Error::CallbackError { cause, .. } => return lua_error_message(cause),
+        e => e.to_string(),
+    };
+    msg.lines().next().unwrap_or_default().to_string()
+}
+
+pub(crate) fn string_table<'lua>(lua: &'lua Lua, items: &[Vec<u8>]) -> mlua::Result<Table<'lua>> {
+    let items = items
+        .iter()
+        .map(|item| lua.create_string(item))
+        .collect::<mlua::Result<Vec<_>>>()?;
+    lua.create_sequence_from(items)
+}
+
+/// redis.call 的参数转换成命令，只接受字符串和数字
+fn command_frame(args: Variadic<Value>) -> Result<RespArray, CommandError> {
+    if args.is_empty() {
+        return Err(CommandError::InvalidArgument(
+            "Please specify at least one argument for this redis lib call".to_string(),
+        ));
+    }
+    let frames = args
+        .iter()
+        .map(|arg| match arg {
+            Value::String(s) => Ok(BulkString::new(s.as_bytes()).into()),
+            Value::Integer(i) => Ok(BulkString::new(i.to_string()).into()),
+            Value::Number(n) => Ok(BulkString::new(format_lua_number(*n)).into()),
+            _ => Err(CommandError::InvalidArgument(
+                "Lua redis lib command arguments must be strings or integers".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<RespFrame>, _>>()?;
+    Ok(RespArray::new(frames))
+}
+
+/// 和 Lua 的 tostring 一样，整数不带小数部分
+fn format_lua_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
+/// 执行脚本中调用的命令，阻塞命令不会阻塞，没有数据时直接返回
+fn execute_command(
+    frame: RespArray,
+    session: &mut Session,
+    backend: &Backend,
+    read_only: bool,
+) -> RespFrame {
+    let name = match command_name(&frame) {
+        Ok(name) => name,
+        Err(e) => return e.into(),
+    };
+    if SCRIPT_DENIED_COMMANDS.contains(&name.as_str()) {
+        return CommandError::InvalidArgument(
+            "This Redis command is not allowed from script".to_string(),
+        )
+        .into();
+    }
+    if read_only && is_write_command(&name) {
+        return CommandError::InvalidArgument(
+            "Write commands are not allowed from read-only scripts.".to_string(),
+        )
+        .into();
+    }
+    // 执行过写命令的脚本不能再被终止
+    if is_write_command(&name) {
+        backend.lua.run().mark_write();
+    }
+    session.argv = propagation_argv(&frame);
+    match Command::try_from(frame) {
+        Ok(cmd) => match cmd.execute_or_block(session, backend) {
+            Outcome::Reply(frame) => frame,
+            Outcome::Replies(frames) => RespArray::new(frames).into(),
+            Outcome::Block(..) | Outcome::Replicate(..) => RespNullArray.into(),
+            Outcome::WaitAck(wait) => backend.ack_reply(&wait),
+        },
+        Err(e) => e.into(),
+    }
+}
+
+/// 命令的返回值转换成 Lua 的值，规则和 Redis 一样：
+/// status -> {ok = ...}，error -> {err = ...}，nil -> false，array -> table
+fn frame_to_lua(lua: &Lua, frame: RespFrame) -> mlua::Result<Value<'_>> {
+    Ok(match frame {
+        RespFrame::SimpleString(s) => {
+            let table = lua.create_table()?;
+            table.set("ok", s.0)?;
+            Value::Table(table)
+        }
+        RespFrame::Error(e) => {
+            let table = lua.create_table()?;
+            table.set("err", e.0)?;
+            Value::Table(table)
+        }
+        RespFrame::Integer(i) => Value::Integer(i),
+        RespFrame::BulkString(s) => Value::String(lua.create_string(&s.0)?),
+        RespFrame::Array(array) => {
+            let items = array
+                .0
+                .into_iter()
+                .map(|frame| frame_to_lua(lua, frame))
+                .collect::<mlua::Result<Vec<_>>>()?;
+            Value::Table(lua.create_sequence_from(items)?)
+        }
+        RespFrame::Double(v) => Value::Number(v),
+        RespFrame::Boolean(v) => Value::Boolean(v),
+        // 其他类型在 RESP2 中已经转换成了上面的类型
+        _ => Value::Boolean(false),
+    })
+}
+
+/// 脚本的返回值转换成 RESP：数字截断成整数，false/nil -> nil，table 的数组部分遇到 nil 为止
+pub(crate) fn lua_to_frame(value: Value) -> RespFrame {
+    match value {
+        Value::Boolean(true) => 1.into(),
+        Value::Integer(i) => i.into(),
+        Value::Number(n) => (n as i64).into(),
+        Value::String(s) => BulkString::new(s.as_bytes()).into(),
+        Value::Table(table) => {
+            if let Ok(Value::String(err)) = table.raw_get::<_, Value>("err") {
+                return SimpleError::new(err.to_string_lossy().to_string()).into();
+            }
+            if let Ok(Value::String(ok)) = table.raw_get::<_, Value>("ok") {
+                return SimpleString::new(ok.to_string_lossy().to_string()).into();
+            }
+            let mut frames = Vec::new();
+            for i in 1.. {
+                match table.raw_get::<_, Value>(i) {
+                    Ok(Value::Nil) | Err(_) => break,
+                    Ok(value) => frames.push(lua_to_frame(value)),
+                }
+            }
+            RespArray::new(frames).into()
+        }
+        _ => RespNullBulkString.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::run;
+    use anyhow::Result;
+
+    fn bulk(s: &str) -> RespFrame {
+        BulkString::new(s).into()
+    }
+
+    #[test]
+    fn test_eval_conversions() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["EVAL", "return 1.9", "0"])?, 1.into());
+        assert_eq!(
+            run(
+                &backend,
+                &["EVAL", "return {1, 'a', false, 2, nil, 3}", "0"]
+            )?,
+            RespArray::new([1.into(), bulk("a"), RespNullBulkString.into(), 2.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["EVAL", "return nil", "0"])?,
+            RespNullBulkString.into()
+        );
+        assert_eq!(
+            run(
+                &backend,
+                &["EVAL", "return redis.status_reply('FINE')", "0"]
+            )?,
+            SimpleString::new("FINE").into()
+        );
+        assert_eq!(
+            run(
+                &backend,
+                &["EVAL", "return redis.error_reply('MY err')", "0"]
+            )?,
+            SimpleError::new("MY err").into()
+        );
+        assert_eq!(
+            run(
+                &backend,
+                &[
+                    "EVAL",
+                    "return {KEYS[1], KEYS[2], ARGV[1]}",
+                    "2",
+                    "a",
+                    "b",
+                    "c"
+                ]
+            )?,
+            RespArray::new([bulk("a"), bulk("b"), bulk("c")]).into()
+        );
+        // 命令的返回值按照 RESP2 的规则转换
+        assert_eq!(
+            run(
+                &backend,
+                &[
+                    "EVAL",
+                    "local ok = redis.call('SET', KEYS[1], 10); \
+                     return {ok.ok, redis.call('GET', KEYS[1]), redis.call('INCRBY', KEYS[1], 5), \
+                     type(redis.call('GET', 'missing'))}",
+                    "1",
+                    "n"
+                ]
+            )?,
+            RespArray::new([bulk("OK"), bulk("10"), 15.into(), bulk("boolean")]).into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_errors() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SET", "s", "v"])?;
+        // redis.call 的错误原样返回，redis.pcall 返回 {err = ...}
+        assert_eq!(
+            run(
+                &backend,
+                &["EVAL", "return redis.call('LPUSH', 's', 'x')", "0"]
+            )?,
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+        assert_eq!(
+            run(
+                &backend,
+                &["EVAL", "return redis.pcall('LPUSH', 's', 'x').err", "0"]
+            )?,
+            bulk("WRONGTYPE Operation against a key holding the wrong kind of value")
+        );
+        assert_eq!(
+            run(&backend, &["EVAL", "return redis.call('MULTI')", "0"])?,
+            SimpleError::new("ERR This Redis command is not allowed from script").into()
+        );
+
+        let RespFrame::Error(e) = run(&backend, &["EVAL", "return (", "0"])? else {
+            panic!("syntax error expected");
+        };
+        assert!(e.starts_with("ERR Error compiling script"));
+        let RespFrame::Error(e) = run(&backend, &["EVAL", "return nosuch()", "0"])? else {
+            panic!("runtime error expected");
+        };
+        assert!(e.starts_with("ERR user_script:1:"));
+
+        assert!(run(&backend, &["EVAL", "return 1", "2", "a"]).is_err());
+        assert!(run(&backend, &["EVAL", "return 1", "-1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_cache() -> Result<()> {
+        let backend = Backend::new();
+        let sha = sha1_hex(b"return ARGV[1]");
+        assert_eq!(
+            run(&backend, &["EVALSHA", &sha, "0", "x"])?,
+            SimpleError::new("NOSCRIPT No matching script. Please use EVAL.").into()
+        );
+        assert_eq!(
+            run(&backend, &["SCRIPT", "LOAD", "return ARGV[1]"])?,
+            bulk(&sha)
+        );
+        assert_eq!(run(&backend, &["EVALSHA", &sha, "0", "x"])?, bulk("x"));
+        assert_eq!(
+            run(&backend, &["SCRIPT", "EXISTS", &sha, "nosuch"])?,
+            RespArray::new([1.into(), 0.into()]).into()
+        );
+        assert!(matches!(
+            run(&backend, &["SCRIPT", "LOAD", "return ("])?,
+            RespFrame::Error(_)
+        ));
+        assert_eq!(run(&backend, &["SCRIPT", "FLUSH"])?, "OK".into());
+        assert_eq!(
+            run(&backend, &["SCRIPT", "EXISTS", &sha])?,
+            RespArray::new([0.into()]).into()
+        );
+
+        // EVAL 执行过的脚本也会被缓存
+        run(&backend, &["EVAL", "return 1", "0"])?;
+        assert_eq!(
+            run(&backend, &["EVALSHA", &sha1_hex(b"return 1"), "0"])?,
+            1.into()
+        );
+
+        // 脚本共用一个虚拟机，不能通过全局变量互相影响
+        let RespFrame::Error(e) = run(&backend, &["EVAL", "x = 1", "0"])? else {
+            panic!("global variable should be denied");
+        };
+        assert!(e.contains("Script attempted to create global variable 'x'"));
+        assert_eq!(
+            run(&backend, &["EVAL", "return type(x)", "0"])?,
+            bulk("nil")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_kill() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, &["SCRIPT", "KILL"])?,
+            SimpleError::new("NOTBUSY No scripts in execution right now.").into()
+        );
+        let b = backend.clone();
+        let handle = std::thread::spawn(move || run(&b, &["EVAL", "while true do end", "0"]));
+        while !backend.lua.run().is_running() {
+            std::thread::yield_now();
+        }
+        assert_eq!(run(&backend, &["SCRIPT", "KILL"])?, "OK".into());
+        let RespFrame::Error(e) = handle.join().unwrap()? else {
+            panic!("killed script should fail");
+        };
+        assert!(e.starts_with("ERR Script killed by user with SCRIPT KILL"));
+        assert!(!backend.lua.run().is_running());
+        // 虚拟机可以继续使用
+        assert_eq!(run(&backend, &["EVAL", "return 1", "0"])?, 1.into());
+        Ok(())
+    }
+}
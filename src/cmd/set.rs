@@ -0,0 +1,749 @@
+use super::{parse_int, validate_args, CommandError, CommandExecutor};
+use crate::{
+    Backend, BackendError, BulkString, RespArray, RespFrame, RespNullBulkString, RespSet, Set,
+    Value,
+};
+use rand::{seq::index, Rng};
+
+/// SADD key member [member ...]
+#[derive(Debug)]
+pub struct SAdd {
+    key: Vec<u8>,
+    members: Vec<Vec<u8>>,
+}
+
+/// SREM key member [member ...]
+#[derive(Debug)]
+pub struct SRem {
+    key: Vec<u8>,
+    members: Vec<Vec<u8>>,
+}
+
+/// SMEMBERS key
+#[derive(Debug)]
+pub struct SMembers {
+    key: Vec<u8>,
+}
+
+/// SISMEMBER key member
+#[derive(Debug)]
+pub struct SIsMember {
+    key: Vec<u8>,
+    member: Vec<u8>,
+}
+
+/// SMISMEMBER key member [member ...]
+#[derive(Debug)]
+pub struct SMIsMember {
+    key: Vec<u8>,
+    members: Vec<Vec<u8>>,
+}
+
+/// SCARD key
+#[derive(Debug)]
+pub struct SCard {
+    key: Vec<u8>,
+}
+
+/// SPOP key [count]
+#[derive(Debug)]
+pub struct SPop {
+    key: Vec<u8>,
+    count: Option<usize>,
+}
+
+/// SRANDMEMBER key [count]
+#[derive(Debug)]
+pub struct SRandMember {
+    key: Vec<u8>,
+    count: Option<i64>,
+}
+
+/// SINTER/SUNION/SDIFF key [key ...]
+/// SINTERSTORE/SUNIONSTORE/SDIFFSTORE destination key [key ...]
+#[derive(Debug)]
+pub struct SetOp {
+    op: SetOpKind,
+    // 为 None 时直接返回结果，否则把结果写入 destination
+    destination: Option<Vec<u8>>,
+    keys: Vec<Vec<u8>>,
+}
+
+/// SMOVE source destination member
+#[derive(Debug)]
+pub struct SMove {
+    source: Vec<u8>,
+    destination: Vec<u8>,
+    member: Vec<u8>,
+}
+
+/// SINTERCARD numkeys key [key ...] [LIMIT limit]
+#[derive(Debug)]
+pub struct SInterCard {
+    keys: Vec<Vec<u8>>,
+    // 0 表示不限制
+    limit: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SetOpKind {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl SAdd {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("sadd".to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(SAdd {
+            key: args.next().unwrap_or_default(),
+            members: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for SAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, true, |set: &mut Set| {
+            let added = self
+                .members
+                .into_iter()
+                .filter(|member| set.insert(member.clone()))
+                .count();
+            (added as i64, added > 0)
+        });
+        match ret {
+            Ok(added) => added.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl SRem {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("srem".to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(SRem {
+            key: args.next().unwrap_or_default(),
+            members: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for SRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |set: &mut Set| {
+            let removed = self
+                .members
+                .iter()
+                .filter(|member| set.remove(member))
+                .count();
+            (removed as i64, removed > 0)
+        });
+        match ret {
+            Ok(removed) => removed.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl SMembers {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("smembers", &args, 1)?;
+        Ok(SMembers {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for SMembers {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |set: &Set| set.members()) {
+            Ok(members) => bulk_set(members.unwrap_or_default()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl SIsMember {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("sismember", &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(SIsMember {
+            key: args.next().unwrap_or_default(),
+            member: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for SIsMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |set: &Set| set.contains(&self.member)) {
+            Ok(exists) => (exists.unwrap_or_default() as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl SMIsMember {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("smismember".to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(SMIsMember {
+            key: args.next().unwrap_or_default(),
+            members: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for SMIsMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |set: &Set| {
+            self.members
+                .iter()
+                .map(|member| set.contains(member))
+                .collect::<Vec<_>>()
+        });
+        match ret {
+            Ok(exists) => {
+                let exists = exists.unwrap_or_else(|| vec![false; self.members.len()]);
+                RespArray::new(
+                    exists
+                        .into_iter()
+                        .map(|v| (v as i64).into())
+                        .collect::<Vec<RespFrame>>(),
+                )
+                .into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl SCard {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("scard", &args, 1)?;
+        Ok(SCard {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for SCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |set: &Set| set.len() as i64) {
+            Ok(len) => len.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl SPop {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(CommandError::WrongArity("spop".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let count = match args.next() {
+            Some(arg) => Some(
+                usize::try_from(parse_int(&arg)?)
+                    .map_err(|_| out_of_range("value is out of range, must be positive"))?,
+            ),
+            None => None,
+        };
+        Ok(SPop { key, count })
+    }
+}
+
+impl CommandExecutor for SPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |set: &mut Set| {
+            let mut rng = rand::thread_rng();
+            let len = set.len();
+            let indexes = match self.count {
+                Some(n) if n >= len => (0..len).collect(),
+                Some(n) => index::sample(&mut rng, len, n).into_vec(),
+                None => vec![rng.gen_range(0..len)],
+            };
+            let members: Vec<_> = indexes
+                .into_iter()
+                .filter_map(|i| set.get_index(i))
+                .collect();
+            // 先取出所有的元素再删除，删除会改变其他元素的下标
+            for member in &members {
+                set.remove(member);
+            }
+            let popped = !members.is_empty();
+            (members, popped)
+        });
+        let members = match ret {
+            Ok(members) => members,
+            Err(e) => return e.into(),
+        };
+        match self.count {
+            Some(_) => bulk_set(members.unwrap_or_default()),
+            None => match members.and_then(|mut members| members.pop()) {
+                Some(member) => BulkString::new(member).into(),
+                None => RespNullBulkString.into(),
+            },
+        }
+    }
+}
+
+impl SRandMember {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(CommandError::WrongArity("srandmember".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let count = match args.next() {
+            Some(arg) => Some(parse_int(&arg)?),
+            None => None,
+        };
+        if count.is_some_and(|n| n == i64::MIN) {
+            return Err(out_of_range("value is out of range"));
+        }
+        Ok(SRandMember { key, count })
+    }
+}
+
+impl CommandExecutor for SRandMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |set: &Set| {
+            let mut rng = rand::thread_rng();
+            let len = set.len();
+            let indexes: Vec<usize> = match self.count {
+                None => vec![rng.gen_range(0..len)],
+                // 负数允许重复
+                Some(n) if n < 0 => (0..n.unsigned_abs())
+                    .map(|_| rng.gen_range(0..len))
+                    .collect(),
+                Some(n) if n as usize >= len => (0..len).collect(),
+                Some(n) => index::sample(&mut rng, len, n as usize).into_vec(),
+            };
+            indexes
+                .into_iter()
+                .filter_map(|i| set.get_index(i))
+                .collect::<Vec<_>>()
+        });
+        let members = match ret {
+            Ok(members) => members,
+            Err(e) => return e.into(),
+        };
+        match self.count {
+            // 允许重复，因此即使在 RESP3 下也返回数组
+            Some(_) => RespArray::new(
+                members
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|v| BulkString::new(v).into())
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into(),
+            None => match members.and_then(|mut members| members.pop()) {
+                Some(member) => BulkString::new(member).into(),
+                None => RespNullBulkString.into(),
+            },
+        }
+    }
+}
+
+impl SetOp {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let store = name.ends_with("store");
+        if args.len() < 1 + store as usize {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let op = match name.trim_end_matches("store") {
+            "sinter" => SetOpKind::Inter,
+            "sunion" => SetOpKind::Union,
+            _ => SetOpKind::Diff,
+        };
+        let mut keys = args;
+        let destination = store.then(|| keys.remove(0));
+        Ok(SetOp {
+            op,
+            destination,
+            keys,
+        })
+    }
+}
+
+impl CommandExecutor for SetOp {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let set = match combine(backend, self.op, &self.keys, 0) {
+            Ok(set) => set,
+            Err(e) => return e.into(),
+        };
+        let Some(destination) = self.destination else {
+            return bulk_set(set.members());
+        };
+        let len = set.len() as i64;
+        // 结果为空时删除 destination
+        if set.is_empty() {
+            backend.remove(&destination);
+        } else {
+            backend.set(destination, Value::Set(set));
+        }
+        len.into()
+    }
+}
+
+impl SMove {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("smove", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(SMove {
+            source: args.next().unwrap_or_default(),
+            destination: args.next().unwrap_or_default(),
+            member: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for SMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // 和 Redis 一样，先检查两个 key 的类型，再移动元素
+        let exists = match backend.read_value(&self.source, |set: &Set| set.contains(&self.member))
+        {
+            Ok(exists) => exists.unwrap_or_default(),
+            Err(e) => return e.into(),
+        };
+        if let Err(e) = backend.read_value(&self.destination, |_: &Set| ()) {
+            return e.into();
+        }
+        if !exists {
+            return 0.into();
+        }
+        if self.source == self.destination {
+            return 1.into();
+        }
+        let ret = backend
+            .write_value(&self.source, false, |set: &mut Set| {
+                let removed = set.remove(&self.member);
+                (removed, removed)
+            })
+            .and_then(|_| {
+                backend.write_value(&self.destination, true, |set: &mut Set| {
+                    let added = set.insert(self.member);
+                    (added, added)
+                })
+            });
+        match ret {
+            Ok(_) => 1.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl SInterCard {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("sintercard".to_string()));
+        }
+        let mut args = args.into_iter();
+        let numkeys = parse_int(&args.next().unwrap_or_default())?;
+        if numkeys <= 0 {
+            return Err(out_of_range("numkeys should be greater than 0"));
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > args.len() {
+            return Err(out_of_range(
+                "Number of keys can't be greater than number of args",
+            ));
+        }
+        let keys = args.by_ref().take(numkeys).collect::<Vec<_>>();
+        let mut limit = 0;
+        while let Some(arg) = args.next() {
+            match args.next() {
+                Some(value) if arg.eq_ignore_ascii_case(b"LIMIT") => {
+                    limit = usize::try_from(parse_int(&value)?)
+                        .map_err(|_| out_of_range("LIMIT can't be negative"))?;
+                }
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(SInterCard { keys, limit })
+    }
+}
+
+impl CommandExecutor for SInterCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match combine(backend, SetOpKind::Inter, &self.keys, self.limit) {
+            Ok(set) => (set.len() as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+/// 计算多个 set 的交集、并集或差集，不存在的 key 视为空集合。limit 不为 0 时交集最多计算 limit 个元素
+fn combine(
+    backend: &Backend,
+    op: SetOpKind,
+    keys: &[Vec<u8>],
+    limit: usize,
+) -> Result<Set, BackendError> {
+    // 直接在原来的集合上计算，任何一个 key 类型不匹配都返回错误
+    backend.read_values(keys, |sets: Vec<Option<&Set>>| match op {
+        SetOpKind::Inter => {
+            // 有 key 不存在时交集为空
+            let Some(mut sets) = sets.into_iter().collect::<Option<Vec<_>>>() else {
+                return Set::new();
+            };
+            // 只遍历最小的集合
+            sets.sort_by_key(|set| set.len());
+            let (first, rest) = sets.split_first().expect("at least one key");
+            let mut result = Set::new();
+            for i in 0..first.len() {
+                if limit > 0 && result.len() >= limit {
+                    break;
+                }
+                let member = first.get_index(i).expect("index in range");
+                if rest.iter().all(|set| set.contains(&member)) {
+                    result.insert(member);
+                }
+            }
+            result
+        }
+        SetOpKind::Union => sets.into_iter().flatten().flat_map(Set::members).collect(),
+        SetOpKind::Diff => {
+            let mut sets = sets.into_iter();
+            let Some(first) = sets.next().flatten() else {
+                return Set::new();
+            };
+            let rest: Vec<&Set> = sets.flatten().collect();
+            first
+                .members()
+                .into_iter()
+                .filter(|member| !rest.iter().any(|set| set.contains(member)))
+                .collect()
+        }
+    })
+}
+
+fn bulk_set(members: Vec<Vec<u8>>) -> RespFrame {
+    RespSet::new(
+        members
+            .into_iter()
+            .map(|v| BulkString::new(v).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+fn out_of_range(msg: &str) -> CommandError {
+    CommandError::InvalidArgument(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::run;
+    use anyhow::Result;
+    use std::collections::BTreeSet;
+
+    fn members(frame: RespFrame) -> BTreeSet<String> {
+        let items = match frame {
+            RespFrame::Set(set) => set.0,
+            RespFrame::Array(array) => array.0,
+            frame => panic!("unexpected frame: {:?}", frame),
+        };
+        items
+            .into_iter()
+            .map(|item| match item {
+                RespFrame::BulkString(s) => String::from_utf8(s.0).unwrap(),
+                item => panic!("unexpected item: {:?}", item),
+            })
+            .collect()
+    }
+
+    fn set_of(items: &[&str]) -> BTreeSet<String> {
+        items.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sadd_srem() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["SADD", "s", "a", "b", "a"])?, 2.into());
+        assert_eq!(run(&backend, &["SADD", "s", "b", "c"])?, 1.into());
+        assert_eq!(run(&backend, &["SCARD", "s"])?, 3.into());
+        assert_eq!(run(&backend, &["SISMEMBER", "s", "a"])?, 1.into());
+        assert_eq!(run(&backend, &["SISMEMBER", "s", "x"])?, 0.into());
+        let ret = run(&backend, &["SMISMEMBER", "s", "a", "x"])?;
+        assert_eq!(ret, RespArray::new([1.into(), 0.into()]).into());
+        let ret = run(&backend, &["SMISMEMBER", "missing", "a"])?;
+        assert_eq!(ret, RespArray::new([0.into()]).into());
+        assert_eq!(
+            members(run(&backend, &["SMEMBERS", "s"])?),
+            set_of(&["a", "b", "c"])
+        );
+
+        assert_eq!(run(&backend, &["SREM", "s", "a", "x"])?, 1.into());
+        assert_eq!(run(&backend, &["SREM", "s", "b", "c"])?, 2.into());
+        assert!(!backend.exists(b"s"));
+        assert_eq!(run(&backend, &["SMEMBERS", "s"])?, RespSet::new([]).into());
+
+        run(&backend, &["LPUSH", "l", "a"])?;
+        let ret = run(&backend, &["SADD", "l", "a"])?;
+        assert_eq!(ret, BackendError::WrongType.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_intset_members() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SADD", "s", "3", "1", "2"])?;
+        // intset 编码按照从小到大的顺序返回
+        let ret = run(&backend, &["SMEMBERS", "s"])?;
+        assert_eq!(
+            ret,
+            RespSet::new([
+                BulkString::new("1").into(),
+                BulkString::new("2").into(),
+                BulkString::new("3").into(),
+            ])
+            .into()
+        );
+        let Some(Value::Set(set)) = backend.get(b"s") else {
+            panic!("s should be a set");
+        };
+        assert_eq!(set.encoding(), "intset");
+        Ok(())
+    }
+
+    #[test]
+    fn test_spop_srandmember() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SADD", "s", "a", "b", "c", "d"])?;
+        let ret = members(run(&backend, &["SRANDMEMBER", "s", "10"])?);
+        assert_eq!(ret, set_of(&["a", "b", "c", "d"]));
+        let RespFrame::Array(ret) = run(&backend, &["SRANDMEMBER", "s", "-10"])? else {
+            panic!("SRANDMEMBER with count should return an array");
+        };
+        assert_eq!(ret.len(), 10);
+        assert_eq!(members(run(&backend, &["SRANDMEMBER", "s", "2"])?).len(), 2);
+
+        let popped = members(run(&backend, &["SPOP", "s", "3"])?);
+        assert_eq!(popped.len(), 3);
+        assert_eq!(run(&backend, &["SCARD", "s"])?, 1.into());
+        let RespFrame::BulkString(last) = run(&backend, &["SPOP", "s"])? else {
+            panic!("SPOP should return a bulk string");
+        };
+        assert!(!popped.contains(&String::from_utf8(last.0)?));
+        assert!(!backend.exists(b"s"));
+        assert_eq!(run(&backend, &["SPOP", "s"])?, RespNullBulkString.into());
+        assert_eq!(run(&backend, &["SPOP", "s", "2"])?, RespSet::new([]).into());
+        assert_eq!(
+            run(&backend, &["SRANDMEMBER", "s"])?,
+            RespNullBulkString.into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_ops() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SADD", "a", "1", "2", "3", "x"])?;
+        run(&backend, &["SADD", "b", "2", "3", "4"])?;
+        run(&backend, &["SADD", "c", "3", "x"])?;
+
+        assert_eq!(
+            members(run(&backend, &["SINTER", "a", "b"])?),
+            set_of(&["2", "3"])
+        );
+        assert_eq!(
+            members(run(&backend, &["SINTER", "a", "b", "c"])?),
+            set_of(&["3"])
+        );
+        assert_eq!(
+            members(run(&backend, &["SINTER", "a", "missing"])?),
+            set_of(&[])
+        );
+        assert_eq!(
+            members(run(&backend, &["SUNION", "b", "c", "missing"])?),
+            set_of(&["2", "3", "4", "x"])
+        );
+        assert_eq!(
+            members(run(&backend, &["SDIFF", "a", "b", "c"])?),
+            set_of(&["1"])
+        );
+
+        assert_eq!(run(&backend, &["SINTERSTORE", "d", "a", "b"])?, 2.into());
+        assert_eq!(
+            members(run(&backend, &["SMEMBERS", "d"])?),
+            set_of(&["2", "3"])
+        );
+        assert_eq!(run(&backend, &["SUNIONSTORE", "d", "a", "b"])?, 5.into());
+        assert_eq!(run(&backend, &["SDIFFSTORE", "d", "c", "a"])?, 0.into());
+        assert!(!backend.exists(b"d"));
+
+        assert_eq!(run(&backend, &["SINTERCARD", "2", "a", "b"])?, 2.into());
+        let ret = run(&backend, &["SINTERCARD", "2", "a", "b", "LIMIT", "1"])?;
+        assert_eq!(ret, 1.into());
+
+        run(&backend, &["LPUSH", "l", "a"])?;
+        let ret = run(&backend, &["SUNION", "a", "l"])?;
+        assert_eq!(ret, BackendError::WrongType.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_smove() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SADD", "a", "x", "y"])?;
+        assert_eq!(run(&backend, &["SMOVE", "a", "b", "x"])?, 1.into());
+        assert_eq!(run(&backend, &["SMOVE", "a", "b", "x"])?, 0.into());
+        assert_eq!(run(&backend, &["SMOVE", "a", "a", "y"])?, 1.into());
+        assert_eq!(run(&backend, &["SMOVE", "a", "b", "y"])?, 1.into());
+        assert!(!backend.exists(b"a"));
+        assert_eq!(
+            members(run(&backend, &["SMEMBERS", "b"])?),
+            set_of(&["x", "y"])
+        );
+
+        run(&backend, &["LPUSH", "l", "a"])?;
+        let ret = run(&backend, &["SMOVE", "b", "l", "x"])?;
+        assert_eq!(ret, BackendError::WrongType.into());
+        assert_eq!(run(&backend, &["SCARD", "b"])?, 2.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_errors() -> Result<()> {
+        let backend = Backend::new();
+        for (args, err) in [
+            (
+                &["SPOP", "s", "-1"][..],
+                "value is out of range, must be positive",
+            ),
+            (
+                &["SINTERCARD", "0", "a"],
+                "numkeys should be greater than 0",
+            ),
+            (
+                &["SINTERCARD", "3", "a", "b"],
+                "Number of keys can't be greater than number of args",
+            ),
+            (
+                &["SINTERCARD", "1", "a", "LIMIT", "-1"],
+                "LIMIT can't be negative",
+            ),
+        ] {
+            let ret = run(&backend, args);
+            assert_eq!(
+                ret.unwrap_err().downcast::<CommandError>()?,
+                CommandError::InvalidArgument(err.to_string())
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,2179 @@
+use super::{
+    parse_int, parse_string, validate_args, BlockingExecutor, CommandError, CommandExecutor,
+    Outcome,
+};
+use crate::{
+    backend::now_ms, Backend, BackendError, BulkString, ClaimOptions, Consumer, ConsumerGroup,
+    Protocol, RespArray, RespFrame, RespMap, RespNull, RespNullArray, RespNullBulkString, ServeFn,
+    SimpleString, Stream, StreamFields, StreamId, TrimStrategy, STREAM_NODE_MAX_ENTRIES,
+};
+use std::{ops::Bound, time::Duration, vec::IntoIter};
+
+/// XADD key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] *|id field value [field value ...]
+#[derive(Debug)]
+pub struct XAdd {
+    key: Vec<u8>,
+    // 为 true 时 key 不存在不会创建 stream
+    nomkstream: bool,
+    trim: Option<TrimOptions>,
+    id: XAddId,
+    fields: StreamFields,
+}
+
+/// XRANGE key start end [COUNT count]
+/// XREVRANGE key end start [COUNT count]
+#[derive(Debug)]
+pub struct XRange {
+    key: Vec<u8>,
+    start: Bound<StreamId>,
+    end: Bound<StreamId>,
+    rev: bool,
+    count: Option<usize>,
+}
+
+/// XLEN key
+#[derive(Debug)]
+pub struct XLen {
+    key: Vec<u8>,
+}
+
+/// XDEL key id [id ...]
+#[derive(Debug)]
+pub struct XDel {
+    key: Vec<u8>,
+    ids: Vec<StreamId>,
+}
+
+/// XTRIM key MAXLEN|MINID [=|~] threshold [LIMIT count]
+#[derive(Debug)]
+pub struct XTrim {
+    key: Vec<u8>,
+    trim: TrimOptions,
+}
+
+/// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+/// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]
+#[derive(Debug)]
+pub struct XRead {
+    // XREADGROUP 的消费组和消费者
+    group: Option<ReadGroup>,
+    count: Option<usize>,
+    // 没有数据时是否阻塞
+    block: bool,
+    timeout: Option<Duration>,
+    streams: Vec<(Vec<u8>, ReadId)>,
+    // RESP2 返回 [[key, entries], ...]，RESP3 返回 key -> entries 的 map
+    protocol: Protocol,
+}
+
+/// XGROUP CREATE|SETID|DESTROY|CREATECONSUMER|DELCONSUMER key group ...
+#[derive(Debug)]
+pub struct XGroup {
+    key: Vec<u8>,
+    group: String,
+    op: XGroupOp,
+}
+
+/// XACK key group id [id ...]
+#[derive(Debug)]
+pub struct XAck {
+    key: Vec<u8>,
+    group: String,
+    ids: Vec<StreamId>,
+}
+
+/// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+#[derive(Debug)]
+pub struct XPending {
+    key: Vec<u8>,
+    group: String,
+    // None 时返回汇总信息
+    range: Option<PendingRange>,
+}
+
+/// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds]
+/// [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID lastid]
+#[derive(Debug)]
+pub struct XClaim {
+    key: Vec<u8>,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    ids: Vec<StreamId>,
+    // IDLE 和 TIME 都用来设置分发时间，后出现的生效
+    idle: Option<i64>,
+    time: Option<i64>,
+    retry_count: Option<u64>,
+    force: bool,
+    just_id: bool,
+    last_id: Option<StreamId>,
+}
+
+/// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+#[derive(Debug)]
+pub struct XAutoClaim {
+    key: Vec<u8>,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    start: Bound<StreamId>,
+    count: usize,
+    just_id: bool,
+}
+
+/// XINFO STREAM key [FULL [COUNT count]] | XINFO GROUPS key | XINFO CONSUMERS key group
+#[derive(Debug)]
+pub struct XInfo {
+    key: Vec<u8>,
+    kind: XInfoKind,
+}
+
+/// XADD 的 ID 参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum XAddId {
+    // `*`，完全自动生成
+    Auto,
+    // `ms-*`，自动生成序号
+    Seq(u64),
+    Explicit(StreamId),
+}
+
+/// XADD/XTRIM 的裁剪参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TrimOptions {
+    strategy: TrimStrategy,
+    // `~` 近似裁剪
+    approx: bool,
+    // 最多删除的数量，None 表示不限制
+    limit: Option<usize>,
+}
+
+/// XREADGROUP 的 GROUP 和 NOACK 参数
+#[derive(Debug, Clone)]
+struct ReadGroup {
+    group: String,
+    consumer: String,
+    // 读取的 entry 不加入 PEL
+    noack: bool,
+}
+
+/// XREAD/XREADGROUP 的 ID 参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReadId {
+    // `$`，只读取执行命令之后添加的 entry，执行前替换成 stream 最后的 ID
+    Last,
+    // `>`，读取还没有分发给消费组中任何消费者的 entry
+    New,
+    // 读取 ID 大于它的 entry，XREADGROUP 中表示读取消费者 PEL 中的历史
+    After(StreamId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum XGroupOp {
+    // id 为 None 表示 `$`
+    Create {
+        id: Option<StreamId>,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    },
+    SetId {
+        id: Option<StreamId>,
+        entries_read: Option<u64>,
+    },
+    Destroy,
+    CreateConsumer(String),
+    DelConsumer(String),
+}
+
+/// XPENDING 的范围查询参数
+#[derive(Debug, Clone, PartialEq)]
+struct PendingRange {
+    min_idle: u64,
+    start: Bound<StreamId>,
+    end: Bound<StreamId>,
+    count: usize,
+    consumer: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum XInfoKind {
+    Stream,
+    // XINFO STREAM FULL，最多返回 count 条 entry，None 表示全部
+    Full(Option<usize>),
+    Groups,
+    Consumers(String),
+}
+
+impl XAdd {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 4 {
+            return Err(CommandError::WrongArity("xadd".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let mut nomkstream = false;
+        let mut trim = None;
+        // 第一个不是选项的参数就是 ID
+        let id = loop {
+            let arg = args.next().ok_or(CommandError::Syntax)?;
+            match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                "NOMKSTREAM" => nomkstream = true,
+                "MAXLEN" | "MINID" => trim = Some(TrimOptions::parse(arg, &mut args)?),
+                _ => break parse_xadd_id(&arg)?,
+            }
+        };
+
+        let args: Vec<_> = args.collect();
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(CommandError::WrongArity("xadd".to_string()));
+        }
+        let fields = args
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        Ok(XAdd {
+            key,
+            nomkstream,
+            trim,
+            id,
+            fields,
+        })
+    }
+}
+
+impl CommandExecutor for XAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, !self.nomkstream, |stream: &mut Stream| {
+            let add = |stream: &mut Stream| {
+                let id = match self.id {
+                    XAddId::Auto => stream.next_id(now_ms()).ok_or_else(|| {
+                        CommandError::InvalidArgument(
+                        "The stream has exhausted the last possible ID, unable to add more items"
+                            .to_string(),
+                    )
+                    })?,
+                    XAddId::Seq(ms) => stream.next_id_with_ms(ms).ok_or_else(id_too_small)?,
+                    XAddId::Explicit(id) => id,
+                };
+                if !stream.insert(id, self.fields) {
+                    return Err(id_too_small());
+                }
+                if let Some(trim) = self.trim {
+                    stream.trim(trim.strategy, trim.approx, trim.limit);
+                }
+                Ok(id)
+            };
+            let ret = add(stream);
+            let modified = ret.is_ok();
+            (ret, modified)
+        });
+        match ret {
+            Ok(Some(Ok(id))) => BulkString::new(id.to_string()).into(),
+            Ok(Some(Err(e))) => e.into(),
+            Ok(None) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XRange {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() != 3 && args.len() != 5 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let rev = name == "xrevrange";
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let (first, second) = (
+            args.next().unwrap_or_default(),
+            args.next().unwrap_or_default(),
+        );
+        // XREVRANGE 的参数顺序是 end start
+        let (start, end) = if rev {
+            (second, first)
+        } else {
+            (first, second)
+        };
+        let count = match (args.next(), args.next()) {
+            (Some(option), Some(count)) if option.eq_ignore_ascii_case(b"COUNT") => {
+                // 和 Redis 一样，负数当作 0 处理
+                Some(parse_int(&count)?.max(0) as usize)
+            }
+            (None, None) => None,
+            _ => return Err(CommandError::Syntax),
+        };
+        Ok(XRange {
+            key,
+            start: parse_range_id(&start, 0)?,
+            end: parse_range_id(&end, u64::MAX)?,
+            rev,
+            count,
+        })
+    }
+}
+
+impl CommandExecutor for XRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |stream: &Stream| {
+            stream.range(self.start, self.end, self.rev, self.count)
+        });
+        match ret {
+            Ok(entries) => entries_array(entries.unwrap_or_default()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XLen {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("xlen", &args, 1)?;
+        Ok(XLen {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for XLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |stream: &Stream| stream.len() as i64) {
+            Ok(len) => len.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XDel {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("xdel".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let ids = args
+            .map(|arg| parse_id(&arg, 0))
+            .collect::<Result<_, _>>()?;
+        Ok(XDel { key, ids })
+    }
+}
+
+impl CommandExecutor for XDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |stream: &mut Stream| {
+            let removed = self.ids.iter().filter(|id| stream.remove(**id)).count();
+            (removed as i64, removed > 0)
+        });
+        match ret {
+            Ok(n) => n.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XTrim {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 3 {
+            return Err(CommandError::WrongArity("xtrim".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let strategy = args.next().unwrap_or_default();
+        if !strategy.eq_ignore_ascii_case(b"MAXLEN") && !strategy.eq_ignore_ascii_case(b"MINID") {
+            return Err(CommandError::Syntax);
+        }
+        let trim = TrimOptions::parse(strategy, &mut args)?;
+        if args.next().is_some() {
+            return Err(CommandError::Syntax);
+        }
+        Ok(XTrim { key, trim })
+    }
+}
+
+impl CommandExecutor for XTrim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let trim = self.trim;
+        let ret = backend.write_value(&self.key, false, |stream: &mut Stream| {
+            let trimmed = stream.trim(trim.strategy, trim.approx, trim.limit);
+            (trimmed as i64, trimmed > 0)
+        });
+        match ret {
+            Ok(n) => n.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XRead {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let is_group = name == "xreadgroup";
+        if args.len() < 3 + 3 * is_group as usize {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let mut group = None;
+        let mut noack = false;
+        let mut count = None;
+        let mut block = false;
+        let mut timeout = None;
+        let rest = loop {
+            let arg = args.next().ok_or(CommandError::Syntax)?;
+            match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                "COUNT" => {
+                    // 和 Redis 一样，COUNT 小于等于 0 时不限制数量
+                    let n = parse_int(&args.next().ok_or(CommandError::Syntax)?)?;
+                    count = (n > 0).then_some(n as usize);
+                }
+                "BLOCK" => {
+                    timeout = parse_block_timeout(&args.next().ok_or(CommandError::Syntax)?)?;
+                    block = true;
+                }
+                "GROUP" if is_group => {
+                    let (Some(group_name), Some(consumer)) = (args.next(), args.next()) else {
+                        return Err(CommandError::Syntax);
+                    };
+                    group = Some((parse_string(group_name)?, parse_string(consumer)?));
+                }
+                "GROUP" => return Err(CommandError::InvalidArgument(
+                    "The GROUP option is only supported by XREADGROUP. You called XREAD instead."
+                        .to_string(),
+                )),
+                "NOACK" if is_group => noack = true,
+                "STREAMS" => break args.collect::<Vec<_>>(),
+                _ => return Err(CommandError::Syntax),
+            }
+        };
+        if rest.is_empty() || !rest.len().is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument(format!(
+                "Unbalanced '{}' list of streams: for each stream key an ID or '{}' must be specified.",
+                name,
+                if is_group { ">" } else { "$" }
+            )));
+        }
+        let group = match group {
+            Some((group, consumer)) => Some(ReadGroup {
+                group,
+                consumer,
+                noack,
+            }),
+            None if is_group => {
+                return Err(CommandError::InvalidArgument(
+                    "Missing GROUP option for XREADGROUP".to_string(),
+                ))
+            }
+            None => None,
+        };
+
+        let (keys, ids) = rest.split_at(rest.len() / 2);
+        let streams = keys
+            .iter()
+            .zip(ids)
+            .map(|(key, id)| {
+                let id = match id.as_slice() {
+                    b"$" if is_group => {
+                        return Err(CommandError::InvalidArgument(
+                            "The $ ID is meaningful only for XREAD command".to_string(),
+                        ))
+                    }
+                    b"$" => ReadId::Last,
+                    b">" if is_group => ReadId::New,
+                    b">" => return Err(CommandError::InvalidArgument(
+                        "The > ID can be specified only when calling XREADGROUP using the GROUP <group> <consumer> option.".to_string(),
+                    )),
+                    id => ReadId::After(parse_id(id, 0)?),
+                };
+                Ok((key.clone(), id))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(XRead {
+            group,
+            count,
+            block,
+            timeout,
+            streams,
+            protocol: Protocol::default(),
+        })
+    }
+
+    /// 按照连接的协议版本构造返回值
+    pub(crate) fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// 读取所有的 stream，没有任何数据时返回 None
+    fn read(&mut self, backend: &Backend) -> Result<Option<RespFrame>, BackendError> {
+        self.prepare(backend)?;
+        let mut ret = Vec::new();
+        for (key, id) in &self.streams {
+            if let Some(entries) = read_stream(backend, key, *id, self.group.as_ref(), self.count)?
+            {
+                ret.push((key.clone(), entries));
+            }
+        }
+        Ok((!ret.is_empty()).then(|| streams_reply(ret, self.protocol)))
+    }
+
+    /// 把 `$` 替换成 stream 当前最后的 ID；XREADGROUP 检查消费组是否存在，同时创建消费者
+    fn prepare(&mut self, backend: &Backend) -> Result<(), BackendError> {
+        let now = now_ms();
+        for (key, id) in self.streams.iter_mut() {
+            match &self.group {
+                Some(group) => {
+                    let found = backend.write_value(key, false, |stream: &mut Stream| {
+                        let found = stream
+                            .group_mut(&group.group)
+                            .map(|g| g.seen_consumer(&group.consumer, now))
+                            .is_some();
+                        (found, found)
+                    })?;
+                    if found != Some(true) {
+                        return Err(no_read_group(key, &group.group));
+                    }
+                }
+                None if *id == ReadId::Last => {
+                    let last_id = backend.read_value(key, |stream: &Stream| stream.last_id())?;
+                    *id = ReadId::After(last_id.unwrap_or_default());
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn serve(&self) -> ServeFn {
+        let streams = self.streams.clone();
+        let group = self.group.clone();
+        let (count, protocol) = (self.count, self.protocol);
+        Box::new(move |backend, key| {
+            let Some((_, id)) = streams.iter().find(|(k, _)| k == key) else {
+                return Ok(None);
+            };
+            let entries = read_stream(backend, key, *id, group.as_ref(), count)?;
+            Ok(entries.map(|entries| streams_reply(vec![(key.to_vec(), entries)], protocol)))
+        })
+    }
+}
+
+impl CommandExecutor for XRead {
+    fn execute(mut self, backend: &Backend) -> RespFrame {
+        match self.read(backend) {
+            Ok(Some(reply)) => reply,
+            Ok(None) => RespNullArray.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl BlockingExecutor for XRead {
+    fn execute_blocking(mut self, backend: &Backend) -> Outcome {
+        match self.read(backend) {
+            Ok(Some(reply)) => Outcome::Reply(reply),
+            Ok(None) if self.block => {
+                let keys = self.streams.iter().map(|(key, _)| key.clone()).collect();
+                Outcome::Block(backend.block(keys, self.serve()), self.timeout)
+            }
+            Ok(None) => Outcome::Reply(RespNullArray.into()),
+            Err(e) => Outcome::Reply(e.into()),
+        }
+    }
+}
+
+impl XGroup {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut args = args.into_iter();
+        let sub = args
+            .next()
+            .ok_or_else(|| CommandError::WrongArity("xgroup".to_string()))?;
+        let name = String::from_utf8_lossy(&sub).to_lowercase();
+        let (min, max) = match name.as_str() {
+            "create" => (3, 6),
+            "setid" => (3, 5),
+            "destroy" => (2, 2),
+            "createconsumer" | "delconsumer" => (3, 3),
+            _ => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand '{}'. Try XGROUP HELP.",
+                    String::from_utf8_lossy(&sub)
+                )))
+            }
+        };
+        if args.len() < min {
+            return Err(CommandError::WrongArity(format!("xgroup|{}", name)));
+        }
+        if args.len() > max {
+            return Err(CommandError::Syntax);
+        }
+        let key = args.next().unwrap_or_default();
+        let group = parse_string(args.next().unwrap_or_default())?;
+        let op = match name.as_str() {
+            "create" | "setid" => {
+                let id = parse_group_id(&args.next().unwrap_or_default())?;
+                let mut mkstream = false;
+                let mut entries_read = None;
+                while let Some(arg) = args.next() {
+                    match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                        "MKSTREAM" if name == "create" => mkstream = true,
+                        "ENTRIESREAD" => {
+                            let n = parse_int(&args.next().ok_or(CommandError::Syntax)?)?;
+                            if n < -1 {
+                                return Err(CommandError::InvalidArgument(
+                                    "value for ENTRIESREAD must be positive or -1".to_string(),
+                                ));
+                            }
+                            entries_read = u64::try_from(n).ok();
+                        }
+                        _ => return Err(CommandError::Syntax),
+                    }
+                }
+                match name.as_str() {
+                    "create" => XGroupOp::Create {
+                        id,
+                        mkstream,
+                        entries_read,
+                    },
+                    _ => XGroupOp::SetId { id, entries_read },
+                }
+            }
+            "destroy" => XGroupOp::Destroy,
+            "createconsumer" => {
+                XGroupOp::CreateConsumer(parse_string(args.next().unwrap_or_default())?)
+            }
+            _ => XGroupOp::DelConsumer(parse_string(args.next().unwrap_or_default())?),
+        };
+        Ok(XGroup { key, group, op })
+    }
+}
+
+impl CommandExecutor for XGroup {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let create = matches!(self.op, XGroupOp::Create { mkstream: true, .. });
+        let now = now_ms();
+        let ret = backend.write_value(&self.key, create, |stream: &mut Stream| {
+            let no_group = || {
+                BackendError::NoGroup(format!(
+                    "No such consumer group '{}' for key name '{}'",
+                    self.group,
+                    String::from_utf8_lossy(&self.key)
+                ))
+            };
+            let run = |stream: &mut Stream| {
+                // 返回回复以及是否修改了 stream
+                let ret: (RespFrame, bool) = match self.op {
+                    XGroupOp::Create {
+                        id, entries_read, ..
+                    } => {
+                        let id = id.unwrap_or(stream.last_id());
+                        if !stream.create_group(&self.group, id, entries_read) {
+                            return Err(BackendError::BusyGroup);
+                        }
+                        (SimpleString::new("OK").into(), true)
+                    }
+                    XGroupOp::SetId { id, entries_read } => {
+                        let id = id.unwrap_or(stream.last_id());
+                        let group = stream.group_mut(&self.group).ok_or_else(no_group)?;
+                        group.set_last_id(id, entries_read);
+                        (SimpleString::new("OK").into(), true)
+                    }
+                    XGroupOp::Destroy => {
+                        let removed = stream.remove_group(&self.group);
+                        ((removed as i64).into(), removed)
+                    }
+                    XGroupOp::CreateConsumer(consumer) => {
+                        let group = stream.group_mut(&self.group).ok_or_else(no_group)?;
+                        let created = group.create_consumer(&consumer, now);
+                        ((created as i64).into(), created)
+                    }
+                    XGroupOp::DelConsumer(consumer) => {
+                        let group = stream.group_mut(&self.group).ok_or_else(no_group)?;
+                        let pending = group.remove_consumer(&consumer);
+                        (
+                            (pending.unwrap_or_default() as i64).into(),
+                            pending.is_some(),
+                        )
+                    }
+                };
+                Ok(ret)
+            };
+            match run(stream) {
+                Ok((frame, modified)) => (Ok(frame), modified),
+                Err(e) => (Err(e), false),
+            }
+        });
+        match ret {
+            Ok(Some(Ok(frame))) => frame,
+            Ok(Some(Err(e))) | Err(e) => e.into(),
+            Ok(None) => CommandError::InvalidArgument(
+                "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.".to_string(),
+            )
+            .into(),
+        }
+    }
+}
+
+impl XAck {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 3 {
+            return Err(CommandError::WrongArity("xack".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let group = parse_string(args.next().unwrap_or_default())?;
+        let ids = args
+            .map(|arg| parse_id(&arg, 0))
+            .collect::<Result<_, _>>()?;
+        Ok(XAck { key, group, ids })
+    }
+}
+
+impl CommandExecutor for XAck {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |stream: &mut Stream| {
+            let acked = match stream.group_mut(&self.group) {
+                Some(group) => self.ids.iter().filter(|id| group.ack(**id)).count(),
+                None => 0,
+            };
+            (acked as i64, acked > 0)
+        });
+        match ret {
+            Ok(n) => n.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XPending {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("xpending".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let group = parse_string(args.next().unwrap_or_default())?;
+        if args.len() == 0 {
+            return Ok(XPending {
+                key,
+                group,
+                range: None,
+            });
+        }
+
+        let mut min_idle = 0;
+        if args.as_slice()[0].eq_ignore_ascii_case(b"IDLE") {
+            let idle = args.nth(1).ok_or(CommandError::Syntax)?;
+            min_idle = parse_int(&idle)?.max(0) as u64;
+        }
+        // IDLE 之后必须是 start end count [consumer]
+        if args.len() != 3 && args.len() != 4 {
+            return Err(CommandError::Syntax);
+        }
+        let start = parse_range_id(&args.next().unwrap_or_default(), 0)?;
+        let end = parse_range_id(&args.next().unwrap_or_default(), u64::MAX)?;
+        let count = parse_int(&args.next().unwrap_or_default())?.max(0) as usize;
+        let consumer = args.next().map(parse_string).transpose()?;
+        Ok(XPending {
+            key,
+            group,
+            range: Some(PendingRange {
+                min_idle,
+                start,
+                end,
+                count,
+                consumer,
+            }),
+        })
+    }
+}
+
+impl CommandExecutor for XPending {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let now = now_ms();
+        let ret = backend.read_value(&self.key, |stream: &Stream| {
+            let group = stream.group(&self.group)?;
+            Some(match &self.range {
+                Some(range) => pending_entries(group, range, now),
+                None => pending_summary(group),
+            })
+        });
+        match ret {
+            Ok(Some(Some(frame))) => frame,
+            Ok(_) => no_such_group(&self.key, &self.group).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XClaim {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 5 {
+            return Err(CommandError::WrongArity("xclaim".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let group = parse_string(args.next().unwrap_or_default())?;
+        let consumer = parse_string(args.next().unwrap_or_default())?;
+        let min_idle = parse_min_idle(&args.next().unwrap_or_default(), "XCLAIM")?;
+        // 第一个不是 ID 的参数开始是选项
+        let mut ids = Vec::new();
+        while let Some(id) = args
+            .as_slice()
+            .first()
+            .and_then(|arg| StreamId::parse(arg, 0))
+        {
+            ids.push(id);
+            args.next();
+        }
+
+        let mut claim = XClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            ids,
+            idle: None,
+            time: None,
+            retry_count: None,
+            force: false,
+            just_id: false,
+            last_id: None,
+        };
+        while let Some(arg) = args.next() {
+            let option = String::from_utf8_lossy(&arg).to_uppercase();
+            let unrecognized = || {
+                CommandError::InvalidArgument(format!(
+                    "Unrecognized XCLAIM option '{}'",
+                    String::from_utf8_lossy(&arg)
+                ))
+            };
+            let mut int_arg = |name: &str| {
+                let arg = args.next().ok_or_else(unrecognized)?;
+                parse_int(&arg).map_err(|_| {
+                    CommandError::InvalidArgument(format!(
+                        "Invalid {} option argument for XCLAIM",
+                        name
+                    ))
+                })
+            };
+            match option.as_str() {
+                "IDLE" => (claim.idle, claim.time) = (Some(int_arg("IDLE")?), None),
+                "TIME" => (claim.time, claim.idle) = (Some(int_arg("TIME")?), None),
+                // 负数表示不设置
+                "RETRYCOUNT" => claim.retry_count = u64::try_from(int_arg("RETRYCOUNT")?).ok(),
+                "FORCE" => claim.force = true,
+                "JUSTID" => claim.just_id = true,
+                "LASTID" => {
+                    let id = args.next().ok_or_else(unrecognized)?;
+                    claim.last_id = Some(parse_id(&id, 0)?);
+                }
+                _ => return Err(unrecognized()),
+            }
+        }
+        Ok(claim)
+    }
+}
+
+impl CommandExecutor for XClaim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let now = now_ms();
+        let delivery_time = match (self.idle, self.time) {
+            (Some(idle), _) => now as i64 - idle,
+            (_, Some(time)) => time,
+            _ => now as i64,
+        };
+        // 和 Redis 一样，分发时间不能是负数或者在未来
+        let delivery_time = u64::try_from(delivery_time)
+            .ok()
+            .filter(|t| *t <= now)
+            .unwrap_or(now);
+        let opts = ClaimOptions {
+            min_idle: self.min_idle,
+            delivery_time,
+            retry_count: self.retry_count,
+            force: self.force,
+            just_id: self.just_id,
+            last_id: self.last_id,
+        };
+        let ret = backend.write_value(&self.key, false, |stream: &mut Stream| {
+            let claimed = stream.claim(&self.group, &self.consumer, &self.ids, &opts, now);
+            let found = claimed.is_some();
+            (claimed, found)
+        });
+        match ret {
+            Ok(Some(Some(entries))) => claimed_array(entries, self.just_id),
+            Ok(_) => no_such_group(&self.key, &self.group).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XAutoClaim {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if !(5..=8).contains(&args.len()) {
+            return Err(CommandError::WrongArity("xautoclaim".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let group = parse_string(args.next().unwrap_or_default())?;
+        let consumer = parse_string(args.next().unwrap_or_default())?;
+        let min_idle = parse_min_idle(&args.next().unwrap_or_default(), "XAUTOCLAIM")?;
+        let start = parse_range_id(&args.next().unwrap_or_default(), 0)?;
+        let mut count = 100;
+        let mut just_id = false;
+        while let Some(arg) = args.next() {
+            match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                "COUNT" => {
+                    // 最多检查 count * 10 条 pending entry，不能溢出
+                    count = args
+                        .next()
+                        .and_then(|arg| parse_int(&arg).ok())
+                        .filter(|n| (1..=i64::MAX / 10).contains(n))
+                        .ok_or_else(|| {
+                            CommandError::InvalidArgument("COUNT must be > 0".to_string())
+                        })? as usize;
+                }
+                "JUSTID" => just_id = true,
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(XAutoClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            start,
+            count,
+            just_id,
+        })
+    }
+}
+
+impl CommandExecutor for XAutoClaim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let now = now_ms();
+        let opts = ClaimOptions {
+            min_idle: self.min_idle,
+            delivery_time: now,
+            retry_count: None,
+            force: false,
+            just_id: self.just_id,
+            last_id: None,
+        };
+        let ret = backend.write_value(&self.key, false, |stream: &mut Stream| {
+            let (group, consumer) = (&self.group, &self.consumer);
+            let claim = stream.auto_claim(group, consumer, self.start, self.count, &opts, now);
+            let found = claim.is_some();
+            (claim, found)
+        });
+        match ret {
+            Ok(Some(Some(claim))) => {
+                let deleted: Vec<RespFrame> = claim.deleted.into_iter().map(id_frame).collect();
+                RespArray::new([
+                    id_frame(claim.next),
+                    claimed_array(claim.claimed, self.just_id),
+                    RespArray::new(deleted).into(),
+                ])
+                .into()
+            }
+            Ok(_) => no_such_group(&self.key, &self.group).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl XInfo {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut args = args.into_iter();
+        let sub = args
+            .next()
+            .ok_or_else(|| CommandError::WrongArity("xinfo".to_string()))?;
+        let name = String::from_utf8_lossy(&sub).to_lowercase();
+        let (min, max) = match name.as_str() {
+            "stream" => (1, 4),
+            "groups" => (1, 1),
+            "consumers" => (2, 2),
+            _ => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand '{}'. Try XINFO HELP.",
+                    String::from_utf8_lossy(&sub)
+                )))
+            }
+        };
+        if !(min..=max).contains(&args.len()) {
+            return Err(CommandError::WrongArity(format!("xinfo|{}", name)));
+        }
+        let key = args.next().unwrap_or_default();
+        let kind = match name.as_str() {
+            "stream" => match (args.next(), args.next(), args.next()) {
+                (None, ..) => XInfoKind::Stream,
+                (Some(full), None, None) if full.eq_ignore_ascii_case(b"FULL") => {
+                    XInfoKind::Full(Some(10))
+                }
+                (Some(full), Some(option), Some(count))
+                    if full.eq_ignore_ascii_case(b"FULL")
+                        && option.eq_ignore_ascii_case(b"COUNT") =>
+                {
+                    // COUNT 0 表示返回全部 entry，负数使用默认值
+                    match parse_int(&count)? {
+                        0 => XInfoKind::Full(None),
+                        n => XInfoKind::Full(Some(usize::try_from(n).unwrap_or(10))),
+                    }
+                }
+                _ => return Err(CommandError::Syntax),
+            },
+            "groups" => XInfoKind::Groups,
+            _ => XInfoKind::Consumers(parse_string(args.next().unwrap_or_default())?),
+        };
+        Ok(XInfo { key, kind })
+    }
+}
+
+impl CommandExecutor for XInfo {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let now = now_ms();
+        let ret = backend.read_value(&self.key, |stream: &Stream| match &self.kind {
+            XInfoKind::Stream => Ok(stream_info(stream, None)),
+            XInfoKind::Full(count) => Ok(stream_info(stream, Some(*count))),
+            XInfoKind::Groups => {
+                let groups: Vec<RespFrame> = stream
+                    .groups()
+                    .map(|(name, group)| group_info(stream, name, group))
+                    .collect();
+                Ok(RespArray::new(groups).into())
+            }
+            XInfoKind::Consumers(name) => {
+                let group = stream.group(name).ok_or_else(|| {
+                    BackendError::NoGroup(format!(
+                        "No such consumer group '{}' for key name '{}'",
+                        name,
+                        String::from_utf8_lossy(&self.key)
+                    ))
+                })?;
+                let consumers: Vec<RespFrame> = group
+                    .consumers()
+                    .map(|(name, consumer)| consumer_info(name, consumer, now))
+                    .collect();
+                Ok(RespArray::new(consumers).into())
+            }
+        });
+        match ret {
+            Ok(Some(Ok(frame))) => frame,
+            Ok(Some(Err(e))) | Err(e) => e.into(),
+            Ok(None) => CommandError::InvalidArgument("no such key".to_string()).into(),
+        }
+    }
+}
+
+impl TrimOptions {
+    /// 解析 MAXLEN|MINID 之后的 [=|~] threshold [LIMIT count]
+    fn parse(strategy: Vec<u8>, args: &mut IntoIter<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut threshold = args.next().ok_or(CommandError::Syntax)?;
+        let mut approx = false;
+        if threshold == b"~" || threshold == b"=" {
+            approx = threshold == b"~";
+            threshold = args.next().ok_or(CommandError::Syntax)?;
+        }
+        let strategy = match strategy.eq_ignore_ascii_case(b"MAXLEN") {
+            true => {
+                let maxlen = usize::try_from(parse_int(&threshold)?).map_err(|_| {
+                    CommandError::InvalidArgument("The MAXLEN argument must be >= 0.".to_string())
+                })?;
+                TrimStrategy::MaxLen(maxlen)
+            }
+            false => TrimStrategy::MinId(parse_id(&threshold, 0)?),
+        };
+
+        // 和 Redis 一样，近似裁剪默认最多删除 100 个节点的 entry，LIMIT 0 表示不限制
+        let mut limit = approx.then_some(100 * STREAM_NODE_MAX_ENTRIES);
+        let rest = args.as_slice();
+        if rest.len() >= 2 && rest[0].eq_ignore_ascii_case(b"LIMIT") {
+            let count = parse_int(&rest[1])?;
+            args.nth(1);
+            if !approx {
+                return Err(CommandError::InvalidArgument(
+                    "syntax error, LIMIT cannot be used without the special ~ option".to_string(),
+                ));
+            }
+            let count = usize::try_from(count).map_err(|_| {
+                CommandError::InvalidArgument("The LIMIT argument must be >= 0.".to_string())
+            })?;
+            limit = (count > 0).then_some(count);
+        }
+        Ok(TrimOptions {
+            strategy,
+            approx,
+            limit,
+        })
+    }
+}
+
+/// 返回 entry 列表，每条 entry 是 [id, [field, value, ...]]
+pub(crate) fn entries_array(entries: Vec<(StreamId, StreamFields)>) -> RespFrame {
+    let entries: Vec<RespFrame> = entries
+        .into_iter()
+        .map(|(id, fields)| entry_frame(id, Some(fields)))
+        .collect();
+    RespArray::new(entries).into()
+}
+
+/// 一条 entry，PEL 中的 entry 已经从 stream 中删除时 fields 为 None，返回 [id, nil]
+fn entry_frame(id: StreamId, fields: Option<StreamFields>) -> RespFrame {
+    let fields = match fields {
+        Some(fields) => {
+            let fields: Vec<RespFrame> = fields
+                .into_iter()
+                .flat_map(|(field, value)| {
+                    [BulkString::new(field).into(), BulkString::new(value).into()]
+                })
+                .collect();
+            RespArray::new(fields).into()
+        }
+        None => RespNullArray.into(),
+    };
+    RespArray::new([id_frame(id), fields]).into()
+}
+
+/// 解析 `ms-seq` 或者 `ms` 形式的 ID，没有 seq 时使用 default_seq
+pub(crate) fn parse_id(arg: &[u8], default_seq: u64) -> Result<StreamId, CommandError> {
+    StreamId::parse(arg, default_seq).ok_or_else(invalid_id)
+}
+
+/// 解析 XRANGE 的边界，支持 `-`、`+` 以及 `(` 前缀表示不包含
+pub(crate) fn parse_range_id(
+    arg: &[u8],
+    default_seq: u64,
+) -> Result<Bound<StreamId>, CommandError> {
+    match arg {
+        b"-" => Ok(Bound::Included(StreamId::MIN)),
+        b"+" => Ok(Bound::Included(StreamId::MAX)),
+        [b'(', id @ ..] => Ok(Bound::Excluded(parse_id(id, default_seq)?)),
+        _ => Ok(Bound::Included(parse_id(arg, default_seq)?)),
+    }
+}
+
+fn parse_xadd_id(arg: &[u8]) -> Result<XAddId, CommandError> {
+    if arg == b"*" {
+        return Ok(XAddId::Auto);
+    }
+    if let Some(ms) = arg.strip_suffix(b"-*") {
+        let id = parse_id(ms, 0)?;
+        // `ms-seq-*` 不是合法的 ID
+        if ms.contains(&b'-') {
+            return Err(invalid_id());
+        }
+        return Ok(XAddId::Seq(id.ms));
+    }
+    match parse_id(arg, 0)? {
+        StreamId::MIN => Err(CommandError::InvalidArgument(
+            "The ID specified in XADD must be greater than 0-0".to_string(),
+        )),
+        id => Ok(XAddId::Explicit(id)),
+    }
+}
+
+fn invalid_id() -> CommandError {
+    CommandError::InvalidArgument(
+        "Invalid stream ID specified as stream command argument".to_string(),
+    )
+}
+
+fn id_too_small() -> CommandError {
+    CommandError::InvalidArgument(
+        "The ID specified in XADD is equal or smaller than the target stream top item".to_string(),
+    )
+}
+
+/// XREAD/XREADGROUP 读取一个 stream，没有数据时返回 None
+///
+/// 阻塞的客户端被唤醒时也会调用，只有确实有新的 entry 时才修改 stream，
+/// 否则写入会再次标记 key 为 ready，导致反复唤醒
+fn read_stream(
+    backend: &Backend,
+    key: &[u8],
+    id: ReadId,
+    group: Option<&ReadGroup>,
+    count: Option<usize>,
+) -> Result<Option<RespFrame>, BackendError> {
+    let now = now_ms();
+    let entries = match (group, id) {
+        (None, ReadId::After(id)) => backend
+            .read_value(key, |stream: &Stream| {
+                stream.range(Bound::Excluded(id), Bound::Unbounded, false, count)
+            })?
+            .unwrap_or_default(),
+        (Some(group), ReadId::New) => {
+            let has_new = backend.read_value(key, |stream: &Stream| {
+                stream.group(&group.group).map(|g| {
+                    let start = Bound::Excluded(g.last_id());
+                    !stream
+                        .range(start, Bound::Unbounded, false, Some(1))
+                        .is_empty()
+                })
+            })?;
+            match has_new {
+                Some(Some(true)) => {}
+                // 阻塞期间消费组被删除 (XGROUP DESTROY)，客户端收到错误
+                Some(None) => return Err(no_read_group(key, &group.group)),
+                _ => return Ok(None),
+            }
+            backend
+                .write_value(key, false, |stream: &mut Stream| {
+                    let entries =
+                        stream.read_group(&group.group, &group.consumer, count, group.noack, now);
+                    let found = entries.is_some();
+                    (entries, found)
+                })?
+                .flatten()
+                .unwrap_or_default()
+        }
+        // 读取 PEL 中的历史，即使没有数据也返回
+        (Some(group), ReadId::After(id)) => {
+            let entries = backend
+                .write_value(key, false, |stream: &mut Stream| {
+                    let entries =
+                        stream.read_pending(&group.group, &group.consumer, id, count, now);
+                    let found = entries.is_some();
+                    (entries, found)
+                })?
+                .flatten()
+                .unwrap_or_default();
+            let entries: Vec<RespFrame> = entries
+                .into_iter()
+                .map(|(id, fields)| entry_frame(id, fields))
+                .collect();
+            return Ok(Some(RespArray::new(entries).into()));
+        }
+        // `$` 在读取之前已经替换成了具体的 ID
+        _ => Vec::new(),
+    };
+    Ok((!entries.is_empty()).then(|| entries_array(entries)))
+}
+
+/// XREAD/XREADGROUP 的返回值，RESP3 中是 key -> entries 的 map
+fn streams_reply(streams: Vec<(Vec<u8>, RespFrame)>, protocol: Protocol) -> RespFrame {
+    match protocol {
+        Protocol::Resp2 => {
+            let streams: Vec<RespFrame> = streams
+                .into_iter()
+                .map(|(key, entries)| RespArray::new([BulkString::new(key).into(), entries]).into())
+                .collect();
+            RespArray::new(streams).into()
+        }
+        Protocol::Resp3 => {
+            let mut map = RespMap::new();
+            map.extend(streams);
+            map.into()
+        }
+    }
+}
+
+/// XPENDING 的汇总信息：[数量, 最小 ID, 最大 ID, [[consumer, 数量], ...]]
+fn pending_summary(group: &ConsumerGroup) -> RespFrame {
+    let (Some(first), Some(last)) = (group.first_pending(), group.last_pending()) else {
+        return RespArray::new([
+            0.into(),
+            RespNullBulkString.into(),
+            RespNullBulkString.into(),
+            RespNullArray.into(),
+        ])
+        .into();
+    };
+    let consumers: Vec<RespFrame> = group
+        .consumers()
+        .filter(|(_, consumer)| consumer.pending_len() > 0)
+        .map(|(name, consumer)| {
+            RespArray::new([
+                BulkString::new(name.as_str()).into(),
+                BulkString::new(consumer.pending_len().to_string()).into(),
+            ])
+            .into()
+        })
+        .collect();
+    RespArray::new([
+        (group.pending_len() as i64).into(),
+        id_frame(first),
+        id_frame(last),
+        RespArray::new(consumers).into(),
+    ])
+    .into()
+}
+
+/// XPENDING 的范围查询：[[id, consumer, 空闲时间, 分发次数], ...]
+fn pending_entries(group: &ConsumerGroup, range: &PendingRange, now: u64) -> RespFrame {
+    let mut entries: Vec<RespFrame> = Vec::new();
+    if range.count > 0 {
+        let consumer = range.consumer.as_deref();
+        group.pending_range(range.start, range.end, consumer, |id, entry| {
+            let idle = now.saturating_sub(entry.delivery_time);
+            if idle >= range.min_idle {
+                entries.push(
+                    RespArray::new([
+                        id_frame(id),
+                        BulkString::new(entry.consumer.as_str()).into(),
+                        (idle as i64).into(),
+                        (entry.delivery_count as i64).into(),
+                    ])
+                    .into(),
+                );
+            }
+            entries.len() < range.count
+        });
+    }
+    RespArray::new(entries).into()
+}
+
+/// XCLAIM/XAUTOCLAIM 认领到的 entry，JUSTID 时只返回 ID
+fn claimed_array(entries: Vec<(StreamId, StreamFields)>, just_id: bool) -> RespFrame {
+    match just_id {
+        true => {
+            let ids: Vec<RespFrame> = entries.into_iter().map(|(id, _)| id_frame(id)).collect();
+            RespArray::new(ids).into()
+        }
+        false => entries_array(entries),
+    }
+}
+
+/// XINFO STREAM，full 为 Some 时返回 FULL 格式，最多包含 count 条 entry
+fn stream_info(stream: &Stream, full: Option<Option<usize>>) -> RespFrame {
+    let mut map = RespMap::new();
+    map.insert("length".to_string(), (stream.len() as i64).into());
+    map.insert("radix-tree-keys".to_string(), (stream.len() as i64).into());
+    map.insert(
+        "radix-tree-nodes".to_string(),
+        (stream.node_count() as i64).into(),
+    );
+    map.insert("last-generated-id".to_string(), id_frame(stream.last_id()));
+    map.insert(
+        "max-deleted-entry-id".to_string(),
+        id_frame(stream.max_deleted_id()),
+    );
+    map.insert(
+        "entries-added".to_string(),
+        (stream.entries_added() as i64).into(),
+    );
+    let first = stream.first_entry().map(|(id, _)| id).unwrap_or_default();
+    map.insert("recorded-first-entry-id".to_string(), id_frame(first));
+
+    let entry = |entry: Option<(StreamId, &StreamFields)>| match entry {
+        Some((id, fields)) => entry_frame(id, Some(fields.clone())),
+        None => RespNull.into(),
+    };
+    match full {
+        None => {
+            map.insert(
+                "groups".to_string(),
+                (stream.groups().count() as i64).into(),
+            );
+            map.insert("first-entry".to_string(), entry(stream.first_entry()));
+            map.insert("last-entry".to_string(), entry(stream.last_entry()));
+        }
+        Some(count) => {
+            let entries = stream.range(Bound::Unbounded, Bound::Unbounded, false, count);
+            map.insert("entries".to_string(), entries_array(entries));
+            let groups: Vec<RespFrame> = stream
+                .groups()
+                .map(|(name, group)| group_full_info(stream, name, group))
+                .collect();
+            map.insert("groups".to_string(), RespArray::new(groups).into());
+        }
+    }
+    map.into()
+}
+
+/// XINFO GROUPS 中的一个消费组
+fn group_info(stream: &Stream, name: &str, group: &ConsumerGroup) -> RespFrame {
+    let mut map = RespMap::new();
+    map.insert("name".to_string(), BulkString::new(name).into());
+    map.insert(
+        "consumers".to_string(),
+        (group.consumers().count() as i64).into(),
+    );
+    map.insert("pending".to_string(), (group.pending_len() as i64).into());
+    map.insert("last-delivered-id".to_string(), id_frame(group.last_id()));
+    map.insert("entries-read".to_string(), optional(group.entries_read()));
+    map.insert("lag".to_string(), optional(stream.group_lag(group)));
+    map.into()
+}
+
+/// XINFO STREAM FULL 中的一个消费组，包含完整的 PEL 和消费者
+fn group_full_info(stream: &Stream, name: &str, group: &ConsumerGroup) -> RespFrame {
+    let mut pending = Vec::new();
+    group.pending_range(Bound::Unbounded, Bound::Unbounded, None, |id, entry| {
+        pending.push(
+            RespArray::new([
+                id_frame(id),
+                BulkString::new(entry.consumer.as_str()).into(),
+                (entry.delivery_time as i64).into(),
+                (entry.delivery_count as i64).into(),
+            ])
+            .into(),
+        );
+        true
+    });
+    let consumers: Vec<RespFrame> = group
+        .consumers()
+        .map(|(consumer_name, consumer)| {
+            let mut pending = Vec::new();
+            let range = (Bound::Unbounded, Bound::Unbounded);
+            group.pending_range(range.0, range.1, Some(consumer_name), |id, entry| {
+                pending.push(
+                    RespArray::new([
+                        id_frame(id),
+                        (entry.delivery_time as i64).into(),
+                        (entry.delivery_count as i64).into(),
+                    ])
+                    .into(),
+                );
+                true
+            });
+            let mut map = RespMap::new();
+            map.insert(
+                "name".to_string(),
+                BulkString::new(consumer_name.as_str()).into(),
+            );
+            map.insert("seen-time".to_string(), (consumer.seen_time as i64).into());
+            let active_time = consumer.active_time.map_or(-1, |t| t as i64);
+            map.insert("active-time".to_string(), active_time.into());
+            map.insert(
+                "pel-count".to_string(),
+                (consumer.pending_len() as i64).into(),
+            );
+            map.insert("pending".to_string(), RespArray::new(pending).into());
+            map.into()
+        })
+        .collect();
+
+    let mut map = RespMap::new();
+    map.insert("name".to_string(), BulkString::new(name).into());
+    map.insert("last-delivered-id".to_string(), id_frame(group.last_id()));
+    map.insert("entries-read".to_string(), optional(group.entries_read()));
+    map.insert("lag".to_string(), optional(stream.group_lag(group)));
+    map.insert("pel-count".to_string(), (group.pending_len() as i64).into());
+    map.insert("pending".to_string(), RespArray::new(pending).into());
+    map.insert("consumers".to_string(), RespArray::new(consumers).into());
+    map.into()
+}
+
+/// XINFO CONSUMERS 中的一个消费者，inactive 为 -1 表示从来没有读取到过 entry
+fn consumer_info(name: &str, consumer: &Consumer, now: u64) -> RespFrame {
+    let mut map = RespMap::new();
+    map.insert("name".to_string(), BulkString::new(name).into());
+    map.insert(
+        "pending".to_string(),
+        (consumer.pending_len() as i64).into(),
+    );
+    let idle = now.saturating_sub(consumer.seen_time);
+    map.insert("idle".to_string(), (idle as i64).into());
+    let inactive = consumer
+        .active_time
+        .map_or(-1, |t| now.saturating_sub(t) as i64);
+    map.insert("inactive".to_string(), inactive.into());
+    map.into()
+}
+
+fn optional(value: Option<u64>) -> RespFrame {
+    match value {
+        Some(value) => (value as i64).into(),
+        None => RespNull.into(),
+    }
+}
+
+fn id_frame(id: StreamId) -> RespFrame {
+    BulkString::new(id.to_string()).into()
+}
+
+fn no_read_group(key: &[u8], group: &str) -> BackendError {
+    BackendError::NoGroup(format!(
+        "No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+        String::from_utf8_lossy(key),
+        group
+    ))
+}
+
+fn no_such_group(key: &[u8], group: &str) -> BackendError {
+    BackendError::NoGroup(format!(
+        "No such key '{}' or consumer group '{}'",
+        String::from_utf8_lossy(key),
+        group
+    ))
+}
+
+/// XGROUP CREATE/SETID 的 ID，`$` 表示 stream 最后的 ID，返回 None
+fn parse_group_id(arg: &[u8]) -> Result<Option<StreamId>, CommandError> {
+    match arg {
+        b"$" => Ok(None),
+        _ => Ok(Some(parse_id(arg, 0)?)),
+    }
+}
+
+/// XCLAIM/XAUTOCLAIM 的 min-idle-time，负数当作 0
+fn parse_min_idle(arg: &[u8], name: &str) -> Result<u64, CommandError> {
+    let min_idle = parse_int(arg).map_err(|_| {
+        CommandError::InvalidArgument(format!("Invalid min-idle-time argument for {}", name))
+    })?;
+    Ok(min_idle.max(0) as u64)
+}
+
+/// 解析 BLOCK 的超时时间 (毫秒)，0 表示一直等待
+fn parse_block_timeout(arg: &[u8]) -> Result<Option<Duration>, CommandError> {
+    let ms = parse_int(arg).map_err(|_| {
+        CommandError::InvalidArgument("timeout is not an integer or out of range".to_string())
+    })?;
+    if ms < 0 {
+        return Err(CommandError::InvalidArgument(
+            "timeout is negative".to_string(),
+        ));
+    }
+    Ok((ms > 0).then(|| Duration::from_millis(ms as u64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::{run, run_blocking};
+    use anyhow::Result;
+
+    fn bulk(v: &str) -> RespFrame {
+        BulkString::new(v).into()
+    }
+
+    fn entry(id: &str, fields: &[&str]) -> RespFrame {
+        let fields: Vec<RespFrame> = fields.iter().map(|v| bulk(v)).collect();
+        RespArray::new([bulk(id), RespArray::new(fields).into()]).into()
+    }
+
+    fn error(ret: Result<RespFrame>) -> Result<CommandError> {
+        ret.unwrap_err().downcast::<CommandError>()
+    }
+
+    fn array<const N: usize>(frames: [RespFrame; N]) -> RespFrame {
+        RespArray::new(frames).into()
+    }
+
+    /// XREAD/XREADGROUP 在 RESP2 中返回的 [[key, entries]]
+    fn read_reply(key: &str, entries: Vec<RespFrame>) -> RespFrame {
+        array([array([bulk(key), RespArray::new(entries).into()])])
+    }
+
+    fn blocked(outcome: Outcome) -> crate::Waiter {
+        match outcome {
+            Outcome::Block(waiter, _) => waiter,
+            Outcome::Reply(frame) => panic!("expected blocking, got {:?}", frame),
+            Outcome::Replies(frames) => panic!("expected blocking, got {:?}", frames),
+            Outcome::Replicate(feed) => panic!("expected blocking, got {:?}", feed),
+            Outcome::WaitAck(wait) => panic!("expected blocking, got {:?}", wait),
+        }
+    }
+
+    fn add_entries(backend: &Backend, key: &str, n: u64) -> Result<()> {
+        for i in 1..=n {
+            let id = format!("{}-0", i);
+            run(backend, &["XADD", key, &id, "f", &i.to_string()])?;
+        }
+        Ok(())
+    }
+
+    fn map_get(frame: &RespFrame, key: &str) -> RespFrame {
+        match frame {
+            RespFrame::Map(map) => map.get(key.as_bytes()).cloned().unwrap_or(RespNull.into()),
+            _ => panic!("expected map, got {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_xadd_ids() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["XADD", "s", "1-1", "f", "v"])?, bulk("1-1"));
+        assert_eq!(run(&backend, &["XADD", "s", "1-*", "f", "v"])?, bulk("1-2"));
+        assert_eq!(run(&backend, &["XADD", "s", "5-*", "f", "v"])?, bulk("5-0"));
+        assert_eq!(run(&backend, &["XADD", "s", "6", "f", "v"])?, bulk("6-0"));
+        assert_eq!(run(&backend, &["XADD", "e", "0-*", "f", "v"])?, bulk("0-1"));
+
+        let RespFrame::BulkString(id) = run(&backend, &["XADD", "s", "*", "a", "1", "b", "2"])?
+        else {
+            panic!("XADD should return the id");
+        };
+        let id = StreamId::parse(&id, 0).expect("valid id");
+        assert!(id > StreamId::new(6, 0));
+        assert_eq!(run(&backend, &["XLEN", "s"])?, 5.into());
+
+        // ID 不能小于等于最后一个 ID
+        let too_small = id_too_small().into();
+        assert_eq!(run(&backend, &["XADD", "s", "6-0", "f", "v"])?, too_small);
+        assert_eq!(run(&backend, &["XADD", "s", "5-*", "f", "v"])?, too_small);
+        let max = format!("{}-{}", u64::MAX, u64::MAX);
+        run(&backend, &["XADD", "m", &max, "f", "v"])?;
+        let ret = run(&backend, &["XADD", "m", "*", "f", "v"])?;
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("exhausted")));
+
+        let ret = run(&backend, &["XADD", "missing", "NOMKSTREAM", "*", "f", "v"])?;
+        assert_eq!(ret, RespNullBulkString.into());
+        assert!(!backend.exists(b"missing"));
+
+        assert_eq!(
+            error(run(&backend, &["XADD", "s", "0-0", "f", "v"]))?,
+            CommandError::InvalidArgument(
+                "The ID specified in XADD must be greater than 0-0".to_string()
+            )
+        );
+        for id in ["abc", "1-2-*", "-1", "1-x"] {
+            let ret = run(&backend, &["XADD", "s", id, "f", "v"]);
+            assert_eq!(error(ret)?, invalid_id(), "{}", id);
+        }
+        let ret = run(&backend, &["XADD", "s", "*", "f"]);
+        assert_eq!(error(ret)?, CommandError::WrongArity("xadd".to_string()));
+
+        run(&backend, &["SET", "str", "v"])?;
+        let ret = run(&backend, &["XADD", "str", "*", "f", "v"])?;
+        assert_eq!(ret, BackendError::WrongType.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xrange_xdel() -> Result<()> {
+        let backend = Backend::new();
+        for (id, v) in [("1-0", "a"), ("1-1", "b"), ("2-0", "c"), ("3-5", "d")] {
+            run(&backend, &["XADD", "s", id, "f", v])?;
+        }
+        let args = ["XRANGE", "s", "-", "+", "COUNT", "2"];
+        assert_eq!(
+            run(&backend, &args)?,
+            RespArray::new([entry("1-0", &["f", "a"]), entry("1-1", &["f", "b"])]).into()
+        );
+        // 省略 seq 时 start 从 0 开始，end 到最大值
+        assert_eq!(
+            run(&backend, &["XRANGE", "s", "1", "1"])?,
+            RespArray::new([entry("1-0", &["f", "a"]), entry("1-1", &["f", "b"])]).into()
+        );
+        assert_eq!(
+            run(&backend, &["XREVRANGE", "s", "+", "(1-1"])?,
+            RespArray::new([entry("3-5", &["f", "d"]), entry("2-0", &["f", "c"])]).into()
+        );
+        let args = ["XRANGE", "s", "-", "+", "COUNT", "0"];
+        assert_eq!(run(&backend, &args)?, RespArray::new(vec![]).into());
+        assert_eq!(
+            run(&backend, &["XRANGE", "x", "-", "+"])?,
+            RespArray::new(vec![]).into()
+        );
+
+        assert_eq!(
+            run(&backend, &["XDEL", "s", "1-1", "9-9", "2-0"])?,
+            2.into()
+        );
+        assert_eq!(run(&backend, &["XLEN", "s"])?, 2.into());
+        assert_eq!(run(&backend, &["XDEL", "s", "1-0", "3-5"])?, 2.into());
+        // 和 Redis 一样，entry 全部删除之后 stream 仍然存在，ID 也不会回退
+        assert!(backend.exists(b"s"));
+        assert_eq!(run(&backend, &["XLEN", "s"])?, 0.into());
+        assert_eq!(run(&backend, &["XADD", "s", "3-*", "f", "v"])?, bulk("3-6"));
+
+        let ret = run(&backend, &["XRANGE", "s", "(-", "+"]);
+        assert_eq!(error(ret)?, invalid_id());
+        let ret = run(&backend, &["XRANGE", "s", "-", "+", "LIMIT", "1"]);
+        assert_eq!(error(ret)?, CommandError::Syntax);
+        Ok(())
+    }
+
+    #[test]
+    fn test_xtrim() -> Result<()> {
+        let backend = Backend::new();
+        for i in 1..=250 {
+            run(&backend, &["XADD", "s", &format!("{}-0", i), "f", "v"])?;
+        }
+        assert_eq!(
+            run(&backend, &["XTRIM", "s", "MAXLEN", "~", "120"])?,
+            100.into()
+        );
+        let args = ["XTRIM", "s", "MAXLEN", "~", "0", "LIMIT", "30"];
+        assert_eq!(run(&backend, &args)?, 30.into());
+        assert_eq!(
+            run(&backend, &["XTRIM", "s", "MAXLEN", "=", "100"])?,
+            20.into()
+        );
+        assert_eq!(run(&backend, &["XTRIM", "s", "MINID", "200"])?, 49.into());
+        assert_eq!(run(&backend, &["XLEN", "s"])?, 51.into());
+
+        let args = ["XADD", "s", "MAXLEN", "2", "*", "f", "v"];
+        run(&backend, &args)?;
+        assert_eq!(run(&backend, &["XLEN", "s"])?, 2.into());
+        let args = [
+            "XADD", "s", "MINID", "~", "1000", "LIMIT", "0", "*", "f", "v",
+        ];
+        run(&backend, &args)?;
+        assert_eq!(run(&backend, &["XLEN", "s"])?, 3.into());
+        assert_eq!(
+            run(&backend, &["XTRIM", "missing", "MAXLEN", "0"])?,
+            0.into()
+        );
+
+        // 近似裁剪没有 LIMIT 时默认最多删除 100 个节点，LIMIT 0 不限制
+        let parse = |args: &[&str]| {
+            let mut args = args
+                .iter()
+                .map(|arg| arg.as_bytes().to_vec())
+                .collect::<Vec<_>>()
+                .into_iter();
+            TrimOptions::parse(b"MAXLEN".to_vec(), &mut args)
+        };
+        assert_eq!(
+            parse(&["~", "0"])?.limit,
+            Some(100 * STREAM_NODE_MAX_ENTRIES)
+        );
+        assert_eq!(parse(&["~", "0", "LIMIT", "0"])?.limit, None);
+        assert_eq!(parse(&["0"])?.limit, None);
+
+        let ret = run(&backend, &["XTRIM", "s", "MAXLEN", "10", "LIMIT", "5"]);
+        assert_eq!(
+            error(ret)?,
+            CommandError::InvalidArgument(
+                "syntax error, LIMIT cannot be used without the special ~ option".to_string()
+            )
+        );
+        let ret = run(&backend, &["XTRIM", "s", "MAXLEN", "-1"]);
+        assert_eq!(
+            error(ret)?,
+            CommandError::InvalidArgument("The MAXLEN argument must be >= 0.".to_string())
+        );
+        let ret = run(&backend, &["XTRIM", "s", "SIZE", "1"]);
+        assert_eq!(error(ret)?, CommandError::Syntax);
+        Ok(())
+    }
+
+    #[test]
+    fn test_xgroup() -> Result<()> {
+        let backend = Backend::new();
+        let ok: RespFrame = SimpleString::new("OK").into();
+        let ret = run(&backend, &["XGROUP", "CREATE", "s", "g", "$"])?;
+        assert!(
+            matches!(ret, RespFrame::Error(e) if e.starts_with("ERR The XGROUP subcommand requires the key to exist"))
+        );
+        let args = ["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"];
+        assert_eq!(run(&backend, &args)?, ok);
+        // 只有消费组的空 stream 不会被删除
+        assert_eq!(run(&backend, &["XLEN", "s"])?, 0.into());
+        assert_eq!(run(&backend, &args)?, BackendError::BusyGroup.into());
+
+        add_entries(&backend, "s", 3)?;
+        let args = ["XGROUP", "SETID", "s", "g", "1-0", "ENTRIESREAD", "1"];
+        assert_eq!(run(&backend, &args)?, ok);
+        let args = ["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", ">"];
+        let ret = run(&backend, &args)?;
+        assert_eq!(
+            ret,
+            read_reply(
+                "s",
+                vec![entry("2-0", &["f", "2"]), entry("3-0", &["f", "3"])]
+            )
+        );
+        let ret = run(&backend, &["XGROUP", "SETID", "s", "missing", "0"])?;
+        assert_eq!(
+            ret,
+            BackendError::NoGroup("No such consumer group 'missing' for key name 's'".to_string())
+                .into()
+        );
+
+        let args = ["XGROUP", "CREATECONSUMER", "s", "g", "other"];
+        assert_eq!(run(&backend, &args)?, 1.into());
+        assert_eq!(run(&backend, &args)?, 0.into());
+        let args = ["XGROUP", "DELCONSUMER", "s", "g", "c"];
+        assert_eq!(run(&backend, &args)?, 2.into());
+        assert_eq!(run(&backend, &args)?, 0.into());
+        let empty = array([
+            0.into(),
+            RespNullBulkString.into(),
+            RespNullBulkString.into(),
+            RespNullArray.into(),
+        ]);
+        assert_eq!(run(&backend, &["XPENDING", "s", "g"])?, empty);
+        assert_eq!(run(&backend, &["XGROUP", "DESTROY", "s", "g"])?, 1.into());
+        assert_eq!(run(&backend, &["XGROUP", "DESTROY", "s", "g"])?, 0.into());
+
+        let ret = run(
+            &backend,
+            &["XGROUP", "CREATE", "s", "g", "0", "ENTRIESREAD", "-2"],
+        );
+        assert_eq!(
+            error(ret)?,
+            CommandError::InvalidArgument(
+                "value for ENTRIESREAD must be positive or -1".to_string()
+            )
+        );
+        let ret = run(&backend, &["XGROUP", "DESTROY", "s"]);
+        assert_eq!(
+            error(ret)?,
+            CommandError::WrongArity("xgroup|destroy".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_xreadgroup_xack() -> Result<()> {
+        let backend = Backend::new();
+        add_entries(&backend, "s", 3)?;
+        run(&backend, &["XGROUP", "CREATE", "s", "g", "0"])?;
+
+        let args = [
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "a",
+            "COUNT",
+            "2",
+            "STREAMS",
+            "s",
+            ">",
+        ];
+        let ret = run(&backend, &args)?;
+        assert_eq!(
+            ret,
+            read_reply(
+                "s",
+                vec![entry("1-0", &["f", "1"]), entry("2-0", &["f", "2"])]
+            )
+        );
+        // NOACK 读取的 entry 不进入 PEL
+        let args = [
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "b",
+            "NOACK",
+            "STREAMS",
+            "s",
+            ">",
+        ];
+        assert_eq!(
+            run(&backend, &args)?,
+            read_reply("s", vec![entry("3-0", &["f", "3"])])
+        );
+        assert_eq!(run(&backend, &args)?, RespNullArray.into());
+
+        let ret = run(&backend, &["XPENDING", "s", "g"])?;
+        let consumers = array([array([bulk("a"), bulk("2")])]);
+        assert_eq!(ret, array([2.into(), bulk("1-0"), bulk("2-0"), consumers]));
+
+        // 读取历史，已经被删除的 entry 返回 nil
+        run(&backend, &["XDEL", "s", "1-0"])?;
+        let args = ["XREADGROUP", "GROUP", "g", "a", "STREAMS", "s", "0"];
+        let deleted = array([bulk("1-0"), RespNullArray.into()]);
+        assert_eq!(
+            run(&backend, &args)?,
+            read_reply("s", vec![deleted, entry("2-0", &["f", "2"])])
+        );
+        let args = ["XPENDING", "s", "g", "-", "+", "10", "a"];
+        let RespFrame::Array(pending) = run(&backend, &args)? else {
+            panic!("XPENDING should return an array");
+        };
+        assert_eq!(pending.len(), 2);
+        // idle 时间和执行速度有关，只检查 id、consumer 和投递次数
+        let RespFrame::Array(second) = &pending[1] else {
+            panic!("pending entry should be an array");
+        };
+        assert_eq!(second[..2], [bulk("2-0"), bulk("a")]);
+        assert_eq!(second[3], 2.into());
+
+        let args = ["XACK", "s", "g", "1-0", "2-0", "3-0"];
+        assert_eq!(run(&backend, &args)?, 2.into());
+        assert_eq!(run(&backend, &["XACK", "s", "missing", "1-0"])?, 0.into());
+        let args = ["XREADGROUP", "GROUP", "g", "a", "STREAMS", "s", "0"];
+        assert_eq!(run(&backend, &args)?, read_reply("s", vec![]));
+
+        // XREAD 不使用消费组
+        let args = ["XREAD", "COUNT", "1", "STREAMS", "s", "missing", "0", "0"];
+        assert_eq!(
+            run(&backend, &args)?,
+            read_reply("s", vec![entry("2-0", &["f", "2"])])
+        );
+        assert_eq!(
+            run(&backend, &["XREAD", "STREAMS", "s", "$"])?,
+            RespNullArray.into()
+        );
+
+        let args = ["XREADGROUP", "GROUP", "missing", "a", "STREAMS", "s", ">"];
+        assert_eq!(
+            run(&backend, &args)?,
+            BackendError::NoGroup(
+                "No such key 's' or consumer group 'missing' in XREADGROUP with GROUP option"
+                    .to_string()
+            )
+            .into()
+        );
+        let ret = run(
+            &backend,
+            &["XREADGROUP", "GROUP", "g", "a", "STREAMS", "s", "$"],
+        );
+        assert_eq!(
+            error(ret)?,
+            CommandError::InvalidArgument(
+                "The $ ID is meaningful only for XREAD command".to_string()
+            )
+        );
+        let ret = run(&backend, &["XREAD", "STREAMS", "s", "t", "0"]);
+        assert_eq!(
+            error(ret)?,
+            CommandError::InvalidArgument(
+                "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+                    .to_string()
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xread_blocking() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"])?;
+        let args = [
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "c",
+            "BLOCK",
+            "0",
+            "STREAMS",
+            "s",
+            ">",
+        ];
+        let group = blocked(run_blocking(&backend, &args)?);
+        let args = ["XREAD", "BLOCK", "1000", "STREAMS", "t", "s", "$", "$"];
+        let reader = blocked(run_blocking(&backend, &args)?);
+
+        run(&backend, &["XADD", "s", "1-0", "f", "v"])?;
+        backend.serve_blocked_clients();
+        let expected = read_reply("s", vec![entry("1-0", &["f", "v"])]);
+        assert_eq!(group.wait(None).await, Some(expected.clone()));
+        assert_eq!(reader.wait(None).await, Some(expected));
+        let ret = run(&backend, &["XPENDING", "s", "g"])?;
+        let consumers = array([array([bulk("c"), bulk("1")])]);
+        assert_eq!(ret, array([1.into(), bulk("1-0"), bulk("1-0"), consumers]));
+
+        // 读取历史时不会阻塞
+        let args = [
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "c",
+            "BLOCK",
+            "0",
+            "STREAMS",
+            "s",
+            "1-0",
+        ];
+        let Outcome::Reply(reply) = run_blocking(&backend, &args)? else {
+            panic!("XREADGROUP should not block when reading history");
+        };
+        assert_eq!(reply, read_reply("s", vec![]));
+        let args = ["XREAD", "BLOCK", "10", "STREAMS", "s", "$"];
+        let waiter = blocked(run_blocking(&backend, &args)?);
+        assert_eq!(waiter.wait(Some(Duration::from_millis(10))).await, None);
+
+        // 阻塞期间消费组被删除时收到错误
+        let args = [
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "c",
+            "BLOCK",
+            "0",
+            "STREAMS",
+            "s",
+            ">",
+        ];
+        let group = blocked(run_blocking(&backend, &args)?);
+        run(&backend, &["XGROUP", "DESTROY", "s", "g"])?;
+        backend.serve_blocked_clients();
+        assert_eq!(
+            group.wait(None).await,
+            Some(
+                BackendError::NoGroup(
+                    "No such key 's' or consumer group 'g' in XREADGROUP with GROUP option"
+                        .to_string()
+                )
+                .into()
+            )
+        );
+        assert!(backend.blocking.is_empty());
+
+        let args = ["XREAD", "BLOCK", "-1", "STREAMS", "s", "$"];
+        let ret = run_blocking(&backend, &args);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>()?,
+            CommandError::InvalidArgument("timeout is negative".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_xclaim_xautoclaim() -> Result<()> {
+        let backend = Backend::new();
+        add_entries(&backend, "s", 5)?;
+        run(&backend, &["XGROUP", "CREATE", "s", "g", "0"])?;
+        run(
+            &backend,
+            &["XREADGROUP", "GROUP", "g", "a", "STREAMS", "s", ">"],
+        )?;
+
+        // 空闲时间不够的 entry 不会被认领
+        let args = ["XCLAIM", "s", "g", "b", "3600000", "1-0"];
+        assert_eq!(run(&backend, &args)?, array([]));
+        let args = ["XCLAIM", "s", "g", "b", "0", "1-0", "2-0", "IDLE", "5000"];
+        assert_eq!(
+            run(&backend, &args)?,
+            array([entry("1-0", &["f", "1"]), entry("2-0", &["f", "2"])])
+        );
+        let args = ["XPENDING", "s", "g", "IDLE", "4000", "-", "+", "10"];
+        let RespFrame::Array(pending) = run(&backend, &args)? else {
+            panic!("XPENDING should return an array");
+        };
+        assert_eq!(pending.len(), 2);
+        let RespFrame::Array(first) = &pending[0] else {
+            panic!("pending entry should be an array");
+        };
+        assert_eq!(first[1], bulk("b"));
+        assert_eq!(first[3], 2.into());
+
+        let args = [
+            "XCLAIM",
+            "s",
+            "g",
+            "c",
+            "0",
+            "3-0",
+            "RETRYCOUNT",
+            "7",
+            "JUSTID",
+        ];
+        assert_eq!(run(&backend, &args)?, array([bulk("3-0")]));
+        let args = ["XPENDING", "s", "g", "(2-0", "3-0", "10", "c"];
+        let RespFrame::Array(pending) = run(&backend, &args)? else {
+            panic!("XPENDING should return an array");
+        };
+        assert_eq!(pending.len(), 1);
+        let RespFrame::Array(entry3) = &pending[0] else {
+            panic!("pending entry should be an array");
+        };
+        assert_eq!(entry3[3], 7.into());
+
+        // FORCE 可以认领不在 PEL 中的 entry，已经删除的 entry 从 PEL 中移除
+        run(&backend, &["XACK", "s", "g", "4-0"])?;
+        run(&backend, &["XDEL", "s", "5-0"])?;
+        let args = [
+            "XCLAIM", "s", "g", "c", "0", "4-0", "5-0", "FORCE", "JUSTID",
+        ];
+        assert_eq!(run(&backend, &args)?, array([bulk("4-0")]));
+        let ret = run(&backend, &["XPENDING", "s", "g"])?;
+        let consumers = array([array([bulk("b"), bulk("2")]), array([bulk("c"), bulk("2")])]);
+        assert_eq!(ret, array([4.into(), bulk("1-0"), bulk("4-0"), consumers]));
+
+        let args = ["XAUTOCLAIM", "s", "g", "d", "0", "-", "COUNT", "3"];
+        let claimed = array([
+            entry("1-0", &["f", "1"]),
+            entry("2-0", &["f", "2"]),
+            entry("3-0", &["f", "3"]),
+        ]);
+        assert_eq!(
+            run(&backend, &args)?,
+            array([bulk("4-0"), claimed, array([])])
+        );
+        run(&backend, &["XDEL", "s", "4-0"])?;
+        let args = ["XAUTOCLAIM", "s", "g", "d", "0", "4-0", "JUSTID"];
+        assert_eq!(
+            run(&backend, &args)?,
+            array([bulk("0-0"), array([]), array([bulk("4-0")])])
+        );
+
+        let ret = run(&backend, &["XCLAIM", "s", "missing", "d", "0", "1-0"])?;
+        assert_eq!(
+            ret,
+            BackendError::NoGroup("No such key 's' or consumer group 'missing'".to_string()).into()
+        );
+        let ret = run(
+            &backend,
+            &["XAUTOCLAIM", "s", "g", "d", "0", "0", "COUNT", "0"],
+        );
+        assert_eq!(
+            error(ret)?,
+            CommandError::InvalidArgument("COUNT must be > 0".to_string())
+        );
+        let ret = run(&backend, &["XCLAIM", "s", "g", "d", "0", "1-0", "NOPE"]);
+        assert_eq!(
+            error(ret)?,
+            CommandError::InvalidArgument("Unrecognized XCLAIM option 'NOPE'".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_xinfo() -> Result<()> {
+        let backend = Backend::new();
+        add_entries(&backend, "s", 4)?;
+        run(&backend, &["XGROUP", "CREATE", "s", "g", "0"])?;
+        run(&backend, &["XGROUP", "CREATE", "s", "h", "$"])?;
+        let args = [
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "a",
+            "COUNT",
+            "1",
+            "STREAMS",
+            "s",
+            ">",
+        ];
+        run(&backend, &args)?;
+
+        let RespFrame::Array(groups) = run(&backend, &["XINFO", "GROUPS", "s"])? else {
+            panic!("XINFO GROUPS should return an array");
+        };
+        assert_eq!(groups.len(), 2);
+        assert_eq!(map_get(&groups[0], "name"), bulk("g"));
+        assert_eq!(map_get(&groups[0], "consumers"), 1.into());
+        assert_eq!(map_get(&groups[0], "pending"), 1.into());
+        assert_eq!(map_get(&groups[0], "last-delivered-id"), bulk("1-0"));
+        assert_eq!(map_get(&groups[0], "entries-read"), 1.into());
+        assert_eq!(map_get(&groups[0], "lag"), 3.into());
+        // 通过 `$` 创建的消费组不知道读取了多少，但可以推算出来 lag
+        assert_eq!(map_get(&groups[1], "entries-read"), RespNull.into());
+        assert_eq!(map_get(&groups[1], "lag"), 0.into());
+
+        // 范围中有被删除的 entry 时无法计算 lag
+        run(&backend, &["XDEL", "s", "3-0"])?;
+        let RespFrame::Array(groups) = run(&backend, &["XINFO", "GROUPS", "s"])? else {
+            panic!("XINFO GROUPS should return an array");
+        };
+        assert_eq!(map_get(&groups[0], "lag"), RespNull.into());
+
+        let RespFrame::Array(consumers) = run(&backend, &["XINFO", "CONSUMERS", "s", "g"])? else {
+            panic!("XINFO CONSUMERS should return an array");
+        };
+        assert_eq!(consumers.len(), 1);
+        assert_eq!(map_get(&consumers[0], "name"), bulk("a"));
+        assert_eq!(map_get(&consumers[0], "pending"), 1.into());
+
+        let info = run(&backend, &["XINFO", "STREAM", "s"])?;
+        assert_eq!(map_get(&info, "length"), 3.into());
+        assert_eq!(map_get(&info, "entries-added"), 4.into());
+        assert_eq!(map_get(&info, "max-deleted-entry-id"), bulk("3-0"));
+        assert_eq!(map_get(&info, "groups"), 2.into());
+        assert_eq!(map_get(&info, "first-entry"), entry("1-0", &["f", "1"]));
+        assert_eq!(map_get(&info, "last-entry"), entry("4-0", &["f", "4"]));
+
+        let info = run(&backend, &["XINFO", "STREAM", "s", "FULL", "COUNT", "1"])?;
+        assert_eq!(
+            map_get(&info, "entries"),
+            array([entry("1-0", &["f", "1"])])
+        );
+        let RespFrame::Array(groups) = map_get(&info, "groups") else {
+            panic!("XINFO STREAM FULL should return groups as an array");
+        };
+        assert_eq!(map_get(&groups[0], "pel-count"), 1.into());
+
+        assert_eq!(
+            run(&backend, &["XINFO", "STREAM", "missing"])?,
+            CommandError::InvalidArgument("no such key".to_string()).into()
+        );
+        assert_eq!(
+            run(&backend, &["XINFO", "CONSUMERS", "s", "missing"])?,
+            BackendError::NoGroup("No such consumer group 'missing' for key name 's'".to_string())
+                .into()
+        );
+        Ok(())
+    }
+}
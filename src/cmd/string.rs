@@ -0,0 +1,885 @@
+use super::{parse_float, parse_int, validate_args, CommandError, CommandExecutor};
+use crate::{
+    backend::{now_ms, parse_i64, Value},
+    format_double, Backend, BulkString, RespArray, RespFrame, RespNullBulkString, SimpleString,
+};
+
+// 和 Redis 的 proto-max-bulk-len 默认值一致
+const MAX_STRING_LENGTH: usize = 512 * 1024 * 1024;
+
+/// GET key
+#[derive(Debug)]
+pub struct Get {
+    key: Vec<u8>,
+}
+
+/// SET key value [NX|XX] [GET] [EX seconds|PX milliseconds|EXAT unix-time-seconds|PXAT unix-time-milliseconds|KEEPTTL]
+#[derive(Debug)]
+pub struct Set {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    condition: Option<SetCondition>,
+    get: bool,
+    expiry: Option<SetExpiry>,
+}
+
+/// INCR key / DECR key / INCRBY key increment / DECRBY key decrement
+#[derive(Debug)]
+pub struct IncrBy {
+    key: Vec<u8>,
+    delta: i64,
+}
+
+/// INCRBYFLOAT key increment
+#[derive(Debug)]
+pub struct IncrByFloat {
+    key: Vec<u8>,
+    delta: f64,
+}
+
+/// APPEND key value
+#[derive(Debug)]
+pub struct Append {
+    key: Vec<u8>,
+    value: Vec<u8>,
+}
+
+/// STRLEN key
+#[derive(Debug)]
+pub struct StrLen {
+    key: Vec<u8>,
+}
+
+/// GETRANGE key start end
+#[derive(Debug)]
+pub struct GetRange {
+    key: Vec<u8>,
+    start: i64,
+    end: i64,
+}
+
+/// SETRANGE key offset value
+#[derive(Debug)]
+pub struct SetRange {
+    key: Vec<u8>,
+    offset: usize,
+    value: Vec<u8>,
+}
+
+/// MGET key [key ...]
+#[derive(Debug)]
+pub struct MGet {
+    keys: Vec<Vec<u8>>,
+}
+
+/// MSET key value [key value ...] / MSETNX key value [key value ...]
+#[derive(Debug)]
+pub struct MSet {
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    nx: bool,
+}
+
+/// GETDEL key
+#[derive(Debug)]
+pub struct GetDel {
+    key: Vec<u8>,
+}
+
+/// GETEX key [EX seconds|PX milliseconds|EXAT unix-time-seconds|PXAT unix-time-milliseconds|PERSIST]
+#[derive(Debug)]
+pub struct GetEx {
+    key: Vec<u8>,
+    expiry: Option<SetExpiry>,
+    persist: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SetCondition {
+    // 只有 key 不存在时才写入
+    Nx,
+    // 只有 key 存在时才写入
+    Xx,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SetExpiry {
+    Ex(i64),
+    Px(i64),
+    ExAt(i64),
+    PxAt(i64),
+    KeepTtl,
+}
+
+impl Get {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("get", &args, 1)?;
+        let mut args = args.into_iter();
+        Ok(Get {
+            key: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for Get {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.get_string(&self.key) {
+            Ok(Some(value)) => BulkString::new(value).into(),
+            Ok(None) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl Set {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("set".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let value = args.next().unwrap_or_default();
+
+        let mut set = Set {
+            key,
+            value,
+            condition: None,
+            get: false,
+            expiry: None,
+        };
+        while let Some(arg) = args.next() {
+            let option = String::from_utf8_lossy(&arg).to_uppercase();
+            match option.as_str() {
+                "NX" | "XX" if set.condition.is_none() => {
+                    set.condition = Some(if option == "NX" {
+                        SetCondition::Nx
+                    } else {
+                        SetCondition::Xx
+                    });
+                }
+                "GET" if !set.get => set.get = true,
+                "KEEPTTL" if set.expiry.is_none() => set.expiry = Some(SetExpiry::KeepTtl),
+                "EX" | "PX" | "EXAT" | "PXAT" if set.expiry.is_none() => {
+                    let arg = args.next().ok_or(CommandError::Syntax)?;
+                    set.expiry = Some(SetExpiry::parse("set", &option, &arg)?);
+                }
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl SetExpiry {
+    /// 解析 EX/PX/EXAT/PXAT 以及它后面的时间参数
+    fn parse(cmd: &str, option: &str, arg: &[u8]) -> Result<Self, CommandError> {
+        let n = parse_int(arg)?;
+        if n <= 0 {
+            return Err(invalid_expire_time(cmd));
+        }
+        Ok(match option {
+            "EX" => SetExpiry::Ex(n),
+            "PX" => SetExpiry::Px(n),
+            "EXAT" => SetExpiry::ExAt(n),
+            _ => SetExpiry::PxAt(n),
+        })
+    }
+
+    /// 转换成绝对的过期时间 (unix 毫秒)，溢出时返回 None
+    fn deadline(&self, now: u64) -> Option<u64> {
+        let now = now as i64;
+        let at = match *self {
+            SetExpiry::Ex(s) => s.checked_mul(1000)?.checked_add(now)?,
+            SetExpiry::Px(ms) => ms.checked_add(now)?,
+            SetExpiry::ExAt(s) => s.checked_mul(1000)?,
+            SetExpiry::PxAt(ms) => ms,
+            SetExpiry::KeepTtl => return None,
+        };
+        Some(at as u64)
+    }
+}
+
+impl CommandExecutor for Set {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let deadline = match self.expiry {
+            Some(SetExpiry::KeepTtl) | None => None,
+            Some(expiry) => match expiry.deadline(now_ms()) {
+                Some(at) => Some(at),
+                None => return invalid_expire_time("set").into(),
+            },
+        };
+
+        // 带 GET 参数时，旧值必须是 string 类型
+        let old = if self.get {
+            match backend.get_string(&self.key) {
+                Ok(old) => old,
+                Err(e) => return e.into(),
+            }
+        } else {
+            None
+        };
+        let exists = old.is_some() || backend.exists(&self.key);
+
+        let should_set = match self.condition {
+            Some(SetCondition::Nx) => !exists,
+            Some(SetCondition::Xx) => exists,
+            None => true,
+        };
+        if should_set {
+            let value = Value::String(self.value);
+            if self.expiry == Some(SetExpiry::KeepTtl) {
+                backend.set_keep_ttl(self.key.clone(), value);
+            } else {
+                backend.set(self.key.clone(), value);
+            }
+            if let Some(at) = deadline {
+                backend.set_expire(&self.key, at);
+            }
+        }
+
+        match (self.get, should_set) {
+            (true, _) => match old {
+                Some(old) => BulkString::new(old).into(),
+                None => RespNullBulkString.into(),
+            },
+            (false, true) => SimpleString::new("OK").into(),
+            (false, false) => RespNullBulkString.into(),
+        }
+    }
+}
+
+impl IncrBy {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let by = matches!(name, "incrby" | "decrby");
+        validate_args(name, &args, if by { 2 } else { 1 })?;
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let delta = match name {
+            "incr" => 1,
+            "decr" => -1,
+            "incrby" => parse_int(&args.next().unwrap_or_default())?,
+            _ => parse_int(&args.next().unwrap_or_default())?
+                .checked_neg()
+                .ok_or_else(|| {
+                    CommandError::InvalidArgument("decrement would overflow".to_string())
+                })?,
+        };
+        Ok(IncrBy { key, delta })
+    }
+}
+
+impl CommandExecutor for IncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let current = match backend.get_string(&self.key) {
+            Ok(Some(value)) => match parse_i64(&value) {
+                Some(n) => n,
+                None => return CommandError::NotInteger.into(),
+            },
+            Ok(None) => 0,
+            Err(e) => return e.into(),
+        };
+        match current.checked_add(self.delta) {
+            Some(n) => {
+                // INCR 不会改变 key 的过期时间
+                backend.set_keep_ttl(self.key, Value::String(n.to_string().into_bytes()));
+                n.into()
+            }
+            None => {
+                CommandError::InvalidArgument("increment or decrement would overflow".to_string())
+                    .into()
+            }
+        }
+    }
+}
+
+impl IncrByFloat {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("incrbyfloat", &args, 2)?;
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let delta = parse_float(&args.next().unwrap_or_default())?;
+        Ok(IncrByFloat { key, delta })
+    }
+}
+
+impl CommandExecutor for IncrByFloat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let current = match backend.get_string(&self.key) {
+            Ok(Some(value)) => match parse_float(&value) {
+                Ok(n) => n,
+                Err(e) => return e.into(),
+            },
+            Ok(None) => 0.0,
+            Err(e) => return e.into(),
+        };
+        let n = current + self.delta;
+        if !n.is_finite() {
+            return CommandError::InvalidArgument(
+                "increment would produce NaN or Infinity".to_string(),
+            )
+            .into();
+        }
+        let value = format_double(n).into_bytes();
+        backend.set_keep_ttl(self.key, Value::String(value.clone()));
+        BulkString::new(value).into()
+    }
+}
+
+impl Append {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("append", &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(Append {
+            key: args.next().unwrap_or_default(),
+            value: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for Append {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut value = match backend.get_string(&self.key) {
+            Ok(value) => value.unwrap_or_default(),
+            Err(e) => return e.into(),
+        };
+        if value.len() + self.value.len() > MAX_STRING_LENGTH {
+            return string_too_long().into();
+        }
+        value.extend_from_slice(&self.value);
+        let len = value.len() as i64;
+        backend.set_keep_ttl(self.key, Value::String(value));
+        len.into()
+    }
+}
+
+impl StrLen {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("strlen", &args, 1)?;
+        Ok(StrLen {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for StrLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.get_string(&self.key) {
+            Ok(value) => (value.map(|v| v.len()).unwrap_or_default() as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl GetRange {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("getrange", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(GetRange {
+            key: args.next().unwrap_or_default(),
+            start: parse_int(&args.next().unwrap_or_default())?,
+            end: parse_int(&args.next().unwrap_or_default())?,
+        })
+    }
+}
+
+impl CommandExecutor for GetRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let value = match backend.get_string(&self.key) {
+            Ok(value) => value.unwrap_or_default(),
+            Err(e) => return e.into(),
+        };
+        let len = value.len() as i64;
+        // 负数表示从末尾开始计算
+        let start = if self.start < 0 {
+            len + self.start
+        } else {
+            self.start
+        }
+        .max(0);
+        let end = if self.end < 0 {
+            len + self.end
+        } else {
+            self.end
+        }
+        .clamp(0, (len - 1).max(0));
+        if len == 0 || start > end || (self.start < 0 && self.end < 0 && self.start > self.end) {
+            return BulkString::new(vec![]).into();
+        }
+        BulkString::new(value[start as usize..=end as usize].to_vec()).into()
+    }
+}
+
+impl SetRange {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("setrange", &args, 3)?;
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let offset = parse_int(&args.next().unwrap_or_default())?;
+        if offset < 0 {
+            return Err(CommandError::InvalidArgument(
+                "offset is out of range".to_string(),
+            ));
+        }
+        Ok(SetRange {
+            key,
+            offset: offset as usize,
+            value: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for SetRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut value = match backend.get_string(&self.key) {
+            Ok(value) => value,
+            Err(e) => return e.into(),
+        };
+        // 写入空字符串时不会创建 key
+        if self.value.is_empty() {
+            return (value.map(|v| v.len()).unwrap_or_default() as i64).into();
+        }
+        let end = self.offset.saturating_add(self.value.len());
+        if end > MAX_STRING_LENGTH {
+            return string_too_long().into();
+        }
+        let value = value.get_or_insert_with(Vec::new);
+        if value.len() < end {
+            value.resize(end, 0);
+        }
+        value[self.offset..end].copy_from_slice(&self.value);
+        let len = value.len() as i64;
+        backend.set_keep_ttl(self.key, Value::String(std::mem::take(value)));
+        len.into()
+    }
+}
+
+impl MGet {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::WrongArity("mget".to_string()));
+        }
+        Ok(MGet { keys: args })
+    }
+}
+
+impl CommandExecutor for MGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // 不存在或者类型不是 string 的 key 都返回 nil
+        let frames: Vec<RespFrame> = self
+            .keys
+            .iter()
+            .map(|key| match backend.get_string(key) {
+                Ok(Some(value)) => BulkString::new(value).into(),
+                _ => RespNullBulkString.into(),
+            })
+            .collect();
+        RespArray::new(frames).into()
+    }
+}
+
+impl MSet {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut pairs = Vec::with_capacity(args.len() / 2);
+        let mut args = args.into_iter();
+        while let (Some(key), Some(value)) = (args.next(), args.next()) {
+            pairs.push((key, value));
+        }
+        Ok(MSet {
+            pairs,
+            nx: name == "msetnx",
+        })
+    }
+}
+
+impl CommandExecutor for MSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // MSETNX 只要有一个 key 存在就不做任何写入
+        if self.nx && self.pairs.iter().any(|(key, _)| backend.exists(key)) {
+            return 0.into();
+        }
+        for (key, value) in self.pairs {
+            backend.set(key, Value::String(value));
+        }
+        if self.nx {
+            1.into()
+        } else {
+            SimpleString::new("OK").into()
+        }
+    }
+}
+
+impl GetDel {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("getdel", &args, 1)?;
+        Ok(GetDel {
+            key: args.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for GetDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.get_string(&self.key) {
+            Ok(Some(value)) => {
+                backend.remove(&self.key);
+                BulkString::new(value).into()
+            }
+            Ok(None) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl GetEx {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::WrongArity("getex".to_string()));
+        }
+        let mut args = args.into_iter();
+        let mut getex = GetEx {
+            key: args.next().unwrap_or_default(),
+            expiry: None,
+            persist: false,
+        };
+        while let Some(arg) = args.next() {
+            let option = String::from_utf8_lossy(&arg).to_uppercase();
+            match option.as_str() {
+                "PERSIST" if getex.expiry.is_none() && !getex.persist => getex.persist = true,
+                "EX" | "PX" | "EXAT" | "PXAT" if getex.expiry.is_none() && !getex.persist => {
+                    let arg = args.next().ok_or(CommandError::Syntax)?;
+                    getex.expiry = Some(SetExpiry::parse("getex", &option, &arg)?);
+                }
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(getex)
+    }
+}
+
+impl CommandExecutor for GetEx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let value = match backend.get_string(&self.key) {
+            Ok(Some(value)) => value,
+            Ok(None) => return RespNullBulkString.into(),
+            Err(e) => return e.into(),
+        };
+        if self.persist {
+            backend.persist(&self.key);
+        } else if let Some(expiry) = self.expiry {
+            match expiry.deadline(now_ms()) {
+                Some(at) if at <= now_ms() => {
+                    backend.remove(&self.key);
+                }
+                Some(at) => {
+                    backend.set_expire(&self.key, at);
+                }
+                None => return invalid_expire_time("getex").into(),
+            }
+        }
+        BulkString::new(value).into()
+    }
+}
+
+fn invalid_expire_time(cmd: &str) -> CommandError {
+    CommandError::InvalidArgument(format!("invalid expire time in '{}' command", cmd))
+}
+
+fn string_too_long() -> CommandError {
+    CommandError::InvalidArgument(
+        "string exceeds maximum allowed size (proto-max-bulk-len)".to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::tests::run;
+    use crate::{cmd::Command, RespArray, SimpleError};
+    use anyhow::Result;
+
+    #[test]
+    fn test_set_get() -> Result<()> {
+        let backend = Backend::new();
+        let ret = run(&backend, &["SET", "hello", "world"])?;
+        assert_eq!(ret, SimpleString::new("OK").into());
+
+        let ret = run(&backend, &["GET", "hello"])?;
+        assert_eq!(ret, BulkString::new("world").into());
+
+        let ret = run(&backend, &["GET", "foo"])?;
+        assert_eq!(ret, RespNullBulkString.into());
+
+        // key 是二进制安全的，不是合法 UTF-8 的 key 也可以使用
+        let key: RespFrame = BulkString::new(vec![b'k', 0xff]).into();
+        let set = RespArray::new([BulkString::new("SET").into(), key.clone(), b"v".into()]);
+        let ret = Command::try_from(set)?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        let get = RespArray::new([BulkString::new("GET").into(), key]);
+        let ret = Command::try_from(get)?.execute(&backend);
+        assert_eq!(ret, BulkString::new("v").into());
+        assert!(!backend.exists(b"k"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_nx_xx() -> Result<()> {
+        let backend = Backend::new();
+        let ret = run(&backend, &["SET", "lock", "1", "XX"])?;
+        assert_eq!(ret, RespNullBulkString.into());
+
+        let ret = run(&backend, &["SET", "lock", "1", "NX", "PX", "10000"])?;
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert!(backend.get_expire(b"lock").is_some());
+
+        let ret = run(&backend, &["SET", "lock", "2", "nx"])?;
+        assert_eq!(ret, RespNullBulkString.into());
+        assert_eq!(backend.get_string(b"lock")?, Some(b"1".to_vec()));
+
+        let ret = run(&backend, &["SET", "lock", "3", "XX", "KEEPTTL"])?;
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert!(backend.get_expire(b"lock").is_some());
+
+        let ret = run(&backend, &["SET", "lock", "4", "XX"])?;
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert_eq!(backend.get_expire(b"lock"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_get_option() -> Result<()> {
+        let backend = Backend::new();
+        let ret = run(&backend, &["SET", "k", "v1", "GET"])?;
+        assert_eq!(ret, RespNullBulkString.into());
+
+        let ret = run(&backend, &["SET", "k", "v2", "GET"])?;
+        assert_eq!(ret, BulkString::new("v1").into());
+
+        // 条件不满足时不写入，但依旧返回旧值
+        let ret = run(&backend, &["SET", "k", "v3", "NX", "GET"])?;
+        assert_eq!(ret, BulkString::new("v2").into());
+        assert_eq!(backend.get_string(b"k")?, Some(b"v2".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_expiry() -> Result<()> {
+        let backend = Backend::new();
+        let now = now_ms();
+        run(&backend, &["SET", "k", "v", "EX", "100"])?;
+        let at = backend.get_expire(b"k").unwrap_or_default();
+        assert!(at >= now + 100_000 && at <= now_ms() + 100_000);
+
+        let at = (now / 1000 + 100).to_string();
+        run(&backend, &["SET", "k", "v", "EXAT", &at])?;
+        assert_eq!(backend.get_expire(b"k"), Some((now / 1000 + 100) * 1000));
+
+        let at = (now + 100).to_string();
+        run(&backend, &["SET", "k", "v", "PXAT", &at])?;
+        assert_eq!(backend.get_expire(b"k"), Some(now + 100));
+
+        // 已经过期的时间点会让 key 立即失效
+        run(&backend, &["SET", "k", "v", "PXAT", "1"])?;
+        assert_eq!(run(&backend, &["GET", "k"])?, RespNullBulkString.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_syntax_error() {
+        let backend = Backend::new();
+        for args in [
+            &["SET", "k", "v", "NX", "XX"][..],
+            &["SET", "k", "v", "EX", "10", "PX", "10"],
+            &["SET", "k", "v", "EX"],
+            &["SET", "k", "v", "KEEPTTL", "EX", "10"],
+            &["SET", "k", "v", "FOO"],
+        ] {
+            let ret = run(&backend, args);
+            assert_eq!(
+                ret.unwrap_err().downcast::<CommandError>().unwrap(),
+                CommandError::Syntax
+            );
+        }
+
+        let ret = run(&backend, &["SET", "k", "v", "EX", "abc"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>().unwrap(),
+            CommandError::NotInteger
+        );
+        let ret = run(&backend, &["SET", "k", "v", "EX", "0"]);
+        assert!(ret.is_err());
+    }
+    #[test]
+    fn test_incr_decr() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["INCR", "n"])?, 1.into());
+        assert_eq!(run(&backend, &["INCRBY", "n", "10"])?, 11.into());
+        assert_eq!(run(&backend, &["DECR", "n"])?, 10.into());
+        assert_eq!(run(&backend, &["DECRBY", "n", "-5"])?, 15.into());
+        assert_eq!(run(&backend, &["GET", "n"])?, BulkString::new("15").into());
+
+        run(&backend, &["SET", "n", "9223372036854775807", "EX", "100"])?;
+        assert_eq!(
+            run(&backend, &["INCR", "n"])?,
+            SimpleError::new("ERR increment or decrement would overflow").into()
+        );
+        assert_eq!(run(&backend, &["DECR", "n"])?, 9223372036854775806.into());
+        assert!(backend.get_expire(b"n").is_some());
+
+        for value in ["abc", " 1", "1.5", "+1", "01"] {
+            run(&backend, &["SET", "n", value])?;
+            assert_eq!(
+                run(&backend, &["INCR", "n"])?,
+                SimpleError::new("ERR value is not an integer or out of range").into()
+            );
+        }
+        assert!(run(&backend, &["DECRBY", "n", "-9223372036854775808"]).is_err());
+        assert!(run(&backend, &["INCRBY", "n", "1.5"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_incr_by_float() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SET", "f", "10.50"])?;
+        assert_eq!(
+            run(&backend, &["INCRBYFLOAT", "f", "0.1"])?,
+            BulkString::new("10.6").into()
+        );
+        assert_eq!(
+            run(&backend, &["INCRBYFLOAT", "f", "-5"])?,
+            BulkString::new("5.6").into()
+        );
+        run(&backend, &["SET", "f", "5.0e3"])?;
+        assert_eq!(
+            run(&backend, &["INCRBYFLOAT", "f", "2.0e2"])?,
+            BulkString::new("5200").into()
+        );
+        assert_eq!(
+            run(&backend, &["INCRBYFLOAT", "missing", "3"])?,
+            BulkString::new("3").into()
+        );
+        assert_eq!(
+            run(&backend, &["INCRBYFLOAT", "f", "inf"])?,
+            SimpleError::new("ERR increment would produce NaN or Infinity").into()
+        );
+        run(&backend, &["SET", "f", "abc"])?;
+        assert_eq!(
+            run(&backend, &["INCRBYFLOAT", "f", "1"])?,
+            SimpleError::new("ERR value is not a valid float").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_strlen() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["STRLEN", "k"])?, 0.into());
+        assert_eq!(run(&backend, &["APPEND", "k", "Hello"])?, 5.into());
+        assert_eq!(run(&backend, &["APPEND", "k", " World"])?, 11.into());
+        assert_eq!(run(&backend, &["STRLEN", "k"])?, 11.into());
+        assert_eq!(
+            run(&backend, &["GET", "k"])?,
+            BulkString::new("Hello World").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_getrange_setrange() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SET", "k", "This is a string"])?;
+        for (start, end, expected) in [
+            ("0", "3", "This"),
+            ("-3", "-1", "ing"),
+            ("0", "-1", "This is a string"),
+            ("10", "100", "string"),
+            ("5", "3", ""),
+            ("-1", "-5", ""),
+            ("-100", "3", "This"),
+            ("0", "-100", "T"),
+        ] {
+            assert_eq!(
+                run(&backend, &["GETRANGE", "k", start, end])?,
+                BulkString::new(expected).into()
+            );
+        }
+        assert_eq!(
+            run(&backend, &["GETRANGE", "missing", "0", "-1"])?,
+            BulkString::new("").into()
+        );
+
+        run(&backend, &["SET", "k", "Hello World"])?;
+        assert_eq!(run(&backend, &["SETRANGE", "k", "6", "Redis"])?, 11.into());
+        assert_eq!(
+            run(&backend, &["GET", "k"])?,
+            BulkString::new("Hello Redis").into()
+        );
+        assert_eq!(run(&backend, &["SETRANGE", "p", "3", "ab"])?, 5.into());
+        assert_eq!(
+            run(&backend, &["GET", "p"])?,
+            BulkString::new(b"\0\0\0ab").into()
+        );
+        assert_eq!(run(&backend, &["SETRANGE", "empty", "3", ""])?, 0.into());
+        assert!(!backend.exists(b"empty"));
+        assert!(run(&backend, &["SETRANGE", "k", "-1", "a"]).is_err());
+        assert_eq!(
+            run(&backend, &["SETRANGE", "k", "536870911", "ab"])?,
+            SimpleError::new("ERR string exceeds maximum allowed size (proto-max-bulk-len)").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mget_mset() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, &["MSET", "a", "1", "b", "2"])?,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(
+            run(&backend, &["MGET", "a", "missing", "b"])?,
+            RespArray::new([
+                BulkString::new("1").into(),
+                RespNullBulkString.into(),
+                BulkString::new("2").into(),
+            ])
+            .into()
+        );
+        assert_eq!(run(&backend, &["MSETNX", "b", "3", "c", "4"])?, 0.into());
+        assert!(!backend.exists(b"c"));
+        assert_eq!(run(&backend, &["MSETNX", "c", "3", "d", "4"])?, 1.into());
+        assert!(run(&backend, &["MSET", "a", "1", "b"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_getdel_getex() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["SET", "k", "v"])?;
+        assert_eq!(
+            run(&backend, &["GETEX", "k", "EX", "100"])?,
+            BulkString::new("v").into()
+        );
+        assert!(backend.get_expire(b"k").is_some());
+        assert_eq!(
+            run(&backend, &["GETEX", "k", "PERSIST"])?,
+            BulkString::new("v").into()
+        );
+        assert_eq!(backend.get_expire(b"k"), None);
+        assert_eq!(
+            run(&backend, &["GETEX", "missing"])?,
+            RespNullBulkString.into()
+        );
+        assert!(run(&backend, &["GETEX", "k", "EX", "10", "PERSIST"]).is_err());
+
+        assert_eq!(
+            run(&backend, &["GETDEL", "k"])?,
+            BulkString::new("v").into()
+        );
+        assert_eq!(run(&backend, &["GETDEL", "k"])?, RespNullBulkString.into());
+        assert!(!backend.exists(b"k"));
+        Ok(())
+    }
+}
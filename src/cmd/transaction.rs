@@ -0,0 +1,407 @@
+use super::{
+    validate_args, Command, CommandError, CommandExecutor, Outcome, Session, SessionExecutor,
+};
+use crate::{Backend, RespArray, RespFrame, RespNullArray, SimpleError, SimpleString};
+
+/// MULTI 之后排队的命令，对应 Redis client 的 mstate
+#[derive(Debug, Default)]
+pub struct Transaction {
+    // 命令以及写命令的原始参数
+    commands: Vec<(Command, Option<Vec<Vec<u8>>>)>,
+    // 有命令入队失败 (例如参数个数错误)，EXEC 时放弃整个事务
+    aborted: bool,
+}
+
+/// MULTI
+#[derive(Debug)]
+pub struct Multi;
+
+/// EXEC
+#[derive(Debug)]
+pub struct Exec;
+
+/// DISCARD
+#[derive(Debug)]
+pub struct Discard;
+
+/// WATCH key [key ...]
+#[derive(Debug)]
+pub struct Watch {
+    keys: Vec<Vec<u8>>,
+}
+
+/// UNWATCH
+#[derive(Debug)]
+pub struct Unwatch;
+
+/// 取消连接 WATCH 的所有 key，EXEC/DISCARD/UNWATCH 以及连接断开时调用
+pub fn unwatch_all(session: &mut Session, backend: &Backend) {
+    for (key, _) in std::mem::take(&mut session.watched) {
+        backend.versions.unwatch(&key);
+    }
+}
+
+/// WATCH 之后有 key 被修改过 (包括删除和过期)
+fn watched_keys_modified(session: &Session, backend: &Backend) -> bool {
+    session.watched.iter().any(|(key, version)| {
+        // 已经过期但是还没有被删除的 key 也算被修改
+        backend.expire_if_needed(key);
+        backend.versions.version(key) != Some(*version)
+    })
+}
+
+impl Transaction {
+    pub(crate) fn queue(&mut self, cmd: Command, argv: Option<Vec<Vec<u8>>>) {
+        self.commands.push((cmd, argv));
+    }
+
+    pub(crate) fn abort(&mut self) {
+        self.aborted = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl Multi {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("multi", &args, 0)?;
+        Ok(Multi)
+    }
+}
+
+impl CommandExecutor for Multi {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for Multi {
+    fn execute_session(self, session: &mut Session, _backend: &Backend) -> RespFrame {
+        if session.transaction.is_some() {
+            return CommandError::InvalidArgument("MULTI calls can not be nested".to_string())
+                .into();
+        }
+        session.transaction = Some(Transaction::default());
+        SimpleString::new("OK").into()
+    }
+}
+
+impl Exec {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("exec", &args, 0)?;
+        Ok(Exec)
+    }
+}
+
+impl CommandExecutor for Exec {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for Exec {
+    /// 依次执行排队的命令，调用方持有执行锁，执行期间不会有其他连接的命令插入
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        let Some(transaction) = session.transaction.take() else {
+            return CommandError::InvalidArgument("EXEC without MULTI".to_string()).into();
+        };
+        let modified = watched_keys_modified(session, backend);
+        unwatch_all(session, backend);
+        if transaction.aborted {
+            return SimpleError::new("EXECABORT Transaction discarded because of previous errors.")
+                .into();
+        }
+        if modified {
+            return RespNullArray.into();
+        }
+        let replies: Vec<RespFrame> = transaction
+            .commands
+            .into_iter()
+            .map(|(cmd, argv)| {
+                session.argv = argv;
+                cmd.execute_or_block(session, backend)
+            })
+            .map(|outcome| match outcome {
+                Outcome::Reply(frame) => frame,
+                Outcome::Replies(frames) => RespArray::new(frames).into(),
+                // 事务中的阻塞命令和 Redis 一样不会阻塞，没有数据时直接返回，waiter drop 时取消阻塞
+                Outcome::Block(..) => RespNullArray.into(),
+                // PSYNC 不能入队
+                Outcome::Replicate(..) => RespNullArray.into(),
+                // 事务中的 WAIT 不阻塞，直接返回当前确认的数量
+                Outcome::WaitAck(wait) => backend.ack_reply(&wait),
+            })
+            .collect();
+        RespArray::new(replies).into()
+    }
+}
+
+impl Discard {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("discard", &args, 0)?;
+        Ok(Discard)
+    }
+}
+
+impl CommandExecutor for Discard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for Discard {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        match session.transaction.take() {
+            Some(_) => {
+                unwatch_all(session, backend);
+                SimpleString::new("OK").into()
+            }
+            None => CommandError::InvalidArgument("DISCARD without MULTI".to_string()).into(),
+        }
+    }
+}
+
+impl Watch {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::WrongArity("watch".to_string()));
+        }
+        Ok(Watch { keys: args })
+    }
+}
+
+impl CommandExecutor for Watch {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for Watch {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        // 不会入队，WATCH 需要在 MULTI 之前记录 key 的版本
+        if session.transaction.is_some() {
+            return CommandError::InvalidArgument("WATCH inside MULTI is not allowed".to_string())
+                .into();
+        }
+        for key in self.keys {
+            if session.watched.iter().any(|(k, _)| *k == key) {
+                continue;
+            }
+            // 先删除已经过期的 key，之后的过期才算作修改
+            backend.expire_if_needed(&key);
+            let version = backend.versions.watch(&key);
+            session.watched.push((key, version));
+        }
+        SimpleString::new("OK").into()
+    }
+}
+
+impl Unwatch {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("unwatch", &args, 0)?;
+        Ok(Unwatch)
+    }
+}
+
+impl CommandExecutor for Unwatch {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for Unwatch {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        unwatch_all(session, backend);
+        SimpleString::new("OK").into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::tests::run, BulkString, RespNullBulkString};
+    use anyhow::Result;
+
+    /// 和网络层一样按照连接的状态解析并执行命令
+    fn execute(session: &mut Session, backend: &Backend, args: &[&str]) -> RespFrame {
+        let frames: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect();
+        match Command::from_session(RespArray::new(frames).into(), session) {
+            Ok(cmd) => match cmd.execute_or_block(session, backend) {
+                Outcome::Reply(frame) => frame,
+                outcome => panic!("unexpected outcome {:?}", outcome),
+            },
+            Err(e) => e.into(),
+        }
+    }
+
+    fn queued() -> RespFrame {
+        SimpleString::new("QUEUED").into()
+    }
+
+    #[test]
+    fn test_multi_exec() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(1);
+        run(&backend, &["SET", "s", "v"])?;
+        assert_eq!(execute(&mut session, &backend, &["MULTI"]), "OK".into());
+        assert_eq!(execute(&mut session, &backend, &["INCR", "n"]), queued());
+        assert_eq!(execute(&mut session, &backend, &["INCR", "n"]), queued());
+        // 运行时的错误不影响其他命令
+        assert_eq!(
+            execute(&mut session, &backend, &["LPUSH", "s", "x"]),
+            queued()
+        );
+        assert_eq!(
+            execute(&mut session, &backend, &["BLPOP", "list", "0"]),
+            queued()
+        );
+        // 命令入队之后还没有执行
+        assert_eq!(run(&backend, &["GET", "n"])?, RespNullBulkString.into());
+        assert_eq!(session.transaction.as_ref().map(|t| t.len()), Some(4));
+
+        let RespFrame::Array(replies) = execute(&mut session, &backend, &["EXEC"]) else {
+            panic!("EXEC should return an array");
+        };
+        assert_eq!(replies[0], 1.into());
+        assert_eq!(replies[1], 2.into());
+        assert!(matches!(replies[2], RespFrame::Error(_)));
+        assert_eq!(replies[3], RespNullArray.into());
+        assert!(session.transaction.is_none());
+        assert!(backend.blocking.is_empty());
+
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            SimpleError::new("ERR EXEC without MULTI").into()
+        );
+        assert_eq!(
+            execute(&mut session, &backend, &["DISCARD"]),
+            SimpleError::new("ERR DISCARD without MULTI").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(1);
+        run(&backend, &["SET", "a", "1"])?;
+
+        // 没有修改时正常执行
+        assert_eq!(
+            execute(&mut session, &backend, &["WATCH", "a", "b"]),
+            "OK".into()
+        );
+        execute(&mut session, &backend, &["MULTI"]);
+        execute(&mut session, &backend, &["INCR", "a"]);
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            RespArray::new([2.into()]).into()
+        );
+        assert!(session.watched.is_empty());
+        assert_eq!(backend.versions.version(b"a"), None);
+
+        // WATCH 之后被其他连接修改，EXEC 失败
+        execute(&mut session, &backend, &["WATCH", "a"]);
+        run(&backend, &["SET", "a", "10"])?;
+        execute(&mut session, &backend, &["MULTI"]);
+        execute(&mut session, &backend, &["INCR", "a"]);
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            RespNullArray.into()
+        );
+        assert_eq!(run(&backend, &["GET", "a"])?, BulkString::new("10").into());
+
+        // 不存在的 key 被创建、删除都算修改
+        execute(&mut session, &backend, &["WATCH", "b"]);
+        run(&backend, &["SET", "b", "1"])?;
+        run(&backend, &["GETDEL", "b"])?;
+        execute(&mut session, &backend, &["MULTI"]);
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            RespNullArray.into()
+        );
+
+        // 没有实际修改集合的写命令不算修改
+        run(&backend, &["RPUSH", "l", "x"])?;
+        execute(&mut session, &backend, &["WATCH", "l", "h"]);
+        run(&backend, &["LREM", "l", "0", "y"])?;
+        run(&backend, &["LINSERT", "l", "BEFORE", "y", "z"])?;
+        run(&backend, &["HDEL", "h", "f"])?;
+        execute(&mut session, &backend, &["MULTI"]);
+        execute(&mut session, &backend, &["LLEN", "l"]);
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            RespArray::new([1.into()]).into()
+        );
+
+        // 过期也算修改
+        run(&backend, &["SET", "c", "1", "PX", "20"])?;
+        execute(&mut session, &backend, &["WATCH", "c"]);
+        std::thread::sleep(std::time::Duration::from_millis(30));
+        execute(&mut session, &backend, &["MULTI"]);
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            RespNullArray.into()
+        );
+
+        // UNWATCH 之后的修改不影响 EXEC
+        execute(&mut session, &backend, &["WATCH", "a"]);
+        assert_eq!(execute(&mut session, &backend, &["UNWATCH"]), "OK".into());
+        run(&backend, &["SET", "a", "11"])?;
+        execute(&mut session, &backend, &["MULTI"]);
+        assert_eq!(
+            execute(&mut session, &backend, &["WATCH", "a"]),
+            SimpleError::new("ERR WATCH inside MULTI is not allowed").into()
+        );
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            RespArray::new([]).into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_exec_abort_and_discard() -> Result<()> {
+        let backend = Backend::new();
+        let mut session = Session::new(1);
+        execute(&mut session, &backend, &["MULTI"]);
+        assert_eq!(
+            execute(&mut session, &backend, &["MULTI"]),
+            SimpleError::new("ERR MULTI calls can not be nested").into()
+        );
+        assert_eq!(
+            execute(&mut session, &backend, &["SET", "a", "1"]),
+            queued()
+        );
+        // 入队时的错误导致整个事务被放弃
+        assert!(matches!(
+            execute(&mut session, &backend, &["GET"]),
+            RespFrame::Error(_)
+        ));
+        assert!(matches!(
+            execute(&mut session, &backend, &["NOSUCHCMD"]),
+            RespFrame::Error(_)
+        ));
+        assert_eq!(
+            execute(&mut session, &backend, &["EXEC"]),
+            SimpleError::new("EXECABORT Transaction discarded because of previous errors.").into()
+        );
+        assert!(backend.get(b"a").is_none());
+
+        execute(&mut session, &backend, &["MULTI"]);
+        execute(&mut session, &backend, &["SET", "a", "1"]);
+        assert_eq!(execute(&mut session, &backend, &["DISCARD"]), "OK".into());
+        assert!(session.transaction.is_none());
+        assert!(backend.get(b"a").is_none());
+        Ok(())
+    }
+}
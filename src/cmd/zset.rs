@@ -0,0 +1,1404 @@
+use super::{
+    list::normalize_range, parse_float, parse_int, parse_timeout, serve_now, serve_or_block,
+    validate_args, BlockingExecutor, CommandError, CommandExecutor, Outcome, Session,
+    SessionExecutor,
+};
+use crate::{
+    Backend, BackendError, BulkString, LexBound, LexRange, Protocol, RespArray, RespFrame,
+    RespNullArray, RespNullBulkString, ScoreRange, ServeFn, Set, Value, ZSet,
+};
+use std::{collections::HashMap, ops::Bound, time::Duration};
+
+type ScoredMember = (Vec<u8>, f64);
+
+/// ZADD key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]
+#[derive(Debug)]
+pub struct ZAdd {
+    key: Vec<u8>,
+    flags: ZAddFlags,
+    pairs: Vec<(f64, Vec<u8>)>,
+}
+
+/// ZREM key member [member ...]
+#[derive(Debug)]
+pub struct ZRem {
+    key: Vec<u8>,
+    members: Vec<Vec<u8>>,
+}
+
+/// ZSCORE key member
+#[derive(Debug)]
+pub struct ZScore {
+    key: Vec<u8>,
+    member: Vec<u8>,
+}
+
+/// ZMSCORE key member [member ...]
+#[derive(Debug)]
+pub struct ZMScore {
+    key: Vec<u8>,
+    members: Vec<Vec<u8>>,
+}
+
+/// ZINCRBY key increment member
+#[derive(Debug)]
+pub struct ZIncrBy {
+    key: Vec<u8>,
+    delta: f64,
+    member: Vec<u8>,
+}
+
+/// ZRANK/ZREVRANK key member [WITHSCORE]
+#[derive(Debug)]
+pub struct ZRank {
+    key: Vec<u8>,
+    member: Vec<u8>,
+    rev: bool,
+    with_score: bool,
+}
+
+/// ZRANGE key start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
+#[derive(Debug)]
+pub struct ZRange {
+    key: Vec<u8>,
+    by: ZRangeBy,
+    rev: bool,
+    // (offset, count)，count 为负数时表示不限制
+    limit: Option<(i64, i64)>,
+    with_scores: bool,
+}
+
+/// ZCOUNT key min max
+#[derive(Debug)]
+pub struct ZCount {
+    key: Vec<u8>,
+    range: ScoreRange,
+}
+
+/// ZLEXCOUNT key min max
+#[derive(Debug)]
+pub struct ZLexCount {
+    key: Vec<u8>,
+    range: LexRange,
+}
+
+/// ZPOPMIN/ZPOPMAX key [count]
+#[derive(Debug)]
+pub struct ZPop {
+    key: Vec<u8>,
+    min: bool,
+    count: Option<usize>,
+}
+
+/// ZMPOP numkeys key [key ...] MIN|MAX [COUNT count]
+/// BZMPOP timeout numkeys key [key ...] MIN|MAX [COUNT count]
+#[derive(Debug)]
+pub struct ZMPop {
+    keys: Vec<Vec<u8>>,
+    min: bool,
+    count: usize,
+    block: bool,
+    timeout: Option<Duration>,
+}
+
+/// BZPOPMIN/BZPOPMAX key [key ...] timeout
+#[derive(Debug)]
+pub struct BZPop {
+    keys: Vec<Vec<u8>>,
+    min: bool,
+    timeout: Option<Duration>,
+}
+
+/// ZUNION/ZINTER numkeys key [key ...] [WEIGHTS weight ...] [AGGREGATE SUM|MIN|MAX] [WITHSCORES]
+/// ZUNIONSTORE/ZINTERSTORE destination numkeys key [key ...] [WEIGHTS weight ...] [AGGREGATE SUM|MIN|MAX]
+/// ZDIFF numkeys key [key ...] [WITHSCORES]
+/// ZDIFFSTORE destination numkeys key [key ...]
+#[derive(Debug)]
+pub struct ZSetOp {
+    op: ZSetOpKind,
+    // 为 None 时直接返回结果，否则把结果写入 destination
+    destination: Option<Vec<u8>>,
+    keys: Vec<Vec<u8>>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    with_scores: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct ZAddFlags {
+    // 只添加新的 member，不更新已有的 member
+    nx: bool,
+    // 只更新已有的 member，不添加新的 member
+    xx: bool,
+    // 只有新的分数大于当前的分数时才更新
+    gt: bool,
+    // 只有新的分数小于当前的分数时才更新
+    lt: bool,
+    // 返回新增和分数发生变化的 member 数量
+    ch: bool,
+    // 和 ZINCRBY 一样增加分数，返回新的分数
+    incr: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ZRangeBy {
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ZSetOpKind {
+    Union,
+    Inter,
+    Diff,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl ZAdd {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 3 {
+            return Err(CommandError::WrongArity("zadd".to_string()));
+        }
+        let mut args = args.into_iter().peekable();
+        let key = args.next().unwrap_or_default();
+        let mut flags = ZAddFlags::default();
+        while let Some(arg) = args.peek() {
+            match String::from_utf8_lossy(arg).to_uppercase().as_str() {
+                "NX" => flags.nx = true,
+                "XX" => flags.xx = true,
+                "GT" => flags.gt = true,
+                "LT" => flags.lt = true,
+                "CH" => flags.ch = true,
+                "INCR" => flags.incr = true,
+                _ => break,
+            }
+            args.next();
+        }
+
+        let args: Vec<_> = args.collect();
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(CommandError::Syntax);
+        }
+        if flags.nx && flags.xx {
+            return Err(CommandError::InvalidArgument(
+                "XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if (flags.gt as u8 + flags.lt as u8 + flags.nx as u8) > 1 {
+            return Err(CommandError::InvalidArgument(
+                "GT, LT, and/or NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if flags.incr && args.len() > 2 {
+            return Err(CommandError::InvalidArgument(
+                "INCR option supports a single increment-element pair".to_string(),
+            ));
+        }
+        let pairs = args
+            .chunks_exact(2)
+            .map(|pair| Ok((parse_float(&pair[0])?, pair[1].clone())))
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        Ok(ZAdd { key, flags, pairs })
+    }
+}
+
+impl CommandExecutor for ZAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let flags = self.flags;
+        let ret = backend.write_value(&self.key, !flags.xx, |zset: &mut ZSet| {
+            let add = |zset: &mut ZSet| {
+                let (mut added, mut changed) = (0, 0);
+                // INCR 时返回的新分数，条件不满足时为 None
+                let mut result = None;
+                for (score, member) in self.pairs {
+                    match zset.score(&member) {
+                        Some(current) => {
+                            if flags.nx {
+                                continue;
+                            }
+                            let score = if flags.incr { current + score } else { score };
+                            if score.is_nan() {
+                                return Err(nan_error());
+                            }
+                            if flags.gt && score <= current || flags.lt && score >= current {
+                                continue;
+                            }
+                            if score != current {
+                                zset.insert(member, score);
+                                changed += 1;
+                            }
+                            result = Some(score);
+                        }
+                        None => {
+                            if flags.xx {
+                                continue;
+                            }
+                            zset.insert(member, score);
+                            added += 1;
+                            result = Some(score);
+                        }
+                    }
+                }
+                Ok((added, changed, result))
+            };
+            let ret = add(zset);
+            let modified = matches!(ret, Ok((added, changed, _)) if added + changed > 0);
+            (ret, modified)
+        });
+        let (added, changed, result) = match ret {
+            Ok(Some(Ok(ret))) => ret,
+            Ok(Some(Err(e))) => return e.into(),
+            Ok(None) => (0, 0, None),
+            Err(e) => return e.into(),
+        };
+        if flags.incr {
+            return match result {
+                Some(score) => score.into(),
+                None => RespNullBulkString.into(),
+            };
+        }
+        let n: i64 = if flags.ch { added + changed } else { added };
+        n.into()
+    }
+}
+
+impl ZRem {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("zrem".to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(ZRem {
+            key: args.next().unwrap_or_default(),
+            members: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for ZRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, false, |zset: &mut ZSet| {
+            let removed = self
+                .members
+                .iter()
+                .filter(|member| zset.remove(member))
+                .count();
+            (removed as i64, removed > 0)
+        });
+        match ret {
+            Ok(removed) => removed.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZScore {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("zscore", &args, 2)?;
+        let mut args = args.into_iter();
+        Ok(ZScore {
+            key: args.next().unwrap_or_default(),
+            member: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for ZScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |zset: &ZSet| zset.score(&self.member)) {
+            Ok(score) => score_or_null(score.flatten()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZMScore {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity("zmscore".to_string()));
+        }
+        let mut args = args.into_iter();
+        Ok(ZMScore {
+            key: args.next().unwrap_or_default(),
+            members: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for ZMScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |zset: &ZSet| {
+            self.members
+                .iter()
+                .map(|member| zset.score(member))
+                .collect::<Vec<_>>()
+        });
+        match ret {
+            Ok(scores) => {
+                let scores = scores.unwrap_or_else(|| vec![None; self.members.len()]);
+                RespArray::new(scores.into_iter().map(score_or_null).collect::<Vec<_>>()).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZIncrBy {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("zincrby", &args, 3)?;
+        let mut args = args.into_iter();
+        Ok(ZIncrBy {
+            key: args.next().unwrap_or_default(),
+            delta: parse_float(&args.next().unwrap_or_default())?,
+            member: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for ZIncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.write_value(&self.key, true, |zset: &mut ZSet| {
+            let score = zset.score(&self.member).unwrap_or_default() + self.delta;
+            if score.is_nan() {
+                return (Err(nan_error()), false);
+            }
+            zset.insert(self.member, score);
+            (Ok(score), true)
+        });
+        match ret {
+            Ok(Some(Ok(score))) => score.into(),
+            Ok(Some(Err(e))) => e.into(),
+            Ok(None) => unreachable!("key is created if missing"),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZRank {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let member = args.next().unwrap_or_default();
+        let with_score = match args.next() {
+            Some(arg) if arg.eq_ignore_ascii_case(b"WITHSCORE") => true,
+            Some(_) => return Err(CommandError::Syntax),
+            None => false,
+        };
+        Ok(ZRank {
+            key,
+            member,
+            rev: name == "zrevrank",
+            with_score,
+        })
+    }
+}
+
+impl CommandExecutor for ZRank {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |zset: &ZSet| {
+            let rank = zset.rank(&self.member)?;
+            let rank = if self.rev {
+                zset.len() - 1 - rank
+            } else {
+                rank
+            };
+            Some((rank as i64, zset.score(&self.member).unwrap_or_default()))
+        });
+        match ret {
+            Ok(Some(Some((rank, score)))) if self.with_score => {
+                RespArray::new([rank.into(), score.into()]).into()
+            }
+            Ok(Some(Some((rank, _)))) => rank.into(),
+            Ok(_) if self.with_score => RespNullArray.into(),
+            Ok(_) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZRange {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 3 {
+            return Err(CommandError::WrongArity("zrange".to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let (start, stop) = (
+            args.next().unwrap_or_default(),
+            args.next().unwrap_or_default(),
+        );
+
+        let (mut by_score, mut by_lex, mut rev, mut with_scores) = (false, false, false, false);
+        let mut limit = None;
+        while let Some(arg) = args.next() {
+            match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                "BYSCORE" => by_score = true,
+                "BYLEX" => by_lex = true,
+                "REV" => rev = true,
+                "WITHSCORES" => with_scores = true,
+                "LIMIT" => match (args.next(), args.next()) {
+                    (Some(offset), Some(count)) => {
+                        limit = Some((parse_int(&offset)?, parse_int(&count)?));
+                    }
+                    _ => return Err(CommandError::Syntax),
+                },
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        if by_score && by_lex {
+            return Err(CommandError::Syntax);
+        }
+        if limit.is_some() && !by_score && !by_lex {
+            return Err(CommandError::InvalidArgument(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                    .to_string(),
+            ));
+        }
+        if with_scores && by_lex {
+            return Err(CommandError::InvalidArgument(
+                "syntax error, WITHSCORES not supported in combination with BYLEX".to_string(),
+            ));
+        }
+
+        // REV 时按照分数/字典序的范围是 max min 的顺序
+        let (min, max) = if rev && (by_score || by_lex) {
+            (stop, start)
+        } else {
+            (start, stop)
+        };
+        let by = if by_score {
+            ZRangeBy::Score(parse_score_range(&min, &max)?)
+        } else if by_lex {
+            ZRangeBy::Lex(parse_lex_range(&min, &max)?)
+        } else {
+            ZRangeBy::Rank(parse_int(&min)?, parse_int(&max)?)
+        };
+        Ok(ZRange {
+            key,
+            by,
+            rev,
+            limit,
+            with_scores,
+        })
+    }
+}
+
+impl CommandExecutor for ZRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for ZRange {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        let ret = backend.read_value(&self.key, |zset: &ZSet| {
+            let (offset, count) = match self.limit {
+                // 和 Redis 一样，offset 为负数时返回空
+                Some((offset, _)) if offset < 0 => return Vec::new(),
+                Some((offset, count)) => (offset as usize, usize::try_from(count).ok()),
+                None => (0, None),
+            };
+            match &self.by {
+                ZRangeBy::Rank(start, stop) => match normalize_range(*start, *stop, zset.len()) {
+                    Some((start, stop)) => zset.range_by_rank(start, stop, self.rev),
+                    None => Vec::new(),
+                },
+                ZRangeBy::Score(range) => zset.range_by_score(range, self.rev, offset, count),
+                ZRangeBy::Lex(range) => zset.range_by_lex(range, self.rev, offset, count),
+            }
+        });
+        match ret {
+            Ok(entries) => scored_array(
+                entries.unwrap_or_default(),
+                self.with_scores,
+                session.protocol,
+            ),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZCount {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("zcount", &args, 3)?;
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let (min, max) = (
+            args.next().unwrap_or_default(),
+            args.next().unwrap_or_default(),
+        );
+        Ok(ZCount {
+            key,
+            range: parse_score_range(&min, &max)?,
+        })
+    }
+}
+
+impl CommandExecutor for ZCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |zset: &ZSet| zset.count(&self.range) as i64) {
+            Ok(n) => n.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZLexCount {
+    pub(crate) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        validate_args("zlexcount", &args, 3)?;
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let (min, max) = (
+            args.next().unwrap_or_default(),
+            args.next().unwrap_or_default(),
+        );
+        Ok(ZLexCount {
+            key,
+            range: parse_lex_range(&min, &max)?,
+        })
+    }
+}
+
+impl CommandExecutor for ZLexCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.read_value(&self.key, |zset: &ZSet| zset.lex_count(&self.range) as i64) {
+            Ok(n) => n.unwrap_or_default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl ZPop {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().unwrap_or_default();
+        let count = match args.next() {
+            Some(arg) => Some(parse_count(&arg)?),
+            None => None,
+        };
+        Ok(ZPop {
+            key,
+            min: name == "zpopmin",
+            count,
+        })
+    }
+}
+
+impl CommandExecutor for ZPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for ZPop {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        let count = self.count.unwrap_or(1);
+        let ret = backend.write_value(&self.key, false, |zset: &mut ZSet| {
+            let entries = pop_n(zset, self.min, count);
+            let popped = !entries.is_empty();
+            (entries, popped)
+        });
+        let entries = match ret {
+            Ok(entries) => entries.unwrap_or_default(),
+            Err(e) => return e.into(),
+        };
+        match self.count {
+            Some(_) => scored_array(entries, true, session.protocol),
+            // 没有 count 时不管协议版本都是 [member, score]
+            None => scored_array(entries, true, Protocol::Resp2),
+        }
+    }
+}
+
+impl ZMPop {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let block = name == "bzmpop";
+        if args.len() < if block { 4 } else { 3 } {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let timeout = match block {
+            true => parse_timeout(&args.next().unwrap_or_default())?,
+            false => None,
+        };
+        let numkeys = parse_int(&args.next().unwrap_or_default())
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| {
+                CommandError::InvalidArgument("numkeys should be greater than 0".to_string())
+            })?;
+        if numkeys as usize >= args.len() {
+            return Err(CommandError::Syntax);
+        }
+        let keys = args.by_ref().take(numkeys as usize).collect();
+        let min = match String::from_utf8_lossy(&args.next().unwrap_or_default())
+            .to_uppercase()
+            .as_str()
+        {
+            "MIN" => true,
+            "MAX" => false,
+            _ => return Err(CommandError::Syntax),
+        };
+        let count = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => 1,
+            (Some(option), Some(count), None) if option.eq_ignore_ascii_case(b"COUNT") => {
+                parse_int(&count).ok().filter(|n| *n > 0).ok_or_else(|| {
+                    CommandError::InvalidArgument("count should be greater than 0".to_string())
+                })? as usize
+            }
+            _ => return Err(CommandError::Syntax),
+        };
+        Ok(ZMPop {
+            keys,
+            min,
+            count,
+            block,
+            timeout,
+        })
+    }
+
+    fn serve(&self) -> ServeFn {
+        let (min, count) = (self.min, self.count);
+        Box::new(move |backend, key| {
+            let entries = backend.write_value(key, false, |zset: &mut ZSet| {
+                let entries = pop_n(zset, min, count);
+                let popped = !entries.is_empty();
+                (entries, popped)
+            })?;
+            // 不管协议版本，每一对 member score 都是一个数组
+            Ok(entries
+                .filter(|entries| !entries.is_empty())
+                .map(|entries| {
+                    let entries = scored_array(entries, true, Protocol::Resp3);
+                    RespArray::new([BulkString::new(key).into(), entries]).into()
+                }))
+        })
+    }
+}
+
+impl CommandExecutor for ZMPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        serve_now(backend, &self.keys, self.serve())
+    }
+}
+
+impl BlockingExecutor for ZMPop {
+    fn execute_blocking(self, backend: &Backend) -> Outcome {
+        if !self.block {
+            return Outcome::Reply(self.execute(backend));
+        }
+        let serve = self.serve();
+        serve_or_block(backend, self.keys, serve, self.timeout)
+    }
+}
+
+impl BZPop {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if args.len() < 2 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args;
+        let timeout = parse_timeout(&args.pop().unwrap_or_default())?;
+        Ok(BZPop {
+            keys: args,
+            min: name == "bzpopmin",
+            timeout,
+        })
+    }
+
+    fn serve(&self) -> ServeFn {
+        let min = self.min;
+        Box::new(move |backend, key| {
+            let entry = backend.write_value(key, false, |zset: &mut ZSet| {
+                let entry = zset.pop(min);
+                let popped = entry.is_some();
+                (entry, popped)
+            })?;
+            Ok(entry.flatten().map(|(member, score)| {
+                RespArray::new([
+                    BulkString::new(key).into(),
+                    BulkString::new(member).into(),
+                    score.into(),
+                ])
+                .into()
+            }))
+        })
+    }
+}
+
+impl CommandExecutor for BZPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        serve_now(backend, &self.keys, self.serve())
+    }
+}
+
+impl BlockingExecutor for BZPop {
+    fn execute_blocking(self, backend: &Backend) -> Outcome {
+        let serve = self.serve();
+        serve_or_block(backend, self.keys, serve, self.timeout)
+    }
+}
+
+impl ZSetOp {
+    pub(crate) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let store = name.ends_with("store");
+        if args.len() < 2 + store as usize {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let op = match name.trim_end_matches("store") {
+            "zunion" => ZSetOpKind::Union,
+            "zinter" => ZSetOpKind::Inter,
+            _ => ZSetOpKind::Diff,
+        };
+        let mut args = args.into_iter();
+        let destination = match store {
+            true => Some(args.next().unwrap_or_default()),
+            false => None,
+        };
+        let numkeys = parse_int(&args.next().unwrap_or_default())?;
+        if numkeys < 1 {
+            return Err(CommandError::InvalidArgument(format!(
+                "at least 1 input key is needed for '{}' command",
+                name
+            )));
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > args.len() {
+            return Err(CommandError::Syntax);
+        }
+        let keys = args.by_ref().take(numkeys).collect::<Vec<_>>();
+
+        let mut weights = vec![1.0; numkeys];
+        let mut aggregate = Aggregate::Sum;
+        let mut with_scores = false;
+        while let Some(arg) = args.next() {
+            match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                "WEIGHTS" if op != ZSetOpKind::Diff => {
+                    for weight in weights.iter_mut() {
+                        let arg = args.next().ok_or(CommandError::Syntax)?;
+                        *weight = parse_float(&arg).map_err(|_| {
+                            CommandError::InvalidArgument("weight value is not a float".to_string())
+                        })?;
+                    }
+                }
+                "AGGREGATE" if op != ZSetOpKind::Diff => {
+                    let arg = args.next().ok_or(CommandError::Syntax)?;
+                    aggregate = match String::from_utf8_lossy(&arg).to_uppercase().as_str() {
+                        "SUM" => Aggregate::Sum,
+                        "MIN" => Aggregate::Min,
+                        "MAX" => Aggregate::Max,
+                        _ => return Err(CommandError::Syntax),
+                    };
+                }
+                "WITHSCORES" if !store => with_scores = true,
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(ZSetOp {
+            op,
+            destination,
+            keys,
+            weights,
+            aggregate,
+            with_scores,
+        })
+    }
+}
+
+impl CommandExecutor for ZSetOp {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_session(&mut Session::default(), backend)
+    }
+}
+
+impl SessionExecutor for ZSetOp {
+    fn execute_session(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        let zset = match self.combine(backend) {
+            Ok(zset) => zset,
+            Err(e) => return e.into(),
+        };
+        let Some(destination) = self.destination else {
+            return scored_array(zset.entries(), self.with_scores, session.protocol);
+        };
+        let len = zset.len() as i64;
+        // 结果为空时删除 destination
+        if zset.is_empty() {
+            backend.remove(&destination);
+        } else {
+            backend.set(destination, Value::ZSet(zset));
+        }
+        len.into()
+    }
+}
+
+impl ZSetOp {
+    /// 计算并集、交集或差集，不存在的 key 视为空集合，set 类型的 key 中每个 member 的分数为 1
+    fn combine(&self, backend: &Backend) -> Result<ZSet, BackendError> {
+        let sources = self
+            .keys
+            .iter()
+            .map(|key| read_scored(backend, key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut sources = sources
+            .into_iter()
+            .zip(self.weights.iter())
+            .map(|(entries, weight)| {
+                entries
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(member, score)| (member, weighted(score, *weight)))
+                    .collect::<HashMap<_, _>>()
+            });
+        let first = sources.next().unwrap_or_default();
+        let rest: Vec<_> = sources.collect();
+        let zset = match self.op {
+            ZSetOpKind::Union => {
+                let mut result = first;
+                for source in rest {
+                    for (member, score) in source {
+                        result
+                            .entry(member)
+                            .and_modify(|v| *v = self.aggregate.apply(*v, score))
+                            .or_insert(score);
+                    }
+                }
+                result.into_iter().collect()
+            }
+            ZSetOpKind::Inter => first
+                .into_iter()
+                .filter_map(|(member, score)| {
+                    rest.iter()
+                        .try_fold(score, |acc, source| {
+                            source
+                                .get(&member)
+                                .map(|score| self.aggregate.apply(acc, *score))
+                        })
+                        .map(|score| (member, score))
+                })
+                .collect(),
+            ZSetOpKind::Diff => first
+                .into_iter()
+                .filter(|(member, _)| !rest.iter().any(|source| source.contains_key(member)))
+                .collect(),
+        };
+        Ok(zset)
+    }
+}
+
+impl Aggregate {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            // 和 Redis 一样，inf 加上 -inf 的结果是 0
+            Aggregate::Sum => {
+                let v = a + b;
+                if v.is_nan() {
+                    0.0
+                } else {
+                    v
+                }
+            }
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+fn weighted(score: f64, weight: f64) -> f64 {
+    // inf 乘以 0 的结果是 0
+    let v = score * weight;
+    if v.is_nan() {
+        0.0
+    } else {
+        v
+    }
+}
+
+/// 读取 sorted set 或者 set 中的所有元素
+fn read_scored(backend: &Backend, key: &[u8]) -> Result<Option<Vec<ScoredMember>>, BackendError> {
+    match backend.read_value(key, |zset: &ZSet| zset.entries()) {
+        Err(BackendError::WrongType) => backend.read_value(key, |set: &Set| {
+            set.members().into_iter().map(|m| (m, 1.0)).collect()
+        }),
+        ret => ret,
+    }
+}
+
+/// 弹出最多 count 个分数最小 (min 为 true) 或者最大的 member
+pub(crate) fn pop_n(zset: &mut ZSet, min: bool, count: usize) -> Vec<(Vec<u8>, f64)> {
+    (0..count).map_while(|_| zset.pop(min)).collect()
+}
+
+/// 返回 member 列表，with_scores 为 true 时 RESP3 下每一对 member score 是一个数组，RESP2 下依次排列
+pub(crate) fn scored_array(
+    entries: Vec<(Vec<u8>, f64)>,
+    with_scores: bool,
+    protocol: Protocol,
+) -> RespFrame {
+    let frames: Vec<RespFrame> = match (with_scores, protocol) {
+        (false, _) => entries
+            .into_iter()
+            .map(|(member, _)| BulkString::new(member).into())
+            .collect(),
+        (true, Protocol::Resp3) => entries
+            .into_iter()
+            .map(|(member, score)| {
+                RespArray::new([BulkString::new(member).into(), score.into()]).into()
+            })
+            .collect(),
+        (true, Protocol::Resp2) => entries
+            .into_iter()
+            .flat_map(|(member, score)| [BulkString::new(member).into(), score.into()])
+            .collect(),
+    };
+    RespArray::new(frames).into()
+}
+
+/// 解析 ZPOPMIN 等命令的 count，不能为负数
+pub(crate) fn parse_count(arg: &[u8]) -> Result<usize, CommandError> {
+    usize::try_from(parse_int(arg)?).map_err(|_| {
+        CommandError::InvalidArgument("value is out of range, must be positive".to_string())
+    })
+}
+
+fn score_or_null(score: Option<f64>) -> RespFrame {
+    match score {
+        Some(score) => score.into(),
+        None => RespNullBulkString.into(),
+    }
+}
+
+fn nan_error() -> CommandError {
+    CommandError::InvalidArgument("resulting score is not a number (NaN)".to_string())
+}
+
+/// 解析分数范围，`(` 前缀表示不包含
+fn parse_score_range(min: &[u8], max: &[u8]) -> Result<ScoreRange, CommandError> {
+    let parse = |arg: &[u8]| {
+        let ret = match arg.strip_prefix(b"(") {
+            Some(v) => parse_float(v).map(Bound::Excluded),
+            None => parse_float(arg).map(Bound::Included),
+        };
+        ret.map_err(|_| CommandError::InvalidArgument("min or max is not a float".to_string()))
+    };
+    Ok(ScoreRange {
+        min: parse(min)?,
+        max: parse(max)?,
+    })
+}
+
+/// 解析字典序范围，必须是 `-`、`+` 或者以 `[`、`(` 开头
+fn parse_lex_range(min: &[u8], max: &[u8]) -> Result<LexRange, CommandError> {
+    let parse = |arg: &[u8]| match arg {
+        b"-" => Ok(LexBound::NegInf),
+        b"+" => Ok(LexBound::PosInf),
+        [b'[', v @ ..] => Ok(LexBound::Included(v.to_vec())),
+        [b'(', v @ ..] => Ok(LexBound::Excluded(v.to_vec())),
+        _ => Err(CommandError::InvalidArgument(
+            "min or max not valid string range item".to_string(),
+        )),
+    };
+    Ok(LexRange {
+        min: parse(min)?,
+        max: parse(max)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{
+        tests::{run, run_blocking},
+        Command,
+    };
+    use anyhow::Result;
+
+    fn bulk(v: &str) -> RespFrame {
+        BulkString::new(v).into()
+    }
+
+    fn array(items: &[&str]) -> RespFrame {
+        RespArray::new(items.iter().map(|v| bulk(v)).collect::<Vec<_>>()).into()
+    }
+
+    fn zrange(backend: &Backend, args: &[&str], protocol: Protocol) -> Result<RespFrame> {
+        let frames = args.iter().map(|v| bulk(v)).collect::<Vec<_>>();
+        let mut session = Session {
+            protocol,
+            ..Default::default()
+        };
+        let ret = match Command::try_from(RespArray::new(frames))? {
+            Command::ZRange(cmd) => cmd.execute_session(&mut session, backend),
+            Command::ZPop(cmd) => cmd.execute_session(&mut session, backend),
+            Command::ZSetOp(cmd) => cmd.execute_session(&mut session, backend),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        };
+        Ok(ret)
+    }
+
+    #[test]
+    fn test_zadd_flags() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, &["ZADD", "z", "1", "a", "2", "b"])?, 2.into());
+        assert_eq!(run(&backend, &["ZADD", "z", "XX", "3", "c"])?, 0.into());
+        assert_eq!(
+            run(&backend, &["ZADD", "z", "NX", "5", "a", "3", "c"])?,
+            1.into()
+        );
+        assert_eq!(run(&backend, &["ZSCORE", "z", "a"])?, 1.0.into());
+        let args = ["ZADD", "z", "CH", "GT", "0", "a", "10", "b", "4", "d"];
+        assert_eq!(run(&backend, &args)?, 2.into());
+        assert_eq!(run(&backend, &["ZSCORE", "z", "b"])?, 10.0.into());
+        let args = ["ZADD", "z", "LT", "CH", "20", "b", "0", "c"];
+        assert_eq!(run(&backend, &args)?, 1.into());
+        assert_eq!(run(&backend, &["ZADD", "z", "INCR", "5", "a"])?, 6.0.into());
+        let args = ["ZADD", "z", "INCR", "GT", "-1", "a"];
+        assert_eq!(run(&backend, &args)?, RespNullBulkString.into());
+        assert_eq!(
+            run(&backend, &["ZADD", "missing", "XX", "1", "a"])?,
+            0.into()
+        );
+        assert!(!backend.exists(b"missing"));
+
+        assert_eq!(run(&backend, &["ZINCRBY", "z", "1.5", "a"])?, 7.5.into());
+        assert_eq!(
+            run(&backend, &["ZINCRBY", "z", "+inf", "a"])?,
+            f64::INFINITY.into()
+        );
+        let ret = run(&backend, &["ZINCRBY", "z", "-inf", "a"])?;
+        assert_eq!(ret, nan_error().into());
+        let ret = run(&backend, &["ZMSCORE", "z", "b", "x"])?;
+        assert_eq!(
+            ret,
+            RespArray::new([10.0.into(), RespNullBulkString.into()]).into()
+        );
+
+        for (args, err) in [
+            (
+                &["ZADD", "z", "NX", "XX", "1", "a"][..],
+                "XX and NX options at the same time are not compatible",
+            ),
+            (
+                &["ZADD", "z", "GT", "LT", "1", "a"],
+                "GT, LT, and/or NX options at the same time are not compatible",
+            ),
+            (
+                &["ZADD", "z", "INCR", "1", "a", "2", "b"],
+                "INCR option supports a single increment-element pair",
+            ),
+        ] {
+            let ret = run(&backend, args);
+            assert_eq!(
+                ret.unwrap_err().downcast::<CommandError>()?,
+                CommandError::InvalidArgument(err.to_string())
+            );
+        }
+        let ret = run(&backend, &["ZADD", "z", "1", "a", "2"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>()?,
+            CommandError::Syntax
+        );
+        let ret = run(&backend, &["ZADD", "z", "x", "a"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>()?,
+            CommandError::NotFloat
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrank_zrem() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["ZADD", "z", "1", "a", "2", "b", "3", "c"])?;
+        assert_eq!(run(&backend, &["ZRANK", "z", "b"])?, 1.into());
+        assert_eq!(run(&backend, &["ZREVRANK", "z", "a"])?, 2.into());
+        let ret = run(&backend, &["ZRANK", "z", "c", "WITHSCORE"])?;
+        assert_eq!(ret, RespArray::new([2.into(), 3.0.into()]).into());
+        assert_eq!(
+            run(&backend, &["ZRANK", "z", "x"])?,
+            RespNullBulkString.into()
+        );
+        let ret = run(&backend, &["ZRANK", "z", "x", "WITHSCORE"])?;
+        assert_eq!(ret, RespNullArray.into());
+
+        assert_eq!(run(&backend, &["ZREM", "z", "a", "x"])?, 1.into());
+        assert_eq!(run(&backend, &["ZRANK", "z", "b"])?, 0.into());
+        assert_eq!(run(&backend, &["ZREM", "z", "b", "c"])?, 2.into());
+        assert!(!backend.exists(b"z"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange() -> Result<()> {
+        let backend = Backend::new();
+        run(
+            &backend,
+            &["ZADD", "z", "1", "a", "2", "b", "3", "c", "4", "d"],
+        )?;
+        assert_eq!(
+            run(&backend, &["ZRANGE", "z", "0", "-1"])?,
+            array(&["a", "b", "c", "d"])
+        );
+        assert_eq!(
+            run(&backend, &["ZRANGE", "z", "0", "1", "REV"])?,
+            array(&["d", "c"])
+        );
+        let args = ["ZRANGE", "z", "(1", "+inf", "BYSCORE", "LIMIT", "1", "2"];
+        assert_eq!(run(&backend, &args)?, array(&["c", "d"]));
+        let args = ["ZRANGE", "z", "3", "-inf", "BYSCORE", "REV"];
+        assert_eq!(run(&backend, &args)?, array(&["c", "b", "a"]));
+        let args = ["ZRANGE", "z", "[b", "(d", "BYLEX"];
+        assert_eq!(run(&backend, &args)?, array(&["b", "c"]));
+        let args = ["ZRANGE", "z", "+", "[c", "BYLEX", "REV", "LIMIT", "0", "1"];
+        assert_eq!(run(&backend, &args)?, array(&["d"]));
+        let args = ["ZRANGE", "z", "-", "+", "BYLEX", "LIMIT", "-1", "1"];
+        assert_eq!(run(&backend, &args)?, array(&[]));
+
+        // WITHSCORES 在 RESP2 下依次排列，RESP3 下每一对是一个数组
+        let args = ["ZRANGE", "z", "0", "1", "WITHSCORES"];
+        assert_eq!(
+            zrange(&backend, &args, Protocol::Resp2)?,
+            RespArray::new([bulk("a"), 1.0.into(), bulk("b"), 2.0.into()]).into()
+        );
+        assert_eq!(
+            zrange(&backend, &args, Protocol::Resp3)?,
+            RespArray::new([
+                RespArray::new([bulk("a"), 1.0.into()]).into(),
+                RespArray::new([bulk("b"), 2.0.into()]).into(),
+            ])
+            .into()
+        );
+
+        assert_eq!(run(&backend, &["ZCOUNT", "z", "(1", "3"])?, 2.into());
+        assert_eq!(run(&backend, &["ZCOUNT", "z", "5", "+inf"])?, 0.into());
+        assert_eq!(run(&backend, &["ZLEXCOUNT", "z", "-", "+"])?, 4.into());
+        assert_eq!(run(&backend, &["ZLEXCOUNT", "z", "(a", "[c"])?, 2.into());
+
+        for (args, err) in [
+            (
+                &["ZRANGE", "z", "0", "1", "LIMIT", "0", "1"][..],
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+            ),
+            (
+                &["ZRANGE", "z", "-", "+", "BYLEX", "WITHSCORES"],
+                "syntax error, WITHSCORES not supported in combination with BYLEX",
+            ),
+            (&["ZCOUNT", "z", "a", "1"], "min or max is not a float"),
+            (
+                &["ZLEXCOUNT", "z", "a", "+"],
+                "min or max not valid string range item",
+            ),
+        ] {
+            let ret = run(&backend, args);
+            assert_eq!(
+                ret.unwrap_err().downcast::<CommandError>()?,
+                CommandError::InvalidArgument(err.to_string())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_zpop() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["ZADD", "z", "1", "a", "2", "b", "3", "c"])?;
+        let ret = zrange(&backend, &["ZPOPMIN", "z"], Protocol::Resp3)?;
+        assert_eq!(ret, RespArray::new([bulk("a"), 1.0.into()]).into());
+        let ret = zrange(&backend, &["ZPOPMAX", "z", "5"], Protocol::Resp3)?;
+        assert_eq!(
+            ret,
+            RespArray::new([
+                RespArray::new([bulk("c"), 3.0.into()]).into(),
+                RespArray::new([bulk("b"), 2.0.into()]).into(),
+            ])
+            .into()
+        );
+        assert!(!backend.exists(b"z"));
+        assert_eq!(run(&backend, &["ZPOPMIN", "z"])?, array(&[]));
+        let ret = run(&backend, &["ZPOPMIN", "z", "-1"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>()?,
+            CommandError::InvalidArgument("value is out of range, must be positive".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_ops() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["ZADD", "a", "1", "x", "2", "y", "3", "z"])?;
+        run(&backend, &["ZADD", "b", "10", "y", "20", "z", "30", "w"])?;
+        run(&backend, &["SADD", "s", "z"])?;
+
+        let args = ["ZUNIONSTORE", "out", "2", "a", "b"];
+        assert_eq!(run(&backend, &args)?, 4.into());
+        let args = ["ZRANGE", "out", "0", "-1", "WITHSCORES"];
+        assert_eq!(
+            run(&backend, &args)?,
+            RespArray::new([
+                bulk("x"),
+                1.0.into(),
+                bulk("y"),
+                12.0.into(),
+                bulk("z"),
+                23.0.into(),
+                bulk("w"),
+                30.0.into(),
+            ])
+            .into()
+        );
+
+        let args = [
+            "ZINTERSTORE",
+            "out",
+            "3",
+            "a",
+            "b",
+            "s",
+            "WEIGHTS",
+            "2",
+            "1",
+            "100",
+            "AGGREGATE",
+            "MAX",
+        ];
+        assert_eq!(run(&backend, &args)?, 1.into());
+        assert_eq!(run(&backend, &["ZSCORE", "out", "z"])?, 100.0.into());
+        let args = ["ZINTERSTORE", "out", "2", "a", "missing"];
+        assert_eq!(run(&backend, &args)?, 0.into());
+        assert!(!backend.exists(b"out"));
+
+        let args = ["ZINTER", "2", "a", "b", "AGGREGATE", "MIN", "WITHSCORES"];
+        assert_eq!(
+            zrange(&backend, &args, Protocol::Resp2)?,
+            RespArray::new([bulk("y"), 2.0.into(), bulk("z"), 3.0.into()]).into()
+        );
+        assert_eq!(run(&backend, &["ZDIFF", "2", "a", "b"])?, array(&["x"]));
+        assert_eq!(
+            run(&backend, &["ZUNION", "2", "a", "s", "WEIGHTS", "1", "-10"])?,
+            array(&["z", "x", "y"])
+        );
+
+        let ret = run(&backend, &["ZDIFF", "2", "a", "b", "WEIGHTS", "1", "2"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>()?,
+            CommandError::Syntax
+        );
+        let ret = run(&backend, &["ZUNIONSTORE", "out", "0", "a"]);
+        assert_eq!(
+            ret.unwrap_err().downcast::<CommandError>()?,
+            CommandError::InvalidArgument(
+                "at least 1 input key is needed for 'zunionstore' command".to_string()
+            )
+        );
+
+        run(&backend, &["LPUSH", "l", "v"])?;
+        let ret = run(&backend, &["ZUNION", "2", "a", "l"])?;
+        assert_eq!(ret, BackendError::WrongType.into());
+        Ok(())
+    }
+
+    fn blocked(outcome: Outcome) -> crate::Waiter {
+        match outcome {
+            Outcome::Block(waiter, _) => waiter,
+            Outcome::Reply(frame) => panic!("expected blocking, got {:?}", frame),
+            Outcome::Replies(frames) => panic!("expected blocking, got {:?}", frames),
+            Outcome::Replicate(feed) => panic!("expected blocking, got {:?}", feed),
+            Outcome::WaitAck(wait) => panic!("expected blocking, got {:?}", wait),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bzpop() -> Result<()> {
+        let backend = Backend::new();
+        run(&backend, &["ZADD", "b", "2", "x", "1", "y"])?;
+        let Outcome::Reply(reply) = run_blocking(&backend, &["BZPOPMIN", "a", "b", "0"])? else {
+            panic!("BZPOPMIN should not block when data is available");
+        };
+        assert_eq!(
+            reply,
+            RespArray::new([bulk("b"), bulk("y"), 1.0.into()]).into()
+        );
+
+        // 先阻塞的客户端先被唤醒，超时时间可以是小数
+        let first = blocked(run_blocking(&backend, &["BZPOPMAX", "a", "0.5"])?);
+        let second = blocked(run_blocking(&backend, &["BZPOPMIN", "c", "a", "0"])?);
+        run(&backend, &["ZADD", "a", "1", "m", "3", "n"])?;
+        assert_eq!(
+            first.wait(Some(Duration::from_millis(500))).await,
+            Some(RespArray::new([bulk("a"), bulk("n"), 3.0.into()]).into())
+        );
+        assert_eq!(
+            second.wait(None).await,
+            Some(RespArray::new([bulk("a"), bulk("m"), 1.0.into()]).into())
+        );
+        assert!(!backend.exists(b"a"));
+
+        let waiter = blocked(run_blocking(&backend, &["BZPOPMIN", "a", "0.01"])?);
+        assert_eq!(waiter.wait(Some(Duration::from_millis(10))).await, None);
+
+        // 不阻塞的版本 (事务中) 直接返回 null array
+        assert_eq!(
+            run(&backend, &["BZPOPMIN", "a", "0"])?,
+            RespNullArray.into()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bzmpop() -> Result<()> {
+        let backend = Backend::new();
+        let waiter = blocked(run_blocking(
+            &backend,
+            &["BZMPOP", "0", "2", "x", "z", "MIN", "COUNT", "2"],
+        )?);
+        run(&backend, &["ZADD", "z", "3", "c", "1", "a", "2", "b"])?;
+        let pairs = RespArray::new([
+            RespArray::new([bulk("a"), 1.0.into()]).into(),
+            RespArray::new([bulk("b"), 2.0.into()]).into(),
+        ]);
+        assert_eq!(
+            waiter.wait(None).await,
+            Some(RespArray::new([bulk("z"), pairs.into()]).into())
+        );
+
+        let pairs = RespArray::new([RespArray::new([bulk("c"), 3.0.into()]).into()]);
+        assert_eq!(
+            run(&backend, &["ZMPOP", "2", "x", "z", "MAX", "COUNT", "10"])?,
+            RespArray::new([bulk("z"), pairs.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, &["ZMPOP", "1", "z", "MIN"])?,
+            RespNullArray.into()
+        );
+
+        run(&backend, &["SET", "s", "v"])?;
+        let Outcome::Reply(reply) = run_blocking(&backend, &["BZPOPMIN", "s", "0"])? else {
+            panic!("BZPOPMIN should fail on the wrong type");
+        };
+        assert_eq!(reply, BackendError::WrongType.into());
+        for args in [
+            &["BZPOPMIN", "a", "-1"][..],
+            &["BZMPOP", "abc", "1", "a", "MIN"],
+            &["ZMPOP", "0", "a", "MIN"],
+            &["ZMPOP", "2", "a", "MIN"],
+            &["ZMPOP", "1", "a", "MIN", "COUNT", "0"],
+            &["ZMPOP", "1", "a", "UP"],
+        ] {
+            assert!(run(&backend, args).is_err(), "{:?}", args);
+        }
+        Ok(())
+    }
+}
@@ -1 +1,9 @@
+mod backend;
+pub mod cluster;
+pub mod cmd;
+pub mod network;
+pub mod replication;
 mod resp;
+
+pub use backend::*;
+pub use resp::*;
@@ -1,3 +1,135 @@
-fn main() {
-    println!("Hello, world!");
+use anyhow::{anyhow, Result};
+use clap::{builder::BoolishValueParser, ArgAction, Parser};
+use simple_redis::{
+    cluster::spawn_cluster_cron, cmd::load_dataset, network, replication::spawn_replication,
+    spawn_active_expire, spawn_aof_cron, spawn_save_scheduler, AofConfig, AppendFsync, Backend,
+    ClusterConfig, SaveConfig, SaveRule, DEFAULT_PUBSUB_BUFFER_LIMIT, DEFAULT_REPLICA_BUFFER_LIMIT,
+};
+use std::{path::PathBuf, time::Duration};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// Simple-Redis 服务器
+#[derive(Debug, Parser)]
+#[command(name = "simple-redis", version, about)]
+struct Args {
+    /// 监听的地址
+    #[arg(long, default_value = "0.0.0.0")]
+    bind: String,
+    /// 监听的端口
+    #[arg(short, long, default_value_t = 6379)]
+    port: u16,
+    /// RDB 文件所在的目录
+    #[arg(long, default_value = ".")]
+    dir: PathBuf,
+    /// RDB 文件名
+    #[arg(long, default_value = "dump.rdb")]
+    dbfilename: String,
+    /// 自动保存的规则 `<seconds> <changes> ...`，空字符串表示关闭自动保存
+    #[arg(long, default_value = "3600 1 300 100 60 10000")]
+    save: String,
+    /// 是否开启 AOF 持久化 (yes/no)
+    #[arg(long, default_value = "no", action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    appendonly: bool,
+    /// AOF fsync 的策略：always、everysec 或 no
+    #[arg(long, default_value = "everysec")]
+    appendfsync: AppendFsync,
+    /// AOF 文件所在的目录，位于 dir 之下
+    #[arg(long, default_value = "appendonlydir")]
+    appenddirname: String,
+    /// AOF 文件名的前缀
+    #[arg(long, default_value = "appendonly.aof")]
+    appendfilename: String,
+    /// AOF 比上次重写之后增长超过这个百分比时自动重写，0 表示关闭
+    #[arg(long, default_value_t = 100)]
+    auto_aof_rewrite_percentage: u64,
+    /// AOF 小于这个字节数时不自动重写
+    #[arg(long, default_value_t = 64 * 1024 * 1024)]
+    auto_aof_rewrite_min_size: u64,
+    /// 启动时作为 replica 同步的 master `<host> <port>`
+    #[arg(long)]
+    replicaof: Option<String>,
+    /// 是否开启集群模式 (yes/no)
+    #[arg(long, default_value = "no", action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    cluster_enabled: bool,
+    /// 集群的节点配置文件，位于 dir 之下，由节点自己维护
+    #[arg(long, default_value = "nodes.conf")]
+    cluster_config_file: String,
+    /// 在集群中公布的自己的 IP，MOVED/ASK 以及 CLUSTER SLOTS 中返回这个地址
+    #[arg(long, default_value = "127.0.0.1")]
+    cluster_announce_ip: String,
+    /// 脚本执行超过这个毫秒数之后，其他客户端的命令返回 BUSY，可以通过 SCRIPT KILL 终止脚本
+    #[arg(long, default_value_t = 5000)]
+    lua_time_limit: u64,
+    /// 订阅者积压的消息超过这个字节数之后断开连接，0 表示不限制
+    #[arg(long, default_value_t = DEFAULT_PUBSUB_BUFFER_LIMIT)]
+    client_output_buffer_limit_pubsub: usize,
+    /// replica 积压的命令流超过这个字节数之后断开连接，0 表示不限制
+    #[arg(long, default_value_t = DEFAULT_REPLICA_BUFFER_LIMIT)]
+    client_output_buffer_limit_replica: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let backend = Backend::new();
+    backend
+        .configure_cluster(ClusterConfig {
+            enabled: args.cluster_enabled,
+            config_file: args.dir.join(&args.cluster_config_file),
+            announce_ip: args.cluster_announce_ip,
+            port: args.port,
+        })
+        .map_err(|e| anyhow!(e))?;
+    let rules = SaveRule::parse_rules(&args.save)
+        .ok_or_else(|| anyhow!("invalid save rules: {}", args.save))?;
+    backend.configure_save(SaveConfig {
+        dir: args.dir,
+        dbfilename: args.dbfilename,
+        rules,
+    });
+    backend.configure_aof(AofConfig {
+        enabled: args.appendonly,
+        fsync: args.appendfsync,
+        dirname: args.appenddirname,
+        filename: args.appendfilename,
+        auto_rewrite_percentage: args.auto_aof_rewrite_percentage,
+        auto_rewrite_min_size: args.auto_aof_rewrite_min_size,
+    });
+    backend.set_lua_time_limit(Duration::from_millis(args.lua_time_limit));
+    backend.set_pubsub_buffer_limit(args.client_output_buffer_limit_pubsub);
+    backend.set_replica_buffer_limit(args.client_output_buffer_limit_replica);
+    load_dataset(&backend).map_err(|e| anyhow!("failed to load data from disk: {}", e))?;
+    backend.set_listening_port(args.port);
+    if let Some(replicaof) = args.replicaof.as_ref().filter(|_| !args.cluster_enabled) {
+        let master = replicaof
+            .split_once(' ')
+            .and_then(|(host, port)| Some((host.to_string(), port.trim().parse().ok()?)))
+            .ok_or_else(|| anyhow!("invalid replicaof: {}", replicaof))?;
+        backend.set_master(Some(master));
+    }
+
+    let addr = format!("{}:{}", args.bind, args.port);
+    info!("Simple-Redis-Server is listening on {}", addr);
+    let listener = TcpListener::bind(&addr).await?;
+
+    spawn_active_expire(backend.clone());
+    spawn_save_scheduler(backend.clone());
+    spawn_aof_cron(backend.clone());
+    spawn_replication(backend.clone());
+    spawn_cluster_cron(backend.clone());
+    loop {
+        let (stream, raddr) = listener.accept().await?;
+        info!("Accepted connection from: {}", raddr);
+        stream.set_nodelay(true)?;
+        let cloned_backend = backend.clone();
+        tokio::spawn(async move {
+            match network::stream_handler(stream, cloned_backend).await {
+                Ok(_) => info!("Connection from {} exited", raddr),
+                Err(e) => warn!("handle error for {}: {:?}", raddr, e),
+            }
+        });
+    }
 }
@@ -0,0 +1,270 @@
+use crate::{
+    cmd::{
+        parse_replconf_ack, unsubscribe_all, unwatch_all, ClusterRequest, Command, Outcome, Session,
+    },
+    Backend, ReplicaFeed, RespDecode, RespDecodeError, RespEncode, RespFrame, RespNullArray,
+    SimpleError, SCRIPT_BUSY_POLL,
+};
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    MutexGuard,
+};
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tracing::{debug, info, warn};
+
+// 客户端的 id，和 Redis 一样从 1 开始递增
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug)]
+struct RespFrameCodec;
+
+#[derive(Debug)]
+struct RedisRequest {
+    frame: RespFrame,
+    backend: Backend,
+}
+
+#[derive(Debug)]
+struct RedisResponse {
+    frames: Vec<RespFrame>,
+    // PSYNC/SYNC 成功之后，连接开始接收命令流
+    replicate: Option<ReplicaFeed>,
+}
+
+/// 处理一个客户端连接，依次读取请求并返回响应
+pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+    let addr = stream.peer_addr().ok().map(|addr| addr.ip().to_string());
+    let mut framed = Framed::new(stream, RespFrameCodec);
+    let mut session = Session::new(NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed)).with_addr(addr);
+    let ret = serve(&mut framed, &mut session, &backend).await;
+    // 连接断开之后不再接收订阅的消息，也不再 WATCH 任何 key
+    unsubscribe_all(&mut session, &backend);
+    unwatch_all(&mut session, &backend);
+    ret
+}
+
+async fn serve(
+    framed: &mut Framed<TcpStream, RespFrameCodec>,
+    session: &mut Session,
+    backend: &Backend,
+) -> Result<()> {
+    loop {
+        let next = tokio::select! {
+            // 先推送已经收到的订阅消息，再处理下一个请求
+            biased;
+            message = session.next_message() => {
+                // 和 Redis 一样，输出缓冲区超过上限的客户端直接断开，
+                // 客户端不读数据时写操作会一直阻塞，所以写的同时也要检查
+                let overflowed = match message {
+                    Some(message) => tokio::select! {
+                        ret = framed.send(message.into_protocol(session.protocol)) => {
+                            ret?;
+                            false
+                        }
+                        _ = session.output_overflowed() => true,
+                    },
+                    None => true,
+                };
+                if overflowed {
+                    warn!(
+                        "Client id={} closed for overcoming of output buffer limits",
+                        session.id
+                    );
+                    return Ok(());
+                }
+                continue;
+            }
+            next = framed.next() => next,
+        };
+        match next {
+            Some(Ok(frame)) => {
+                debug!("Received frame: {:?}", frame);
+                let request = RedisRequest {
+                    frame,
+                    backend: backend.clone(),
+                };
+                let response = tokio::select! {
+                    biased;
+                    response = request_handler(request, session) => response?,
+                    // 阻塞命令等待期间客户端断开连接，放弃等待，避免消费掉其他客户端的数据
+                    _ = closed(framed.get_ref()) => return Ok(()),
+                };
+                debug!("Sending response: {:?}", response.frames);
+                for frame in response.frames {
+                    framed.feed(frame).await?;
+                }
+                framed.flush().await?;
+                if let Some(feed) = response.replicate {
+                    return serve_replica(framed, feed, backend).await;
+                }
+            }
+            Some(Err(e)) => return Err(e),
+            None => return Ok(()),
+        }
+    }
+}
+
+async fn request_handler(request: RedisRequest, session: &mut Session) -> Result<RedisResponse> {
+    let (frame, backend) = (request.frame, request.backend);
+    // 集群模式下先记录命令访问的 key，由其他节点负责的 key 返回 MOVED/ASK
+    let cluster = ClusterRequest::new(&frame, &backend);
+    let outcome = match Command::from_session(frame, session) {
+        Ok(cmd) if cmd.is_script_kill() => cmd.execute_or_block(session, &backend),
+        Ok(cmd) => match lock_or_busy(&backend).await {
+            // 脚本可能执行很久，在 tokio 的线程之外执行，其他连接才能收到 BUSY 或者发送 SCRIPT KILL
+            Ok(_guard) if cmd.runs_script() => {
+                tokio::task::block_in_place(|| execute_locked(cmd, cluster, session, &backend))
+            }
+            Ok(_guard) => execute_locked(cmd, cluster, session, &backend),
+            Err(busy) => Outcome::Reply(busy),
+        },
+        Err(e) => Outcome::Reply(e.into()),
+    };
+    // 等待时不能持有执行锁
+    let (frames, replicate) = match outcome {
+        Outcome::Reply(frame) => (vec![frame], None),
+        Outcome::Replies(frames) => (frames, None),
+        Outcome::Block(waiter, timeout) => {
+            let reply = waiter.wait(timeout).await;
+            // 被唤醒时执行的写入已经传播，offset 不会小于这次写入的位置
+            if reply.is_some() {
+                session.woff = backend.replication_offset();
+            }
+            (vec![reply.unwrap_or_else(|| RespNullArray.into())], None)
+        }
+        Outcome::WaitAck(wait) => (vec![backend.wait_ack(wait).await], None),
+        Outcome::Replicate(feed) => (vec![], Some(feed)),
+    };
+    Ok(RedisResponse {
+        frames: frames
+            .into_iter()
+            .map(|frame| frame.into_protocol(session.protocol))
+            .collect(),
+        replicate,
+    })
+}
+
+/// 持有执行锁时执行命令，然后传播命令产生的写入
+fn execute_locked(
+    cmd: Command,
+    cluster: Option<ClusterRequest>,
+    session: &mut Session,
+    backend: &Backend,
+) -> Outcome {
+    let outcome = match cluster.and_then(|request| request.redirect(session, backend)) {
+        Some(reply) => Outcome::Reply(reply),
+        None => cmd.execute_or_block(session, backend),
+    };
+    let offset = backend.replication_offset();
+    // 命令已经执行但是没有写入 AOF，返回错误，之后的写命令也会被拒绝，直到写入恢复
+    let outcome = match backend.propagate_pending() {
+        Ok(()) => outcome,
+        Err(e) => Outcome::Reply(SimpleError::new(e.to_string()).into()),
+    };
+    // 命令产生了写入，WAIT 需要等待到这个位置 (WAIT 自己发送的 GETACK 不算)
+    if backend.replication_offset() != offset {
+        session.woff = backend.replication_offset();
+    }
+    // 命令可能写入了阻塞客户端等待的 key
+    backend.serve_blocked_clients();
+    // 被唤醒的客户端已经得到响应，写入失败时只能拒绝之后的写命令
+    let _ = backend.propagate_pending();
+    outcome
+}
+
+/// 获取执行锁。脚本执行超过 lua-time-limit 之后不再等待，返回 BUSY 错误
+async fn lock_or_busy(backend: &Backend) -> Result<MutexGuard<'_, ()>, RespFrame> {
+    loop {
+        if let Some(guard) = backend.try_lock() {
+            return Ok(guard);
+        }
+        // 没有脚本在执行时其他命令很快就会释放锁
+        let run = backend.lua.run();
+        if !run.is_running() {
+            return Ok(backend.lock());
+        }
+        if let Some(e) = run.busy_error() {
+            return Err(SimpleError::new(e).into());
+        }
+        // 不能阻塞 tokio 的线程，否则 SCRIPT KILL 可能没有机会执行
+        tokio::time::sleep(SCRIPT_BUSY_POLL).await;
+    }
+}
+
+/// 复制连接：先发送快照或者 backlog 中的数据，之后转发命令流，同时接收 replica 的 REPLCONF ACK
+async fn serve_replica(
+    framed: &mut Framed<TcpStream, RespFrameCodec>,
+    mut feed: ReplicaFeed,
+    backend: &Backend,
+) -> Result<()> {
+    let ret = async {
+        // RDB 快照后面没有 CRLF，不是完整的 RESP frame，直接写入。
+        // replica 读得太慢时写操作会一直阻塞，积压超过上限被断开时放弃写入
+        let preamble = std::mem::take(&mut feed.preamble);
+        tokio::select! {
+            ret = framed.get_mut().write_all(&preamble) => ret?,
+            _ = feed.closed() => return Ok(()),
+        }
+        loop {
+            tokio::select! {
+                data = feed.recv() => match data {
+                    Some(data) => tokio::select! {
+                        ret = framed.get_mut().write_all(&data) => ret?,
+                        _ = feed.closed() => return Ok(()),
+                    },
+                    // master 断开了这个 replica：输出缓冲区溢出，或者自己变成了 replica
+                    None => return Ok(()),
+                },
+                next = framed.next() => match next {
+                    Some(Ok(frame)) => {
+                        if let Some((offset, aof_offset)) = parse_replconf_ack(&frame) {
+                            backend.replica_ack(feed.id, offset, aof_offset);
+                        }
+                    }
+                    Some(Err(e)) => return Err(e),
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+    .await;
+    info!("Replica {} disconnected", feed.id);
+    backend.remove_replica(feed.id);
+    ret
+}
+
+/// 客户端关闭连接时返回。客户端在等待期间发送了新的请求时无法判断，一直等待
+async fn closed(stream: &TcpStream) {
+    let mut buf = [0u8; 1];
+    match stream.peek(&mut buf).await {
+        Ok(0) | Err(_) => {}
+        Ok(_) => std::future::pending().await,
+    }
+}
+
+impl Encoder<RespFrame> for RespFrameCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
+        let encoded = item.encode();
+        dst.extend_from_slice(&encoded);
+        Ok(())
+    }
+}
+
+impl Decoder for RespFrameCodec {
+    type Item = RespFrame;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<RespFrame>> {
+        match RespFrame::decode(src) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(RespDecodeError::NotComplete) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use crate::{
+    cmd::{load_snapshot, Command, Session},
+    Backend, BulkString, LinkState, RespArray, RespDecode, RespDecodeError, RespEncode, RespFrame,
+};
+use anyhow::{anyhow, bail, Result};
+use bytes::{Buf, BytesMut};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    task::JoinHandle,
+    time::{interval, sleep, timeout},
+};
+use tracing::{info, warn};
+
+// 连接断开之后重连的间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+// replica 发送 REPLCONF ACK 的间隔
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+// master 向 replica 发送 PING 的间隔，对应 repl-ping-replica-period
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+// 超过这个时间没有收到 master 的数据时断开重连，对应 repl-timeout
+const REPL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 和 master 之间的连接，握手阶段按行读取响应，之后读取 RDB 快照和命令流
+struct MasterLink {
+    stream: TcpStream,
+    buf: BytesMut,
+}
+
+/// 启动主从复制的后台任务：作为 replica 时连接 master 并同步，断开之后自动重连；
+/// 作为 master 时定期向 replica 发送 PING，replica 可以据此判断连接是否正常
+pub fn spawn_replication(backend: Backend) -> JoinHandle<()> {
+    let pinger = backend.clone();
+    tokio::spawn(async move {
+        let mut ticker = interval(PING_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if !pinger.is_replica() && pinger.connected_replicas() > 0 {
+                let _guard = pinger.lock();
+                pinger.feed_replication(&encode_command(&["PING"]));
+            }
+        }
+    });
+    tokio::spawn(async move {
+        let mut master = backend.watch_master();
+        loop {
+            let target = master.borrow_and_update().clone();
+            let Some((host, port)) = target else {
+                if master.changed().await.is_err() {
+                    return;
+                }
+                continue;
+            };
+            // REPLICAOF 修改了 master 时放弃当前的连接
+            tokio::select! {
+                ret = sync_with_master(&backend, &host, port) => {
+                    if let Err(e) = ret {
+                        warn!("Connection with master {}:{} lost: {}", host, port, e);
+                    }
+                    backend.set_link_state(LinkState::Connect);
+                    tokio::select! {
+                        _ = sleep(RECONNECT_INTERVAL) => {}
+                        ret = master.changed() => if ret.is_err() { return },
+                    }
+                }
+                ret = master.changed() => if ret.is_err() { return },
+            }
+        }
+    })
+}
+
+/// 握手、同步数据，之后一直接收并执行 master 的命令流，直到连接断开
+async fn sync_with_master(backend: &Backend, host: &str, port: u16) -> Result<()> {
+    backend.set_link_state(LinkState::Connecting);
+    info!("Connecting to MASTER {}:{}", host, port);
+    let stream = TcpStream::connect((host, port)).await?;
+    let mut link = MasterLink {
+        stream,
+        buf: BytesMut::new(),
+    };
+    link.request(&["PING"]).await?;
+    let listening_port = backend.listening_port().to_string();
+    link.request(&["REPLCONF", "listening-port", &listening_port])
+        .await?;
+    link.request(&["REPLCONF", "capa", "eof", "capa", "psync2"])
+        .await?;
+
+    let (replid, offset) = backend.psync_position();
+    link.send(&["PSYNC", &replid, &offset.to_string()]).await?;
+    let reply = match link.read_frame().await? {
+        RespFrame::SimpleString(s) => s.0,
+        RespFrame::Error(e) => bail!("PSYNC failed: {}", e.0),
+        frame => bail!("unexpected reply to PSYNC: {:?}", frame),
+    };
+    let mut parts = reply.split_whitespace();
+    match parts.next() {
+        Some("FULLRESYNC") => {
+            let replid = parts.next().ok_or_else(|| anyhow!("invalid FULLRESYNC"))?;
+            let offset = parts
+                .next()
+                .and_then(|offset| offset.parse().ok())
+                .ok_or_else(|| anyhow!("invalid FULLRESYNC"))?;
+            info!("Full resync from master: {}:{}", replid, offset);
+            backend.set_link_state(LinkState::Sync);
+            let snapshot = link.read_snapshot().await?;
+            let _guard = backend.lock_async().await;
+            load_snapshot(backend, &snapshot).map_err(|e| anyhow!("{}", e))?;
+            backend.full_synced(replid.to_string(), offset);
+            backend.mark_aof_written();
+            // 数据被整个替换，AOF 需要重写
+            if backend.aof_config().enabled {
+                if let Err(e) = backend.rewrite_aof() {
+                    warn!("failed to rewrite AOF after full resync: {}", e);
+                }
+            }
+        }
+        Some("CONTINUE") => {
+            info!("Successful partial resynchronization with master");
+            backend.partial_synced(parts.next().map(|id| id.to_string()));
+        }
+        _ => bail!("unexpected reply to PSYNC: {}", reply),
+    }
+    info!("MASTER <-> REPLICA sync: Finished with success");
+    link.stream_commands(backend).await
+}
+
+impl MasterLink {
+    async fn send(&mut self, args: &[&str]) -> Result<()> {
+        self.stream.write_all(&encode_command(args)).await?;
+        Ok(())
+    }
+
+    /// 发送握手的命令，master 返回错误时放弃这次连接
+    async fn request(&mut self, args: &[&str]) -> Result<()> {
+        self.send(args).await?;
+        match self.read_frame().await? {
+            RespFrame::Error(e) => bail!("error reply to {}: {}", args[0], e.0),
+            _ => Ok(()),
+        }
+    }
+
+    async fn read_more(&mut self) -> Result<()> {
+        let n = timeout(REPL_TIMEOUT, self.stream.read_buf(&mut self.buf))
+            .await
+            .map_err(|_| anyhow!("timeout, no data from master"))??;
+        if n == 0 {
+            bail!("connection closed by master");
+        }
+        Ok(())
+    }
+
+    async fn read_frame(&mut self) -> Result<RespFrame> {
+        loop {
+            match RespFrame::decode(&mut self.buf) {
+                Ok(frame) => return Ok(frame),
+                Err(RespDecodeError::NotComplete) => self.read_more().await?,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// 读取 `$<len>\r\n` 之后的 RDB 快照，快照后面没有 CRLF。
+    /// master 准备快照期间可能会发送换行保持连接
+    async fn read_snapshot(&mut self) -> Result<Vec<u8>> {
+        let len = loop {
+            while self.buf.first() == Some(&b'\n') {
+                self.buf.advance(1);
+            }
+            if let Some(pos) = self.buf.windows(2).position(|w| w == b"\r\n") {
+                let line = self.buf.split_to(pos + 2);
+                let len = std::str::from_utf8(&line[..pos])?
+                    .strip_prefix('$')
+                    .and_then(|len| len.parse::<usize>().ok())
+                    .ok_or_else(|| anyhow!("invalid snapshot header"))?;
+                break len;
+            }
+            self.read_more().await?;
+        };
+        while self.buf.len() < len {
+            self.read_more().await?;
+        }
+        Ok(self.buf.split_to(len).to_vec())
+    }
+
+    /// 执行 master 发送过来的命令，原样追加到自己的 backlog 中，offset 和 master 保持一致
+    async fn stream_commands(&mut self, backend: &Backend) -> Result<()> {
+        let mut session = Session::default();
+        let mut ack = interval(ACK_INTERVAL);
+        loop {
+            loop {
+                let len = match RespFrame::expect_length(&self.buf) {
+                    Ok(len) if len <= self.buf.len() => len,
+                    Ok(_) | Err(RespDecodeError::NotComplete) => break,
+                    Err(e) => return Err(e.into()),
+                };
+                let raw = self.buf.split_to(len);
+                let frame = RespFrame::decode(&mut raw.clone())?;
+                if let Some(reply) = apply_command(backend, &mut session, frame, &raw) {
+                    self.stream.write_all(&reply).await?;
+                }
+            }
+            tokio::select! {
+                ret = self.read_more() => {
+                    ret?;
+                    backend.touch_master_io();
+                }
+                _ = ack.tick() => {
+                    self.stream.write_all(&ack_command(backend)).await?;
+                }
+            }
+        }
+    }
+}
+
+/// 在执行锁中执行 master 的一条命令，REPLCONF GETACK 返回需要发送给 master 的 ACK
+fn apply_command(
+    backend: &Backend,
+    session: &mut Session,
+    frame: RespFrame,
+    raw: &[u8],
+) -> Option<Vec<u8>> {
+    let name = match &frame {
+        RespFrame::Array(array) => match array.0.first() {
+            Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(&name.0).to_lowercase(),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    };
+    let _guard = backend.lock();
+    let reply = match name.as_str() {
+        "replconf" => Some(ack_command(backend)),
+        "ping" => None,
+        _ => {
+            match Command::from_session(frame, session) {
+                Ok(cmd) => {
+                    // master 的命令不会被再次改写
+                    session.argv = None;
+                    cmd.execute_or_block(session, backend);
+                    // replica 不能拒绝 master 的命令，写入失败的数据会在恢复之后继续写入
+                    let _ = backend.feed_aof(raw);
+                }
+                Err(e) => warn!("failed to parse command from master: {}", e),
+            }
+            None
+        }
+    };
+    backend.feed_replication(raw);
+    if reply.is_none() && name != "ping" {
+        backend.mark_aof_written();
+    }
+    reply
+}
+
+/// REPLCONF ACK <offset> FACK <aofoffset>，告知 master 已经处理以及 AOF 已经 fsync 到的位置
+fn ack_command(backend: &Backend) -> Vec<u8> {
+    let offset = backend.replication_offset().to_string();
+    let aof_offset = backend.aof_fsynced_offset().to_string();
+    encode_command(&["REPLCONF", "ACK", &offset, "FACK", &aof_offset])
+}
+
+pub(crate) fn encode_command(args: &[&str]) -> Vec<u8> {
+    RespArray::new(
+        args.iter()
+            .map(|arg| BulkString::new(arg.as_bytes()).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{network, AofConfig, AppendFsync, SaveConfig, Value};
+    use tokio::net::TcpListener;
+
+    async fn start_server(backend: Backend) -> Result<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        backend.set_listening_port(port);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(network::stream_handler(stream, backend.clone()));
+            }
+        });
+        Ok(port)
+    }
+
+    async fn wait_for(f: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if f() {
+                return;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timeout");
+    }
+
+    #[tokio::test]
+    async fn test_replication() -> Result<()> {
+        let master = Backend::new();
+        master.set(b"a".to_vec(), Value::String(b"1".to_vec()));
+        let port = start_server(master.clone()).await?;
+        let replica = Backend::new();
+        start_server(replica.clone()).await?;
+        spawn_replication(replica.clone());
+
+        // 全量同步
+        replica.set_master(Some(("127.0.0.1".to_string(), port)));
+        wait_for(|| replica.get(b"a").is_some()).await;
+        assert_eq!(master.connected_replicas(), 1);
+
+        // 命令流
+        let mut client = TcpStream::connect(("127.0.0.1", port)).await?;
+        client
+            .write_all(&encode_command(&["SET", "b", "2"]))
+            .await?;
+        client.write_all(&encode_command(&["INCR", "b"])).await?;
+        wait_for(|| replica.get(b"b") == Some(Value::String(b"3".to_vec()))).await;
+        let offset = master.replication_status().offset;
+        assert_eq!(replica.replication_status().offset, offset);
+        wait_for(|| master.replication_status().replicas[0].ack_offset == offset).await;
+
+        // 断开之后部分同步
+        replica.set_master(None);
+        client
+            .write_all(&encode_command(&["SET", "c", "3"]))
+            .await?;
+        wait_for(|| master.connected_replicas() == 0 && master.get(b"c").is_some()).await;
+        let replid = master.replication_status().replid;
+        replica.full_synced(replid.clone(), offset);
+        replica.set(b"gone".to_vec(), Value::String(b"x".to_vec()));
+        replica.set_master(Some(("127.0.0.1".to_string(), port)));
+        wait_for(|| replica.get(b"c").is_some()).await;
+        // 没有全量同步，本地的数据还在
+        assert!(replica.get(b"gone").is_some());
+        assert_eq!(replica.replication_status().replid, replid);
+        Ok(())
+    }
+
+    async fn request(client: &mut TcpStream, args: &[&str]) -> Result<RespFrame> {
+        client.write_all(&encode_command(args)).await?;
+        let mut buf = BytesMut::new();
+        loop {
+            match RespFrame::decode(&mut buf) {
+                Ok(frame) => return Ok(frame),
+                Err(RespDecodeError::NotComplete) => {
+                    if client.read_buf(&mut buf).await? == 0 {
+                        bail!("connection closed");
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait() -> Result<()> {
+        let master = Backend::new();
+        let port = start_server(master.clone()).await?;
+        let dir = std::env::temp_dir().join(format!("simple-redis-wait-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let replica = Backend::new();
+        replica.configure_save(SaveConfig {
+            dir: dir.clone(),
+            rules: vec![],
+            ..SaveConfig::default()
+        });
+        replica.configure_aof(AofConfig {
+            enabled: true,
+            fsync: AppendFsync::Always,
+            ..AofConfig::default()
+        });
+        replica.open_aof()?;
+        start_server(replica.clone()).await?;
+        spawn_replication(replica.clone());
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).await?;
+        // 没有 replica 时超时返回 0
+        assert_eq!(request(&mut client, &["SET", "a", "1"]).await?, "OK".into());
+        assert_eq!(request(&mut client, &["WAIT", "1", "50"]).await?, 0.into());
+
+        replica.set_master(Some(("127.0.0.1".to_string(), port)));
+        wait_for(|| master.connected_replicas() == 1).await;
+        assert_eq!(request(&mut client, &["INCR", "n"]).await?, 1.into());
+        assert_eq!(request(&mut client, &["WAIT", "1", "0"]).await?, 1.into());
+        assert_eq!(
+            request(&mut client, &["WAITAOF", "0", "1", "0"]).await?,
+            RespArray::new(vec![0.into(), 1.into()]).into()
+        );
+        assert_eq!(replica.get(b"n"), Some(Value::String(b"1".to_vec())));
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
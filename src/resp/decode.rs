@@ -2,7 +2,7 @@ use crate::resp::{RespDecode, RespDecodeError, RespFrame, SimpleString};
 use bytes::{Buf, BytesMut};
 
 use super::{
-    BulkString, RespArray, RespMap, RespNull, RespNullArray, RespNullBulkString, RespSet,
+    BulkString, RespArray, RespMap, RespNull, RespNullArray, RespNullBulkString, RespPush, RespSet,
     SimpleError,
 };
 
@@ -17,8 +17,6 @@ const CRLF_LEN: usize = CRLF.len();
 impl RespDecode for RespFrame {
     const PREFIX: &'static str = "";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespDecodeError> {
-        let a = String::from_utf8_lossy(buf);
-        println!("测试结果={:?}", a);
         let mut iter = buf.iter().peekable();
         match iter.peek() {
             Some(b'+') => {
@@ -67,6 +65,10 @@ impl RespDecode for RespFrame {
                 let frame = bool::decode(buf)?;
                 Ok(frame.into())
             }
+            Some(b',') => {
+                let frame = f64::decode(buf)?;
+                Ok(frame.into())
+            }
             Some(b'%') => {
                 let frame = RespMap::decode(buf)?;
                 Ok(frame.into())
@@ -75,6 +77,12 @@ impl RespDecode for RespFrame {
                 let frame = RespSet::decode(buf)?;
                 Ok(frame.into())
             }
+            Some(b'>') => {
+                let frame = RespPush::decode(buf)?;
+                Ok(frame.into())
+            }
+            // 还没有收到任何数据
+            None => Err(RespDecodeError::NotComplete),
             _ => Err(RespDecodeError::InvalidFrameType(format!(
                 "unknown frame type: {:?}",
                 buf
@@ -86,9 +94,17 @@ impl RespDecode for RespFrame {
     fn expect_length(buf: &[u8]) -> Result<usize, RespDecodeError> {
         let mut iter = buf.iter().peekable();
         match iter.peek() {
+            // 空数组和空字符串的长度是固定的，需要优先判断，否则 -1 无法被解析成 usize
+            Some(b'*') if buf.starts_with(RespNullArray::PREFIX.as_bytes()) => {
+                RespNullArray::expect_length(buf)
+            }
+            Some(b'$') if buf.starts_with(RespNullBulkString::PREFIX.as_bytes()) => {
+                RespNullBulkString::expect_length(buf)
+            }
             Some(b'*') => RespArray::expect_length(buf),
             // ~
             Some(b'~') => RespSet::expect_length(buf),
+            Some(b'>') => RespPush::expect_length(buf),
             Some(b'%') => RespMap::expect_length(buf),
             Some(b'$') => BulkString::expect_length(buf),
             Some(b':') => i64::expect_length(buf),
@@ -187,7 +203,7 @@ impl RespDecode for RespNullArray {
     }
 
     fn expect_length(_buf: &[u8]) -> Result<usize, RespDecodeError> {
-        Ok(4)
+        Ok(5)
     }
 }
 
@@ -224,7 +240,6 @@ impl RespDecode for BulkString {
     const PREFIX: &'static str = "$";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespDecodeError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        println!("预测得到的结果，end={}, len={}", end, len);
         // end =2,
         // 获取从标识符$之后的\r\n开始真是的内容，但是包含了\r\n的结尾
         let remained = &buf[end + CRLF_LEN..];
@@ -335,11 +350,21 @@ impl RespDecode for RespMap {
 
         let mut frames = RespMap::new();
         for _ in 0..len {
-            // 这里我们认为所有的key都是 SimpleString
-            let key = SimpleString::decode(buf)?;
+            // key 可以是 SimpleString 或者 BulkString (Redis 返回的是 BulkString)
+            let key = match RespFrame::decode(buf)? {
+                RespFrame::SimpleString(s) => s.0.into_bytes(),
+                RespFrame::BulkString(s) => s.0,
+                frame => {
+                    return Err(RespDecodeError::InvalidFrameType(format!(
+                        "map key must be a string: {:?}",
+                        frame
+                    )))
+                }
+            };
             // 所有的value就根据 prefix进行动态获取
             let value = RespFrame::decode(buf)?;
-            frames.insert(key.0, value);
+            // 保留收到的顺序，不检查重复的 key，避免大的 map 逐个比较
+            frames.push(key, value);
         }
         Ok(frames)
     }
@@ -377,44 +402,51 @@ impl RespDecode for RespSet {
     }
 }
 
+// Push
+// - ><number-of-elements>\r\n<element-1>...<element-n>
+impl RespDecode for RespPush {
+    const PREFIX: &'static str = ">";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespDecodeError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+        if buf.len() < total_len {
+            return Err(RespDecodeError::NotComplete);
+        }
+        buf.advance(end + CRLF_LEN);
+
+        let mut frames = Vec::new();
+        for _ in 0..len {
+            frames.push(RespFrame::decode(buf)?);
+        }
+        Ok(RespPush::new(frames))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespDecodeError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        calc_total_length(buf, end, len, Self::PREFIX)
+    }
+}
+
 fn extract_fixed_data(
     buf: &mut BytesMut,
     expected: &str,
     data_type: &str,
 ) -> Result<usize, RespDecodeError> {
-    if buf.len() < 2 {
-        return Err(RespDecodeError::NotComplete);
-    }
-    // 检查是否匹配
-    if !buf.starts_with(expected.as_bytes()) {
+    // 先比较已经收到的部分，不匹配时说明不是这个类型
+    let n = buf.len().min(expected.len());
+    if buf[..n] != expected.as_bytes()[..n] {
         return Err(RespDecodeError::InvalidFrameType(format!(
             "except: {}, got:{:?}",
             data_type, buf
         )));
     }
-    println!(
-        "===前缀:{}, buf的内容={:?}",
-        expected,
-        String::from_utf8_lossy(buf)
-    );
-    let mut end = 0;
-    // 检查类型如果是 Bool类型，则根据数据格式，从buf中取出内容
-    if data_type.eq("Bool") {
-        for i in 0..buf.len() - 1 {
-            if buf[i] == b'\r' && buf[i + 1] == b'\n' {
-                // 获取到\r的下标
-                end = i;
-                break;
-            }
-        }
-        // 移除buf中的数据
-        let _ = buf.split_to(end + 2);
-    }
-    // 判定end不为0，否则抛出异常
-    if end == 0 {
+    // 匹配但是长度不够，说明还没接收完成
+    if buf.len() < expected.len() {
         return Err(RespDecodeError::NotComplete);
     }
-    Ok(end)
+    // 移除buf中的数据
+    buf.advance(expected.len());
+    Ok(expected.len())
 }
 
 // 增加额外参数，用于传入前缀（如"+" 或 "-"）
@@ -472,14 +504,12 @@ fn calc_total_length(
     // 假设一切都是美好的
     let mut total = end + CRLF_LEN; //此处是， array, map, set 类型去掉前缀之后的end + \r\n 的长度。
     let mut data = &buf[total..]; // 获取去掉 前缀开始，到整个buf的全部内容
-    let tmp = String::from_utf8_lossy(data);
-    println!("测试结果={:?}", tmp);
     match prefix {
         // array and set of prefix
         // *<number-of-elements>\r\n<element-1>...<element-n>
         // ~<number-of-elements>\r\n<element-1>...<element-n>
         // 对于 array 和 set 而言，
-        "*" | "~" => {
+        "*" | "~" | ">" => {
             // this CRLF in the buffer, for array and set , we need to find 1 CRLF for each element
             // find_crlf(data, len)
             //     .map(|end| len + CRLF_LEN + end)
@@ -487,6 +517,10 @@ fn calc_total_length(
             for _ in 0..len {
                 // 针对不同类型，获取相应的 item_len
                 let item_len = RespFrame::expect_length(data)?;
+                // 元素还没接收完整时，不能直接切片，否则会越界
+                if data.len() < item_len {
+                    return Err(RespDecodeError::NotComplete);
+                }
                 // 根据length，截取对应长度的数据到data
                 data = &data[item_len..]; //data[len..] 意味着创建从 len 索引位置开始（包含 len 索引位置的元素），直到 data 末尾的切片。
                 total += item_len;
@@ -502,13 +536,19 @@ fn calc_total_length(
             // .ok_or(RespDecodeError::NotComplete)
 
             for _ in 0..len {
-                // fist map the key is SimpleString type.
-                let key_len = SimpleString::expect_length(data)?;
+                // fist map the key is SimpleString or BulkString type.
+                let key_len = RespFrame::expect_length(data)?;
+                if data.len() < key_len {
+                    return Err(RespDecodeError::NotComplete);
+                }
                 data = &data[key_len..];
                 total += key_len;
 
                 // second map the value is any RespFrame.
                 let value_len = RespFrame::expect_length(data)?;
+                if data.len() < value_len {
+                    return Err(RespDecodeError::NotComplete);
+                }
                 data = &data[value_len..];
                 total += value_len;
             }
@@ -685,7 +725,7 @@ mod tests {
     #[test]
     fn test_map_decode() -> Result<()> {
         let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"%2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n");
+        buf.extend_from_slice(b"%2\r\n+hello\r\n$5\r\nworld\r\n$3\r\nfoo\r\n$3\r\nbar\r\n");
 
         let frame = RespMap::decode(&mut buf)?;
         let mut map = RespMap::new();
@@ -699,6 +739,68 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_null_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"$-1\r\n*-1\r\n_\r\n");
+
+        assert_eq!(RespFrame::decode(&mut buf)?, RespNullBulkString.into());
+        assert_eq!(RespFrame::decode(&mut buf)?, RespNullArray.into());
+        assert_eq!(RespFrame::decode(&mut buf)?, RespNull.into());
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b"*-1\r");
+        let ret = RespFrame::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespDecodeError::NotComplete);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_with_null_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$1\r\na\r\n$-1\r\n,1.5\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespArray::new([b"a".into(), RespNullBulkString.into(), 1.5.into()]).into()
+        );
+
+        // 嵌套元素只收到一部分时，需要等待更多数据
+        buf.extend_from_slice(b"*2\r\n$5\r\nhel");
+        let ret = RespFrame::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespDecodeError::NotComplete);
+
+        buf.extend_from_slice(b"lo\r\n*0\r\n");
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespArray::new([b"hello".into(), RespArray::new([]).into()]).into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b">3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespPush::new([b"message".into(), b"ch".into(), b"hi".into()]).into()
+        );
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b">2\r\n$7\r\nmessage\r\n");
+        let ret = RespFrame::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespDecodeError::NotComplete);
+
+        Ok(())
+    }
+
     #[test]
     fn dymmy_test() {
         let s = "+1.23456e-9";
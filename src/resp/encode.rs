@@ -1,6 +1,6 @@
 use super::{
     BulkString, RespArray, RespEncode, RespMap, RespNull, RespNullArray, RespNullBulkString,
-    RespSet, SimpleError, SimpleString,
+    RespPush, RespSet, SimpleError, SimpleString,
 };
 
 /// 为每个枚举实现 encode
@@ -95,18 +95,34 @@ impl RespEncode for f64 {
         let mut buf = Vec::with_capacity(BUF_CAP);
         // 大于1亿 就启用科学计数法, 数据过小时，也需要考虑0.000000001234这样的小数位数，这种是使用`1.23456 × 10^(-9)`这种方式计数的
         // 虽然abs去掉的负号，但是小数位数的科学技术仍旧需要保留
-        let ret = if self.abs() >= 1e+8 || self.abs() <= 1e-7 {
+        let ret = if !self.is_finite() {
+            // inf, -inf, nan 按照 RESP3 的约定直接输出
+            format!(",{}\r\n", format_double(self))
+        } else if self.abs() >= 1e+8 || self.abs() <= 1e-7 {
             format!(",{:+e}\r\n", self)
         } else {
             // 否则，就直接存
             let sign = if self < 0.0 { "" } else { "+" };
-            format!(",{}{}\r\n", sign, self)
+            format!(",{}{}\r\n", sign, format_double(self))
         };
         buf.extend_from_slice(&ret.into_bytes());
         buf
     }
 }
 
+/// 将 f64 转换成 Redis 返回给用户的可读形式，例如 INCRBYFLOAT 的结果:
+/// 不使用科学计数法，整数不带小数点，无穷大为 inf/-inf
+pub fn format_double(v: f64) -> String {
+    if v.is_nan() {
+        "nan".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        // Display 输出的是能够还原成同一个 f64 的最短表示
+        format!("{}", v)
+    }
+}
+
 // map数据。
 // %<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>
 impl RespEncode for RespMap {
@@ -114,7 +130,8 @@ impl RespEncode for RespMap {
         let mut buf = Vec::with_capacity(BUF_CAP);
         buf.extend_from_slice(&format!("%{}\r\n", self.len()).into_bytes());
         for (key, value) in self.0 {
-            buf.extend_from_slice(&SimpleString::new(key).encode());
+            // key 使用 BulkString，hash 的 field 等可能包含 \r\n
+            buf.extend_from_slice(&BulkString::new(key).encode());
             buf.extend_from_slice(&value.encode());
         }
         buf
@@ -132,6 +149,17 @@ impl RespEncode for RespSet {
     }
 }
 
+impl RespEncode for RespPush {
+    fn encode(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(BUF_CAP);
+        buf.extend_from_slice(&format!(">{}\r\n", self.len()).into_bytes());
+        for value in self.0 {
+            buf.extend_from_slice(&value.encode());
+        }
+        buf
+    }
+}
+
 /// 单元测试
 #[cfg(test)]
 mod tests {
@@ -242,6 +270,20 @@ mod tests {
         let frame: RespFrame = (-1.23456e-8).into();
         // assert_eq!(String::from_utf8_lossy(&frame.encode()), ",-1.23456e-7\r\n");
         assert_eq!(frame.encode(), b",-1.23456e-8\r\n");
+
+        let frame: RespFrame = f64::INFINITY.into();
+        assert_eq!(frame.encode(), b",inf\r\n");
+        let frame: RespFrame = f64::NEG_INFINITY.into();
+        assert_eq!(frame.encode(), b",-inf\r\n");
+    }
+
+    #[test]
+    fn test_format_double() {
+        assert_eq!(format_double(10.5), "10.5");
+        assert_eq!(format_double(5000.0), "5000");
+        assert_eq!(format_double(-0.1), "-0.1");
+        assert_eq!(format_double(1e20), "100000000000000000000");
+        assert_eq!(format_double(f64::INFINITY), "inf");
     }
 
     #[test]
@@ -256,8 +298,8 @@ mod tests {
         let frame: RespFrame = map.into();
         assert_eq!(
             frame.encode(),
-            // 由于Btreemap内部按照ascii进行了排序，因此，foo的key在hellokey的前面
-            b"%2\r\n+foo\r\n,-123456.789\r\n+hello\r\n$5\r\nworld\r\n"
+            // 按照插入的顺序编码
+            b"%2\r\n$5\r\nhello\r\n$5\r\nworld\r\n$3\r\nfoo\r\n,-123456.789\r\n"
         );
     }
 
@@ -281,4 +323,14 @@ mod tests {
         //     "~2\r\n:1234\r\n#t\r\n$5\r\nworld\r\n"
         // );
     }
+
+    #[test]
+    fn test_push_encode() {
+        let frame: RespFrame = RespPush::new([
+            BulkString::new("message").into(),
+            BulkString::new("ch").into(),
+        ])
+        .into();
+        assert_eq!(frame.encode(), b">2\r\n$7\r\nmessage\r\n$2\r\nch\r\n");
+    }
 }
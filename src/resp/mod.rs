@@ -1,12 +1,11 @@
 mod decode;
 mod encode;
 
+pub use encode::format_double;
+
 use bytes::BytesMut;
 use enum_dispatch::enum_dispatch;
-use std::{
-    collections::BTreeMap,
-    ops::{Deref, DerefMut},
-};
+use std::ops::{Deref, DerefMut};
 use thiserror::Error;
 /*
 Simple strings
@@ -71,7 +70,7 @@ pub enum RespDecodeError {
 }
 
 #[enum_dispatch(RespEncode)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum RespFrame {
     SimpleString(SimpleString),
     Error(SimpleError),
@@ -85,32 +84,38 @@ pub enum RespFrame {
     Double(f64),
     Map(RespMap),
     Set(RespSet),
+    Push(RespPush),
 }
-#[derive(Debug, PartialEq, Eq)]
-pub struct SimpleString(String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleString(pub(crate) String);
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct SimpleError(String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleError(pub(crate) String);
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct BulkString(Vec<u8>);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkString(pub(crate) Vec<u8>);
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RespNullBulkString;
 
-#[derive(Debug, PartialEq)]
-pub struct RespArray(Vec<RespFrame>);
+#[derive(Debug, Clone, PartialEq)]
+pub struct RespArray(pub(crate) Vec<RespFrame>);
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RespNull;
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RespNullArray;
 
-#[derive(Debug, PartialEq)]
-pub struct RespMap(BTreeMap<String, RespFrame>);
+/// 按照插入的顺序保存 key，和 Redis 一样回复的顺序就是构造的顺序 (例如 HGETALL 按照 hash 的存储顺序)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RespMap(pub(crate) Vec<(Vec<u8>, RespFrame)>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RespSet(pub(crate) Vec<RespFrame>);
 
-#[derive(Debug, PartialEq)]
-pub struct RespSet(Vec<RespFrame>);
+/// RESP3 的 push 类型，服务端主动推送给客户端的数据 (例如 pub/sub 的消息)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RespPush(pub(crate) Vec<RespFrame>);
 
 impl Deref for SimpleString {
     type Target = String;
@@ -128,6 +133,14 @@ impl Deref for SimpleError {
     }
 }
 
+impl Deref for BulkString {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 impl Deref for RespArray {
     type Target = Vec<RespFrame>;
 
@@ -137,7 +150,7 @@ impl Deref for RespArray {
 }
 
 impl Deref for RespMap {
-    type Target = BTreeMap<String, RespFrame>;
+    type Target = Vec<(Vec<u8>, RespFrame)>;
 
     fn deref(&self) -> &Self::Target {
         // 元组数据取值方式
@@ -159,6 +172,13 @@ impl Deref for RespSet {
     }
 }
 
+impl Deref for RespPush {
+    type Target = Vec<RespFrame>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 impl SimpleString {
     pub fn new(s: impl Into<String>) -> Self {
         SimpleString(s.into())
@@ -185,7 +205,35 @@ impl RespArray {
 
 impl RespMap {
     pub fn new() -> Self {
-        RespMap(BTreeMap::new())
+        RespMap(Vec::new())
+    }
+
+    /// key 是二进制安全的，hash 的 field 等可以不是合法的 UTF-8。
+    /// key 已经存在时替换原来的 value，位置不变
+    pub fn insert(&mut self, key: impl Into<Vec<u8>>, value: RespFrame) -> Option<RespFrame> {
+        let key = key.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(std::mem::replace(v, value)),
+            None => {
+                self.0.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// 追加一个 key，调用方保证 key 不重复 (例如 hash 的 field)，不需要逐个比较
+    pub fn push(&mut self, key: impl Into<Vec<u8>>, value: RespFrame) {
+        self.0.push((key.into(), value));
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&RespFrame> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
+impl Default for RespMap {
+    fn default() -> Self {
+        RespMap::new()
     }
 }
 
@@ -195,6 +243,12 @@ impl RespSet {
     }
 }
 
+impl RespPush {
+    pub fn new(s: impl Into<Vec<RespFrame>>) -> Self {
+        RespPush(s.into())
+    }
+}
+
 // impl From<&[u8]> for BulkString {
 //     fn from(value: &[u8]) -> Self {
 //         BulkString(value.into())
@@ -230,3 +284,129 @@ impl<const N: usize> From<&[u8; N]> for RespFrame {
         BulkString(s.to_vec()).into()
     }
 }
+
+/// 客户端使用的协议版本，默认是 RESP2，通过 HELLO 切换
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl RespFrame {
+    /// 命令统一返回 RESP3 的 frame，发送前根据客户端的协议版本进行转换
+    ///
+    /// RESP2 没有 map/set/push/double/boolean/null，分别转换成数组、bulk string 和整数；
+    /// RESP3 中所有的空值都使用 null
+    pub fn into_protocol(self, protocol: Protocol) -> RespFrame {
+        match (self, protocol) {
+            (RespFrame::Array(array), _) => RespArray::new(
+                array
+                    .0
+                    .into_iter()
+                    .map(|frame| frame.into_protocol(protocol))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            (RespFrame::Map(map), Protocol::Resp2) => RespArray::new(
+                map.0
+                    .into_iter()
+                    .flat_map(|(key, value)| {
+                        [BulkString::new(key).into(), value.into_protocol(protocol)]
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            (RespFrame::Map(mut map), Protocol::Resp3) => {
+                for (_, value) in map.iter_mut() {
+                    *value = std::mem::replace(value, RespNull.into()).into_protocol(protocol);
+                }
+                map.into()
+            }
+            (RespFrame::Set(set), Protocol::Resp2) => RespArray::new(
+                set.0
+                    .into_iter()
+                    .map(|frame| frame.into_protocol(protocol))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            (RespFrame::Set(set), Protocol::Resp3) => RespSet::new(
+                set.0
+                    .into_iter()
+                    .map(|frame| frame.into_protocol(protocol))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            (RespFrame::Push(push), Protocol::Resp2) => RespArray::new(
+                push.0
+                    .into_iter()
+                    .map(|frame| frame.into_protocol(protocol))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            (RespFrame::Push(push), Protocol::Resp3) => RespPush::new(
+                push.0
+                    .into_iter()
+                    .map(|frame| frame.into_protocol(protocol))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            (RespFrame::Double(v), Protocol::Resp2) => BulkString::new(format_double(v)).into(),
+            (RespFrame::Boolean(v), Protocol::Resp2) => (v as i64).into(),
+            (RespFrame::Null(_), Protocol::Resp2) => RespNullBulkString.into(),
+            (RespFrame::NullBulkString(_) | RespFrame::NullArray(_), Protocol::Resp3) => {
+                RespNull.into()
+            }
+            (frame, _) => frame,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_protocol() {
+        let mut map = RespMap::new();
+        map.insert("a".to_string(), 1.5.into());
+        map.insert("b".to_string(), RespNullBulkString.into());
+        let frame: RespFrame = RespArray::new([map.into(), true.into()]).into();
+
+        assert_eq!(
+            frame.clone().into_protocol(Protocol::Resp2),
+            RespArray::new([
+                RespArray::new([
+                    BulkString::new("a").into(),
+                    BulkString::new("1.5").into(),
+                    BulkString::new("b").into(),
+                    RespNullBulkString.into(),
+                ])
+                .into(),
+                1.into(),
+            ])
+            .into()
+        );
+
+        let mut map = RespMap::new();
+        map.insert("a".to_string(), 1.5.into());
+        map.insert("b".to_string(), RespNull.into());
+        assert_eq!(
+            frame.into_protocol(Protocol::Resp3),
+            RespArray::new([map.into(), true.into()]).into()
+        );
+
+        let frame: RespFrame = RespSet::new([RespNull.into()]).into();
+        assert_eq!(
+            frame.into_protocol(Protocol::Resp2),
+            RespArray::new([RespNullBulkString.into()]).into()
+        );
+
+        let frame: RespFrame = RespPush::new([BulkString::new("message").into()]).into();
+        assert_eq!(
+            frame.clone().into_protocol(Protocol::Resp2),
+            RespArray::new([BulkString::new("message").into()]).into()
+        );
+        assert_eq!(frame.clone().into_protocol(Protocol::Resp3), frame);
+    }
+}